@@ -0,0 +1,217 @@
+// The audit log: a record of every statement the clients run, for compliance, kept apart from
+// the server's own log. AUDIT_LOG names the file it is appended to, or is `syslog` for the
+// local syslog daemon. AUDIT_USERS, a comma-separated list, limits it to those users' statements;
+// without it, everyone's are audited. Each statement is a JSON object on a line of its own:
+//
+//   {"time":"2024-03-01T12:00:00.123Z","connection_id":7,"client":"10.0.0.5:51234",
+//    "user":"app","database":"shop","command":"COM_QUERY",
+//    "statement":"UPDATE accounts SET balance = 10 WHERE id = 3","error_code":null,
+//    "connection_attributes":{"_client_name":"libmysql","program_name":"billing"}}
+//
+// `statement` is the SQL exactly as the client sent it, before any rewriting or translation.
+// An executed prepared statement has the values it was run with in `params`, as text. With
+// AUDIT_REDACT set, the literals in statements are written as `?` and `params` is left out, so
+// the log shows who ran what without the data. A statement that can't be tokenized then has a
+// null `statement`, as its literals can't be told apart.
+//
+// Statements are audited as they finish, so `error_code` is the MySQL error number the client
+// got, if any. Prepared statements are audited each time they are executed, rather than when
+// they are prepared. Switching databases with COM_INIT_DB is audited as `USE`.
+//
+// `connection_attributes` are those the client sent as it connected, to tell which program ran
+// a statement when many share a user; a client that sent none has none.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::logging::{push_string, Logger};
+use crate::translator::lexer::{self, Token};
+
+/// Where the audit log goes (AUDIT_LOG).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditSink {
+    File(String),
+    Syslog,
+}
+
+/// The audit log's settings (AUDIT_LOG, AUDIT_USERS, AUDIT_REDACT).
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub sink: AuditSink,
+    // The users audited, all of them when None.
+    pub users: Option<Vec<String>>,
+    pub redact: bool,
+}
+
+/// A statement that has finished, for the audit log.
+pub(crate) struct AuditRecord<'a> {
+    pub connection_id: u32,
+    pub client: SocketAddr,
+    pub user: &'a str,
+    pub database: Option<&'a str>,
+    pub command: &'static str,
+    pub statement: &'a str,
+    pub params: Option<&'a [Option<String>]>,
+    pub error_code: Option<u16>,
+    pub connect_attrs: &'a [(String, String)],
+}
+
+enum Sink {
+    File(File),
+    #[cfg(unix)]
+    Syslog(std::os::unix::net::UnixDatagram),
+}
+
+/// The audit log, shared by all connections.
+pub(crate) struct AuditLog {
+    sink: Mutex<Sink>,
+    users: Option<Vec<String>>,
+    redact: bool,
+    log: Logger,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditConfig, log: Logger) -> io::Result<AuditLog> {
+        let sink = match &config.sink {
+            AuditSink::File(path) => {
+                Sink::File(OpenOptions::new().create(true).append(true).open(path)?)
+            }
+            AuditSink::Syslog => syslog()?,
+        };
+        Ok(AuditLog {
+            sink: Mutex::new(sink),
+            users: config.users.clone(),
+            redact: config.redact,
+            log,
+        })
+    }
+
+    /// Whether the statements of `user` are audited.
+    pub fn covers(&self, user: &str) -> bool {
+        self.users
+            .as_ref()
+            .is_none_or(|users| users.iter().any(|audited| audited == user))
+    }
+
+    pub fn record(&self, record: &AuditRecord) {
+        if !self.covers(record.user) {
+            return;
+        }
+        let statement = match self.redact {
+            true => redact(record.statement),
+            false => Some(record.statement.to_string()),
+        };
+
+        let mut line = String::with_capacity(256 + record.statement.len());
+        line.push_str("{\"time\":\"");
+        line.push_str(&Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        line.push_str(&format!(
+            "\",\"connection_id\":{},\"client\":\"{}\",\"user\":",
+            record.connection_id, record.client
+        ));
+        push_string(&mut line, record.user);
+        line.push_str(",\"database\":");
+        match record.database {
+            Some(database) => push_string(&mut line, database),
+            None => line.push_str("null"),
+        }
+        line.push_str(",\"command\":");
+        push_string(&mut line, record.command);
+        line.push_str(",\"statement\":");
+        match &statement {
+            Some(statement) => push_string(&mut line, statement),
+            None => line.push_str("null"),
+        }
+        if let (Some(params), false) = (record.params, self.redact) {
+            line.push_str(",\"params\":[");
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                match param {
+                    Some(value) => push_string(&mut line, value),
+                    None => line.push_str("null"),
+                }
+            }
+            line.push(']');
+        }
+        line.push_str(",\"error_code\":");
+        match record.error_code {
+            Some(code) => line.push_str(&code.to_string()),
+            None => line.push_str("null"),
+        }
+        if !record.connect_attrs.is_empty() {
+            line.push_str(",\"connection_attributes\":{");
+            for (i, (name, value)) in record.connect_attrs.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                push_string(&mut line, name);
+                line.push(':');
+                push_string(&mut line, value);
+            }
+            line.push('}');
+        }
+        line.push('}');
+
+        if let Err(e) = self.write(line) {
+            self.log
+                .error(format_args!("Failed to write the audit log: {}", e));
+        }
+    }
+
+    fn write(&self, mut line: String) -> io::Result<()> {
+        match &mut *self.sink.lock().unwrap() {
+            Sink::File(file) => {
+                line.push('\n');
+                file.write_all(line.as_bytes())
+            }
+            // RFC 3164, as syslog(3) sends it: facility user, severity info.
+            #[cfg(unix)]
+            Sink::Syslog(socket) => {
+                let message = format!(
+                    "<14>{} postmyrustache[{}]: {}",
+                    chrono::Local::now().format("%b %e %H:%M:%S"),
+                    std::process::id(),
+                    line
+                );
+                socket.send(message.as_bytes()).map(|_| ())
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn syslog() -> io::Result<Sink> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    // Linux has the socket in /dev, macOS in /var/run.
+    socket
+        .connect("/dev/log")
+        .or_else(|_| socket.connect("/var/run/syslog"))?;
+    Ok(Sink::Syslog(socket))
+}
+
+#[cfg(not(unix))]
+fn syslog() -> io::Result<Sink> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "there is no syslog on this platform; set AUDIT_LOG to a file",
+    ))
+}
+
+// `sql` with its literals written as `?`, None if it can't be tokenized.
+fn redact(sql: &str) -> Option<String> {
+    let tokens = lexer::tokenize(sql).ok()?;
+    let mut out = String::with_capacity(sql.len());
+    for token in tokens {
+        match token {
+            Token::String(_) | Token::Number(_) | Token::Hex(_) | Token::Bit(_) => out.push('?'),
+            token => out.push_str(&token.to_string()),
+        }
+    }
+    Some(out)
+}
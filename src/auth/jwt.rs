@@ -0,0 +1,437 @@
+// AUTH_PROVIDER = jwt: the client sends a signed token as its password, a JWT an identity
+// provider or a cloud's IAM gave it for a few minutes, as cloud-managed databases take them:
+//
+//   mysql -u alice --enable-cleartext-plugin -p"$(get-token)"
+//
+// The user is let in if the token is signed with the proxy's key, hasn't expired, and names them:
+//
+//   AUTH_JWT_SECRET       a shared secret, for tokens signed with HS256, HS384 or HS512
+//   AUTH_JWT_PUBLIC_KEY   a PEM file of a public key (-----BEGIN PUBLIC KEY-----): RSA for RS256,
+//                         RS384 and RS512, EC P-256 for ES256, P-384 for ES384
+//   AUTH_JWT_ISSUER       the iss the tokens must have, if set
+//   AUTH_JWT_AUDIENCE     an aud they must have, if set
+//   AUTH_JWT_USER_CLAIM   the claim that is the user's name, `sub` by default
+//   AUTH_JWT_ROLE_CLAIM   a claim naming the PostgreSQL role the user's sessions take, if set
+//
+// A token must have an exp, and is taken from its nbf, each a minute either way for clocks that
+// differ. With AUTH_JWT_ROLE_CLAIM set, a token without the claim is refused rather than let in
+// with DB_USER's role.
+//
+// The proxy gets the token as it was sent, so clients log in with mysql_clear_password.
+
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::{hmac, signature};
+use serde_json::{Map, Value};
+
+use super::{AuthProvider, Credentials, Identity, Method};
+use crate::der;
+
+/// AUTH_PROVIDER = jwt's settings.
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    pub key: JwtKey,
+    // Checked against the token's iss and aud, when set.
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub user_claim: String,
+    pub role_claim: Option<String>,
+}
+
+/// The key tokens are signed with.
+#[derive(Debug, Clone)]
+pub enum JwtKey {
+    /// AUTH_JWT_SECRET.
+    Secret(String),
+    /// AUTH_JWT_PUBLIC_KEY.
+    PublicKeyFile(PathBuf),
+}
+
+// How far exp and nbf may be off, in seconds.
+const LEEWAY: f64 = 60.0;
+
+// The algorithm identifiers of a SubjectPublicKeyInfo, as DER has them.
+const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+
+enum Key {
+    Secret(Vec<u8>),
+    // An RSAPublicKey, as PKCS #1 has it.
+    Rsa(Vec<u8>),
+    // Uncompressed points.
+    P256(Vec<u8>),
+    P384(Vec<u8>),
+}
+
+pub struct Jwt {
+    key: Key,
+    issuer: Option<String>,
+    audience: Option<String>,
+    user_claim: String,
+    role_claim: Option<String>,
+}
+
+impl Jwt {
+    /// Checks tokens as `config` has it. Fails if the public key can't be read.
+    pub fn new(config: &JwtConfig) -> io::Result<Jwt> {
+        let key = match &config.key {
+            JwtKey::Secret(secret) => Key::Secret(secret.as_bytes().to_vec()),
+            JwtKey::PublicKeyFile(path) => {
+                let pem = std::fs::read_to_string(path).map_err(|e| {
+                    io::Error::other(format!(
+                        "Can't read AUTH_JWT_PUBLIC_KEY {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                public_key(&pem).ok_or_else(|| {
+                    io::Error::other(format!(
+                        "AUTH_JWT_PUBLIC_KEY {} isn't a PEM RSA, P-256 or P-384 public key",
+                        path.display()
+                    ))
+                })?
+            }
+        };
+        Ok(Jwt {
+            key,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            user_claim: config.user_claim.clone(),
+            role_claim: config.role_claim.clone(),
+        })
+    }
+
+    // The claims of `token`, `header.payload.signature`, if it is signed with the key.
+    fn claims(&self, token: &str) -> Option<Map<String, Value>> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).ok();
+        let header: Value = serde_json::from_slice(&decode(header)?).ok()?;
+        let algorithm = header.get("alg")?.as_str()?;
+        let signed = &token[..token.len() - signature.len() - 1];
+        if !self.verify(algorithm, signed.as_bytes(), &decode(signature)?) {
+            return None;
+        }
+        match serde_json::from_slice(&decode(payload)?).ok()? {
+            Value::Object(claims) => Some(claims),
+            _ => None,
+        }
+    }
+
+    // Whether `signature` is `message`'s with the key, by `algorithm`, a JWT's alg.
+    fn verify(&self, algorithm: &str, message: &[u8], signature: &[u8]) -> bool {
+        let hmac = |algorithm, secret: &[u8]| {
+            hmac::verify(&hmac::Key::new(algorithm, secret), message, signature).is_ok()
+        };
+        let public = |algorithm: &'static dyn signature::VerificationAlgorithm, key: &[u8]| {
+            signature::UnparsedPublicKey::new(algorithm, key)
+                .verify(message, signature)
+                .is_ok()
+        };
+        match (&self.key, algorithm) {
+            (Key::Secret(secret), "HS256") => hmac(hmac::HMAC_SHA256, secret),
+            (Key::Secret(secret), "HS384") => hmac(hmac::HMAC_SHA384, secret),
+            (Key::Secret(secret), "HS512") => hmac(hmac::HMAC_SHA512, secret),
+            (Key::Rsa(key), "RS256") => public(&signature::RSA_PKCS1_2048_8192_SHA256, key),
+            (Key::Rsa(key), "RS384") => public(&signature::RSA_PKCS1_2048_8192_SHA384, key),
+            (Key::Rsa(key), "RS512") => public(&signature::RSA_PKCS1_2048_8192_SHA512, key),
+            (Key::P256(key), "ES256") => public(&signature::ECDSA_P256_SHA256_FIXED, key),
+            (Key::P384(key), "ES384") => public(&signature::ECDSA_P384_SHA384_FIXED, key),
+            // `none` among them.
+            _ => false,
+        }
+    }
+
+    // Whether `claims` are those of a token for `user` that can be used now.
+    fn valid(&self, claims: &Map<String, Value>, user: &str) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let time = |claim| claims.get(claim).and_then(Value::as_f64);
+        let string = |claim| claims.get(claim).and_then(Value::as_str);
+        let audience = |expected: &str| match claims.get("aud") {
+            Some(Value::String(audience)) => audience == expected,
+            Some(Value::Array(audiences)) => audiences.iter().any(|a| a.as_str() == Some(expected)),
+            _ => false,
+        };
+        time("exp").is_some_and(|exp| now <= exp + LEEWAY)
+            && time("nbf").is_none_or(|nbf| nbf - LEEWAY <= now)
+            && self
+                .issuer
+                .as_deref()
+                .is_none_or(|issuer| string("iss") == Some(issuer))
+            && self.audience.as_deref().is_none_or(audience)
+            && string(&self.user_claim) == Some(user)
+    }
+}
+
+#[async_trait]
+impl AuthProvider for Jwt {
+    fn method(&self) -> Method {
+        Method::ClearPassword
+    }
+
+    async fn authenticate(
+        &self,
+        user: &str,
+        credentials: Credentials<'_>,
+        _peer: SocketAddr,
+    ) -> Result<Option<Identity>, Box<dyn Error + Send + Sync>> {
+        let Credentials::Clear(token) = credentials else {
+            return Ok(None);
+        };
+        let Some(claims) = self.claims(token.trim()) else {
+            return Ok(None);
+        };
+        if !self.valid(&claims, user) {
+            return Ok(None);
+        }
+        let role = match &self.role_claim {
+            Some(claim) => match claims.get(claim).and_then(Value::as_str) {
+                Some(role) if !role.is_empty() => Some(role.to_string()),
+                _ => return Ok(None),
+            },
+            None => None,
+        };
+        Ok(Some(Identity { role }))
+    }
+}
+
+// The key of a PEM SubjectPublicKeyInfo, as `openssl pkey -pubout` writes it.
+fn public_key(pem: &str) -> Option<Key> {
+    let base64: String = pem
+        .lines()
+        .skip_while(|line| line.trim() != "-----BEGIN PUBLIC KEY-----")
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END"))
+        .map(str::trim)
+        .collect();
+    let der = STANDARD.decode(base64).ok()?;
+    // SEQUENCE { SEQUENCE { algorithm, parameters }, BIT STRING }
+    let (info, _) = der::element(&der, 0x30)?;
+    let (algorithm, rest) = der::element(info, 0x30)?;
+    let (bits, _) = der::element(rest, 0x03)?;
+    let key = bits.strip_prefix(&[0])?.to_vec();
+    let (oid, parameters) = der::element(algorithm, 0x06)?;
+    match oid {
+        RSA_ENCRYPTION => Some(Key::Rsa(key)),
+        EC_PUBLIC_KEY => match der::element(parameters, 0x06)?.0 {
+            P256 => Some(Key::P256(key)),
+            P384 => Some(Key::P384(key)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::json;
+
+    use super::*;
+
+    fn now() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+    }
+
+    fn jwt(key: Key) -> Jwt {
+        Jwt {
+            key,
+            issuer: Some("idp".to_string()),
+            audience: Some("proxy".to_string()),
+            user_claim: "sub".to_string(),
+            role_claim: Some("role".to_string()),
+        }
+    }
+
+    fn secret() -> Jwt {
+        jwt(Key::Secret(b"secret".to_vec()))
+    }
+
+    // Claims for alice that are valid now.
+    fn claims() -> Value {
+        json!({
+            "sub": "alice",
+            "iss": "idp",
+            "aud": ["other", "proxy"],
+            "exp": now() + 300.0,
+            "role": "reporting",
+        })
+    }
+
+    fn token(algorithm: &str, claims: &Value, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let header = json!({ "alg": algorithm, "typ": "JWT" });
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = URL_SAFE_NO_PAD.encode(sign(signed.as_bytes()));
+        format!("{}.{}", signed, signature)
+    }
+
+    fn hs256(secret: &'static [u8]) -> impl Fn(&[u8]) -> Vec<u8> {
+        move |message| {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+            hmac::sign(&key, message).as_ref().to_vec()
+        }
+    }
+
+    async fn login(jwt: &Jwt, user: &str, token: &str) -> Option<Identity> {
+        let peer = "127.0.0.1:50000".parse().unwrap();
+        jwt.authenticate(user, Credentials::Clear(token), peer)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lets_in_tokens_signed_with_the_key() {
+        let reporting = Some(Identity {
+            role: Some("reporting".to_string()),
+        });
+        let signed = token("HS256", &claims(), hs256(b"secret"));
+        assert_eq!(login(&secret(), "alice", &signed).await, reporting);
+
+        let random = SystemRandom::new();
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &random).unwrap();
+        let pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &random)
+                .unwrap();
+        let es256 = jwt(Key::P256(pair.public_key().as_ref().to_vec()));
+        let signed = token("ES256", &claims(), |message| {
+            pair.sign(&random, message).unwrap().as_ref().to_vec()
+        });
+        assert_eq!(login(&es256, "alice", &signed).await, reporting);
+        // Another user's token isn't theirs.
+        assert_eq!(login(&es256, "bob", &signed).await, None);
+    }
+
+    #[tokio::test]
+    async fn refuses_bad_signatures_and_algorithms_of_another_key() {
+        let provider = secret();
+        let forged = token("HS256", &claims(), hs256(b"guessed"));
+        assert_eq!(login(&provider, "alice", &forged).await, None);
+        // A payload swapped under a good signature.
+        let good = token("HS256", &claims(), hs256(b"secret"));
+        let other = token("HS256", &json!({ "sub": "root" }), hs256(b"secret"));
+        let parts: Vec<&str> = good.split('.').collect();
+        let swapped = format!(
+            "{}.{}.{}",
+            parts[0],
+            other.split('.').nth(1).unwrap(),
+            parts[2]
+        );
+        assert_eq!(login(&provider, "root", &swapped).await, None);
+        // alg: none, with or without a signature.
+        let unsigned = token("none", &claims(), |_| Vec::new());
+        assert_eq!(login(&provider, "alice", &unsigned).await, None);
+        let unsigned = token("none", &claims(), hs256(b"secret"));
+        assert_eq!(login(&provider, "alice", &unsigned).await, None);
+        // An RS256 token for a secret, and an HS256 one signed with a public key as the secret.
+        let rs256 = token("RS256", &claims(), hs256(b"secret"));
+        assert_eq!(login(&provider, "alice", &rs256).await, None);
+        let public = b"-----BEGIN PUBLIC KEY-----";
+        let confused = token("HS256", &claims(), hs256(public));
+        assert_eq!(
+            login(&jwt(Key::Rsa(public.to_vec())), "alice", &confused).await,
+            None
+        );
+        assert_eq!(login(&provider, "alice", "not.a.token").await, None);
+        assert_eq!(login(&provider, "alice", "").await, None);
+    }
+
+    #[tokio::test]
+    async fn refuses_tokens_out_of_their_time() {
+        let provider = secret();
+        let with = |claim: &str, value: Value| {
+            let mut claims = claims();
+            claims[claim] = value;
+            token("HS256", &claims, hs256(b"secret"))
+        };
+        let expired = with("exp", json!(now() - 120.0));
+        assert_eq!(login(&provider, "alice", &expired).await, None);
+        let early = with("nbf", json!(now() + 120.0));
+        assert_eq!(login(&provider, "alice", &early).await, None);
+        let mut claims = claims();
+        claims.as_object_mut().unwrap().remove("exp");
+        let forever = token("HS256", &claims, hs256(b"secret"));
+        assert_eq!(login(&provider, "alice", &forever).await, None);
+        // Clocks may differ by a minute.
+        let just_expired = with("exp", json!(now() - 30.0));
+        assert!(login(&provider, "alice", &just_expired).await.is_some());
+        let nearly_valid = with("nbf", json!(now() + 30.0));
+        assert!(login(&provider, "alice", &nearly_valid).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn refuses_tokens_for_another_audience_issuer_or_user() {
+        let provider = secret();
+        let with = |claim: &str, value: Value| {
+            let mut claims = claims();
+            claims[claim] = value;
+            token("HS256", &claims, hs256(b"secret"))
+        };
+        for token in [
+            with("aud", json!("other")),
+            with("aud", json!(["other"])),
+            with("aud", json!(null)),
+            with("iss", json!("elsewhere")),
+            with("sub", json!("bob")),
+        ] {
+            assert_eq!(login(&provider, "alice", &token).await, None);
+        }
+        let token = with("aud", json!("proxy"));
+        assert!(login(&provider, "alice", &token).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn refuses_tokens_without_a_role_when_one_is_claimed() {
+        let provider = secret();
+        let with = |role: Option<Value>| {
+            let mut claims = claims();
+            match role {
+                Some(role) => claims["role"] = role,
+                None => drop(claims.as_object_mut().unwrap().remove("role")),
+            }
+            token("HS256", &claims, hs256(b"secret"))
+        };
+        for token in [
+            with(None),
+            with(Some(json!(""))),
+            with(Some(json!(7))),
+            with(Some(json!(["reporting"]))),
+            with(Some(json!(null))),
+        ] {
+            assert_eq!(login(&provider, "alice", &token).await, None);
+        }
+        // Without AUTH_JWT_ROLE_CLAIM, the user takes DB_USER's role.
+        let unroled = Jwt {
+            role_claim: None,
+            ..secret()
+        };
+        assert_eq!(
+            login(&unroled, "alice", &with(None)).await,
+            Some(Identity::default())
+        );
+    }
+}
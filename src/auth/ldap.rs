@@ -0,0 +1,78 @@
+// AUTH_PROVIDER = ldap, with the ldap feature: lets a user in if the directory at
+// AUTH_LDAP_URL (`ldap://` or `ldaps://`) accepts a simple bind with their password, as the DN
+// AUTH_LDAP_BIND_DN names with `{user}` replaced by the user, escaped:
+//
+//   AUTH_LDAP_BIND_DN = uid={user},ou=people,dc=example,dc=com
+//
+// A directory that can't be reached, or doesn't answer within AUTH_LDAP_TIMEOUT seconds (5 by
+// default), keeps the user out, which is logged. An empty password is refused without asking:
+// directories take a bind without one as an anonymous bind, and let it through.
+//
+// The directory gets the password as it was typed, so clients log in with mysql_clear_password.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ldap3::{dn_escape, LdapConnAsync, LdapConnSettings};
+
+use super::{AuthProvider, Credentials, Identity, Method};
+
+// The result code of a bind with the wrong password.
+const INVALID_CREDENTIALS: u32 = 49;
+
+pub struct Ldap {
+    url: String,
+    bind_dn: String,
+    timeout: Duration,
+}
+
+impl Ldap {
+    pub fn new(url: &str, bind_dn: &str, timeout: Duration) -> Ldap {
+        Ldap {
+            url: url.to_string(),
+            bind_dn: bind_dn.to_string(),
+            timeout,
+        }
+    }
+
+    async fn bind(&self, dn: &str, password: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout);
+        let (connection, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(connection);
+        let result = ldap.simple_bind(dn, password).await?;
+        let _ = ldap.unbind().await;
+        match result.rc {
+            0 => Ok(true),
+            INVALID_CREDENTIALS => Ok(false),
+            _ => Err(result.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for Ldap {
+    fn method(&self) -> Method {
+        Method::ClearPassword
+    }
+
+    async fn authenticate(
+        &self,
+        user: &str,
+        credentials: Credentials<'_>,
+        _peer: SocketAddr,
+    ) -> Result<Option<Identity>, Box<dyn Error + Send + Sync>> {
+        let Credentials::Clear(password) = credentials else {
+            return Ok(None);
+        };
+        if password.is_empty() {
+            return Ok(None);
+        }
+        let dn = self.bind_dn.replace("{user}", &dn_escape(user));
+        let bound = tokio::time::timeout(self.timeout, self.bind(&dn, password))
+            .await
+            .map_err(|_| format!("the directory didn't answer within {:?}", self.timeout))??;
+        Ok(bound.then(Identity::default))
+    }
+}
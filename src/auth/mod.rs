@@ -0,0 +1,317 @@
+// Who may log in (AUTH_PROVIDER). By default anyone may, as any user with any password, and the
+// proxy reaches PostgreSQL as DB_USER whoever they are. An AuthProvider checks the user and
+// password each client logs in with, and at COM_CHANGE_USER:
+//
+//   static   the users and passwords of AUTH_USERS
+//   webhook  an HTTP service of the enterprise's, asked at AUTH_WEBHOOK_URL (see webhook.rs)
+//   ldap     a simple bind to an LDAP directory, with the ldap feature (see ldap.rs)
+//   jwt      a signed, short-lived token in place of the password (see jwt.rs)
+//
+// A program embedding the proxy can give ServerBuilder::auth a provider of its own.
+//
+// A provider that checks the password against a MySQL password hash takes it as
+// mysql_native_password sends it, scrambled with a salt of the connection's. One that hands it
+// to another system needs it as it was typed, and has clients log in with mysql_clear_password,
+// which sends it so. Clients only do that when told they may (--enable-cleartext-plugin,
+// allowCleartextPasswords=true and the like), and unless they connect over TLS
+// (LISTEN_TLS_CERT, see client_tls.rs), only on a network the passwords can cross.
+//
+// A provider may give the user a PostgreSQL role, which their sessions take with SET ROLE as they
+// start, so that PostgreSQL's grants apply to them rather than to DB_USER's. The client can't
+// undo it: statements that would change or reset the role are refused (see policy.rs).
+//
+// A client logging in with another method, MySQL 8's caching_sha2_password say, is asked to
+// switch to the provider's (see protocol.rs).
+
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+mod jwt;
+#[cfg(feature = "ldap")]
+mod ldap;
+mod webhook;
+
+pub use jwt::{JwtConfig, JwtKey};
+
+/// How clients send their passwords, by the authentication method they log in with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// mysql_native_password: the password scrambled with the connection's salt.
+    NativePassword,
+    /// mysql_clear_password: the password itself.
+    ClearPassword,
+}
+
+impl Method {
+    /// The name of the method's MySQL authentication plugin.
+    pub fn plugin(self) -> &'static str {
+        match self {
+            Method::NativePassword => "mysql_native_password",
+            Method::ClearPassword => "mysql_clear_password",
+        }
+    }
+}
+
+/// A password as a client sent it.
+#[derive(Debug, Clone, Copy)]
+pub enum Credentials<'a> {
+    /// mysql_native_password's: `SHA1(password) XOR SHA1(salt + SHA1(SHA1(password)))`, or
+    /// nothing for an empty password.
+    Scrambled {
+        salt: &'a [u8],
+        scramble: &'a [u8],
+    },
+    Clear(&'a str),
+}
+
+impl Credentials<'_> {
+    /// Whether this is the password of `hash`, as `native_password_hash` gives it.
+    pub fn matches(&self, hash: &PasswordHash) -> bool {
+        let Some(hash) = hash.0 else {
+            return match *self {
+                Credentials::Scrambled { scramble, .. } => scramble.is_empty(),
+                Credentials::Clear(password) => password.is_empty(),
+            };
+        };
+        match *self {
+            Credentials::Scrambled { salt, scramble } => {
+                if scramble.len() != 20 {
+                    return false;
+                }
+                let mask = Sha1::new().chain_update(salt).chain_update(hash).finalize();
+                let stage1: Vec<u8> = scramble.iter().zip(mask).map(|(a, b)| a ^ b).collect();
+                Sha1::digest(stage1)[..] == hash
+            }
+            Credentials::Clear(password) => {
+                native_password_hash(password) == PasswordHash(Some(hash))
+            }
+        }
+    }
+}
+
+/// A password as mysql_native_password checks it, `SHA1(SHA1(password))`, which is what MySQL
+/// keeps in mysql.user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHash(Option<[u8; 20]>);
+
+impl PasswordHash {
+    /// The hash as MySQL shows it, `*` and 40 hex digits, as PASSWORD('...') gave it; an empty
+    /// string for no password.
+    pub fn parse(text: &str) -> Option<PasswordHash> {
+        if text.is_empty() {
+            return Some(PasswordHash(None));
+        }
+        let hex = text.strip_prefix('*').filter(|hex| hex.len() == 40)?;
+        let mut hash = [0; 20];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(PasswordHash(Some(hash)))
+    }
+}
+
+/// The hash of `password`.
+pub fn native_password_hash(password: &str) -> PasswordHash {
+    if password.is_empty() {
+        return PasswordHash(None);
+    }
+    PasswordHash(Some(Sha1::digest(Sha1::digest(password)).into()))
+}
+
+/// A user a provider let in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// The PostgreSQL role the user's sessions take; DB_USER's own for `None`.
+    pub role: Option<String>,
+}
+
+/// Decides who may log in.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// How the clients have to send their passwords.
+    fn method(&self) -> Method;
+
+    /// Who `user`, connecting from `peer`, is if they may log in with `credentials`; `None` if
+    /// they may not. An error, the provider's service being down say, keeps the user out, and is
+    /// logged.
+    async fn authenticate(
+        &self,
+        user: &str,
+        credentials: Credentials<'_>,
+        peer: SocketAddr,
+    ) -> Result<Option<Identity>, Box<dyn Error + Send + Sync>>;
+}
+
+/// The provider AUTH_PROVIDER picks, with its settings.
+#[derive(Debug, Clone, Default)]
+pub enum AuthConfig {
+    /// Anyone may log in.
+    #[default]
+    None,
+    /// The users of AUTH_USERS, with their passwords.
+    Static(Vec<(String, PasswordHash)>),
+    /// AUTH_WEBHOOK_URL, answering within AUTH_WEBHOOK_TIMEOUT.
+    Webhook { url: String, timeout: Duration },
+    /// AUTH_LDAP_URL, binding as AUTH_LDAP_BIND_DN with `{user}` replaced by the user.
+    Ldap {
+        url: String,
+        bind_dn: String,
+        timeout: Duration,
+    },
+    /// Tokens signed with AUTH_JWT_SECRET or AUTH_JWT_PUBLIC_KEY.
+    Jwt(JwtConfig),
+}
+
+/// The length of the salt a connection's password is scrambled with.
+pub const SALT_LENGTH: usize = 20;
+
+/// A salt for a connection, of printable characters other than `$`, as MySQL's.
+pub fn salt() -> [u8; SALT_LENGTH] {
+    let mut rng = rand::thread_rng();
+    std::array::from_fn(|_| rng.gen_range(b'%'..=b'~'))
+}
+
+/// How long a webhook or directory has to answer by default, in seconds.
+pub const DEFAULT_TIMEOUT: u64 = 5;
+
+/// The provider `config` describes, `None` for AuthConfig::None. Fails if it can't be set up:
+/// LDAP without the ldap feature, a webhook URL that isn't one, or a JWT key that can't be read.
+pub fn provider(config: &AuthConfig) -> io::Result<Option<Arc<dyn AuthProvider>>> {
+    let provider: Arc<dyn AuthProvider> = match config {
+        AuthConfig::None => return Ok(None),
+        AuthConfig::Static(users) => Arc::new(StaticUsers(users.clone())),
+        AuthConfig::Webhook { url, timeout } => Arc::new(webhook::Webhook::new(url, *timeout)?),
+        AuthConfig::Jwt(config) => Arc::new(jwt::Jwt::new(config)?),
+        #[cfg(feature = "ldap")]
+        AuthConfig::Ldap {
+            url,
+            bind_dn,
+            timeout,
+        } => Arc::new(ldap::Ldap::new(url, bind_dn, *timeout)),
+        #[cfg(not(feature = "ldap"))]
+        AuthConfig::Ldap { .. } => {
+            return Err(io::Error::other(
+                "AUTH_PROVIDER is ldap, but this build has no LDAP; build with --features ldap",
+            ))
+        }
+    };
+    Ok(Some(provider))
+}
+
+// AUTH_PROVIDER = static.
+struct StaticUsers(Vec<(String, PasswordHash)>);
+
+#[async_trait]
+impl AuthProvider for StaticUsers {
+    fn method(&self) -> Method {
+        Method::NativePassword
+    }
+
+    async fn authenticate(
+        &self,
+        user: &str,
+        credentials: Credentials<'_>,
+        _peer: SocketAddr,
+    ) -> Result<Option<Identity>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .0
+            .iter()
+            .any(|(name, hash)| name == user && credentials.matches(hash))
+            .then(Identity::default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What a client sends for `password` with mysql_native_password.
+    fn scramble(password: &str, salt: &[u8]) -> Vec<u8> {
+        if password.is_empty() {
+            return Vec::new();
+        }
+        let stage1 = Sha1::digest(password);
+        let stage2 = Sha1::digest(stage1);
+        let mask = Sha1::new()
+            .chain_update(salt)
+            .chain_update(stage2)
+            .finalize();
+        stage1.iter().zip(mask).map(|(a, b)| a ^ b).collect()
+    }
+
+    async fn login(users: &StaticUsers, user: &str, credentials: Credentials<'_>) -> bool {
+        let peer = "127.0.0.1:50000".parse().unwrap();
+        users
+            .authenticate(user, credentials, peer)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    fn users() -> StaticUsers {
+        StaticUsers(vec![
+            ("alice".to_string(), native_password_hash("secret")),
+            ("guest".to_string(), native_password_hash("")),
+        ])
+    }
+
+    #[tokio::test]
+    async fn static_users_log_in_with_their_scrambled_passwords() {
+        let salt = salt();
+        let other_salt = b"01234567890123456789";
+        let alice = scramble("secret", &salt);
+        for (user, scramble, salt, let_in) in [
+            ("alice", alice.clone(), &salt, true),
+            ("guest", Vec::new(), &salt, true),
+            ("alice", scramble("wrong", &salt), &salt, false),
+            // Scrambled with another connection's salt.
+            ("alice", alice.clone(), other_salt, false),
+            ("alice", alice[..19].to_vec(), &salt, false),
+            // An empty password isn't alice's, nor is a password the guest's.
+            ("alice", Vec::new(), &salt, false),
+            ("guest", scramble("secret", &salt), &salt, false),
+            ("mallory", alice.clone(), &salt, false),
+            ("Alice", alice.clone(), &salt, false),
+        ] {
+            let credentials = Credentials::Scrambled {
+                salt,
+                scramble: &scramble,
+            };
+            assert_eq!(login(&users(), user, credentials).await, let_in, "{}", user);
+        }
+    }
+
+    #[tokio::test]
+    async fn static_users_take_clear_passwords_too() {
+        assert!(login(&users(), "alice", Credentials::Clear("secret")).await);
+        assert!(login(&users(), "guest", Credentials::Clear("")).await);
+        assert!(!login(&users(), "alice", Credentials::Clear("Secret")).await);
+        assert!(!login(&users(), "alice", Credentials::Clear("")).await);
+        assert!(!login(&users(), "nobody", Credentials::Clear("")).await);
+    }
+
+    #[test]
+    fn parses_password_hashes_as_mysql_shows_them() {
+        // SELECT PASSWORD('secret') on MySQL 5.7.
+        let hash = PasswordHash::parse("*14E65567ABDB5135D0CFD9A70B3032C179A49EE7").unwrap();
+        assert_eq!(hash, native_password_hash("secret"));
+        let lower = PasswordHash::parse("*14e65567abdb5135d0cfd9a70b3032c179a49ee7");
+        assert_eq!(lower, Some(hash));
+        assert_eq!(PasswordHash::parse(""), Some(native_password_hash("")));
+        for text in [
+            "14E65567ABDB5135D0CFD9A70B3032C179A49EE7",
+            "*14E65567ABDB5135D0CFD9A70B3032C179A49EE",
+            "*14E65567ABDB5135D0CFD9A70B3032C179A49EEZ",
+            "secret",
+        ] {
+            assert_eq!(PasswordHash::parse(text), None, "{}", text);
+        }
+    }
+}
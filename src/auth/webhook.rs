@@ -0,0 +1,260 @@
+// AUTH_PROVIDER = webhook: asks an HTTP service whether a user may log in, POSTing
+//
+//   {"user": "alice", "password": "...", "address": "10.0.0.5:51234"}
+//
+// to AUTH_WEBHOOK_URL, the address being the client's. An answer of 2xx lets the user in, any
+// other keeps them out. So does a service that can't be reached, or doesn't answer within
+// AUTH_WEBHOOK_TIMEOUT seconds (5 by default), which is logged. An https URL's certificate is
+// checked against the public web CAs.
+//
+// The service gets the password as it was typed, so clients log in with mysql_clear_password.
+
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rustls::crypto;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use super::{AuthProvider, Credentials, Identity, Method};
+use crate::logging::push_string;
+
+pub struct Webhook {
+    // `host:port` as the URL gives it, for the Host header.
+    authority: String,
+    host: String,
+    port: u16,
+    path: String,
+    tls: Option<TlsConnector>,
+    timeout: Duration,
+}
+
+impl Webhook {
+    /// A webhook at `url`, `http://` or `https://`, answering within `timeout`.
+    pub fn new(url: &str, timeout: Duration) -> io::Result<Webhook> {
+        let invalid = || io::Error::other(format!("AUTH_WEBHOOK_URL isn't an http URL: {}", url));
+        let (https, rest) = match url.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => (false, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => (true, rest),
+            _ => return Err(invalid()),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(at) => (&rest[..at], &rest[at..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                (host, port.parse().map_err(|_| invalid())?)
+            }
+            _ => (authority, if https { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let tls = match https {
+            true => {
+                let mut roots = RootCertStore::empty();
+                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+                let config =
+                    ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                        .with_safe_default_protocol_versions()
+                        .map_err(io::Error::other)?
+                        .with_root_certificates(roots)
+                        .with_no_client_auth();
+                Some(TlsConnector::from(Arc::new(config)))
+            }
+            false => None,
+        };
+        Ok(Webhook {
+            authority: authority.to_string(),
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port,
+            path: path.to_string(),
+            tls,
+            timeout,
+        })
+    }
+
+    // The status of the service's answer to `body`.
+    async fn post(&self, body: &str) -> io::Result<u16> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        match &self.tls {
+            Some(tls) => {
+                let name = ServerName::try_from(self.host.clone()).map_err(io::Error::other)?;
+                self.exchange(tls.connect(name, stream).await?, body).await
+            }
+            None => self.exchange(stream, body).await,
+        }
+    }
+
+    async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut stream: S,
+        body: &str,
+    ) -> io::Result<u16> {
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.authority,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await?;
+        stream.flush().await?;
+        // Only the status line matters: `HTTP/1.1 200 OK`.
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status).await?;
+        status
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::other(format!("not an HTTP answer: {:?}", status.trim())))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for Webhook {
+    fn method(&self) -> Method {
+        Method::ClearPassword
+    }
+
+    async fn authenticate(
+        &self,
+        user: &str,
+        credentials: Credentials<'_>,
+        peer: SocketAddr,
+    ) -> Result<Option<Identity>, Box<dyn Error + Send + Sync>> {
+        let Credentials::Clear(password) = credentials else {
+            return Ok(None);
+        };
+        let mut body = String::from("{\"user\":");
+        push_string(&mut body, user);
+        body.push_str(",\"password\":");
+        push_string(&mut body, password);
+        body.push_str(",\"address\":");
+        push_string(&mut body, &peer.to_string());
+        body.push('}');
+        let status = tokio::time::timeout(self.timeout, self.post(&body))
+            .await
+            .map_err(|_| format!("the webhook didn't answer within {:?}", self.timeout))??;
+        Ok((200..300).contains(&status).then(Identity::default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    use super::*;
+
+    // A service that answers one request with `answer`, or never if it's None, and gives back the
+    // request it got.
+    async fn service(answer: Option<&'static str>) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/login", listener.local_addr().unwrap());
+        let served = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap();
+            match answer {
+                Some(answer) => stream.write_all(answer.as_bytes()).await.unwrap(),
+                None => std::future::pending().await,
+            }
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+        (url, served)
+    }
+
+    async fn login(url: &str, password: &str) -> Result<Option<Identity>, String> {
+        let webhook = Webhook::new(url, Duration::from_millis(200)).unwrap();
+        let peer = "10.0.0.5:51234".parse().unwrap();
+        webhook
+            .authenticate("alice", Credentials::Clear(password), peer)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn lets_in_whom_the_service_answers_2xx() {
+        for (answer, let_in) in [
+            ("HTTP/1.1 200 OK\r\n\r\n", true),
+            ("HTTP/1.0 204 No Content\r\n\r\n", true),
+            ("HTTP/1.1 403 Forbidden\r\n\r\n", false),
+            ("HTTP/1.1 401 Unauthorized\r\n\r\n", false),
+            ("HTTP/1.1 302 Found\r\nLocation: /\r\n\r\n", false),
+            ("HTTP/1.1 500 Internal Server Error\r\n\r\n", false),
+        ] {
+            let (url, served) = service(Some(answer)).await;
+            let identity = login(&url, "p\"w").await.unwrap();
+            assert_eq!(identity.is_some(), let_in, "{}", answer);
+            let request = served.await.unwrap();
+            assert!(
+                request.starts_with("POST /login HTTP/1.1\r\n"),
+                "{}",
+                request
+            );
+            assert!(request
+                .ends_with(r#"{"user":"alice","password":"p\"w","address":"10.0.0.5:51234"}"#));
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_users_out_when_the_service_fails() {
+        // Not HTTP.
+        let (url, _) = service(Some("SSH-2.0-OpenSSH\r\n")).await;
+        assert!(login(&url, "pw").await.is_err());
+        // No answer within the timeout.
+        let (url, _) = service(None).await;
+        let error = login(&url, "pw").await.unwrap_err();
+        assert!(error.contains("didn't answer"), "{}", error);
+        // Nothing listening.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(login(&url, "pw").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_scrambled_passwords_without_asking() {
+        let webhook = Webhook::new("http://127.0.0.1:9/", Duration::from_millis(200)).unwrap();
+        let peer = "10.0.0.5:51234".parse().unwrap();
+        let credentials = Credentials::Scrambled {
+            salt: &[0; 20],
+            scramble: &[0; 20],
+        };
+        let identity = webhook.authenticate("alice", credentials, peer).await;
+        assert_eq!(identity.unwrap(), None);
+    }
+
+    #[test]
+    fn takes_http_and_https_urls() {
+        let webhook = Webhook::new("https://auth.example.com/check", Duration::ZERO).unwrap();
+        assert_eq!(
+            (webhook.host.as_str(), webhook.port, webhook.path.as_str()),
+            ("auth.example.com", 443, "/check")
+        );
+        assert!(webhook.tls.is_some());
+        let webhook = Webhook::new("HTTP://[::1]:8080", Duration::ZERO).unwrap();
+        assert_eq!(
+            (webhook.host.as_str(), webhook.port, webhook.path.as_str()),
+            ("::1", 8080, "/")
+        );
+        assert_eq!(webhook.authority, "[::1]:8080");
+        for url in ["ftp://host/", "host:80", "http://:80/", "http://host:port/"] {
+            assert!(Webhook::new(url, Duration::ZERO).is_err(), "{}", url);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// CALL of a PostgreSQL function.
+//
+// A MySQL procedure that returns a result set has no PostgreSQL procedure counterpart; it is
+// usually migrated to a set-returning function, which PostgreSQL calls from a SELECT. So CALL
+// keeps working for those:
+//
+//   CALL top_customers(10)  ->  SELECT * FROM top_customers(10)
+//
+// when `top_customers` is a function. Procedures are left to CALL.
+
+use tokio_postgres::{Client, Error};
+
+use crate::catalog;
+use crate::emulation;
+use crate::translator::{self, render, statement_starts_with};
+
+/// The translated statement `translated` of the CALL `sql`, rewritten for PostgreSQL if it
+/// calls a function. `None` when there is nothing to rewrite.
+pub async fn rewrite(
+    client: &Client,
+    sql: &str,
+    translated: &str,
+) -> Result<Option<String>, Error> {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return Ok(None);
+    };
+    if !tokens.first().is_some_and(|t| t.is_word("CALL")) {
+        return Ok(None);
+    }
+    let Some((routine, _)) = emulation::object_name(&tokens[1..]) else {
+        return Ok(None);
+    };
+    if catalog::is_function(client, &routine).await? != Some(true) {
+        return Ok(None);
+    }
+
+    let Ok(nodes) = translator::parse(translated) else {
+        return Ok(None);
+    };
+    if !statement_starts_with(&nodes, &["CALL"]) {
+        return Ok(None);
+    }
+    let call = nodes
+        .iter()
+        .position(|n| !n.is_trivia())
+        .expect("the statement starts with CALL");
+    Ok(Some(format!(
+        "{}SELECT * FROM{}",
+        render(&nodes[..call]),
+        render(&nodes[call + 1..])
+    )))
+}
@@ -0,0 +1,300 @@
+// PostgreSQL catalog introspection, presented in MySQL terms.
+//
+// MySQL databases map onto PostgreSQL schemas (USE sets the search_path), so a table is looked
+// up by name in the given schema. An unqualified name is the table PostgreSQL would resolve it
+// to: the session's temporary table if there is one, as in MySQL, and otherwise the one in the
+// search_path.
+
+use tokio_postgres::{Client, Error};
+
+/// A possibly schema-qualified table (or view/routine) name, already folded to PostgreSQL case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectName {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub name: String,
+    // PostgreSQL type as printed by format_type(), e.g. "character varying(50)".
+    pub pg_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub auto_increment: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub name: String,
+    pub primary: bool,
+    pub unique: bool,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignKeyInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    // The schema of the referenced table when it isn't the table's own.
+    pub referenced_schema: Option<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    // The referential actions other than the default, NO ACTION.
+    pub on_delete: Option<&'static str>,
+    pub on_update: Option<&'static str>,
+}
+
+const TABLE_OID: &str = "(SELECT c.oid FROM pg_class c \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     WHERE c.relname = $1 AND CASE WHEN $2::text IS NULL THEN pg_table_is_visible(c.oid) \
+         ELSE n.nspname = $2 OR ($2 = 'pg_temp' AND n.oid = pg_my_temp_schema()) END)";
+
+/// Columns of a table in definition order. Empty when the table doesn't exist.
+pub async fn table_columns(client: &Client, table: &ObjectName) -> Result<Vec<ColumnInfo>, Error> {
+    let sql = format!(
+        "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull, \
+                pg_get_expr(d.adbin, d.adrelid), a.attidentity <> '' \
+         FROM pg_attribute a \
+         LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+         WHERE a.attrelid = {} AND a.attnum > 0 AND NOT a.attisdropped \
+         ORDER BY a.attnum",
+        TABLE_OID
+    );
+    let rows = client
+        .query(sql.as_str(), &[&table.name, &table.schema])
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let default: Option<String> = row.get(3);
+            let identity: bool = row.get(4);
+            let auto_increment = identity
+                || default
+                    .as_deref()
+                    .is_some_and(|d| d.starts_with("nextval("));
+            ColumnInfo {
+                name: row.get(0),
+                pg_type: row.get(1),
+                nullable: row.get(2),
+                default: if auto_increment { None } else { default },
+                auto_increment,
+            }
+        })
+        .collect())
+}
+
+/// Whether a table is one of the session's temporary tables.
+pub async fn is_temporary(client: &Client, table: &ObjectName) -> Result<bool, Error> {
+    let sql = format!(
+        "SELECT relpersistence = 't' FROM pg_class WHERE oid = {}",
+        TABLE_OID
+    );
+    let row = client
+        .query_opt(sql.as_str(), &[&table.name, &table.schema])
+        .await?;
+    Ok(row.is_some_and(|row| row.get(0)))
+}
+
+/// Indexes of a table, primary key first.
+pub async fn table_indexes(client: &Client, table: &ObjectName) -> Result<Vec<IndexInfo>, Error> {
+    let sql = format!(
+        "SELECT ic.relname::text, i.indisprimary, i.indisunique, \
+                ARRAY(SELECT a.attname::text \
+                      FROM unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord) \
+                      JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum \
+                      ORDER BY k.ord) \
+         FROM pg_index i \
+         JOIN pg_class ic ON ic.oid = i.indexrelid \
+         WHERE i.indrelid = {} \
+         ORDER BY i.indisprimary DESC, ic.relname",
+        TABLE_OID
+    );
+    let rows = client
+        .query(sql.as_str(), &[&table.name, &table.schema])
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| IndexInfo {
+            name: row.get(0),
+            primary: row.get(1),
+            unique: row.get(2),
+            columns: row.get(3),
+        })
+        .collect())
+}
+
+/// Foreign keys of a table, by name.
+pub async fn table_foreign_keys(
+    client: &Client,
+    table: &ObjectName,
+) -> Result<Vec<ForeignKeyInfo>, Error> {
+    let sql = format!(
+        "SELECT con.conname::text, \
+                ARRAY(SELECT a.attname::text \
+                      FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, ord) \
+                      JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum \
+                      ORDER BY k.ord), \
+                CASE WHEN rc.relnamespace <> tc.relnamespace THEN rn.nspname::text END, \
+                rc.relname::text, \
+                ARRAY(SELECT a.attname::text \
+                      FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, ord) \
+                      JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum \
+                      ORDER BY k.ord), \
+                con.confdeltype::text, con.confupdtype::text \
+         FROM pg_constraint con \
+         JOIN pg_class tc ON tc.oid = con.conrelid \
+         JOIN pg_class rc ON rc.oid = con.confrelid \
+         JOIN pg_namespace rn ON rn.oid = rc.relnamespace \
+         WHERE con.conrelid = {} AND con.contype = 'f' \
+         ORDER BY con.conname",
+        TABLE_OID
+    );
+    let rows = client
+        .query(sql.as_str(), &[&table.name, &table.schema])
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ForeignKeyInfo {
+            name: row.get(0),
+            columns: row.get(1),
+            referenced_schema: row.get(2),
+            referenced_table: row.get(3),
+            referenced_columns: row.get(4),
+            on_delete: referential_action(row.get(5)),
+            on_update: referential_action(row.get(6)),
+        })
+        .collect())
+}
+
+// A pg_constraint action code, as written in MySQL.
+fn referential_action(code: &str) -> Option<&'static str> {
+    match code {
+        "r" => Some("RESTRICT"),
+        "c" => Some("CASCADE"),
+        "n" => Some("SET NULL"),
+        "d" => Some("SET DEFAULT"),
+        _ => None,
+    }
+}
+
+/// Whether a routine is a function rather than a procedure; `None` if there is no routine of
+/// that name.
+pub async fn is_function(client: &Client, routine: &ObjectName) -> Result<Option<bool>, Error> {
+    let row = client
+        .query_opt(
+            "SELECT p.prokind <> 'p' FROM pg_proc p \
+             JOIN pg_namespace n ON n.oid = p.pronamespace \
+             WHERE p.proname = $1 AND CASE WHEN $2::text IS NULL \
+                 THEN pg_function_is_visible(p.oid) ELSE n.nspname = $2 END \
+             LIMIT 1",
+            &[&routine.name, &routine.schema],
+        )
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Where the temporal_tables `versioning` trigger of a table keeps its history.
+#[derive(Debug, Clone)]
+pub struct History {
+    // The tstzrange column holding each row version's validity period.
+    pub period_column: String,
+    // The history table, as written in the trigger (possibly schema-qualified).
+    pub table: String,
+}
+
+/// The history of a system-versioned table, `None` if it has no `versioning` trigger.
+pub async fn table_history(client: &Client, table: &ObjectName) -> Result<Option<History>, Error> {
+    let sql = format!(
+        "SELECT t.tgargs \
+         FROM pg_trigger t \
+         JOIN pg_proc p ON p.oid = t.tgfoid \
+         WHERE t.tgrelid = {} AND p.proname = 'versioning' AND t.tgnargs >= 2",
+        TABLE_OID
+    );
+    let rows = client
+        .query(sql.as_str(), &[&table.name, &table.schema])
+        .await?;
+
+    // versioning(period_column, history_table, adjust): the arguments are NUL-terminated.
+    Ok(rows.first().and_then(|row| {
+        let args: Vec<u8> = row.get(0);
+        let mut args = args
+            .split(|&b| b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).into_owned());
+        Some(History {
+            period_column: args.next()?,
+            table: args.next()?,
+        })
+    }))
+}
+
+/// Maps a PostgreSQL type (format_type() output) to the closest MySQL column type.
+pub fn mysql_column_type(pg_type: &str) -> String {
+    let (base, modifier) = match pg_type.find('(') {
+        Some(open) => {
+            let close = pg_type.rfind(')').unwrap_or(pg_type.len());
+            (
+                format!("{}{}", &pg_type[..open], &pg_type[close + 1..]),
+                Some(&pg_type[open + 1..close]),
+            )
+        }
+        None => (pg_type.to_string(), None),
+    };
+
+    if base.ends_with("[]") {
+        return "json".to_string();
+    }
+    match (base.trim(), modifier) {
+        ("smallint", _) => "smallint".to_string(),
+        ("integer", _) => "int".to_string(),
+        ("bigint", _) => "bigint".to_string(),
+        ("boolean", _) => "tinyint(1)".to_string(),
+        ("real", _) => "float".to_string(),
+        ("double precision", _) => "double".to_string(),
+        ("numeric", Some(m)) => format!("decimal({})", m),
+        ("numeric", None) => "decimal(65,30)".to_string(),
+        ("character varying", Some(m)) => format!("varchar({})", m),
+        ("character", Some(m)) => format!("char({})", m),
+        ("character varying" | "character" | "text", _) => "text".to_string(),
+        ("timestamp without time zone", _) => "datetime".to_string(),
+        ("timestamp with time zone", _) => "timestamp".to_string(),
+        ("time without time zone" | "time with time zone", _) => "time".to_string(),
+        ("date", _) => "date".to_string(),
+        ("bytea", _) => "longblob".to_string(),
+        ("json" | "jsonb", _) => "json".to_string(),
+        ("uuid", _) => "char(36)".to_string(),
+        ("bit", Some(m)) => format!("bit({})", m),
+        (other, Some(m)) => format!("{}({})", other, m),
+        (other, None) => other.to_string(),
+    }
+}
+
+/// Renders a PostgreSQL column default (pg_get_expr() output) the way MySQL prints it.
+pub fn mysql_default(expr: &str) -> String {
+    let lower = expr.to_ascii_lowercase();
+    if lower == "now()" || lower.starts_with("current_timestamp") || lower == "localtimestamp" {
+        return "CURRENT_TIMESTAMP".to_string();
+    }
+    // 'abc'::character varying -> 'abc'
+    if expr.starts_with('\'') {
+        if let Some(end) = expr.rfind("'::") {
+            return expr[..=end].to_string();
+        }
+    }
+    match lower.as_str() {
+        "true" => return "'1'".to_string(),
+        "false" => return "'0'".to_string(),
+        _ => {}
+    }
+    // Numeric defaults of non-integer columns come back as e.g. (0)::numeric.
+    let value = expr.split("::").next().unwrap_or(expr);
+    value
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .unwrap_or(value)
+        .to_string()
+}
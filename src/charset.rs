@@ -0,0 +1,461 @@
+// The character sets of a connection, as MySQL's character_set_client, character_set_connection,
+// character_set_results and collation_connection: those of the collation the client names in its
+// handshake response to begin with, and then whatever SET NAMES, SET CHARACTER SET or a SET of
+// the variables makes them:
+//
+//   SET NAMES latin1                           client, connection and results latin1
+//   SET NAMES utf8mb4 COLLATE utf8mb4_bin      and collation_connection utf8mb4_bin
+//   SET CHARACTER SET latin1                   client and results latin1, connection utf8mb4
+//   SET character_set_results = NULL           results sent as they are
+//
+// The proxy talks UTF-8 to PostgreSQL whatever the client does, as its driver reads text as
+// UTF-8, so the session's client_encoding stays UTF8 and the proxy converts: a client sending
+// latin1 has its statements and their parameters read as latin1, and one reading latin1 gets
+// text values in it, with `?` for characters latin1 doesn't have, as MySQL sends them. utf8mb4,
+// utf8mb3 (utf8), ascii and binary need no converting. Other character sets are refused with
+// error 1235 rather than have text mangled; column names and messages are always UTF-8.
+//
+// Column definitions describe text columns with the default collation of character_set_results,
+// or of utf8mb4 with results sent as they are, and other columns with `binary`, as MySQL does.
+// SHOW VARIABLES and SELECT @@character_set_client and the like report the variables.
+
+use std::borrow::Cow;
+
+use mysql_common::value::Value;
+use opensrv_mysql::{Column, ColumnFlags, ErrorKind};
+
+use crate::error::MysqlError;
+use crate::translator::{self, literals, Token};
+
+/// A character set the proxy can serve a client in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8mb4,
+    Utf8mb3,
+    Latin1,
+    Ascii,
+    Binary,
+}
+
+/// A collation of one of the character sets, by the id the protocol gives it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Collation {
+    pub id: u16,
+    pub name: &'static str,
+    pub charset: Charset,
+}
+
+// The collations of each character set, its default first.
+const COLLATIONS: &[Collation] = &[
+    collation(255, "utf8mb4_0900_ai_ci", Charset::Utf8mb4),
+    collation(45, "utf8mb4_general_ci", Charset::Utf8mb4),
+    collation(46, "utf8mb4_bin", Charset::Utf8mb4),
+    collation(224, "utf8mb4_unicode_ci", Charset::Utf8mb4),
+    collation(33, "utf8mb3_general_ci", Charset::Utf8mb3),
+    collation(83, "utf8mb3_bin", Charset::Utf8mb3),
+    collation(192, "utf8mb3_unicode_ci", Charset::Utf8mb3),
+    collation(8, "latin1_swedish_ci", Charset::Latin1),
+    collation(47, "latin1_bin", Charset::Latin1),
+    collation(48, "latin1_general_ci", Charset::Latin1),
+    collation(11, "ascii_general_ci", Charset::Ascii),
+    collation(65, "ascii_bin", Charset::Ascii),
+    collation(63, "binary", Charset::Binary),
+];
+
+const fn collation(id: u16, name: &'static str, charset: Charset) -> Collation {
+    Collation { id, name, charset }
+}
+
+// MySQL's other character sets, which it would take but the proxy can't convert.
+const UNSUPPORTED: &[&str] = &[
+    "armscii8", "big5", "cp1250", "cp1251", "cp1256", "cp1257", "cp850", "cp852", "cp866", "cp932",
+    "dec8", "eucjpms", "euckr", "gb18030", "gb2312", "gbk", "geostd8", "greek", "hebrew", "hp8",
+    "keybcs2", "koi8r", "koi8u", "latin2", "latin5", "latin7", "macce", "macroman", "sjis", "swe7",
+    "tis620", "ucs2", "ujis", "utf16", "utf16le", "utf32",
+];
+
+// What Windows-1252, MySQL's latin1, has at 0x80 to 0x9f. The five bytes it leaves undefined
+// are the control characters of the same code, as MySQL has them.
+const LATIN1_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+impl Charset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8mb4 => "utf8mb4",
+            Charset::Utf8mb3 => "utf8mb3",
+            Charset::Latin1 => "latin1",
+            Charset::Ascii => "ascii",
+            Charset::Binary => "binary",
+        }
+    }
+
+    /// The character set called `name`, `utf8` being utf8mb3 as in MySQL.
+    pub fn named(name: &str) -> Result<Charset, MysqlError> {
+        let name = name.to_ascii_lowercase();
+        let charset = [
+            Charset::Utf8mb4,
+            Charset::Utf8mb3,
+            Charset::Latin1,
+            Charset::Ascii,
+            Charset::Binary,
+        ]
+        .into_iter()
+        .find(|charset| charset.name() == name || (name == "utf8" && *charset == Charset::Utf8mb3));
+        match charset {
+            Some(charset) => Ok(charset),
+            None if UNSUPPORTED.contains(&name.as_str()) => Err(MysqlError::new(
+                ErrorKind::ER_NOT_SUPPORTED_YET,
+                format!(
+                    "This version of MySQL doesn't yet support 'character set {}'",
+                    name
+                ),
+            )),
+            None => Err(MysqlError::new(
+                ErrorKind::ER_UNKNOWN_CHARACTER_SET,
+                format!("Unknown character set: '{}'", name),
+            )),
+        }
+    }
+
+    pub fn default_collation(self) -> &'static Collation {
+        COLLATIONS
+            .iter()
+            .find(|collation| collation.charset == self)
+            .expect("every character set has a collation")
+    }
+
+    /// Whether text in it has to be converted from and to UTF-8.
+    pub fn converts(self) -> bool {
+        self == Charset::Latin1
+    }
+
+    /// `bytes` sent in this character set, as UTF-8.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, [u8]> {
+        if !self.converts() || bytes.is_ascii() {
+            return Cow::Borrowed(bytes);
+        }
+        let text: String = bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9f => LATIN1_HIGH[usize::from(b - 0x80)],
+                _ => char::from(b),
+            })
+            .collect();
+        Cow::Owned(text.into_bytes())
+    }
+
+    /// `text`, UTF-8, in this character set.
+    pub fn encode(self, text: &[u8]) -> Cow<'_, [u8]> {
+        if !self.converts() || text.is_ascii() {
+            return Cow::Borrowed(text);
+        }
+        let bytes = String::from_utf8_lossy(text)
+            .chars()
+            .map(|c| match u8::try_from(u32::from(c)) {
+                Ok(b) if !(0x80..=0x9f).contains(&b) => b,
+                _ => LATIN1_HIGH
+                    .iter()
+                    .position(|&high| high == c)
+                    .map_or(b'?', |i| 0x80 + i as u8),
+            })
+            .collect();
+        Cow::Owned(bytes)
+    }
+}
+
+impl Collation {
+    /// The collation with protocol id `id`.
+    pub fn with_id(id: u16) -> Option<&'static Collation> {
+        COLLATIONS.iter().find(|collation| collation.id == id)
+    }
+
+    /// The collation called `name`, `utf8_` standing for `utf8mb3_` as in MySQL.
+    pub fn named(name: &str) -> Result<&'static Collation, MysqlError> {
+        let name = name.to_ascii_lowercase();
+        let name = match name.strip_prefix("utf8_") {
+            Some(rest) => format!("utf8mb3_{}", rest),
+            None => name,
+        };
+        COLLATIONS
+            .iter()
+            .find(|collation| collation.name == name)
+            .ok_or_else(|| {
+                MysqlError::new(
+                    ErrorKind::ER_UNKNOWN_COLLATION,
+                    format!("Unknown collation: '{}'", name),
+                )
+            })
+    }
+}
+
+/// A connection's character sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Charsets {
+    pub client: Charset,
+    pub connection: &'static Collation,
+    // None after SET character_set_results = NULL: results are sent as they are.
+    pub results: Option<Charset>,
+}
+
+impl Default for Charsets {
+    fn default() -> Charsets {
+        Charsets::named(Charset::Utf8mb4.default_collation())
+    }
+}
+
+impl Charsets {
+    // All of them `collation`'s, as after SET NAMES.
+    fn named(collation: &'static Collation) -> Charsets {
+        Charsets {
+            client: collation.charset,
+            connection: collation,
+            results: Some(collation.charset),
+        }
+    }
+
+    /// Those of the collation a client names in its handshake response; MySQL's defaults for
+    /// one it doesn't know.
+    pub fn handshake(id: u8) -> Charsets {
+        Collation::with_id(id.into()).map_or_else(Charsets::default, Charsets::named)
+    }
+
+    /// The collation column definitions describe text columns with.
+    pub fn results_collation(&self) -> u16 {
+        self.results
+            .unwrap_or(Charset::Utf8mb4)
+            .default_collation()
+            .id
+    }
+
+    /// `row`, read from PostgreSQL with `columns`, with its text in the character set of the
+    /// results.
+    pub fn encode_row(&self, columns: &[Column], row: Vec<Value>) -> Vec<Value> {
+        let Some(results) = self.results.filter(|results| results.converts()) else {
+            return row;
+        };
+        row.into_iter()
+            .zip(columns)
+            .map(|(value, column)| match value {
+                Value::Bytes(bytes) if !column.colflags.contains(ColumnFlags::BINARY_FLAG) => {
+                    Value::Bytes(results.encode(&bytes).into_owned())
+                }
+                value => value,
+            })
+            .collect()
+    }
+
+    /// The character set variables, as SHOW VARIABLES lists them. The database and server are
+    /// utf8mb4, as PostgreSQL's UTF8 is.
+    pub fn variables(&self) -> Vec<(&'static str, String)> {
+        let utf8mb4 = Charset::Utf8mb4.default_collation().name.to_string();
+        vec![
+            ("character_set_client", self.client.name().to_string()),
+            (
+                "character_set_connection",
+                self.connection.charset.name().to_string(),
+            ),
+            ("character_set_database", "utf8mb4".to_string()),
+            ("character_set_filesystem", "binary".to_string()),
+            (
+                "character_set_results",
+                self.results.map_or("", Charset::name).to_string(),
+            ),
+            ("character_set_server", "utf8mb4".to_string()),
+            ("character_set_system", "utf8mb3".to_string()),
+            ("collation_connection", self.connection.name.to_string()),
+            ("collation_database", utf8mb4.clone()),
+            ("collation_server", utf8mb4),
+        ]
+    }
+
+    /// The value of the character set variable `name`, lower case, as SELECT @@name reads it;
+    /// `None` if it isn't one. character_set_results is NULL after it was set to NULL.
+    pub fn variable(&self, name: &str) -> Option<Option<String>> {
+        if name == "character_set_results" && self.results.is_none() {
+            return Some(None);
+        }
+        self.variables()
+            .into_iter()
+            .find(|(variable, _)| *variable == name)
+            .map(|(_, value)| Some(value))
+    }
+}
+
+/// The character sets after `sql`, a statement as the client sent it, if it is a SET of them:
+/// SET NAMES, SET CHARACTER SET or CHARSET, or a SET of the session's character_set_client,
+/// character_set_connection, character_set_results and collation_connection alone. `current`
+/// are those before it.
+pub fn set(sql: &str, current: Charsets) -> Option<Result<Charsets, MysqlError>> {
+    let tokens = translator::significant_tokens(sql)?;
+    let (set, rest) = tokens.split_first()?;
+    if !set.is_word("SET") {
+        return None;
+    }
+    match rest {
+        [names, rest @ ..] if names.is_word("NAMES") => Some(names_of(rest)),
+        [character, set, rest @ ..] if character.is_word("CHARACTER") && set.is_word("SET") => {
+            Some(character_set(rest))
+        }
+        [charset, rest @ ..] if charset.is_word("CHARSET") => Some(character_set(rest)),
+        _ => {
+            let assignments = rest
+                .split(|token| *token == Token::Comma)
+                .map(assignment)
+                .collect::<Option<Vec<_>>>()?;
+            let mut charsets = current;
+            for (variable, value) in assignments {
+                if let Err(error) = assign(&mut charsets, variable, value) {
+                    return Some(Err(error));
+                }
+            }
+            Some(Ok(charsets))
+        }
+    }
+}
+
+// SET NAMES {charset | DEFAULT} [COLLATE collation]
+fn names_of(tokens: &[Token]) -> Result<Charsets, MysqlError> {
+    let (charset, rest) = tokens.split_first().ok_or_else(syntax)?;
+    let charset = match name(charset).ok_or_else(syntax)? {
+        Some(name) => Charset::named(&name)?,
+        None => Charset::Utf8mb4,
+    };
+    let collation = match rest {
+        [] => charset.default_collation(),
+        [collate, name] if collate.is_word("COLLATE") => {
+            let name = self::name(name).flatten().ok_or_else(syntax)?;
+            let collation = Collation::named(&name)?;
+            if collation.charset != charset {
+                return Err(MysqlError::new(
+                    ErrorKind::ER_COLLATION_CHARSET_MISMATCH,
+                    format!(
+                        "COLLATION '{}' is not valid for CHARACTER SET '{}'",
+                        collation.name,
+                        charset.name()
+                    ),
+                ));
+            }
+            collation
+        }
+        _ => return Err(syntax()),
+    };
+    Ok(Charsets::named(collation))
+}
+
+// SET CHARACTER SET {charset | DEFAULT}: the client's and the results', the connection's being
+// the database's.
+fn character_set(tokens: &[Token]) -> Result<Charsets, MysqlError> {
+    let [charset] = tokens else {
+        return Err(syntax());
+    };
+    let charset = match name(charset).ok_or_else(syntax)? {
+        Some(name) => Charset::named(&name)?,
+        None => Charset::Utf8mb4,
+    };
+    Ok(Charsets {
+        client: charset,
+        connection: Charset::Utf8mb4.default_collation(),
+        results: Some(charset),
+    })
+}
+
+// `[SESSION] name = value`, `@@[session.]name = value` and the like, of the variables above.
+fn assignment(tokens: &[Token]) -> Option<(String, &Token)> {
+    let tokens = match tokens {
+        [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => rest,
+        tokens => tokens,
+    };
+    let (name, value) = match tokens {
+        [name, equals, value] if equals.is_operator("=") || equals.is_operator(":=") => {
+            (name, value)
+        }
+        _ => return None,
+    };
+    let name = match name {
+        Token::Word(word) => word.to_ascii_lowercase(),
+        Token::Variable(variable) => {
+            let variable = variable.to_ascii_lowercase();
+            ["@@session.", "@@local.", "@@"]
+                .iter()
+                .find_map(|prefix| variable.strip_prefix(prefix))?
+                .to_string()
+        }
+        _ => return None,
+    };
+    [
+        "character_set_client",
+        "character_set_connection",
+        "character_set_results",
+        "collation_connection",
+    ]
+    .contains(&name.as_str())
+    .then_some((name, value))
+}
+
+fn assign(charsets: &mut Charsets, variable: String, token: &Token) -> Result<(), MysqlError> {
+    let wrong = || {
+        MysqlError::new(
+            ErrorKind::ER_WRONG_VALUE_FOR_VAR,
+            format!(
+                "Variable '{}' can't be set to the value of '{}'",
+                variable, token
+            ),
+        )
+    };
+    if token.is_word("NULL") {
+        return match variable.as_str() {
+            "character_set_results" => {
+                charsets.results = None;
+                Ok(())
+            }
+            _ => Err(wrong()),
+        };
+    }
+    let value = name(token).ok_or_else(wrong)?;
+    match variable.as_str() {
+        "collation_connection" => {
+            charsets.connection = match value {
+                Some(name) => Collation::named(&name)?,
+                None => Charset::Utf8mb4.default_collation(),
+            }
+        }
+        _ => {
+            let charset = match value {
+                Some(name) => Charset::named(&name).map_err(|_| wrong())?,
+                None => Charset::Utf8mb4,
+            };
+            match variable.as_str() {
+                "character_set_client" => charsets.client = charset,
+                "character_set_results" => charsets.results = Some(charset),
+                _ => charsets.connection = charset.default_collation(),
+            }
+        }
+    }
+    Ok(())
+}
+
+// A character set or collation as named in a SET: a word, a string or a quoted identifier, or
+// DEFAULT (`Some(None)`).
+fn name(token: &Token) -> Option<Option<String>> {
+    match token {
+        token if token.is_word("DEFAULT") => Some(None),
+        Token::Word(word) => Some(Some(word.clone())),
+        Token::String(raw) => Some(Some(literals::mysql_string_value(raw))),
+        Token::QuotedIdent(_) | Token::DoubleQuoted(_) => {
+            literals::identifier_name(token).map(Some)
+        }
+        _ => None,
+    }
+}
+
+fn syntax() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_PARSE_ERROR,
+        "You have an error in your SQL syntax; check the character set or collation named",
+    )
+}
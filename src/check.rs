@@ -0,0 +1,236 @@
+// `postmyrustache check`: reports how the statements of a dump or migration file would fare
+// through the proxy, without a PostgreSQL server.
+//
+//   postmyrustache check schema.sql
+//
+// The file is read the way import reads a dump, a statement at a time honouring DELIMITER lines
+// and version comments. Every statement is translated and sorted into one of three groups:
+//
+//   - clean: translated, with nothing known to go wrong
+//   - with warnings: translated, but not quite to what MySQL would do, like an index on a column
+//     prefix that indexes the whole column
+//   - unsupported: the translator can't parse or convert it, or the translation still has
+//     MySQL-only syntax or types that PostgreSQL will reject
+//
+// The unsupported constructs are totalled by the statements using them, which shows what to
+// work on first. Only what the text of a statement shows is checked: an unknown function or a
+// table the file doesn't create shows when the statement runs. The exit status is 1 when any
+// statement is unsupported.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::failures::{self, Category, Failure};
+use crate::import::{self, Statements};
+use crate::translator::dates::{self, Coerced};
+use crate::translator::mysql_only::{self, LeftoverKind};
+use crate::translator::{
+    self, split_args, statement_starts_with, CheckConstraints, Node, Token, TranslationOptions,
+    Translator,
+};
+
+struct Finding {
+    line: usize,
+    sql: String,
+    // What the statement uses that PostgreSQL won't take, and where its translation differs
+    // from what MySQL would do. An unsupported statement can have warnings too.
+    unsupported: Vec<Failure>,
+    warnings: Vec<String>,
+}
+
+/// Checks the statements of `path`. Returns whether every one of them is supported.
+pub fn run(path: &str, translator: &Translator) -> Result<bool, Box<dyn Error>> {
+    let file = File::open(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let mut statements = Statements::new(BufReader::new(file));
+
+    let mut findings = Vec::new();
+    while let Some(statement) = statements
+        .next()
+        .map_err(|e| format!("can't read {}: {}", path, e))?
+    {
+        let sql = import::unwrap_version_comments(&statement.sql);
+        if translator::significant_tokens(&sql).is_some_and(|tokens| tokens.is_empty()) {
+            continue;
+        }
+        let (unsupported, warnings) = check(&sql, translator);
+        findings.push(Finding {
+            line: statement.line,
+            sql,
+            unsupported,
+            warnings,
+        });
+    }
+
+    let mut warned = 0;
+    let mut unsupported = 0;
+    let mut constructs: BTreeMap<Failure, usize> = BTreeMap::new();
+    for finding in &findings {
+        if !finding.unsupported.is_empty() {
+            unsupported += 1;
+        } else if !finding.warnings.is_empty() {
+            warned += 1;
+        } else {
+            continue;
+        }
+        for failure in &finding.unsupported {
+            println!(
+                "line {}: unsupported: {} ({})",
+                finding.line, failure.construct, failure.category
+            );
+            *constructs.entry(failure.clone()).or_default() += 1;
+        }
+        for warning in &finding.warnings {
+            println!("line {}: warning: {}", finding.line, warning);
+        }
+        println!("    {}", excerpt(&finding.sql));
+    }
+
+    println!(
+        "{} statements: {} clean, {} with warnings, {} unsupported",
+        findings.len(),
+        findings.len() - warned - unsupported,
+        warned,
+        unsupported
+    );
+    if constructs.is_empty() {
+        return Ok(true);
+    }
+    let mut constructs: Vec<(Failure, usize)> = constructs.into_iter().collect();
+    constructs.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    println!("Unsupported constructs, by statements using them:");
+    for (failure, count) in constructs {
+        println!(
+            "  {:>5}  {} ({})",
+            count, failure.construct, failure.category
+        );
+    }
+    Ok(false)
+}
+
+// The unsupported constructs `sql` uses, and the warnings about its translation.
+fn check(sql: &str, translator: &Translator) -> (Vec<Failure>, Vec<String>) {
+    let nodes = match translator.parse(sql) {
+        Ok(nodes) => nodes,
+        Err(e) => return (vec![failures::translate_error(&e)], Vec::new()),
+    };
+    let warnings = warnings(&nodes, translator.options());
+    match translator.rewrite(nodes) {
+        Ok(translated) => (mysql_only(&translated), warnings),
+        Err(e) => (vec![failures::translate_error(&e)], warnings),
+    }
+}
+
+// Where the translation of the MySQL statement `nodes` differs from what MySQL would do.
+fn warnings(nodes: &[Node], options: &TranslationOptions) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let tokens: Vec<&Token> = tokens(nodes).filter(|t| !t.is_trivia()).collect();
+
+    for token in &tokens {
+        let value = match token {
+            Token::String(raw) if raw.starts_with('\'') => raw,
+            Token::DoubleQuoted(raw) if !options.ansi_quotes => raw,
+            _ => continue,
+        };
+        let text = translator::literals::mysql_string_value(value);
+        match dates::coerce(&text, options.dates) {
+            // The check has no catalog to tell whether the column is a date.
+            Some(Coerced::Null) => warnings.push(format!(
+                "date '{}' is stored as NULL in a date column",
+                text
+            )),
+            Some(Coerced::Clamped(date)) => warnings.push(format!(
+                "date '{}' is stored as '{}' in a date column",
+                text, date
+            )),
+            None => {}
+        }
+    }
+
+    let table_ddl = statement_starts_with(nodes, &["CREATE", "TABLE"])
+        || statement_starts_with(nodes, &["CREATE", "TEMPORARY", "TABLE"])
+        || statement_starts_with(nodes, &["ALTER", "TABLE"]);
+    if !table_ddl {
+        return warnings;
+    }
+    if options.check_constraints == CheckConstraints::Strip
+        && tokens.iter().any(|t| t.is_word("CHECK"))
+    {
+        warnings.push("CHECK constraint dropped, as CHECK_CONSTRAINTS is strip".to_string());
+    }
+    if !options.fulltext_indexes && tokens.iter().any(|t| t.is_word("FULLTEXT")) {
+        warnings.push("FULLTEXT index left out, as FULLTEXT_INDEXES is off".to_string());
+    }
+    if let Some(Node::Group(body)) = nodes.iter().find(|n| matches!(n, Node::Group(_))) {
+        for item in split_args(body) {
+            let first = item.iter().find(|n| !n.is_trivia());
+            let is_index = ["KEY", "INDEX", "UNIQUE", "PRIMARY", "CONSTRAINT"]
+                .iter()
+                .any(|word| matches!(first, Some(Node::Token(t)) if t.is_word(word)));
+            let key_parts = item.iter().find_map(|n| match n {
+                Node::Group(parts) => Some(parts),
+                Node::Token(_) => None,
+            });
+            if let (true, Some(key_parts)) = (is_index, key_parts) {
+                for column in split_args(key_parts)
+                    .into_iter()
+                    .filter_map(prefixed_column)
+                {
+                    warnings.push(format!(
+                        "prefix length of {} ignored, the whole column is indexed",
+                        column
+                    ));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+// The column of a `column(length)` key part.
+fn prefixed_column(part: &[Node]) -> Option<String> {
+    match part.iter().filter(|n| !n.is_trivia()).collect::<Vec<_>>()[..] {
+        [Node::Token(name), Node::Group(length)]
+            if matches!(
+                length.iter().filter(|n| !n.is_trivia()).collect::<Vec<_>>()[..],
+                [Node::Token(Token::Number(_))]
+            ) =>
+        {
+            Some(name.to_string())
+        }
+        _ => None,
+    }
+}
+
+// The MySQL-only constructs left in a translation, which PostgreSQL will reject.
+fn mysql_only(translated: &str) -> Vec<Failure> {
+    mysql_only::find(translated)
+        .into_iter()
+        .map(|leftover| {
+            let category = match leftover.kind {
+                LeftoverKind::Syntax => Category::Syntax,
+                LeftoverKind::Type => Category::Type,
+            };
+            Failure::new(category, leftover.construct)
+        })
+        .collect()
+}
+
+// Every token of `nodes`, those inside groups included, in order.
+fn tokens(nodes: &[Node]) -> Box<dyn Iterator<Item = &Token> + '_> {
+    Box::new(nodes.iter().flat_map(|node| match node {
+        Node::Token(token) => Box::new(std::iter::once(token)),
+        Node::Group(inner) => tokens(inner),
+    }))
+}
+
+// The start of a statement, on one line.
+fn excerpt(sql: &str) -> String {
+    let sql = sql.trim();
+    let mut excerpt: String = sql.chars().take(100).collect();
+    if excerpt.len() < sql.len() {
+        excerpt.push_str("...");
+    }
+    excerpt.replace('\n', " ")
+}
@@ -0,0 +1,575 @@
+// TLS for the MySQL clients' connections, and the users who have to log in over it, or with a
+// certificate in place of their password:
+//
+//   LISTEN_TLS_CERT  a PEM file of the proxy's certificate, and the CAs between it and a root
+//   LISTEN_TLS_KEY   a PEM file of its private key
+//   LISTEN_TLS_CA    a PEM file of the CAs whose certificates clients may log in with
+//   USER_REQUIRE     what some users' connections need, as MySQL's REQUIRE clause has it
+//
+//   USER_REQUIRE = "app: SSL; deploy: X509; alice: SUBJECT '/O=Acme/CN=alice' AND ISSUER '/O=Acme/CN=Acme CA'"
+//
+// With a certificate, the greeting offers CLIENT_SSL, and a client that asks for TLS with an
+// SSLRequest, as `mysql --ssl-mode=REQUIRED` does, has its connection encrypted from there on,
+// its handshake response included. TLS is under everything else on the connection, compression
+// and the protocol trace among them, which see the packets decrypted. A client that doesn't ask
+// goes on unencrypted, unless its user is in USER_REQUIRE.
+//
+// With LISTEN_TLS_CA, clients may send a certificate signed by one of its CAs; those that send
+// another are turned away in the TLS handshake. A user of USER_REQUIRE needs:
+//
+//   SSL      an encrypted connection, and their password as anyone else
+//   X509     a certificate, and their password
+//   SUBJECT  a certificate with this subject, and ISSUER one from this issuer, as OpenSSL's
+//            one-line form writes them, which MySQL's REQUIRE uses; a '/' or '\' in a value is
+//            escaped with a '\' (written '\\' in the quotes), so a value can't pass for
+//            several attributes
+//
+// A user with SUBJECT whose certificate matches is let in without their password being checked,
+// the certificate naming them being the proof. X509 is met by any certificate of LISTEN_TLS_CA's,
+// another user's among them, and ISSUER alone by any the issuer gave, so with those the password
+// is checked as well. A user whose connection doesn't meet their requirement is refused with
+// error 1045, whatever the password.
+// SHOW GRANTS gives the requirement on the user's global grant, as MySQL 5.7 does.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+
+use rustls::crypto;
+use rustls::server::{ServerConnection, WebPkiClientVerifier};
+use rustls::{RootCertStore, ServerConfig};
+use tokio::io::{AsyncRead, AsyncWrite, Join, ReadBuf};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::{Accept, TlsAcceptor};
+
+use crate::der;
+use crate::protocol;
+use crate::tls;
+use crate::translator::{self, literals, Token};
+
+/// TLS for the clients (LISTEN_TLS_CERT, LISTEN_TLS_KEY, LISTEN_TLS_CA, USER_REQUIRE).
+#[derive(Debug, Clone)]
+pub struct ClientTlsConfig {
+    pub cert_file: String,
+    pub key_file: String,
+    pub ca_file: Option<String>,
+    pub requirements: Vec<(String, Requirement)>,
+}
+
+/// What a user's connections need, from USER_REQUIRE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    Ssl,
+    X509,
+    // SUBJECT and ISSUER, one or both.
+    Certificate {
+        subject: Option<String>,
+        issuer: Option<String>,
+    },
+}
+
+impl Requirement {
+    /// Whether it takes a certificate.
+    pub fn needs_certificate(&self) -> bool {
+        *self != Requirement::Ssl
+    }
+
+    /// Whether a certificate that meets it lets the user in without their password: one that
+    /// names the certificate's subject does, as a bare X509, which any certificate of
+    /// LISTEN_TLS_CA's meets, or an ISSUER alone, which any of the issuer's does, doesn't.
+    pub fn replaces_password(&self) -> bool {
+        matches!(
+            self,
+            Requirement::Certificate {
+                subject: Some(_),
+                ..
+            }
+        )
+    }
+
+    /// Whether a connection with `tls`, its TLS if it has any, meets it.
+    pub fn met_by(&self, tls: Option<&TlsSession>) -> bool {
+        let Some(tls) = tls else {
+            return false;
+        };
+        match self {
+            Requirement::Ssl => true,
+            Requirement::X509 => tls.certificate.is_some(),
+            Requirement::Certificate { subject, issuer } => {
+                tls.certificate.as_ref().is_some_and(|certificate| {
+                    subject.as_ref().is_none_or(|s| *s == certificate.subject)
+                        && issuer.as_ref().is_none_or(|i| *i == certificate.issuer)
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Requirement, ()> {
+        let tokens = translator::significant_tokens(s).ok_or(())?;
+        match tokens.as_slice() {
+            [ssl] if ssl.is_word("SSL") => return Ok(Requirement::Ssl),
+            [x509] if x509.is_word("X509") => return Ok(Requirement::X509),
+            _ => {}
+        }
+        let (mut subject, mut issuer) = (None, None);
+        let mut tokens = tokens.as_slice();
+        while let [name, Token::String(raw), rest @ ..] = tokens {
+            let option = if name.is_word("SUBJECT") {
+                &mut subject
+            } else if name.is_word("ISSUER") {
+                &mut issuer
+            } else {
+                return Err(());
+            };
+            if option.replace(literals::mysql_string_value(raw)).is_some() {
+                return Err(());
+            }
+            tokens = match rest {
+                [and, rest @ ..] if and.is_word("AND") && !rest.is_empty() => rest,
+                rest => rest,
+            };
+        }
+        if !tokens.is_empty() || (subject.is_none() && issuer.is_none()) {
+            return Err(());
+        }
+        Ok(Requirement::Certificate { subject, issuer })
+    }
+}
+
+// As SHOW GRANTS writes it after REQUIRE.
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Requirement::Ssl => f.write_str("SSL"),
+            Requirement::X509 => f.write_str("X509"),
+            Requirement::Certificate { subject, issuer } => {
+                let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+                let options: Vec<String> = [("ISSUER", issuer), ("SUBJECT", subject)]
+                    .into_iter()
+                    .filter_map(|(name, value)| {
+                        value.as_ref().map(|v| format!("{} {}", name, quote(v)))
+                    })
+                    .collect();
+                f.write_str(&options.join(" AND "))
+            }
+        }
+    }
+}
+
+/// A client's TLS, once its handshake is through.
+#[derive(Debug, Clone, Default)]
+pub struct TlsSession {
+    // The client's certificate, if it sent one.
+    pub certificate: Option<Certificate>,
+}
+
+/// The names of a client's certificate, as OpenSSL's one-line form writes them:
+/// `/C=SE/O=Acme/CN=alice`.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+}
+
+/// The acceptor of the clients' TLS, with the certificates `config` names. Fails if they can't be
+/// read.
+pub fn acceptor(config: &ClientTlsConfig) -> io::Result<TlsAcceptor> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?;
+    let builder = match &config.ca_file {
+        Some(file) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in tls::read_certificates(file)? {
+                roots.add(certificate).map_err(|e| tls::invalid(file, e))?;
+            }
+            // Those who log in with a password needn't have one.
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| tls::invalid(file, e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(
+            tls::read_certificates(&config.cert_file)?,
+            tls::read_private_key(&config.key_file)?,
+        )
+        .map_err(|e| tls::invalid(&config.key_file, e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// `reader` and `writer`, a client's connection, starting TLS with `acceptor` should the client
+/// ask for it. `session` is set once the handshake is through.
+pub fn upgradable<R, W>(
+    reader: R,
+    writer: W,
+    acceptor: TlsAcceptor,
+    session: Arc<OnceLock<TlsSession>>,
+) -> (TlsReader<R, W>, TlsWriter<R, W>)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::First {
+            reader,
+            writer,
+            packet: Vec::new(),
+        }),
+        acceptor,
+        session,
+    });
+    (TlsReader(Arc::clone(&shared)), TlsWriter(shared))
+}
+
+enum State<R, W> {
+    // Until the client's first packet has been read whole, nothing past it is, the TLS handshake
+    // following an SSLRequest straight away.
+    First {
+        reader: R,
+        writer: W,
+        packet: Vec<u8>,
+    },
+    // A client that didn't ask for TLS.
+    Plain {
+        reader: R,
+        writer: W,
+    },
+    Accepting(Accept<Join<R, W>>),
+    Tls(TlsStream<Join<R, W>>),
+    // After the handshake failed.
+    Closed,
+}
+
+// What the two halves share: the TLS handshake needs both of the connection's, and the stream
+// it makes is read and written as one.
+struct Shared<R, W> {
+    state: Mutex<State<R, W>>,
+    acceptor: TlsAcceptor,
+    session: Arc<OnceLock<TlsSession>>,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> Shared<R, W> {
+    // Drives the TLS handshake, if one has started, to its end.
+    fn poll_accepted(&self, state: &mut State<R, W>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let State::Accepting(accept) = state {
+            match ready!(Pin::new(accept).poll(cx)) {
+                Ok(stream) => {
+                    let _ = self.session.set(session(stream.get_ref().1));
+                    *state = State::Tls(stream);
+                }
+                Err(e) => {
+                    *state = State::Closed;
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    // Runs `f` on what the connection is written to.
+    fn poll_write_with<T>(
+        &self,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(Pin<&mut (dyn AsyncWrite + Unpin)>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let mut state = self.state.lock().unwrap();
+        ready!(self.poll_accepted(&mut state, cx))?;
+        match &mut *state {
+            State::First { writer, .. } | State::Plain { writer, .. } => f(Pin::new(writer), cx),
+            State::Tls(stream) => f(Pin::new(stream), cx),
+            State::Accepting(_) | State::Closed => {
+                Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+            }
+        }
+    }
+}
+
+/// A client's read half, from `upgradable`.
+pub struct TlsReader<R, W>(Arc<Shared<R, W>>);
+
+/// A client's write half, from `upgradable`.
+pub struct TlsWriter<R, W>(Arc<Shared<R, W>>);
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> AsyncRead for TlsReader<R, W> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let shared = &*self.0;
+        let mut state = shared.state.lock().unwrap();
+        ready!(shared.poll_accepted(&mut state, cx))?;
+        match &mut *state {
+            State::First { reader, packet, .. } => {
+                let wanted = match packet.len() {
+                    0..=3 => 4 - packet.len(),
+                    read => 4 + protocol::payload_length(packet) - read,
+                };
+                let mut chunk = vec![0; wanted.min(buf.remaining())];
+                let mut read = ReadBuf::new(&mut chunk);
+                ready!(Pin::new(reader).poll_read(cx, &mut read))?;
+                buf.put_slice(read.filled());
+                packet.extend_from_slice(read.filled());
+                let whole =
+                    packet.len() >= 4 && packet.len() == 4 + protocol::payload_length(packet);
+                if whole {
+                    let State::First {
+                        reader,
+                        writer,
+                        packet,
+                    } = std::mem::replace(&mut *state, State::Closed)
+                    else {
+                        unreachable!()
+                    };
+                    *state = match protocol::is_ssl_request(&packet) {
+                        true => State::Accepting(
+                            shared.acceptor.accept(tokio::io::join(reader, writer)),
+                        ),
+                        false => State::Plain { reader, writer },
+                    };
+                }
+                Poll::Ready(Ok(()))
+            }
+            State::Plain { reader, .. } => Pin::new(reader).poll_read(cx, buf),
+            State::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            State::Accepting(_) | State::Closed => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> AsyncWrite for TlsWriter<R, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0
+            .poll_write_with(cx, |writer, cx| writer.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.0
+            .poll_write_with(cx, |writer, cx| writer.poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.0
+            .poll_write_with(cx, |writer, cx| writer.poll_shutdown(cx))
+    }
+}
+
+// What the client's side of `connection` showed of itself.
+fn session(connection: &ServerConnection) -> TlsSession {
+    let certificate = connection
+        .peer_certificates()
+        .and_then(<[_]>::first)
+        .and_then(|certificate| names(certificate));
+    TlsSession { certificate }
+}
+
+// The subject and issuer of `certificate`, a DER X.509 certificate.
+fn names(certificate: &[u8]) -> Option<Certificate> {
+    // Certificate ::= SEQUENCE { tbsCertificate, ... }, and tbsCertificate ::= SEQUENCE {
+    // [0] version, serialNumber, signature, issuer, validity, subject, ... }
+    let (certificate, _) = der::element(certificate, 0x30)?;
+    let (tbs, _) = der::element(certificate, 0x30)?;
+    let tbs = der::element(tbs, 0xa0).map_or(tbs, |(_, rest)| rest);
+    let (_, rest) = der::element(tbs, 0x02)?;
+    let (_, rest) = der::element(rest, 0x30)?;
+    let (issuer, rest) = der::element(rest, 0x30)?;
+    let (_, rest) = der::element(rest, 0x30)?;
+    let (subject, _) = der::element(rest, 0x30)?;
+    Some(Certificate {
+        subject: name(subject)?,
+        issuer: name(issuer)?,
+    })
+}
+
+// The short names OpenSSL gives the attributes of names, by their object identifiers.
+const ATTRIBUTES: &[(&[u8], &str)] = &[
+    (&[0x55, 0x04, 0x03], "CN"),
+    (&[0x55, 0x04, 0x04], "SN"),
+    (&[0x55, 0x04, 0x05], "serialNumber"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x09], "street"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (&[0x55, 0x04, 0x0c], "title"),
+    (&[0x55, 0x04, 0x2a], "GN"),
+    (
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01],
+        "emailAddress",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01],
+        "UID",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19],
+        "DC",
+    ),
+];
+
+// A Name, SEQUENCE OF SET OF SEQUENCE { type, value }, as `/C=SE/O=Acme/CN=alice`, the '/'
+// and '\' of the values escaped.
+fn name(mut der: &[u8]) -> Option<String> {
+    let mut name = String::new();
+    while !der.is_empty() {
+        let (mut set, rest) = der::element(der, 0x31)?;
+        der = rest;
+        while !set.is_empty() {
+            let (attribute, rest) = der::element(set, 0x30)?;
+            set = rest;
+            let (oid, value) = der::element(attribute, 0x06)?;
+            let (tag, value, _) = der::next(value)?;
+            name.push('/');
+            match ATTRIBUTES.iter().find(|(known, _)| *known == oid) {
+                Some((_, short)) => name.push_str(short),
+                None => name.push_str(&dotted(oid)),
+            }
+            name.push('=');
+            let value = match tag {
+                // BMPString, in UTF-16.
+                0x1e => {
+                    let units = value
+                        .chunks(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]));
+                    char::decode_utf16(units)
+                        .map(|c| c.unwrap_or('\u{fffd}'))
+                        .collect()
+                }
+                _ => String::from_utf8_lossy(value).into_owned(),
+            };
+            for c in value.chars() {
+                if c == '/' || c == '\\' {
+                    name.push('\\');
+                }
+                name.push(c);
+            }
+        }
+    }
+    Some(name)
+}
+
+// An object identifier as its numbers, `2.5.4.3`.
+fn dotted(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc: u64 = 0;
+    for &byte in oid {
+        arc = arc << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    let mut numbers = match arcs.first() {
+        Some(&first) if first < 80 => vec![first / 40, first % 40],
+        Some(&first) => vec![2, first - 80],
+        None => Vec::new(),
+    };
+    numbers.extend(arcs.iter().skip(1));
+    numbers
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(subject: &str) -> TlsSession {
+        TlsSession {
+            certificate: Some(Certificate {
+                subject: subject.to_string(),
+                issuer: "/O=Acme/CN=Acme CA".to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn parses_requirements() {
+        assert_eq!("ssl".parse(), Ok(Requirement::Ssl));
+        assert_eq!("X509".parse(), Ok(Requirement::X509));
+        assert_eq!(
+            "SUBJECT '/O=Acme/CN=alice' AND ISSUER '/O=Acme/CN=Acme CA'".parse(),
+            Ok(Requirement::Certificate {
+                subject: Some("/O=Acme/CN=alice".to_string()),
+                issuer: Some("/O=Acme/CN=Acme CA".to_string()),
+            })
+        );
+        assert_eq!("SUBJECT".parse::<Requirement>(), Err(()));
+    }
+
+    #[test]
+    fn only_a_named_certificate_replaces_the_password() {
+        let alice: Requirement = "SUBJECT '/O=Acme/CN=alice'".parse().unwrap();
+        assert!(alice.met_by(Some(&session("/O=Acme/CN=alice"))));
+        assert!(!alice.met_by(Some(&session("/O=Acme/CN=bob"))));
+        assert!(alice.replaces_password());
+        // Any certificate meets X509, so the password is still checked.
+        assert!(Requirement::X509.met_by(Some(&session("/O=Acme/CN=bob"))));
+        assert!(!Requirement::X509.replaces_password());
+        assert!(!Requirement::Ssl.replaces_password());
+        assert!(!Requirement::X509.met_by(Some(&TlsSession { certificate: None })));
+        assert!(!Requirement::Ssl.met_by(None));
+    }
+
+    #[test]
+    fn an_issuer_alone_doesnt_replace_the_password() {
+        let issuer: Requirement = "ISSUER '/O=Acme/CN=Acme CA'".parse().unwrap();
+        // Every certificate of the CA's meets it, whoever it was given to.
+        assert!(issuer.met_by(Some(&session("/O=Acme/CN=bob"))));
+        assert!(!issuer.replaces_password());
+        let both: Requirement = "SUBJECT '/O=Acme/CN=alice' AND ISSUER '/O=Acme/CN=Acme CA'"
+            .parse()
+            .unwrap();
+        assert!(both.replaces_password());
+    }
+
+    // A DER Name of one attribute, CN, per value.
+    fn der_name(values: &[&str]) -> Vec<u8> {
+        let element = |tag: u8, content: &[u8]| {
+            let mut element = vec![tag, content.len() as u8];
+            element.extend_from_slice(content);
+            element
+        };
+        let mut name = Vec::new();
+        for value in values {
+            let mut attribute = element(0x06, &[0x55, 0x04, 0x03]);
+            attribute.extend(element(0x0c, value.as_bytes()));
+            name.extend(element(0x31, &element(0x30, &attribute)));
+        }
+        name
+    }
+
+    #[test]
+    fn escapes_slashes_in_values() {
+        assert_eq!(name(&der_name(&["alice"])).unwrap(), "/CN=alice");
+        // A value can't pass for two attributes.
+        let crafted = name(&der_name(&["x/CN=alice"])).unwrap();
+        assert_eq!(crafted, "/CN=x\\/CN=alice");
+        assert_ne!(crafted, name(&der_name(&["x", "alice"])).unwrap());
+        assert_eq!(name(&der_name(&["a\\b"])).unwrap(), "/CN=a\\\\b");
+        let alice: Requirement = "SUBJECT '/CN=x/CN=alice'".parse().unwrap();
+        assert!(!alice.met_by(Some(&session(&crafted))));
+        let escaped: Requirement = "SUBJECT '/CN=x\\\\/CN=alice'".parse().unwrap();
+        assert!(escaped.met_by(Some(&session(&crafted))));
+    }
+}
@@ -0,0 +1,423 @@
+// The MySQL protocol's compression, for clients on slow links: `mysql --compress`, or
+// --compression-algorithms=zstd, and connectors' useCompression and the like.
+//
+//   PROTOCOL_COMPRESSION  the algorithms offered to clients, "zlib,zstd" by default, or "off"
+//
+// The greeting offers them with the CLIENT_COMPRESS and CLIENT_ZSTD_COMPRESSION_ALGORITHM
+// capabilities, and a client that asks for one in its handshake response gets it from the end of
+// the handshake on, zlib if it asks for both, as MySQL does. From then on, both ways, the packets
+// go inside compressed packets: a header with the compressed length, a sequence number that
+// starts again with each command, and the length uncompressed, or 0 for a payload under 50
+// bytes sent as it is.
+//
+// `Decompressing` wraps the client's read half and `Compressing` its write half, under
+// everything else, so the rest of the proxy, its protocol traces included, sees the packets as
+// they are uncompressed. `Compressing` compresses what opensrv writes whenever it flushes, at
+// the end of each reply, and every 64 KiB as a long result streams out. The zstd level is zstd's
+// default, whatever the client asks for its own side.
+
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::protocol;
+
+const CLIENT_COMPRESS: u32 = 0x20;
+const CLIENT_ZSTD_COMPRESSION_ALGORITHM: u32 = 0x0400_0000;
+
+// Payloads shorter than this aren't worth compressing.
+const MIN_COMPRESS_LENGTH: usize = 50;
+// What is compressed at most before it is sent, though opensrv hasn't flushed.
+const CHUNK: usize = 64 * 1024;
+// The largest length a compressed packet's header can give.
+const MAX_PAYLOAD: usize = 0xff_ffff;
+const HEADER: usize = 7;
+
+/// The algorithms offered to clients (PROTOCOL_COMPRESSION).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Algorithms {
+    pub zlib: bool,
+    pub zstd: bool,
+}
+
+impl Default for Algorithms {
+    fn default() -> Algorithms {
+        Algorithms {
+            zlib: true,
+            zstd: true,
+        }
+    }
+}
+
+impl Algorithms {
+    /// The algorithms PROTOCOL_COMPRESSION lists, comma-separated, or `off`; `None` if it names
+    /// one there isn't.
+    pub fn parse(list: &str) -> Option<Algorithms> {
+        let mut algorithms = Algorithms {
+            zlib: false,
+            zstd: false,
+        };
+        if list.trim().eq_ignore_ascii_case("off") {
+            return Some(algorithms);
+        }
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name.to_ascii_lowercase().as_str() {
+                "zlib" => algorithms.zlib = true,
+                "zstd" => algorithms.zstd = true,
+                _ => return None,
+            }
+        }
+        Some(algorithms)
+    }
+
+    // The capabilities offering them.
+    fn capabilities(&self) -> u32 {
+        let zlib = if self.zlib { CLIENT_COMPRESS } else { 0 };
+        let zstd = if self.zstd {
+            CLIENT_ZSTD_COMPRESSION_ALGORITHM
+        } else {
+            0
+        };
+        zlib | zstd
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Zlib,
+    Zstd,
+}
+
+// Not yet known, none, zlib or zstd, as stored in `Negotiation::algorithm`.
+const UNDECIDED: u8 = 0;
+const NONE: u8 = 1;
+const ZLIB: u8 = 2;
+const ZSTD: u8 = 3;
+
+/// What a connection's two halves agree on: the algorithm, once the handshake is over, and the
+/// sequence number of the next compressed packet.
+pub struct Negotiation {
+    offered: Algorithms,
+    // The capabilities of the client's handshake response.
+    client: AtomicU32,
+    algorithm: AtomicU8,
+    sequence: AtomicU8,
+}
+
+impl Negotiation {
+    pub fn new(offered: Algorithms) -> Arc<Negotiation> {
+        Arc::new(Negotiation {
+            offered,
+            client: AtomicU32::new(0),
+            algorithm: AtomicU8::new(UNDECIDED),
+            sequence: AtomicU8::new(0),
+        })
+    }
+
+    fn algorithm(&self) -> Option<Option<Algorithm>> {
+        match self.algorithm.load(Ordering::Acquire) {
+            UNDECIDED => None,
+            ZLIB => Some(Some(Algorithm::Zlib)),
+            ZSTD => Some(Some(Algorithm::Zstd)),
+            _ => Some(None),
+        }
+    }
+
+    // Settles the algorithm as the handshake ends.
+    fn decide(&self) {
+        let client = self.client.load(Ordering::Relaxed);
+        let algorithm = if client & CLIENT_COMPRESS != 0 && self.offered.zlib {
+            ZLIB
+        } else if client & CLIENT_ZSTD_COMPRESSION_ALGORITHM != 0 && self.offered.zstd {
+            ZSTD
+        } else {
+            NONE
+        };
+        self.algorithm.store(algorithm, Ordering::Release);
+    }
+}
+
+/// A connection's read half, its packets uncompressed once compression is on.
+pub struct Decompressing<R> {
+    inner: R,
+    negotiation: Arc<Negotiation>,
+    // Read from `inner` and not yet passed on.
+    pending: Vec<u8>,
+    // Uncompressed and ready to be read, from `offset` on.
+    ready: Vec<u8>,
+    offset: usize,
+    // Whether the client's handshake response has been seen, while passing the handshake on.
+    response_seen: bool,
+    eof: bool,
+}
+
+impl<R> Decompressing<R> {
+    pub fn new(inner: R, negotiation: Arc<Negotiation>) -> Decompressing<R> {
+        Decompressing {
+            inner,
+            negotiation,
+            pending: Vec::new(),
+            ready: Vec::new(),
+            offset: 0,
+            response_seen: false,
+            eof: false,
+        }
+    }
+
+    // Moves what can be from `pending` to `ready`.
+    fn process(&mut self) -> io::Result<()> {
+        match self.negotiation.algorithm() {
+            // The handshake: passed on as it is, once the capabilities are noted.
+            None | Some(None) => {
+                if !self.response_seen {
+                    let Some(flags) = self.pending.get(4..8) else {
+                        return Ok(());
+                    };
+                    let flags = u32::from_le_bytes([flags[0], flags[1], flags[2], flags[3]]);
+                    self.negotiation.client.store(flags, Ordering::Relaxed);
+                    self.response_seen = true;
+                }
+                self.ready.append(&mut self.pending);
+            }
+            Some(Some(algorithm)) => {
+                while self.pending.len() >= HEADER {
+                    let length = u24(&self.pending[0..3]);
+                    if self.pending.len() < HEADER + length {
+                        break;
+                    }
+                    let sequence = self.pending[3];
+                    let uncompressed = u24(&self.pending[4..7]);
+                    let payload = &self.pending[HEADER..HEADER + length];
+                    match uncompressed {
+                        0 => self.ready.extend_from_slice(payload),
+                        _ => decompress(algorithm, payload, uncompressed, &mut self.ready)?,
+                    }
+                    self.pending.drain(..HEADER + length);
+                    // The reply follows on from the client's packets.
+                    self.negotiation
+                        .sequence
+                        .store(sequence.wrapping_add(1), Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Decompressing<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.offset < this.ready.len() {
+                let n = buf.remaining().min(this.ready.len() - this.offset);
+                buf.put_slice(&this.ready[this.offset..this.offset + n]);
+                this.offset += n;
+                return Poll::Ready(Ok(()));
+            }
+            this.ready.clear();
+            this.offset = 0;
+            this.process()?;
+            if !this.ready.is_empty() {
+                continue;
+            }
+            if this.eof {
+                return Poll::Ready(Ok(()));
+            }
+            let mut chunk = [0; 8192];
+            let mut read = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            if read.filled().is_empty() {
+                this.eof = true;
+            } else {
+                this.pending.extend_from_slice(read.filled());
+            }
+        }
+    }
+}
+
+/// A connection's write half, compressing once compression is on.
+pub struct Compressing<W> {
+    inner: W,
+    negotiation: Arc<Negotiation>,
+    // During the handshake, the packet being written, held to be looked at whole.
+    packet: Vec<u8>,
+    greeted: bool,
+    // Written and not yet compressed, once compression is on.
+    plain: Vec<u8>,
+    // Bytes on their way to `inner`, from `offset` on.
+    sending: Vec<u8>,
+    offset: usize,
+}
+
+impl<W> Compressing<W> {
+    pub fn new(inner: W, negotiation: Arc<Negotiation>) -> Compressing<W> {
+        Compressing {
+            inner,
+            negotiation,
+            packet: Vec::new(),
+            greeted: false,
+            plain: Vec::new(),
+            sending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    // Takes `buf`, the handshake's packets passing through on their way, until it ends.
+    fn take(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.negotiation.algorithm() {
+                Some(None) => {
+                    self.sending.extend_from_slice(buf);
+                    return Ok(());
+                }
+                Some(Some(algorithm)) => {
+                    self.plain.extend_from_slice(buf);
+                    if self.plain.len() >= CHUNK {
+                        self.compress(algorithm)?;
+                    }
+                    return Ok(());
+                }
+                None => {}
+            }
+            let wanted = if self.packet.len() < 4 {
+                4 - self.packet.len()
+            } else {
+                4 + u24(&self.packet[0..3]) - self.packet.len()
+            };
+            let (now, rest) = buf.split_at(wanted.min(buf.len()));
+            self.packet.extend_from_slice(now);
+            buf = rest;
+            if self.packet.len() < 4 || self.packet.len() < 4 + u24(&self.packet[0..3]) {
+                continue;
+            }
+            let mut packet = std::mem::take(&mut self.packet);
+            if !self.greeted {
+                self.greeted = true;
+                protocol::offer_capabilities(&mut packet, self.negotiation.offered.capabilities());
+            } else if packet.get(4) == Some(&0x00) {
+                // The OK ending the handshake goes out as it is, and what follows compressed.
+                self.negotiation.decide();
+            } else if packet.get(4) == Some(&0xff) {
+                self.negotiation.algorithm.store(NONE, Ordering::Release);
+            }
+            self.sending.append(&mut packet);
+        }
+        Ok(())
+    }
+
+    // Compresses what has been written into compressed packets.
+    fn compress(&mut self, algorithm: Algorithm) -> io::Result<()> {
+        let sending = &mut self.sending;
+        for chunk in self.plain.chunks(MAX_PAYLOAD) {
+            let compressed = match chunk.len() < MIN_COMPRESS_LENGTH {
+                true => None,
+                false => Some(compress(algorithm, chunk)?),
+            };
+            let (payload, uncompressed) = match &compressed {
+                // Not worth it if it didn't get any smaller.
+                Some(compressed) if compressed.len() < chunk.len() => {
+                    (compressed.as_slice(), chunk.len())
+                }
+                _ => (chunk, 0),
+            };
+            let sequence = self.negotiation.sequence.fetch_add(1, Ordering::Relaxed);
+            sending.extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+            sending.push(sequence);
+            sending.extend_from_slice(&(uncompressed as u32).to_le_bytes()[..3]);
+            sending.extend_from_slice(payload);
+        }
+        self.plain.clear();
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Compressing<W> {
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let sending = &mut self.sending;
+        while self.offset < sending.len() {
+            let written =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &sending[self.offset..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.offset += written;
+        }
+        sending.clear();
+        self.offset = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Compressing<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_send(cx))?;
+        self.take(buf)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(Some(algorithm)) = self.negotiation.algorithm() {
+            if !self.plain.is_empty() {
+                self.compress(algorithm)?;
+            }
+        }
+        ready!(self.poll_send(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn compress(algorithm: Algorithm, data: &[u8]) -> io::Result<Vec<u8>> {
+    match algorithm {
+        Algorithm::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Algorithm::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL),
+    }
+}
+
+fn decompress(
+    algorithm: Algorithm,
+    data: &[u8],
+    uncompressed: usize,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    let start = out.len();
+    match algorithm {
+        Algorithm::Zlib => {
+            ZlibDecoder::new(data).read_to_end(out)?;
+        }
+        Algorithm::Zstd => out.extend(zstd::bulk::decompress(data, uncompressed)?),
+    }
+    match out.len() - start == uncompressed {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "a compressed packet's payload isn't the length its header gives",
+        )),
+    }
+}
+
+fn u24(bytes: &[u8]) -> usize {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize
+}
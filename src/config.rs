@@ -0,0 +1,1101 @@
+// Runtime configuration, read from the environment (and the .env file loaded by main) and, with
+// --config, a TOML file. A variable set in the environment wins over the file.
+//
+// The file has the same settings as the environment, under the variables' names in lower case.
+// A table's name prefixes the names of its keys, so these are the same setting:
+//
+//   db_sslmode = "require"
+//
+//   [db]
+//   sslmode = "require"
+//
+// Lists such as estimated_count_tables can be arrays. A setting the proxy doesn't know is an
+// error rather than ignored, so a misspelt name doesn't go unnoticed.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDateTime};
+use toml_edit::{Document, Item, Value};
+
+use crate::audit::{AuditConfig, AuditSink};
+use crate::auth::{self, AuthConfig, JwtConfig, JwtKey, PasswordHash};
+use crate::catalog::ObjectName;
+use crate::client_tls::{ClientTlsConfig, Requirement};
+use crate::compression::Algorithms;
+use crate::guc_mappings::GucMapping;
+use crate::limits::StatementLimits;
+use crate::logging::{LogFormat, SqlLogFormat};
+use crate::policy::{Grant, PolicyConfig, StatementClass};
+use crate::protocol;
+use crate::reconnect;
+use crate::result_cache::{self, CacheRule, ResultCacheConfig};
+use crate::runtime::{RuntimeConfig, DEFAULT_THREAD_NAME};
+use crate::session_init::SessionInits;
+use crate::shadow::ShadowConfig;
+use crate::statement_cache;
+use crate::telemetry::{TelemetryConfig, DEFAULT_SERVICE_NAME};
+use crate::throttle::ThrottleConfig;
+use crate::timeouts::{self, TimeoutConfig};
+use crate::tls::{SslMode, TlsConfig};
+use crate::trace::TraceConfig;
+use crate::translator::pretty::{KeywordCase, Layout};
+use crate::translator::{CheckConstraints, IdentifierCase, TranslationOptions, ZeroDates};
+use crate::transport::TransportKind;
+use crate::tunnel::{self, TunnelConfig};
+
+pub struct Config {
+    pub db_host: String,
+    // The hosts to fail over to when DB_HOST can't be reached (DB_STANDBY_HOSTS), in order.
+    pub db_standby_hosts: Vec<String>,
+    pub db_user: String,
+    pub db_password: String,
+    // TLS to PostgreSQL (DB_SSLMODE, DB_SSLROOTCERT, DB_SSLCERT, DB_SSLKEY).
+    pub tls: TlsConfig,
+    // The `postmyrustache tunnel` the sessions go through, compressed, rather than straight to
+    // DB_HOST (DB_TUNNEL), if any.
+    pub db_tunnel: Option<String>,
+    // How long opening a session may take (DB_CONNECT_TIMEOUT, in seconds), and how long
+    // PostgreSQL lets a statement run before it cancels it itself (DB_STATEMENT_TIMEOUT, in
+    // milliseconds), if at all.
+    pub db_connect_timeout: Option<Duration>,
+    pub db_statement_timeout: Option<Duration>,
+    // How many more times opening a session is tried, backing off, when it fails
+    // (DB_RECONNECT_ATTEMPTS).
+    pub db_reconnect_attempts: u32,
+    // Run the writes outside a transaction with idempotency keys, so that one whose session is
+    // lost can be run again on a new one (IDEMPOTENT_WRITES).
+    pub idempotent_writes: bool,
+    // Where the MySQL listener binds, `host:port`.
+    pub listen_addr: String,
+    // A second listener for sidecar tooling and health scripts (ADMIN_LISTEN_ADDR), off when
+    // unset. Its clients are trusted to log in as anyone, so it must be on a loopback address.
+    pub admin_listen_addr: Option<String>,
+    // What accepts the clients and moves their bytes (LISTEN_TRANSPORT), `tcp` or `io-uring`.
+    pub listen_transport: TransportKind,
+    // TLS for the clients, and the users who have to log in over it (LISTEN_TLS_CERT,
+    // LISTEN_TLS_KEY, LISTEN_TLS_CA, USER_REQUIRE), off when unset.
+    pub client_tls: Option<ClientTlsConfig>,
+    // Who may log in (AUTH_PROVIDER, with AUTH_USERS, AUTH_WEBHOOK_URL, AUTH_WEBHOOK_TIMEOUT,
+    // AUTH_LDAP_URL, AUTH_LDAP_BIND_DN, AUTH_LDAP_TIMEOUT and the AUTH_JWT_ settings), anyone
+    // when unset.
+    pub auth: AuthConfig,
+    pub translation: TranslationOptions,
+    pub parameterize: bool,
+    pub parse_failure: ParseFailure,
+    // Give NOT NULL columns an INSERT leaves out MySQL's implicit default (IMPLICIT_DEFAULTS).
+    pub implicit_defaults: bool,
+    // Keep the schema changes made, with the tables' definitions before and after (DDL_HISTORY).
+    pub ddl_history: bool,
+    // Create the schema of a database a client uses, or creates a table in, that doesn't exist
+    // yet (AUTO_CREATE_DATABASES), rather than failing.
+    pub auto_create_databases: bool,
+    // The directory LOAD DATA INFILE reads files from (SECURE_FILE_PRIV), as an absolute path
+    // without symbolic links; LOAD DATA INFILE is refused when it's unset or empty.
+    pub secure_file_priv: Option<String>,
+    // How many of a session's recent errors SHOW ERRORS lists.
+    pub error_history: usize,
+    // The protocol trace (TRACE_FILE, TRACE_CONNECTION, TRACE_USER), off when unset.
+    pub trace: Option<TraceConfig>,
+    // Tables whose SELECT COUNT(*) is answered from the planner's estimate
+    // (ESTIMATED_COUNT_TABLES).
+    pub estimated_counts: Vec<ObjectName>,
+    // How long a client has to log in after connecting (HANDSHAKE_TIMEOUT, in seconds).
+    pub handshake_timeout: Duration,
+    // How many clients may be connected without having logged in yet (MAX_HANDSHAKES); more are
+    // disconnected straight away.
+    pub max_handshakes: usize,
+    // The file of rewrite rules applied to statements before they are translated
+    // (REWRITE_RULES), reread on SIGHUP.
+    pub rewrite_rules: Option<String>,
+    // How many prepared statements each connection keeps on its session
+    // (STATEMENT_CACHE_SIZE).
+    pub statement_cache_size: usize,
+    // Where the running totals are kept between runs (STATS_FILE), if anywhere.
+    pub stats_file: Option<String>,
+    // The tokio runtime's threads (RUNTIME_WORKER_THREADS, RUNTIME_MAX_BLOCKING_THREADS,
+    // RUNTIME_THREAD_NAME).
+    pub runtime: RuntimeConfig,
+    // Statements at least this long, in bytes, are translated on the blocking pool
+    // (BLOCKING_TRANSLATION_SIZE).
+    pub blocking_translation_size: usize,
+    // Where the statements' tracing spans are exported over OTLP (OTEL_EXPORTER_OTLP_ENDPOINT,
+    // OTEL_SERVICE_NAME), off when unset.
+    pub telemetry: Option<TelemetryConfig>,
+    // `text`, or `json` for a JSON object per line with a record of each statement (LOG_FORMAT).
+    pub log_format: LogFormat,
+    // How the text log shows statements, as they are or laid out over lines (LOG_SQL_FORMAT,
+    // LOG_SQL_KEYWORDS, LOG_SQL_COLOR).
+    pub log_sql_format: SqlLogFormat,
+    // The longest, most deeply nested statement, the most UNION branches and IN list items
+    // accepted (MAX_STATEMENT_LENGTH, MAX_PARSE_DEPTH, MAX_UNION_BRANCHES, MAX_IN_LIST_ITEMS).
+    pub limits: StatementLimits,
+    // The largest command a client may send, in bytes, 0 for no limit (MAX_ALLOWED_PACKET).
+    pub max_allowed_packet: usize,
+    // The compression algorithms offered to clients (PROTOCOL_COMPRESSION).
+    pub protocol_compression: Algorithms,
+    // How many clients may connect, in all and as each user, and how many statements a user may
+    // run a second (MAX_CONNECTIONS, MAX_USER_CONNECTIONS, USER_MAX_CONNECTIONS,
+    // MAX_QUERIES_PER_SECOND).
+    pub throttle: ThrottleConfig,
+    // How long a connection may be idle, and a statement run on PostgreSQL, before it is closed
+    // or cancelled (WAIT_TIMEOUT, in seconds, and MAX_EXECUTION_TIME, in milliseconds).
+    pub timeouts: TimeoutConfig,
+    // The MySQL server statements are also run on, for their outcomes to be compared
+    // (SHADOW_MYSQL_URL, SHADOW_QUEUE_SIZE), off when unset.
+    pub shadow: Option<ShadowConfig>,
+    // Where each statement the clients run is recorded, and for whom (AUDIT_LOG, AUDIT_USERS,
+    // AUDIT_REDACT), off when unset.
+    pub audit: Option<AuditConfig>,
+    // The statements refused: all writes, those of the classes a user isn't allowed, or those
+    // using tables a user isn't granted (READ_ONLY, ALLOWED_STATEMENTS, USER_ALLOWED_STATEMENTS,
+    // USER_GRANTS).
+    pub policy: PolicyConfig,
+    // The SELECT results kept in memory, for how long and how many (RESULT_CACHE_RULES,
+    // RESULT_CACHE_SIZE), off when unset.
+    pub result_cache: Option<ResultCacheConfig>,
+    // The MySQL session variables a SET of sets PostgreSQL settings instead (GUC_MAPPINGS).
+    pub guc_mappings: Vec<GucMapping>,
+    // The database each user's sessions use when the client names none, and the statements they
+    // start with (USER_DEFAULT_DATABASE, USER_INIT_SQL).
+    pub session_inits: SessionInits,
+}
+
+/// What to do with a statement the translator can't parse (PARSE_FAILURE).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseFailure {
+    /// Forward it to PostgreSQL untranslated and leave a warning for SHOW WARNINGS.
+    #[default]
+    Passthrough,
+    /// Reject it with a syntax error without contacting PostgreSQL.
+    Reject,
+}
+
+const DEFAULT_ERROR_HISTORY: usize = 20;
+const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:3306";
+// MySQL's connect_timeout.
+const DEFAULT_HANDSHAKE_TIMEOUT: u64 = 10;
+// libpq has none, which leaves the proxy waiting for as long as the kernel tries to connect to
+// a server that doesn't answer.
+const DEFAULT_DB_CONNECT_TIMEOUT: u64 = 10;
+const DEFAULT_MAX_HANDSHAKES: usize = 100;
+const DEFAULT_BLOCKING_TRANSLATION_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Missing(&'static str),
+    Invalid { var: &'static str, value: String },
+    // The config file can't be read or isn't valid TOML.
+    File { path: String, error: String },
+    // A setting in the config file that isn't one of the proxy's.
+    Unknown { path: String, key: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Missing(var) => write!(f, "{} must be set", var),
+            ConfigError::Invalid { var, value } => {
+                write!(f, "invalid value for {}: {:?}", var, value)
+            }
+            ConfigError::File { path, error } => write!(f, "can't read {}: {}", path, error),
+            ConfigError::Unknown { path, key } => write!(f, "unknown setting in {}: {}", path, key),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Config::from_settings(&Settings::default())
+    }
+
+    /// The configuration in the TOML file at `path`, with the environment overriding it.
+    pub fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let settings = Settings::from_file(path)?;
+        let config = Config::from_settings(&settings)?;
+        let read = settings.read.borrow();
+        if let Some(key) = settings
+            .file
+            .keys()
+            .find(|key| !read.contains(key.as_str()))
+        {
+            return Err(ConfigError::Unknown {
+                path: path.to_string(),
+                key: key.to_lowercase(),
+            });
+        }
+        Ok(config)
+    }
+
+    fn from_settings(settings: &Settings) -> Result<Config, ConfigError> {
+        let tls = tls(settings)?;
+        Ok(Config {
+            db_host: settings.required("DB_HOST")?,
+            db_standby_hosts: db_standby_hosts(settings)?,
+            db_user: settings.required("DB_USER")?,
+            db_password: settings.required("DB_PASSWORD")?,
+            db_tunnel: db_tunnel(settings, &tls)?,
+            db_connect_timeout: Some(
+                settings.number("DB_CONNECT_TIMEOUT", DEFAULT_DB_CONNECT_TIMEOUT)?,
+            )
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs),
+            db_statement_timeout: Some(settings.number("DB_STATEMENT_TIMEOUT", 0)?)
+                .filter(|milliseconds| *milliseconds > 0)
+                .map(Duration::from_millis),
+            db_reconnect_attempts: settings
+                .number("DB_RECONNECT_ATTEMPTS", reconnect::DEFAULT_ATTEMPTS)?,
+            idempotent_writes: settings.flag("IDEMPOTENT_WRITES")?,
+            tls,
+            listen_addr: settings
+                .optional("LISTEN_ADDR")
+                .unwrap_or_else(|| DEFAULT_LISTEN_ADDR.to_string()),
+            admin_listen_addr: admin_listen_addr(settings)?,
+            listen_transport: match settings.optional("LISTEN_TRANSPORT") {
+                None => TransportKind::default(),
+                Some(value) => value.parse().map_err(|_| ConfigError::Invalid {
+                    var: "LISTEN_TRANSPORT",
+                    value,
+                })?,
+            },
+            client_tls: client_tls(settings)?,
+            auth: auth(settings)?,
+            translation: translation(settings)?,
+            parameterize: settings.flag("PARAMETERIZE_QUERIES")?,
+            parse_failure: match settings.optional("PARSE_FAILURE") {
+                None => ParseFailure::default(),
+                Some(v) if v.eq_ignore_ascii_case("passthrough") => ParseFailure::Passthrough,
+                Some(v) if v.eq_ignore_ascii_case("reject") => ParseFailure::Reject,
+                Some(value) => {
+                    return Err(ConfigError::Invalid {
+                        var: "PARSE_FAILURE",
+                        value,
+                    })
+                }
+            },
+            implicit_defaults: settings.flag("IMPLICIT_DEFAULTS")?,
+            ddl_history: settings.flag("DDL_HISTORY")?,
+            auto_create_databases: settings.flag("AUTO_CREATE_DATABASES")?,
+            secure_file_priv: secure_file_priv(settings)?,
+            error_history: match settings.optional("ERROR_HISTORY") {
+                None => DEFAULT_ERROR_HISTORY,
+                Some(value) => value.parse().map_err(|_| ConfigError::Invalid {
+                    var: "ERROR_HISTORY",
+                    value,
+                })?,
+            },
+            trace: trace(settings)?,
+            estimated_counts: estimated_counts(settings),
+            handshake_timeout: Duration::from_secs(
+                settings.number("HANDSHAKE_TIMEOUT", DEFAULT_HANDSHAKE_TIMEOUT)?,
+            ),
+            max_handshakes: settings.number("MAX_HANDSHAKES", DEFAULT_MAX_HANDSHAKES)?,
+            rewrite_rules: settings.optional("REWRITE_RULES"),
+            statement_cache_size: settings
+                .number("STATEMENT_CACHE_SIZE", statement_cache::DEFAULT_SIZE)?,
+            stats_file: settings.optional("STATS_FILE"),
+            runtime: runtime(settings)?,
+            blocking_translation_size: settings.number(
+                "BLOCKING_TRANSLATION_SIZE",
+                DEFAULT_BLOCKING_TRANSLATION_SIZE,
+            )?,
+            telemetry: settings
+                .optional("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .map(|endpoint| TelemetryConfig {
+                    endpoint,
+                    service_name: settings
+                        .optional("OTEL_SERVICE_NAME")
+                        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string()),
+                }),
+            log_format: log_format(settings)?,
+            log_sql_format: log_sql_format(settings)?,
+            limits: limits(settings)?,
+            max_allowed_packet: match settings
+                .number("MAX_ALLOWED_PACKET", protocol::DEFAULT_MAX_ALLOWED_PACKET)?
+            {
+                0 => usize::MAX,
+                max => max,
+            },
+            protocol_compression: match settings.optional("PROTOCOL_COMPRESSION") {
+                None => Algorithms::default(),
+                Some(value) => Algorithms::parse(&value).ok_or(ConfigError::Invalid {
+                    var: "PROTOCOL_COMPRESSION",
+                    value,
+                })?,
+            },
+            throttle: throttle(settings)?,
+            timeouts: TimeoutConfig {
+                wait_timeout: Some(
+                    settings.number("WAIT_TIMEOUT", timeouts::DEFAULT_WAIT_TIMEOUT)?,
+                )
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+                max_execution_time: Some(settings.number("MAX_EXECUTION_TIME", 0)?)
+                    .filter(|milliseconds| *milliseconds > 0)
+                    .map(Duration::from_millis),
+            },
+            shadow: shadow(settings)?,
+            audit: audit(settings)?,
+            policy: policy(settings)?,
+            result_cache: result_cache(settings)?,
+            guc_mappings: guc_mappings(settings)?,
+            session_inits: session_inits(settings)?,
+        })
+    }
+}
+
+/// The translation options alone, from the environment and, with `path`, a TOML file, for
+/// translating statements without a PostgreSQL server to send them to.
+pub fn translation_options(path: Option<&str>) -> Result<TranslationOptions, ConfigError> {
+    match path {
+        Some(path) => translation(&Settings::from_file(path)?),
+        None => translation(&Settings::default()),
+    }
+}
+
+/// The settings of `postmyrustache tunnel`, from the environment and, with `path`, a TOML file;
+/// the tunnel needs none of the proxy's.
+pub fn tunnel_config(path: Option<&str>) -> Result<TunnelConfig, ConfigError> {
+    let settings = match path {
+        Some(path) => Settings::from_file(path)?,
+        None => Settings::default(),
+    };
+    Ok(TunnelConfig {
+        listen_addr: settings
+            .optional("TUNNEL_LISTEN_ADDR")
+            .unwrap_or_else(|| tunnel::DEFAULT_LISTEN_ADDR.to_string()),
+        target: settings
+            .optional("TUNNEL_TARGET")
+            .unwrap_or_else(|| tunnel::DEFAULT_TARGET.to_string()),
+        runtime: runtime(&settings)?,
+        log_format: log_format(&settings)?,
+    })
+}
+
+fn translation(settings: &Settings) -> Result<TranslationOptions, ConfigError> {
+    // The options are non-exhaustive, so they're set one by one on the sql_mode's.
+    let mut options =
+        TranslationOptions::default().sql_mode(&settings.optional("SQL_MODE").unwrap_or_default());
+    options.check_constraints = match settings.optional("CHECK_CONSTRAINTS") {
+        None => CheckConstraints::default(),
+        Some(v) if v.eq_ignore_ascii_case("enforce") => CheckConstraints::Enforce,
+        Some(v) if v.eq_ignore_ascii_case("strip") => CheckConstraints::Strip,
+        Some(value) => {
+            return Err(ConfigError::Invalid {
+                var: "CHECK_CONSTRAINTS",
+                value,
+            })
+        }
+    };
+    options.pinned_now = match settings.optional("PINNED_NOW") {
+        None => None,
+        Some(value) => Some(parse_datetime(&value).ok_or(ConfigError::Invalid {
+            var: "PINNED_NOW",
+            value,
+        })?),
+    };
+    options.fulltext_indexes = settings.flag("FULLTEXT_INDEXES")?;
+    options.identifiers = match settings.optional("IDENTIFIER_CASE") {
+        None => IdentifierCase::default(),
+        Some(v) if v.eq_ignore_ascii_case("lower") => IdentifierCase::Lower,
+        Some(v) if v.eq_ignore_ascii_case("preserve") => IdentifierCase::Preserve,
+        Some(value) => {
+            return Err(ConfigError::Invalid {
+                var: "IDENTIFIER_CASE",
+                value,
+            })
+        }
+    };
+    options.strict = settings.flag("STRICT_TRANSLATION")?;
+    options.dates.zero_dates = zero_dates(settings)?;
+    Ok(options)
+}
+
+// ZERO_DATES: null, null-as-zero, or a sentinel date PostgreSQL can store, so from 0001-01-01.
+fn zero_dates(settings: &Settings) -> Result<ZeroDates, ConfigError> {
+    let Some(value) = settings.optional("ZERO_DATES") else {
+        return Ok(ZeroDates::default());
+    };
+    if value.eq_ignore_ascii_case("null") {
+        return Ok(ZeroDates::Null);
+    }
+    if value.eq_ignore_ascii_case("null-as-zero") {
+        return Ok(ZeroDates::NullAsZero);
+    }
+    match chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+        Ok(sentinel) if sentinel.year() >= 1 => Ok(ZeroDates::Sentinel(sentinel)),
+        _ => Err(ConfigError::Invalid {
+            var: "ZERO_DATES",
+            value,
+        }),
+    }
+}
+
+// ADMIN_LISTEN_ADDR, whose clients log in without a password: refused unless only this host can
+// reach it, on `localhost` or a loopback address.
+fn admin_listen_addr(settings: &Settings) -> Result<Option<String>, ConfigError> {
+    let Some(value) = settings.optional("ADMIN_LISTEN_ADDR") else {
+        return Ok(None);
+    };
+    match is_loopback(&value) {
+        true => Ok(Some(value)),
+        false => Err(ConfigError::Invalid {
+            var: "ADMIN_LISTEN_ADDR",
+            value,
+        }),
+    }
+}
+
+/// Whether `host:port` is on the loopback interface.
+pub(crate) fn is_loopback(addr: &str) -> bool {
+    if let Ok(addr) = addr.parse::<SocketAddr>() {
+        return addr.ip().is_loopback();
+    }
+    addr.rsplit_once(':').is_some_and(|(host, port)| {
+        host.eq_ignore_ascii_case("localhost") && port.parse::<u16>().is_ok()
+    })
+}
+
+// SECURE_FILE_PRIV, which must name a directory: a typo fails at startup, not at the first LOAD
+// DATA INFILE.
+fn secure_file_priv(settings: &Settings) -> Result<Option<String>, ConfigError> {
+    let Some(value) = settings.optional("SECURE_FILE_PRIV") else {
+        return Ok(None);
+    };
+    match fs::canonicalize(&value) {
+        Ok(directory) if directory.is_dir() => Ok(Some(directory.to_string_lossy().into_owned())),
+        _ => Err(ConfigError::Invalid {
+            var: "SECURE_FILE_PRIV",
+            value,
+        }),
+    }
+}
+
+fn trace(settings: &Settings) -> Result<Option<TraceConfig>, ConfigError> {
+    let connection = match settings.optional("TRACE_CONNECTION") {
+        None => None,
+        Some(value) => Some(value.parse().map_err(|_| ConfigError::Invalid {
+            var: "TRACE_CONNECTION",
+            value,
+        })?),
+    };
+    let user = settings.optional("TRACE_USER");
+    match settings.optional("TRACE_FILE") {
+        Some(file) => Ok(Some(TraceConfig {
+            file,
+            connection,
+            user,
+        })),
+        // A filter on its own would trace nothing, which is surely not what was meant.
+        None if connection.is_some() || user.is_some() => Err(ConfigError::Missing("TRACE_FILE")),
+        None => Ok(None),
+    }
+}
+
+fn runtime(settings: &Settings) -> Result<RuntimeConfig, ConfigError> {
+    // tokio can't run without a worker or a blocking thread.
+    let threads = |var: &'static str| match settings.optional(var) {
+        None => Ok(None),
+        Some(value) => match value.parse() {
+            Ok(0) | Err(_) => Err(ConfigError::Invalid { var, value }),
+            Ok(threads) => Ok(Some(threads)),
+        },
+    };
+    Ok(RuntimeConfig {
+        worker_threads: threads("RUNTIME_WORKER_THREADS")?,
+        max_blocking_threads: threads("RUNTIME_MAX_BLOCKING_THREADS")?,
+        thread_name: settings
+            .optional("RUNTIME_THREAD_NAME")
+            .unwrap_or_else(|| DEFAULT_THREAD_NAME.to_string()),
+    })
+}
+
+fn log_format(settings: &Settings) -> Result<LogFormat, ConfigError> {
+    match settings.optional("LOG_FORMAT") {
+        None => Ok(LogFormat::default()),
+        Some(value) => value.parse().map_err(|_| ConfigError::Invalid {
+            var: "LOG_FORMAT",
+            value,
+        }),
+    }
+}
+
+fn log_sql_format(settings: &Settings) -> Result<SqlLogFormat, ConfigError> {
+    let value = |var: &'static str, values: &[&str]| match settings.optional(var) {
+        None => Ok(None),
+        Some(value) => match values.iter().position(|v| value.eq_ignore_ascii_case(v)) {
+            Some(index) => Ok(Some(index)),
+            None => Err(ConfigError::Invalid { var, value }),
+        },
+    };
+    let pretty = value("LOG_SQL_FORMAT", &["raw", "pretty"])? == Some(1);
+    let keywords = match value("LOG_SQL_KEYWORDS", &["upper", "lower", "preserve"])? {
+        Some(1) => KeywordCase::Lower,
+        Some(2) => KeywordCase::Preserve,
+        _ => KeywordCase::Upper,
+    };
+    let ansi = match value("LOG_SQL_COLOR", &["auto", "always", "never"])? {
+        Some(1) => true,
+        Some(2) => false,
+        _ => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    Ok(match pretty {
+        true => SqlLogFormat::Pretty(Layout { keywords, ansi }),
+        false => SqlLogFormat::Raw,
+    })
+}
+
+fn limits(settings: &Settings) -> Result<StatementLimits, ConfigError> {
+    let defaults = StatementLimits::default();
+    let limit = |var, default: Option<usize>| {
+        Ok(match settings.number(var, default.unwrap_or(0))? {
+            0 => None,
+            limit => Some(limit),
+        })
+    };
+    Ok(StatementLimits {
+        max_length: limit("MAX_STATEMENT_LENGTH", defaults.max_length)?,
+        max_depth: limit("MAX_PARSE_DEPTH", defaults.max_depth)?,
+        max_union_branches: limit("MAX_UNION_BRANCHES", defaults.max_union_branches)?,
+        max_in_list_items: limit("MAX_IN_LIST_ITEMS", defaults.max_in_list_items)?,
+    })
+}
+
+fn throttle(settings: &Settings) -> Result<ThrottleConfig, ConfigError> {
+    // 0 is no limit, as for the statement limits.
+    let limit = |var| Ok(Some(settings.number(var, 0)?).filter(|limit| *limit > 0));
+    let mut users = Vec::new();
+    if let Some(list) = settings.optional("USER_MAX_CONNECTIONS") {
+        for entry in list.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let invalid = || ConfigError::Invalid {
+                var: "USER_MAX_CONNECTIONS",
+                value: entry.trim().to_string(),
+            };
+            let (user, max) = entry.split_once(':').ok_or_else(invalid)?;
+            let max = max.trim().parse().map_err(|_| invalid())?;
+            users.push((user.trim().to_string(), max));
+        }
+    }
+    Ok(ThrottleConfig {
+        max_connections: limit("MAX_CONNECTIONS")?,
+        max_user_connections: limit("MAX_USER_CONNECTIONS")?,
+        users,
+        max_queries_per_second: Some(settings.number("MAX_QUERIES_PER_SECOND", 0)?)
+            .filter(|limit| *limit > 0),
+    })
+}
+
+fn shadow(settings: &Settings) -> Result<Option<ShadowConfig>, ConfigError> {
+    let queue_size = match settings.optional("SHADOW_QUEUE_SIZE") {
+        None => None,
+        // A queue needs room for a statement.
+        Some(value) => match value.parse() {
+            Ok(0) | Err(_) => {
+                return Err(ConfigError::Invalid {
+                    var: "SHADOW_QUEUE_SIZE",
+                    value,
+                })
+            }
+            Ok(size) => Some(size),
+        },
+    };
+    Ok(settings
+        .optional("SHADOW_MYSQL_URL")
+        .map(|url| ShadowConfig::new(url, queue_size)))
+}
+
+fn audit(settings: &Settings) -> Result<Option<AuditConfig>, ConfigError> {
+    let users = settings.optional("AUDIT_USERS").map(|users| {
+        users
+            .split(',')
+            .map(|user| user.trim().to_string())
+            .filter(|user| !user.is_empty())
+            .collect()
+    });
+    let redact = settings.flag("AUDIT_REDACT")?;
+    match settings.optional("AUDIT_LOG") {
+        Some(sink) => Ok(Some(AuditConfig {
+            sink: match sink.as_str() {
+                "syslog" => AuditSink::Syslog,
+                _ => AuditSink::File(sink),
+            },
+            users,
+            redact,
+        })),
+        // As with the trace, a filter on its own would audit nothing.
+        None if users.is_some() || redact => Err(ConfigError::Missing("AUDIT_LOG")),
+        None => Ok(None),
+    }
+}
+
+fn policy(settings: &Settings) -> Result<PolicyConfig, ConfigError> {
+    // A comma-separated list of statement classes.
+    let classes = |var: &'static str, list: &str| {
+        list.split(',')
+            .filter(|class| !class.trim().is_empty())
+            .map(|class| {
+                class.parse().map_err(|_| ConfigError::Invalid {
+                    var,
+                    value: class.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<StatementClass>, _>>()
+    };
+    let allowed = match settings.optional("ALLOWED_STATEMENTS") {
+        Some(list) => Some(classes("ALLOWED_STATEMENTS", &list)?),
+        None => None,
+    };
+    let mut users = Vec::new();
+    if let Some(list) = settings.optional("USER_ALLOWED_STATEMENTS") {
+        for entry in list.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let Some((user, list)) = entry.split_once(':') else {
+                return Err(ConfigError::Invalid {
+                    var: "USER_ALLOWED_STATEMENTS",
+                    value: entry.trim().to_string(),
+                });
+            };
+            users.push((
+                user.trim().to_string(),
+                classes("USER_ALLOWED_STATEMENTS", list)?,
+            ));
+        }
+    }
+    let mut grants = Vec::new();
+    if let Some(list) = settings.optional("USER_GRANTS") {
+        for entry in list.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let invalid = |value: &str| ConfigError::Invalid {
+                var: "USER_GRANTS",
+                value: value.trim().to_string(),
+            };
+            let Some((user, list)) = entry.split_once(':') else {
+                return Err(invalid(entry));
+            };
+            let user_grants = list
+                .split(',')
+                .filter(|grant| !grant.trim().is_empty())
+                .map(|grant| grant.parse().map_err(|_| invalid(grant)))
+                .collect::<Result<Vec<Grant>, _>>()?;
+            grants.push((user.trim().to_string(), user_grants));
+        }
+    }
+    Ok(PolicyConfig {
+        read_only: settings.flag("READ_ONLY")?,
+        allowed,
+        users,
+        grants,
+    })
+}
+
+// RESULT_CACHE_RULES is a comma-separated list of `table:seconds`.
+fn result_cache(settings: &Settings) -> Result<Option<ResultCacheConfig>, ConfigError> {
+    let Some(list) = settings.optional("RESULT_CACHE_RULES") else {
+        return Ok(None);
+    };
+    let mut rules = Vec::new();
+    for entry in list.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let invalid = || ConfigError::Invalid {
+            var: "RESULT_CACHE_RULES",
+            value: entry.trim().to_string(),
+        };
+        let (table, seconds) = entry.rsplit_once(':').ok_or_else(invalid)?;
+        let seconds: u64 = seconds.trim().parse().map_err(|_| invalid())?;
+        if table.trim().is_empty() || seconds == 0 {
+            return Err(invalid());
+        }
+        rules.push(CacheRule::new(table, Duration::from_secs(seconds)));
+    }
+    Ok(Some(ResultCacheConfig {
+        rules,
+        size: settings.number("RESULT_CACHE_SIZE", result_cache::DEFAULT_SIZE)?,
+    }))
+}
+
+// GUC_MAPPINGS is a comma-separated list of `variable:guc` or `variable:guc:unit`.
+fn guc_mappings(settings: &Settings) -> Result<Vec<GucMapping>, ConfigError> {
+    let Some(list) = settings.optional("GUC_MAPPINGS") else {
+        return Ok(Vec::new());
+    };
+    list.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            GucMapping::parse(entry).ok_or_else(|| ConfigError::Invalid {
+                var: "GUC_MAPPINGS",
+                value: entry.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+// AUTH_USERS is a semicolon-separated list of `user:password`, the password as it is typed or
+// hashed as MySQL's PASSWORD() does, `*` and 40 hex digits.
+fn auth(settings: &Settings) -> Result<AuthConfig, ConfigError> {
+    let Some(provider) = settings.optional("AUTH_PROVIDER") else {
+        return Ok(AuthConfig::None);
+    };
+    let timeout = |var| {
+        settings
+            .number(var, auth::DEFAULT_TIMEOUT)
+            .map(Duration::from_secs)
+    };
+    match provider.to_ascii_lowercase().as_str() {
+        "none" => Ok(AuthConfig::None),
+        "static" => {
+            let mut users = Vec::new();
+            for entry in settings
+                .required("AUTH_USERS")?
+                .split(';')
+                .filter(|entry| !entry.trim().is_empty())
+            {
+                let Some((user, password)) = entry.split_once(':') else {
+                    return Err(ConfigError::Invalid {
+                        var: "AUTH_USERS",
+                        value: entry.trim().to_string(),
+                    });
+                };
+                let hash = PasswordHash::parse(password)
+                    .filter(|_| password.starts_with('*'))
+                    .unwrap_or_else(|| auth::native_password_hash(password));
+                users.push((user.trim().to_string(), hash));
+            }
+            Ok(AuthConfig::Static(users))
+        }
+        "webhook" => Ok(AuthConfig::Webhook {
+            url: settings.required("AUTH_WEBHOOK_URL")?,
+            timeout: timeout("AUTH_WEBHOOK_TIMEOUT")?,
+        }),
+        "ldap" => Ok(AuthConfig::Ldap {
+            url: settings.required("AUTH_LDAP_URL")?,
+            bind_dn: settings.required("AUTH_LDAP_BIND_DN")?,
+            timeout: timeout("AUTH_LDAP_TIMEOUT")?,
+        }),
+        "jwt" => {
+            let key = match (
+                settings.optional("AUTH_JWT_SECRET"),
+                settings.optional("AUTH_JWT_PUBLIC_KEY"),
+            ) {
+                (Some(secret), None) => JwtKey::Secret(secret),
+                (None, Some(path)) => JwtKey::PublicKeyFile(path.into()),
+                (Some(_), Some(_)) => {
+                    return Err(ConfigError::Invalid {
+                        var: "AUTH_JWT_PUBLIC_KEY",
+                        value: "set along with AUTH_JWT_SECRET".to_string(),
+                    })
+                }
+                (None, None) => return Err(ConfigError::Missing("AUTH_JWT_SECRET")),
+            };
+            Ok(AuthConfig::Jwt(JwtConfig {
+                key,
+                issuer: settings.optional("AUTH_JWT_ISSUER"),
+                audience: settings.optional("AUTH_JWT_AUDIENCE"),
+                user_claim: settings
+                    .optional("AUTH_JWT_USER_CLAIM")
+                    .unwrap_or_else(|| "sub".to_string()),
+                role_claim: settings.optional("AUTH_JWT_ROLE_CLAIM"),
+            }))
+        }
+        _ => Err(ConfigError::Invalid {
+            var: "AUTH_PROVIDER",
+            value: provider,
+        }),
+    }
+}
+
+// Both are semicolon-separated lists of `user:value`, a user's init statements one to an entry.
+fn session_inits(settings: &Settings) -> Result<SessionInits, ConfigError> {
+    let mut inits = SessionInits::default();
+    for var in ["USER_DEFAULT_DATABASE", "USER_INIT_SQL"] {
+        let Some(list) = settings.optional(var) else {
+            continue;
+        };
+        for entry in list.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let invalid = || ConfigError::Invalid {
+                var,
+                value: entry.trim().to_string(),
+            };
+            let (user, value) = entry.split_once(':').ok_or_else(invalid)?;
+            let (user, value) = (user.trim(), value.trim());
+            if value.is_empty() {
+                return Err(invalid());
+            }
+            match var {
+                "USER_DEFAULT_DATABASE" => inits.set_database(user, value),
+                _ => inits.add_statement(user, value),
+            }
+        }
+    }
+    Ok(inits)
+}
+
+fn tls(settings: &Settings) -> Result<TlsConfig, ConfigError> {
+    let mode = match settings.optional("DB_SSLMODE") {
+        None => Default::default(),
+        Some(value) => value.parse().map_err(|_| ConfigError::Invalid {
+            var: "DB_SSLMODE",
+            value,
+        })?,
+    };
+    let client_cert = match (
+        settings.optional("DB_SSLCERT"),
+        settings.optional("DB_SSLKEY"),
+    ) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        (Some(_), None) => return Err(ConfigError::Missing("DB_SSLKEY")),
+        (None, Some(_)) => return Err(ConfigError::Missing("DB_SSLCERT")),
+    };
+    Ok(TlsConfig {
+        mode,
+        root_cert: settings.optional("DB_SSLROOTCERT"),
+        client_cert,
+    })
+}
+
+// USER_REQUIRE is a semicolon-separated list of `user:requirement`, as MySQL's REQUIRE clause
+// has it. The certificates they take are those of LISTEN_TLS_CA, so it has to be set for them.
+fn client_tls(settings: &Settings) -> Result<Option<ClientTlsConfig>, ConfigError> {
+    let mut requirements = Vec::new();
+    if let Some(list) = settings.optional("USER_REQUIRE") {
+        for entry in list.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let invalid = || ConfigError::Invalid {
+                var: "USER_REQUIRE",
+                value: entry.trim().to_string(),
+            };
+            let (user, requirement) = entry.split_once(':').ok_or_else(invalid)?;
+            let requirement: Requirement = requirement.parse().map_err(|_| invalid())?;
+            requirements.push((user.trim().to_string(), requirement));
+        }
+    }
+    let ca_file = settings.optional("LISTEN_TLS_CA");
+    let (cert_file, key_file) = match (
+        settings.optional("LISTEN_TLS_CERT"),
+        settings.optional("LISTEN_TLS_KEY"),
+    ) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) if ca_file.is_none() && requirements.is_empty() => return Ok(None),
+        (_, Some(_)) | (None, None) => return Err(ConfigError::Missing("LISTEN_TLS_CERT")),
+        (Some(_), None) => return Err(ConfigError::Missing("LISTEN_TLS_KEY")),
+    };
+    if ca_file.is_none() && requirements.iter().any(|(_, r)| r.needs_certificate()) {
+        return Err(ConfigError::Missing("LISTEN_TLS_CA"));
+    }
+    Ok(Some(ClientTlsConfig {
+        cert_file,
+        key_file,
+        ca_file,
+        requirements,
+    }))
+}
+
+// TLS inside the tunnel's stream would leave it nothing to compress, so the sessions through it
+// go without, and an sslmode that insists on TLS can't be had with it.
+fn db_tunnel(settings: &Settings, tls: &TlsConfig) -> Result<Option<String>, ConfigError> {
+    let tunnel = settings.optional("DB_TUNNEL");
+    if tunnel.is_some() && matches!(tls.mode, SslMode::Require | SslMode::VerifyFull) {
+        return Err(ConfigError::Invalid {
+            var: "DB_SSLMODE",
+            value: tls.mode.to_string(),
+        });
+    }
+    Ok(tunnel)
+}
+
+// DB_STANDBY_HOSTS is a comma-separated list of hosts. A tunnel goes to the one server, so
+// there's nothing to fail over to through one.
+fn db_standby_hosts(settings: &Settings) -> Result<Vec<String>, ConfigError> {
+    let Some(hosts) = settings.optional("DB_STANDBY_HOSTS") else {
+        return Ok(Vec::new());
+    };
+    if settings.optional("DB_TUNNEL").is_some() {
+        return Err(ConfigError::Invalid {
+            var: "DB_STANDBY_HOSTS",
+            value: hosts,
+        });
+    }
+    Ok(hosts
+        .split(',')
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .collect())
+}
+
+// ESTIMATED_COUNT_TABLES is a comma-separated list of `table` or `db.table`; a table without a
+// database is the table of that name in any database.
+fn estimated_counts(settings: &Settings) -> Vec<ObjectName> {
+    let Some(tables) = settings.optional("ESTIMATED_COUNT_TABLES") else {
+        return Vec::new();
+    };
+    tables
+        .split(',')
+        .map(|table| table.trim().to_lowercase())
+        .filter(|table| !table.is_empty())
+        .map(|table| match table.split_once('.') {
+            Some((schema, name)) => ObjectName {
+                schema: Some(schema.to_string()),
+                name: name.to_string(),
+            },
+            None => ObjectName {
+                schema: None,
+                name: table,
+            },
+        })
+        .collect()
+}
+
+// Accepts `YYYY-MM-DD HH:MM:SS[.ffffff]`, or a date alone for midnight.
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+}
+
+// Where settings are read from: the environment, then the config file. The file's settings
+// are kept under their variables' names.
+#[derive(Default)]
+struct Settings {
+    file: HashMap<String, String>,
+    // The names looked up, to tell the file's unknown settings from the rest.
+    read: RefCell<HashSet<String>>,
+}
+
+impl Settings {
+    // The settings in the TOML file at `path`.
+    fn from_file(path: &str) -> Result<Settings, ConfigError> {
+        let file_error = |error: String| ConfigError::File {
+            path: path.to_string(),
+            error,
+        };
+        let text = fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+        let document: Document = text.parse().map_err(|e| file_error(format!("{}", e)))?;
+        let mut file = HashMap::new();
+        flatten(document.iter(), "", &mut file).map_err(file_error)?;
+        Ok(Settings {
+            file,
+            read: RefCell::default(),
+        })
+    }
+
+    fn required(&self, var: &'static str) -> Result<String, ConfigError> {
+        self.optional(var).ok_or(ConfigError::Missing(var))
+    }
+
+    // Boolean settings accept 1/0, true/false, on/off and yes/no; unset means off.
+    fn flag(&self, var: &'static str) -> Result<bool, ConfigError> {
+        let Some(value) = self.optional(var) else {
+            return Ok(false);
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "on" | "yes" => Ok(true),
+            "0" | "false" | "off" | "no" => Ok(false),
+            _ => Err(ConfigError::Invalid { var, value }),
+        }
+    }
+
+    fn number<T: FromStr>(&self, var: &'static str, default: T) -> Result<T, ConfigError> {
+        match self.optional(var) {
+            None => Ok(default),
+            Some(value) => value
+                .parse()
+                .map_err(|_| ConfigError::Invalid { var, value }),
+        }
+    }
+
+    fn optional(&self, var: &str) -> Option<String> {
+        self.read.borrow_mut().insert(var.to_string());
+        let set = |value: &String| !value.is_empty();
+        env::var(var)
+            .ok()
+            .filter(set)
+            .or_else(|| self.file.get(var).cloned().filter(set))
+    }
+}
+
+// Adds the settings of a TOML table to `settings`, named by their path in the file.
+fn flatten<'a>(
+    entries: impl Iterator<Item = (&'a str, &'a Item)>,
+    prefix: &str,
+    settings: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (key, item) in entries {
+        let name = format!("{}{}", prefix, key.to_uppercase());
+        match item {
+            Item::Table(table) => flatten(table.iter(), &format!("{}_", name), settings)?,
+            Item::Value(Value::InlineTable(table)) => {
+                let table = table.clone().into_table();
+                flatten(table.iter(), &format!("{}_", name), settings)?
+            }
+            Item::Value(value) => {
+                let text = setting_text(value)
+                    .ok_or_else(|| format!("{} isn't a string, number, boolean or list", key))?;
+                settings.insert(name, text);
+            }
+            Item::ArrayOfTables(_) | Item::None => {
+                return Err(format!("{} isn't a string, number, boolean or list", key))
+            }
+        }
+    }
+    Ok(())
+}
+
+// A value from the file as it would be written in the environment; lists are comma-separated.
+fn setting_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.value().clone()),
+        Value::Integer(i) => Some(i.value().to_string()),
+        Value::Float(f) => Some(f.value().to_string()),
+        Value::Boolean(b) => Some(b.value().to_string()),
+        Value::Datetime(d) => Some(d.value().to_string()),
+        Value::Array(array) => {
+            let items: Option<Vec<String>> = array.iter().map(setting_text).collect();
+            Some(items?.join(","))
+        }
+        Value::InlineTable(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_listener_is_on_loopback_only() {
+        assert!(is_loopback("127.0.0.1:3307"));
+        assert!(is_loopback("[::1]:3307"));
+        assert!(is_loopback("localhost:3307"));
+        assert!(!is_loopback("0.0.0.0:3307"));
+        assert!(!is_loopback("[::]:3307"));
+        assert!(!is_loopback("10.0.0.5:3307"));
+        assert!(!is_loopback("db.example.com:3307"));
+        assert!(!is_loopback("localhost"));
+    }
+
+    #[test]
+    fn zero_dates_sentinel_is_a_postgresql_date() {
+        let settings = |value: &str| Settings {
+            file: HashMap::from([("ZERO_DATES".to_string(), value.to_string())]),
+            read: RefCell::default(),
+        };
+        assert_eq!(
+            zero_dates(&settings("null-as-zero")).unwrap(),
+            ZeroDates::NullAsZero
+        );
+        assert_eq!(
+            zero_dates(&settings("0001-01-01")).unwrap(),
+            ZeroDates::Sentinel(chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap())
+        );
+        assert!(zero_dates(&settings("0000-01-01")).is_err());
+        assert!(zero_dates(&settings("0000-00-00")).is_err());
+    }
+}
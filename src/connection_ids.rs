@@ -0,0 +1,102 @@
+// The ids of the proxy's client connections: the thread id the handshake gives the client, what
+// CONNECTION_ID() returns, and what SHOW PROCESSLIST lists and KILL takes.
+//
+// Ids count up from 1 and wrap around after u32::MAX, skipping 0, which clients take for no
+// connection. An id isn't given out while its connection is open, nor for a minute after it
+// ends, so that a KILL sent for a connection that has just gone doesn't end the next one to be
+// given its id.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+
+// How long an id is held back after its connection ends.
+pub const REUSE_DELAY: Duration = Duration::from_secs(60);
+
+/// The connection ids in use, shared by all connections.
+pub struct ConnectionIds {
+    state: Mutex<State>,
+    reuse_delay: Duration,
+}
+
+struct State {
+    next: u32,
+    in_use: HashSet<u32>,
+    // The ids released within the reuse delay, with when, oldest first, and the same ids to look
+    // up.
+    released: VecDeque<(u32, Instant)>,
+    held_back: HashSet<u32>,
+}
+
+/// An id given to a connection, released when dropped.
+pub struct ConnectionId {
+    id: u32,
+    ids: Arc<ConnectionIds>,
+}
+
+impl ConnectionIds {
+    pub fn new(reuse_delay: Duration) -> ConnectionIds {
+        ConnectionIds {
+            state: Mutex::new(State {
+                next: 1,
+                in_use: HashSet::new(),
+                released: VecDeque::new(),
+                held_back: HashSet::new(),
+            }),
+            reuse_delay,
+        }
+    }
+
+    /// The next id free to give out, or error 1040 if every one is taken.
+    pub fn allocate(self: &Arc<Self>) -> Result<ConnectionId, MysqlError> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        while let Some(&(id, at)) = state.released.front() {
+            if now.duration_since(at) < self.reuse_delay {
+                break;
+            }
+            state.released.pop_front();
+            state.held_back.remove(&id);
+        }
+        for _ in 0..u32::MAX {
+            let id = state.next;
+            state.next = state.next.checked_add(1).unwrap_or(1);
+            if !state.in_use.contains(&id) && !state.held_back.contains(&id) {
+                state.in_use.insert(id);
+                return Ok(ConnectionId {
+                    id,
+                    ids: Arc::clone(self),
+                });
+            }
+        }
+        Err(MysqlError::new(
+            ErrorKind::ER_CON_COUNT_ERROR,
+            "Too many connections",
+        ))
+    }
+
+    fn release(&self, id: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.in_use.remove(&id);
+        if !self.reuse_delay.is_zero() {
+            state.released.push_back((id, Instant::now()));
+            state.held_back.insert(id);
+        }
+    }
+}
+
+impl ConnectionId {
+    pub fn get(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for ConnectionId {
+    fn drop(&mut self) {
+        self.ids.release(self.id);
+    }
+}
@@ -0,0 +1,86 @@
+// Server-side cursors, for clients that read a large result a batch at a time: JDBC with
+// useCursorFetch=true and a fetch size, say. A COM_STMT_EXECUTE with the CURSOR_TYPE_READ_ONLY
+// flag, of a statement that returns rows, opens a cursor on the PostgreSQL session rather than
+// sending them:
+//
+//   DECLARE postmyrustache_cursor_3 NO SCROLL CURSOR WITH HOLD FOR SELECT ... WHERE id > $1
+//
+// and its reply has the statement's columns, with SERVER_STATUS_CURSOR_EXISTS, but no rows. Each
+// COM_STMT_FETCH then reads the next batch with FETCH FORWARD and sends it in the binary
+// protocol, so only a batch is held at a time; the batch that runs out of rows says so with
+// SERVER_STATUS_LAST_ROW_SENT, and the cursor is closed.
+//
+// A statement has one cursor, as in MySQL: executing it again, or closing it, closes the one it
+// had, and COM_STMT_FETCH for a statement without one fails with error 1421. WITH HOLD keeps a
+// cursor open after the transaction it was opened in commits, as a MySQL cursor is; outside a
+// transaction, where DECLARE commits as it runs, PostgreSQL reads the rows there and then and
+// keeps them on its side, much as MySQL materializes a cursor's rows. A cursor opened in a
+// transaction that is rolled back goes with it. COM_RESET_CONNECTION closes every cursor, as
+// does losing the PostgreSQL session.
+//
+// The rows fetched aren't compared by the shadow, nor kept in the result cache.
+
+use std::io;
+
+use mysql_common::value::Value;
+use opensrv_mysql::{Column, ErrorKind, ToMysqlValue};
+
+use crate::error::MysqlError;
+
+/// An open cursor: its name on the session, and the columns its rows have.
+pub struct Cursor {
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+impl Cursor {
+    /// The cursor of prepared statement `statement`, of `columns`.
+    pub fn new(statement: u32, columns: Vec<Column>) -> Cursor {
+        Cursor {
+            name: format!("postmyrustache_cursor_{}", statement),
+            columns,
+        }
+    }
+
+    /// The statement opening the cursor for `sql`, the statement translated.
+    pub fn declare(&self, sql: &str) -> String {
+        let sql = sql.trim().trim_end_matches(';');
+        format!(
+            "DECLARE {} NO SCROLL CURSOR WITH HOLD FOR {}",
+            self.name, sql
+        )
+    }
+
+    /// The statement reading the next `rows` rows.
+    pub fn fetch(&self, rows: u32) -> String {
+        format!("FETCH FORWARD {} FROM {}", rows, self.name)
+    }
+
+    /// The statement closing the cursor.
+    pub fn close(&self) -> String {
+        format!("CLOSE {}", self.name)
+    }
+}
+
+/// `values`, a row of `columns`, as the payload of a row in the binary protocol: a header, the
+/// bitmap of the columns that are NULL, then the others' values.
+pub fn binary_row(values: &[Value], columns: &[Column]) -> io::Result<Vec<u8>> {
+    // The bitmap starts two bits in.
+    let bitmap = (values.len() + 7 + 2) / 8;
+    let mut row = vec![0; 1 + bitmap];
+    for (i, (value, column)) in values.iter().zip(columns).enumerate() {
+        match value.is_null() {
+            true => row[1 + (i + 2) / 8] |= 1 << ((i + 2) % 8),
+            false => value.to_mysql_bin(&mut row, column)?,
+        }
+    }
+    Ok(row)
+}
+
+/// The error of COM_STMT_FETCH for a statement without a cursor.
+pub fn no_open_cursor(statement: u32) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_STMT_HAS_NO_OPEN_CURSOR,
+        format!("The statement ({}) has no open cursor.", statement),
+    )
+}
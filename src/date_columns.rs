@@ -0,0 +1,390 @@
+// Zero and impossible dates given to date and datetime columns.
+//
+// The translator only coerces the strings it can tell are dates (see the translator's dates
+// module). A string such as '0000-00-00' or '2024-02-31' is coerced here once the column it is
+// given to or compared with is known to be a date, datetime or timestamp column:
+//
+//   INSERT INTO events (id, starts) VALUES (1, '0000-00-00')  ->  ... VALUES (1, NULL)
+//   UPDATE events SET starts = '2024-02-31' WHERE id = 1      ->  ... SET starts = NULL ...
+//   SELECT * FROM events WHERE starts = '0000-00-00'          ->  ... WHERE starts IS NULL
+//   SELECT * FROM events WHERE starts <> '0000-00-00'         ->  ... WHERE starts IS NOT NULL
+//
+// A date compared for equality with a date that becomes NULL takes IS [NOT] NULL, as `= NULL`
+// matches nothing; written the other way round, `'0000-00-00' = starts` takes IS [NOT] DISTINCT
+// FROM. With ZERO_DATES set to a sentinel the zero date is compared with the sentinel instead.
+// `WHERE note = '2024-02-31'` on a text column is left alone. The columns are looked up in the
+// catalog by name, among those of the tables the statement names, and only for statements that
+// hold such a string at all; a statement without a quote followed by a four-digit year and a dash
+// isn't even parsed. A column is recognized when it's the column list of an INSERT,
+// or next to the string across a comparison or assignment, BETWEEN or IN.
+
+use std::collections::HashMap;
+
+use tokio_postgres::{Client, Error};
+
+use crate::catalog::{self, ObjectName};
+use crate::translator::dates::{self, Coerced, DateModes};
+use crate::translator::{self, is_word, literals, Node, Token};
+
+// Words after which the statement names a table.
+const TABLE_BEFORE: &[&str] = &["FROM", "JOIN", "UPDATE", "INTO"];
+
+// Operators that compare or assign a column and a value.
+const COMPARISONS: &[&str] = &["=", "<>", "!=", "<", ">", "<=", ">="];
+
+/// The translated statement `translated` with the zero and impossible dates it gives to date
+/// columns coerced as `modes` has MySQL store them. `None` when there are none.
+pub async fn rewrite(
+    client: &Client,
+    translated: &str,
+    modes: DateModes,
+) -> Result<Option<String>, Error> {
+    if !may_hold_date(translated) {
+        return Ok(None);
+    }
+    let Ok(mut nodes) = translator::parse(translated) else {
+        return Ok(None);
+    };
+    if !has_impossible_date(&nodes, modes) {
+        return Ok(None);
+    }
+    let mut tables = Vec::new();
+    table_names(&nodes, &mut tables);
+    let mut columns = Columns::default();
+    for table in tables {
+        let info = catalog::table_columns(client, &table).await?;
+        columns.order.insert(
+            table.name.clone(),
+            info.iter().map(|c| c.name.clone()).collect(),
+        );
+        columns.dates.extend(
+            info.into_iter()
+                .filter(|c| is_date_type(&c.pg_type))
+                .map(|c| c.name),
+        );
+    }
+    if columns.dates.is_empty() {
+        return Ok(None);
+    }
+    let mut changed = coerce_insert(&mut nodes, &columns, modes);
+    changed |= coerce_compared(&mut nodes, &columns, modes);
+    Ok(changed.then(|| translator::render(&nodes)))
+}
+
+/// Whether a column of the PostgreSQL type `pg_type`, as format_type() prints it, holds dates.
+pub fn is_date_type(pg_type: &str) -> bool {
+    pg_type == "date" || pg_type.starts_with("timestamp")
+}
+
+// The date columns of the statement's tables, and each table's columns in definition order.
+#[derive(Default)]
+struct Columns {
+    dates: Vec<String>,
+    order: HashMap<String, Vec<String>>,
+}
+
+impl Columns {
+    fn is_date(&self, node: &Node) -> bool {
+        identifier(node).is_some_and(|name| self.dates.contains(&name))
+    }
+}
+
+// Whether the SQL has a string starting with a date's four-digit year and dash, short of which
+// there's nothing to coerce. This runs on every statement, so it doesn't tokenize.
+fn may_hold_date(sql: &str) -> bool {
+    sql.as_bytes()
+        .windows(6)
+        .any(|w| w[0] == b'\'' && w[1..5].iter().all(u8::is_ascii_digit) && w[5] == b'-')
+}
+
+fn has_impossible_date(nodes: &[Node], modes: DateModes) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Group(inner) => has_impossible_date(inner, modes),
+        Node::Token(_) => coerced(node, modes).is_some(),
+    })
+}
+
+// What a string literal becomes, when it's a zero or impossible date.
+fn coerced(node: &Node, modes: DateModes) -> Option<Node> {
+    let Node::Token(Token::String(raw)) = node else {
+        return None;
+    };
+    match dates::coerce(&literals::pg_string_value(raw)?, modes)? {
+        Coerced::Null => Some(Node::Token(Token::Word("NULL".to_string()))),
+        Coerced::Clamped(date) => Some(Node::Token(Token::String(literals::pg_string(&date)))),
+    }
+}
+
+// Replaces the string literal `node` with what it's stored as. Whether it changed.
+fn coerce(node: &mut Node, modes: DateModes) -> bool {
+    match coerced(node, modes) {
+        Some(replacement) => {
+            *node = replacement;
+            true
+        }
+        None => false,
+    }
+}
+
+// The tables named anywhere in the statement.
+fn table_names(nodes: &[Node], tables: &mut Vec<ObjectName>) {
+    let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+    for (i, node) in significant.iter().enumerate() {
+        if let Node::Group(inner) = node {
+            table_names(inner, tables);
+            continue;
+        }
+        if !TABLE_BEFORE.iter().any(|word| is_word(Some(*node), word)) {
+            continue;
+        }
+        let Some(first) = significant.get(i + 1).and_then(|n| identifier(n)) else {
+            continue;
+        };
+        let dotted = matches!(significant.get(i + 2), Some(Node::Token(t)) if t.is_operator("."));
+        let table = match significant.get(i + 3).and_then(|n| identifier(n)) {
+            Some(name) if dotted => ObjectName {
+                schema: Some(first),
+                name,
+            },
+            _ => ObjectName {
+                schema: None,
+                name: first,
+            },
+        };
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+}
+
+// `INSERT INTO table [(columns)] VALUES (row), ...`: the values of the rows given to date columns.
+fn coerce_insert(nodes: &mut [Node], columns: &Columns, modes: DateModes) -> bool {
+    if !translator::statement_starts_with(nodes, &["INSERT", "INTO"]) {
+        return false;
+    }
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    let mut at = 2;
+    let Some(mut table) = significant.get(at).and_then(|&i| identifier(&nodes[i])) else {
+        return false;
+    };
+    if matches!(significant.get(at + 1).map(|&i| &nodes[i]), Some(Node::Token(t)) if t.is_operator("."))
+    {
+        at += 2;
+        match significant.get(at).and_then(|&i| identifier(&nodes[i])) {
+            Some(name) => table = name,
+            None => return false,
+        }
+    }
+    at += 1;
+    let named: Vec<String> = match significant.get(at).map(|&i| &nodes[i]) {
+        Some(Node::Group(inner)) => {
+            at += 1;
+            match translator::split_args(inner)
+                .into_iter()
+                .map(
+                    |arg| match arg.iter().filter(|n| !n.is_trivia()).collect::<Vec<_>>()[..] {
+                        [node] => identifier(node),
+                        _ => None,
+                    },
+                )
+                .collect()
+            {
+                Some(named) => named,
+                None => return false,
+            }
+        }
+        _ => match columns.order.get(&table) {
+            Some(order) => order.clone(),
+            None => return false,
+        },
+    };
+    if !is_word(significant.get(at).map(|&i| &nodes[i]), "VALUES") {
+        return false;
+    }
+    let dated: Vec<bool> = named
+        .iter()
+        .map(|name| columns.dates.contains(name))
+        .collect();
+    let mut changed = false;
+    for &i in &significant[at + 1..] {
+        match &mut nodes[i] {
+            Node::Group(row) => {
+                let mut column = 0;
+                for node in row.iter_mut() {
+                    match node {
+                        Node::Token(Token::Comma) => column += 1,
+                        _ if dated.get(column) == Some(&true) => changed |= coerce(node, modes),
+                        _ => {}
+                    }
+                }
+            }
+            Node::Token(Token::Comma) => {}
+            _ => break,
+        }
+    }
+    changed
+}
+
+// Strings compared with or assigned to a date column, anywhere in the statement: `column = '...'`
+// and `'...' = column`, `column [NOT] BETWEEN '...' AND '...'` and `column [NOT] IN ('...', ...)`.
+// An equality with a string that becomes NULL turns into a test for NULL.
+fn coerce_compared(nodes: &mut [Node], columns: &Columns, modes: DateModes) -> bool {
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    let node = |k: usize| significant.get(k).map(|&i| &nodes[i]);
+    let operator = |k: usize| matches!(node(k), Some(Node::Token(t)) if COMPARISONS.iter().any(|op| t.is_operator(op)));
+    let date_column = |k: usize| node(k).is_some_and(|n| columns.is_date(n));
+    // The column at `k`, or the last part of a dotted name starting there.
+    let column_after = |k: usize| match matches!(node(k + 1), Some(Node::Token(t)) if t.is_operator("."))
+    {
+        true => date_column(k + 2),
+        false => date_column(k),
+    };
+    // The column before `word`, past a NOT.
+    let column_before = |k: usize| match is_word(node(k.wrapping_sub(1)), "NOT") {
+        true => date_column(k.wrapping_sub(2)),
+        false => date_column(k.wrapping_sub(1)),
+    };
+
+    // Each string, with the operator it's compared by and whether the string comes first.
+    let mut targets: Vec<(usize, Option<(usize, bool)>)> = Vec::new();
+    let mut lists = Vec::new();
+    for (k, &i) in significant.iter().enumerate() {
+        let before = k.wrapping_sub(1);
+        match node(k) {
+            Some(Node::Group(_)) if is_word(node(before), "IN") && column_before(before) => {
+                lists.push(i);
+            }
+            Some(Node::Token(Token::String(_))) => {
+                let compared = if operator(before) && date_column(k.wrapping_sub(2)) {
+                    Some((significant[before], false))
+                } else if operator(k + 1) && column_after(k + 2) {
+                    Some((significant[k + 1], true))
+                } else {
+                    None
+                };
+                let between = is_word(node(before), "BETWEEN") && column_before(before);
+                let and = is_word(node(before), "AND")
+                    && is_word(node(k.wrapping_sub(3)), "BETWEEN")
+                    && column_before(k.wrapping_sub(3));
+                if compared.is_some() || between || and {
+                    targets.push((i, compared));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut changed = false;
+    for (i, compared) in targets {
+        if !coerce(&mut nodes[i], modes) {
+            continue;
+        }
+        changed = true;
+        let (Some((at, first)), Node::Token(Token::Word(_))) = (compared, &nodes[i]) else {
+            continue;
+        };
+        let Node::Token(Token::Operator(op)) = &nodes[at] else {
+            continue;
+        };
+        let test = match (op.as_str(), first) {
+            ("=", false) => " IS ",
+            ("<>" | "!=", false) => " IS NOT ",
+            ("=", true) => " IS NOT DISTINCT FROM ",
+            ("<>" | "!=", true) => " IS DISTINCT FROM ",
+            _ => continue,
+        };
+        nodes[at] = Node::Token(Token::Word(test.to_string()));
+    }
+    for i in lists {
+        if let Node::Group(inner) = &mut nodes[i] {
+            for node in inner.iter_mut() {
+                changed |= coerce(node, modes);
+            }
+        }
+    }
+    for node in nodes.iter_mut() {
+        if let Node::Group(inner) = node {
+            changed |= coerce_compared(inner, columns, modes);
+        }
+    }
+    changed
+}
+
+// A name in the translated statement, as in implicit_defaults: a bare word folds to lower case.
+fn identifier(node: &Node) -> Option<String> {
+    match node {
+        Node::Token(Token::Word(word)) => Some(word.to_lowercase()),
+        Node::Token(Token::DoubleQuoted(quoted)) => {
+            Some(quoted[1..quoted.len() - 1].replace("\"\"", "\""))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::dates::ZeroDates;
+    use chrono::NaiveDate;
+
+    fn compared(sql: &str, modes: DateModes) -> String {
+        let mut nodes = translator::parse(sql).unwrap();
+        let columns = Columns {
+            dates: vec!["starts".to_string()],
+            order: HashMap::new(),
+        };
+        coerce_compared(&mut nodes, &columns, modes);
+        translator::render(&nodes)
+    }
+
+    #[test]
+    fn compares_dates_that_become_null_as_null() {
+        let modes = DateModes::default();
+        assert_eq!(
+            compared("SELECT * FROM events WHERE starts = '0000-00-00'", modes),
+            "SELECT * FROM events WHERE starts  IS  NULL"
+        );
+        assert_eq!(
+            compared("SELECT * FROM events WHERE e.starts<>'2024-02-31'", modes),
+            "SELECT * FROM events WHERE e.starts IS NOT NULL"
+        );
+        assert_eq!(
+            compared("SELECT * FROM events WHERE '0000-00-00' != starts", modes),
+            "SELECT * FROM events WHERE NULL  IS DISTINCT FROM  starts"
+        );
+        assert_eq!(
+            compared("SELECT * FROM events WHERE starts < '0000-00-00'", modes),
+            "SELECT * FROM events WHERE starts < NULL"
+        );
+        assert_eq!(
+            compared("SELECT * FROM events WHERE note = '0000-00-00'", modes),
+            "SELECT * FROM events WHERE note = '0000-00-00'"
+        );
+    }
+
+    #[test]
+    fn compares_the_zero_date_with_the_sentinel() {
+        let modes = DateModes {
+            zero_dates: ZeroDates::Sentinel(NaiveDate::from_ymd_opt(1, 1, 1).unwrap()),
+            ..DateModes::default()
+        };
+        assert_eq!(
+            compared("SELECT * FROM events WHERE starts = '0000-00-00'", modes),
+            "SELECT * FROM events WHERE starts = '0001-01-01'"
+        );
+    }
+
+    #[test]
+    fn only_parses_statements_with_a_date_shaped_string() {
+        assert!(may_hold_date(
+            "SELECT * FROM events WHERE starts = '0000-00-00'"
+        ));
+        assert!(may_hold_date("UPDATE events SET starts = E'2024-02-31'"));
+        assert!(!may_hold_date("SELECT * FROM events WHERE id = 2024"));
+        assert!(!may_hold_date("SELECT '2024'"));
+    }
+}
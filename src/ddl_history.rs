@@ -0,0 +1,255 @@
+// The history of the schema changes made through the proxy (DDL_HISTORY), for teams to see what
+// changed when and by whom without tooling of their own.
+//
+// A CREATE TABLE, ALTER TABLE, DROP TABLE, RENAME TABLE, CREATE INDEX or DROP INDEX that
+// succeeds is kept in `proxy_metadata.ddl_history` (created on first use, from a session of its
+// own so a failure there can't touch the client's transaction), with each table's definition
+// before and after, as SHOW CREATE TABLE gives it. A statement that changed nothing, such as
+// CREATE TABLE IF NOT EXISTS of a table that was there, isn't. The history is read as
+//
+//   SELECT executed_at, user_name, statement, before_definition, after_definition
+//     FROM proxy_stats.ddl_history WHERE table_name = 'orders' ORDER BY id
+//
+// where a definition is empty for a table that wasn't there, before a CREATE TABLE or after a
+// DROP TABLE. A renamed table's change is kept under its new name. Temporary tables aren't
+// kept.
+
+use std::error::Error;
+
+use tokio_postgres::Client;
+
+use crate::catalog::ObjectName;
+use crate::emulation::virtual_tables::{self, SCHEMA};
+use crate::emulation::{self, show_create};
+use crate::server::Upstream;
+use crate::translator::{self, Token};
+
+const TABLE: &str = "proxy_metadata.ddl_history";
+
+const CREATE_TABLE: &str = "CREATE SCHEMA IF NOT EXISTS proxy_metadata; \
+     CREATE TABLE IF NOT EXISTS proxy_metadata.ddl_history ( \
+       id bigserial PRIMARY KEY, \
+       executed_at timestamptz NOT NULL DEFAULT now(), \
+       user_name text NOT NULL, \
+       table_schema text NOT NULL, \
+       table_name text NOT NULL, \
+       statement text NOT NULL, \
+       before_definition text, \
+       after_definition text)";
+
+// The columns of proxy_stats.ddl_history.
+const COLUMNS: &[(&str, &str)] = &[
+    ("id", "bigint"),
+    ("executed_at", "text"),
+    ("user_name", "text"),
+    ("table_schema", "text"),
+    ("table_name", "text"),
+    ("statement", "text"),
+    ("before_definition", "text"),
+    ("after_definition", "text"),
+];
+
+/// A table a statement changes, by its name before the statement and after, which differ if it
+/// renames the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub before: ObjectName,
+    pub after: ObjectName,
+}
+
+/// A table's definition before the statement changing it ran; `None` if there was no table.
+#[derive(Debug, Clone)]
+pub struct Before {
+    target: Target,
+    definition: Option<String>,
+}
+
+/// The tables `sql`, a statement as the client sent it, changes the definition of.
+pub fn targets(sql: &str) -> Vec<Target> {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return Vec::new();
+    };
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    let same = |name: ObjectName| Target {
+        before: name.clone(),
+        after: name,
+    };
+    match tokens {
+        [create, table, rest @ ..] if create.is_word("CREATE") && table.is_word("TABLE") => {
+            let rest = match rest {
+                [if_, not, exists, rest @ ..]
+                    if if_.is_word("IF") && not.is_word("NOT") && exists.is_word("EXISTS") =>
+                {
+                    rest
+                }
+                rest => rest,
+            };
+            emulation::object_name(rest)
+                .map(|(name, _)| vec![same(name)])
+                .unwrap_or_default()
+        }
+        [alter, table, rest @ ..] if alter.is_word("ALTER") && table.is_word("TABLE") => {
+            let Some((name, rest)) = emulation::object_name(rest) else {
+                return Vec::new();
+            };
+            vec![Target {
+                after: renamed(rest).unwrap_or_else(|| name.clone()),
+                before: name,
+            }]
+        }
+        [drop, table, rest @ ..] if drop.is_word("DROP") && table.is_word("TABLE") => {
+            let rest = match rest {
+                [if_, exists, rest @ ..] if if_.is_word("IF") && exists.is_word("EXISTS") => rest,
+                rest => rest,
+            };
+            rest.split(|token| *token == Token::Comma)
+                .filter_map(|name| emulation::object_name(name).map(|(name, _)| same(name)))
+                .collect()
+        }
+        [rename, table, rest @ ..] if rename.is_word("RENAME") && table.is_word("TABLE") => rest
+            .split(|token| *token == Token::Comma)
+            .filter_map(|pair| {
+                let (before, rest) = emulation::object_name(pair)?;
+                let (to, rest) = rest.split_first()?;
+                let (after, _) = emulation::object_name(rest).filter(|_| to.is_word("TO"))?;
+                Some(Target { before, after })
+            })
+            .collect(),
+        // CREATE [UNIQUE | FULLTEXT | SPATIAL] INDEX name ... ON table, DROP INDEX name ON table
+        [first, rest @ ..] if first.is_word("CREATE") || first.is_word("DROP") => {
+            let Some(index) = rest.iter().position(|t| t.is_word("INDEX")) else {
+                return Vec::new();
+            };
+            let kinds = ["UNIQUE", "FULLTEXT", "SPATIAL"];
+            if !rest[..index]
+                .iter()
+                .all(|t| kinds.iter().any(|k| t.is_word(k)))
+            {
+                return Vec::new();
+            }
+            let on = rest[index..].iter().position(|t| t.is_word("ON"));
+            on.and_then(|on| emulation::object_name(&rest[index + on + 1..]))
+                .map(|(name, _)| vec![same(name)])
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// The new name of ALTER TABLE ... RENAME [TO | AS] name, given what follows the table's name.
+fn renamed(tokens: &[Token]) -> Option<ObjectName> {
+    let at = tokens.iter().position(|t| t.is_word("RENAME"))?;
+    let rest = &tokens[at + 1..];
+    let rest = match rest.first() {
+        Some(column) if ["COLUMN", "INDEX", "KEY"].iter().any(|w| column.is_word(w)) => {
+            return None
+        }
+        Some(to) if to.is_word("TO") || to.is_word("AS") => &rest[1..],
+        _ => rest,
+    };
+    emulation::object_name(rest).map(|(name, _)| name)
+}
+
+/// The definitions of `targets` as they are now, before the statement changing them runs, on
+/// `session`, the client's. Temporary tables are left out.
+pub async fn before(
+    session: &Client,
+    targets: Vec<Target>,
+) -> Result<Vec<Before>, tokio_postgres::Error> {
+    let mut befores = Vec::with_capacity(targets.len());
+    for target in targets {
+        let definition = show_create::table_definition(session, &target.before).await?;
+        if definition
+            .as_deref()
+            .is_some_and(|d| d.starts_with("CREATE TEMPORARY TABLE"))
+        {
+            continue;
+        }
+        befores.push(Before { target, definition });
+    }
+    Ok(befores)
+}
+
+/// Keeps `statement`, run by `user`, with the definitions of the tables it changed, `befores`
+/// and now. `session` is the one that ran it; the history is written from a session of
+/// `upstream`'s.
+pub async fn record(
+    session: &Client,
+    upstream: &dyn Upstream,
+    user: &str,
+    statement: &str,
+    befores: Vec<Before>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut changes = Vec::new();
+    for Before { target, definition } in befores {
+        let after = show_create::table_definition(session, &target.after).await?;
+        if after
+            .as_deref()
+            .is_some_and(|d| d.starts_with("CREATE TEMPORARY TABLE"))
+            || after == definition
+        {
+            continue;
+        }
+        let schema: String = match &target.after.schema {
+            Some(schema) => schema.clone(),
+            None => session
+                .query_one("SELECT current_schema()::text", &[])
+                .await?
+                .get(0),
+        };
+        changes.push((schema, target.after.name, definition, after));
+    }
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let metadata = upstream.connect().await?;
+    metadata.batch_execute(CREATE_TABLE).await?;
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    for (schema, table, before, after) in changes {
+        metadata
+            .execute(
+                &format!(
+                    "INSERT INTO {} (user_name, table_schema, table_name, statement, \
+                     before_definition, after_definition) VALUES ($1, $2, $3, $4, $5, $6)",
+                    TABLE
+                ),
+                &[&user, &schema, &table, &statement, &before, &after],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+// Whether any statement has been kept, that is whether the table is there.
+async fn kept(client: &Client) -> Result<bool, tokio_postgres::Error> {
+    Ok(client
+        .query_one("SELECT to_regclass($1::text) IS NOT NULL", &[&TABLE])
+        .await?
+        .get(0))
+}
+
+/// The translated statement `sql` with its references to proxy_stats.ddl_history replaced by the
+/// statements kept. `None` if it has none.
+pub async fn rewrite(client: &Client, sql: &str) -> Result<Option<String>, tokio_postgres::Error> {
+    let lowered = sql.to_ascii_lowercase();
+    if !lowered.contains(SCHEMA) || !lowered.contains("ddl_history") {
+        return Ok(None);
+    }
+    let history = match kept(client).await? {
+        true => format!(
+            "SELECT id, to_char(executed_at, 'YYYY-MM-DD HH24:MI:SS') AS executed_at, user_name, \
+               table_schema, table_name, statement, \
+               COALESCE(before_definition, '') AS before_definition, \
+               COALESCE(after_definition, '') AS after_definition \
+             FROM {}",
+            TABLE
+        ),
+        false => virtual_tables::values_query(COLUMNS, &[]),
+    };
+    Ok(virtual_tables::replace_tables(sql, &|schema, table| {
+        (schema == SCHEMA && table == "ddl_history").then(|| history.clone())
+    }))
+}
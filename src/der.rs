@@ -0,0 +1,29 @@
+// The little of DER, the encoding of ASN.1, the proxy reads: the public keys of
+// AUTH_JWT_PUBLIC_KEY (see auth/jwt.rs), and the names in the certificates clients log in with
+// (see client_tls.rs).
+
+/// The tag and contents of the element at the start of `der`, and what follows it.
+pub fn next(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&length, rest) = rest.split_first()?;
+    let (length, rest) = match length {
+        0..=0x7f => (usize::from(length), rest),
+        0x81 | 0x82 => {
+            let bytes = usize::from(length & 0x7f);
+            let length = rest
+                .get(..bytes)?
+                .iter()
+                .fold(0, |length, &byte| length << 8 | usize::from(byte));
+            (length, &rest[bytes..])
+        }
+        _ => return None,
+    };
+    let (contents, rest) = (rest.len() >= length).then(|| rest.split_at(length))?;
+    Some((tag, contents, rest))
+}
+
+/// The contents of the element at the start of `der` if it has `tag`, and what follows it.
+pub fn element(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (found, contents, rest) = next(der)?;
+    (found == tag).then_some((contents, rest))
+}
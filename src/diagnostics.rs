@@ -0,0 +1,122 @@
+// Per-session diagnostics: the warnings and errors raised by the last statement, which
+// MySQL clients read back with SHOW WARNINGS, and the session's most recent errors for SHOW
+// ERRORS. The warning count is also kept in the connection's protocol `Status`, for the OK
+// packets that end each reply.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::protocol::Status;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Warning => "Warning",
+            Level::Error => "Error",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    // The MySQL error number.
+    pub code: u16,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct Diagnostics {
+    current: Vec<Diagnostic>,
+    // The last `error_history` errors of the session, oldest first. Unlike `current` they
+    // survive later statements, so a client can still fetch the details after it has moved on.
+    errors: VecDeque<Diagnostic>,
+    error_history: usize,
+    status: Arc<Status>,
+    // The code of the last error sent to the client, until the statement's log record takes it.
+    last_error: Option<u16>,
+}
+
+impl Diagnostics {
+    pub fn new(error_history: usize, status: Arc<Status>) -> Self {
+        Diagnostics {
+            current: Vec::new(),
+            errors: VecDeque::new(),
+            error_history,
+            status,
+            last_error: None,
+        }
+    }
+
+    /// Forgets the previous statement's diagnostics; called as each new statement starts.
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.status.set_warnings(0);
+    }
+
+    /// Forgets the session's errors as well, as COM_RESET_CONNECTION does.
+    pub fn reset(&mut self) {
+        self.clear();
+        self.errors.clear();
+    }
+
+    pub fn push(&mut self, level: Level, kind: ErrorKind, message: impl Into<String>) {
+        self.push_code(level, kind as u16, message.into());
+    }
+
+    fn push_code(&mut self, level: Level, code: u16, message: String) {
+        let diagnostic = Diagnostic {
+            level,
+            code,
+            message,
+        };
+        if level == Level::Error && self.error_history > 0 {
+            if self.errors.len() == self.error_history {
+                self.errors.pop_front();
+            }
+            self.errors.push_back(diagnostic.clone());
+        }
+        self.current.push(diagnostic);
+        self.status.set_warnings(self.warning_count());
+    }
+
+    /// Records an error that is being sent to the client.
+    pub fn push_error(&mut self, error: &MysqlError) {
+        self.push_code(Level::Error, error.code(), error.message.clone());
+        self.status.set_error_code(error.code());
+        self.last_error = Some(error.code());
+    }
+
+    /// The code of the error sent to the client since this was last called, if any.
+    pub fn take_error(&mut self) -> Option<u16> {
+        self.last_error.take()
+    }
+
+    pub fn all(&self) -> &[Diagnostic] {
+        &self.current
+    }
+
+    /// The session's recent errors, oldest first.
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.errors.iter()
+    }
+
+    /// The count MySQL reports in OK packets and as `@@warning_count`.
+    pub fn warning_count(&self) -> u16 {
+        self.current.len().try_into().unwrap_or(u16::MAX)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+}
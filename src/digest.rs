@@ -0,0 +1,72 @@
+// Statement digests: a statement with its values taken out, and a fingerprint hashing that, so
+// that the same query run with other values is known as one:
+//
+//   SELECT * FROM t WHERE id IN (1, 2, 3) AND name = 'x'
+//   ->  SELECT * FROM t WHERE id IN (?, ...) AND name = ?
+//
+// Literals become `?`, comments go and whitespace is a single space; runs of values, IN lists
+// and the rows of a multi-row INSERT, are shortened to the first. The fingerprint is the first
+// 8 bytes of the digest text's SHA-256, in hex.
+//
+// The tracing spans and the JSON log carry the digests of the statements, and Stats totals the
+// statements run by digest for SHOW PROXY DIGESTS and proxy_stats.digests.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use sha2::{Digest as _, Sha256};
+
+use crate::translator::lexer::{self, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub fingerprint: String,
+    pub text: String,
+}
+
+impl Digest {
+    pub fn of(sql: &str) -> Digest {
+        let text = normalize(sql);
+        Digest {
+            fingerprint: fingerprint(&text),
+            text,
+        }
+    }
+}
+
+/// `sql` with its values taken out, the digest text.
+pub fn normalize(sql: &str) -> String {
+    let Ok(tokens) = lexer::tokenize(sql) else {
+        return sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    };
+    let mut out = String::with_capacity(sql.len());
+    let mut space = false;
+    for token in tokens {
+        match token {
+            Token::Whitespace(_) | Token::Comment(_) => {
+                space = !out.is_empty();
+                continue;
+            }
+            _ if space => out.push(' '),
+            _ => {}
+        }
+        space = false;
+        match token {
+            Token::String(_) | Token::Number(_) | Token::Hex(_) | Token::Bit(_) => out.push('?'),
+            token => out.push_str(&token.to_string()),
+        }
+    }
+    static VALUES: OnceLock<Regex> = OnceLock::new();
+    static ROWS: OnceLock<Regex> = OnceLock::new();
+    let values = VALUES.get_or_init(|| Regex::new(r"\?(?:\s*,\s*\?)+").unwrap());
+    let rows = ROWS.get_or_init(|| Regex::new(r"(\([^()]*\))(?:\s*,\s*\([^()]*\))+").unwrap());
+    let out = values.replace_all(&out, "?, ...");
+    rows.replace_all(&out, "$1, ...").into_owned()
+}
+
+fn fingerprint(text: &str) -> String {
+    Sha256::digest(text.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,72 @@
+// SHOW PROXY CACHES: how full the proxy's caches are and how well they do, for sizing them
+// without scraping metrics, and the PostgreSQL sessions open with each server.
+//
+//   Cache            Target          Entries  Capacity  Hits   Misses  Evictions
+//   statement cache  NULL            412      2560      91204  412     0
+//   result cache     NULL            37       1024      5120   880     12
+//   sessions         db1.internal    10       NULL      NULL   NULL    NULL
+//
+// The statement cache's entries are those kept on every connection's session, and its
+// capacity STATEMENT_CACHE_SIZE for each connection open. A cache that is off has a capacity of
+// 0. Hits, misses and evictions are running totals, kept in STATS_FILE with the others.
+
+use crate::resultset::ResultSet;
+use crate::translator::{self, Token};
+
+/// How a cache is used.
+pub struct CacheUsage {
+    pub name: &'static str,
+    pub entries: u64,
+    pub capacity: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+pub fn parse(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    matches!(tokens, [show, proxy, caches]
+        if show.is_word("SHOW") && proxy.is_word("PROXY") && caches.is_word("CACHES"))
+}
+
+/// The caches, then each server with the `sessions` open with it.
+pub fn execute(caches: &[CacheUsage], sessions: &[(String, usize)]) -> ResultSet {
+    let mut result = ResultSet::new(&[
+        "Cache",
+        "Target",
+        "Entries",
+        "Capacity",
+        "Hits",
+        "Misses",
+        "Evictions",
+    ]);
+    for cache in caches {
+        result.push_row(vec![
+            Some(cache.name.to_string()),
+            None,
+            Some(cache.entries.to_string()),
+            Some(cache.capacity.to_string()),
+            Some(cache.hits.to_string()),
+            Some(cache.misses.to_string()),
+            Some(cache.evictions.to_string()),
+        ]);
+    }
+    for (target, open) in sessions {
+        result.push_row(vec![
+            Some("sessions".to_string()),
+            Some(target.clone()),
+            Some(open.to_string()),
+            None,
+            None,
+            None,
+            None,
+        ]);
+    }
+    result
+}
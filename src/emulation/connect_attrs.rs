@@ -0,0 +1,59 @@
+// performance_schema.session_connect_attrs and session_account_connect_attrs: the connection
+// attributes clients sent as they connected, a row for each, as in MySQL:
+//
+//   SELECT processlist_id, attr_value FROM performance_schema.session_connect_attrs
+//   WHERE attr_name = 'program_name'
+//
+// session_account_connect_attrs has only the connections of the user running the statement.
+// Both are also answered as information_schema tables. PROCESSLIST_ID is the Id SHOW
+// PROCESSLIST lists.
+
+use super::virtual_tables::{replace_tables, values_query};
+use crate::sessions::Sessions;
+use crate::translator::literals;
+
+const COLUMNS: &[(&str, &str)] = &[
+    ("processlist_id", "bigint"),
+    ("attr_name", "text"),
+    ("attr_value", "text"),
+    ("ordinal_position", "integer"),
+];
+
+/// `sql`, a translated statement, with the attribute tables it references replaced by their
+/// rows, as `user` sees them. `None` if it references neither.
+pub fn expand(sql: &str, sessions: &Sessions, user: &str) -> Option<String> {
+    if !sql.to_ascii_lowercase().contains("connect_attrs") {
+        return None;
+    }
+    replace_tables(sql, &|schema, table| {
+        if schema != "performance_schema" && schema != "information_schema" {
+            return None;
+        }
+        let own = match table {
+            "session_connect_attrs" => false,
+            "session_account_connect_attrs" => true,
+            _ => return None,
+        };
+        let rows: Vec<Vec<String>> = sessions
+            .processes()
+            .into_iter()
+            .filter(|process| !own || process.user.as_deref() == Some(user))
+            .flat_map(|process| {
+                let id = process.id;
+                process
+                    .connect_attrs
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, (name, value))| {
+                        vec![
+                            id.to_string(),
+                            literals::pg_string(&name),
+                            literals::pg_string(&value),
+                            i.to_string(),
+                        ]
+                    })
+            })
+            .collect();
+        Some(values_query(COLUMNS, &rows))
+    })
+}
@@ -0,0 +1,75 @@
+// SHOW WARNINGS, SHOW ERRORS and their COUNT(*) forms, answered from the session's diagnostics.
+
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::resultset::ResultSet;
+use crate::translator::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Show {
+    Warnings { limit: Option<usize> },
+    WarningCount,
+    Errors { limit: Option<usize> },
+    ErrorCount,
+}
+
+pub fn parse(tokens: &[Token]) -> Option<Show> {
+    let [show, rest @ ..] = tokens else {
+        return None;
+    };
+    if !show.is_word("SHOW") {
+        return None;
+    }
+    let (count, rest) = match rest {
+        [count, Token::LParen, star, Token::RParen, rest @ ..]
+            if count.is_word("COUNT") && star.is_operator("*") =>
+        {
+            (true, rest)
+        }
+        _ => (false, rest),
+    };
+    let (kind, limit) = match rest {
+        [kind] => (kind, None),
+        [kind, limit, Token::Number(n)] if !count && limit.is_word("LIMIT") => {
+            (kind, Some(n.parse().ok()?))
+        }
+        _ => return None,
+    };
+
+    match (kind.is_word("WARNINGS"), kind.is_word("ERRORS"), count) {
+        (true, _, false) => Some(Show::Warnings { limit }),
+        (true, _, true) => Some(Show::WarningCount),
+        (_, true, false) => Some(Show::Errors { limit }),
+        (_, true, true) => Some(Show::ErrorCount),
+        _ => None,
+    }
+}
+
+pub fn execute(diagnostics: &Diagnostics, show: Show) -> ResultSet {
+    match show {
+        Show::Warnings { limit } => list(diagnostics.all().iter(), limit),
+        Show::Errors { limit } => list(diagnostics.errors(), limit),
+        Show::WarningCount => count(
+            "@@session.warning_count",
+            diagnostics.warning_count().into(),
+        ),
+        Show::ErrorCount => count("@@session.error_count", diagnostics.error_count()),
+    }
+}
+
+fn list<'a>(diagnostics: impl Iterator<Item = &'a Diagnostic>, limit: Option<usize>) -> ResultSet {
+    let mut result = ResultSet::new(&["Level", "Code", "Message"]);
+    for d in diagnostics.take(limit.unwrap_or(usize::MAX)) {
+        result.push_row(vec![
+            Some(d.level.to_string()),
+            Some(d.code.to_string()),
+            Some(d.message.clone()),
+        ]);
+    }
+    result
+}
+
+fn count(name: &str, value: usize) -> ResultSet {
+    let mut result = ResultSet::new(&[name]);
+    result.push_row(vec![Some(value.to_string())]);
+    result
+}
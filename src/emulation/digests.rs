@@ -0,0 +1,65 @@
+// SHOW PROXY DIGESTS [LIMIT n]: the statements run since the proxy started, by digest, those
+// that took the longest in all first. The same data is in proxy_stats.digests, for filtering
+// with SQL.
+
+use std::time::Duration;
+
+use crate::resultset::ResultSet;
+use crate::stats::Stats;
+use crate::translator::Token;
+
+/// The number of digests to list, `None` for all of them.
+pub fn parse(tokens: &[Token]) -> Option<Option<usize>> {
+    match tokens {
+        [show, proxy, digests, rest @ ..]
+            if show.is_word("SHOW") && proxy.is_word("PROXY") && digests.is_word("DIGESTS") =>
+        {
+            match rest {
+                [] => Some(None),
+                [limit, Token::Number(count)] if limit.is_word("LIMIT") => {
+                    Some(Some(count.parse().ok()?))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn execute(stats: &Stats, limit: Option<usize>) -> ResultSet {
+    let mut result = ResultSet::new(&[
+        "Digest",
+        "Digest_text",
+        "Count",
+        "Errors",
+        "Total_ms",
+        "Avg_ms",
+        "Min_ms",
+        "Max_ms",
+        "First_seen",
+        "Last_seen",
+    ]);
+    let digests = stats.digests();
+    let limit = limit.unwrap_or(digests.len());
+    for (fingerprint, counts) in digests.into_iter().take(limit) {
+        let average = counts.total.div_f64(counts.count as f64);
+        result.push_row(vec![
+            Some(fingerprint),
+            Some(counts.text),
+            Some(counts.count.to_string()),
+            Some(counts.errors.to_string()),
+            Some(milliseconds(counts.total)),
+            Some(milliseconds(average)),
+            Some(milliseconds(counts.min)),
+            Some(milliseconds(counts.max)),
+            Some(counts.first_seen.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Some(counts.last_seen.format("%Y-%m-%d %H:%M:%S").to_string()),
+        ]);
+    }
+    result
+}
+
+// A latency in milliseconds, to the microsecond.
+pub(super) fn milliseconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,106 @@
+// SELECT COUNT(*) FROM table, answered from the planner's row estimate for the tables listed in
+// ESTIMATED_COUNT_TABLES.
+//
+// Admin UIs and ORMs count a table before paginating it, and an exact count reads the whole
+// table. For the configured tables the count is pg_class.reltuples instead, which VACUUM and
+// ANALYZE keep close to the real number and which costs nothing to read. Only a bare count of a
+// whole table is answered this way; a WHERE, a join or anything else goes to PostgreSQL. A table
+// that has never been analyzed has no estimate and is counted exactly too, and so is any count
+// with the EXACT_COUNT hint:
+//
+//   SELECT /*+ EXACT_COUNT */ COUNT(*) FROM orders
+
+use tokio_postgres::Client;
+
+use super::{alias_name, object_name, Reply};
+use crate::catalog::ObjectName;
+use crate::resultset::ResultSet;
+use crate::translator::{self, lexer, literals, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Count {
+    pub table: ObjectName,
+    // The column name: the alias, or the count as written.
+    pub label: String,
+}
+
+/// The count `sql` asks for, if it is a count of one of `tables` without the EXACT_COUNT hint.
+pub fn parse(sql: &str, tables: &[ObjectName]) -> Option<Count> {
+    if tables.is_empty() {
+        return None;
+    }
+    let tokens = translator::significant_tokens(sql)?;
+    let [select, count, Token::LParen, argument, Token::RParen, rest @ ..] = &tokens[..] else {
+        return None;
+    };
+    if !select.is_word("SELECT")
+        || !count.is_word("COUNT")
+        || !(argument.is_operator("*") || matches!(argument, Token::Number(n) if n == "1"))
+    {
+        return None;
+    }
+    let (label, rest) = match rest {
+        [from, rest @ ..] if from.is_word("FROM") => (format!("{}({})", count, argument), rest),
+        [as_, alias, from, rest @ ..] if as_.is_word("AS") && from.is_word("FROM") => {
+            (alias_name(alias)?, rest)
+        }
+        [alias, from, rest @ ..] if from.is_word("FROM") => (alias_name(alias)?, rest),
+        _ => return None,
+    };
+    let (table, rest) = object_name(rest)?;
+    if !rest.is_empty() || !tables.iter().any(|t| t.name == table.name) || exact(sql) {
+        return None;
+    }
+    Some(Count { table, label })
+}
+
+// Whether an optimizer-hint comment in `sql` asks for EXACT_COUNT.
+fn exact(sql: &str) -> bool {
+    lexer::tokenize(sql).is_ok_and(|tokens| {
+        tokens.iter().any(|token| {
+            matches!(token, Token::Comment(comment)
+                if comment.starts_with("/*+")
+                    && comment.to_ascii_uppercase().contains("EXACT_COUNT"))
+        })
+    })
+}
+
+/// Answers the count from the estimate, or `None` if the table isn't one of `tables` or has no
+/// estimate, for it to be counted exactly.
+pub async fn execute(client: &Client, count: &Count, tables: &[ObjectName]) -> Option<Reply> {
+    // The table as PostgreSQL resolves it, so an unqualified name is looked up in the
+    // search_path.
+    let name = match &count.table.schema {
+        Some(schema) => format!(
+            "{}.{}",
+            literals::pg_identifier(schema),
+            literals::pg_identifier(&count.table.name)
+        ),
+        None => literals::pg_identifier(&count.table.name),
+    };
+    let row = match client
+        .query_opt(
+            "SELECT n.nspname::text, c.reltuples::float8 FROM pg_class c \
+             JOIN pg_namespace n ON n.oid = c.relnamespace \
+             WHERE c.oid = to_regclass($1) AND c.relkind = 'r'",
+            &[&name],
+        )
+        .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return None,
+        Err(e) => return Some(Err(e.into())),
+    };
+    let schema: String = row.get(0);
+    let estimate: f64 = row.get(1);
+    let configured = tables
+        .iter()
+        .any(|t| t.name == count.table.name && t.schema.as_ref().is_none_or(|s| *s == schema));
+    // reltuples is -1 until the table is first vacuumed or analyzed.
+    if !configured || estimate < 0.0 {
+        return None;
+    }
+    let mut result = ResultSet::new(&[count.label.as_str()]);
+    result.push_row(vec![Some((estimate.round() as i64).to_string())]);
+    Some(Ok(result))
+}
@@ -0,0 +1,363 @@
+// EXPLAIN of a statement: the statement is translated and PostgreSQL's plan returned in MySQL's
+// shape.
+//
+//   EXPLAIN [ANALYZE] [FORMAT = {TRADITIONAL | JSON | TREE}] statement
+//
+// The traditional format is a table row per table the plan reads, made from PostgreSQL's text
+// plan: a sequential scan is type ALL, an index scan is ref or range (index without a condition)
+// with the index as the key, and rows is the planner's estimate. key_len isn't known. FORMAT=JSON
+// returns PostgreSQL's JSON plan as it is, and TREE and ANALYZE its text plan, each in a single
+// EXPLAIN column. EXPLAIN ANALYZE runs the statement, as in MySQL.
+//
+// `EXPLAIN table` and `DESCRIBE table`, which list columns, aren't handled here.
+
+use tokio_postgres::{Client, SimpleQueryMessage};
+
+use super::Reply;
+use crate::resultset::ResultSet;
+use crate::translator::{lexer, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Traditional,
+    Json,
+    Tree,
+}
+
+pub struct Explain<'a> {
+    pub format: Format,
+    pub analyze: bool,
+    // The explained statement, as written.
+    pub statement: &'a str,
+}
+
+// The statements EXPLAIN takes, by their first word.
+const EXPLAINABLE: &[&str] = &[
+    "SELECT", "WITH", "TABLE", "VALUES", "INSERT", "REPLACE", "UPDATE", "DELETE",
+];
+
+pub fn parse(sql: &str) -> Option<Explain<'_>> {
+    let tokens = lexer::tokenize(sql).ok()?;
+    let mut offset = 0;
+    let mut significant = Vec::new();
+    for token in &tokens {
+        if !token.is_trivia() {
+            significant.push((offset, token));
+        }
+        offset += token.to_string().len();
+    }
+    let mut significant = significant.into_iter();
+
+    let (_, first) = significant.next()?;
+    if !["EXPLAIN", "DESCRIBE", "DESC"]
+        .iter()
+        .any(|w| first.is_word(w))
+    {
+        return None;
+    }
+    let mut format = Format::Traditional;
+    let mut analyze = false;
+    loop {
+        let (start, token) = significant.next()?;
+        if token.is_word("ANALYZE") {
+            analyze = true;
+        } else if token.is_word("EXTENDED") || token.is_word("PARTITIONS") {
+            // Old modifiers; their information is always shown.
+        } else if token.is_word("FORMAT") {
+            let (_, equals) = significant.next()?;
+            if !equals.is_operator("=") {
+                return None;
+            }
+            let (_, name) = significant.next()?;
+            format = match name {
+                Token::Word(w) if w.eq_ignore_ascii_case("TRADITIONAL") => Format::Traditional,
+                Token::Word(w) if w.eq_ignore_ascii_case("JSON") => Format::Json,
+                Token::Word(w) if w.eq_ignore_ascii_case("TREE") => Format::Tree,
+                _ => return None,
+            };
+        } else if token == &Token::LParen || EXPLAINABLE.iter().any(|w| token.is_word(w)) {
+            return Some(Explain {
+                format,
+                analyze,
+                statement: &sql[start..],
+            });
+        } else {
+            return None;
+        }
+    }
+}
+
+/// Explains `translated`, the PostgreSQL translation of the explained statement.
+pub async fn execute(client: &Client, explain: &Explain<'_>, translated: &str) -> Reply {
+    let options = match (explain.format, explain.analyze) {
+        (Format::Json, true) => "(ANALYZE, FORMAT JSON) ",
+        (Format::Json, false) => "(FORMAT JSON) ",
+        (_, true) => "(ANALYZE) ",
+        (_, false) => "",
+    };
+    let plan: Vec<String> = client
+        .simple_query(&format!("EXPLAIN {}{}", options, translated))
+        .await?
+        .into_iter()
+        .filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => row.get(0).map(str::to_string),
+            _ => None,
+        })
+        .collect();
+
+    if explain.format == Format::Traditional && !explain.analyze {
+        return Ok(traditional(&plan));
+    }
+    let mut result = ResultSet::new(&["EXPLAIN"]);
+    result.push_row(vec![Some(plan.join("\n"))]);
+    Ok(result)
+}
+
+struct Row {
+    id: usize,
+    select_type: &'static str,
+    table: Option<String>,
+    access: &'static str,
+    key: Option<String>,
+    reference: Option<String>,
+    rows: Option<String>,
+    extra: Vec<&'static str>,
+}
+
+// A query in the plan: the statement itself, or one of its InitPlans (uncorrelated subqueries,
+// run once) and SubPlans (correlated ones, run per row).
+struct Subquery {
+    indent: usize,
+    id: usize,
+    select_type: &'static str,
+}
+
+// The traditional, tabular format, from the lines of a PostgreSQL text plan.
+fn traditional(plan: &[String]) -> ResultSet {
+    let mut rows: Vec<Row> = Vec::new();
+    let mut subqueries: Vec<Subquery> = Vec::new();
+    let mut next_id = 2;
+    let mut modified: Option<(&'static str, String)> = None;
+    let mut filesort = false;
+    let mut temporary = false;
+    // The row the detail lines (`Filter: ...`) being read belong to.
+    let mut current: Option<usize> = None;
+
+    for (i, line) in plan.iter().enumerate() {
+        let text = line.trim_start();
+        let indent = line.len() - text.len();
+        // Only the first line is an unindented node; after it come JIT and timing sections.
+        if i > 0 && indent == 0 {
+            break;
+        }
+        if text.starts_with("InitPlan ") || text.starts_with("SubPlan ") {
+            subqueries.push(Subquery {
+                indent,
+                id: next_id,
+                select_type: if text.starts_with("InitPlan ") {
+                    "SUBQUERY"
+                } else {
+                    "DEPENDENT SUBQUERY"
+                },
+            });
+            next_id += 1;
+            current = None;
+            continue;
+        }
+        let node = match text.strip_prefix("->") {
+            Some(node) => node.trim_start(),
+            None if i == 0 => text,
+            None => {
+                if let Some(row) = current.map(|r| &mut rows[r]) {
+                    detail(row, text);
+                }
+                continue;
+            }
+        };
+        while subqueries.last().is_some_and(|s| s.indent >= indent) {
+            subqueries.pop();
+        }
+        let (header, estimate) = match node.split_once("  (cost=") {
+            Some((header, cost)) => (header, row_estimate(cost)),
+            None => (node, None),
+        };
+        let header = header.strip_prefix("Parallel ").unwrap_or(header);
+        current = None;
+
+        let modification = [
+            ("Insert on ", "INSERT"),
+            ("Update on ", "UPDATE"),
+            ("Delete on ", "DELETE"),
+        ]
+        .into_iter()
+        .find_map(|(prefix, select_type)| Some((select_type, header.strip_prefix(prefix)?)));
+        if let Some((select_type, table)) = modification {
+            modified = Some((select_type, table_name(table)));
+            continue;
+        }
+        if header == "Sort" || header == "Incremental Sort" {
+            filesort = true;
+        }
+        if header == "HashAggregate" {
+            temporary = true;
+        }
+        if let Some(index) = header.strip_prefix("Bitmap Index Scan on ") {
+            // Under its Bitmap Heap Scan, which the index condition is for.
+            if let Some(r) = rows.iter().rposition(|r| r.key.is_none()) {
+                rows[r].key = Some(index.to_string());
+                current = Some(r);
+            }
+            continue;
+        }
+        let Some((scan, table)) = header.split_once(" on ") else {
+            continue;
+        };
+        let (access, key, extra) = if scan == "Seq Scan" {
+            ("ALL", None, vec![])
+        } else if let Some((kind, index)) = scan.split_once(" using ") {
+            // Without an index condition the whole index is read.
+            let extra = if kind.starts_with("Index Only Scan") {
+                vec!["Using index"]
+            } else {
+                vec![]
+            };
+            ("index", Some(index.to_string()), extra)
+        } else if scan == "Bitmap Heap Scan" {
+            ("ref", None, vec![])
+        } else if scan.ends_with(" Scan") {
+            // Function, CTE, subquery and VALUES scans and the like.
+            ("ALL", None, vec![])
+        } else {
+            continue;
+        };
+        let subquery = subqueries.last();
+        rows.push(Row {
+            id: subquery.map_or(1, |s| s.id),
+            select_type: subquery.map_or("SIMPLE", |s| s.select_type),
+            table: Some(table_name(table)),
+            access,
+            key,
+            reference: None,
+            rows: estimate.map(str::to_string),
+            extra,
+        });
+        current = Some(rows.len() - 1);
+    }
+
+    if let Some((select_type, table)) = modified {
+        match rows.iter().position(|r| r.table.as_ref() == Some(&table)) {
+            Some(r) => rows[r].select_type = select_type,
+            None => rows.insert(
+                0,
+                Row {
+                    id: 1,
+                    select_type,
+                    table: Some(table),
+                    access: "ALL",
+                    key: None,
+                    reference: None,
+                    rows: None,
+                    extra: vec![],
+                },
+            ),
+        }
+    } else if rows.iter().any(|r| r.id > 1) {
+        for row in rows.iter_mut().filter(|r| r.id == 1) {
+            row.select_type = "PRIMARY";
+        }
+    }
+    // The statement's own tables first, then its subqueries.
+    rows.sort_by_key(|r| r.id);
+    match rows.first_mut() {
+        Some(first) => {
+            if temporary {
+                first.extra.push("Using temporary");
+            }
+            if filesort {
+                first.extra.push("Using filesort");
+            }
+        }
+        None => rows.push(Row {
+            id: 1,
+            select_type: "SIMPLE",
+            table: None,
+            access: "",
+            key: None,
+            reference: None,
+            rows: None,
+            extra: vec!["No tables used"],
+        }),
+    }
+
+    let mut result = ResultSet::new(&[
+        "id",
+        "select_type",
+        "table",
+        "partitions",
+        "type",
+        "possible_keys",
+        "key",
+        "key_len",
+        "ref",
+        "rows",
+        "filtered",
+        "Extra",
+    ]);
+    for row in rows {
+        let has_table = row.table.is_some();
+        result.push_row(vec![
+            Some(row.id.to_string()),
+            Some(row.select_type.to_string()),
+            row.table,
+            None,
+            has_table.then(|| row.access.to_string()),
+            row.key.clone(),
+            row.key,
+            None,
+            row.reference,
+            row.rows,
+            has_table.then(|| "100.00".to_string()),
+            (!row.extra.is_empty()).then(|| row.extra.join("; ")),
+        ]);
+    }
+    result
+}
+
+// A `Key: value` line under a scan.
+fn detail(row: &mut Row, text: &str) {
+    if text.starts_with("Filter: ") {
+        row.extra.insert(0, "Using where");
+    } else if let Some(condition) = text.strip_prefix("Index Cond: ") {
+        let equality = condition.contains(" = ")
+            && !condition.contains(" ANY ")
+            && !condition.contains(['<', '>']);
+        if equality {
+            row.access = "ref";
+            row.reference = condition_reference(condition);
+        } else {
+            row.access = "range";
+        }
+    }
+}
+
+// What an index lookup compares the index to: `const` for a constant or parameter, or the
+// column of another table in a join.
+fn condition_reference(condition: &str) -> Option<String> {
+    let (_, value) = condition.split_once(" = ")?;
+    let value = value.trim_end_matches(')');
+    let constant = value
+        .starts_with(|c: char| c.is_ascii_digit() || c == '\'' || c == '$' || c == '-' || c == '(');
+    Some(if constant { "const" } else { value }.to_string())
+}
+
+// The row estimate of `cost=0.00..1.02 rows=2 width=8)`.
+fn row_estimate(cost: &str) -> Option<&str> {
+    let (_, rows) = cost.split_once("rows=")?;
+    rows.split(|c: char| !c.is_ascii_digit()).next()
+}
+
+// How MySQL names the table of `table [alias]`: by its alias, if it has one.
+fn table_name(table: &str) -> String {
+    let name = table.rsplit(' ').next().unwrap_or(table);
+    name.trim_matches('"').to_string()
+}
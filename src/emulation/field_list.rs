@@ -0,0 +1,102 @@
+// COM_FIELD_LIST, which old clients and some GUI tools send for a table's columns instead of
+// querying them. The columns are read from the catalog as for SHOW CREATE TABLE, and typed by
+// the MySQL type SHOW CREATE TABLE gives them.
+
+use opensrv_mysql::{Column, ColumnFlags, ColumnType};
+use tokio_postgres::Client;
+
+use super::show_create::no_such_table;
+use crate::catalog::{self, ObjectName};
+use crate::error::MysqlError;
+
+/// The columns of `table` in the current database whose names match `wildcard`, a LIKE
+/// pattern; all of them if it is empty.
+pub async fn columns(
+    client: &Client,
+    table: &str,
+    wildcard: &str,
+) -> Result<Vec<Column>, MysqlError> {
+    let name = ObjectName {
+        schema: None,
+        name: table.to_lowercase(),
+    };
+    let columns = catalog::table_columns(client, &name).await?;
+    if columns.is_empty() {
+        return Err(no_such_table(client, &name).await);
+    }
+    let indexes = catalog::table_indexes(client, &name).await?;
+    let primary_key: Vec<&String> = indexes
+        .iter()
+        .filter(|index| index.primary)
+        .flat_map(|index| &index.columns)
+        .collect();
+
+    Ok(columns
+        .iter()
+        .filter(|column| wildcard.is_empty() || like(wildcard, &column.name))
+        .map(|column| {
+            let mysql_type = catalog::mysql_column_type(&column.pg_type);
+            let (coltype, mut colflags) = column_type(&mysql_type);
+            if !column.nullable {
+                colflags |= ColumnFlags::NOT_NULL_FLAG;
+            }
+            if primary_key.contains(&&column.name) {
+                colflags |= ColumnFlags::PRI_KEY_FLAG;
+            }
+            if column.auto_increment {
+                colflags |= ColumnFlags::AUTO_INCREMENT_FLAG;
+            }
+            Column {
+                table: name.name.clone(),
+                column: column.name.clone(),
+                coltype,
+                colflags,
+            }
+        })
+        .collect())
+}
+
+// The protocol type of a MySQL column type as catalog::mysql_column_type writes it.
+fn column_type(mysql_type: &str) -> (ColumnType, ColumnFlags) {
+    let base = mysql_type.split('(').next().unwrap_or(mysql_type);
+    let coltype = match base {
+        "tinyint" => ColumnType::MYSQL_TYPE_TINY,
+        "smallint" => ColumnType::MYSQL_TYPE_SHORT,
+        "int" => ColumnType::MYSQL_TYPE_LONG,
+        "bigint" => ColumnType::MYSQL_TYPE_LONGLONG,
+        "float" => ColumnType::MYSQL_TYPE_FLOAT,
+        "double" => ColumnType::MYSQL_TYPE_DOUBLE,
+        "decimal" => ColumnType::MYSQL_TYPE_NEWDECIMAL,
+        "char" => ColumnType::MYSQL_TYPE_STRING,
+        "text" => ColumnType::MYSQL_TYPE_BLOB,
+        "datetime" => ColumnType::MYSQL_TYPE_DATETIME,
+        "timestamp" => ColumnType::MYSQL_TYPE_TIMESTAMP,
+        "time" => ColumnType::MYSQL_TYPE_TIME,
+        "date" => ColumnType::MYSQL_TYPE_DATE,
+        "json" => ColumnType::MYSQL_TYPE_JSON,
+        "bit" => ColumnType::MYSQL_TYPE_BIT,
+        "longblob" => return (ColumnType::MYSQL_TYPE_BLOB, ColumnFlags::BINARY_FLAG),
+        _ => ColumnType::MYSQL_TYPE_VAR_STRING,
+    };
+    (coltype, ColumnFlags::empty())
+}
+
+/// MySQL's LIKE, case-insensitively: `%` is any run of characters, `_` any one, and `\`
+/// escapes.
+pub fn like(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    like_from(&pattern, &name)
+}
+
+fn like_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('%', rest)) => (0..=name.len()).any(|skip| like_from(rest, &name[skip..])),
+        Some(('_', rest)) => !name.is_empty() && like_from(rest, &name[1..]),
+        Some(('\\', [escaped, rest @ ..])) => {
+            name.first() == Some(escaped) && like_from(rest, &name[1..])
+        }
+        Some((c, rest)) => name.first() == Some(c) && like_from(rest, &name[1..]),
+    }
+}
@@ -0,0 +1,77 @@
+// SHOW GRANTS [FOR user]: the tables a user was granted with USER_GRANTS (see policy.rs), as
+// the GRANT statements MySQL would list. Users without grants have every privilege, the proxy
+// not limiting them. What USER_REQUIRE has the user's connections need (see client_tls.rs)
+// follows their global grant, as MySQL 5.7 lists it:
+//
+//   GRANT USAGE ON *.* TO `alice`@`%` REQUIRE SUBJECT '/O=Acme/CN=alice'
+
+use crate::client_tls::Requirement;
+use crate::policy::Grant;
+use crate::resultset::ResultSet;
+use crate::translator::{self, literals, Token};
+
+/// The user whose grants are shown, `None` for the client's own.
+pub fn parse(sql: &str) -> Option<Option<String>> {
+    let tokens = translator::significant_tokens(sql)?;
+    match &tokens[..] {
+        [show, grants] if show.is_word("SHOW") && grants.is_word("GRANTS") => Some(None),
+        [show, grants, four, rest @ ..]
+            if show.is_word("SHOW") && grants.is_word("GRANTS") && four.is_word("FOR") =>
+        {
+            match rest {
+                [current] | [current, Token::LParen, Token::RParen]
+                    if current.is_word("CURRENT_USER") =>
+                {
+                    Some(None)
+                }
+                // The host, as in 'app'@'%', makes no difference.
+                [user] | [user, Token::Variable(_)] => Some(Some(match user {
+                    Token::String(raw) | Token::DoubleQuoted(raw) => {
+                        literals::mysql_string_value(raw)
+                    }
+                    token => literals::identifier_name(token)?,
+                })),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn execute(
+    grants: &[(String, Vec<Grant>)],
+    requirements: &[(String, Requirement)],
+    user: &str,
+) -> ResultSet {
+    let mut result = ResultSet::new(&[format!("Grants for {}@%", user)]);
+    let escaped = user.replace('`', "``");
+    let require = requirements
+        .iter()
+        .find(|(name, _)| name == user)
+        .map_or_else(String::new, |(_, requirement)| {
+            format!(" REQUIRE {}", requirement)
+        });
+    match grants.iter().find(|(name, _)| name == user) {
+        Some((_, grants)) => {
+            // MySQL lists USAGE for those with no privilege on every database.
+            if !grants.iter().any(|grant| grant.schema.is_none()) {
+                result.push_row(vec![Some(format!(
+                    "GRANT USAGE ON *.* TO `{}`@`%`{}",
+                    escaped, require
+                ))]);
+            }
+            for grant in grants {
+                let mut statement = grant.statement(user);
+                if grant.schema.is_none() {
+                    statement.push_str(&require);
+                }
+                result.push_row(vec![Some(statement)]);
+            }
+        }
+        None => result.push_row(vec![Some(format!(
+            "GRANT ALL PRIVILEGES ON *.* TO `{}`@`%`{}",
+            escaped, require
+        ))]),
+    }
+    result
+}
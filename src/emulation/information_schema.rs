@@ -0,0 +1,208 @@
+// information_schema.TABLES, COLUMNS, STATISTICS and KEY_COLUMN_USAGE as MySQL has them, for the
+// tools that read the schema from there: ORMs, migration tools, GUI clients.
+//
+// PostgreSQL's information_schema has tables of those names, or none, with other columns and
+// other meanings: its table_catalog is the database, where MySQL's is always `def` and the
+// database is TABLE_SCHEMA, and it knows nothing of engines, AUTO_INCREMENT or MySQL's types.
+// References to them in a translated statement are replaced by a subquery over pg_catalog with
+// MySQL's columns, as routine_sources.rs does for ROUTINES:
+//
+//   SELECT TABLE_NAME, AUTO_INCREMENT FROM information_schema.TABLES WHERE TABLE_SCHEMA = 'shop'
+//   -> SELECT table_name, auto_increment FROM (SELECT ... FROM pg_class ...) AS tables WHERE ...
+//
+// A database is a schema, as everywhere in the proxy, and PostgreSQL's own schemas and the
+// sessions' temporary tables are left out. Types are given as SHOW CREATE TABLE gives them (see
+// catalog::mysql_column_type), tables as InnoDB, and a table's AUTO_INCREMENT is the next value
+// of its serial or identity column's sequence. The primary key's index and constraint are named
+// PRIMARY. Columns PostgreSQL has nothing for, such as CREATE_TIME, are NULL.
+
+use super::virtual_tables::replace_tables;
+
+// PostgreSQL's schemas, which aren't databases a MySQL client knows.
+const USER_SCHEMAS: &str = "n.nspname NOT IN ('pg_catalog', 'information_schema') \
+     AND n.nspname NOT LIKE 'pg\\_%'";
+
+const TABLES: &str = "SELECT 'def'::text AS table_catalog, \
+       n.nspname::text AS table_schema, \
+       c.relname::text AS table_name, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN 'VIEW' ELSE 'BASE TABLE' END::text AS table_type, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE 'InnoDB' END::text AS engine, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE 10 END::bigint AS version, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE 'Dynamic' END::text AS row_format, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL \
+         ELSE greatest(c.reltuples, 0) END::bigint AS table_rows, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL WHEN c.reltuples > 0 \
+         THEN pg_relation_size(c.oid) / c.reltuples ELSE 0 END::bigint AS avg_row_length, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL \
+         ELSE pg_relation_size(c.oid) END::bigint AS data_length, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE 0 END::bigint AS max_data_length, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL \
+         ELSE pg_indexes_size(c.oid) END::bigint AS index_length, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE 0 END::bigint AS data_free, \
+       (SELECT COALESCE(pg_sequence_last_value(d.objid) + s.seqincrement, s.seqstart) \
+          FROM pg_depend d JOIN pg_sequence s ON s.seqrelid = d.objid \
+          WHERE d.classid = 'pg_class'::regclass AND d.refclassid = 'pg_class'::regclass \
+            AND d.refobjid = c.oid AND d.deptype IN ('a', 'i') \
+          LIMIT 1)::bigint AS auto_increment, \
+       NULL::timestamp AS create_time, \
+       NULL::timestamp AS update_time, \
+       NULL::timestamp AS check_time, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL \
+         ELSE 'utf8mb4_0900_ai_ci' END::text AS table_collation, \
+       NULL::bigint AS checksum, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN NULL ELSE '' END::text AS create_options, \
+       CASE WHEN c.relkind IN ('v', 'm') THEN 'VIEW' \
+         ELSE COALESCE(obj_description(c.oid, 'pg_class'), '') END::text AS table_comment \
+     FROM pg_class c \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     WHERE c.relkind IN ('r', 'p', 'v', 'm') AND NOT c.relispartition \
+       AND c.relpersistence <> 't' AND {schemas}";
+
+const COLUMNS: &str = "SELECT 'def'::text AS table_catalog, \
+       n.nspname::text AS table_schema, \
+       c.relname::text AS table_name, \
+       a.attname::text AS column_name, \
+       a.attnum::bigint AS ordinal_position, \
+       CASE WHEN a.attidentity <> '' OR d.adbin IS NULL THEN NULL \
+         WHEN t.expr LIKE 'nextval(%' THEN NULL \
+         WHEN lower(t.expr) IN ('now()', 'current_timestamp', 'localtimestamp') \
+           THEN 'CURRENT_TIMESTAMP' \
+         ELSE regexp_replace(regexp_replace(t.expr, '::[a-z ]+(\\[\\])?$', ''), \
+           '^''(.*)''$', '\\1') END::text AS column_default, \
+       CASE WHEN a.attnotnull THEN 'NO' ELSE 'YES' END::text AS is_nullable, \
+       split_part(ty.column_type, '(', 1)::text AS data_type, \
+       CASE WHEN ty.base IN ('character varying', 'character') THEN m.modifier::bigint \
+         WHEN ty.base = 'text' THEN 65535 END::bigint AS character_maximum_length, \
+       CASE WHEN ty.base IN ('character varying', 'character') THEN m.modifier::bigint * 4 \
+         WHEN ty.base = 'text' THEN 65535 END::bigint AS character_octet_length, \
+       CASE ty.base WHEN 'smallint' THEN 5 WHEN 'integer' THEN 10 WHEN 'bigint' THEN 19 \
+         WHEN 'boolean' THEN 3 WHEN 'real' THEN 12 WHEN 'double precision' THEN 22 \
+         WHEN 'numeric' THEN COALESCE(split_part(m.modifier, ',', 1)::bigint, 65) \
+         END::bigint AS numeric_precision, \
+       CASE WHEN ty.base IN ('smallint', 'integer', 'bigint', 'boolean') THEN 0 \
+         WHEN ty.base = 'numeric' \
+           THEN COALESCE(nullif(split_part(m.modifier, ',', 2), '')::bigint, 0) \
+         END::bigint AS numeric_scale, \
+       CASE WHEN ty.base LIKE 'time%' THEN COALESCE(m.modifier::bigint, 0) \
+         END::bigint AS datetime_precision, \
+       CASE WHEN ty.base IN ('character varying', 'character', 'text') \
+         THEN 'utf8mb4' END::text AS character_set_name, \
+       CASE WHEN ty.base IN ('character varying', 'character', 'text') \
+         THEN 'utf8mb4_0900_ai_ci' END::text AS collation_name, \
+       ty.column_type::text AS column_type, \
+       CASE WHEN EXISTS (SELECT 1 FROM pg_index i WHERE i.indrelid = c.oid \
+             AND i.indisprimary AND a.attnum = ANY (i.indkey)) THEN 'PRI' \
+         WHEN EXISTS (SELECT 1 FROM pg_index i WHERE i.indrelid = c.oid \
+             AND i.indisunique AND i.indnkeyatts = 1 AND i.indkey[0] = a.attnum) THEN 'UNI' \
+         WHEN EXISTS (SELECT 1 FROM pg_index i WHERE i.indrelid = c.oid \
+             AND i.indkey[0] = a.attnum) THEN 'MUL' \
+         ELSE '' END::text AS column_key, \
+       CASE WHEN a.attidentity <> '' OR t.expr LIKE 'nextval(%' THEN 'auto_increment' \
+         ELSE '' END::text AS extra, \
+       'select,insert,update,references'::text AS privileges, \
+       COALESCE(col_description(c.oid, a.attnum), '')::text AS column_comment, \
+       ''::text AS generation_expression, \
+       NULL::bigint AS srs_id \
+     FROM pg_attribute a \
+     JOIN pg_class c ON c.oid = a.attrelid \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+     CROSS JOIN LATERAL (SELECT pg_get_expr(d.adbin, d.adrelid) AS expr, \
+       format_type(a.atttypid, a.atttypmod) AS full_type) t \
+     CROSS JOIN LATERAL (SELECT substring(t.full_type FROM '\\((.*)\\)') AS modifier) m \
+     CROSS JOIN LATERAL (SELECT b.base, CASE \
+         WHEN t.full_type LIKE '%[]' THEN 'json' \
+         WHEN b.base = 'integer' THEN 'int' \
+         WHEN b.base = 'boolean' THEN 'tinyint(1)' \
+         WHEN b.base = 'real' THEN 'float' \
+         WHEN b.base = 'double precision' THEN 'double' \
+         WHEN b.base = 'numeric' THEN 'decimal(' || COALESCE(m.modifier, '65,30') || ')' \
+         WHEN b.base = 'character varying' AND m.modifier IS NOT NULL \
+           THEN 'varchar(' || m.modifier || ')' \
+         WHEN b.base = 'character' AND m.modifier IS NOT NULL \
+           THEN 'char(' || m.modifier || ')' \
+         WHEN b.base IN ('character varying', 'character', 'text') THEN 'text' \
+         WHEN b.base = 'timestamp without time zone' THEN 'datetime' \
+         WHEN b.base = 'timestamp with time zone' THEN 'timestamp' \
+         WHEN b.base LIKE 'time %' THEN 'time' \
+         WHEN b.base = 'bytea' THEN 'longblob' \
+         WHEN b.base IN ('json', 'jsonb') THEN 'json' \
+         WHEN b.base = 'uuid' THEN 'char(36)' \
+         WHEN m.modifier IS NOT NULL THEN b.base || '(' || m.modifier || ')' \
+         ELSE b.base END AS column_type \
+       FROM (SELECT regexp_replace(t.full_type, '\\(.*\\)', '') AS base) b) ty \
+     WHERE c.relkind IN ('r', 'p', 'v', 'm') AND NOT c.relispartition \
+       AND c.relpersistence <> 't' AND a.attnum > 0 AND NOT a.attisdropped AND {schemas}";
+
+const STATISTICS: &str = "SELECT 'def'::text AS table_catalog, \
+       n.nspname::text AS table_schema, \
+       c.relname::text AS table_name, \
+       CASE WHEN i.indisunique THEN 0 ELSE 1 END::bigint AS non_unique, \
+       n.nspname::text AS index_schema, \
+       CASE WHEN i.indisprimary THEN 'PRIMARY' ELSE ic.relname END::text AS index_name, \
+       k.seq::bigint AS seq_in_index, \
+       a.attname::text AS column_name, \
+       'A'::text AS collation, \
+       greatest(c.reltuples, 0)::bigint AS cardinality, \
+       NULL::bigint AS sub_part, \
+       NULL::text AS packed, \
+       CASE WHEN a.attnotnull OR a.attname IS NULL THEN '' ELSE 'YES' END::text AS nullable, \
+       CASE am.amname WHEN 'gin' THEN 'FULLTEXT' WHEN 'hash' THEN 'HASH' \
+         ELSE 'BTREE' END::text AS index_type, \
+       ''::text AS comment, \
+       COALESCE(obj_description(ic.oid, 'pg_class'), '')::text AS index_comment, \
+       'YES'::text AS is_visible, \
+       CASE WHEN k.attnum = 0 \
+         THEN pg_get_indexdef(i.indexrelid, k.seq::integer, true) END::text AS expression \
+     FROM pg_index i \
+     JOIN pg_class c ON c.oid = i.indrelid \
+     JOIN pg_class ic ON ic.oid = i.indexrelid \
+     JOIN pg_am am ON am.oid = ic.relam \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     CROSS JOIN LATERAL unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, seq) \
+     LEFT JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = k.attnum \
+     WHERE k.seq <= i.indnkeyatts AND c.relpersistence <> 't' AND {schemas}";
+
+const KEY_COLUMN_USAGE: &str = "SELECT 'def'::text AS constraint_catalog, \
+       n.nspname::text AS constraint_schema, \
+       CASE WHEN con.contype = 'p' THEN 'PRIMARY' ELSE con.conname END::text AS constraint_name, \
+       'def'::text AS table_catalog, \
+       n.nspname::text AS table_schema, \
+       c.relname::text AS table_name, \
+       a.attname::text AS column_name, \
+       k.seq::bigint AS ordinal_position, \
+       CASE WHEN con.contype = 'f' THEN k.seq END::bigint AS position_in_unique_constraint, \
+       rn.nspname::text AS referenced_table_schema, \
+       rc.relname::text AS referenced_table_name, \
+       ra.attname::text AS referenced_column_name \
+     FROM pg_constraint con \
+     JOIN pg_class c ON c.oid = con.conrelid \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     CROSS JOIN LATERAL unnest(con.conkey) WITH ORDINALITY AS k(attnum, seq) \
+     JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = k.attnum \
+     LEFT JOIN pg_class rc ON rc.oid = con.confrelid \
+     LEFT JOIN pg_namespace rn ON rn.oid = rc.relnamespace \
+     LEFT JOIN pg_attribute ra ON ra.attrelid = con.confrelid \
+       AND ra.attnum = con.confkey[k.seq] \
+     WHERE con.contype IN ('p', 'u', 'f') AND c.relpersistence <> 't' AND {schemas}";
+
+/// `sql`, a translated statement, with the information_schema tables it references that MySQL
+/// has other columns for replaced by MySQL's. `None` if it references none of them.
+pub fn expand(sql: &str) -> Option<String> {
+    if !sql.to_ascii_lowercase().contains("information_schema") {
+        return None;
+    }
+    replace_tables(sql, &|schema, table| {
+        if !schema.eq_ignore_ascii_case("information_schema") {
+            return None;
+        }
+        let query = match table.to_ascii_lowercase().as_str() {
+            "tables" => TABLES,
+            "columns" => COLUMNS,
+            "statistics" => STATISTICS,
+            "key_column_usage" => KEY_COLUMN_USAGE,
+            _ => return None,
+        };
+        Some(query.replace("{schemas}", USER_SCHEMAS))
+    })
+}
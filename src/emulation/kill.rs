@@ -0,0 +1,88 @@
+// KILL [CONNECTION | QUERY] id: ends another connection, or just the statement it is running.
+//
+// The statement is cancelled with pg_cancel_backend on the connection's PostgreSQL session, and
+// its client gets MySQL's "Query execution was interrupted". Killing the
+// connection cancels its statement too and then closes it. A client may kill the connections of
+// its own user; those of other users only from the admin listener (ADMIN_LISTEN_ADDR) or as a
+// user allowed admin statements (see policy.rs), as only a MySQL account with CONNECTION_ADMIN
+// could.
+
+use opensrv_mysql::ErrorKind;
+use tokio_postgres::Client;
+
+use crate::error::MysqlError;
+use crate::sessions::Sessions;
+use crate::translator::{self, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kill {
+    Connection(u32),
+    Query(u32),
+}
+
+pub fn parse(sql: &str) -> Option<Kill> {
+    let tokens = translator::significant_tokens(sql)?;
+    let (first, rest) = tokens.split_first()?;
+    if !first.is_word("KILL") {
+        return None;
+    }
+    let (query, rest) = match rest.split_first()? {
+        (word, rest) if word.is_word("QUERY") => (true, rest),
+        (word, rest) if word.is_word("CONNECTION") => (false, rest),
+        _ => (false, rest),
+    };
+    let [Token::Number(id)] = rest else {
+        return None;
+    };
+    let id = id.parse().ok()?;
+    Some(if query {
+        Kill::Query(id)
+    } else {
+        Kill::Connection(id)
+    })
+}
+
+/// Runs a KILL given by `connection`, which may kill other users' connections if `admin`.
+pub async fn execute(
+    client: &Client,
+    sessions: &Sessions,
+    connection: u32,
+    admin: bool,
+    kill: Kill,
+) -> Result<(), MysqlError> {
+    let id = match kill {
+        Kill::Connection(id) | Kill::Query(id) => id,
+    };
+    let Some(pid) = sessions.backend_pid(id) else {
+        return Err(MysqlError::new(
+            ErrorKind::ER_NO_SUCH_THREAD,
+            format!("Unknown thread id: {}", id),
+        ));
+    };
+    if !admin && sessions.user(id) != sessions.user(connection) {
+        return Err(MysqlError::new(
+            ErrorKind::ER_KILL_DENIED_ERROR,
+            format!("You are not owner of thread {}", id),
+        ));
+    }
+    // A connection's own statement is this KILL.
+    if id == connection {
+        return match kill {
+            Kill::Query(_) => Err(MysqlError::new(
+                ErrorKind::ER_QUERY_INTERRUPTED,
+                "Query execution was interrupted",
+            )),
+            Kill::Connection(_) => {
+                sessions.kill(id);
+                Ok(())
+            }
+        };
+    }
+    client
+        .execute("SELECT pg_cancel_backend($1)", &[&pid])
+        .await?;
+    if let Kill::Connection(id) = kill {
+        sessions.kill(id);
+    }
+    Ok(())
+}
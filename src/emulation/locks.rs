@@ -0,0 +1,332 @@
+// Named user-level locks: GET_LOCK, RELEASE_LOCK, IS_FREE_LOCK and RELEASE_ALL_LOCKS.
+//
+// Each name is a PostgreSQL advisory lock keyed by `hashtext(name)`, taken in the connection's
+// own PostgreSQL session, so the lock is also seen by other proxies and by PostgreSQL clients
+// using the same key. Which connection holds which lock is tracked here as well, to answer
+// RELEASE_LOCK and RELEASE_ALL_LOCKS the way MySQL does. Waiting is done by polling
+// `pg_try_advisory_lock`, so the proxy can apply GET_LOCK's timeout.
+//
+// As in MySQL, a connection can take the same lock several times and has to release it as many
+// times; its locks are released when it disconnects.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use opensrv_mysql::ErrorKind;
+use tokio_postgres::Client;
+
+use super::{alias_name, Reply};
+use crate::error::MysqlError;
+use crate::resultset::ResultSet;
+use crate::translator::{literals, Token};
+
+// How often a waiting GET_LOCK retries.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// MySQL's limit on lock names.
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockCall {
+    // A negative timeout waits forever.
+    Get { name: String, timeout: f64 },
+    Release { name: String },
+    IsFree { name: String },
+    ReleaseAll,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockQuery {
+    pub call: LockCall,
+    // The column name: the alias, or the call as written.
+    pub label: String,
+}
+
+struct Holder {
+    connection: u32,
+    count: u32,
+}
+
+/// Which connection holds each named lock, shared by all connections.
+#[derive(Default)]
+pub struct Locks {
+    held: Mutex<HashMap<String, Holder>>,
+}
+
+enum Claim {
+    // Already held by this connection; its count went up.
+    Reentered,
+    // Reserved for this connection; the advisory lock still has to be taken.
+    Reserved,
+    HeldElsewhere,
+}
+
+impl Locks {
+    /// GET_LOCK: whether the lock was obtained within `timeout` seconds.
+    pub async fn get(
+        &self,
+        client: &Client,
+        connection: u32,
+        name: &str,
+        timeout: f64,
+    ) -> Result<bool, tokio_postgres::Error> {
+        let deadline = (timeout >= 0.0)
+            .then(|| Instant::now() + Duration::from_secs_f64(timeout.min(u32::MAX as f64)));
+        loop {
+            match self.claim(connection, name) {
+                Claim::Reentered => return Ok(true),
+                Claim::Reserved => match try_advisory_lock(client, name).await {
+                    Ok(true) => return Ok(true),
+                    // Held by another PostgreSQL session.
+                    Ok(false) => self.forget(name),
+                    Err(e) => {
+                        self.forget(name);
+                        return Err(e);
+                    }
+                },
+                Claim::HeldElsewhere => {}
+            }
+            let wait = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Ok(false);
+                    }
+                    left.min(POLL_INTERVAL)
+                }
+                None => POLL_INTERVAL,
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// RELEASE_LOCK: `Some(true)` if released, `Some(false)` if someone else holds it, `None` if
+    /// nobody does.
+    pub async fn release(
+        &self,
+        client: &Client,
+        connection: u32,
+        name: &str,
+    ) -> Result<Option<bool>, tokio_postgres::Error> {
+        let released = {
+            let mut held = self.held.lock().unwrap();
+            match held.get_mut(name) {
+                Some(holder) if holder.connection != connection => return Ok(Some(false)),
+                Some(holder) => {
+                    holder.count -= 1;
+                    if holder.count == 0 {
+                        held.remove(name);
+                        true
+                    } else {
+                        return Ok(Some(true));
+                    }
+                }
+                None => false,
+            }
+        };
+        if released {
+            advisory_unlock(client, name).await?;
+            return Ok(Some(true));
+        }
+        Ok(if is_free_upstream(client, name).await? {
+            None
+        } else {
+            Some(false)
+        })
+    }
+
+    /// IS_FREE_LOCK: whether nobody holds the lock.
+    pub async fn is_free(
+        &self,
+        client: &Client,
+        name: &str,
+    ) -> Result<bool, tokio_postgres::Error> {
+        if self.held.lock().unwrap().contains_key(name) {
+            return Ok(false);
+        }
+        is_free_upstream(client, name).await
+    }
+
+    /// RELEASE_ALL_LOCKS, and what happens when a connection goes away: releases every lock
+    /// `connection` holds and returns how many times they had been taken.
+    pub async fn release_all(
+        &self,
+        client: &Client,
+        connection: u32,
+    ) -> Result<u64, tokio_postgres::Error> {
+        let released: Vec<(String, u32)> = {
+            let mut held = self.held.lock().unwrap();
+            let names: Vec<String> = held
+                .iter()
+                .filter(|(_, holder)| holder.connection == connection)
+                .map(|(name, _)| name.clone())
+                .collect();
+            names
+                .into_iter()
+                .filter_map(|name| held.remove(&name).map(|holder| (name, holder.count)))
+                .collect()
+        };
+        let mut count = 0;
+        for (name, taken) in released {
+            advisory_unlock(client, &name).await?;
+            count += u64::from(taken);
+        }
+        Ok(count)
+    }
+
+    fn claim(&self, connection: u32, name: &str) -> Claim {
+        let mut held = self.held.lock().unwrap();
+        match held.get_mut(name) {
+            Some(holder) if holder.connection == connection => {
+                holder.count += 1;
+                Claim::Reentered
+            }
+            Some(_) => Claim::HeldElsewhere,
+            None => {
+                held.insert(
+                    name.to_string(),
+                    Holder {
+                        connection,
+                        count: 1,
+                    },
+                );
+                Claim::Reserved
+            }
+        }
+    }
+
+    fn forget(&self, name: &str) {
+        self.held.lock().unwrap().remove(name);
+    }
+}
+
+async fn try_advisory_lock(client: &Client, name: &str) -> Result<bool, tokio_postgres::Error> {
+    let row = client
+        .query_one("SELECT pg_try_advisory_lock(hashtext($1))", &[&name])
+        .await?;
+    Ok(row.get(0))
+}
+
+async fn advisory_unlock(client: &Client, name: &str) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute("SELECT pg_advisory_unlock(hashtext($1))", &[&name])
+        .await?;
+    Ok(())
+}
+
+// Whether no other PostgreSQL session holds the lock. Only called for locks no connection of
+// this proxy holds, so taking the lock for a moment tells.
+async fn is_free_upstream(client: &Client, name: &str) -> Result<bool, tokio_postgres::Error> {
+    if try_advisory_lock(client, name).await? {
+        advisory_unlock(client, name).await?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Recognizes `SELECT GET_LOCK('name', timeout) [[AS] alias]` and the other lock functions
+/// called on their own with literal arguments, which is how scripts use them.
+pub fn parse(tokens: &[Token]) -> Option<LockQuery> {
+    let [select, function, Token::LParen, rest @ ..] = tokens else {
+        return None;
+    };
+    if !select.is_word("SELECT") {
+        return None;
+    }
+    let close = rest.iter().position(|t| *t == Token::RParen)?;
+    let (args, alias) = (&rest[..close], &rest[close + 1..]);
+
+    let call = if function.is_word("GET_LOCK") {
+        match args {
+            [name, Token::Comma, timeout @ ..] => LockCall::Get {
+                name: string_value(name)?,
+                timeout: number_value(timeout)?,
+            },
+            _ => return None,
+        }
+    } else if function.is_word("RELEASE_LOCK") {
+        LockCall::Release {
+            name: string_value(single(args)?)?,
+        }
+    } else if function.is_word("IS_FREE_LOCK") {
+        LockCall::IsFree {
+            name: string_value(single(args)?)?,
+        }
+    } else if function.is_word("RELEASE_ALL_LOCKS") && args.is_empty() {
+        LockCall::ReleaseAll
+    } else {
+        return None;
+    };
+
+    let label = match alias {
+        [] => {
+            let args: Vec<String> = args
+                .split(|t| *t == Token::Comma)
+                .map(|arg| arg.iter().map(Token::to_string).collect())
+                .collect();
+            format!("{}({})", function, args.join(", "))
+        }
+        [as_, alias] if as_.is_word("AS") => alias_name(alias)?,
+        [alias] => alias_name(alias)?,
+        _ => return None,
+    };
+    Some(LockQuery { call, label })
+}
+
+fn single(args: &[Token]) -> Option<&Token> {
+    match args {
+        [arg] => Some(arg),
+        _ => None,
+    }
+}
+
+fn string_value(token: &Token) -> Option<String> {
+    match token {
+        Token::String(raw) | Token::DoubleQuoted(raw) => Some(literals::mysql_string_value(raw)),
+        _ => None,
+    }
+}
+
+// A number, possibly negative; NULL counts as no wait.
+fn number_value(tokens: &[Token]) -> Option<f64> {
+    match tokens {
+        [Token::Number(n)] => n.parse().ok(),
+        [minus, Token::Number(n)] if minus.is_operator("-") => n.parse::<f64>().ok().map(|n| -n),
+        [null] if null.is_word("NULL") => Some(0.0),
+        _ => None,
+    }
+}
+
+pub async fn execute(client: &Client, locks: &Locks, connection: u32, query: LockQuery) -> Reply {
+    let name = match &query.call {
+        LockCall::Get { name, .. } | LockCall::Release { name } | LockCall::IsFree { name } => {
+            Some(name)
+        }
+        LockCall::ReleaseAll => None,
+    };
+    if let Some(name) = name {
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(MysqlError::new(
+                ErrorKind::ER_WRONG_ARGUMENTS,
+                format!("Incorrect user-level lock name '{}'.", name),
+            ));
+        }
+    }
+
+    let value = match query.call {
+        LockCall::Get { name, timeout } => {
+            Some(flag(locks.get(client, connection, &name, timeout).await?))
+        }
+        LockCall::Release { name } => locks.release(client, connection, &name).await?.map(flag),
+        LockCall::IsFree { name } => Some(flag(locks.is_free(client, &name).await?)),
+        LockCall::ReleaseAll => Some(locks.release_all(client, connection).await?.to_string()),
+    };
+    let mut result = ResultSet::new(&[query.label]);
+    result.push_row(vec![value]);
+    Ok(result)
+}
+
+fn flag(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
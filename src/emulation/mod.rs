@@ -0,0 +1,154 @@
+// Statements the proxy answers itself instead of forwarding to PostgreSQL, mostly MySQL's
+// SHOW family and other server introspection that has no PostgreSQL equivalent.
+
+pub mod caches;
+pub mod connect_attrs;
+pub mod diagnostics;
+pub mod digests;
+pub mod estimated_count;
+pub mod explain;
+pub mod field_list;
+pub mod grants;
+pub mod information_schema;
+pub mod kill;
+pub mod locks;
+pub mod processlist;
+pub mod profiling;
+pub mod routine_code;
+pub mod shadow_mismatches;
+pub mod show_create;
+pub mod status;
+pub mod translation_stats;
+pub mod variables;
+pub mod virtual_tables;
+
+use tokio_postgres::Client;
+
+use crate::catalog::ObjectName;
+use crate::diagnostics::Diagnostics;
+use crate::error::MysqlError;
+use crate::resultset::ResultSet;
+use crate::sessions::Sessions;
+use crate::stats::Stats;
+use crate::translator::{self, literals, Node, Token};
+use locks::Locks;
+
+pub type Reply = Result<ResultSet, MysqlError>;
+
+/// Answers `sql` if it is an emulated statement, `None` if it should go to PostgreSQL.
+///
+/// Apart from SHOW WARNINGS and SHOW ERRORS, which read them, every statement starts by
+/// clearing the session's diagnostics.
+pub async fn handle(
+    client: &Client,
+    sql: &str,
+    diagnostics: &mut Diagnostics,
+    locks: &Locks,
+    connection: u32,
+    sessions: &Sessions,
+    stats: &Stats,
+) -> Option<Reply> {
+    let tokens = translator::significant_tokens(sql);
+    if let Some(show) = tokens.as_deref().and_then(diagnostics::parse) {
+        return Some(Ok(diagnostics::execute(diagnostics, show)));
+    }
+    diagnostics.clear();
+    let tokens = tokens?;
+
+    if let Some(target) = show_create::parse(&tokens) {
+        return Some(show_create::execute(client, target).await);
+    }
+    if let Some((kind, name)) = routine_code::parse(&tokens) {
+        return Some(routine_code::execute(client, kind, &name).await);
+    }
+    if let Some(limit) = digests::parse(&tokens) {
+        return Some(Ok(digests::execute(stats, limit)));
+    }
+    if let Some(limit) = shadow_mismatches::parse(&tokens) {
+        return Some(Ok(shadow_mismatches::execute(stats, limit)));
+    }
+    if translation_stats::parse(&tokens) {
+        return Some(Ok(translation_stats::execute(stats)));
+    }
+    if let Some(pattern) = status::parse(&tokens) {
+        return Some(Ok(status::execute(stats, sessions, &pattern)));
+    }
+    if let Some(full) = processlist::parse(&tokens) {
+        return Some(Ok(processlist::execute(sessions, full)));
+    }
+    if let Some(query) = locks::parse(&tokens) {
+        return Some(locks::execute(client, locks, connection, query).await);
+    }
+    None
+}
+
+/// Reads a possibly qualified object name (`name`, `db.name`) from the front of `tokens`.
+pub fn object_name(tokens: &[Token]) -> Option<(ObjectName, &[Token])> {
+    let first = literals::identifier_name(tokens.first()?)?;
+    match (tokens.get(1), tokens.get(2)) {
+        (Some(dot), Some(second)) if dot.is_operator(".") => {
+            let name = literals::identifier_name(second)?;
+            Some((
+                ObjectName {
+                    schema: Some(first),
+                    name,
+                },
+                &tokens[3..],
+            ))
+        }
+        _ => Some((
+            ObjectName {
+                schema: None,
+                name: first,
+            },
+            &tokens[1..],
+        )),
+    }
+}
+
+/// The name a column alias gives, quoted as a string or as an identifier.
+pub fn alias_name(token: &Token) -> Option<String> {
+    match token {
+        Token::String(raw) | Token::DoubleQuoted(raw) => Some(literals::mysql_string_value(raw)),
+        _ => literals::identifier_name(token),
+    }
+}
+
+/// Quotes a name with backticks, the way MySQL prints identifiers.
+pub fn backtick(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+// Words that can follow a table reference without being its alias.
+const NOT_ALIAS: &[&str] = &[
+    "WHERE",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "CROSS",
+    "NATURAL",
+    "FULL",
+    "ON",
+    "USING",
+    "GROUP",
+    "ORDER",
+    "LIMIT",
+    "HAVING",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "WINDOW",
+    "FOR",
+    "OFFSET",
+    "FETCH",
+];
+
+/// Whether `node`, following a table reference, is the table's alias.
+pub fn is_table_alias(node: &Node) -> bool {
+    match node {
+        Node::Token(Token::Word(w)) => !NOT_ALIAS.iter().any(|k| w.eq_ignore_ascii_case(k)),
+        Node::Token(Token::QuotedIdent(_)) => true,
+        _ => false,
+    }
+}
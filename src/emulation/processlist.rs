@@ -0,0 +1,69 @@
+// SHOW [FULL] PROCESSLIST: the proxy's client connections and what each is running.
+//
+// Command is Query or Execute while a statement runs and Sleep between statements, and Time is
+// the seconds since that started. db is the database chosen with USE. Without FULL, Info is cut
+// to its first 100 characters, as in MySQL. Program, last as MariaDB's Progress is, is the
+// program_name the client sent as it connected, or failing that its _client_name.
+
+use crate::resultset::ResultSet;
+use crate::sessions::Sessions;
+use crate::translator::Token;
+
+// How much of the statement SHOW PROCESSLIST shows without FULL.
+const INFO_LENGTH: usize = 100;
+
+/// Whether the statement is SHOW PROCESSLIST, and if so whether it is SHOW FULL PROCESSLIST.
+pub fn parse(tokens: &[Token]) -> Option<bool> {
+    match tokens {
+        [show, processlist] if show.is_word("SHOW") && processlist.is_word("PROCESSLIST") => {
+            Some(false)
+        }
+        [show, full, processlist]
+            if show.is_word("SHOW")
+                && full.is_word("FULL")
+                && processlist.is_word("PROCESSLIST") =>
+        {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+pub fn execute(sessions: &Sessions, full: bool) -> ResultSet {
+    let mut result = ResultSet::new(&[
+        "Id", "User", "Host", "db", "Command", "Time", "State", "Info", "Program",
+    ]);
+    for process in sessions.processes() {
+        let state = if process.info.is_some() {
+            "executing"
+        } else {
+            ""
+        };
+        let attr = |name: &str| {
+            process
+                .connect_attrs
+                .iter()
+                .find(|(attr, _)| attr == name)
+                .map(|(_, value)| value.clone())
+        };
+        let program = attr("program_name").or_else(|| attr("_client_name"));
+        let info = process
+            .info
+            .map(|info| match info.char_indices().nth(INFO_LENGTH) {
+                Some((end, _)) if !full => info[..end].to_string(),
+                _ => info,
+            });
+        result.push_row(vec![
+            Some(process.id.to_string()),
+            process.user,
+            Some(process.host),
+            process.db,
+            Some(process.command.to_string()),
+            Some(process.time.to_string()),
+            Some(state.to_string()),
+            info,
+            program,
+        ]);
+    }
+    result
+}
@@ -0,0 +1,167 @@
+// SET profiling, SHOW PROFILES and SHOW PROFILE, over the connection's statement timings (see
+// profiling.rs):
+//
+//   SET [SESSION] profiling = {0 | 1 | OFF | ON}
+//   SHOW PROFILES
+//   SHOW PROFILE [type [, type] ...] [FOR QUERY n] [LIMIT n [OFFSET m]]
+//
+// SHOW PROFILE lists the proxy's phases rather than MySQL's stages. Its types ask MySQL for CPU,
+// memory and other counters next to the durations; the proxy only measures durations, so they
+// are accepted and left out.
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::profiling::Profiler;
+use crate::resultset::ResultSet;
+use crate::translator::{self, literals, Token};
+
+// The words SHOW PROFILE's types are made of.
+const TYPE_WORDS: &[&str] = &[
+    "ALL", "BLOCK", "IO", "CONTEXT", "SWITCHES", "CPU", "IPC", "MEMORY", "PAGE", "FAULTS",
+    "SOURCE", "SWAPS",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    // The value, unquoted.
+    SetProfiling(String),
+    ShowProfiles,
+    ShowProfile {
+        query: Option<u32>,
+        limit: Option<usize>,
+        offset: usize,
+    },
+}
+
+pub fn parse(sql: &str) -> Option<Statement> {
+    let tokens = translator::significant_tokens(sql)?;
+    match &tokens[..] {
+        [set, rest @ ..] if set.is_word("SET") => parse_set(rest),
+        [show, profiles] if show.is_word("SHOW") && profiles.is_word("PROFILES") => {
+            Some(Statement::ShowProfiles)
+        }
+        [show, profile, rest @ ..] if show.is_word("SHOW") && profile.is_word("PROFILE") => {
+            parse_show_profile(rest)
+        }
+        _ => None,
+    }
+}
+
+fn parse_set(tokens: &[Token]) -> Option<Statement> {
+    let tokens = match tokens {
+        [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => rest,
+        _ => tokens,
+    };
+    let [name, assign, value] = tokens else {
+        return None;
+    };
+    let named = match name {
+        Token::Word(word) => word.eq_ignore_ascii_case("profiling"),
+        Token::Variable(variable) => ["@@", "@@session.", "@@local."]
+            .iter()
+            .any(|prefix| variable.eq_ignore_ascii_case(&format!("{}profiling", prefix))),
+        _ => false,
+    };
+    if !named || !(assign.is_operator("=") || assign.is_operator(":=")) {
+        return None;
+    }
+    let value = match value {
+        Token::String(raw) => literals::mysql_string_value(raw),
+        other => other.to_string(),
+    };
+    Some(Statement::SetProfiling(value))
+}
+
+fn parse_show_profile(tokens: &[Token]) -> Option<Statement> {
+    let types = tokens
+        .iter()
+        .take_while(|token| !token.is_word("FOR") && !token.is_word("LIMIT"))
+        .count();
+    let (types, rest) = tokens.split_at(types);
+    if !types.iter().all(|token| {
+        matches!(token, Token::Comma) || TYPE_WORDS.iter().any(|word| token.is_word(word))
+    }) {
+        return None;
+    }
+    let (query, rest) = match rest {
+        [for_, query, Token::Number(id), rest @ ..]
+            if for_.is_word("FOR") && query.is_word("QUERY") =>
+        {
+            (Some(id.parse().ok()?), rest)
+        }
+        _ => (None, rest),
+    };
+    let (limit, offset) = match rest {
+        [] => (None, 0),
+        [limit, Token::Number(n)] if limit.is_word("LIMIT") => (Some(n.parse().ok()?), 0),
+        [limit, Token::Number(n), offset, Token::Number(m)]
+            if limit.is_word("LIMIT") && offset.is_word("OFFSET") =>
+        {
+            (Some(n.parse().ok()?), m.parse().ok()?)
+        }
+        _ => return None,
+    };
+    Some(Statement::ShowProfile {
+        query,
+        limit,
+        offset,
+    })
+}
+
+/// Runs the statement: SET answers with OK, the SHOW statements with a result set.
+pub fn execute(
+    profiler: &mut Profiler,
+    statement: Statement,
+) -> Result<Option<ResultSet>, MysqlError> {
+    match statement {
+        Statement::SetProfiling(value) => {
+            let enabled = match value.to_ascii_uppercase().as_str() {
+                "1" | "ON" | "TRUE" => true,
+                "0" | "OFF" | "FALSE" | "DEFAULT" => false,
+                _ => {
+                    return Err(MysqlError::new(
+                        ErrorKind::ER_WRONG_VALUE_FOR_VAR,
+                        format!(
+                            "Variable 'profiling' can't be set to the value of '{}'",
+                            value
+                        ),
+                    ))
+                }
+            };
+            profiler.set_enabled(enabled);
+            Ok(None)
+        }
+        Statement::ShowProfiles => {
+            let mut result = ResultSet::new(&["Query_ID", "Duration", "Query"]);
+            for profile in profiler.history() {
+                result.push_row(vec![
+                    Some(profile.query_id.to_string()),
+                    Some(format!("{:.8}", profile.duration().as_secs_f64())),
+                    Some(profile.sql.clone()),
+                ]);
+            }
+            Ok(Some(result))
+        }
+        Statement::ShowProfile {
+            query,
+            limit,
+            offset,
+        } => {
+            let mut result = ResultSet::new(&["Status", "Duration"]);
+            if let Some(profile) = profiler.profile(query) {
+                for (phase, duration) in profile
+                    .phases()
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                {
+                    result.push_row(vec![
+                        Some(phase.status().to_string()),
+                        Some(format!("{:.6}", duration.as_secs_f64())),
+                    ]);
+                }
+            }
+            Ok(Some(result))
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// SHOW PROCEDURE CODE / SHOW FUNCTION CODE.
+//
+// MySQL lists the instructions it compiled a routine to; the proxy lists the lines of the
+// PL/pgSQL it translated the routine to, which is what PostgreSQL runs, for comparing with the
+// source SHOW CREATE shows.
+
+use opensrv_mysql::ErrorKind;
+use tokio_postgres::Client;
+
+use super::{object_name, Reply};
+use crate::catalog::ObjectName;
+use crate::error::MysqlError;
+use crate::resultset::ResultSet;
+use crate::translator::Token;
+
+/// SHOW {PROCEDURE | FUNCTION} CODE name: the kind (`PROCEDURE` or `FUNCTION`) and the name.
+pub fn parse(tokens: &[Token]) -> Option<(&'static str, ObjectName)> {
+    let [show, kind, code, rest @ ..] = tokens else {
+        return None;
+    };
+    if !show.is_word("SHOW") || !code.is_word("CODE") {
+        return None;
+    }
+    let kind = ["PROCEDURE", "FUNCTION"]
+        .into_iter()
+        .find(|word| kind.is_word(word))?;
+    match object_name(rest)? {
+        (name, []) => Some((kind, name)),
+        _ => None,
+    }
+}
+
+pub async fn execute(client: &Client, kind: &str, name: &ObjectName) -> Reply {
+    let prokind = if kind == "PROCEDURE" { "p" } else { "f" };
+    let row = client
+        .query_opt(
+            "SELECT pg_get_functiondef(p.oid) FROM pg_proc p \
+             JOIN pg_namespace n ON n.oid = p.pronamespace \
+             WHERE p.proname = $1 AND n.nspname = COALESCE($2::text, current_schema()) \
+               AND p.prokind::text = $3 \
+             LIMIT 1",
+            &[&name.name, &name.schema, &prokind],
+        )
+        .await?;
+    let Some(row) = row else {
+        return Err(MysqlError::new(
+            ErrorKind::ER_SP_DOES_NOT_EXIST,
+            format!("{} {} does not exist", kind, name.name),
+        ));
+    };
+    let definition: String = row.get(0);
+
+    let mut result = ResultSet::new(&["Pos", "Instruction"]);
+    for (pos, line) in definition.trim_end().lines().enumerate() {
+        result.push_row(vec![Some(pos.to_string()), Some(line.to_string())]);
+    }
+    Ok(result)
+}
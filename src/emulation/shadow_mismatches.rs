@@ -0,0 +1,59 @@
+// SHOW PROXY SHADOW MISMATCHES [LIMIT n]: the statements the shadow MySQL server (see shadow.rs)
+// disagreed with PostgreSQL on, by digest and kind of mismatch, the most frequent first, with
+// the latest example of each. The same data is in proxy_stats.shadow_mismatches, for filtering
+// with SQL.
+
+use crate::resultset::ResultSet;
+use crate::stats::Stats;
+use crate::translator::Token;
+
+/// The number of mismatches to list, `None` for all of them.
+pub fn parse(tokens: &[Token]) -> Option<Option<usize>> {
+    match tokens {
+        [show, proxy, shadow, mismatches, rest @ ..]
+            if show.is_word("SHOW")
+                && proxy.is_word("PROXY")
+                && shadow.is_word("SHADOW")
+                && mismatches.is_word("MISMATCHES") =>
+        {
+            match rest {
+                [] => Some(None),
+                [limit, Token::Number(count)] if limit.is_word("LIMIT") => {
+                    Some(Some(count.parse().ok()?))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn execute(stats: &Stats, limit: Option<usize>) -> ResultSet {
+    let mut result = ResultSet::new(&[
+        "Digest",
+        "Digest_text",
+        "Mismatch",
+        "Count",
+        "First_seen",
+        "Last_seen",
+        "Example",
+        "PostgreSQL",
+        "MySQL",
+    ]);
+    let mismatches = stats.shadow_mismatches();
+    let limit = limit.unwrap_or(mismatches.len());
+    for (fingerprint, mismatch, counts) in mismatches.into_iter().take(limit) {
+        result.push_row(vec![
+            Some(fingerprint),
+            Some(counts.text),
+            Some(mismatch.to_string()),
+            Some(counts.count.to_string()),
+            Some(counts.first_seen.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Some(counts.last_seen.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Some(counts.example),
+            Some(counts.postgres),
+            Some(counts.mysql),
+        ]);
+    }
+    result
+}
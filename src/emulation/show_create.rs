@@ -0,0 +1,402 @@
+// SHOW CREATE DATABASE / TABLE / VIEW / PROCEDURE / FUNCTION.
+//
+// The output is rebuilt from the PostgreSQL catalogs and rendered in MySQL syntax, close enough
+// for tools that parse it (ORMs diffing schemas, GUI clients showing DDL). Routines created
+// through the proxy show the MySQL source they were created with (see routine_sources.rs).
+
+use tokio_postgres::Client;
+
+use super::{backtick, object_name, Reply};
+use crate::catalog::{self, ColumnInfo, ForeignKeyInfo, IndexInfo, ObjectName};
+use crate::error::MysqlError;
+use crate::resultset::ResultSet;
+use crate::routine_sources;
+use crate::translator::{lexer, literals, Token};
+use opensrv_mysql::ErrorKind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShowCreate {
+    Database(String),
+    Table(ObjectName),
+    View(ObjectName),
+    Procedure(ObjectName),
+    Function(ObjectName),
+}
+
+pub fn parse(tokens: &[Token]) -> Option<ShowCreate> {
+    let [show, create, kind, rest @ ..] = tokens else {
+        return None;
+    };
+    if !show.is_word("SHOW") || !create.is_word("CREATE") {
+        return None;
+    }
+
+    if kind.is_word("DATABASE") || kind.is_word("SCHEMA") {
+        let rest = match rest {
+            [if_, not, exists, rest @ ..]
+                if if_.is_word("IF") && not.is_word("NOT") && exists.is_word("EXISTS") =>
+            {
+                rest
+            }
+            _ => rest,
+        };
+        return match rest {
+            [name] => literals::identifier_name(name).map(ShowCreate::Database),
+            _ => None,
+        };
+    }
+
+    let (name, rest) = object_name(rest)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    if kind.is_word("TABLE") {
+        Some(ShowCreate::Table(name))
+    } else if kind.is_word("VIEW") {
+        Some(ShowCreate::View(name))
+    } else if kind.is_word("PROCEDURE") {
+        Some(ShowCreate::Procedure(name))
+    } else if kind.is_word("FUNCTION") {
+        Some(ShowCreate::Function(name))
+    } else {
+        None
+    }
+}
+
+pub async fn execute(client: &Client, target: ShowCreate) -> Reply {
+    match target {
+        ShowCreate::Database(name) => show_create_database(client, &name).await,
+        ShowCreate::Table(name) => show_create_table(client, &name).await,
+        ShowCreate::View(name) => match show_create_view(client, &name).await? {
+            Some(result) => Ok(result),
+            None => Err(no_such_table(client, &name).await),
+        },
+        ShowCreate::Procedure(name) => show_create_routine(client, &name, "PROCEDURE").await,
+        ShowCreate::Function(name) => show_create_routine(client, &name, "FUNCTION").await,
+    }
+}
+
+// Charset and collation MySQL would report for a database with the given PostgreSQL encoding.
+fn mysql_charset(pg_encoding: &str) -> (&'static str, &'static str) {
+    match pg_encoding {
+        "LATIN1" => ("latin1", "latin1_swedish_ci"),
+        "SQL_ASCII" => ("ascii", "ascii_general_ci"),
+        _ => ("utf8mb4", "utf8mb4_0900_ai_ci"),
+    }
+}
+
+async fn show_create_database(client: &Client, name: &str) -> Reply {
+    let row = client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1) \
+                 OR EXISTS (SELECT 1 FROM pg_database WHERE datname = $1), \
+                 pg_encoding_to_char(encoding)::text \
+             FROM pg_database WHERE datname = current_database()",
+            &[&name],
+        )
+        .await?;
+    let exists: bool = row.get(0);
+    if !exists {
+        return Err(MysqlError::new(
+            ErrorKind::ER_BAD_DB_ERROR,
+            format!("Unknown database '{}'", name),
+        ));
+    }
+    let encoding: String = row.get(1);
+    let (charset, collation) = mysql_charset(&encoding);
+
+    let mut result = ResultSet::new(&["Database", "Create Database"]);
+    result.push_row(vec![
+        Some(name.to_string()),
+        Some(format!(
+            "CREATE DATABASE {} /*!40100 DEFAULT CHARACTER SET {} COLLATE {} */ \
+             /*!80016 DEFAULT ENCRYPTION='N' */",
+            backtick(name),
+            charset,
+            collation
+        )),
+    ]);
+    Ok(result)
+}
+
+async fn show_create_table(client: &Client, name: &ObjectName) -> Reply {
+    // MySQL answers SHOW CREATE TABLE on a view with the view definition.
+    if let Some(view) = show_create_view(client, name).await? {
+        return Ok(view);
+    }
+
+    let Some(create) = table_definition(client, name).await? else {
+        return Err(no_such_table(client, name).await);
+    };
+
+    let mut result = ResultSet::new(&["Table", "Create Table"]);
+    result.push_row(vec![Some(name.name.clone()), Some(create)]);
+    Ok(result)
+}
+
+/// The MySQL CREATE TABLE statement of table `name`, `None` if there is no such table.
+pub async fn table_definition(
+    client: &Client,
+    name: &ObjectName,
+) -> Result<Option<String>, tokio_postgres::Error> {
+    let columns = catalog::table_columns(client, name).await?;
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    let indexes = catalog::table_indexes(client, name).await?;
+    let foreign_keys = catalog::table_foreign_keys(client, name).await?;
+    let mut create = create_table_sql(&name.name, &columns, &indexes, &foreign_keys);
+    if catalog::is_temporary(client, name).await? {
+        create = create.replacen("CREATE TABLE", "CREATE TEMPORARY TABLE", 1);
+    }
+    Ok(Some(create))
+}
+
+/// Renders a MySQL CREATE TABLE statement for a table described by the catalog.
+pub fn create_table_sql(
+    table: &str,
+    columns: &[ColumnInfo],
+    indexes: &[IndexInfo],
+    foreign_keys: &[ForeignKeyInfo],
+) -> String {
+    let mut lines: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let mut line = format!(
+                "  {} {}",
+                backtick(&column.name),
+                catalog::mysql_column_type(&column.pg_type)
+            );
+            if !column.nullable {
+                line.push_str(" NOT NULL");
+            }
+            match &column.default {
+                Some(default) => {
+                    line.push_str(" DEFAULT ");
+                    line.push_str(&catalog::mysql_default(default));
+                }
+                None if column.nullable => line.push_str(" DEFAULT NULL"),
+                None => {}
+            }
+            if column.auto_increment {
+                line.push_str(" AUTO_INCREMENT");
+            }
+            line
+        })
+        .collect();
+
+    for index in indexes {
+        let columns: Vec<String> = index.columns.iter().map(|c| backtick(c)).collect();
+        let columns = columns.join(",");
+        lines.push(if index.primary {
+            format!("  PRIMARY KEY ({})", columns)
+        } else if index.unique {
+            format!("  UNIQUE KEY {} ({})", backtick(&index.name), columns)
+        } else {
+            format!("  KEY {} ({})", backtick(&index.name), columns)
+        });
+    }
+
+    for foreign_key in foreign_keys {
+        let columns: Vec<String> = foreign_key.columns.iter().map(|c| backtick(c)).collect();
+        let referenced_columns: Vec<String> = foreign_key
+            .referenced_columns
+            .iter()
+            .map(|c| backtick(c))
+            .collect();
+        let referenced_table = match &foreign_key.referenced_schema {
+            Some(schema) => format!(
+                "{}.{}",
+                backtick(schema),
+                backtick(&foreign_key.referenced_table)
+            ),
+            None => backtick(&foreign_key.referenced_table),
+        };
+        let mut line = format!(
+            "  CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({})",
+            backtick(&foreign_key.name),
+            columns.join(", "),
+            referenced_table,
+            referenced_columns.join(", ")
+        );
+        if let Some(action) = foreign_key.on_delete {
+            line.push_str(" ON DELETE ");
+            line.push_str(action);
+        }
+        if let Some(action) = foreign_key.on_update {
+            line.push_str(" ON UPDATE ");
+            line.push_str(action);
+        }
+        lines.push(line);
+    }
+
+    format!(
+        "CREATE TABLE {} (\n{}\n) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci",
+        backtick(table),
+        lines.join(",\n")
+    )
+}
+
+async fn show_create_view(
+    client: &Client,
+    name: &ObjectName,
+) -> Result<Option<ResultSet>, MysqlError> {
+    let row = client
+        .query_opt(
+            "SELECT viewowner::text, definition FROM pg_views \
+             WHERE viewname = $1 AND schemaname = COALESCE($2::text, current_schema())",
+            &[&name.name, &name.schema],
+        )
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let owner: String = row.get(0);
+    let definition: String = row.get(1);
+
+    let mut result = ResultSet::new(&[
+        "View",
+        "Create View",
+        "character_set_client",
+        "collation_connection",
+    ]);
+    result.push_row(vec![
+        Some(name.name.clone()),
+        Some(format!(
+            "CREATE ALGORITHM=UNDEFINED DEFINER={}@`%` SQL SECURITY DEFINER VIEW {} AS {}",
+            backtick(&owner),
+            backtick(&name.name),
+            mysql_view_definition(&definition)
+        )),
+        Some("utf8mb4".to_string()),
+        Some("utf8mb4_0900_ai_ci".to_string()),
+    ]);
+    Ok(Some(result))
+}
+
+// Words that continue a multi-word PostgreSQL type name after a `::` cast.
+const TYPE_CONTINUATIONS: &[&str] = &["varying", "precision", "with", "without", "time", "zone"];
+
+/// Re-renders a pg_views definition in MySQL style: backtick identifiers, no `::type` casts, and
+/// on a single line.
+fn mysql_view_definition(definition: &str) -> String {
+    let Ok(tokens) = lexer::tokenize(definition.trim().trim_end_matches(';')) else {
+        return definition.trim().to_string();
+    };
+
+    let mut out = String::with_capacity(definition.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Operator(op) if op == "::" => {
+                i += 1;
+                // Type name, possibly several words, a modifier and array brackets.
+                if matches!(tokens.get(i), Some(Token::Word(_) | Token::DoubleQuoted(_))) {
+                    i += 1;
+                }
+                loop {
+                    match (tokens.get(i), tokens.get(i + 1)) {
+                        (Some(Token::Whitespace(_)), Some(Token::Word(w)))
+                            if TYPE_CONTINUATIONS.iter().any(|c| w.eq_ignore_ascii_case(c)) =>
+                        {
+                            i += 2;
+                        }
+                        (Some(Token::LParen), _) => {
+                            while i < tokens.len() && tokens[i] != Token::RParen {
+                                i += 1;
+                            }
+                            i += 1;
+                        }
+                        (Some(open), Some(close))
+                            if open.is_operator("[") && close.is_operator("]") =>
+                        {
+                            i += 2;
+                        }
+                        _ => break,
+                    }
+                }
+                continue;
+            }
+            Token::DoubleQuoted(raw) => {
+                out.push_str(&backtick(&raw[1..raw.len() - 1].replace("\"\"", "\"")));
+            }
+            Token::Whitespace(_) => {
+                if !out.ends_with(' ') && !out.ends_with('(') {
+                    out.push(' ');
+                }
+            }
+            other => {
+                if *other == Token::RParen && out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push_str(&other.to_string());
+            }
+        }
+        i += 1;
+    }
+    out.trim().to_string()
+}
+
+async fn show_create_routine(client: &Client, name: &ObjectName, kind: &str) -> Reply {
+    let prokind = if kind == "PROCEDURE" { "p" } else { "f" };
+    let row = client
+        .query_opt(
+            "SELECT pg_get_functiondef(p.oid), n.nspname::text FROM pg_proc p \
+             JOIN pg_namespace n ON n.oid = p.pronamespace \
+             WHERE p.proname = $1 AND n.nspname = COALESCE($2::text, current_schema()) \
+               AND p.prokind::text = $3 \
+             LIMIT 1",
+            &[&name.name, &name.schema, &prokind],
+        )
+        .await?;
+    let Some(row) = row else {
+        return Err(MysqlError::new(
+            ErrorKind::ER_SP_DOES_NOT_EXIST,
+            format!("{} {} does not exist", kind, name.name),
+        ));
+    };
+    // What the client wrote, if the proxy kept it, rather than the PL/pgSQL it became.
+    let source = match routine_sources::source(client, row.get(1), &name.name, kind).await? {
+        Some(source) => source,
+        None => row.get(0),
+    };
+
+    let title = if kind == "PROCEDURE" {
+        "Procedure"
+    } else {
+        "Function"
+    };
+    let mut result = ResultSet::new(&[
+        title.to_string(),
+        "sql_mode".to_string(),
+        format!("Create {}", title),
+        "character_set_client".to_string(),
+        "collation_connection".to_string(),
+        "Database Collation".to_string(),
+    ]);
+    result.push_row(vec![
+        Some(name.name.clone()),
+        Some(String::new()),
+        Some(source.trim_end().to_string()),
+        Some("utf8mb4".to_string()),
+        Some("utf8mb4_0900_ai_ci".to_string()),
+        Some("utf8mb4_0900_ai_ci".to_string()),
+    ]);
+    Ok(result)
+}
+
+pub async fn no_such_table(client: &Client, name: &ObjectName) -> MysqlError {
+    let schema = match &name.schema {
+        Some(schema) => schema.clone(),
+        None => client
+            .query_one("SELECT current_schema()::text", &[])
+            .await
+            .ok()
+            .and_then(|row| row.get(0))
+            .unwrap_or_default(),
+    };
+    MysqlError::new(
+        ErrorKind::ER_NO_SUCH_TABLE,
+        format!("Table '{}.{}' doesn't exist", schema, name.name),
+    )
+}
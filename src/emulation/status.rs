@@ -0,0 +1,59 @@
+// SHOW [GLOBAL | SESSION] STATUS [LIKE 'pattern']: the proxy's counters under the names MySQL's
+// status variables have, for monitoring tools that read them.
+//
+// There are only server-wide counters, so SESSION gives the same values as GLOBAL. With
+// STATS_FILE set, Connections, Questions and the byte counts go on from what earlier runs
+// counted; Uptime is always this process's.
+
+use super::field_list::like;
+use crate::resultset::ResultSet;
+use crate::sessions::Sessions;
+use crate::stats::Stats;
+use crate::translator::{literals, Token};
+
+/// The LIKE pattern of a SHOW STATUS statement, empty when it has none.
+pub fn parse(tokens: &[Token]) -> Option<String> {
+    let rest = match tokens {
+        [show, rest @ ..] if show.is_word("SHOW") => rest,
+        _ => return None,
+    };
+    let rest = match rest {
+        [scope, rest @ ..]
+            if scope.is_word("GLOBAL") || scope.is_word("SESSION") || scope.is_word("LOCAL") =>
+        {
+            rest
+        }
+        _ => rest,
+    };
+    match rest {
+        [status] if status.is_word("STATUS") => Some(String::new()),
+        [status, like, Token::String(pattern)]
+            if status.is_word("STATUS") && like.is_word("LIKE") =>
+        {
+            Some(literals::mysql_string_value(pattern))
+        }
+        _ => None,
+    }
+}
+
+pub fn execute(stats: &Stats, sessions: &Sessions, pattern: &str) -> ResultSet {
+    let (received, sent) = stats.bytes();
+    let uptime = stats.uptime().as_secs();
+    let variables = [
+        ("Bytes_received", received),
+        ("Bytes_sent", sent),
+        ("Connections", stats.connections()),
+        ("Queries", stats.statements()),
+        ("Questions", stats.statements()),
+        ("Threads_connected", sessions.processes().len() as u64),
+        ("Uptime", uptime),
+        ("Uptime_since_flush_status", uptime),
+    ];
+    let mut result = ResultSet::new(&["Variable_name", "Value"]);
+    for (name, value) in variables {
+        if pattern.is_empty() || like(pattern, name) {
+            result.push_row(vec![Some(name.to_string()), Some(value.to_string())]);
+        }
+    }
+    result
+}
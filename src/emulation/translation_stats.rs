@@ -0,0 +1,32 @@
+// SHOW PROXY TRANSLATION STATS: the statements that failed for want of translation, by construct.
+// The same data is in proxy_stats.translation_failures, for filtering with SQL.
+
+use crate::resultset::ResultSet;
+use crate::stats::Stats;
+use crate::translator::Token;
+
+pub fn parse(tokens: &[Token]) -> bool {
+    matches!(tokens, [show, proxy, translation, stats]
+        if show.is_word("SHOW") && proxy.is_word("PROXY")
+            && translation.is_word("TRANSLATION") && stats.is_word("STATS"))
+}
+
+pub fn execute(stats: &Stats) -> ResultSet {
+    let mut result = ResultSet::new(&[
+        "Category",
+        "Construct",
+        "Failures",
+        "Last_failure",
+        "Example",
+    ]);
+    for (failure, counts) in stats.failures() {
+        result.push_row(vec![
+            Some(failure.category.to_string()),
+            Some(failure.construct),
+            Some(counts.failures.to_string()),
+            Some(counts.last_failure.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Some(counts.example),
+        ]);
+    }
+    result
+}
@@ -0,0 +1,47 @@
+// SHOW [GLOBAL | SESSION] VARIABLES [LIKE 'pattern']: the system variables the proxy knows the
+// value of, the connection's character sets among them, which drivers read as they connect.
+//
+// GLOBAL gives the connection's values as SESSION does. Variables the proxy doesn't know aren't
+// listed.
+
+use super::field_list::like;
+use crate::resultset::ResultSet;
+use crate::translator::{literals, Token};
+
+/// The LIKE pattern of a SHOW VARIABLES statement, empty when it has none.
+pub fn parse(tokens: &[Token]) -> Option<String> {
+    let rest = match tokens {
+        [show, rest @ ..] if show.is_word("SHOW") => rest,
+        _ => return None,
+    };
+    let rest = match rest {
+        [scope, rest @ ..]
+            if scope.is_word("GLOBAL") || scope.is_word("SESSION") || scope.is_word("LOCAL") =>
+        {
+            rest
+        }
+        _ => rest,
+    };
+    match rest {
+        [variables] if variables.is_word("VARIABLES") => Some(String::new()),
+        [variables, like, Token::String(pattern)]
+            if variables.is_word("VARIABLES") && like.is_word("LIKE") =>
+        {
+            Some(literals::mysql_string_value(pattern))
+        }
+        _ => None,
+    }
+}
+
+/// `variables`, by name, as SHOW VARIABLES lists those matching `pattern`.
+pub fn execute(variables: &[(&str, String)], pattern: &str) -> ResultSet {
+    let mut variables = variables.to_vec();
+    variables.sort();
+    let mut result = ResultSet::new(&["Variable_name", "Value"]);
+    for (name, value) in variables {
+        if pattern.is_empty() || like(pattern, name) {
+            result.push_row(vec![Some(name.to_string()), Some(value)]);
+        }
+    }
+    result
+}
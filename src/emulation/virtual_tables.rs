@@ -0,0 +1,264 @@
+// Virtual tables in the `proxy_stats` schema.
+//
+// References to `proxy_stats.<table>` in a translated statement are replaced by an inline
+// subquery holding the current data, so the client can filter, sort and aggregate them with
+// plain SQL and PostgreSQL does the work:
+//
+//   SELECT * FROM proxy_stats.table_access WHERE writes > 0
+//   -> SELECT * FROM (SELECT * FROM (VALUES (...), ...) AS v (...)) AS table_access WHERE ...
+
+use super::digests::milliseconds;
+use super::is_table_alias;
+use crate::stats::Stats;
+use crate::translator::{self, literals, Node, Token};
+
+pub const SCHEMA: &str = "proxy_stats";
+
+// Column names and PostgreSQL types of each virtual table.
+const TABLE_ACCESS: &[(&str, &str)] = &[
+    ("user_name", "text"),
+    ("table_schema", "text"),
+    ("table_name", "text"),
+    ("reads", "bigint"),
+    ("writes", "bigint"),
+    ("last_access", "text"),
+];
+const METRICS: &[(&str, &str)] = &[("name", "text"), ("value", "bigint")];
+const DIGESTS: &[(&str, &str)] = &[
+    ("digest", "text"),
+    ("digest_text", "text"),
+    ("count", "bigint"),
+    ("errors", "bigint"),
+    ("total_ms", "double precision"),
+    ("avg_ms", "double precision"),
+    ("min_ms", "double precision"),
+    ("max_ms", "double precision"),
+    ("first_seen", "text"),
+    ("last_seen", "text"),
+];
+const SHADOW_MISMATCHES: &[(&str, &str)] = &[
+    ("digest", "text"),
+    ("digest_text", "text"),
+    ("mismatch", "text"),
+    ("count", "bigint"),
+    ("first_seen", "text"),
+    ("last_seen", "text"),
+    ("example", "text"),
+    ("postgres_result", "text"),
+    ("mysql_result", "text"),
+];
+const TRANSLATION_FAILURES: &[(&str, &str)] = &[
+    ("category", "text"),
+    ("construct", "text"),
+    ("failures", "bigint"),
+    ("last_failure", "text"),
+    ("example", "text"),
+];
+
+/// Expands the virtual tables referenced by `sql`. `None` if it doesn't reference any.
+pub fn expand(sql: &str, stats: &Stats) -> Option<String> {
+    if !sql.to_ascii_lowercase().contains(SCHEMA) {
+        return None;
+    }
+    replace_tables(sql, &|schema, table| match schema == SCHEMA {
+        true => subquery(table, stats),
+        false => None,
+    })
+}
+
+/// `sql` with the tables `subquery` has SQL for, given their schema and name, replaced by that
+/// SQL as a derived table. `None` if it references none of them.
+pub(crate) fn replace_tables(
+    sql: &str,
+    subquery: &dyn Fn(&str, &str) -> Option<String>,
+) -> Option<String> {
+    let nodes = translator::parse(sql).ok()?;
+    let mut expanded = false;
+    let nodes = expand_nodes(nodes, subquery, &mut expanded);
+    expanded.then(|| translator::render(&nodes))
+}
+
+fn expand_nodes(
+    nodes: Vec<Node>,
+    subquery: &dyn Fn(&str, &str) -> Option<String>,
+    expanded: &mut bool,
+) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut iter = nodes.into_iter().peekable();
+
+    while let Some(node) = iter.next() {
+        let node = match node {
+            Node::Group(inner) => Node::Group(expand_nodes(inner, subquery, expanded)),
+            other => other,
+        };
+        out.push(node);
+
+        // Looking for `schema . name`, ending at the node just pushed.
+        let [.., Node::Token(schema), Node::Token(dot), Node::Token(name)] = out.as_slice() else {
+            continue;
+        };
+        if !dot.is_operator(".") {
+            continue;
+        }
+        let (Some(schema), Some(table)) = (
+            literals::identifier_name(schema),
+            literals::identifier_name(name),
+        ) else {
+            continue;
+        };
+        let Some(subquery) = subquery(&schema, &table) else {
+            continue;
+        };
+
+        out.truncate(out.len() - 3);
+        out.push(Node::Group(
+            translator::parse(&subquery).expect("generated SQL is balanced"),
+        ));
+        // A derived table needs an alias; keep the client's if it gave one.
+        let aliased = iter
+            .clone()
+            .find(|n| !n.is_trivia())
+            .is_some_and(|n| is_table_alias(&n));
+        if !aliased {
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::Word("AS".to_string())));
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::Word(table)));
+        }
+        *expanded = true;
+    }
+
+    out
+}
+
+// The SQL producing the rows of virtual table `table`.
+fn subquery(table: &str, stats: &Stats) -> Option<String> {
+    let (columns, rows) = match table {
+        "table_access" => (
+            TABLE_ACCESS,
+            stats
+                .table_access()
+                .into_iter()
+                .map(|(key, counts)| {
+                    vec![
+                        literals::pg_string(&key.user),
+                        literals::pg_string(&key.schema),
+                        literals::pg_string(&key.table),
+                        counts.reads.to_string(),
+                        counts.writes.to_string(),
+                        literals::pg_string(
+                            &counts.last_access.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        "metrics" => (
+            METRICS,
+            stats
+                .metrics()
+                .into_iter()
+                .map(|(name, value)| vec![literals::pg_string(name), value.to_string()])
+                .collect(),
+        ),
+        "digests" => (
+            DIGESTS,
+            stats
+                .digests()
+                .into_iter()
+                .map(|(fingerprint, counts)| {
+                    let average = counts.total.div_f64(counts.count as f64);
+                    vec![
+                        literals::pg_string(&fingerprint),
+                        literals::pg_string(&counts.text),
+                        counts.count.to_string(),
+                        counts.errors.to_string(),
+                        milliseconds(counts.total),
+                        milliseconds(average),
+                        milliseconds(counts.min),
+                        milliseconds(counts.max),
+                        literals::pg_string(
+                            &counts.first_seen.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                        literals::pg_string(
+                            &counts.last_seen.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                    ]
+                })
+                .collect(),
+        ),
+        "shadow_mismatches" => (
+            SHADOW_MISMATCHES,
+            stats
+                .shadow_mismatches()
+                .into_iter()
+                .map(|(fingerprint, mismatch, counts)| {
+                    vec![
+                        literals::pg_string(&fingerprint),
+                        literals::pg_string(&counts.text),
+                        literals::pg_string(&mismatch.to_string()),
+                        counts.count.to_string(),
+                        literals::pg_string(
+                            &counts.first_seen.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                        literals::pg_string(
+                            &counts.last_seen.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                        literals::pg_string(&counts.example),
+                        literals::pg_string(&counts.postgres),
+                        literals::pg_string(&counts.mysql),
+                    ]
+                })
+                .collect(),
+        ),
+        "translation_failures" => (
+            TRANSLATION_FAILURES,
+            stats
+                .failures()
+                .into_iter()
+                .map(|(failure, counts)| {
+                    vec![
+                        literals::pg_string(&failure.category.to_string()),
+                        literals::pg_string(&failure.construct),
+                        counts.failures.to_string(),
+                        literals::pg_string(
+                            &counts.last_failure.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        ),
+                        literals::pg_string(&counts.example),
+                    ]
+                })
+                .collect(),
+        ),
+        _ => return None,
+    };
+    Some(values_query(columns, &rows))
+}
+
+// `SELECT` over a VALUES list with typed, named columns; an empty result when there are no rows.
+pub(crate) fn values_query(columns: &[(&str, &str)], rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        let nulls: Vec<String> = columns
+            .iter()
+            .map(|(name, ty)| format!("NULL::{} AS {}", ty, name))
+            .collect();
+        return format!("SELECT {} WHERE false", nulls.join(", "));
+    }
+
+    let values: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let typed: Vec<String> = row
+                .iter()
+                .zip(columns)
+                .map(|(value, (_, ty))| format!("{}::{}", value, ty))
+                .collect();
+            format!("({})", typed.join(", "))
+        })
+        .collect();
+    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    format!(
+        "SELECT * FROM (VALUES {}) AS v ({})",
+        values.join(", "),
+        names.join(", ")
+    )
+}
@@ -0,0 +1,256 @@
+// Errors reported to the MySQL client as ERR packets.
+
+use std::fmt;
+use std::io;
+
+use opensrv_mysql::{ErrorKind, QueryResultWriter, RowWriter};
+use tokio::io::AsyncWrite;
+use tokio_postgres::error::{DbError, SqlState};
+
+use crate::{timeouts, transaction_modes};
+
+#[derive(Debug, Clone)]
+pub struct MysqlError {
+    pub kind: ErrorKind,
+    // The error number sent in place of `kind`'s, for errors newer than opensrv's list of them;
+    // `kind` still gives the SQLSTATE. Set with `with_code`.
+    code: Option<u16>,
+    pub message: String,
+}
+
+impl MysqlError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        MysqlError {
+            kind,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    /// An error opensrv has no ErrorKind for, numbered `code`, with the SQLSTATE of `kind`.
+    /// The number is swapped into the ERR packet on its way out (see `Status::set_error_code`).
+    pub fn with_code(kind: ErrorKind, code: u16, message: impl Into<String>) -> Self {
+        MysqlError {
+            code: Some(code),
+            ..MysqlError::new(kind, message)
+        }
+    }
+
+    /// The error number the client gets.
+    pub fn code(&self) -> u16 {
+        self.code.unwrap_or(self.kind as u16)
+    }
+
+    pub async fn write<W: AsyncWrite + Send + Unpin>(
+        &self,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        results.error(self.kind, self.message.as_bytes()).await
+    }
+
+    /// Ends a result set, some of whose rows were sent, with the error.
+    pub async fn finish<W: AsyncWrite + Send + Unpin>(
+        &self,
+        rows: RowWriter<'_, W>,
+    ) -> io::Result<()> {
+        rows.finish_error(self.kind, &self.message.as_bytes()).await
+    }
+}
+
+impl fmt::Display for MysqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ERROR {}: {}", self.code(), self.message)
+    }
+}
+
+impl From<tokio_postgres::Error> for MysqlError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        // A statement cancelled by KILL QUERY, or DB_STATEMENT_TIMEOUT, fails the way it does
+        // in MySQL.
+        if e.code() == Some(&SqlState::QUERY_CANCELED) {
+            if e.as_db_error()
+                .is_some_and(|db_error| db_error.message().contains("statement timeout"))
+            {
+                return timeouts::exceeded();
+            }
+            return MysqlError::new(
+                ErrorKind::ER_QUERY_INTERRUPTED,
+                "Query execution was interrupted",
+            );
+        }
+        if let Some(db_error) = e.as_db_error() {
+            match db_error.code() {
+                code if code == &SqlState::UNIQUE_VIOLATION => return duplicate_entry(db_error),
+                code if code == &SqlState::FOREIGN_KEY_VIOLATION => return foreign_key(db_error),
+                code if code == &SqlState::READ_ONLY_SQL_TRANSACTION => {
+                    return transaction_modes::read_only_transaction()
+                }
+                code if code == &SqlState::NOT_NULL_VIOLATION => {
+                    if let Some(column) = db_error.column() {
+                        return MysqlError::new(
+                            ErrorKind::ER_BAD_NULL_ERROR,
+                            format!("Column '{}' cannot be null", column),
+                        );
+                    }
+                }
+                code if code == &SqlState::CHECK_VIOLATION => {
+                    if let Some(constraint) = db_error.constraint() {
+                        return MysqlError::with_code(
+                            ErrorKind::ER_UNKNOWN_ERROR,
+                            ER_CHECK_CONSTRAINT_VIOLATED,
+                            format!("Check constraint '{}' is violated.", constraint),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        let message = match e.as_db_error() {
+            Some(db_error) => db_error.message().to_string(),
+            None => e.to_string(),
+        };
+        MysqlError::new(ErrorKind::ER_UNKNOWN_ERROR, message)
+    }
+}
+
+// MySQL 8's error for a failed CHECK constraint, which opensrv doesn't know. Its SQLSTATE is
+// HY000, as ER_UNKNOWN_ERROR's is.
+const ER_CHECK_CONSTRAINT_VIOLATED: u16 = 3819;
+
+// MySQL's longest quoted entry in a duplicate key error.
+const ENTRY_LENGTH: usize = 64;
+
+// A unique violation the way MySQL reports it, which frameworks match to tell duplicates from
+// other failures:
+//
+//   Key (a, b)=(1, x) already exists.  ->  Duplicate entry '1-x' for key 'table.key'
+//
+// The key is named the way MySQL named it: PRIMARY for the primary key, and without the table
+// prefix and `_key` suffix PostgreSQL and the translator add to constraint names.
+fn duplicate_entry(db_error: &DbError) -> MysqlError {
+    let (Some(table), Some(constraint)) = (db_error.table(), db_error.constraint()) else {
+        return MysqlError::new(ErrorKind::ER_DUP_ENTRY, db_error.message());
+    };
+    let (columns, values) = db_error
+        .detail()
+        .and_then(key_detail)
+        .unwrap_or((Vec::new(), String::new()));
+
+    let key = if constraint == format!("{}_pkey", table) {
+        "PRIMARY".to_string()
+    } else {
+        let name = constraint
+            .strip_prefix(table)
+            .and_then(|name| name.strip_prefix('_'))
+            .unwrap_or(constraint);
+        // An unnamed UNIQUE is named after its first column, as in MySQL.
+        match columns.first() {
+            Some(first) if name == format!("{}_key", columns.join("_")) => first.to_string(),
+            _ => name.to_string(),
+        }
+    };
+
+    // The parts of a multi-column key are joined with '-'.
+    let parts: Vec<&str> = values.split(", ").collect();
+    let entry = if columns.len() > 1 && parts.len() == columns.len() {
+        parts.join("-")
+    } else {
+        values
+    };
+    let entry: String = entry.chars().take(ENTRY_LENGTH).collect();
+    MysqlError::new(
+        ErrorKind::ER_DUP_ENTRY,
+        format!("Duplicate entry '{}' for key '{}.{}'", entry, table, key),
+    )
+}
+
+// The columns and values of `Key (columns)=(values) already exists.`
+fn key_detail(detail: &str) -> Option<(Vec<&str>, String)> {
+    let rest = detail.strip_prefix("Key (")?;
+    let (columns, rest) = rest.split_once(")=(")?;
+    let values = rest.strip_suffix(") already exists.")?;
+    let columns = columns
+        .split(", ")
+        .map(|column| column.trim_matches('"'))
+        .collect();
+    Some((columns, values.to_string()))
+}
+
+// A foreign key violation the way MySQL reports it, telling a child row without a parent (1452)
+// from a parent row that still has children (1451):
+//
+//   Cannot add or update a child row: a foreign key constraint fails
+//   (`schema`.`child`, CONSTRAINT `fk` FOREIGN KEY (`parent_id`) REFERENCES `parent`)
+//
+// PostgreSQL's error names the child table and the constraint, and the key of only one side:
+// the child's columns when a row is added, the parent's when one is deleted. The other side's
+// columns are left out rather than looked up.
+fn foreign_key(db_error: &DbError) -> MysqlError {
+    let message = db_error.message();
+    let detail = db_error.detail().unwrap_or("");
+    let columns = |detail: &str| {
+        let columns = detail.strip_prefix("Key (")?.split_once(")=(")?.0;
+        Some(
+            columns
+                .split(", ")
+                .map(|column| format!("`{}`", column.trim_matches('"')))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    };
+    // `update or delete on table "parent" violates ...`, with `Key (id)=(1) is still
+    // referenced from table "child".`
+    let parent_row = message.starts_with("update or delete on table ");
+    let (kind, what) = if parent_row {
+        (
+            ErrorKind::ER_ROW_IS_REFERENCED_2,
+            "Cannot delete or update a parent row",
+        )
+    } else {
+        (
+            ErrorKind::ER_NO_REFERENCED_ROW_2,
+            "Cannot add or update a child row",
+        )
+    };
+    let (Some(table), Some(constraint)) = (db_error.table(), db_error.constraint()) else {
+        return MysqlError::new(kind, format!("{}: a foreign key constraint fails", what));
+    };
+
+    let mut constraint = format!("CONSTRAINT `{}`", constraint);
+    if parent_row {
+        if let Some(parent) = quoted_after(message, "update or delete on table ") {
+            constraint.push_str(&format!(" REFERENCES `{}`", parent));
+            if let Some(columns) = columns(detail) {
+                constraint.push_str(&format!(" ({})", columns));
+            }
+        }
+    } else {
+        // `Key (parent_id)=(5) is not present in table "parent".`
+        if let Some(columns) = columns(detail) {
+            constraint.push_str(&format!(" FOREIGN KEY ({})", columns));
+        }
+        if let Some(parent) = detail
+            .split_once(" is not present in table ")
+            .and_then(|(_, rest)| quoted_after(rest, ""))
+        {
+            constraint.push_str(&format!(" REFERENCES `{}`", parent));
+        }
+    }
+    let table = match db_error.schema() {
+        Some(schema) => format!("`{}`.`{}`", schema, table),
+        None => format!("`{}`", table),
+    };
+    MysqlError::new(
+        kind,
+        format!(
+            "{}: a foreign key constraint fails ({}, {})",
+            what, table, constraint
+        ),
+    )
+}
+
+// The double-quoted name right after `prefix` in `text`.
+fn quoted_after<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(prefix)?.strip_prefix('"')?;
+    rest.split_once('"').map(|(name, _)| name)
+}
@@ -0,0 +1,335 @@
+// `postmyrustache export`: writes the tables of a PostgreSQL schema out as a MySQL dump.
+//
+//   postmyrustache export --db name [--no-data] [table ...] > dump.sql
+//
+// The output has the shape of mysqldump's: per table, DROP TABLE IF EXISTS, the CREATE TABLE
+// SHOW CREATE TABLE gives, and extended INSERTs of its rows, with foreign key checks off so the
+// tables load in any order. It goes back into MySQL, or to tools that only read MySQL dumps.
+//
+// The rows are read with COPY and written as MySQL literals: bytea as hexadecimal, booleans as
+// 1 and 0, bit strings as b'' literals, arrays as JSON, and timestamps with time zone in UTC,
+// which the dump sets as its time zone. Views, routines and triggers aren't exported.
+
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+
+use futures_util::{pin_mut, StreamExt};
+use tokio_postgres::Client;
+
+use crate::catalog::{self, ColumnInfo, ObjectName};
+use crate::emulation::backtick;
+use crate::emulation::show_create::create_table_sql;
+
+const USAGE: &str = "usage: postmyrustache export --db <name> [--no-data] [table ...]";
+
+// The length at which an extended INSERT is ended and a new one started, mysqldump's default
+// net_buffer_length.
+const INSERT_LENGTH: usize = 1_000_000;
+
+/// Runs the subcommand with the arguments after `export`, writing the dump to standard output.
+pub async fn run(args: &[String], client: &Client) -> Result<(), Box<dyn Error>> {
+    let mut database = None;
+    let mut data = true;
+    let mut tables = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => database = args.next(),
+            "--no-data" => data = false,
+            _ if !arg.starts_with("--") => tables.push(arg.clone()),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let database = database.ok_or(USAGE)?;
+
+    let exists = client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)",
+            &[database],
+        )
+        .await?;
+    if !exists.get::<_, bool>(0) {
+        return Err(format!("Unknown database '{}'", database).into());
+    }
+    if tables.is_empty() {
+        tables = client
+            .query(
+                "SELECT c.relname::text FROM pg_class c \
+                 JOIN pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE n.nspname = $1 AND c.relkind IN ('r', 'p') AND NOT c.relispartition \
+                 ORDER BY c.relname",
+                &[database],
+            )
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+    }
+    // Timestamps with time zone are written in the dump's time zone.
+    client.batch_execute("SET TIME ZONE 'UTC'").await?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    writeln!(out, "-- PostMyRustache dump")?;
+    writeln!(out, "--")?;
+    writeln!(out, "-- Database: {}", database)?;
+    writeln!(
+        out,
+        "-- ------------------------------------------------------"
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;"
+    )?;
+    writeln!(out, "/*!40101 SET NAMES utf8mb4 */;")?;
+    writeln!(out, "/*!40103 SET @OLD_TIME_ZONE=@@TIME_ZONE */;")?;
+    writeln!(out, "/*!40103 SET TIME_ZONE='+00:00' */;")?;
+    writeln!(
+        out,
+        "/*!40014 SET @OLD_UNIQUE_CHECKS=@@UNIQUE_CHECKS, UNIQUE_CHECKS=0 */;"
+    )?;
+    writeln!(
+        out,
+        "/*!40014 SET @OLD_FOREIGN_KEY_CHECKS=@@FOREIGN_KEY_CHECKS, FOREIGN_KEY_CHECKS=0 */;"
+    )?;
+    writeln!(
+        out,
+        "/*!40101 SET @OLD_SQL_MODE=@@SQL_MODE, SQL_MODE='NO_AUTO_VALUE_ON_ZERO' */;"
+    )?;
+
+    let mut total = 0;
+    for table in &tables {
+        let name = ObjectName {
+            schema: Some(database.clone()),
+            name: table.clone(),
+        };
+        let columns = catalog::table_columns(client, &name).await?;
+        if columns.is_empty() {
+            return Err(format!("Table '{}.{}' doesn't exist", database, table).into());
+        }
+        let indexes = catalog::table_indexes(client, &name).await?;
+        let foreign_keys = catalog::table_foreign_keys(client, &name).await?;
+
+        writeln!(out)?;
+        writeln!(out, "--")?;
+        writeln!(out, "-- Table structure for table {}", backtick(table))?;
+        writeln!(out, "--")?;
+        writeln!(out)?;
+        writeln!(out, "DROP TABLE IF EXISTS {};", backtick(table))?;
+        writeln!(
+            out,
+            "{};",
+            create_table_sql(table, &columns, &indexes, &foreign_keys)
+        )?;
+        if !data {
+            continue;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "--")?;
+        writeln!(out, "-- Dumping data for table {}", backtick(table))?;
+        writeln!(out, "--")?;
+        writeln!(out)?;
+        writeln!(out, "LOCK TABLES {} WRITE;", backtick(table))?;
+        writeln!(
+            out,
+            "/*!40000 ALTER TABLE {} DISABLE KEYS */;",
+            backtick(table)
+        )?;
+        let rows = table_rows(client, &name, &columns, &mut out).await?;
+        writeln!(
+            out,
+            "/*!40000 ALTER TABLE {} ENABLE KEYS */;",
+            backtick(table)
+        )?;
+        writeln!(out, "UNLOCK TABLES;")?;
+        eprintln!("{}: {} rows", backtick(table), rows);
+        total += rows;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "/*!40101 SET SQL_MODE=@OLD_SQL_MODE */;")?;
+    writeln!(
+        out,
+        "/*!40014 SET FOREIGN_KEY_CHECKS=@OLD_FOREIGN_KEY_CHECKS */;"
+    )?;
+    writeln!(out, "/*!40014 SET UNIQUE_CHECKS=@OLD_UNIQUE_CHECKS */;")?;
+    writeln!(out, "/*!40103 SET TIME_ZONE=@OLD_TIME_ZONE */;")?;
+    writeln!(
+        out,
+        "/*!40101 SET CHARACTER_SET_CLIENT=@OLD_CHARACTER_SET_CLIENT */;"
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "-- Dump completed on {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    )?;
+    out.flush()?;
+    eprintln!("Exported {} tables and {} rows", tables.len(), total);
+    Ok(())
+}
+
+// How a column's values are written.
+#[derive(Clone, Copy)]
+enum Literal {
+    Number,
+    String,
+    Hex,
+    Bit,
+}
+
+// The expression a column is read with, and how its values are written.
+fn column_expression(column: &ColumnInfo) -> (String, Literal) {
+    let name = format!("\"{}\"", column.name.replace('"', "\"\""));
+    // Without its modifier: `timestamp(3) with time zone` is `timestamp with time zone`.
+    let base = match (column.pg_type.find('('), column.pg_type.rfind(')')) {
+        (Some(open), Some(close)) => format!(
+            "{}{}",
+            &column.pg_type[..open],
+            &column.pg_type[close + 1..]
+        ),
+        _ => column.pg_type.clone(),
+    };
+    if base.ends_with("[]") {
+        return (format!("to_json({})", name), Literal::String);
+    }
+    match base.as_str() {
+        "smallint" | "integer" | "bigint" | "real" | "double precision" | "numeric" => {
+            (name, Literal::Number)
+        }
+        "boolean" => (format!("{}::int", name), Literal::Number),
+        "bytea" => (format!("encode({}, 'hex')", name), Literal::Hex),
+        "bit" | "bit varying" => (name, Literal::Bit),
+        "timestamp with time zone" => (format!("{}::timestamp", name), Literal::String),
+        "time with time zone" => (format!("{}::time", name), Literal::String),
+        _ => (name, Literal::String),
+    }
+}
+
+// Writes the rows of a table as extended INSERTs. Returns the number of rows.
+async fn table_rows(
+    client: &Client,
+    table: &ObjectName,
+    columns: &[ColumnInfo],
+    out: &mut impl Write,
+) -> Result<u64, Box<dyn Error>> {
+    let (expressions, literals): (Vec<String>, Vec<Literal>) =
+        columns.iter().map(column_expression).unzip();
+    let schema = table.schema.as_deref().unwrap_or_default();
+    let copy = format!(
+        "COPY (SELECT {} FROM \"{}\".\"{}\") TO STDOUT",
+        expressions.join(", "),
+        schema.replace('"', "\"\""),
+        table.name.replace('"', "\"\"")
+    );
+    let stream = client.copy_out(copy.as_str()).await?;
+    pin_mut!(stream);
+
+    let insert = format!("INSERT INTO {} VALUES ", backtick(&table.name));
+    let mut statement = String::new();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut rows = 0;
+    while let Some(chunk) = stream.next().await {
+        pending.extend_from_slice(&chunk?);
+        // COPY escapes the newlines in values, so every newline ends a row.
+        let Some(end) = pending.iter().rposition(|&b| b == b'\n') else {
+            continue;
+        };
+        let complete: Vec<u8> = pending.drain(..=end).collect();
+        for line in std::str::from_utf8(&complete)?.lines() {
+            let mut row = String::from("(");
+            for (i, (field, literal)) in line.split('\t').zip(&literals).enumerate() {
+                if i > 0 {
+                    row.push(',');
+                }
+                write_value(field, *literal, &mut row);
+            }
+            row.push(')');
+
+            if statement.is_empty() {
+                statement.push_str(&insert);
+            } else {
+                statement.push(',');
+            }
+            statement.push_str(&row);
+            rows += 1;
+            if statement.len() >= INSERT_LENGTH {
+                writeln!(out, "{};", statement)?;
+                statement.clear();
+            }
+        }
+    }
+    if !statement.is_empty() {
+        writeln!(out, "{};", statement)?;
+    }
+    Ok(rows)
+}
+
+// Writes a field of COPY's text format as a MySQL literal.
+fn write_value(field: &str, literal: Literal, out: &mut String) {
+    if field == "\\N" {
+        out.push_str("NULL");
+        return;
+    }
+    let value = copy_field_value(field);
+    match literal {
+        Literal::Number
+            if value
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) =>
+        {
+            out.push_str(&value)
+        }
+        Literal::Hex if value.is_empty() => out.push_str("''"),
+        Literal::Hex => {
+            out.push_str("0x");
+            out.push_str(&value);
+        }
+        Literal::Bit => {
+            out.push_str("b'");
+            out.push_str(&value);
+            out.push('\'');
+        }
+        // NaN and Infinity, which MySQL has no numbers for, are left to fail as strings.
+        Literal::Number | Literal::String => {
+            out.push('\'');
+            for c in value.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '\'' => out.push_str("\\'"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\0' => out.push_str("\\0"),
+                    '\x1a' => out.push_str("\\Z"),
+                    c => out.push(c),
+                }
+            }
+            out.push('\'');
+        }
+    }
+}
+
+// Decodes the backslash escapes of a COPY text field.
+fn copy_field_value(field: &str) -> String {
+    let mut value = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => value.push('\x08'),
+            Some('f') => value.push('\x0c'),
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some('v') => value.push('\x0b'),
+            Some(c) => value.push(c),
+            None => value.push('\\'),
+        }
+    }
+    value
+}
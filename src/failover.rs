@@ -0,0 +1,116 @@
+// Failing over to a standby when the primary PostgreSQL can't be reached.
+//
+// DB_STANDBY_HOSTS lists, comma-separated, the hosts to try when DB_HOST can't be: the standbys
+// one of which is promoted when the primary fails. Sessions are opened with the active host,
+// DB_HOST to begin with; should it not accept one, the others are tried in order, and the first
+// that does becomes the active host for the sessions opened after it. With standbys, a host
+// only accepts sessions once it takes writes (target_session_attrs=read-write), so a standby
+// that hasn't been promoted yet is passed over, and tried again with the next session.
+//
+// The proxy keeps running meanwhile: the sessions lost with the primary are replaced on the new
+// one as reconnect.rs describes, backing off while no host accepts them. Host names are looked
+// up for each session, so a DB_HOST that names the primary in DNS follows it when the record is
+// changed, without any standbys listed.
+//
+// Until a host accepts a session, a statement that needs one fails with error 1053 (SQLSTATE
+// 08S01), as statements do while MySQL shuts down, which connectors take for a lost connection
+// worth trying again; and a client connecting is refused with it in place of the greeting.
+//
+// The sessions open with each host are counted, for SHOW PROXY CACHES.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::logging::Logger;
+
+/// DB_HOST and DB_STANDBY_HOSTS, and which of them sessions are opened with.
+pub struct Hosts {
+    hosts: Vec<String>,
+    active: AtomicUsize,
+    // The sessions open with each host.
+    open: Vec<AtomicUsize>,
+}
+
+/// A session open with a host, counted for as long as it is kept.
+pub struct HostSession {
+    hosts: Arc<Hosts>,
+    index: usize,
+}
+
+impl Hosts {
+    pub fn new(primary: &str, standbys: &[String]) -> Hosts {
+        let hosts: Vec<String> = std::iter::once(primary.to_string())
+            .chain(standbys.iter().cloned())
+            .collect();
+        Hosts {
+            open: hosts.iter().map(|_| AtomicUsize::new(0)).collect(),
+            hosts,
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts a session opened with host `index` until the returned guard is dropped.
+    pub fn session(self: &Arc<Self>, index: usize) -> HostSession {
+        self.open[index].fetch_add(1, Ordering::Relaxed);
+        HostSession {
+            hosts: Arc::clone(self),
+            index,
+        }
+    }
+
+    /// Each host, with the sessions open with it.
+    pub fn sessions(&self) -> Vec<(String, usize)> {
+        self.hosts
+            .iter()
+            .zip(&self.open)
+            .map(|(host, open)| (host.clone(), open.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Whether there are standbys to fail over to.
+    pub fn standbys(&self) -> bool {
+        self.hosts.len() > 1
+    }
+
+    /// The hosts to try, by index: the active one, and then the others in order after it.
+    pub fn in_order(&self) -> impl Iterator<Item = (usize, &str)> {
+        let active = self.active.load(Ordering::Relaxed);
+        (0..self.hosts.len())
+            .map(move |offset| (active + offset) % self.hosts.len())
+            .map(|index| (index, self.hosts[index].as_str()))
+    }
+
+    /// Notes that host `index` accepted a session, failing over to it if it wasn't the active
+    /// host.
+    pub fn connected(&self, index: usize, log: Logger) {
+        let previous = self.active.swap(index, Ordering::Relaxed);
+        if previous != index {
+            log.info(format_args!(
+                "PostgreSQL at {} can't be reached, failing over to {}",
+                self.hosts[previous], self.hosts[index]
+            ));
+        }
+    }
+}
+
+impl Drop for HostSession {
+    fn drop(&mut self) {
+        self.hosts.open[self.index].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// What a statement fails with, or a client connecting is refused with, while no host accepts
+/// a session: `error` is why the last one didn't.
+pub fn unavailable(error: &dyn fmt::Display) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_SERVER_SHUTDOWN,
+        format!(
+            "Lost the connection to PostgreSQL and can't reconnect yet, try again: {}",
+            error
+        ),
+    )
+}
@@ -0,0 +1,146 @@
+// Statements that failed because the proxy doesn't handle something they use, by what that is.
+//
+// PostgreSQL's error tells which construct it didn't accept: a function or type name, the word
+// a syntax error is at, a configuration parameter. Those are counted per construct so a user can
+// see which ones block a workload, e.g. that GROUP_CONCAT accounts for most failures:
+//
+//   SHOW PROXY TRANSLATION STATS
+//   SELECT * FROM proxy_stats.translation_failures
+//
+// Errors about the data or the schema (duplicate keys, missing tables) are not translation
+// failures and aren't counted.
+
+use std::fmt;
+
+use crate::translator::{self, TranslateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    // The translator couldn't parse the statement.
+    Parse,
+    Function,
+    Operator,
+    Type,
+    // A SET of a variable PostgreSQL doesn't have.
+    Setting,
+    // Some other object PostgreSQL doesn't know.
+    Object,
+    Syntax,
+    // A syntax error in CREATE, ALTER and the like; usually a table or column option.
+    DdlClause,
+    // PostgreSQL understood the statement but doesn't support what it asks for.
+    Feature,
+    // Something the proxy emulates, used in a way it can't handle.
+    Emulation,
+    // A construct the translator recognised but can't convert, like a condition handler in a
+    // stored procedure.
+    Unsupported,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Parse => "parse",
+            Category::Function => "function",
+            Category::Operator => "operator",
+            Category::Type => "type",
+            Category::Setting => "setting",
+            Category::Object => "object",
+            Category::Syntax => "syntax",
+            Category::DdlClause => "ddl clause",
+            Category::Feature => "feature",
+            Category::Emulation => "emulation",
+            Category::Unsupported => "unsupported",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Failure {
+    pub category: Category,
+    pub construct: String,
+}
+
+impl Failure {
+    pub fn new(category: Category, construct: impl Into<String>) -> Failure {
+        Failure {
+            category,
+            construct: construct.into(),
+        }
+    }
+}
+
+// Statements whose syntax errors count as DDL clauses.
+const DDL: &[&str] = &["CREATE", "ALTER", "DROP", "RENAME", "TRUNCATE"];
+
+/// A statement the translator couldn't parse or convert.
+pub fn translate_error(e: &TranslateError) -> Failure {
+    match e {
+        TranslateError::Lex(e) => Failure::new(Category::Parse, e.message.clone()),
+        TranslateError::UnbalancedParens { .. } => {
+            Failure::new(Category::Parse, "unbalanced parentheses")
+        }
+        TranslateError::Unsupported(what) => Failure::new(Category::Unsupported, what.clone()),
+    }
+}
+
+/// What PostgreSQL rejected in the translation of `sql`, the statement as the client sent it, if
+/// the error is one about the statement rather than the data.
+pub fn upstream_error(sql: &str, e: &tokio_postgres::Error) -> Option<Failure> {
+    let db_error = e.as_db_error()?;
+    let message = db_error.message();
+    let failure = match db_error.code().code() {
+        // undefined_function: `function foo(integer) does not exist` or
+        // `operator does not exist: text + integer`
+        "42883" => match message.strip_prefix("operator does not exist: ") {
+            Some(operator) => Failure::new(Category::Operator, operator),
+            None => {
+                let name = message.strip_prefix("function ")?;
+                let name = &name[..name.find('(')?];
+                Failure::new(Category::Function, name.to_ascii_uppercase())
+            }
+        },
+        // undefined_object: `type "x" does not exist`, `unrecognized configuration parameter "x"`
+        "42704" => {
+            let category = if message.starts_with("type ") {
+                Category::Type
+            } else if message.starts_with("unrecognized configuration parameter") {
+                Category::Setting
+            } else {
+                Category::Object
+            };
+            Failure::new(category, quoted(message).unwrap_or(message))
+        }
+        // syntax_error: `syntax error at or near "x"`, `syntax error at end of input`
+        "42601" => {
+            let construct = match quoted(message) {
+                Some(near) => near.to_ascii_uppercase(),
+                None => message
+                    .strip_prefix("syntax error at ")
+                    .unwrap_or(message)
+                    .to_string(),
+            };
+            let tokens = translator::significant_tokens(sql).unwrap_or_default();
+            let ddl = tokens
+                .first()
+                .is_some_and(|first| DDL.iter().any(|w| first.is_word(w)));
+            let category = if ddl {
+                Category::DdlClause
+            } else {
+                Category::Syntax
+            };
+            Failure::new(category, construct)
+        }
+        // feature_not_supported
+        "0A000" => Failure::new(Category::Feature, message),
+        _ => return None,
+    };
+    Some(failure)
+}
+
+// The first double-quoted part of a message.
+fn quoted(message: &str) -> Option<&str> {
+    let start = message.find('"')? + 1;
+    let end = start + message[start..].find('"')?;
+    Some(&message[start..end])
+}
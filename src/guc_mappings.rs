@@ -0,0 +1,163 @@
+// MySQL session variables set as PostgreSQL settings (GUCs), so that clients that can only send
+// MySQL can still tune their PostgreSQL session:
+//
+//   GUC_MAPPINGS  "max_execution_time:statement_timeout, max_statement_time:statement_timeout:s,
+//                  tenant:app.tenant"
+//
+// Each mapping is a MySQL variable, the GUC it sets, and optionally a unit PostgreSQL reads a
+// number in: `s` above, as MariaDB's max_statement_time is in seconds and statement_timeout
+// otherwise takes milliseconds. A GUC with a dot in its name is a custom one, which PostgreSQL
+// takes without knowing it, for functions and policies to read with current_setting().
+//
+//   SET max_statement_time = 5        ->  SET statement_timeout TO '5s'
+//   SET @@session.tenant = 'acme'     ->  SET app.tenant TO 'acme'
+//   SET SESSION tenant = DEFAULT      ->  RESET app.tenant
+//
+// A SET of the connection's session, whether SESSION, LOCAL, @@session. or neither, is taken
+// when every variable it sets is mapped; any other SET is translated and forwarded as before. The
+// settings are kept, and made again on a new session should the connection's be lost; they go
+// with COM_RESET_CONNECTION. Like any SET on PostgreSQL, one made in a transaction that is then
+// rolled back is undone.
+
+use crate::translator::{self, literals, Token};
+
+/// A MySQL variable and the GUC it sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GucMapping {
+    // Lower case.
+    pub variable: String,
+    pub guc: String,
+    // Appended to a number, `s` or `MB` say.
+    pub unit: Option<String>,
+}
+
+impl GucMapping {
+    /// The mapping GUC_MAPPINGS has as `variable:guc` or `variable:guc:unit`; `None` if it isn't
+    /// one, or names aren't plain.
+    pub fn parse(entry: &str) -> Option<GucMapping> {
+        let mut parts = entry.split(':').map(str::trim);
+        let variable = parts.next()?.to_ascii_lowercase();
+        let guc = parts.next()?.to_string();
+        let unit = parts.next().map(str::to_string);
+        let plain = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        };
+        let unit_plain = unit
+            .as_deref()
+            .is_none_or(|unit| !unit.is_empty() && unit.chars().all(|c| c.is_ascii_alphabetic()));
+        (parts.next().is_none() && plain(&variable) && plain(&guc) && unit_plain).then_some(
+            GucMapping {
+                variable,
+                guc,
+                unit,
+            },
+        )
+    }
+}
+
+/// What a mapped variable is set to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Setting {
+    Value(String),
+    // SET ... = DEFAULT, the GUC's own default.
+    Default,
+}
+
+/// The GUCs the SET `sql`, a statement as the client sent it, sets, if it only sets mapped
+/// variables of the session.
+pub fn parse<'m>(sql: &str, mappings: &'m [GucMapping]) -> Option<Vec<(&'m GucMapping, Setting)>> {
+    if mappings.is_empty() {
+        return None;
+    }
+    let tokens = translator::significant_tokens(sql)?;
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    let (set, rest) = tokens.split_first()?;
+    if !set.is_word("SET") {
+        return None;
+    }
+    rest.split(|token| *token == Token::Comma)
+        .map(|assignment| assignment_of(assignment, mappings))
+        .collect()
+}
+
+// A mapped variable and what an assignment, `[SESSION] name = value`, sets it to.
+fn assignment_of<'m>(
+    tokens: &[Token],
+    mappings: &'m [GucMapping],
+) -> Option<(&'m GucMapping, Setting)> {
+    let tokens = match tokens {
+        [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => rest,
+        tokens => tokens,
+    };
+    let (name, rest) = tokens.split_first()?;
+    let name = match name {
+        Token::Word(word) => word.to_ascii_lowercase(),
+        Token::Variable(variable) => {
+            let variable = variable.to_ascii_lowercase();
+            ["@@session.", "@@local.", "@@"]
+                .iter()
+                .find_map(|prefix| variable.strip_prefix(prefix))?
+                .to_string()
+        }
+        _ => return None,
+    };
+    let mapping = mappings.iter().find(|m| m.variable == name)?;
+    let value = match rest {
+        [equals, value @ ..] if equals.is_operator("=") || equals.is_operator(":=") => value,
+        _ => return None,
+    };
+    let setting = match value {
+        [word] if word.is_word("DEFAULT") => Setting::Default,
+        [Token::String(raw)] => Setting::Value(literals::mysql_string_value(raw)),
+        [Token::Word(word)] => Setting::Value(word.clone()),
+        [Token::Number(number)] => {
+            Setting::Value(number.clone() + mapping.unit.as_deref().unwrap_or(""))
+        }
+        [minus, Token::Number(number)] if minus.is_operator("-") => Setting::Value(format!(
+            "-{}{}",
+            number,
+            mapping.unit.as_deref().unwrap_or("")
+        )),
+        _ => return None,
+    };
+    Some((mapping, setting))
+}
+
+/// The statement making `setting` of `mapping`'s GUC on PostgreSQL.
+pub fn sql(mapping: &GucMapping, setting: &Setting) -> String {
+    match setting {
+        Setting::Value(value) => format!("SET {} TO {}", mapping.guc, literals::pg_string(value)),
+        Setting::Default => format!("RESET {}", mapping.guc),
+    }
+}
+
+/// The settings a connection made through mappings, to make again on a new session.
+#[derive(Debug, Clone, Default)]
+pub struct MappedSettings {
+    // By GUC, in the order first set.
+    settings: Vec<(GucMapping, Setting)>,
+}
+
+impl MappedSettings {
+    pub fn set(&mut self, mapping: &GucMapping, setting: Setting) {
+        self.settings.retain(|(m, _)| m.guc != mapping.guc);
+        self.settings.push((mapping.clone(), setting));
+    }
+
+    /// The statements making the settings on a new session, if any were made.
+    pub fn session_sql(&self) -> Option<String> {
+        (!self.settings.is_empty()).then(|| {
+            self.settings
+                .iter()
+                .map(|(mapping, setting)| sql(mapping, setting))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+    }
+}
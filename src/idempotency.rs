@@ -0,0 +1,123 @@
+// Idempotency keys for writes (IDEMPOTENT_WRITES), so that one whose session is lost as it runs,
+// to a failover say, can be run again on the new session without being applied twice.
+//
+// reconnect.rs runs a statement again only when that is safe, which a write isn't: whether it
+// committed before the session went can't be told from the error. With IDEMPOTENT_WRITES set, a
+// single INSERT, UPDATE or DELETE run outside a transaction runs in a transaction of its own,
+// which also records a key for it, with the rows it changed, in
+// `proxy_metadata.idempotency_keys` (created as a write first needs it):
+//
+//   BEGIN
+//   UPDATE accounts SET balance = balance - 10 WHERE id = 7
+//   INSERT INTO proxy_metadata.idempotency_keys (key, row_count) VALUES ('<key>', 1); COMMIT
+//
+// Should the session be lost, the key is looked up on the new one. The write and its key commit
+// together or not at all, on a standby promoted since too: a key found means the write took
+// effect, and the client is answered with the rows it changed; none means it didn't, and it is
+// run again, once, the same way. A write that fails otherwise is rolled back and fails as it
+// would have.
+//
+// A write costs two more round trips. Its key is deleted with the connection's next keyed
+// write, and the keys left by connections that ended after a day.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio_postgres::Client;
+
+use crate::translator::{self, literals, Token};
+
+const TABLE: &str = "proxy_metadata.idempotency_keys";
+
+const CREATE_TABLE: &str = "CREATE SCHEMA IF NOT EXISTS proxy_metadata; \
+     CREATE TABLE IF NOT EXISTS proxy_metadata.idempotency_keys ( \
+       key text PRIMARY KEY, \
+       row_count bigint NOT NULL, \
+       created timestamptz NOT NULL DEFAULT now())";
+
+/// The keys of the writes run by the proxy's connections.
+pub struct IdempotencyKeys {
+    // Tells this run of the proxy's keys from those of others, and of runs before it.
+    prefix: String,
+    next: AtomicU64,
+    created: AtomicBool,
+}
+
+impl IdempotencyKeys {
+    pub fn new() -> IdempotencyKeys {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        IdempotencyKeys {
+            prefix: format!("{:x}-{:x}", started, std::process::id()),
+            next: AtomicU64::new(1),
+            created: AtomicBool::new(false),
+        }
+    }
+
+    /// A key no other write has.
+    pub fn next_key(&self) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// Creates the table of keys, with `client`, if it hasn't been yet.
+    pub async fn create_table(&self, client: &Client) -> Result<(), tokio_postgres::Error> {
+        if !self.created.load(Ordering::Relaxed) {
+            client.batch_execute(CREATE_TABLE).await?;
+            self.created.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Whether `sql`, a translated statement run outside a transaction, is a write to key.
+pub fn keyed(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    let write = tokens.first().is_some_and(|first| {
+        ["INSERT", "UPDATE", "DELETE"]
+            .iter()
+            .any(|word| first.is_word(word))
+    });
+    // Not a script, whose other statements could be anything.
+    write && !tokens.iter().any(|token| matches!(token, Token::Semicolon))
+}
+
+/// What starts a keyed write's transaction, deleting `previous`, the key of the connection's
+/// last one, and the keys that have expired.
+pub fn begin(previous: Option<&str>) -> String {
+    let previous = previous
+        .map(|key| format!("key = {} OR ", literals::pg_string(key)))
+        .unwrap_or_default();
+    format!(
+        "BEGIN; DELETE FROM {} WHERE {}created < now() - interval '1 day'",
+        TABLE, previous
+    )
+}
+
+/// What records `key` for a write that changed `row_count` rows, and commits it.
+pub fn commit(key: &str, row_count: u64) -> String {
+    format!(
+        "INSERT INTO {} (key, row_count) VALUES ({}, {}); COMMIT",
+        TABLE,
+        literals::pg_string(key),
+        row_count
+    )
+}
+
+/// The rows the write of `key` changed, if it committed.
+pub async fn recorded(client: &Client, key: &str) -> Result<Option<u64>, tokio_postgres::Error> {
+    let row = client
+        .query_opt(
+            &format!("SELECT row_count FROM {} WHERE key = $1", TABLE),
+            &[&key],
+        )
+        .await?;
+    Ok(row.map(|row| row.get::<_, i64>(0) as u64))
+}
@@ -0,0 +1,204 @@
+// Implicit defaults for the NOT NULL columns an INSERT leaves out (IMPLICIT_DEFAULTS).
+//
+// Outside strict mode, MySQL fills a NOT NULL column that has no DEFAULT and isn't named by an
+// INSERT with its type's implicit default, 0 or the empty string, and warns that the field
+// doesn't have a default value. PostgreSQL rejects the row with "null value violates not-null
+// constraint" instead, which is among the first errors a freshly migrated application runs
+// into. With IMPLICIT_DEFAULTS on, such columns are added to the translated INSERT:
+//
+//   INSERT INTO users (email) VALUES ('a@b.c')  ->  INSERT INTO users (email, name) VALUES ('a@b.c', '')
+//
+// when `name` is a NOT NULL varchar without a default. Only INSERTs with a column list and
+// VALUES are rewritten, and each one costs a catalog lookup of the table's columns. Columns of
+// types with no implicit value PostgreSQL accepts, such as dates, whose MySQL implicit default
+// is the zero date, are left for PostgreSQL to reject.
+
+use tokio_postgres::{Client, Error};
+
+use crate::catalog::{self, ObjectName};
+use crate::translator::{self, literals, split_args, statement_starts_with, Node, Token};
+
+/// The translated INSERT `translated` with the NOT NULL columns it leaves out added, and the
+/// names of those columns. `None` when there are none.
+pub async fn rewrite(
+    client: &Client,
+    translated: &str,
+) -> Result<Option<(String, Vec<String>)>, Error> {
+    let Ok(mut nodes) = translator::parse(translated) else {
+        return Ok(None);
+    };
+    let Some(insert) = Insert::find(&nodes) else {
+        return Ok(None);
+    };
+    let named: Vec<String> = match split_args(group(&nodes[insert.columns]))
+        .into_iter()
+        .map(column_name)
+        .collect()
+    {
+        Some(named) => named,
+        None => return Ok(None),
+    };
+    if insert
+        .rows
+        .iter()
+        .any(|&row| split_args(group(&nodes[row])).len() != named.len())
+    {
+        return Ok(None);
+    }
+
+    let missing: Vec<(String, Token)> = catalog::table_columns(client, &insert.table)
+        .await?
+        .into_iter()
+        .filter(|column| {
+            !column.nullable
+                && column.default.is_none()
+                && !column.auto_increment
+                && !named.contains(&column.name)
+        })
+        .filter_map(|column| Some((column.name, implicit_value(&column.pg_type)?)))
+        .collect();
+    if missing.is_empty() {
+        return Ok(None);
+    }
+
+    for (name, value) in &missing {
+        let quoted = Token::DoubleQuoted(literals::pg_identifier(name));
+        append(&mut nodes[insert.columns], quoted);
+        for &row in &insert.rows {
+            append(&mut nodes[row], value.clone());
+        }
+    }
+    let columns = missing.into_iter().map(|(name, _)| name).collect();
+    Ok(Some((translator::render(&nodes), columns)))
+}
+
+// Where the parts of `INSERT INTO table (columns) VALUES (row), ...` are among the nodes.
+struct Insert {
+    table: ObjectName,
+    columns: usize,
+    rows: Vec<usize>,
+}
+
+impl Insert {
+    fn find(nodes: &[Node]) -> Option<Insert> {
+        if !statement_starts_with(nodes, &["INSERT", "INTO"]) {
+            return None;
+        }
+        // A single statement only.
+        let end = nodes
+            .iter()
+            .position(|n| matches!(n, Node::Token(Token::Semicolon)))
+            .unwrap_or(nodes.len());
+        if nodes[end..]
+            .iter()
+            .any(|n| !n.is_trivia() && !matches!(n, Node::Token(Token::Semicolon)))
+        {
+            return None;
+        }
+        let mut significant = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.is_trivia())
+            .skip(2)
+            .peekable();
+
+        let (_, first) = significant.next()?;
+        let mut name = ObjectName {
+            schema: None,
+            name: identifier(first)?,
+        };
+        if significant
+            .next_if(|(_, n)| matches!(n, Node::Token(t) if t.is_operator(".")))
+            .is_some()
+        {
+            let (_, second) = significant.next()?;
+            name = ObjectName {
+                schema: Some(name.name),
+                name: identifier(second)?,
+            };
+        }
+        let (columns, Node::Group(_)) = significant.next()? else {
+            return None;
+        };
+        let (_, values) = significant.next()?;
+        if !matches!(values, Node::Token(t) if t.is_word("VALUES")) {
+            return None;
+        }
+        let mut rows = Vec::new();
+        loop {
+            let (row, Node::Group(_)) = significant.next()? else {
+                return None;
+            };
+            rows.push(row);
+            if significant
+                .next_if(|(_, n)| matches!(n, Node::Token(Token::Comma)))
+                .is_none()
+            {
+                break;
+            }
+        }
+        Some(Insert {
+            table: name,
+            columns,
+            rows,
+        })
+    }
+}
+
+fn group(node: &Node) -> &[Node] {
+    match node {
+        Node::Group(inner) => inner,
+        Node::Token(_) => &[],
+    }
+}
+
+// Adds `, item` to the end of a group.
+fn append(node: &mut Node, item: Token) {
+    if let Node::Group(inner) = node {
+        inner.push(Node::Token(Token::Comma));
+        inner.push(Node::Token(Token::Whitespace(" ".to_string())));
+        inner.push(Node::Token(item));
+    }
+}
+
+// A column of the column list, as the catalog names it.
+fn column_name(nodes: &[Node]) -> Option<String> {
+    match nodes.iter().filter(|n| !n.is_trivia()).collect::<Vec<_>>()[..] {
+        [node] => identifier(node),
+        _ => None,
+    }
+}
+
+// A name in the translated statement: a bare word folds to lower case, and the translator
+// quotes names it was given in backticks.
+fn identifier(node: &Node) -> Option<String> {
+    match node {
+        Node::Token(Token::Word(word)) => Some(word.to_lowercase()),
+        Node::Token(Token::DoubleQuoted(quoted)) => {
+            Some(quoted[1..quoted.len() - 1].replace("\"\"", "\""))
+        }
+        _ => None,
+    }
+}
+
+// MySQL's implicit default for a column of the PostgreSQL type `pg_type`, as format_type()
+// prints it.
+fn implicit_value(pg_type: &str) -> Option<Token> {
+    if pg_type.ends_with(']') {
+        return None;
+    }
+    let base = pg_type.split('(').next().unwrap_or(pg_type);
+    match base {
+        "smallint" | "integer" | "bigint" | "numeric" | "real" | "double precision" => {
+            Some(Token::Number("0".to_string()))
+        }
+        "boolean" => Some(Token::Word("false".to_string())),
+        "character varying" | "character" | "text" | "citext" | "bytea" => {
+            Some(Token::String("''".to_string()))
+        }
+        "time" | "time without time zone" | "time with time zone" => {
+            Some(Token::String("'00:00:00'".to_string()))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,610 @@
+// `postmyrustache import`: loads a MySQL dump into PostgreSQL directly, without the proxy.
+//
+//   postmyrustache import dump.sql [--schema name] [--failures file]
+//
+// The dump is read a statement at a time, honouring DELIMITER lines, and the statements inside
+// `/*!40101 ... */` version comments are run as MySQL would. INSERTs of plain values, which is
+// what mysqldump writes for table data, are loaded with COPY; every other statement is
+// translated and run as the proxy would run it. Session settings (SET, LOCK TABLES and the
+// DISABLE KEYS pairs) don't apply to the import and are skipped.
+//
+// mysqldump creates tables alphabetically with foreign key checks off, so foreign keys are taken
+// out of CREATE TABLE and added once all the data is in. USE and CREATE DATABASE pick and create
+// schemas; --schema loads everything into one schema instead.
+//
+// A failed statement doesn't stop the import. The failures are listed at the end, and with
+// --failures the statements are written to a file to be fixed and run again. The exit status is
+// 1 when any statement failed.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
+use tokio_postgres::types::Type;
+use tokio_postgres::Client;
+
+use crate::error::MysqlError;
+use crate::translator::dates::Coerced;
+use crate::translator::{
+    self, lexer, literals, render, split_args, DateModes, Node, Token, Translator,
+};
+
+const USAGE: &str = "usage: postmyrustache import <dump.sql> [--schema <name>] [--failures <file>]";
+
+// How often the progress line is printed.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Runs the subcommand with the arguments after `import`. Returns whether every statement loaded.
+pub async fn run(
+    args: &[String],
+    client: &Client,
+    translator: &Translator,
+) -> Result<bool, Box<dyn Error>> {
+    let mut dump = None;
+    let mut schema = None;
+    let mut failures_file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schema" => schema = args.next(),
+            "--failures" => failures_file = args.next(),
+            _ if dump.is_none() && !arg.starts_with("--") => dump = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let dump = dump.ok_or(USAGE)?;
+    let file = File::open(dump).map_err(|e| format!("can't read {}: {}", dump, e))?;
+    let size = file.metadata()?.len();
+    let mut statements = Statements::new(BufReader::new(file));
+
+    let mut import = Import {
+        client,
+        translator,
+        schema: schema.map(|s| literals::pg_identifier(s)),
+        statements: 0,
+        rows: 0,
+        foreign_keys: Vec::new(),
+        identities: Vec::new(),
+        failures: Vec::new(),
+    };
+    if let Some(schema) = &import.schema {
+        client
+            .batch_execute(&format!(
+                "CREATE SCHEMA IF NOT EXISTS {0}; SET search_path TO {0}",
+                schema
+            ))
+            .await?;
+    }
+
+    let started = Instant::now();
+    let mut reported = started;
+    while let Some(statement) = statements
+        .next()
+        .map_err(|e| format!("can't read {}: {}", dump, e))?
+    {
+        import.statement(statement).await;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            println!(
+                "{}% ({} of {} MB): {} statements, {} rows",
+                statements.read * 100 / size.max(1),
+                statements.read / 1_000_000,
+                size / 1_000_000,
+                import.statements,
+                import.rows
+            );
+        }
+    }
+    for foreign_key in std::mem::take(&mut import.foreign_keys) {
+        import.statement(foreign_key).await;
+    }
+    for identity in std::mem::take(&mut import.identities) {
+        if let Err(e) = client.batch_execute(&identity.sql).await {
+            import.failures.push(Failure {
+                line: identity.line,
+                sql: identity.sql,
+                error: pg_message(e),
+            });
+        }
+    }
+
+    println!(
+        "Imported {} statements and {} rows in {:.1}s",
+        import.statements,
+        import.rows,
+        started.elapsed().as_secs_f64()
+    );
+    if import.failures.is_empty() {
+        return Ok(true);
+    }
+    println!("{} statements failed:", import.failures.len());
+    for failure in &import.failures {
+        let mut excerpt: String = failure.sql.trim().chars().take(100).collect();
+        if excerpt.len() < failure.sql.trim().len() {
+            excerpt.push_str("...");
+        }
+        println!("  line {}: {}", failure.line, failure.error);
+        println!("    {}", excerpt.replace('\n', " "));
+    }
+    if let Some(path) = failures_file {
+        let mut out = String::new();
+        for failure in &import.failures {
+            out.push_str(&format!("-- line {}: {}\n", failure.line, failure.error));
+            if failure.sql.contains(';') {
+                out.push_str(&format!("DELIMITER ;;\n{};;\nDELIMITER ;\n\n", failure.sql));
+            } else {
+                out.push_str(&format!("{};\n\n", failure.sql));
+            }
+        }
+        fs::write(path, out).map_err(|e| format!("can't write {}: {}", path, e))?;
+        println!("The failed statements are in {}", path);
+    }
+    Ok(false)
+}
+
+pub(crate) struct Statement {
+    // The line of the dump the statement starts on.
+    pub(crate) line: usize,
+    pub(crate) sql: String,
+}
+
+struct Failure {
+    line: usize,
+    sql: String,
+    error: String,
+}
+
+struct Import<'a> {
+    client: &'a Client,
+    translator: &'a Translator,
+    // The quoted schema given with --schema.
+    schema: Option<String>,
+    statements: u64,
+    rows: u64,
+    // ALTER TABLE ... ADD FOREIGN KEY, for after the data.
+    foreign_keys: Vec<Statement>,
+    // PostgreSQL statements moving the identity sequences of the created tables past the rows
+    // loaded with their own ids, which is all of them in a dump.
+    identities: Vec<Statement>,
+    failures: Vec<Failure>,
+}
+
+impl Import<'_> {
+    async fn statement(&mut self, statement: Statement) {
+        let sql = unwrap_version_comments(&statement.sql);
+        match self.execute(&statement, &sql).await {
+            Ok(true) => self.statements += 1,
+            Ok(false) => {}
+            Err(error) => self.failures.push(Failure {
+                line: statement.line,
+                sql,
+                error,
+            }),
+        }
+    }
+
+    // Runs one statement of the dump. Returns whether it was run rather than skipped.
+    async fn execute(&mut self, statement: &Statement, sql: &str) -> Result<bool, String> {
+        let nodes = translator::parse(sql).map_err(|e| e.to_string())?;
+        let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+        let sql = match significant.as_slice() {
+            [] => return Ok(false),
+            [first, ..] if ["SET", "LOCK", "UNLOCK"].iter().any(|w| is_word(first, w)) => {
+                return Ok(false)
+            }
+            [alter, .., disable, keys]
+                if is_word(alter, "ALTER")
+                    && (is_word(disable, "DISABLE") || is_word(disable, "ENABLE"))
+                    && is_word(keys, "KEYS") =>
+            {
+                return Ok(false)
+            }
+            [use_, Node::Token(name)] if is_word(use_, "USE") => {
+                if self.schema.is_some() {
+                    return Ok(false);
+                }
+                let name = literals::identifier_name(name).ok_or("USE of a non-name")?;
+                format!("SET search_path TO {}", literals::pg_identifier(&name))
+            }
+            [create, database, rest @ ..]
+                if is_word(create, "CREATE")
+                    && (is_word(database, "DATABASE") || is_word(database, "SCHEMA")) =>
+            {
+                if self.schema.is_some() {
+                    return Ok(false);
+                }
+                // `[IF NOT EXISTS] name [options]`
+                let name = match rest {
+                    [if_, _, _, name, ..] if is_word(if_, "IF") => name,
+                    [name, ..] => name,
+                    [] => return Err("CREATE DATABASE without a name".to_string()),
+                };
+                let name = match name {
+                    Node::Token(name) => literals::identifier_name(name),
+                    Node::Group(_) => None,
+                }
+                .ok_or("CREATE DATABASE of a non-name")?;
+                format!(
+                    "CREATE SCHEMA IF NOT EXISTS {}",
+                    literals::pg_identifier(&name)
+                )
+            }
+            [insert, ..] if is_word(insert, "INSERT") => {
+                if let Some(copy) = copy(&significant) {
+                    self.rows += self.copy(copy).await.map_err(pg_message)?;
+                    return Ok(true);
+                }
+                self.translate(sql)?
+            }
+            [create, table, rest @ ..] if is_word(create, "CREATE") && is_word(table, "TABLE") => {
+                let name = match rest {
+                    [if_, _, _, rest @ ..] if is_word(if_, "IF") => rest,
+                    rest => rest,
+                };
+                let name = name
+                    .iter()
+                    .position(|n| matches!(n, Node::Group(_)) || is_word(n, "LIKE"))
+                    .and_then(|end| table_name(&name[..end]));
+                let (table, foreign_keys) = take_foreign_keys(nodes.clone());
+                self.client
+                    .batch_execute(&self.translate(&table)?)
+                    .await
+                    .map_err(pg_message)?;
+                self.foreign_keys
+                    .extend(foreign_keys.into_iter().map(|sql| Statement {
+                        line: statement.line,
+                        sql,
+                    }));
+                if let Some(name) = name {
+                    let identities = self
+                        .client
+                        .query(IDENTITY_SEQUENCES, &[&name])
+                        .await
+                        .map_err(pg_message)?;
+                    self.identities
+                        .extend(identities.iter().map(|row| Statement {
+                            line: statement.line,
+                            sql: row.get(0),
+                        }));
+                }
+                return Ok(true);
+            }
+            _ => self.translate(sql)?,
+        };
+        self.client.batch_execute(&sql).await.map_err(pg_message)?;
+        Ok(true)
+    }
+
+    fn translate(&self, sql: &str) -> Result<String, String> {
+        self.translator.translate(sql).map_err(|e| e.to_string())
+    }
+
+    async fn copy(&self, copy: Copy) -> Result<u64, tokio_postgres::Error> {
+        // A COPY that fails before it starts, on a missing table or column, leaves the connection
+        // out of step with the server, so those are checked first. Their types tell which values
+        // are dates.
+        let columns = copy.columns.as_deref().unwrap_or("*");
+        let probe = self
+            .client
+            .prepare(&format!("SELECT {} FROM {} LIMIT 0", columns, copy.table))
+            .await?;
+        let dated: Vec<bool> = probe.columns().iter().map(|c| is_date(c.type_())).collect();
+        let data = copy_data(&copy.rows, &dated, self.translator.options().dates);
+        let columns = match &copy.columns {
+            Some(columns) => format!(" ({})", columns),
+            None => String::new(),
+        };
+        let sink = self
+            .client
+            .copy_in(&format!("COPY {}{} FROM STDIN", copy.table, columns))
+            .await?;
+        pin_mut!(sink);
+        sink.send(Bytes::from(data)).await?;
+        sink.finish().await
+    }
+}
+
+// The setval of each identity column of the table named $1.
+const IDENTITY_SEQUENCES: &str = "\
+    SELECT format('SELECT setval(%L, max(%I)) FROM %I.%I HAVING max(%I) IS NOT NULL', \
+                  pg_get_serial_sequence(format('%I.%I', n.nspname, c.relname), a.attname), \
+                  a.attname, n.nspname, c.relname, a.attname) \
+    FROM pg_attribute a \
+    JOIN pg_class c ON c.oid = a.attrelid \
+    JOIN pg_namespace n ON n.oid = c.relnamespace \
+    WHERE c.oid = $1::text::regclass AND a.attidentity <> ''";
+
+fn is_word(node: &Node, word: &str) -> bool {
+    matches!(node, Node::Token(t) if t.is_word(word))
+}
+
+fn pg_message(e: tokio_postgres::Error) -> String {
+    MysqlError::from(e).message
+}
+
+/// The statements of a dump, split on the current delimiter.
+pub(crate) struct Statements<R> {
+    reader: R,
+    delimiter: String,
+    line: usize,
+    // Bytes read so far, for the progress report.
+    read: u64,
+}
+
+impl<R: BufRead> Statements<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Statements {
+            reader,
+            delimiter: ";".to_string(),
+            line: 0,
+            read: 0,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> io::Result<Option<Statement>> {
+        let mut sql = String::new();
+        let mut start = None;
+        loop {
+            let mut line = String::new();
+            let n = self.reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+            self.read += n as u64;
+            self.line += 1;
+
+            // `DELIMITER ;;` is a client command, so it only comes between statements.
+            let blank = translator::significant_tokens(&sql).is_some_and(|t| t.is_empty());
+            if blank {
+                if let Some(delimiter) = delimiter_command(&line) {
+                    self.delimiter = delimiter.to_string();
+                    sql.clear();
+                    continue;
+                }
+            }
+            if start.is_none() && !line.trim().is_empty() {
+                start = Some(self.line);
+            }
+            sql.push_str(&line);
+            // The delimiter ends the statement unless it is inside a string or comment.
+            if let Some(body) = sql.trim_end().strip_suffix(self.delimiter.as_str()) {
+                if lexer::tokenize(body).is_ok() {
+                    return Ok(Some(Statement {
+                        line: start.unwrap_or(self.line),
+                        sql: body.to_string(),
+                    }));
+                }
+            }
+        }
+        if sql.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Statement {
+            line: start.unwrap_or(self.line),
+            sql,
+        }))
+    }
+}
+
+fn delimiter_command(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let (command, delimiter) = line.split_once(char::is_whitespace)?;
+    if !command.eq_ignore_ascii_case("DELIMITER") {
+        return None;
+    }
+    Some(delimiter.trim()).filter(|d| !d.is_empty())
+}
+
+/// Replaces the `/*!NNNNN ... */` comments, which MySQL runs, with their contents.
+pub(crate) fn unwrap_version_comments(sql: &str) -> String {
+    let Ok(tokens) = lexer::tokenize(sql) else {
+        return sql.to_string();
+    };
+    let mut out = String::with_capacity(sql.len());
+    for token in tokens {
+        match &token {
+            Token::Comment(comment) if comment.starts_with("/*!") => {
+                let inner = &comment[3..comment.len() - 2];
+                out.push(' ');
+                out.push_str(inner.trim_start_matches(|c: char| c.is_ascii_digit()));
+                out.push(' ');
+            }
+            token => out.push_str(&token.to_string()),
+        }
+    }
+    out
+}
+
+// An INSERT as data for COPY.
+struct Copy {
+    // The quoted table and columns.
+    table: String,
+    columns: Option<String>,
+    // The values of the rows, None for NULL.
+    rows: Vec<Vec<Option<String>>>,
+}
+
+// The COPY for `INSERT INTO name [(columns)] VALUES (...), ...` of plain values. `None` for any
+// other INSERT, which is translated instead.
+fn copy(significant: &[&Node]) -> Option<Copy> {
+    let [insert, into, rest @ ..] = significant else {
+        return None;
+    };
+    if !is_word(insert, "INSERT") || !is_word(into, "INTO") {
+        return None;
+    }
+    let values_at = rest
+        .iter()
+        .position(|n| is_word(n, "VALUES") || is_word(n, "VALUE"))?;
+    let (target, rows) = (&rest[..values_at], &rest[values_at + 1..]);
+
+    let (name, columns) = match target {
+        [name @ .., Node::Group(columns)] => (name, Some(columns)),
+        name => (name, None),
+    };
+    let name = table_name(name)?;
+    let columns = match columns {
+        Some(columns) => {
+            let names = split_args(columns)
+                .iter()
+                .map(|column| match column.iter().find(|n| !n.is_trivia()) {
+                    Some(Node::Token(t)) => literals::identifier_name(t),
+                    _ => None,
+                })
+                .map(|name| name.map(|n| literals::pg_identifier(&n)))
+                .collect::<Option<Vec<String>>>()?;
+            Some(names.join(", "))
+        }
+        None => None,
+    };
+
+    let mut values = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        match row {
+            Node::Group(row) if i % 2 == 0 => {
+                values.push(
+                    split_args(row)
+                        .into_iter()
+                        .map(copy_value)
+                        .collect::<Option<Vec<_>>>()?,
+                );
+            }
+            Node::Token(Token::Comma) if i % 2 == 1 => {}
+            // ON DUPLICATE KEY UPDATE, a row alias and the like.
+            _ => return None,
+        }
+    }
+    if values.is_empty() {
+        return None;
+    }
+    Some(Copy {
+        table: name,
+        columns,
+        rows: values,
+    })
+}
+
+// `name` or `schema.name`, quoted for PostgreSQL.
+fn table_name(nodes: &[&Node]) -> Option<String> {
+    match nodes {
+        [Node::Token(name)] => Some(literals::pg_identifier(&literals::identifier_name(name)?)),
+        [Node::Token(schema), Node::Token(dot), Node::Token(name)] if dot.is_operator(".") => {
+            Some(format!(
+                "{}.{}",
+                literals::pg_identifier(&literals::identifier_name(schema)?),
+                literals::pg_identifier(&literals::identifier_name(name)?)
+            ))
+        }
+        _ => None,
+    }
+}
+
+// A string, number or NULL, None being NULL. Anything else, hexadecimal literals and
+// expressions included, needs translating.
+fn copy_value(value: &[Node]) -> Option<Option<String>> {
+    let significant: Vec<&Node> = value.iter().filter(|n| !n.is_trivia()).collect();
+    match significant.as_slice() {
+        [null] if is_word(null, "NULL") => Some(None),
+        [Node::Token(Token::Number(n))] => Some(Some(n.clone())),
+        [Node::Token(minus), Node::Token(Token::Number(n))] if minus.is_operator("-") => {
+            Some(Some(format!("-{}", n)))
+        }
+        [Node::Token(Token::String(s))] => Some(Some(literals::mysql_string_value(s))),
+        _ => None,
+    }
+}
+
+/// Whether values of the PostgreSQL type `pg_type` are dates, whose zero and impossible values
+/// are coerced.
+pub(crate) fn is_date(pg_type: &Type) -> bool {
+    matches!(*pg_type, Type::DATE | Type::TIMESTAMP | Type::TIMESTAMPTZ)
+}
+
+/// `rows` in COPY's text format. Zero and impossible dates in the columns `dated` marks are
+/// coerced as the translator would coerce them.
+pub(crate) fn copy_data(rows: &[Vec<Option<String>>], dated: &[bool], dates: DateModes) -> String {
+    let mut data = String::new();
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                data.push('\t');
+            }
+            let coerced = match value {
+                Some(value) if dated.get(i) == Some(&true) => {
+                    translator::dates::coerce(value, dates)
+                }
+                _ => None,
+            };
+            let value = match (coerced, value) {
+                (Some(Coerced::Null), _) | (None, None) => {
+                    data.push_str("\\N");
+                    continue;
+                }
+                (Some(Coerced::Clamped(date)), _) => date,
+                (None, Some(value)) => value.clone(),
+            };
+            for c in value.chars() {
+                match c {
+                    '\\' => data.push_str("\\\\"),
+                    '\n' => data.push_str("\\n"),
+                    '\r' => data.push_str("\\r"),
+                    '\t' => data.push_str("\\t"),
+                    c => data.push(c),
+                }
+            }
+        }
+        data.push('\n');
+    }
+    data
+}
+
+// Splits the FOREIGN KEY items out of a CREATE TABLE, as ALTER TABLE statements to run once the
+// tables they reference exist.
+fn take_foreign_keys(mut nodes: Vec<Node>) -> (String, Vec<String>) {
+    let Some(body_at) = nodes.iter().position(|n| matches!(n, Node::Group(_))) else {
+        return (render(&nodes), Vec::new());
+    };
+    let mut name_start = 0;
+    for (i, node) in nodes[..body_at].iter().enumerate() {
+        if is_word(node, "TABLE") || is_word(node, "EXISTS") {
+            name_start = i + 1;
+        }
+    }
+    let table = render(&nodes[name_start..body_at]).trim().to_string();
+    let Node::Group(body) = &nodes[body_at] else {
+        unreachable!("found a group");
+    };
+
+    let mut kept: Vec<&[Node]> = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for item in split_args(body) {
+        let mut significant = item.iter().filter(|n| !n.is_trivia());
+        let is_foreign_key = match significant.next() {
+            Some(first) if is_word(first, "FOREIGN") => true,
+            Some(first) if is_word(first, "CONSTRAINT") => {
+                significant.take(2).any(|n| is_word(n, "FOREIGN"))
+            }
+            _ => false,
+        };
+        if is_foreign_key {
+            foreign_keys.push(format!("ALTER TABLE {} ADD {}", table, render(item).trim()));
+        } else {
+            kept.push(item);
+        }
+    }
+    if foreign_keys.is_empty() {
+        return (render(&nodes), foreign_keys);
+    }
+    let mut body = Vec::new();
+    for (i, item) in kept.into_iter().enumerate() {
+        if i > 0 {
+            body.push(Node::Token(Token::Comma));
+        }
+        body.extend_from_slice(item);
+    }
+    nodes[body_at] = Node::Group(body);
+    (render(&nodes), foreign_keys)
+}
@@ -0,0 +1,106 @@
+// Query interceptors: hooks a program embedding the proxy registers with
+// ServerBuilder::interceptor to rewrite, refuse or watch its clients' statements without
+// changing the translator, say to add a tenant condition, rename tables or deny some statements.
+//
+// An interceptor is called at three points of each statement:
+//
+//   - before_translate, with the statement as the client sent it, before the proxy answers or
+//     translates it; COM_QUERY and COM_STMT_PREPARE both go through it
+//   - after_translate, with the PostgreSQL the translator made of it, before it runs
+//   - on_result, once PostgreSQL has run it, with the rows or affected count, or the error
+//
+// The interceptors are chained in the order they were registered: each is given what the one
+// before it returned, and the first to return an error stops the statement, the client getting
+// that error. Statements the proxy answers itself, SHOW WARNINGS or KILL for example, only go
+// through before_translate.
+
+use crate::error::MysqlError;
+use crate::TranslationOptions;
+
+/// The connection a statement came from.
+#[derive(Debug, Clone, Copy)]
+pub struct Context<'a> {
+    pub connection_id: u32,
+    // The MySQL user the client logged in as.
+    pub user: &'a str,
+    // The database chosen with USE, if any.
+    pub database: Option<&'a str>,
+    // Whether the statement runs read-only, in a transaction started READ ONLY or after SET
+    // [SESSION] TRANSACTION READ ONLY: a hint that it can go to a replica.
+    pub read_only: bool,
+    // How the session reads its SQL, from its sql_mode: ANSI_QUOTES, NO_BACKSLASH_ESCAPES...
+    pub options: &'a TranslationOptions,
+}
+
+/// How a statement run on PostgreSQL turned out.
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome<'a> {
+    // The rows of a statement returning some, SELECT or ... RETURNING.
+    Rows(usize),
+    // The rows changed by any other statement.
+    Affected(u64),
+    Failed(&'a MysqlError),
+}
+
+/// Hooks into the statements of every connection. Each does nothing unless implemented.
+pub trait QueryInterceptor: Send + Sync {
+    /// Rewrites or refuses the MySQL statement `sql`. `None` leaves it as it is.
+    fn before_translate(
+        &self,
+        _context: &Context,
+        _sql: &str,
+    ) -> Result<Option<String>, MysqlError> {
+        Ok(None)
+    }
+
+    /// Rewrites or refuses `translated`, the PostgreSQL a statement was translated to. `None`
+    /// leaves it as it is.
+    fn after_translate(
+        &self,
+        _context: &Context,
+        _translated: &str,
+    ) -> Result<Option<String>, MysqlError> {
+        Ok(None)
+    }
+
+    /// Called with the outcome of `sql`, the MySQL statement as before_translate left it.
+    fn on_result(&self, _context: &Context, _sql: &str, _outcome: Outcome) {}
+}
+
+/// Runs `sql` through the before_translate hook of each of `interceptors`.
+pub(crate) fn before_translate(
+    interceptors: &[Box<dyn QueryInterceptor>],
+    context: &Context,
+    sql: &str,
+) -> Result<Option<String>, MysqlError> {
+    chain(interceptors, sql, |interceptor, sql| {
+        interceptor.before_translate(context, sql)
+    })
+}
+
+/// Runs `translated` through the after_translate hook of each of `interceptors`.
+pub(crate) fn after_translate(
+    interceptors: &[Box<dyn QueryInterceptor>],
+    context: &Context,
+    translated: &str,
+) -> Result<Option<String>, MysqlError> {
+    chain(interceptors, translated, |interceptor, sql| {
+        interceptor.after_translate(context, sql)
+    })
+}
+
+// Passes `sql` from one interceptor to the next. `None` if none of them rewrote it.
+fn chain(
+    interceptors: &[Box<dyn QueryInterceptor>],
+    sql: &str,
+    hook: impl Fn(&dyn QueryInterceptor, &str) -> Result<Option<String>, MysqlError>,
+) -> Result<Option<String>, MysqlError> {
+    let mut rewritten: Option<String> = None;
+    for interceptor in interceptors {
+        let current = rewritten.as_deref().unwrap_or(sql);
+        if let Some(sql) = hook(interceptor.as_ref(), current)? {
+            rewritten = Some(sql);
+        }
+    }
+    Ok(rewritten)
+}
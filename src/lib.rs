@@ -0,0 +1,78 @@
+// PostMyRustache as a library, for programs that embed the MySQL façade, in their tests or in
+// servers of their own, rather than run the binary. ServerBuilder (see server.rs) is the place to
+// start; the translator can also be used on its own, through translate_mysql_to_postgres, or
+// as the postmyrustache-translator crate.
+
+mod audit;
+pub mod auth;
+mod call;
+mod catalog;
+mod charset;
+pub mod check;
+mod client_tls;
+mod compression;
+pub mod config;
+mod connection_ids;
+mod cursors;
+mod date_columns;
+mod ddl_history;
+mod der;
+mod diagnostics;
+mod digest;
+mod emulation;
+mod error;
+pub mod export;
+mod failover;
+mod failures;
+mod guc_mappings;
+mod idempotency;
+mod implicit_defaults;
+pub mod import;
+pub mod intercept;
+mod limits;
+mod load_data;
+pub mod logging;
+mod mysql_specific;
+mod policy;
+mod profiling;
+mod protocol;
+mod reconnect;
+mod result_cache;
+mod resultset;
+pub mod rewrite_rules;
+mod routine_sources;
+mod runtime;
+pub mod schema_diff;
+pub mod server;
+mod session_init;
+mod sessions;
+mod shadow;
+mod snapshot;
+mod sql_mode;
+mod statement_cache;
+mod stats;
+pub mod summary;
+mod system_variables;
+pub mod telemetry;
+mod throttle;
+mod time_zone;
+mod timeouts;
+mod tls;
+mod trace;
+mod transaction_modes;
+pub mod transport;
+pub mod tunnel;
+mod upstream;
+pub mod verify;
+
+pub use auth::AuthProvider;
+pub use config::Config;
+pub use error::MysqlError;
+pub use intercept::QueryInterceptor;
+pub use server::{Backend, Server, ServerBuilder, Upstream};
+// The translator is a crate of its own, without I/O, so it builds for wasm32 as well; see
+// translator/src/wasm.rs.
+pub use postmyrustache_translator as translator;
+pub use translator::{translate_mysql_to_postgres, TranslationOptions, Translator};
+// The kinds of error a QueryInterceptor can refuse a statement with.
+pub use opensrv_mysql::ErrorKind;
@@ -0,0 +1,215 @@
+// Limits on the statements clients send, so that a pathological one, from a query builder gone
+// wrong say, is refused with a clear error instead of tying up the translator or PostgreSQL's
+// planner:
+//
+//   MAX_STATEMENT_LENGTH  bytes in the statement, 64 MiB by default, as MySQL's
+//                         max_allowed_packet
+//   MAX_PARSE_DEPTH       parentheses nested in one another, 256 by default
+//   MAX_UNION_BRANCHES    SELECTs combined with UNION in the statement, 1024 by default
+//   MAX_IN_LIST_ITEMS     values in one IN (...) list, 65535 by default
+//
+// A limit of 0 is no limit. The length and the nesting are checked before anything else is done
+// with the statement, the nesting on its tokens: a parse tree nested deep enough overflows the
+// stack as it is built, walked or dropped. The others are checked once the statement is parsed
+// and before the translator's passes run over it.
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::translator::{self, lexer, Node, Token};
+
+const DEFAULT_MAX_LENGTH: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_DEPTH: usize = 256;
+const DEFAULT_MAX_UNION_BRANCHES: usize = 1024;
+const DEFAULT_MAX_IN_LIST_ITEMS: usize = 65535;
+
+/// The limits, None where there is none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementLimits {
+    pub max_length: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub max_union_branches: Option<usize>,
+    pub max_in_list_items: Option<usize>,
+}
+
+impl Default for StatementLimits {
+    fn default() -> Self {
+        StatementLimits {
+            max_length: Some(DEFAULT_MAX_LENGTH),
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_union_branches: Some(DEFAULT_MAX_UNION_BRANCHES),
+            max_in_list_items: Some(DEFAULT_MAX_IN_LIST_ITEMS),
+        }
+    }
+}
+
+impl StatementLimits {
+    pub(crate) fn check_length(&self, sql: &str) -> Result<(), MysqlError> {
+        match self.max_length {
+            Some(max) if sql.len() > max => Err(MysqlError::new(
+                ErrorKind::ER_NET_PACKET_TOO_LARGE,
+                format!(
+                    "The statement is {} bytes, more than MAX_STATEMENT_LENGTH ({})",
+                    sql.len(),
+                    max
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks how deeply the parentheses of `sql` nest, before it is parsed.
+    pub(crate) fn check_depth(&self, sql: &str) -> Result<(), MysqlError> {
+        let Some(max) = self.max_depth else {
+            return Ok(());
+        };
+        // No more parentheses than the limit can't nest deeper than it.
+        if sql.bytes().filter(|&b| b == b'(').count() <= max {
+            return Ok(());
+        }
+        // Left for the parse to report.
+        let Ok(tokens) = lexer::tokenize(sql) else {
+            return Ok(());
+        };
+        let mut depth = 0usize;
+        for token in tokens {
+            match token {
+                Token::LParen if depth == max => {
+                    return Err(MysqlError::new(
+                        ErrorKind::ER_TOO_HIGH_LEVEL_OF_NESTING_FOR_SELECT,
+                        format!(
+                            "Too high level of nesting: the statement's parentheses nest more \
+                             than MAX_PARSE_DEPTH ({}) deep",
+                            max
+                        ),
+                    ))
+                }
+                Token::LParen => depth += 1,
+                Token::RParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the UNIONs and IN lists of `nodes`, a parsed statement.
+    pub(crate) fn check(&self, nodes: &[Node]) -> Result<(), MysqlError> {
+        let mut unions = 0;
+        // The groups still to look at, walked with a stack of its own rather than recursion.
+        let mut groups = vec![nodes];
+        while let Some(group) = groups.pop() {
+            let mut previous: Option<&Token> = None;
+            for node in group {
+                match node {
+                    Node::Token(token) if token.is_trivia() => continue,
+                    Node::Token(token) => {
+                        if token.is_word("UNION") {
+                            unions += 1;
+                        }
+                        previous = Some(token);
+                        continue;
+                    }
+                    Node::Group(inner) => {
+                        if previous.is_some_and(|token| token.is_word("IN")) {
+                            self.check_in_list(inner)?;
+                        }
+                        groups.push(inner);
+                    }
+                }
+                previous = None;
+            }
+        }
+        match self.max_union_branches {
+            Some(max) if unions + 1 > max => Err(MysqlError::new(
+                ErrorKind::ER_TOO_BIG_SELECT,
+                format!(
+                    "The statement has {} UNION branches, more than MAX_UNION_BRANCHES ({})",
+                    unions + 1,
+                    max
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_in_list(&self, list: &[Node]) -> Result<(), MysqlError> {
+        let Some(max) = self.max_in_list_items else {
+            return Ok(());
+        };
+        let items = translator::split_args(list).len();
+        if items > max {
+            return Err(MysqlError::new(
+                ErrorKind::ER_TOO_BIG_SELECT,
+                format!(
+                    "An IN list has {} items, more than MAX_IN_LIST_ITEMS ({})",
+                    items, max
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested(depth: usize) -> String {
+        format!("SELECT {}1{}", "(".repeat(depth), ")".repeat(depth))
+    }
+
+    #[test]
+    fn refuses_a_statement_nested_deeper_than_the_limit() {
+        let limits = StatementLimits::default();
+        assert!(limits.check_depth(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+        let error = limits
+            .check_depth(&nested(DEFAULT_MAX_DEPTH + 1))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::ER_TOO_HIGH_LEVEL_OF_NESTING_FOR_SELECT
+        );
+    }
+
+    #[test]
+    fn refuses_a_deeply_nested_statement_without_parsing_it() {
+        // Deep enough to overflow the stack if it were folded into groups and dropped.
+        let limits = StatementLimits::default();
+        assert!(limits.check_depth(&nested(1_000_000)).is_err());
+    }
+
+    #[test]
+    fn parentheses_in_literals_and_comments_dont_nest() {
+        let limits = StatementLimits {
+            max_depth: Some(2),
+            ..StatementLimits::default()
+        };
+        assert!(limits
+            .check_depth("SELECT '((((', ((1)) /* (((( */")
+            .is_ok());
+        assert!(limits.check_depth("SELECT (((1)))").is_err());
+    }
+
+    #[test]
+    fn no_depth_limit() {
+        let limits = StatementLimits {
+            max_depth: None,
+            ..StatementLimits::default()
+        };
+        assert!(limits.check_depth(&nested(DEFAULT_MAX_DEPTH + 1)).is_ok());
+    }
+
+    #[test]
+    fn counts_union_branches_and_in_list_items() {
+        let limits = StatementLimits {
+            max_union_branches: Some(2),
+            max_in_list_items: Some(3),
+            ..StatementLimits::default()
+        };
+        let check = |sql: &str| limits.check(&translator::parse(sql).unwrap());
+        assert!(check("SELECT 1 UNION SELECT 2").is_ok());
+        assert!(check("SELECT 1 UNION SELECT 2 UNION SELECT 3").is_err());
+        assert!(check("SELECT * FROM t WHERE a IN (1, 2, 3)").is_ok());
+        assert!(check("SELECT * FROM t WHERE a IN (1, 2, 3, 4)").is_err());
+    }
+}
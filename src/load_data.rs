@@ -0,0 +1,558 @@
+// LOAD DATA INFILE: loads a file of delimited rows into a table.
+//
+//   LOAD DATA INFILE 'orders.csv' INTO TABLE orders
+//     FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '"' LINES TERMINATED BY '\n'
+//     IGNORE 1 LINES (id, placed, total)
+//
+// The file is read on the proxy's host, from the directory SECURE_FILE_PRIV names, as MySQL
+// reads it from its own; without SECURE_FILE_PRIV the statement is refused, unlike MySQL's empty
+// secure_file_priv, which reads files anywhere. The name is relative to the directory, or an
+// absolute path within it. A name with `..`, an absolute path elsewhere, or a symbolic link
+// leading out of the directory is refused as a file outside it. The rows are split with MySQL's FIELDS and LINES options and their defaults
+// (tab-separated fields, backslash escapes, \N for NULL) and loaded with COPY. Zero and
+// impossible dates in date and datetime columns are coerced as in statements.
+//
+// LOAD DATA LOCAL, whose file the client sends, is refused as by a MySQL server with local_infile
+// off. So are REPLACE and IGNORE of duplicate keys, SET clauses and fixed-width rows. Every row
+// needs a field for each column: a short or long row fails the statement, as in strict mode.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
+use opensrv_mysql::ErrorKind;
+use tokio_postgres::Client;
+
+use crate::catalog::ObjectName;
+use crate::error::MysqlError;
+use crate::import;
+use crate::translator::{self, literals, DateModes, Token};
+
+/// A LOAD DATA statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Load {
+    local: bool,
+    file: String,
+    table: ObjectName,
+    format: Format,
+    // Lines skipped at the start of the file.
+    ignore_lines: usize,
+    // The columns the fields go to, all of the table's when empty.
+    columns: Vec<String>,
+}
+
+// How the file is split into rows and fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Format {
+    fields_terminated: String,
+    enclosed: Option<char>,
+    escaped: Option<char>,
+    lines_starting: String,
+    lines_terminated: String,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            fields_terminated: "\t".to_string(),
+            enclosed: None,
+            escaped: Some('\\'),
+            lines_starting: String::new(),
+            lines_terminated: "\n".to_string(),
+        }
+    }
+}
+
+/// The LOAD DATA statement `sql`, or the error for one the proxy can't run. `None` for any other
+/// statement.
+pub fn parse(sql: &str) -> Option<Result<Load, MysqlError>> {
+    let mut tokens = translator::significant_tokens(sql)?;
+    if tokens.last() == Some(&Token::Semicolon) {
+        tokens.pop();
+    }
+    match &tokens[..] {
+        [load, data, ..] if load.is_word("LOAD") && data.is_word("DATA") => {}
+        _ => return None,
+    }
+    let mut cursor = Cursor {
+        tokens: &tokens,
+        at: 2,
+    };
+    Some(cursor.load())
+}
+
+/// Runs `load`, reading its file from `directory` (SECURE_FILE_PRIV). Returns how many rows were
+/// loaded.
+pub async fn execute(
+    client: &Client,
+    load: &Load,
+    directory: Option<&str>,
+    dates: DateModes,
+) -> Result<u64, MysqlError> {
+    if load.local {
+        return Err(MysqlError::new(
+            ErrorKind::ER_NOT_ALLOWED_COMMAND,
+            "The used command is not allowed with this MySQL version",
+        ));
+    }
+    let path = resolve(directory, &load.file)?;
+    let text = match tokio::fs::read(&path).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => return Err(unreadable(&load.file)),
+    };
+
+    let table = match &load.table.schema {
+        Some(schema) => format!(
+            "{}.{}",
+            literals::pg_identifier(schema),
+            literals::pg_identifier(&load.table.name)
+        ),
+        None => literals::pg_identifier(&load.table.name),
+    };
+    let quoted: Vec<String> = load
+        .columns
+        .iter()
+        .map(|c| literals::pg_identifier(c))
+        .collect();
+    let (selected, columns) = match quoted.is_empty() {
+        true => ("*".to_string(), String::new()),
+        false => (quoted.join(", "), format!(" ({})", quoted.join(", "))),
+    };
+    // The columns' types, and a missing table or column reported before the COPY starts.
+    let probe = client
+        .prepare(&format!("SELECT {} FROM {} LIMIT 0", selected, table))
+        .await?;
+    let dated: Vec<bool> = probe
+        .columns()
+        .iter()
+        .map(|c| import::is_date(c.type_()))
+        .collect();
+
+    let rows: Vec<Vec<Option<String>>> = rows(&text, &load.format)
+        .into_iter()
+        .skip(load.ignore_lines)
+        .collect();
+    for (i, row) in rows.iter().enumerate() {
+        if row.len() < dated.len() {
+            return Err(MysqlError::new(
+                ErrorKind::ER_WARN_TOO_FEW_RECORDS,
+                format!("Row {} doesn't contain data for all columns", i + 1),
+            ));
+        }
+        if row.len() > dated.len() {
+            return Err(MysqlError::new(
+                ErrorKind::ER_WARN_TOO_MANY_RECORDS,
+                format!(
+                    "Row {} was truncated; it contained more data than there were input columns",
+                    i + 1
+                ),
+            ));
+        }
+    }
+    let data = import::copy_data(&rows, &dated, dates);
+    let sink = client
+        .copy_in(&format!("COPY {}{} FROM STDIN", table, columns))
+        .await?;
+    pin_mut!(sink);
+    sink.send(Bytes::from(data)).await?;
+    Ok(sink.finish().await?)
+}
+
+// Where the file named `file` is, within `directory` (SECURE_FILE_PRIV). A name outside the
+// directory is refused before the file system is looked at, so a missing file elsewhere can't be
+// told from one that's there; the directory's own symbolic links are followed, and refused when
+// they lead out of it.
+fn resolve(directory: Option<&str>, file: &str) -> Result<PathBuf, MysqlError> {
+    let prevented = || {
+        MysqlError::new(
+            ErrorKind::ER_OPTION_PREVENTS_STATEMENT,
+            "The MySQL server is running with the --secure-file-priv option so it cannot \
+             execute this statement",
+        )
+    };
+    let directory = Path::new(directory.ok_or_else(prevented)?)
+        .canonicalize()
+        .map_err(|_| prevented())?;
+    let named = Path::new(file);
+    let relative = match named.strip_prefix(&directory) {
+        Ok(relative) => relative,
+        Err(_) if named.is_absolute() => return Err(prevented()),
+        Err(_) => named,
+    };
+    if !relative
+        .components()
+        .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(prevented());
+    }
+    let path = match directory.join(relative).canonicalize() {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(MysqlError::new(
+                ErrorKind::ER_FILE_NOT_FOUND,
+                format!(
+                    "Can't find file: '{}' (errno: 2 - No such file or directory)",
+                    file
+                ),
+            ))
+        }
+        Err(_) => return Err(unreadable(file)),
+    };
+    // Through a symbolic link, the file may be somewhere else.
+    if !path.starts_with(&directory) {
+        return Err(prevented());
+    }
+    Ok(path)
+}
+
+fn unreadable(file: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_TEXTFILE_NOT_READABLE,
+        format!(
+            "The file '{}' must be in the database directory or be readable by all",
+            file
+        ),
+    )
+}
+
+// Reads the clauses of a LOAD DATA statement in order.
+struct Cursor<'t> {
+    tokens: &'t [Token],
+    at: usize,
+}
+
+impl Cursor<'_> {
+    fn load(&mut self) -> Result<Load, MysqlError> {
+        let _ = self.word("LOW_PRIORITY") || self.word("CONCURRENT");
+        let local = self.word("LOCAL");
+        self.expect("INFILE")?;
+        let file = self.string()?;
+        if self.word("REPLACE") || self.word("IGNORE") {
+            return Err(unsupported("LOAD DATA with REPLACE or IGNORE"));
+        }
+        if self.word("PARTITION") {
+            return Err(unsupported("LOAD DATA into partitions"));
+        }
+        self.expect("INTO")?;
+        self.expect("TABLE")?;
+        let table = self.table()?;
+        if self.word("CHARACTER") {
+            self.expect("SET")?;
+            self.next();
+        } else if self.word("CHARSET") {
+            self.next();
+        }
+
+        let mut format = Format::default();
+        if self.word("FIELDS") || self.word("COLUMNS") {
+            loop {
+                if self.word("TERMINATED") {
+                    self.expect("BY")?;
+                    format.fields_terminated = self.string()?;
+                } else if self.word("OPTIONALLY") || self.word("ENCLOSED") {
+                    let _ = self.word("ENCLOSED");
+                    self.expect("BY")?;
+                    format.enclosed = self.character()?;
+                } else if self.word("ESCAPED") {
+                    self.expect("BY")?;
+                    format.escaped = self.character()?;
+                } else {
+                    break;
+                }
+            }
+        }
+        if self.word("LINES") {
+            loop {
+                if self.word("STARTING") {
+                    self.expect("BY")?;
+                    format.lines_starting = self.string()?;
+                } else if self.word("TERMINATED") {
+                    self.expect("BY")?;
+                    format.lines_terminated = self.string()?;
+                } else {
+                    break;
+                }
+            }
+        }
+        if format.fields_terminated.is_empty() || format.lines_terminated.is_empty() {
+            return Err(unsupported("LOAD DATA of fixed-width rows"));
+        }
+
+        let mut ignore_lines = 0;
+        if self.word("IGNORE") {
+            ignore_lines = match self.next() {
+                Some(Token::Number(n)) => n.parse().map_err(|_| self.syntax())?,
+                _ => return Err(self.syntax()),
+            };
+            if !self.word("LINES") && !self.word("ROWS") {
+                return Err(self.syntax());
+            }
+        }
+        let mut columns = Vec::new();
+        if self.tokens.get(self.at) == Some(&Token::LParen) {
+            self.at += 1;
+            loop {
+                columns.push(self.identifier()?);
+                match self.next() {
+                    Some(Token::Comma) => {}
+                    Some(Token::RParen) => break,
+                    _ => return Err(self.syntax()),
+                }
+            }
+        }
+        if self.word("SET") {
+            return Err(unsupported("LOAD DATA with SET"));
+        }
+        if self.at < self.tokens.len() {
+            return Err(self.syntax());
+        }
+        Ok(Load {
+            local,
+            file,
+            table,
+            format,
+            ignore_lines,
+            columns,
+        })
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.at)?;
+        self.at += 1;
+        Some(token)
+    }
+
+    // Takes the next token if it's `word`.
+    fn word(&mut self, word: &str) -> bool {
+        let found = self.tokens.get(self.at).is_some_and(|t| t.is_word(word));
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), MysqlError> {
+        match self.word(word) {
+            true => Ok(()),
+            false => Err(self.syntax()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, MysqlError> {
+        match self.next() {
+            Some(Token::String(raw) | Token::DoubleQuoted(raw)) => {
+                Ok(literals::mysql_string_value(raw))
+            }
+            _ => Err(self.syntax()),
+        }
+    }
+
+    // ENCLOSED BY and ESCAPED BY: a single character, or nothing for ''.
+    fn character(&mut self) -> Result<Option<char>, MysqlError> {
+        let value = self.string()?;
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (first, None) => Ok(first),
+            _ => Err(MysqlError::new(
+                ErrorKind::ER_WRONG_FIELD_TERMINATORS,
+                "Field separator argument is not what is expected; check the manual",
+            )),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, MysqlError> {
+        match self.next().and_then(literals::identifier_name) {
+            Some(name) => Ok(name),
+            None => Err(self.syntax()),
+        }
+    }
+
+    fn table(&mut self) -> Result<ObjectName, MysqlError> {
+        let name = self.identifier()?;
+        if self.tokens.get(self.at).is_some_and(|t| t.is_operator(".")) {
+            self.at += 1;
+            return Ok(ObjectName {
+                schema: Some(name),
+                name: self.identifier()?,
+            });
+        }
+        Ok(ObjectName { schema: None, name })
+    }
+
+    fn syntax(&self) -> MysqlError {
+        let near: String = self.tokens[self.at.min(self.tokens.len())..]
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        MysqlError::new(
+            ErrorKind::ER_PARSE_ERROR,
+            format!(
+                "You have an error in your SQL syntax; check the manual that corresponds to your \
+                 MySQL server version for the right syntax to use near '{}'",
+                near
+            ),
+        )
+    }
+}
+
+fn unsupported(what: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_NOT_SUPPORTED_YET,
+        format!("This version of MySQL doesn't yet support '{}'", what),
+    )
+}
+
+// The rows of `text`, None being NULL.
+fn rows(text: &str, format: &Format) -> Vec<Vec<Option<String>>> {
+    let mut rows = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if !format.lines_starting.is_empty() {
+            // Lines without the prefix are skipped.
+            match rest.find(&format.lines_starting) {
+                Some(at) => rest = &rest[at + format.lines_starting.len()..],
+                None => break,
+            }
+        }
+        let mut row = Vec::new();
+        loop {
+            let (value, after) = field(rest, format);
+            row.push(value);
+            rest = after;
+            if let Some(after) = rest.strip_prefix(format.fields_terminated.as_str()) {
+                rest = after;
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix(format.lines_terminated.as_str()) {
+                rest = after;
+            }
+            break;
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+// The field at the start of `text`, None for NULL, and the text after it.
+fn field<'t>(text: &'t str, format: &Format) -> (Option<String>, &'t str) {
+    let ends = |rest: &str| {
+        rest.is_empty()
+            || rest.starts_with(&format.fields_terminated)
+            || rest.starts_with(&format.lines_terminated)
+    };
+    let enclosed = format.enclosed.filter(|&quote| text.starts_with(quote));
+    let mut rest = match enclosed {
+        Some(quote) => &text[quote.len_utf8()..],
+        None => text,
+    };
+    let mut value = String::new();
+    // \N, read as NULL when it's the whole field.
+    let mut null = false;
+    loop {
+        match enclosed {
+            Some(quote) => {
+                if let Some(after) = rest.strip_prefix(quote) {
+                    if let Some(after) = after.strip_prefix(quote) {
+                        value.push(quote);
+                        rest = after;
+                        continue;
+                    }
+                    if ends(after) {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+            None if ends(rest) => break,
+            None => {}
+        }
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        rest = &rest[c.len_utf8()..];
+        if Some(c) != format.escaped {
+            value.push(c);
+            continue;
+        }
+        let Some(escaped) = rest.chars().next() else {
+            value.push(c);
+            break;
+        };
+        rest = &rest[escaped.len_utf8()..];
+        null = escaped == 'N' && value.is_empty();
+        value.push(match escaped {
+            '0' => '\0',
+            'b' => '\u{8}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'Z' => '\u{1a}',
+            other => other,
+        });
+    }
+    let is_null = match enclosed {
+        Some(_) => false,
+        None => (null && value == "N") || (format.enclosed.is_some() && value == "NULL"),
+    };
+    (if is_null { None } else { Some(value) }, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A SECURE_FILE_PRIV directory holding orders.csv, beside a secret file outside it.
+    fn directory(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("load_data_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("files")).unwrap();
+        fs::write(root.join("files/orders.csv"), "1\t2\n").unwrap();
+        fs::write(root.join("secret"), "").unwrap();
+        root
+    }
+
+    fn kind(result: Result<PathBuf, MysqlError>) -> ErrorKind {
+        result.unwrap_err().kind
+    }
+
+    #[test]
+    fn reads_files_within_secure_file_priv_only() {
+        let root = directory("within");
+        let files = root.join("files");
+        let dir = files.to_str();
+        let orders = files.join("orders.csv").canonicalize().unwrap();
+        assert_eq!(resolve(dir, "orders.csv").unwrap(), orders);
+        assert_eq!(resolve(dir, "./orders.csv").unwrap(), orders);
+        assert_eq!(resolve(dir, orders.to_str().unwrap()).unwrap(), orders);
+        let prevented = ErrorKind::ER_OPTION_PREVENTS_STATEMENT;
+        assert_eq!(kind(resolve(None, "orders.csv")), prevented);
+        assert_eq!(kind(resolve(dir, "../secret")), prevented);
+        assert_eq!(kind(resolve(dir, "../missing")), prevented);
+        assert_eq!(
+            kind(resolve(dir, root.join("secret").to_str().unwrap())),
+            prevented
+        );
+        assert_eq!(kind(resolve(dir, "/etc/missing")), prevented);
+        assert_eq!(
+            kind(resolve(dir, "missing.csv")),
+            ErrorKind::ER_FILE_NOT_FOUND
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symbolic_links_out_of_secure_file_priv() {
+        let root = directory("links");
+        let files = root.join("files");
+        std::os::unix::fs::symlink(root.join("secret"), files.join("link.csv")).unwrap();
+        assert_eq!(
+            kind(resolve(files.to_str(), "link.csv")),
+            ErrorKind::ER_OPTION_PREVENTS_STATEMENT
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+// The server's log: lines of text by default, or with LOG_FORMAT = json, one JSON object per
+// line for log shippers (Filebeat, the Datadog agent) to pick up as they are.
+//
+// In JSON, each statement a client runs is logged once, as it finishes:
+//
+//   {"time":"2024-03-01T12:00:00.123Z","level":"info","message":"statement",
+//    "connection_id":7,"command":"COM_QUERY","user":"app","database":"shop",
+//    "fingerprint":"5d0b2ac1e6f3a7c4","statement":"SELECT * FROM t WHERE id IN (?, ...)",
+//    "duration_us":812,"rows":3,"error_code":null}
+//
+// `statement` and `fingerprint` are the statement's digest (see digest.rs): the statement with
+// its values left out, and a hash of that. `rows` is the rows returned or affected, null
+// when the statement failed or was answered by the proxy itself, and `error_code` the MySQL error
+// number the client got, if any. duration_us counts from when the command arrived.
+//
+// Everything else the server logs becomes a {"time","level","message"} object, with the
+// connection_id of the connection it is about, if any. The step-by-step lines the text log
+// has for each statement (received, translated, the rows sent) are left out of the JSON log,
+// which has the statement's record instead.
+//
+// Those lines show statements as they are, quoted on one line; with LOG_SQL_FORMAT = pretty,
+// they are laid out over lines of their own instead (see the translator's pretty.rs), for
+// comparing a long statement with its translation:
+//
+//   LOG_SQL_FORMAT    `raw` (the default) or `pretty`
+//   LOG_SQL_KEYWORDS  `upper` (the default), `lower` or `preserve`, the case of keywords
+//   LOG_SQL_COLOR     `auto` (the default), `always` or `never`: keywords, strings and comments
+//                     in colour, with ANSI escapes; `auto` when the log goes to a terminal and
+//                     NO_COLOR isn't set
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::digest::Digest;
+use crate::translator::pretty::{self, Layout};
+
+/// How the server logs (LOG_FORMAT).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<LogFormat, ()> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How statements are shown in the text log's lines (LOG_SQL_FORMAT).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SqlLogFormat {
+    #[default]
+    Raw,
+    Pretty(Layout),
+}
+
+/// `sql` as a line of the text log shows it.
+pub(crate) struct ShowSql<'a> {
+    sql: &'a str,
+    format: SqlLogFormat,
+}
+
+impl fmt::Display for ShowSql<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            SqlLogFormat::Raw => write!(f, "{:?}", self.sql),
+            SqlLogFormat::Pretty(layout) => {
+                for line in pretty::pretty(self.sql, &layout).lines() {
+                    write!(f, "\n    {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A statement that has finished, for the JSON log.
+pub(crate) struct StatementRecord<'a> {
+    pub command: &'static str,
+    pub user: &'a str,
+    pub database: Option<&'a str>,
+    pub digest: &'a Digest,
+    pub duration: Duration,
+    pub rows: Option<u64>,
+    pub error_code: Option<u16>,
+}
+
+/// Writes the log in the configured format, about a connection or the server as a whole.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Logger {
+    format: LogFormat,
+    sql_format: SqlLogFormat,
+    connection_id: Option<u32>,
+}
+
+impl Logger {
+    pub fn new(format: LogFormat) -> Logger {
+        Logger {
+            format,
+            sql_format: SqlLogFormat::default(),
+            connection_id: None,
+        }
+    }
+
+    /// The same log, showing statements as `sql_format` has it.
+    pub fn sql_format(self, sql_format: SqlLogFormat) -> Logger {
+        Logger { sql_format, ..self }
+    }
+
+    /// The same log, for what happens on connection `connection_id`.
+    pub fn connection(self, connection_id: u32) -> Logger {
+        Logger {
+            connection_id: Some(connection_id),
+            ..self
+        }
+    }
+
+    pub fn info(&self, message: fmt::Arguments) {
+        self.event("info", message, &mut io::stdout().lock());
+    }
+
+    pub fn error(&self, message: fmt::Arguments) {
+        self.event("error", message, &mut io::stderr().lock());
+    }
+
+    /// A step of a statement's handling, only in the text log.
+    pub fn debug(&self, message: fmt::Arguments) {
+        if self.format == LogFormat::Text {
+            println!("{}", message);
+        }
+    }
+
+    /// `sql` to show in a step of its handling.
+    pub fn sql<'a>(&self, sql: &'a str) -> ShowSql<'a> {
+        ShowSql {
+            sql,
+            format: self.sql_format,
+        }
+    }
+
+    /// A statement's record, only in the JSON log.
+    pub fn statement(&self, record: &StatementRecord) {
+        if self.format != LogFormat::Json {
+            return;
+        }
+        let level = if record.error_code.is_some() {
+            "error"
+        } else {
+            "info"
+        };
+        let mut line = self.json_start(level, "statement");
+        line.push_str(",\"command\":");
+        push_string(&mut line, record.command);
+        line.push_str(",\"user\":");
+        push_string(&mut line, record.user);
+        line.push_str(",\"database\":");
+        match record.database {
+            Some(database) => push_string(&mut line, database),
+            None => line.push_str("null"),
+        }
+        line.push_str(",\"fingerprint\":");
+        push_string(&mut line, &record.digest.fingerprint);
+        line.push_str(",\"statement\":");
+        push_string(&mut line, &record.digest.text);
+        line.push_str(&format!(
+            ",\"duration_us\":{},\"rows\":{},\"error_code\":{}}}",
+            record.duration.as_micros(),
+            optional(record.rows),
+            optional(record.error_code)
+        ));
+        println!("{}", line);
+    }
+
+    fn event(&self, level: &str, message: fmt::Arguments, out: &mut impl Write) {
+        let line = match self.format {
+            LogFormat::Text => message.to_string(),
+            LogFormat::Json => {
+                let mut line = self.json_start(level, &message.to_string());
+                line.push('}');
+                line
+            }
+        };
+        // Nowhere to report a log that can't be written.
+        let _ = writeln!(out, "{}", line);
+    }
+
+    // The fields every JSON line starts with, without the closing brace.
+    fn json_start(&self, level: &str, message: &str) -> String {
+        let mut line = String::with_capacity(256);
+        line.push_str("{\"time\":\"");
+        line.push_str(&Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        line.push_str("\",\"level\":");
+        push_string(&mut line, level);
+        line.push_str(",\"message\":");
+        push_string(&mut line, message);
+        if let Some(id) = self.connection_id {
+            line.push_str(&format!(",\"connection_id\":{}", id));
+        }
+        line
+    }
+}
+
+// `value` as a JSON string.
+pub(crate) fn push_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn optional(value: Option<impl fmt::Display>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
@@ -1,320 +1,157 @@
-// Standard I/O module for basic input and output operations.
-use std::io;
-use std::sync::Arc; // For shared ownership of the PostgreSQL client.
+// The postmyrustache binary: the proxy and its subcommands, over the library in lib.rs.
 
-// AsyncWrite trait from tokio, required for asynchronous write operations.
-use tokio::io::AsyncWrite;
-use tokio::net::TcpListener; // TcpListener from tokio for listening to TCP connections.
+use clap::Parser;
+use tokio_postgres::Client;
 
-// Importing necessary components from the opensrv_mysql crate.
-use async_trait::async_trait;
-use opensrv_mysql::*;
-use mysql_common as myc;
-
-// Additional imports for PostgreSQL support and environment variables handling.
+// Additional imports for environment variables handling.
 use dotenv::dotenv;
-use std::env;
-use tokio_postgres::{Client, NoTls};
 
-// Backend struct that will implement the AsyncMysqlShim trait and hold a PostgreSQL client.
-struct Backend {
-    pg_client: Arc<Client>,
+use postmyrustache::rewrite_rules::Rules;
+use postmyrustache::server::Connector;
+use postmyrustache::{
+    check, config, export, import, schema_diff, summary, telemetry, tunnel, verify,
+};
+use postmyrustache::{translate_mysql_to_postgres, Config, ServerBuilder, Translator};
+
+/// A MySQL server that runs its clients' statements on PostgreSQL.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Read the settings from this TOML file as well; the environment overrides it.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 }
 
-#[async_trait]
-impl<W: AsyncWrite + Send + Unpin> AsyncMysqlShim<W> for Backend {
-    type Error = io::Error;
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Run the proxy, which is what happens without a subcommand.
+    Serve,
+    /// Print the PostgreSQL a MySQL statement is translated to, without running it.
+    Translate { sql: String },
+    /// Check the settings and that PostgreSQL can be connected to with them.
+    CheckConfig,
+    /// Report which statements of a dump or migration file translate, without PostgreSQL.
+    Check { file: String },
+    /// Compare the tables of a MySQL schema dump with PostgreSQL's.
+    DiffSchema {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Load a MySQL dump into PostgreSQL.
+    Import {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Write the tables of a PostgreSQL schema out as a MySQL dump.
+    Export {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Compare the rows of tables on a MySQL server with PostgreSQL's.
+    Verify {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Relay the compressed PostgreSQL sessions of proxies with DB_TUNNEL set, next to
+    /// PostgreSQL.
+    Tunnel,
+}
 
-    async fn on_prepare<'a>(
-        &'a mut self,
-        _: &'a str,
-        _info: StatementMetaWriter<'a, W>,
-    ) -> io::Result<()> {
-        todo!()
-    }
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok(); // Load environment variables from .env file.
 
-    async fn on_execute<'a>(
-        &'a mut self,
-        _: u32,
-        _: opensrv_mysql::ParamParser<'a>,
-        _: QueryResultWriter<'a, W>,
-    ) -> io::Result<()> {
-        todo!()
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Subcommand::Serve);
+    // Translating needs none of the PostgreSQL settings.
+    if let Subcommand::Translate { sql } = &command {
+        let options = config::translation_options(cli.config.as_deref())?;
+        let translated = translate_mysql_to_postgres(sql, &options);
+        println!("{}", translated.map_err(|e| e.to_string())?);
+        return Ok(());
     }
-
-    async fn on_close(&mut self, _: u32) {
-        // Clean up resources here, if necessary.
+    if let Subcommand::Check { file } = &command {
+        let options = config::translation_options(cli.config.as_deref())?;
+        let supported = check::run(file, &Translator::with_options(options))?;
+        std::process::exit(if supported { 0 } else { 1 });
     }
+    if let Subcommand::Tunnel = &command {
+        let config = config::tunnel_config(cli.config.as_deref())?;
+        config.runtime.build()?.block_on(tunnel::serve(&config))?;
+        return Ok(());
+    }
+    let config = match &cli.config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::from_env()?,
+    };
+    // The runtime is set up from the configuration, so it can't be made before it is read.
+    config.runtime.build()?.block_on(run(command, config))
+}
 
-    async fn on_query<'a>(
-        &'a mut self,
-        sql: &'a str,
-        results: QueryResultWriter<'a, W>,
-    ) -> io::Result<()> {
-        println!("Received SQL query: {:?}", sql);
-    
-        // Check and handle MySQL-specific system variable queries or other incompatible queries.
-        if sql
-            .trim()
-            .eq_ignore_ascii_case("select @@version_comment limit 1")
-        {
-            println!("Intercepted MySQL-specific query, returning dummy response.");
-            return results.completed(OkResponse::default()).await;
-        } else if sql.trim().starts_with("select $$") {
-            // Intercepting a query that's not compatible with PostgreSQL.
-            println!("Intercepted query with unsupported syntax, returning dummy response.");
-            return results.completed(OkResponse::default()).await;
-        } else if sql.trim().eq_ignore_ascii_case("set autocommit=1") {
-            println!("Intercepted MySQL-specific query, returning dummy response.");
-            return results.completed(OkResponse::default()).await;
-        } else if sql.trim().to_lowercase().starts_with("create table") {
-            // Intercepting a MySQL-specific CREATE TABLE query.
-            if sql.contains("INT AUTO_INCREMENT") {
-                println!("Intercepted MySQL-specific query, modifying to PostgreSQL syntax.");
-                let modified_sql = sql.replace("INT AUTO_INCREMENT", "SERIAL");
-                match self.pg_client.execute(&modified_sql, &[]).await {
-                    Ok(_) => {
-                        println!("Table created successfully with modified query.");
-                        return results.completed(OkResponse::default()).await;
-                    },
-                    Err(e) => {
-                        println!("Failed to execute modified query: {:?}", e);
-                        // Handle error...
-                    }
-                }
-            }
-        } else if sql.trim().to_lowercase().starts_with("create database") {
-            // Intercepting a MySQL-specific CREATE DATABASE query.
-            let parts: Vec<&str> = sql.split_whitespace().collect();
-            let db_name_index = parts.iter().position(|&r| r == "database").unwrap_or(0) + 1;
-            let db_name = parts.get(db_name_index).unwrap_or(&"");
-            let db_name = db_name.split_whitespace().next().unwrap_or(""); // Add this line
-            let create_db_query = format!("CREATE DATABASE {}", db_name); 
-            match self.pg_client.execute(&create_db_query, &[]).await {
-                Ok(_) => {
-                    println!("Database {} created successfully.", db_name);
-                    return results.completed(OkResponse::default()).await;
-                },
-                Err(err) => {
-                    if let Some(db_error) = err.as_db_error() {
-                        if db_error.code() == &tokio_postgres::error::SqlState::UNIQUE_VIOLATION {
-                            println!("Database {} already exists.", db_name);
-                        } else {
-                            println!("Failed to execute modified query: {:?}", err);
-                        }
-                    } else {
-                        println!("Failed to execute modified query: {:?}", err);
-                    }
-                    // Handle error...
-                }
-            }
-        } else if sql.trim().to_lowercase().starts_with("create database if not exists") {
-            // Intercepting a MySQL-specific CREATE DATABASE IF NOT EXISTS query.
-            let db_name = sql.trim().split_whitespace().last().unwrap();
-            let check_db_exists = format!("SELECT 1 FROM pg_database WHERE datname = '{}'", db_name);
-            match self.pg_client.execute(&check_db_exists, &[]).await {
-                Ok(_) => {
-                    println!("Database {} already exists, skipping creation.", db_name);
-                    return results.completed(OkResponse::default()).await;
-                },
-                Err(_) => {
-                    // Handle error...
-                }
-            } // Add closing brace here
-        } else if sql.trim().to_lowercase().starts_with("use ") {
-            // Intercepting a MySQL-specific USE DATABASE query.
-            let parts: Vec<&str> = sql.split_whitespace().collect();
-            let db_name = parts.get(1).unwrap_or(&"");
-            let use_db_query = format!("SET search_path TO {}", db_name);
-            match self.pg_client.execute(&use_db_query, &[]).await {
-                Ok(_) => {
-                    println!("Switched to database {} successfully.", db_name);
-                    return results.completed(OkResponse::default()).await;
-                },
-                Err(err) => {
-                    // Handle error...
-                }
-            }
-        } else if sql.trim().to_lowercase().contains("database()") {
-            // Intercepting a query that contains the MySQL-specific `database()` function.
-            let modified_sql = sql.to_lowercase().replace("database()", "current_database()");
-            match self.pg_client.execute(&modified_sql, &[]).await {
-                Ok(_) => {
-                    println!("Query executed successfully.");
-                    return results.completed(OkResponse::default()).await;
-                },
-                Err(err) => {
-                    println!("Error executing query: {:?}", err);
-                    return Err(io::Error::new(io::ErrorKind::Other, "Failed to execute query."));
-                }
-            }
-        } else if sql.trim().eq_ignore_ascii_case("select current_user()") {
-            println!("Intercepted MySQL-specific query, returning dummy response.");
-            let current_user_query = "SELECT CURRENT_USER".to_string(); // Convert &str to String
-            match self.pg_client.execute(&current_user_query, &[]).await {
-                Ok(_) => {
-                    println!("Query executed successfully.");
-                    return results.completed(OkResponse::default()).await;
-                },
-                Err(err) => {
-                    println!("Error executing query: {:?}", err);
-                    return Err(io::Error::new(io::ErrorKind::Other, "Failed to execute query."));
-                }
-            }
-        } 
-        // Rest of the function...
-
-    
-        // Forward other queries to PostgreSQL.
-        match self.pg_client.execute(sql, &[]).await {
-            Ok(row_count) => {
-                println!("Query executed successfully, {} rows affected.", row_count);
-    
-                if sql.trim().to_lowercase().starts_with("select") {
-                    println!("SELECT query was found"); 
-            // Start the resultset response with columns information
-            //let mut row_writer = results.start(&[]).await?;
-            let pg_results_raw = self.pg_client.execute(sql, &[]).await;
-            println!("{:?}", pg_results_raw);
-
-            // Execute the same query against PostgreSQL to get the results
-            let pg_results = self.pg_client.query(sql, &[]).await.map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Error executing query: {:?}", e),
-                )
-            })?;
-
-            println!("result: {:?}", pg_results);
-
-            let mut column_names: Vec<String> = Vec::new();
-            let mut cols: Vec<Column> = Vec::new();
-
+async fn run(command: Subcommand, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let connector = Connector::new(&config)?;
 
-            if let Some(first_row) = pg_results.get(0) {
-                let columns = first_row.columns();
-                column_names = columns.iter().map(|col| col.name().to_string()).collect();
-            
-                // Populate cols vector here, outside of the row iteration loop
-                for column_name in &column_names {
-                    cols.push(Column {
-                        table: String::new(),
-                        column: column_name.to_string(),
-                        coltype: myc::constants::ColumnType::MYSQL_TYPE_LONG,
-                        colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
-                    });
-                }
-            
-                // Iterate over rows and send each row to the MySQL client
-                let mut w = results.start(&cols).await?;
-                for row in &pg_results {
-                    let mut row_values = Vec::new();
-                    for (i, column_name) in column_names.iter().enumerate() {
-                        let column_type = row.columns()[i].type_();
-                        let value = match *column_type {
-                            tokio_postgres::types::Type::INT4 => {
-                                let value: i32 = row.get(i);
-                                myc::Value::Int(value.into())
-                            },
-                            tokio_postgres::types::Type::VARCHAR => {
-                                let value: String = row.get(i);
-                                myc::Value::Bytes(value.into_bytes())
-                            },
-                            tokio_postgres::types::Type::BOOL => {
-                                let value: bool = row.get(i);
-                                myc::Value::Bytes(value.to_string().into_bytes())
-                            },
-                            tokio_postgres::types::Type::FLOAT4 => {
-                                let value: f32 = row.get(i);
-                                myc::Value::Float(value)
-                            },
-                            tokio_postgres::types::Type::FLOAT8 => {
-                                let value: f64 = row.get(i);
-                                myc::Value::Double(value)
-                            },
-                            // Add more match arms for other types as needed
-                            _ => return Err(io::Error::new(io::ErrorKind::Other, "Unsupported type")),
-                        };
-                        println!("Column: '{}', Value being sent: {:?}", column_name, value); // Debugging line
-                        row_values.push(value);
-}
-                    // Write each row separately
-                    w.write_row(row_values).await?;
-                }
-                w.finish().await?;
-            }
-                } else {
-                    // For non-SELECT queries, send response indicating rows affected
-                    let mut response = OkResponse::default();
-                    response.affected_rows = row_count; // Set the actual number of affected rows
-                    results.completed(response).await?;
-                }
-            }
-            Err(e) => {
-                println!("Error executing query: {:?}", e);
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Failed to execute query.",
-                ));
+    // diff-schema and verify check a migration, and import and export load and write dumps,
+    // instead of running the server.
+    match command {
+        Subcommand::DiffSchema { args } => {
+            let client = client(&connector).await?;
+            let same = schema_diff::run(&args, &client).await?;
+            std::process::exit(if same { 0 } else { 1 });
+        }
+        Subcommand::Verify { args } => {
+            let client = client(&connector).await?;
+            let same = verify::run(&args, &client).await?;
+            std::process::exit(if same { 0 } else { 1 });
+        }
+        Subcommand::Import { args } => {
+            let client = client(&connector).await?;
+            let translator = Translator::with_options(config.translation.clone());
+            let complete = import::run(&args, &client, &translator).await?;
+            std::process::exit(if complete { 0 } else { 1 });
+        }
+        Subcommand::Export { args } => {
+            let client = client(&connector).await?;
+            export::run(&args, &client).await?;
+            return Ok(());
+        }
+        Subcommand::CheckConfig => {
+            if let Some(path) = &config.rewrite_rules {
+                Rules::load(path)?;
             }
+            client(&connector).await?;
+            println!(
+                "The configuration is valid: PostgreSQL at {} as {} (sslmode {}), MySQL clients \
+                 on {}",
+                config.db_host, config.db_user, config.tls.mode, config.listen_addr
+            );
+            return Ok(());
         }
+        Subcommand::Serve
+        | Subcommand::Translate { .. }
+        | Subcommand::Check { .. }
+        | Subcommand::Tunnel => {}
+    }
 
-        Ok(())
+    summary::print(&config)?;
+    if let Some(telemetry) = &config.telemetry {
+        telemetry::export(telemetry)?;
     }
+    let server = ServerBuilder::new(config)
+        .upstream(connector)
+        .build()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+
+    server.run().await?;
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok(); // Load environment variables from .env file.
-
-    let db_host = env::var("DB_HOST").expect("DB_HOST must be set");
-    let db_user = env::var("DB_USER").expect("DB_USER must be set");
-    let db_password = env::var("DB_PASSWORD").expect("DB_PASSWORD must be set");
-
-    let connection_string = format!("host={} user={} password={}", db_host, db_user, db_password);
-
-    // Connect to PostgreSQL database.
-    let (pg_client, connection) = tokio_postgres::connect(&connection_string, NoTls).await?;
-
-    // The connection object performs the communication with the database, so spawn it off to run on its own.
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-
-    let pg_client = Arc::new(pg_client); // Wrap the client in an Arc for shared ownership.
-    let listener = TcpListener::bind("0.0.0.0:3306").await?;
-
-    println!(
-        r#"
-________             ___________  ___       ________              _____             ______      ______
-___  __ \______________  /___   |/  /____  ____  __ \___  __________  /______ _________  /_________  /
-__  /_/ /  __ \_  ___/  __/_  /|_/ /__  / / /_  /_/ /  / / /_  ___/  __/  __ `/  ___/_  __ \  _ \_  /
-_  ____// /_/ /(__  )/ /_ _  /  / / _  /_/ /_  _, _// /_/ /_(__  )/ /_ / /_/ // /__ _  / / /  __//_/
-/_/     \____//____/ \__/ /_/  /_/  _\__, / /_/ |_| \__,_/ /____/ \__/ \__,_/ \___/ /_/ /_/\___/(_)
-                                    /____/
-"#
-    );
-
-    println!("MySQL server is running on port 3306");
-
-    loop {
-        let (stream, _) = listener.accept().await?;
-        let (r, w) = stream.into_split();
-        let pg_client_clone = Arc::clone(&pg_client); // Clone the Arc, not the Client.
-        tokio::spawn(async move {
-            if let Err(e) = AsyncMysqlIntermediary::run_on(
-                Backend {
-                    pg_client: pg_client_clone,
-                },
-                r,
-                w,
-            )
-            .await
-            {
-                eprintln!("Error: {}", e);
-            }
-        });
-    }
+// The connector's errors are Send and Sync, for the server's tasks; the subcommands' needn't be.
+async fn client(connector: &Connector) -> Result<Client, Box<dyn std::error::Error>> {
+    connector
+        .client()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)
 }
@@ -0,0 +1,191 @@
+// The few translated statements Backend::query answers or adjusts itself before forwarding
+// them: client start-up chatter with no PostgreSQL counterpart, CREATE DATABASE, DROP DATABASE
+// and USE, which work on the schemas MySQL databases are, the
+// DATABASE() and CURRENT_USER() calls PostgreSQL spells differently, and CONNECTION_ID(), which
+// is the proxy's id for the connection rather than anything PostgreSQL knows. It also finds the database
+// a CREATE TABLE and the like creates its object in, for AUTO_CREATE_DATABASES.
+//
+// Statements are matched on their tokens, keywords by whole word, so the same words inside a
+// string literal, a quoted identifier or a comment never make a data query look like one of
+// these:
+//
+//   SELECT 'database()' AS x    ->  forwarded unchanged
+//   SELECT DATABASE(), a FROM t ->  SELECT current_schema(), a FROM t
+//   SELECT CONNECTION_ID()      ->  SELECT 7
+
+use crate::translator::{self, literals, Node, Token};
+
+/// What Backend::query does with a statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Specific {
+    // Answered with an OK packet and nothing run: SELECT @@version_comment LIMIT 1, which the
+    // mysql client sends on start-up, SET autocommit = 1 and SELECT $$.
+    Ignored,
+    CreateDatabase { name: String, if_not_exists: bool },
+    DropDatabase { name: String, if_exists: bool },
+    Use(String),
+    // The statement with its function calls respelled, to forward in its place.
+    Rewritten(String),
+}
+
+/// What to do with `sql`, a translated statement of connection `connection_id`, if it isn't
+/// simply forwarded.
+pub fn classify(sql: &str, connection_id: u32) -> Option<Specific> {
+    let tokens = translator::significant_tokens(sql)?;
+    let words = |expected: &[&str]| {
+        tokens.len() >= expected.len()
+            && tokens
+                .iter()
+                .zip(expected)
+                .all(|(token, word)| token.is_word(word))
+    };
+
+    match &tokens[..] {
+        [select, Token::Variable(variable), limit, Token::Number(one)]
+            if select.is_word("SELECT")
+                && variable.eq_ignore_ascii_case("@@version_comment")
+                && limit.is_word("LIMIT")
+                && one == "1" =>
+        {
+            return Some(Specific::Ignored)
+        }
+        [set, autocommit, equals, Token::Number(one)]
+            if set.is_word("SET")
+                && autocommit.is_word("autocommit")
+                && equals.is_operator("=")
+                && one == "1" =>
+        {
+            return Some(Specific::Ignored)
+        }
+        [select, Token::Word(dollars), ..]
+            if select.is_word("SELECT") && dollars.starts_with("$$") =>
+        {
+            return Some(Specific::Ignored)
+        }
+        _ => {}
+    }
+
+    // SCHEMA is MySQL's synonym for DATABASE.
+    for database in ["DATABASE", "SCHEMA"] {
+        if words(&["CREATE", database, "IF", "NOT", "EXISTS"]) {
+            return Some(Specific::CreateDatabase {
+                name: single_name(&tokens[5..])?,
+                if_not_exists: true,
+            });
+        }
+        if words(&["CREATE", database]) {
+            return Some(Specific::CreateDatabase {
+                name: single_name(&tokens[2..])?,
+                if_not_exists: false,
+            });
+        }
+        if words(&["DROP", database, "IF", "EXISTS"]) {
+            return Some(Specific::DropDatabase {
+                name: single_name(&tokens[4..])?,
+                if_exists: true,
+            });
+        }
+        if words(&["DROP", database]) {
+            return Some(Specific::DropDatabase {
+                name: single_name(&tokens[2..])?,
+                if_exists: false,
+            });
+        }
+    }
+    if words(&["USE"]) {
+        return Some(Specific::Use(single_name(&tokens[1..])?));
+    }
+
+    let nodes = translator::parse(sql).ok()?;
+    let (rewritten, changed) = respell_functions(nodes, connection_id);
+    changed.then(|| Specific::Rewritten(translator::render(&rewritten)))
+}
+
+/// The database a translated CREATE TABLE, VIEW, SEQUENCE, FUNCTION or PROCEDURE puts its object
+/// in, if it names one: `shop` for CREATE TABLE shop.orders (...).
+pub fn created_in(sql: &str) -> Option<String> {
+    let tokens = translator::significant_tokens(sql)?;
+    let (create, mut rest) = tokens.split_first()?;
+    if !create.is_word("CREATE") {
+        return None;
+    }
+    for words in [&["OR", "REPLACE"][..], &["TEMPORARY"], &["TEMP"]] {
+        if rest.len() > words.len() && rest.iter().zip(words).all(|(t, w)| t.is_word(w)) {
+            rest = &rest[words.len()..];
+        }
+    }
+    let (kind, mut rest) = rest.split_first()?;
+    if !["TABLE", "VIEW", "SEQUENCE", "FUNCTION", "PROCEDURE"]
+        .iter()
+        .any(|word| kind.is_word(word))
+    {
+        return None;
+    }
+    let words = ["IF", "NOT", "EXISTS"];
+    if rest.len() > words.len() && rest.iter().zip(words).all(|(t, w)| t.is_word(w)) {
+        rest = &rest[words.len()..];
+    }
+    match rest {
+        [schema, dot, _, ..] if dot.is_operator(".") => single_name(std::slice::from_ref(schema)),
+        _ => None,
+    }
+}
+
+// The name `tokens` consist of, as PostgreSQL folds it. The translator has turned backticks
+// into double quotes by now.
+fn single_name(tokens: &[Token]) -> Option<String> {
+    match tokens {
+        [Token::DoubleQuoted(raw)] => {
+            Some(raw[1..raw.len() - 1].replace("\"\"", "\"").to_lowercase())
+        }
+        [token] => literals::identifier_name(token),
+        _ => None,
+    }
+}
+
+// DATABASE() as current_schema(), the schema USE made current, CURRENT_USER() as CURRENT_USER,
+// which PostgreSQL only takes without parentheses, and CONNECTION_ID() as `connection_id`,
+// wherever they are called.
+// Also says whether there were any.
+fn respell_functions(nodes: Vec<Node>, connection_id: u32) -> (Vec<Node>, bool) {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut changed = false;
+    let mut nodes = nodes.into_iter().peekable();
+    while let Some(node) = nodes.next() {
+        let node = match node {
+            Node::Group(inner) => {
+                let (inner, inner_changed) = respell_functions(inner, connection_id);
+                changed |= inner_changed;
+                Node::Group(inner)
+            }
+            token => token,
+        };
+        let empty_call =
+            matches!(nodes.peek(), Some(Node::Group(args)) if args.iter().all(Node::is_trivia));
+        // A column or method named like the function, as in t.database(), is left alone.
+        let qualified = matches!(
+            out.iter().rev().find(|n| !n.is_trivia()),
+            Some(Node::Token(t)) if t.is_operator(".")
+        );
+        match &node {
+            Node::Token(t) if empty_call && !qualified && t.is_word("DATABASE") => {
+                nodes.next();
+                out.push(Node::Token(Token::Word("current_schema".to_string())));
+                out.push(Node::Group(Vec::new()));
+                changed = true;
+            }
+            Node::Token(t) if empty_call && !qualified && t.is_word("CURRENT_USER") => {
+                nodes.next();
+                out.push(Node::Token(Token::Word("CURRENT_USER".to_string())));
+                changed = true;
+            }
+            Node::Token(t) if empty_call && !qualified && t.is_word("CONNECTION_ID") => {
+                nodes.next();
+                out.push(Node::Token(Token::Number(connection_id.to_string())));
+                changed = true;
+            }
+            _ => out.push(node),
+        }
+    }
+    (out, changed)
+}
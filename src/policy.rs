@@ -0,0 +1,1851 @@
+// The statement policy: which statements the clients may run, by the class of statement, checked
+// before the proxy answers or translates them.
+//
+// The classes are:
+//
+//   select  SELECT, SHOW, DESCRIBE, EXPLAIN and other reads
+//   dml     INSERT, UPDATE, DELETE, REPLACE, LOAD DATA and CALL
+//   ddl     CREATE, ALTER, DROP, TRUNCATE and RENAME, and GRANT, REVOKE and SET PASSWORD
+//   admin   KILL, FLUSH, SET GLOBAL, ANALYZE, OPTIMIZE and the like
+//
+// PostgreSQL's own statements are classed as well: MERGE and COPY are dml, COMMENT and REFRESH
+// ddl, VACUUM, CLUSTER and REINDEX admin. A SELECT ... INTO a table, rather than into variables
+// or a file, is ddl, since PostgreSQL creates the table.
+//
+// Statements that only touch the session (SET, USE, BEGIN, COMMIT, LOCK TABLES...) are of no
+// class, and always allowed. A WITH is dml if its statement is, and so is an EXPLAIN of one,
+// since EXPLAIN ANALYZE runs it. Any other statement, PREPARE and EXECUTE among them, is of a
+// class that can't be told, and refused if any restriction applies to the user.
+//
+// With READ_ONLY set, dml and ddl are refused, the way MySQL refuses them on a read_only
+// server, with error 1290, and so are OPTIMIZE, REPAIR and the statements of PostgreSQL that
+// rewrite tables, calls of setval(), nextval() and the large object functions, NEXT VALUE FOR a
+// sequence, a WITH whose CTE writes, SELECT ... FOR UPDATE or FOR SHARE, and what would make the
+// session's transactions, or the server, read-write: START TRANSACTION READ WRITE, setting or
+// resetting default_transaction_read_only, transaction_read_only or read_only. The sessions of the proxy
+// are made read-only as they start as well (see Backend::session_sql), for whatever gets past
+// the classes. ALLOWED_STATEMENTS, a comma-separated list of classes, restricts every user to
+// those, and USER_ALLOWED_STATEMENTS restricts some users, overriding it for them:
+//
+//   USER_ALLOWED_STATEMENTS = "report:select; etl:select,dml"
+//
+// A statement of a class the user isn't allowed is refused with error 1142, as MySQL refuses a
+// statement the user has no privilege for, or 1227 for an admin statement, as MySQL refuses it
+// without the SUPER privilege. A statement that can't be tokenized is refused too if any
+// restriction applies to it, its class being unknown, and so is a compound statement, `BEGIN ...
+// END`, whose statements aren't classed one by one. Only users allowed `admin` by name may KILL
+// the connections of other users, as only MySQL accounts with CONNECTION_ADMIN may.
+//
+// USER_GRANTS limits some users to the tables they are granted, read-only or to write as well,
+// by database or one table at a time:
+//
+//   USER_GRANTS = "app: shop.*=write, logs.*=read; report: shop.orders=read, shop.customers"
+//
+// A grant is `read` unless it says otherwise. A table is read by the SELECTs, subqueries and
+// joins, in parentheses or not, that name it, in statements of no class such as SET too, by
+// TABLE t, DELETE ... USING t, LOCK TABLES t READ and EXPLAIN or DESCRIBE, by the query of a
+// view or CREATE TABLE ... AS SELECT, by CREATE TABLE ... LIKE t and REFERENCES t, and by the
+// SHOW CREATE TABLE, SHOW COLUMNS and SHOW INDEX of it, and written by the INSERT, UPDATE,
+// DELETE, TRUNCATE, LOCK TABLES ... WRITE, CREATE, ALTER, DROP or RENAME that names it, and by
+// the ALTER TABLE ... RENAME TO or SET SCHEMA that moves a table to it. Unqualified, it is in the
+// current database, unless its name starts with pg_: PostgreSQL looks those up in pg_catalog
+// first. A statement whose tables can't be told is refused. Quoted names are read as the
+// session's sql_mode quotes them, "double quoted" ones with ANSI_QUOTES. The
+// table refused is named in error 1142, or the database in error 1044 for CREATE DATABASE and
+// DROP DATABASE, which need the whole of it, and for SHOW TABLES, TABLE STATUS, TRIGGERS, EVENTS
+// and CREATE DATABASE, which need a grant in it. Users without grants may use every table, and so may every user read
+// information_schema. Other DDL (GRANT, CREATE TRIGGER...) needs a write grant on `*.*`, as
+// which tables it touches can't be told. COM_FIELD_LIST needs what a SELECT of the table does.
+// SHOW GRANTS lists a user's grants the way MySQL prints them.
+//
+// Functions that have PostgreSQL run a query given as a string, query_to_xml('SELECT ...') and
+// the like (RUNS_TEXT), and DO blocks are refused to users with grants, with error 1370, since
+// the tables they read can't be told. Routines already created run as they were written, so
+// grants only go as far as the functions the proxy's PostgreSQL role may call: a user that must
+// never see a table should have the proxy connect as a role without access to it.
+//
+// SET ROLE and SET SESSION AUTHORIZATION are admin statements, as is RESET. For a user whose
+// PostgreSQL role an auth provider gave (see auth/mod.rs), the proxy refuses whatever could
+// change or reset it, whatever the policy (see changes_role), with error 1227: functions and
+// procedures that set it as they are called, or whose bodies reset it or run SQL from text, as
+// well. The sessions still log in as DB_USER, who may always take its own role back, so routines
+// already created run as they were written here too.
+//
+// The policy is a QueryInterceptor, the last of them, so it sees the statements as the rewrite
+// rules and the embedding program's interceptors left them.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use opensrv_mysql::ErrorKind;
+
+use crate::catalog::ObjectName;
+use crate::emulation::object_name;
+use crate::error::MysqlError;
+use crate::intercept::{Context, QueryInterceptor};
+use crate::stats::{closing, table_access};
+use crate::translator::{self, literals, Token};
+use crate::TranslationOptions;
+
+// PostgreSQL's functions that run a query, or read a table, named in a string.
+const RUNS_TEXT: &[&str] = &[
+    "query_to_xml",
+    "query_to_xmlschema",
+    "query_to_xml_and_xmlschema",
+    "table_to_xml",
+    "table_to_xmlschema",
+    "table_to_xml_and_xmlschema",
+    "schema_to_xml",
+    "schema_to_xmlschema",
+    "schema_to_xml_and_xmlschema",
+    "database_to_xml",
+    "database_to_xmlschema",
+    "database_to_xml_and_xmlschema",
+    "cursor_to_xml",
+    "ts_stat",
+    "ts_rewrite",
+    "dblink",
+    "dblink_exec",
+    "dblink_open",
+    "dblink_send_query",
+];
+
+// PostgreSQL's functions that write, refused in READ_ONLY mode.
+const WRITES: &[&str] = &[
+    "setval",
+    "nextval",
+    "lo_import",
+    "lo_export",
+    "lo_create",
+    "lo_creat",
+    "lo_unlink",
+    "lo_put",
+    "lo_from_bytea",
+    // On a session of its own, which isn't read-only.
+    "dblink",
+    "dblink_exec",
+    "dblink_send_query",
+];
+
+// Admin statements that rewrite tables, refused in READ_ONLY mode.
+const REWRITES_TABLES: &[&str] = &["OPTIMIZE", "REPAIR", "VACUUM", "CLUSTER", "REINDEX"];
+
+// The settings that make the session's transactions, or the server, read-only.
+const READ_ONLY_SETTINGS: &[&str] = &[
+    "read_only",
+    "super_read_only",
+    "default_transaction_read_only",
+    "transaction_read_only",
+    "tx_read_only",
+    // SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE, SESSION being skipped.
+    "characteristics",
+];
+
+// The statements of no class, that only touch the session.
+const SESSION_STATEMENTS: &[&str] = &[
+    "SET",
+    "USE",
+    "BEGIN",
+    "START",
+    "COMMIT",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "RELEASE",
+    "LOCK",
+    "UNLOCK",
+    "XA",
+    "DECLARE",
+    "OPEN",
+    "FETCH",
+    "CLOSE",
+    "DEALLOCATE",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementClass {
+    Select,
+    Dml,
+    Ddl,
+    Admin,
+}
+
+impl FromStr for StatementClass {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<StatementClass, ()> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "select" => Ok(StatementClass::Select),
+            "dml" => Ok(StatementClass::Dml),
+            "ddl" => Ok(StatementClass::Ddl),
+            "admin" => Ok(StatementClass::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Access to the tables of a database, or of every database: `shop.*=write`, `shop.orders`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    // The database, every one when None.
+    pub schema: Option<String>,
+    // The table, every one when None.
+    pub table: Option<String>,
+    // Whether the tables may be written as well as read.
+    pub write: bool,
+}
+
+impl Grant {
+    fn covers(&self, schema: Option<&str>, table: Option<&str>) -> bool {
+        let matches = |granted: &Option<String>, name: Option<&str>| match granted {
+            None => true,
+            Some(granted) => name == Some(granted.as_str()),
+        };
+        matches(&self.schema, schema) && matches(&self.table, table)
+    }
+
+    /// The GRANT statement MySQL would show for this grant to `user`.
+    pub fn statement(&self, user: &str) -> String {
+        let privileges = match self.write {
+            true => "SELECT, INSERT, UPDATE, DELETE, CREATE, DROP, ALTER",
+            false => "SELECT",
+        };
+        let name = |name: &Option<String>| match name {
+            Some(name) => format!("`{}`", name.replace('`', "``")),
+            None => "*".to_string(),
+        };
+        format!(
+            "GRANT {} ON {}.{} TO `{}`@`%`",
+            privileges,
+            name(&self.schema),
+            name(&self.table),
+            user.replace('`', "``")
+        )
+    }
+}
+
+impl FromStr for Grant {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Grant, ()> {
+        let (object, access) = s.split_once('=').unwrap_or((s, "read"));
+        let write = match access.trim().to_ascii_lowercase().as_str() {
+            "read" => false,
+            "write" => true,
+            _ => return Err(()),
+        };
+        let (schema, table) = object.split_once('.').ok_or(())?;
+        let name = |name: &str| match name.trim() {
+            "*" => Ok(None),
+            "" => Err(()),
+            name => Ok(Some(name.trim_matches('`').to_lowercase())),
+        };
+        let (schema, table) = (name(schema)?, name(table)?);
+        // Every database's table of some name isn't a grant MySQL has.
+        if schema.is_none() && table.is_some() {
+            return Err(());
+        }
+        Ok(Grant {
+            schema,
+            table,
+            write,
+        })
+    }
+}
+
+/// Which statements may run (READ_ONLY, ALLOWED_STATEMENTS, USER_ALLOWED_STATEMENTS,
+/// USER_GRANTS).
+#[derive(Debug, Clone, Default)]
+pub struct PolicyConfig {
+    pub read_only: bool,
+    // The classes every user may run, all of them when None.
+    pub allowed: Option<Vec<StatementClass>>,
+    // The classes some users may run, in place of `allowed`.
+    pub users: Vec<(String, Vec<StatementClass>)>,
+    // The tables some users may use; the others may use every table.
+    pub grants: Vec<(String, Vec<Grant>)>,
+}
+
+impl PolicyConfig {
+    /// Whether any statement could be refused.
+    pub fn restricts(&self) -> bool {
+        self.read_only
+            || self.allowed.is_some()
+            || !self.users.is_empty()
+            || !self.grants.is_empty()
+    }
+
+    // Whether any statement of `user` could be refused.
+    fn restricts_user(&self, user: &str) -> bool {
+        self.read_only || self.allowed(user).is_some() || self.grants(user).is_some()
+    }
+
+    /// The tables `user` may use, None for all of them.
+    pub fn grants(&self, user: &str) -> Option<&[Grant]> {
+        self.grants
+            .iter()
+            .find(|(name, _)| name == user)
+            .map(|(_, grants)| grants.as_slice())
+    }
+
+    /// Whether `user` is allowed admin statements by name, in ALLOWED_STATEMENTS or
+    /// USER_ALLOWED_STATEMENTS, and so may KILL other users' connections.
+    pub fn admin(&self, user: &str) -> bool {
+        self.allowed(user)
+            .is_some_and(|classes| classes.contains(&StatementClass::Admin))
+    }
+
+    // The classes `user` may run, None for all of them.
+    fn allowed(&self, user: &str) -> Option<&[StatementClass]> {
+        match self.users.iter().find(|(name, _)| name == user) {
+            Some((_, classes)) => Some(classes),
+            None => self.allowed.as_deref(),
+        }
+    }
+
+    /// Refuses `sql`, a statement or several, if the policy doesn't allow them to the user of
+    /// `context`.
+    pub(crate) fn check(&self, context: &Context, sql: &str) -> Result<(), MysqlError> {
+        let allowed = self.allowed(context.user);
+        let tokens = translator::significant_tokens_with(sql, context.options)
+            .filter(|tokens| !translator::is_compound_statement(tokens));
+        let Some(tokens) = tokens else {
+            return match (self.read_only, self.restricts_user(context.user)) {
+                (_, false) => Ok(()),
+                (true, _) => Err(read_only()),
+                (false, true) => Err(denied(context, "UNKNOWN", "")),
+            };
+        };
+        // Every statement of a multi-statement query.
+        for statement in tokens.split(|token| *token == Token::Semicolon) {
+            let grants = self.grants(context.user);
+            if grants.is_some() {
+                check_runs_text(context, statement)?;
+            }
+            if self.read_only && writes_read_only(statement) {
+                return Err(read_only());
+            }
+            let Some((class, command)) = classify(statement) else {
+                if !is_session_statement(statement) && self.restricts_user(context.user) {
+                    return Err(match (self.read_only, statement.first()) {
+                        (true, _) => read_only(),
+                        (false, Some(Token::Word(word))) => {
+                            denied(context, &word.to_uppercase(), "")
+                        }
+                        (false, _) => denied(context, "UNKNOWN", ""),
+                    });
+                }
+                // SET @v = (SELECT ...), DECLARE ... CURSOR FOR SELECT and LOCK TABLES use tables
+                // too.
+                if let Some(grants) = grants {
+                    let locks = statement.first().is_some_and(|t| t.is_word("LOCK"));
+                    let used = match locks {
+                        true => table_access(statement),
+                        false => queries_read(statement),
+                    };
+                    let verb = if locks { "LOCK TABLES" } else { "SELECT" };
+                    let used = used.ok_or_else(|| denied(context, verb, ""))?;
+                    check_tables(context, grants, used, verb)?;
+                }
+                continue;
+            };
+            if self.read_only
+                && (matches!(class, StatementClass::Dml | StatementClass::Ddl)
+                    || REWRITES_TABLES
+                        .iter()
+                        .any(|word| command.first().is_some_and(|t| t.is_word(word))))
+            {
+                return Err(read_only());
+            }
+            if allowed.is_some_and(|allowed| !allowed.contains(&class)) {
+                return Err(match (class, command.first()) {
+                    (StatementClass::Admin, _) => super_denied(),
+                    (_, Some(Token::Word(word))) => {
+                        denied(context, &word.to_uppercase(), &table(command))
+                    }
+                    _ => denied(context, "SELECT", ""),
+                });
+            }
+            if let Some(grants) = grants {
+                check_grants(context, grants, statement, class, command)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses COM_FIELD_LIST on `table` to a user who couldn't SELECT from it.
+    pub(crate) fn check_field_list(
+        &self,
+        context: &Context,
+        table: &str,
+    ) -> Result<(), MysqlError> {
+        self.check(
+            context,
+            &format!("SELECT * FROM `{}`", table.replace('`', "``")),
+        )
+    }
+}
+
+/// Refuses the statements the configured policy doesn't allow.
+pub(crate) struct Policy {
+    config: PolicyConfig,
+}
+
+impl Policy {
+    pub fn new(config: PolicyConfig) -> Policy {
+        Policy { config }
+    }
+}
+
+// Refuses a statement using a table `grants` don't cover.
+fn check_grants(
+    context: &Context,
+    grants: &[Grant],
+    statement: &[Token],
+    class: StatementClass,
+    command: &[Token],
+) -> Result<(), MysqlError> {
+    let verb = match command.first() {
+        // MySQL checks LOAD DATA's INSERT privilege.
+        Some(Token::Word(word)) if word.eq_ignore_ascii_case("LOAD") => "INSERT".to_string(),
+        Some(Token::Word(word)) => word.to_uppercase(),
+        _ => "SELECT".to_string(),
+    };
+    // Tables that can't be told are refused.
+    let mut used = table_access(statement).ok_or_else(|| denied(context, &verb, ""))?;
+    if command.len() < statement.len() {
+        // The statement a WITH or EXPLAIN writes with.
+        used.extend(table_access(command).ok_or_else(|| denied(context, &verb, ""))?);
+    } else if let Some((first, rest)) = statement.split_first() {
+        if first.is_word("SHOW") {
+            match shown(rest) {
+                Some(Shown::Table(table)) => used.push((table, false)),
+                Some(Shown::Database(schema)) => {
+                    if let Some(schema) = schema.as_deref().or(context.database) {
+                        check_database(context, grants, schema)?;
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    // table_access finds the tables of DML and TRUNCATE; the rest are found here.
+    let found = ["INSERT", "REPLACE", "UPDATE", "DELETE", "TRUNCATE"]
+        .iter()
+        .any(|word| command.first().is_some_and(|t| t.is_word(word)));
+    if matches!(class, StatementClass::Dml | StatementClass::Ddl) && !found {
+        match altered(command) {
+            Some(Altered::Tables(tables)) => used.extend(tables),
+            Some(Altered::Database(schema)) => {
+                if !grants
+                    .iter()
+                    .any(|g| g.write && g.covers(Some(&schema), None))
+                {
+                    return Err(database_denied(context, &schema));
+                }
+            }
+            None => {
+                if !grants.iter().any(|g| g.write && g.covers(None, None)) {
+                    return Err(denied(context, &verb, ""));
+                }
+            }
+        }
+    }
+
+    check_tables(context, grants, used, &verb)
+}
+
+// Refuses the use of a table `grants` don't cover, by `verb` if it's written.
+fn check_tables(
+    context: &Context,
+    grants: &[Grant],
+    used: Vec<(ObjectName, bool)>,
+    verb: &str,
+) -> Result<(), MysqlError> {
+    for (table, write) in used {
+        // PostgreSQL looks an unqualified name up in pg_catalog before the schemas of its
+        // search_path, and the names of its catalogs all start with pg_.
+        let schema = match table.schema.as_deref() {
+            None if table.name.starts_with("pg_") => Some("pg_catalog"),
+            schema => schema.or(context.database),
+        };
+        if !write && schema == Some("information_schema") {
+            continue;
+        }
+        let granted = grants
+            .iter()
+            .any(|g| (g.write || !write) && g.covers(schema, Some(&table.name)));
+        if !granted {
+            let verb = if write { verb } else { "SELECT" };
+            return Err(denied(context, verb, &table.name));
+        }
+    }
+    Ok(())
+}
+
+// Refuses to show what database `schema` has to a user none of whose `grants` is in it.
+fn check_database(context: &Context, grants: &[Grant], schema: &str) -> Result<(), MysqlError> {
+    let granted = grants.iter().any(|g| match &g.schema {
+        Some(granted) => granted == schema,
+        None => true,
+    });
+    match granted || schema == "information_schema" {
+        true => Ok(()),
+        false => Err(database_denied(context, schema)),
+    }
+}
+
+// What a SHOW shows of the tables.
+enum Shown {
+    // A table's definition, columns or indexes.
+    Table(ObjectName),
+    // The tables, triggers or events of a database, or its definition; the current one if None.
+    Database(Option<String>),
+}
+
+// What a SHOW, `rest` the tokens after it, shows of the tables: the table of SHOW CREATE TABLE
+// or VIEW and of SHOW COLUMNS, FIELDS, INDEX or KEYS, in the database a FROM or IN after it
+// names; or the database of SHOW CREATE DATABASE, and that SHOW TABLES, TABLE STATUS, TRIGGERS
+// or EVENTS list the tables of. None for the other SHOWs.
+fn shown(rest: &[Token]) -> Option<Shown> {
+    let database = |tokens: &[Token]| match tokens {
+        [from, name, ..] if from.is_word("FROM") || from.is_word("IN") => {
+            literals::identifier_name(name)
+        }
+        _ => None,
+    };
+    let skipped = rest
+        .iter()
+        .take_while(|t| t.is_word("FULL") || t.is_word("EXTENDED"))
+        .count();
+    let (kind, rest) = rest[skipped..].split_first()?;
+    if kind.is_word("CREATE") {
+        let (what, rest) = rest.split_first()?;
+        if what.is_word("TABLE") || what.is_word("VIEW") {
+            return object_name(rest).map(|(table, _)| Shown::Table(table));
+        }
+        if what.is_word("DATABASE") || what.is_word("SCHEMA") {
+            let skipped = rest
+                .iter()
+                .take_while(|t| ["IF", "NOT", "EXISTS"].iter().any(|w| t.is_word(w)))
+                .count();
+            let schema = literals::identifier_name(rest.get(skipped)?)?;
+            return Some(Shown::Database(Some(schema)));
+        }
+        return None;
+    }
+    if ["COLUMNS", "FIELDS", "INDEX", "INDEXES", "KEYS"]
+        .iter()
+        .any(|word| kind.is_word(word))
+    {
+        let (from, rest) = rest.split_first()?;
+        if !from.is_word("FROM") && !from.is_word("IN") {
+            return None;
+        }
+        let (mut table, after) = object_name(rest)?;
+        if let Some(schema) = database(after) {
+            table.schema = Some(schema);
+        }
+        return Some(Shown::Table(table));
+    }
+    let rest = match kind.is_word("TABLE") {
+        // SHOW TABLE STATUS.
+        true => rest.get(1..).filter(|_| rest[0].is_word("STATUS"))?,
+        false
+            if ["TABLES", "TRIGGERS", "EVENTS"]
+                .iter()
+                .any(|word| kind.is_word(word)) =>
+        {
+            rest
+        }
+        false => return None,
+    };
+    Some(Shown::Database(database(rest)))
+}
+
+// Whether a statement is a PostgreSQL DO block, `DO [LANGUAGE name] 'code'`, the code most often
+// dollar-quoted: DO $$ ... $$. MySQL's DO takes expressions.
+fn is_do_block(statement: &[Token]) -> bool {
+    statement.first().is_some_and(|t| t.is_word("DO"))
+        && match statement.get(1) {
+            Some(Token::Word(word)) => {
+                word.starts_with('$') || word.eq_ignore_ascii_case("LANGUAGE")
+            }
+            Some(Token::String(_)) => true,
+            _ => false,
+        }
+}
+
+// Refuses a statement that has PostgreSQL run SQL, or read a table, it is given as text, where
+// the tables it uses can't be told: a call to one of RUNS_TEXT, or a DO block.
+fn check_runs_text(context: &Context, statement: &[Token]) -> Result<(), MysqlError> {
+    if is_do_block(statement) {
+        return Err(routine_denied(context, "DO"));
+    }
+    match calls(statement, RUNS_TEXT) {
+        Some(name) => Err(routine_denied(context, &name)),
+        None => Ok(()),
+    }
+}
+
+// The tables the queries of a statement of no class read: each (SELECT ...) in it, and the
+// SELECT that ends it, DECLARE ... CURSOR FOR SELECT's. None if they can't be told.
+fn queries_read(statement: &[Token]) -> Option<Vec<(ObjectName, bool)>> {
+    let mut used = Vec::new();
+    let mut rest = statement;
+    while let Some(select) = rest
+        .iter()
+        .position(|t| t.is_word("SELECT") || t.is_word("WITH"))
+    {
+        let query = &rest[select..];
+        let end = match select.checked_sub(1).map(|before| &rest[before]) {
+            // Up to the parenthesis closing the one before it.
+            Some(Token::LParen) => {
+                let mut depth = 1usize;
+                query.iter().position(|token| {
+                    match token {
+                        Token::LParen => depth += 1,
+                        Token::RParen => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })?
+            }
+            _ => query.len(),
+        };
+        used.extend(table_access(&query[..end])?);
+        rest = &query[end..];
+    }
+    Some(used)
+}
+
+// The first of `functions` a statement calls, if it calls one.
+fn calls(statement: &[Token], functions: &[&str]) -> Option<String> {
+    statement.windows(2).find_map(|pair| {
+        identifier(&pair[0]).filter(|name| {
+            pair[1] == Token::LParen && functions.iter().any(|f| name.eq_ignore_ascii_case(f))
+        })
+    })
+}
+
+// What a DDL statement creates, changes or drops.
+enum Altered {
+    // Each with whether it is written: a CREATE TABLE ... AS SELECT reads the tables of its
+    // query.
+    Tables(Vec<(ObjectName, bool)>),
+    Database(String),
+}
+
+// The tables a CREATE, ALTER, DROP or RENAME of tables, views or indexes or a LOAD DATA changes,
+// and those a CREATE or ALTER of a table or view reads (see defined), or the database of a
+// CREATE or DROP DATABASE; None for other statements, CALL among them, and when the tables can't
+// be told.
+fn altered(command: &[Token]) -> Option<Altered> {
+    // The words between the verb and what it changes.
+    const MODIFIERS: [&str; 9] = [
+        "OR",
+        "REPLACE",
+        "TEMPORARY",
+        "UNIQUE",
+        "FULLTEXT",
+        "SPATIAL",
+        "ONLINE",
+        "OFFLINE",
+        "IGNORE",
+    ];
+    let (verb, rest) = command.split_first()?;
+    if verb.is_word("SELECT") || verb.is_word("WITH") {
+        // SELECT ... INTO [TEMPORARY] [TABLE] t.
+        let into = selects_into(rest)?;
+        let rest = &rest[into + 1..];
+        let skipped = rest
+            .iter()
+            .take_while(|t| {
+                ["TEMPORARY", "TEMP", "UNLOGGED", "TABLE"]
+                    .iter()
+                    .any(|w| t.is_word(w))
+            })
+            .count();
+        let (table, _) = object_name(&rest[skipped..])?;
+        return Some(Altered::Tables(vec![(table, true)]));
+    }
+    if verb.is_word("LOAD") {
+        // LOAD DATA ... INTO TABLE t.
+        let table = rest.iter().position(|t| t.is_word("TABLE"))?;
+        let (table, _) = object_name(&rest[table + 1..])?;
+        return Some(Altered::Tables(vec![(table, true)]));
+    }
+    if !["CREATE", "ALTER", "DROP", "RENAME"]
+        .iter()
+        .any(|word| verb.is_word(word))
+    {
+        return None;
+    }
+    let skipped = |words: &[&str], tokens: &[Token]| {
+        tokens
+            .iter()
+            .take_while(|t| words.iter().any(|word| t.is_word(word)))
+            .count()
+    };
+    let (kind, rest) = rest[skipped(&MODIFIERS, rest)..].split_first()?;
+    let mut rest = &rest[skipped(&["IF", "NOT", "EXISTS"], rest)..];
+    if kind.is_word("DATABASE") || kind.is_word("SCHEMA") {
+        return Some(Altered::Database(literals::identifier_name(rest.first()?)?));
+    }
+    if kind.is_word("INDEX") {
+        // The table is after ON: CREATE INDEX i ON t (c), DROP INDEX i ON t.
+        let on = rest.iter().position(|t| t.is_word("ON"))?;
+        rest = &rest[on + 1..];
+    } else if !kind.is_word("TABLE") && !kind.is_word("VIEW") {
+        return None;
+    }
+    // DROP TABLE a, b and RENAME TABLE a TO b, c TO d name several.
+    let mut tables = Vec::new();
+    while let Some((table, after)) = object_name(rest) {
+        tables.push((table, true));
+        match after.split_first() {
+            Some((separator, after)) if *separator == Token::Comma || separator.is_word("TO") => {
+                rest = after
+            }
+            _ => {
+                rest = after;
+                break;
+            }
+        }
+    }
+    if (verb.is_word("CREATE") || verb.is_word("ALTER")) && !kind.is_word("INDEX") {
+        let (table, _) = tables.first()?;
+        let table = table.clone();
+        tables.extend(defined(&table, rest)?);
+    }
+    Some(Altered::Tables(tables))
+}
+
+// The tables the CREATE or ALTER of `table`, `rest` the tokens after its name, uses besides it:
+// those the query of CREATE TABLE ... AS SELECT or of a view reads, LIKE copies and REFERENCES
+// points at, each read, and the table RENAME TO or SET SCHEMA moves it to, written. None if they
+// can't be told.
+fn defined(table: &ObjectName, rest: &[Token]) -> Option<Vec<(ObjectName, bool)>> {
+    let mut used = Vec::new();
+    let mut depth = 0usize;
+    for (i, token) in rest.iter().enumerate() {
+        let next = &rest[i + 1..];
+        match token {
+            Token::LParen if depth == 0 && next.first().is_some_and(starts_query) => {
+                used.extend(table_access(&rest[i..])?);
+                break;
+            }
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.checked_sub(1)?,
+            t if depth == 0 && starts_query(t) => {
+                used.extend(table_access(&rest[i..])?);
+                break;
+            }
+            // CREATE TABLE t LIKE x and PostgreSQL's CREATE TABLE t (LIKE x INCLUDING ALL).
+            t if t.is_word("LIKE") && (depth == 0 || rest[i - 1] == Token::LParen) => {
+                used.push((object_name(next)?.0, false));
+            }
+            t if t.is_word("REFERENCES") => used.push((object_name(next)?.0, false)),
+            // ALTER TABLE t RENAME [TO | AS] x, but not RENAME COLUMN, INDEX, KEY or CONSTRAINT.
+            t if depth == 0 && t.is_word("RENAME") => {
+                let next = match next.first() {
+                    Some(word) if word.is_word("TO") || word.is_word("AS") => &next[1..],
+                    Some(word)
+                        if ["COLUMN", "INDEX", "KEY", "CONSTRAINT"]
+                            .iter()
+                            .any(|w| word.is_word(w)) =>
+                    {
+                        continue
+                    }
+                    _ => next,
+                };
+                used.push((object_name(next)?.0, true));
+            }
+            t if depth == 0
+                && t.is_word("SET")
+                && next.first().is_some_and(|t| t.is_word("SCHEMA")) =>
+            {
+                let schema = literals::identifier_name(next.get(1)?)?;
+                let moved = ObjectName {
+                    schema: Some(schema),
+                    name: table.name.clone(),
+                };
+                used.push((moved, true));
+            }
+            _ => {}
+        }
+    }
+    Some(used)
+}
+
+// Whether a query starts with `token`, in a DDL statement that has one.
+fn starts_query(token: &Token) -> bool {
+    ["SELECT", "WITH", "TABLE", "VALUES"]
+        .iter()
+        .any(|word| token.is_word(word))
+}
+
+impl QueryInterceptor for Policy {
+    fn before_translate(&self, context: &Context, sql: &str) -> Result<Option<String>, MysqlError> {
+        self.config.check(context, sql)?;
+        Ok(None)
+    }
+}
+
+/// Whether `sql` is, or has, a ddl statement. A compound statement may have one.
+pub fn changes_schema(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    if translator::is_compound_statement(&tokens) {
+        return true;
+    }
+    tokens
+        .split(|token| *token == Token::Semicolon)
+        .filter_map(classify)
+        .any(|(class, _)| class == StatementClass::Ddl)
+}
+
+/// Whether `sql` changes data or the schema, as a read-only server refuses it: a dml or ddl
+/// statement, one of a class that can't be told, or one that can't be tokenized or is a
+/// compound statement.
+pub fn writes_data(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return true;
+    };
+    if translator::is_compound_statement(&tokens) {
+        return true;
+    }
+    tokens
+        .split(|token| *token == Token::Semicolon)
+        .any(|statement| match classify(statement) {
+            Some((class, _)) => matches!(class, StatementClass::Dml | StatementClass::Ddl),
+            None => !is_session_statement(statement),
+        })
+}
+
+/// Whether `sql` has a statement that changes, or resets, the PostgreSQL role the session runs
+/// as: SET ROLE, SET SESSION AUTHORIZATION, their RESET, RESET ALL and DISCARD ALL, a call of
+/// set_config() that may set either, a DO block, whose code could, or a function or procedure
+/// definition that sets the role as it is called or whose body could change it. A compound
+/// statement, or one that can't be tokenized, may be one. `options` are the session's, for how
+/// it quotes.
+pub fn changes_role(sql: &str, options: &TranslationOptions) -> bool {
+    let Some(tokens) = translator::significant_tokens_with(sql, options) else {
+        return true;
+    };
+    if translator::is_compound_statement(&tokens) {
+        return true;
+    }
+    tokens
+        .split(|token| *token == Token::Semicolon)
+        .any(|statement| {
+            let Some((first, rest)) = statement.split_first() else {
+                return false;
+            };
+            let all = rest.first().is_some_and(|t| t.is_word("ALL"));
+            (first.is_word("SET") && sets(rest, ROLE_SETTINGS))
+                || (first.is_word("RESET") && (all || names_setting(rest, ROLE_SETTINGS)))
+                || (first.is_word("DISCARD") && all)
+                || is_do_block(statement)
+                || calls_set_config(statement, ROLE_SETTINGS)
+                || (defines_routine(statement) && routine_changes_role(statement))
+        })
+}
+
+// The settings that say whose privileges PostgreSQL applies.
+const ROLE_SETTINGS: &[&str] = &["role", "session_authorization"];
+
+// Whether a statement creates or alters a function or procedure, whose SET clauses apply, and
+// whose body runs, whenever it is called. A trigger's EXECUTE FUNCTION, after its ON, doesn't.
+fn defines_routine(statement: &[Token]) -> bool {
+    let Some((first, rest)) = statement.split_first() else {
+        return false;
+    };
+    (first.is_word("CREATE") || first.is_word("ALTER"))
+        && rest
+            .iter()
+            .take_while(|t| **t != Token::LParen && !t.is_word("ON"))
+            .any(|t| {
+                ["FUNCTION", "PROCEDURE", "ROUTINE"]
+                    .iter()
+                    .any(|w| t.is_word(w))
+            })
+}
+
+// Whether the tokens of a routine definition, or of its body given as a string, could change
+// the role: a SET clause or statement setting it, a RESET of it or RESET ALL, anywhere, or SQL
+// run from text (EXECUTE, PREPARE), which can't be told.
+fn routine_changes_role(tokens: &[Token]) -> bool {
+    tokens.iter().enumerate().any(|(i, token)| {
+        let rest = &tokens[i + 1..];
+        let all = rest.first().is_some_and(|t| t.is_word("ALL"));
+        (token.is_word("SET") && names_setting(rest, ROLE_SETTINGS))
+            || (token.is_word("RESET") && (all || names_setting(rest, ROLE_SETTINGS)))
+            || token.is_word("EXECUTE")
+            || token.is_word("PREPARE")
+            || match token {
+                Token::String(raw) => {
+                    translator::significant_tokens(&literals::mysql_string_value(raw))
+                        .is_some_and(|body| routine_changes_role(&body))
+                }
+                _ => false,
+            }
+    })
+}
+
+// Whether a statement, of no class, admin or a select, writes in a way READ_ONLY refuses: by
+// calling one of WRITES or taking a sequence's NEXT VALUE, by locking rows, which PostgreSQL
+// refuses in a read-only transaction, or by making the session's transactions, or the server,
+// read-write, with READ WRITE or by setting or resetting one of READ_ONLY_SETTINGS.
+fn writes_read_only(statement: &[Token]) -> bool {
+    let Some((first, rest)) = statement.split_first() else {
+        return false;
+    };
+    let all = rest.first().is_some_and(|t| t.is_word("ALL"));
+    let read_write = statement
+        .windows(2)
+        .any(|pair| pair[0].is_word("READ") && pair[1].is_word("WRITE"));
+    let words = |words: &[&str]| {
+        statement
+            .windows(words.len())
+            .any(|w| w.iter().zip(words).all(|(token, word)| token.is_word(word)))
+    };
+    let locks_rows = words(&["FOR", "UPDATE"])
+        || words(&["FOR", "SHARE"])
+        || words(&["FOR", "NO", "KEY", "UPDATE"])
+        || words(&["FOR", "KEY", "SHARE"])
+        || words(&["LOCK", "IN", "SHARE", "MODE"]);
+    calls(statement, WRITES).is_some()
+        || words(&["NEXT", "VALUE", "FOR"])
+        || ((first.is_word("SELECT") || first.is_word("WITH") || first.is_word("TABLE"))
+            && locks_rows)
+        || ((first.is_word("SET") || first.is_word("START")) && read_write)
+        || (first.is_word("SET") && sets(rest, READ_ONLY_SETTINGS))
+        || (first.is_word("RESET") && (all || names_setting(rest, READ_ONLY_SETTINGS)))
+        || calls_set_config(statement, READ_ONLY_SETTINGS)
+}
+
+// Whether a statement calls set_config() for one of `settings`, or for a setting it names with
+// other than a string.
+fn calls_set_config(statement: &[Token], settings: &[&str]) -> bool {
+    statement.windows(3).any(|call| {
+        let set_config = identifier(&call[0])
+            .is_some_and(|name| name.eq_ignore_ascii_case("set_config"))
+            && call[1] == Token::LParen;
+        // Unless it names another setting.
+        let other = match &call[2] {
+            Token::String(raw) => {
+                !names_setting(&[Token::Word(literals::mysql_string_value(raw))], settings)
+            }
+            _ => false,
+        };
+        set_config && !other
+    })
+}
+
+// Whether the assignments of a SET, `rest` the tokens after SET, set one of `settings`.
+fn sets(rest: &[Token], settings: &[&str]) -> bool {
+    let mut depth = 0usize;
+    rest.split(|token| {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        depth == 0 && *token == Token::Comma
+    })
+    .any(|assignment| names_setting(assignment, settings))
+}
+
+// Whether a SET assignment or what RESET resets, past its scope, SESSION, LOCAL or GLOBAL, is one of
+// `settings`: `role = ...`, `@@role = ...`, `ROLE ...`, and `SESSION AUTHORIZATION ...` for
+// session_authorization.
+fn names_setting(tokens: &[Token], settings: &[&str]) -> bool {
+    let authorization = |tokens: &[Token]| {
+        tokens.first().is_some_and(|t| t.is_word("SESSION"))
+            && tokens.get(1).is_some_and(|t| t.is_word("AUTHORIZATION"))
+    };
+    let mut tokens = tokens;
+    while !authorization(tokens)
+        && tokens.first().is_some_and(|t| {
+            ["SESSION", "LOCAL", "GLOBAL", "PERSIST", "PERSIST_ONLY"]
+                .iter()
+                .any(|scope| t.is_word(scope))
+        })
+    {
+        tokens = &tokens[1..];
+    }
+    let name = match tokens.first() {
+        _ if authorization(tokens) => "session_authorization".to_string(),
+        // @@role, not the user variable @role.
+        Some(Token::Variable(name)) => {
+            let Some(name) = name.strip_prefix("@@") else {
+                return false;
+            };
+            let name = name.to_ascii_lowercase();
+            ["session.", "local.", "global.", "persist.", "persist_only."]
+                .iter()
+                .fold(name, |name, scope| {
+                    name.strip_prefix(scope).map(str::to_string).unwrap_or(name)
+                })
+        }
+        Some(token) => match identifier(token) {
+            Some(name) => name.to_ascii_lowercase(),
+            None => return false,
+        },
+        None => return false,
+    };
+    settings.contains(&name.as_str())
+}
+
+// The class of a statement, None for those that only touch the session, and the statement that
+// makes it that class: the statement itself, or the one a WITH or EXPLAIN writes with.
+fn classify(statement: &[Token]) -> Option<(StatementClass, &[Token])> {
+    let (first, rest) = statement.split_first()?;
+    let Token::Word(word) = first else {
+        // A parenthesized SELECT.
+        return matches!(first, Token::LParen).then_some((StatementClass::Select, statement));
+    };
+    let next = |word: &str| rest.first().is_some_and(|token| token.is_word(word));
+    let class = match word.to_ascii_uppercase().as_str() {
+        // SELECT ... INTO t creates t.
+        "SELECT" if selects_into(rest).is_some() => StatementClass::Ddl,
+        "WITH" if writes(rest).is_none() && selects_into(rest).is_some() => StatementClass::Ddl,
+        "SELECT" | "SHOW" | "TABLE" | "VALUES" | "HELP" => StatementClass::Select,
+        // MySQL's DO evaluates expressions, PostgreSQL's runs a block.
+        "DO" if !is_do_block(statement) => StatementClass::Select,
+        "INSERT" | "UPDATE" | "DELETE" | "REPLACE" | "LOAD" | "CALL" | "MERGE" | "COPY" => {
+            StatementClass::Dml
+        }
+        "WITH" | "EXPLAIN" | "DESCRIBE" | "DESC" => match writes(rest) {
+            Some(write) => return Some((StatementClass::Dml, &rest[write])),
+            None => StatementClass::Select,
+        },
+        "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" | "GRANT" | "REVOKE" | "COMMENT"
+        | "REFRESH" => StatementClass::Ddl,
+        "SET" if next("PASSWORD") => StatementClass::Ddl,
+        "SET" if next("GLOBAL") || next("PERSIST") || next("PERSIST_ONLY") => StatementClass::Admin,
+        // SET ROLE and SET SESSION AUTHORIZATION change whose privileges PostgreSQL applies.
+        "SET" if sets(rest, ROLE_SETTINGS) => StatementClass::Admin,
+        "SET" => match rest.first() {
+            Some(Token::Variable(name)) if name.to_ascii_lowercase().starts_with("@@global.") => {
+                StatementClass::Admin
+            }
+            _ => return None,
+        },
+        "KILL" | "FLUSH" | "RESET" | "PURGE" | "ANALYZE" | "OPTIMIZE" | "REPAIR" | "CHECK"
+        | "CHECKSUM" | "INSTALL" | "UNINSTALL" | "SHUTDOWN" | "RESTART" | "CACHE" | "CHANGE"
+        | "BINLOG" | "VACUUM" | "CLUSTER" | "REINDEX" | "CHECKPOINT" => StatementClass::Admin,
+        _ => return None,
+    };
+    Some((class, statement))
+}
+
+// Whether a statement classify() finds of no class only touches the session, rather than being
+// of a class that can't be told.
+fn is_session_statement(statement: &[Token]) -> bool {
+    match statement.first() {
+        Some(first) => SESSION_STATEMENTS.iter().any(|word| first.is_word(word)),
+        None => true,
+    }
+}
+
+// Where the INTO of a SELECT into a table is, `rest` the tokens after SELECT or WITH, if it has
+// one outside parentheses. SELECT ... INTO @v and INTO OUTFILE or DUMPFILE don't create one.
+fn selects_into(rest: &[Token]) -> Option<usize> {
+    let mut depth = 0usize;
+    let into = rest.iter().position(|token| {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        depth == 0 && token.is_word("INTO")
+    })?;
+    match rest.get(into + 1)? {
+        Token::Variable(_) => None,
+        next if next.is_word("OUTFILE") || next.is_word("DUMPFILE") => None,
+        _ => Some(into),
+    }
+}
+
+// Where the statement a WITH or EXPLAIN writes with is: outside the parentheses of its
+// subqueries, or one of its CTEs, `d AS (DELETE ... RETURNING *)`. SELECT ... FOR UPDATE doesn't
+// write, and neither do the functions INSERT() and REPLACE().
+fn writes(tokens: &[Token]) -> Option<Range<usize>> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let previous = i.checked_sub(1).map(|p| &tokens[p]);
+        let write = ["INSERT", "UPDATE", "DELETE", "REPLACE"]
+            .iter()
+            .any(|word| token.is_word(word))
+            && !previous.is_some_and(|previous| previous.is_word("FOR"));
+        if !write {
+            continue;
+        }
+        if depth == 0 {
+            return Some(i..tokens.len());
+        }
+        let statement =
+            previous == Some(&Token::LParen) && tokens.get(i + 1) != Some(&Token::LParen);
+        if statement {
+            return Some(i..closing(tokens, i - 1).unwrap_or(tokens.len()));
+        }
+    }
+    None
+}
+
+// The table a statement names first, for the error refusing it; empty if there is none.
+fn table(statement: &[Token]) -> String {
+    // The words between a command and its table.
+    const SKIPPED: [&str; 14] = [
+        "INTO",
+        "FROM",
+        "TABLE",
+        "TEMPORARY",
+        "IGNORE",
+        "LOW_PRIORITY",
+        "DELAYED",
+        "HIGH_PRIORITY",
+        "QUICK",
+        "IF",
+        "NOT",
+        "EXISTS",
+        "ONLINE",
+        "ONLY",
+    ];
+    let mut rest = statement.iter().skip(1).peekable();
+    let skipped = |token: &&Token| SKIPPED.iter().any(|word| token.is_word(word));
+    while rest.next_if(skipped).is_some() {}
+    let mut name = String::new();
+    // schema.table names the table.
+    while let Some(part) = rest.next().and_then(identifier) {
+        name = part;
+        if rest.next_if(|token| token.is_operator(".")).is_none() {
+            break;
+        }
+    }
+    name
+}
+
+fn identifier(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(name) => Some(name.clone()),
+        Token::QuotedIdent(name) => {
+            let quote = &name[..1];
+            Some(name[1..name.len() - 1].replace(&quote.repeat(2), quote))
+        }
+        _ => None,
+    }
+}
+
+// MySQL's refusal of a write on a read_only server.
+fn read_only() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_OPTION_PREVENTS_STATEMENT,
+        "The MySQL server is running with the --read-only option so it cannot execute this \
+         statement",
+    )
+}
+
+// MySQL's refusal of an administrative statement to a user without the SUPER privilege.
+fn super_denied() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_SPECIFIC_ACCESS_DENIED_ERROR,
+        "Access denied; you need (at least one of) the SUPER privilege(s) for this operation",
+    )
+}
+
+/// The refusal of a statement that would change the PostgreSQL role an auth provider gave the
+/// session's user.
+pub fn role_denied() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_SPECIFIC_ACCESS_DENIED_ERROR,
+        "Access denied; the session's role was given at login and can't be changed",
+    )
+}
+
+// MySQL's refusal of a statement on a database the user has no privilege for.
+fn database_denied(context: &Context, database: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_DBACCESS_DENIED_ERROR,
+        format!(
+            "Access denied for user '{}'@'%' to database '{}'",
+            context.user, database
+        ),
+    )
+}
+
+// MySQL's refusal of a routine the user has no EXECUTE privilege for.
+fn routine_denied(context: &Context, routine: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_PROCACCESS_DENIED_ERROR,
+        format!(
+            "execute command denied to user '{}'@'%' for routine '{}'",
+            context.user, routine
+        ),
+    )
+}
+
+// MySQL's refusal of a statement the user has no privilege for. Users aren't tied to hosts
+// here, so the host is always '%'.
+fn denied(context: &Context, command: &str, table: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_TABLEACCESS_DENIED_ERROR,
+        format!(
+            "{} command denied to user '{}'@'%' for table '{}'",
+            command, context.user, table
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    fn class(sql: &str) -> Option<StatementClass> {
+        let tokens = translator::significant_tokens(sql).unwrap();
+        classify(&tokens).map(|(class, _)| class)
+    }
+
+    fn context(user: &str) -> Context<'_> {
+        static OPTIONS: OnceLock<TranslationOptions> = OnceLock::new();
+        Context {
+            connection_id: 1,
+            user,
+            database: Some("shop"),
+            read_only: false,
+            options: OPTIONS.get_or_init(TranslationOptions::default),
+        }
+    }
+
+    fn check(config: PolicyConfig, user: &str, sql: &str) -> Result<(), ErrorKind> {
+        config.check(&context(user), sql).map_err(|e| e.kind)
+    }
+
+    fn grants(user: &str, grants: &[&str]) -> PolicyConfig {
+        PolicyConfig {
+            grants: vec![(
+                user.to_string(),
+                grants.iter().map(|g| g.parse().unwrap()).collect(),
+            )],
+            ..PolicyConfig::default()
+        }
+    }
+
+    #[test]
+    fn classifies_statements() {
+        use StatementClass::*;
+        assert_eq!(class("SELECT * FROM t"), Some(Select));
+        assert_eq!(class("(SELECT 1) UNION (SELECT 2)"), Some(Select));
+        assert_eq!(class("SHOW TABLES"), Some(Select));
+        assert_eq!(class("INSERT INTO t VALUES (1)"), Some(Dml));
+        assert_eq!(class("LOAD DATA INFILE 'f' INTO TABLE t"), Some(Dml));
+        assert_eq!(class("CALL p()"), Some(Dml));
+        assert_eq!(class("CREATE TABLE t (a int)"), Some(Ddl));
+        assert_eq!(class("SET PASSWORD = 'x'"), Some(Ddl));
+        assert_eq!(class("KILL 7"), Some(Admin));
+        assert_eq!(class("SET GLOBAL max_connections = 10"), Some(Admin));
+        assert_eq!(class("SET @@global.max_connections = 10"), Some(Admin));
+        assert_eq!(class("SET autocommit = 0"), None);
+        assert_eq!(class("USE shop"), None);
+        assert_eq!(class("BEGIN"), None);
+        assert_eq!(class("MERGE INTO t USING s ON t.a = s.a"), Some(Dml));
+        assert_eq!(class("COPY t FROM STDIN"), Some(Dml));
+        assert_eq!(class("COMMENT ON TABLE t IS 'x'"), Some(Ddl));
+        assert_eq!(class("VACUUM t"), Some(Admin));
+        assert_eq!(class("DO 1 + 1"), Some(Select));
+        assert_eq!(class("DO $$ BEGIN NULL; END $$"), None);
+        assert_eq!(class("SELECT * INTO newt FROM t"), Some(Ddl));
+        assert_eq!(class("SELECT a INTO @a FROM t"), Some(Select));
+        assert_eq!(
+            class("SELECT REPLACE(a, 'x', 'y') INTO t2 FROM t"),
+            Some(Ddl)
+        );
+        assert_eq!(
+            class("WITH c AS (SELECT 1) INSERT INTO t SELECT * FROM c"),
+            Some(Dml)
+        );
+        assert_eq!(class("PREPARE p AS SELECT 1"), None);
+    }
+
+    #[test]
+    fn statements_changing_the_role_are_admin() {
+        use StatementClass::*;
+        assert_eq!(class("SET ROLE postgres"), Some(Admin));
+        assert_eq!(class("SET SESSION AUTHORIZATION postgres"), Some(Admin));
+        assert_eq!(class("SET LOCAL role = 'postgres'"), Some(Admin));
+        assert_eq!(
+            class("SET autocommit = 1, @@role = 'postgres'"),
+            Some(Admin)
+        );
+        assert_eq!(class("RESET ROLE"), Some(Admin));
+        assert_eq!(class("SET SESSION sql_mode = ''"), None);
+    }
+
+    #[test]
+    fn finds_what_changes_the_role() {
+        for sql in [
+            "SET ROLE postgres",
+            "set session role none",
+            "SET role TO DEFAULT",
+            "SET SESSION AUTHORIZATION DEFAULT",
+            "SET LOCAL SESSION AUTHORIZATION postgres",
+            "SET session_authorization = 'postgres'",
+            "SET @@session.role = 'postgres'",
+            "SET autocommit = 1, role = 'postgres'",
+            "RESET ROLE",
+            "RESET SESSION AUTHORIZATION",
+            "RESET ALL",
+            "DISCARD ALL",
+            "SELECT 1; SET ROLE postgres",
+            "SELECT set_config('role', 'postgres', false)",
+            "SELECT pg_catalog.set_config(@setting, 'postgres', false)",
+            "DO $$ BEGIN EXECUTE 'RESET ROLE'; END $$",
+            "ALTER FUNCTION f() SET role = 'postgres'",
+            "ALTER PROCEDURE p SET SESSION AUTHORIZATION postgres",
+            "CREATE FUNCTION f() RETURNS int SET role TO postgres AS 'SELECT 1' LANGUAGE sql",
+            "CREATE FUNCTION f() RETURNS void AS $$ RESET ROLE $$ LANGUAGE sql",
+            "CREATE FUNCTION f() RETURNS void AS 'RESET ROLE' LANGUAGE sql",
+            "CREATE OR REPLACE FUNCTION f() RETURNS void AS $$ BEGIN EXECUTE 'RE' || 'SET ROLE'; \
+             END $$ LANGUAGE plpgsql",
+            "CREATE PROCEDURE p() BEGIN PREPARE s FROM @sql; EXECUTE s; END",
+        ] {
+            assert!(changes_role(sql, &TranslationOptions::default()), "{}", sql);
+        }
+        for sql in [
+            "SET autocommit = 1",
+            "SET SESSION sql_mode = ''",
+            "SET @role = 'admin'",
+            "SELECT role FROM users",
+            "RESET QUERY CACHE",
+            "DISCARD TEMP",
+            "SELECT set_config('search_path', 'shop', false)",
+            "CREATE FUNCTION f() RETURNS int SET search_path = shop AS 'SELECT 1' LANGUAGE sql",
+            "CREATE TABLE t (role text)",
+            "CREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW EXECUTE FUNCTION f()",
+        ] {
+            assert!(
+                !changes_role(sql, &TranslationOptions::default()),
+                "{}",
+                sql
+            );
+        }
+        let ansi_quotes = TranslationOptions::default().sql_mode("ANSI_QUOTES");
+        assert!(changes_role(r#"SET "role" = 'postgres'"#, &ansi_quotes));
+    }
+
+    #[test]
+    fn classifies_with_and_explain_by_the_statement_they_run() {
+        use StatementClass::*;
+        assert_eq!(class("WITH c AS (SELECT 1) SELECT * FROM c"), Some(Select));
+        assert_eq!(
+            class("WITH c AS (SELECT 1) DELETE FROM t WHERE a IN (SELECT * FROM c)"),
+            Some(Dml)
+        );
+        assert_eq!(class("EXPLAIN ANALYZE UPDATE t SET a = 1"), Some(Dml));
+        assert_eq!(
+            class("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"),
+            Some(Dml)
+        );
+        assert_eq!(class("SELECT * FROM t FOR UPDATE"), Some(Select));
+        assert_eq!(
+            class("WITH c AS (SELECT * FROM t FOR UPDATE) SELECT 1"),
+            Some(Select)
+        );
+    }
+
+    #[test]
+    fn read_only_refuses_writes() {
+        let config = || PolicyConfig {
+            read_only: true,
+            ..PolicyConfig::default()
+        };
+        let refused = Err(ErrorKind::ER_OPTION_PREVENTS_STATEMENT);
+        assert_eq!(check(config(), "app", "SELECT * FROM t"), Ok(()));
+        assert_eq!(check(config(), "app", "SET autocommit = 0"), Ok(()));
+        assert_eq!(check(config(), "app", "UPDATE t SET a = 1"), refused);
+        assert_eq!(check(config(), "app", "DROP TABLE t"), refused);
+        assert_eq!(check(config(), "app", "SELECT 1; DELETE FROM t"), refused);
+        assert_eq!(
+            check(config(), "app", "BEGIN NOT ATOMIC DELETE FROM t; END"),
+            refused
+        );
+        assert_eq!(check(config(), "app", "SELECT 'unterminated"), refused);
+    }
+
+    #[test]
+    fn read_only_refuses_what_it_cant_class_and_what_undoes_it() {
+        let config = || PolicyConfig {
+            read_only: true,
+            ..PolicyConfig::default()
+        };
+        for sql in [
+            "PREPARE p AS DELETE FROM t",
+            "EXECUTE p",
+            "MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN DELETE",
+            "COMMENT ON TABLE t IS 'x'",
+            "COPY t FROM STDIN",
+            "VACUUM FULL t",
+            "OPTIMIZE TABLE t",
+            "DO $$ BEGIN DELETE FROM t; END $$",
+            "LISTEN events",
+            "DISCARD ALL",
+            "SELECT * INTO newt FROM t",
+            "SELECT setval('t_id_seq', 1)",
+            "SELECT pg_catalog.nextval('t_id_seq')",
+            "SELECT dblink_exec('host=db', 'DELETE FROM t')",
+            "SET default_transaction_read_only = off",
+            "SET SESSION transaction_read_only = 0",
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE",
+            "SET SESSION TRANSACTION READ WRITE",
+            "START TRANSACTION READ WRITE",
+            "RESET default_transaction_read_only",
+            "RESET ALL",
+            "SELECT set_config('default_transaction_read_only', 'off', false)",
+            "SELECT NEXT VALUE FOR s",
+            "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d",
+            "WITH d AS MATERIALIZED (INSERT INTO t VALUES (1) RETURNING id) SELECT id FROM d",
+            "SELECT * FROM t FOR UPDATE",
+            "SELECT * FROM t WHERE id = 1 FOR NO KEY UPDATE SKIP LOCKED",
+            "SELECT * FROM t LOCK IN SHARE MODE",
+            "SET GLOBAL read_only = 0",
+            "SET PERSIST super_read_only = OFF",
+            "SET @@global.read_only = 0",
+        ] {
+            assert_eq!(
+                check(config(), "app", sql),
+                Err(ErrorKind::ER_OPTION_PREVENTS_STATEMENT),
+                "{}",
+                sql
+            );
+        }
+        for sql in [
+            "SELECT a INTO @a FROM t",
+            "SELECT a FROM t INTO OUTFILE '/tmp/a'",
+            "DO SLEEP(1)",
+            "START TRANSACTION READ ONLY",
+            "SET SESSION TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+            "DEALLOCATE PREPARE p",
+            "SELECT set_config('search_path', 'shop', false)",
+            "ANALYZE TABLE t",
+            "SELECT PREVIOUS VALUE FOR s",
+            "SELECT INSERT(a, 1, 2, 'x'), (REPLACE(a, 'x', 'y')) FROM t",
+            "WITH c AS (SELECT (REPLACE(a, 'x', 'y')) FROM t) SELECT * FROM c",
+            "SET GLOBAL max_connections = 10",
+        ] {
+            assert_eq!(check(config(), "app", sql), Ok(()), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn statements_of_no_known_class_are_refused_to_restricted_users() {
+        let config = || PolicyConfig {
+            users: vec![("report".to_string(), vec![StatementClass::Select])],
+            ..PolicyConfig::default()
+        };
+        assert_eq!(
+            check(config(), "report", "EXECUTE p"),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(
+            check(config(), "report", "PREPARE p AS DELETE FROM t"),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(check(config(), "report", "USE shop"), Ok(()));
+        // Nothing restricts other users.
+        assert_eq!(check(config(), "app", "EXECUTE p"), Ok(()));
+        assert_eq!(check(config(), "app", "SELECT 'unterminated"), Ok(()));
+    }
+
+    #[test]
+    fn refuses_classes_a_user_isnt_allowed() {
+        let config = || PolicyConfig {
+            allowed: Some(vec![StatementClass::Select, StatementClass::Dml]),
+            users: vec![("report".to_string(), vec![StatementClass::Select])],
+            ..PolicyConfig::default()
+        };
+        assert_eq!(check(config(), "app", "INSERT INTO t VALUES (1)"), Ok(()));
+        assert_eq!(
+            check(config(), "app", "CREATE TABLE t (a int)"),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(
+            check(config(), "app", "KILL 7"),
+            Err(ErrorKind::ER_SPECIFIC_ACCESS_DENIED_ERROR)
+        );
+        assert_eq!(check(config(), "report", "SELECT * FROM t"), Ok(()));
+        assert_eq!(
+            check(config(), "report", "DELETE FROM t"),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+    }
+
+    #[test]
+    fn names_the_refused_command_and_table() {
+        let config = PolicyConfig {
+            allowed: Some(vec![StatementClass::Select]),
+            ..PolicyConfig::default()
+        };
+        let error = config
+            .check(&context("app"), "INSERT INTO shop.orders VALUES (1)")
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "INSERT command denied to user 'app'@'%' for table 'orders'"
+        );
+    }
+
+    #[test]
+    fn only_users_allowed_admin_by_name_are_admins() {
+        let config = PolicyConfig {
+            users: vec![("ops".to_string(), vec![StatementClass::Admin])],
+            ..PolicyConfig::default()
+        };
+        assert!(config.admin("ops"));
+        assert!(!config.admin("app"));
+    }
+
+    #[test]
+    fn parses_grants() {
+        let grant = |s: &str| s.parse::<Grant>();
+        assert_eq!(
+            grant("shop.*=write"),
+            Ok(Grant {
+                schema: Some("shop".to_string()),
+                table: None,
+                write: true,
+            })
+        );
+        assert_eq!(
+            grant("`Shop`.orders"),
+            Ok(Grant {
+                schema: Some("shop".to_string()),
+                table: Some("orders".to_string()),
+                write: false,
+            })
+        );
+        assert_eq!(grant("*.*=read").map(|g| g.schema), Ok(None));
+        assert!(grant("*.orders").is_err());
+        assert!(grant("orders").is_err());
+        assert!(grant("shop.*=all").is_err());
+    }
+
+    #[test]
+    fn matches_tables_against_grants() {
+        let config = || grants("app", &["shop.*=write", "logs.*=read", "crm.customers"]);
+        let denied = Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR);
+        assert_eq!(check(config(), "app", "SELECT * FROM orders"), Ok(()));
+        assert_eq!(
+            check(config(), "app", "UPDATE shop.orders SET a = 1"),
+            Ok(())
+        );
+        assert_eq!(check(config(), "app", "SELECT * FROM logs.events"), Ok(()));
+        assert_eq!(check(config(), "app", "DELETE FROM logs.events"), denied);
+        assert_eq!(
+            check(config(), "app", "SELECT * FROM crm.customers"),
+            Ok(())
+        );
+        assert_eq!(check(config(), "app", "SELECT * FROM crm.leads"), denied);
+        assert_eq!(
+            check(
+                config(),
+                "app",
+                "SELECT * FROM orders WHERE id IN (SELECT id FROM crm.leads)"
+            ),
+            denied
+        );
+        assert_eq!(
+            check(
+                config(),
+                "app",
+                "INSERT INTO orders SELECT * FROM logs.events"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(config(), "app", "SELECT * FROM information_schema.tables"),
+            Ok(())
+        );
+        // Users without grants use every table.
+        assert_eq!(check(config(), "other", "DELETE FROM crm.leads"), Ok(()));
+    }
+
+    #[test]
+    fn statements_of_no_class_are_checked_for_the_tables_they_read() {
+        let config = || grants("app", &["shop.*"]);
+        let denied = Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR);
+        assert_eq!(
+            check(config(), "app", "SET @n = (SELECT COUNT(*) FROM orders)"),
+            Ok(())
+        );
+        assert_eq!(
+            check(config(), "app", "SET @n = (SELECT COUNT(*) FROM crm.leads)"),
+            denied
+        );
+        assert_eq!(
+            check(
+                config(),
+                "app",
+                "DECLARE c CURSOR FOR SELECT * FROM crm.leads"
+            ),
+            denied
+        );
+    }
+
+    #[test]
+    fn finds_the_tables_of_table_straight_join_and_show() {
+        let config = || grants("app", &["shop.*"]);
+        let denied = Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR);
+        for sql in [
+            "TABLE crm.leads",
+            "SELECT * FROM orders UNION TABLE crm.leads",
+            "SELECT * FROM (TABLE crm.leads) l",
+            "SELECT * FROM orders STRAIGHT_JOIN crm.leads",
+            "SHOW CREATE TABLE crm.leads",
+            "SHOW CREATE VIEW crm.leads",
+            "SHOW FULL COLUMNS FROM leads FROM crm",
+            "SHOW INDEX FROM crm.leads",
+            "SHOW KEYS IN leads IN crm",
+        ] {
+            assert_eq!(check(config(), "app", sql), denied, "{}", sql);
+        }
+        let denied = Err(ErrorKind::ER_DBACCESS_DENIED_ERROR);
+        for sql in [
+            "SHOW TABLES FROM crm",
+            "SHOW FULL TABLES IN crm",
+            "SHOW TABLE STATUS FROM crm",
+            "SHOW TRIGGERS FROM crm",
+            "SHOW CREATE DATABASE crm",
+        ] {
+            assert_eq!(check(config(), "app", sql), denied, "{}", sql);
+        }
+        for sql in [
+            "TABLE orders",
+            "SELECT STRAIGHT_JOIN id FROM orders",
+            "SELECT * FROM orders STRAIGHT_JOIN shop.customers",
+            "SHOW CREATE TABLE orders",
+            "SHOW COLUMNS FROM orders",
+            "SHOW TABLES",
+            "SHOW TABLES FROM shop",
+            "SHOW CREATE DATABASE shop",
+            "SHOW VARIABLES",
+        ] {
+            assert_eq!(check(config(), "app", sql), Ok(()), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn finds_the_tables_of_joins_in_parentheses_using_lock_and_explain() {
+        let config = || grants("app", &["shop.*=write", "logs.*=read"]);
+        let denied = Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR);
+        for sql in [
+            "SELECT * FROM (shop.orders JOIN secret.passwords ON true)",
+            "SELECT * FROM (secret.passwords)",
+            "SELECT * FROM ((orders) LEFT JOIN (secret.passwords) ON true)",
+            "SELECT * FROM orders, (secret.passwords JOIN customers ON true)",
+            "SELECT * FROM (SELECT 1) d, secret.passwords",
+            "SELECT * FROM orders USE INDEX (a, b), secret.passwords",
+            "SELECT * FROM orders JOIN customers ON orders.id = customers.id, secret.passwords",
+            "SELECT * FROM orders JOIN LATERAL (SELECT * FROM secret.passwords) p ON true",
+            "INSERT INTO shop.x SELECT * FROM (shop.orders JOIN secret.passwords ON true)",
+            "DELETE FROM shop.t USING shop.t JOIN secret.x ON true",
+            "DELETE FROM shop.t USING (shop.t JOIN secret.x ON true)",
+            "UPDATE orders JOIN logs.events ON true SET logs.events.a = 1",
+            "WITH d AS (DELETE FROM logs.events RETURNING *) SELECT * FROM d",
+            "LOCK TABLES secret.passwords WRITE",
+            "LOCK TABLES orders READ, logs.events WRITE",
+            "EXPLAIN ANALYZE SELECT * FROM secret.passwords",
+            "EXPLAIN FORMAT=JSON SELECT * FROM secret.passwords",
+            "DESCRIBE secret.passwords",
+            "SELECT * FROM pg_stat_activity",
+            "SELECT * FROM pg_shadow",
+            // Tables that can't be told.
+            "SELECT * FROM orders WHERE id IN (SELECT id FROM",
+            "SELECT * FROM 'orders'",
+        ] {
+            assert_eq!(check(config(), "app", sql), denied, "{}", sql);
+        }
+        for sql in [
+            "SELECT * FROM (orders JOIN customers ON true)",
+            "SELECT * FROM orders JOIN customers USING (id), logs.events",
+            "SELECT * FROM orders PARTITION (p0, p1), logs.events",
+            "SELECT TRIM(BOTH ' ' FROM name), a, b FROM orders ORDER BY a, b",
+            "DELETE FROM orders USING orders JOIN logs.events ON true",
+            "LOCK TABLES orders WRITE, logs.events READ",
+            "EXPLAIN ANALYZE SELECT * FROM orders",
+            "EXPLAIN FOR CONNECTION 7",
+            "SELECT * FROM shop.pg_things",
+        ] {
+            assert_eq!(check(config(), "app", sql), Ok(()), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn reads_quoted_names_as_the_session_quotes_them() {
+        let config = grants("app", &["shop.*"]);
+        let ansi_quotes = TranslationOptions::default().sql_mode("ANSI_QUOTES");
+        let quoted = Context {
+            options: &ansi_quotes,
+            ..context("app")
+        };
+        let sql = r#"SELECT * FROM "secret"."passwords""#;
+        assert_eq!(
+            config.check(&quoted, sql).map_err(|e| e.kind),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        let sql = r#"SELECT * FROM "orders" WHERE name = 'a""b'"#;
+        assert_eq!(config.check(&quoted, sql).map_err(|e| e.kind), Ok(()));
+        assert_eq!(
+            check(config.clone(), "app", sql),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        let no_backslash_escapes = TranslationOptions::default().sql_mode("NO_BACKSLASH_ESCAPES");
+        let escaped = Context {
+            options: &no_backslash_escapes,
+            ..context("app")
+        };
+        // Without NO_BACKSLASH_ESCAPES, all of it up to the comment's quote is a string.
+        let sql = r"SELECT 'a\' FROM secret.passwords -- '";
+        assert_eq!(
+            config.check(&escaped, sql).map_err(|e| e.kind),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(check(config, "app", sql), Ok(()));
+    }
+
+    #[test]
+    fn refuses_prepared_statements_to_users_with_grants() {
+        let config = || grants("app", &["shop.*"]);
+        let denied = Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR);
+        assert_eq!(check(config(), "app", "EXECUTE q"), denied);
+        assert_eq!(
+            check(config(), "app", "PREPARE q AS SELECT * FROM crm.leads"),
+            denied
+        );
+        assert_eq!(check(config(), "app", "SELECT 'unterminated"), denied);
+    }
+
+    #[test]
+    fn refuses_sql_run_from_text() {
+        let config = || grants("app", &["shop.*"]);
+        let denied = Err(ErrorKind::ER_PROCACCESS_DENIED_ERROR);
+        for sql in [
+            "SELECT query_to_xml('SELECT * FROM crm.leads', true, false, '')",
+            "SELECT pg_catalog.table_to_xml('crm.leads', true, false, '')",
+            "SELECT * FROM ts_stat('SELECT body FROM crm.leads')",
+            "DO $$ BEGIN PERFORM 1; END $$",
+            "DO 'BEGIN PERFORM 1; END'",
+        ] {
+            assert_eq!(check(config(), "app", sql), denied, "{}", sql);
+        }
+        // Users without grants may.
+        assert_eq!(
+            check(
+                config(),
+                "other",
+                "SELECT query_to_xml('SELECT 1', true, false, '')"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn field_lists_need_a_select_grant() {
+        let config = grants("app", &["shop.orders"]);
+        assert!(config.check_field_list(&context("app"), "orders").is_ok());
+        assert_eq!(
+            config
+                .check_field_list(&context("app"), "customers")
+                .map_err(|e| e.kind),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        let config = PolicyConfig {
+            users: vec![("etl".to_string(), vec![StatementClass::Dml])],
+            ..PolicyConfig::default()
+        };
+        assert!(config.check_field_list(&context("etl"), "orders").is_err());
+    }
+
+    #[test]
+    fn ddl_needs_a_write_grant_on_what_it_changes() {
+        let config = || grants("app", &["shop.*=write", "logs.*=read"]);
+        assert_eq!(check(config(), "app", "CREATE TABLE t (a int)"), Ok(()));
+        assert_eq!(check(config(), "app", "DROP TABLE shop.a, shop.b"), Ok(()));
+        assert_eq!(
+            check(config(), "app", "RENAME TABLE shop.a TO logs.a"),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(check(config(), "app", "DROP DATABASE shop"), Ok(()));
+        // What a definition reads, and where a table is moved to.
+        for sql in [
+            "CREATE VIEW shop.v AS SELECT * FROM secret.passwords",
+            "CREATE OR REPLACE VIEW v (a) AS (SELECT a FROM secret.passwords)",
+            "CREATE TABLE shop.t AS SELECT * FROM secret.x",
+            "CREATE TABLE shop.t (a int) SELECT a FROM (orders JOIN secret.x ON true)",
+            "CREATE TABLE shop.t LIKE secret.x",
+            "CREATE TABLE shop.t (LIKE secret.x INCLUDING ALL)",
+            "CREATE TABLE shop.t (id int REFERENCES secret.x(id))",
+            "ALTER TABLE shop.t ADD FOREIGN KEY (a) REFERENCES secret.x (id)",
+            "ALTER TABLE shop.t RENAME TO secret.t",
+            "ALTER TABLE shop.t RENAME logs.t",
+            "ALTER TABLE shop.t SET SCHEMA secret",
+            "ALTER TABLE shop.t SET SCHEMA logs",
+        ] {
+            assert_eq!(
+                check(config(), "app", sql),
+                Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR),
+                "{}",
+                sql
+            );
+        }
+        for sql in [
+            "CREATE VIEW v AS SELECT * FROM logs.events",
+            "CREATE TABLE t AS SELECT * FROM orders JOIN logs.events ON true",
+            "CREATE TABLE t (LIKE logs.events)",
+            "CREATE TABLE t (a int REFERENCES orders (id), CHECK (b LIKE 'x%'))",
+            "ALTER TABLE t RENAME COLUMN a TO b",
+            "ALTER TABLE t RENAME TO u",
+        ] {
+            assert_eq!(check(config(), "app", sql), Ok(()), "{}", sql);
+        }
+        assert_eq!(
+            check(config(), "app", "DROP DATABASE logs"),
+            Err(ErrorKind::ER_DBACCESS_DENIED_ERROR)
+        );
+        assert_eq!(
+            check(
+                config(),
+                "app",
+                "CREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW SET @x = 1"
+            ),
+            Err(ErrorKind::ER_TABLEACCESS_DENIED_ERROR)
+        );
+        assert_eq!(
+            check(
+                grants("app", &["*.*=write"]),
+                "app",
+                "CREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW SET @x = 1"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn prints_grants_as_mysql_does() {
+        let grant: Grant = "shop.orders=write".parse().unwrap();
+        assert_eq!(
+            grant.statement("app"),
+            "GRANT SELECT, INSERT, UPDATE, DELETE, CREATE, DROP, ALTER ON `shop`.`orders` TO `app`@`%`"
+        );
+        let grant: Grant = "*.*".parse().unwrap();
+        assert_eq!(grant.statement("app"), "GRANT SELECT ON *.* TO `app`@`%`");
+    }
+}
@@ -0,0 +1,148 @@
+// Per-statement timings, collected while a connection has SET profiling = 1.
+//
+// Each statement is timed phase by phase: how long it waited after arriving before the proxy
+// started on it, parsing, translating, running on PostgreSQL (preparing included) and sending
+// the reply to the client. The last HISTORY statements are kept for SHOW PROFILES and SHOW
+// PROFILE, and each one's timings are logged and written to the protocol trace as it finishes.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// How many statements are kept, as MySQL's default profiling_history_size.
+const HISTORY: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Queue,
+    Parse,
+    Translate,
+    Execute,
+    Serialize,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Queue,
+        Phase::Parse,
+        Phase::Translate,
+        Phase::Execute,
+        Phase::Serialize,
+    ];
+
+    /// The phase as SHOW PROFILE's Status names it.
+    pub fn status(self) -> &'static str {
+        match self {
+            Phase::Queue => "queued",
+            Phase::Parse => "parsing",
+            Phase::Translate => "translating",
+            Phase::Execute => "executing",
+            Phase::Serialize => "sending data",
+        }
+    }
+}
+
+/// The timings of one statement.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// The statement's number, as SHOW PROFILES lists it.
+    pub query_id: u32,
+    pub sql: String,
+    // The time spent in each phase, in the order of Phase::ALL.
+    durations: [Duration; 5],
+}
+
+impl Profile {
+    /// Every phase with the time spent in it, in the order they happen.
+    pub fn phases(&self) -> impl Iterator<Item = (Phase, Duration)> + '_ {
+        Phase::ALL
+            .into_iter()
+            .map(|phase| (phase, self.durations[phase as usize]))
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "query {}", self.query_id)?;
+        for (phase, duration) in self.phases() {
+            write!(f, ", {} {:?}", phase.status(), duration)?;
+        }
+        write!(f, ", total {:?}", self.duration())
+    }
+}
+
+/// A connection's profiling setting and the statements profiled.
+#[derive(Default)]
+pub struct Profiler {
+    enabled: bool,
+    history: VecDeque<Profile>,
+    last_query_id: u32,
+    // The statement being timed, and when its last phase ended.
+    current: Option<(Profile, Instant)>,
+}
+
+impl Profiler {
+    /// Turns profiling of the following statements on or off. Statements already profiled are
+    /// kept either way.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Starts timing `sql` if profiling is on. `received` is when the client's command arrived.
+    pub fn start(&mut self, sql: &str, received: Option<Instant>) {
+        if !self.enabled {
+            return;
+        }
+        self.last_query_id += 1;
+        let now = Instant::now();
+        let mut durations = [Duration::ZERO; 5];
+        if let Some(received) = received {
+            durations[Phase::Queue as usize] = now.saturating_duration_since(received);
+        }
+        let profile = Profile {
+            query_id: self.last_query_id,
+            sql: sql.to_string(),
+            durations,
+        };
+        self.current = Some((profile, now));
+    }
+
+    /// Ends a phase of the statement being timed: the time since the last one ended counts
+    /// towards `phase`.
+    pub fn mark(&mut self, phase: Phase) {
+        if let Some((profile, last)) = &mut self.current {
+            let now = Instant::now();
+            profile.durations[phase as usize] += now - *last;
+            *last = now;
+        }
+    }
+
+    /// Stops timing the statement, counting the time since the last phase towards sending the
+    /// reply, and returns its profile. `None` if it wasn't being timed.
+    pub fn finish(&mut self) -> Option<&Profile> {
+        self.mark(Phase::Serialize);
+        let (profile, _) = self.current.take()?;
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(profile);
+        self.history.back()
+    }
+
+    /// The statements profiled, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Profile> {
+        self.history.iter()
+    }
+
+    /// The statement profiled with `query_id`, or the last one.
+    pub fn profile(&self, query_id: Option<u32>) -> Option<&Profile> {
+        match query_id {
+            Some(id) => self.history.iter().find(|profile| profile.query_id == id),
+            None => self.history.back(),
+        }
+    }
+}
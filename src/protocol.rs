@@ -0,0 +1,1346 @@
+// Client commands opensrv doesn't hand to the shim: COM_PING, which it answers itself without
+// asking whether the PostgreSQL session is still there, COM_RESET_CONNECTION and
+// COM_CHANGE_USER, which it acknowledges without doing anything, COM_FIELD_LIST, which it
+// answers with no columns, and COM_STMT_FETCH, which it doesn't know.
+//
+// The client's read half is wrapped in `Intercepted`, which takes these commands out of the
+// stream, queues them for the connection's Backend, and puts a COM_QUERY of `COMMAND_QUERY` in
+// their place. The Backend runs the queued command when it receives that query, so the reply
+// goes out in the right place among the client's other commands. A client sending
+// `COMMAND_QUERY` itself, with nothing queued, gets whatever PostgreSQL makes of the comment.
+//
+// Most replies are OK and ERR packets the shim can send. COM_FIELD_LIST's, column definitions
+// without a result set around them, can't be, so the Backend queues its packets here and the
+// write half, wrapped in `Replies`, sends them in place of a reply from opensrv. So are those of
+// cursors: a COM_STMT_EXECUTE that opens one is answered with its columns and no rows, and
+// COM_STMT_FETCH with rows and no columns (see cursors.rs). opensrv also drops the flags of
+// COM_STMT_EXECUTE, so `Intercepted` notes whether each asks for a cursor as it passes.
+//
+// `Replies` also fixes up every OK and EOF packet going to the client. opensrv always reports
+// no warnings in them and, other than in an OK the shim sends, no status either, while drivers
+// read both: the warning count to fetch SHOW WARNINGS, and the in-transaction and autocommit
+// flags to tell whether a pooled connection is clean. `Replies` follows each reply packet by
+// packet to find them, and gives them the session's `Status`. The packet ending a result is held
+// back until the next one shows whether more results follow, or opensrv flushes the reply.
+//
+// Commands can be larger than a packet's 16MB, sent as packets of 16MB followed by one that is
+// shorter, and so can rows going the other way. `Intercepted` lets a command of up to
+// MAX_ALLOWED_PACKET bytes through, 64 MiB by default, as MySQL's max_allowed_packet. It reads a
+// command split over packets to its end before passing any of it on; one that is too large is
+// dropped unread by opensrv, and the client gets error 1153 (ER_NET_PACKET_TOO_LARGE) in its
+// place. `Replies` passes the packets continuing a large row through untouched, whatever their
+// first byte, and the replies the Backend queues are split as they need to be.
+//
+// A client the Backend refuses at login for a reason of its own, one connection too many for
+// its user say, gets the error the Backend gives `Commands::refuse` in place of opensrv's
+// "Access denied".
+//
+// Clients older than the 4.1 protocol, and 4.1 clients set up to log in with the old
+// (mysql_old_password) hashing, which doesn't send CLIENT_SECURE_CONNECTION, can't be served.
+// opensrv would drop them without a word, or fail to parse their handshake response, so
+// `Intercepted` gives opensrv a stand-in response it can parse, the Backend refuses it, and
+// `Replies` turns opensrv's error into the one MySQL sends them, ER_NOT_SUPPORTED_AUTH_MODE,
+// in the format the client reads:
+//
+//   ERROR 1251: Client does not support authentication protocol requested by server; consider
+//   upgrading MySQL client
+//
+// With LISTEN_TLS_CERT, `Replies` offers CLIENT_SSL in the greeting as well, and `Intercepted`
+// passes the SSLRequest of a client that takes it up on to opensrv as it is: the handshake
+// response follows it, over TLS started under here (see client_tls.rs).
+//
+// `Replies` adds CLIENT_CONNECT_ATTRS to the capabilities of opensrv's greeting, which doesn't
+// offer it, so that clients send their connection attributes, program_name, _client_name, _os,
+// _pid and the like, at the end of their handshake response, past what opensrv reads.
+// `Intercepted` takes them from there for the Backend.
+//
+// It adds CLIENT_LONG_PASSWORD as well, which MariaDB calls CLIENT_MYSQL. A greeting without it
+// is a MariaDB server's to MariaDB's connectors, which then read MariaDB-only capabilities out of
+// its filler and talk to it as MariaDB, with progress reports and bulk statements on offer.
+// opensrv speaks none of that, so they are told they are talking to MySQL, as the version says.
+//
+// The connection's character sets (see charset.rs) are kept here too, starting with the
+// collation of the handshake response. `Intercepted` converts the statements of COM_QUERY and
+// COM_STMT_PREPARE from the client's character set to UTF-8, which is all opensrv reads, and
+// `Replies` gives the column definitions of result sets the collation of their columns, where
+// opensrv gives every column utf8_general_ci.
+
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
+
+use opensrv_mysql::{Column, ErrorKind};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::charset::Charsets;
+use crate::error::MysqlError;
+
+/// The statement an intercepted command is replaced by.
+pub const COMMAND_QUERY: &str = "/* postmyrustache: intercepted command */";
+
+const COM_SLEEP: u8 = 0x00;
+const COM_QUERY: u8 = 0x03;
+const COM_FIELD_LIST: u8 = 0x04;
+const COM_PING: u8 = 0x0e;
+const COM_CHANGE_USER: u8 = 0x11;
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_STMT_EXECUTE: u8 = 0x17;
+const COM_STMT_FETCH: u8 = 0x1c;
+const COM_RESET_CONNECTION: u8 = 0x1f;
+
+// The flag of COM_STMT_EXECUTE asking for a cursor; the other cursor types aren't supported by
+// MySQL either.
+const CURSOR_TYPE_READ_ONLY: u8 = 0x01;
+
+const CLIENT_LONG_PASSWORD: u32 = 0x1;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x8;
+const CLIENT_PROTOCOL_41: u32 = 0x200;
+const CLIENT_SSL: u32 = 0x800;
+const CLIENT_TRANSACTIONS: u32 = 0x2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+const CLIENT_CONNECT_ATTRS: u32 = 0x0010_0000;
+const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+const CLIENT_SESSION_TRACK: u32 = 0x0080_0000;
+const CLIENT_DEPRECATE_EOF: u32 = 0x0100_0000;
+
+const SERVER_STATUS_IN_TRANS: u16 = 0x0001;
+const SERVER_STATUS_AUTOCOMMIT: u16 = 0x0002;
+const SERVER_MORE_RESULTS_EXISTS: u16 = 0x0008;
+const SERVER_STATUS_CURSOR_EXISTS: u16 = 0x0040;
+const SERVER_STATUS_LAST_ROW_SENT: u16 = 0x0080;
+// The collation of columns that aren't text, and the flag of binary text columns.
+const BINARY_COLLATION: u16 = 63;
+const BINARY_FLAG: u16 = 0x80;
+// utf8_general_ci, the character set of the stand-in handshake response.
+const UTF8_GENERAL_CI: u8 = 33;
+
+/// The largest command a client may send by default, as MySQL's max_allowed_packet.
+pub const DEFAULT_MAX_ALLOWED_PACKET: usize = 64 * 1024 * 1024;
+
+// The largest payload of a packet; a packet this long is continued by the next.
+const MAX_PAYLOAD: usize = 0xff_ffff;
+
+const ER_NOT_SUPPORTED_AUTH_MODE: u16 = 1251;
+const NOT_SUPPORTED_AUTH_MODE: &str = "Client does not support authentication protocol requested \
+                                       by server; consider upgrading MySQL client";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Ping,
+    ResetConnection,
+    ChangeUser {
+        user: String,
+        // The password, as the client's authentication method sends it.
+        auth_response: Vec<u8>,
+        database: Option<String>,
+    },
+    FieldList {
+        table: String,
+        // A LIKE pattern the columns' names have to match; empty for every column.
+        wildcard: String,
+    },
+    // COM_STMT_FETCH: the next `rows` rows of the cursor of prepared statement `statement`.
+    Fetch {
+        statement: u32,
+        rows: u32,
+    },
+    // A command larger than MAX_ALLOWED_PACKET, dropped.
+    TooLarge,
+}
+
+/// The intercepted commands of a connection, waiting for the Backend, and the replies the
+/// Backend has for them.
+#[derive(Default)]
+pub struct Commands {
+    queue: Mutex<VecDeque<Command>>,
+    replies: Mutex<Vec<u8>>,
+    // The command byte of every command read and not yet replied to, oldest first, so the write
+    // half knows what each reply answers, and when it arrived.
+    unanswered: Mutex<VecDeque<(u8, Instant)>>,
+    // When the last reply went out, for WAIT_TIMEOUT.
+    replied: Mutex<Option<Instant>>,
+    // Whether each COM_STMT_EXECUTE read and not yet run asked for a cursor, oldest first.
+    cursors: Mutex<VecDeque<bool>>,
+    // The capabilities the client asked for in its handshake response.
+    capabilities: AtomicU32,
+    // Whether the client is too old to be served.
+    outdated: AtomicBool,
+    // The connection attributes of the client's handshake response, by name, in the order sent.
+    connect_attrs: OnceLock<Vec<(String, String)>>,
+    // The error refusing the client at login, if the Backend has one.
+    refusal: Mutex<Option<MysqlError>>,
+    // The connection's character sets, and those its handshake response started it with.
+    charsets: Mutex<Charsets>,
+    handshake_charsets: OnceLock<Charsets>,
+    // The authentication method the client has to log in with, if it matters (AUTH_PROVIDER).
+    auth_plugin: OnceLock<&'static str>,
+    // Whether the greeting offers TLS (LISTEN_TLS_CERT).
+    tls: AtomicBool,
+}
+
+impl Commands {
+    fn push(&self, command: Command) {
+        self.queue.lock().unwrap().push_back(command);
+    }
+
+    fn read(&self, command: u8) {
+        let mut unanswered = self.unanswered.lock().unwrap();
+        unanswered.push_back((command, Instant::now()));
+    }
+
+    // The command the reply being written answers.
+    fn replying_to(&self) -> Option<u8> {
+        let unanswered = self.unanswered.lock().unwrap();
+        unanswered.front().map(|(command, _)| *command)
+    }
+
+    /// When the command being run, the oldest not replied to, arrived from the client.
+    pub fn received(&self) -> Option<Instant> {
+        let unanswered = self.unanswered.lock().unwrap();
+        unanswered.front().map(|(_, received)| *received)
+    }
+
+    fn answered(&self) {
+        self.unanswered.lock().unwrap().pop_front();
+        *self.replied.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Since when the client has been idle, with no command unanswered: since the last reply,
+    /// or since it `connected` if it hasn't had one. `None` while a command is being run.
+    pub fn idle_since(&self, connected: Instant) -> Option<Instant> {
+        if !self.unanswered.lock().unwrap().is_empty() {
+            return None;
+        }
+        Some(self.replied.lock().unwrap().unwrap_or(connected))
+    }
+
+    /// Whether the client's handshake response was for a protocol older than 4.1, or the old
+    /// password hashing, which the client is refused for.
+    pub fn outdated(&self) -> bool {
+        self.outdated.load(Ordering::Relaxed)
+    }
+
+    /// The connection attributes the client sent, none if it sent none.
+    pub fn connect_attrs(&self) -> &[(String, String)] {
+        self.connect_attrs.get().map_or(&[], Vec::as_slice)
+    }
+
+    /// The connection's character sets.
+    pub fn charsets(&self) -> Charsets {
+        *self.charsets.lock().unwrap()
+    }
+
+    pub fn set_charsets(&self, charsets: Charsets) {
+        *self.charsets.lock().unwrap() = charsets;
+    }
+
+    /// Puts the character sets back to those the client logged in with.
+    pub fn reset_charsets(&self) {
+        let charsets = self.handshake_charsets.get().copied().unwrap_or_default();
+        self.set_charsets(charsets);
+    }
+
+    /// Has the client refused at login with `error`, rather than "Access denied", when the
+    /// Backend's authenticate turns it away.
+    pub fn refuse(&self, error: MysqlError) {
+        *self.refusal.lock().unwrap() = Some(error);
+    }
+
+    /// Has the client log in with authentication method `plugin`, asking it to switch if its
+    /// handshake response is for another.
+    pub fn require_auth_plugin(&self, plugin: &'static str) {
+        let _ = self.auth_plugin.set(plugin);
+    }
+
+    /// Has the greeting offer the client TLS.
+    pub fn offer_tls(&self) {
+        self.tls.store(true, Ordering::Relaxed);
+    }
+
+    /// The oldest command not yet run.
+    pub fn take(&self) -> Option<Command> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    /// Whether the COM_STMT_EXECUTE being run asked for a cursor. Called once for each.
+    pub fn take_cursor(&self) -> bool {
+        self.cursors.lock().unwrap().pop_front().unwrap_or(false)
+    }
+
+    /// Sends the reply to COM_FIELD_LIST: a column definition per column, then EOF.
+    pub fn reply_field_list(
+        &self,
+        database: &str,
+        table: &str,
+        columns: &[Column],
+        status: &Status,
+    ) {
+        let results = self.charsets().results_collation();
+        let mut replies = self.replies.lock().unwrap();
+        // The reply follows the command, which was packet 0.
+        let mut sequence = 1;
+        for column in columns {
+            let mut packet = Vec::new();
+            column_definition(&mut packet, database, table, column, results);
+            sequence = write_packet(&mut replies, sequence, &packet);
+        }
+        write_packet(&mut replies, sequence, &self.end(status, 0));
+    }
+
+    /// Sends the reply to a COM_STMT_EXECUTE that opened a cursor: the column count and
+    /// definitions, then EOF saying the cursor exists, and no rows.
+    pub fn reply_cursor(&self, columns: &[Column], status: &Status) {
+        let results = self.charsets().results_collation();
+        let mut replies = self.replies.lock().unwrap();
+        let mut packet = Vec::new();
+        length_encoded(&mut packet, columns.len());
+        let mut sequence = write_packet(&mut replies, 1, &packet);
+        for column in columns {
+            let mut packet = Vec::new();
+            column_definition(&mut packet, "", &column.table, column, results);
+            sequence = write_packet(&mut replies, sequence, &packet);
+        }
+        let end = self.end(status, SERVER_STATUS_CURSOR_EXISTS);
+        write_packet(&mut replies, sequence, &end);
+    }
+
+    /// Sends the reply to COM_STMT_FETCH: `rows`, each the payload of a row in the binary
+    /// protocol, then EOF, saying the last row was sent if the cursor is `done`.
+    pub fn reply_fetch(&self, rows: &[Vec<u8>], done: bool, status: &Status) {
+        let mut replies = self.replies.lock().unwrap();
+        let mut sequence: u8 = 1;
+        for row in rows {
+            sequence = write_packet(&mut replies, sequence, row);
+        }
+        let flags = match done {
+            true => SERVER_STATUS_CURSOR_EXISTS | SERVER_STATUS_LAST_ROW_SENT,
+            false => SERVER_STATUS_CURSOR_EXISTS,
+        };
+        write_packet(&mut replies, sequence, &self.end(status, flags));
+    }
+
+    // The EOF ending a list of columns or rows, with `flags` and the session's status.
+    fn end(&self, status: &Status, flags: u16) -> Vec<u8> {
+        let capabilities = self.capabilities.load(Ordering::Relaxed);
+        let mut end = if capabilities & CLIENT_DEPRECATE_EOF != 0 {
+            // An OK packet with EOF's header.
+            vec![0xfe, 0, 0, 0, 0, 0, 0]
+        } else {
+            vec![0xfe, 0, 0, 0, 0]
+        };
+        // The status comes after the warnings in EOF, and after the affected rows and insert id
+        // in OK, at the same place in both.
+        end[3..5].copy_from_slice(&flags.to_le_bytes());
+        status.apply(&mut end, capabilities, false);
+        end
+    }
+}
+
+// A column definition, of `column` in `table` of `database`, its text in collation `results`.
+fn column_definition(
+    packet: &mut Vec<u8>,
+    database: &str,
+    table: &str,
+    column: &Column,
+    results: u16,
+) {
+    for text in [
+        "def",
+        database,
+        table,
+        table,
+        column.column.as_str(),
+        column.column.as_str(),
+    ] {
+        length_encoded_string(packet, text.as_bytes());
+    }
+    packet.push(0x0c);
+    let collation = column_collation(column.coltype as u8, column.colflags.bits(), results);
+    packet.extend_from_slice(&collation.to_le_bytes());
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.push(column.coltype as u8);
+    packet.extend_from_slice(&column.colflags.bits().to_le_bytes());
+    // Decimals and filler, then the column default, always NULL.
+    packet.extend_from_slice(&[0, 0, 0, 0xfb]);
+}
+
+// The collation of a column of type `coltype` with `flags`: that of the results for text,
+// binary for the rest, JSON included, as MySQL has them.
+fn column_collation(coltype: u8, flags: u16, results: u16) -> u16 {
+    // VARCHAR, ENUM, SET, the BLOB types, VAR_STRING and STRING.
+    let text = matches!(coltype, 0x0f | 0xf7..=0xfe) && flags & BINARY_FLAG == 0;
+    match text {
+        true => results,
+        false => BINARY_COLLATION,
+    }
+}
+
+// Gives the column definition `payload`, as opensrv wrote it, the collation of its column: after
+// the catalog, database, table and column names, and the length of the fixed fields, come the
+// collation, the column length, type and flags.
+fn fix_column_collation(payload: &mut [u8], results: u16) {
+    let mut at = 0;
+    for _ in 0..6 {
+        let Some((length, _)) = payload.get(at..).and_then(read_length_encoded) else {
+            return;
+        };
+        let Some(next) = skip_length_encoded(payload, at) else {
+            return;
+        };
+        at = next + length as usize;
+    }
+    let at = at + 1;
+    let Some(fields) = payload.get(at..at + 9) else {
+        return;
+    };
+    let flags = u16::from_le_bytes([fields[7], fields[8]]);
+    let collation = column_collation(fields[6], flags, results);
+    payload[at..at + 2].copy_from_slice(&collation.to_le_bytes());
+}
+
+/// What OK and EOF packets say about the session.
+#[derive(Debug, Default)]
+pub struct Status {
+    in_transaction: AtomicBool,
+    // The warnings of the last statement.
+    warnings: AtomicU16,
+    // The number of the error being sent, which opensrv may not know.
+    error_code: AtomicU16,
+}
+
+impl Status {
+    pub fn set_in_transaction(&self, in_transaction: bool) {
+        self.in_transaction.store(in_transaction, Ordering::Relaxed);
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction.load(Ordering::Relaxed)
+    }
+
+    pub fn set_warnings(&self, warnings: u16) {
+        self.warnings.store(warnings, Ordering::Relaxed);
+    }
+
+    /// Numbers the next ERR packet `code`, whatever ErrorKind it was written with.
+    pub fn set_error_code(&self, code: u16) {
+        self.error_code.store(code, Ordering::Relaxed);
+    }
+
+    // Statements are always committed as they run unless a transaction is open, so autocommit
+    // is always on.
+    fn flags(&self) -> u16 {
+        if self.in_transaction.load(Ordering::Relaxed) {
+            SERVER_STATUS_AUTOCOMMIT | SERVER_STATUS_IN_TRANS
+        } else {
+            SERVER_STATUS_AUTOCOMMIT
+        }
+    }
+
+    // Writes the status into `payload` if it is an OK or EOF packet, saying that another result
+    // follows if `more_results`.
+    fn apply(&self, payload: &mut [u8], capabilities: u32, more_results: bool) {
+        let (status, warnings) = match payload.first() {
+            Some(0x00) => match ok_status(payload, capabilities) {
+                Some(offsets) => offsets,
+                None => return,
+            },
+            // With CLIENT_DEPRECATE_EOF, result sets end in an OK packet with EOF's header.
+            Some(0xfe) if capabilities & CLIENT_DEPRECATE_EOF != 0 => {
+                match ok_status(payload, capabilities) {
+                    Some(offsets) => offsets,
+                    None => return,
+                }
+            }
+            Some(0xfe) if payload.len() == 5 => (3, Some(1)),
+            _ => return,
+        };
+        let Some(bytes) = payload.get_mut(status..status + 2) else {
+            return;
+        };
+        let mut flags = u16::from_le_bytes([bytes[0], bytes[1]]) & !SERVER_STATUS_IN_TRANS;
+        flags |= self.flags();
+        if more_results {
+            flags |= SERVER_MORE_RESULTS_EXISTS;
+        }
+        bytes.copy_from_slice(&flags.to_le_bytes());
+        if let Some(at) = warnings {
+            self.apply_warnings(payload, at);
+        }
+    }
+
+    // Writes the pending error number into `payload`, an ERR packet.
+    fn apply_error_code(&self, payload: &mut [u8]) {
+        let code = self.error_code.swap(0, Ordering::Relaxed);
+        if let (true, Some(bytes)) = (code != 0, payload.get_mut(1..3)) {
+            bytes.copy_from_slice(&code.to_le_bytes());
+        }
+    }
+
+    fn apply_warnings(&self, payload: &mut [u8], at: usize) {
+        if let Some(bytes) = payload.get_mut(at..at + 2) {
+            let warnings = self.warnings.load(Ordering::Relaxed);
+            bytes.copy_from_slice(&warnings.to_le_bytes());
+        }
+    }
+}
+
+// Where an OK packet has its status flags and warning count: after the header and the
+// length-encoded affected rows and last insert id. Clients before 4.1 get no warning count,
+// and no status either unless they asked for it.
+fn ok_status(payload: &[u8], capabilities: u32) -> Option<(usize, Option<usize>)> {
+    let at = skip_length_encoded(payload, 1)?;
+    let at = skip_length_encoded(payload, at)?;
+    if capabilities & CLIENT_PROTOCOL_41 != 0 {
+        Some((at, Some(at + 2)))
+    } else if capabilities & CLIENT_TRANSACTIONS != 0 {
+        Some((at, None))
+    } else {
+        None
+    }
+}
+
+fn skip_length_encoded(payload: &[u8], at: usize) -> Option<usize> {
+    let width = match *payload.get(at)? {
+        0xfc => 3,
+        0xfd => 4,
+        0xfe => 9,
+        _ => 1,
+    };
+    Some(at + width)
+}
+
+// Writes `payload` as packets from `sequence` on, as many as it takes, and returns the sequence
+// of the next packet.
+fn write_packet(out: &mut Vec<u8>, mut sequence: u8, mut payload: &[u8]) -> u8 {
+    loop {
+        let (chunk, rest) = payload.split_at(payload.len().min(MAX_PAYLOAD));
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes()[..3]);
+        out.push(sequence);
+        out.extend_from_slice(chunk);
+        sequence = sequence.wrapping_add(1);
+        // A payload that fills its last packet is ended by an empty one.
+        if chunk.len() < MAX_PAYLOAD {
+            return sequence;
+        }
+        payload = rest;
+    }
+}
+
+fn length_encoded_string(out: &mut Vec<u8>, text: &[u8]) {
+    length_encoded(out, text.len());
+    out.extend_from_slice(text);
+}
+
+fn length_encoded(out: &mut Vec<u8>, length: usize) {
+    if length < 0xfb {
+        out.push(length as u8);
+    } else if length <= 0xffff {
+        out.push(0xfc);
+        out.extend_from_slice(&(length as u16).to_le_bytes());
+    } else {
+        out.push(0xfd);
+        out.extend_from_slice(&(length as u32).to_le_bytes()[..3]);
+    }
+}
+
+// opensrv writes an OK packet's info, "Rows matched: ..." and the like, as the rest of the
+// packet unless the client has CLIENT_SESSION_TRACK. MySQL length-encodes it either way, and
+// that is how clients read it.
+fn length_encode_info(packet: &mut Vec<u8>, capabilities: u32) {
+    if capabilities & CLIENT_SESSION_TRACK != 0 {
+        return;
+    }
+    let Some((status, warnings)) = ok_status(&packet[4..], capabilities) else {
+        return;
+    };
+    let at = 4 + warnings.unwrap_or(status) + 2;
+    if packet.len() <= at {
+        return;
+    }
+    let info = packet.split_off(at);
+    length_encoded_string(packet, &info);
+    let length = (packet.len() - 4) as u32;
+    packet[..3].copy_from_slice(&length.to_le_bytes()[..3]);
+}
+
+/// A connection's read half, with the commands above taken out.
+pub struct Intercepted<R> {
+    inner: R,
+    commands: Arc<Commands>,
+    // Read from `inner` but not yet looked at.
+    pending: Vec<u8>,
+    // Looked at and ready to be read, from `offset` on.
+    ready: Vec<u8>,
+    offset: usize,
+    // What is left of a packet passed through untouched, and of one dropped.
+    passthrough: usize,
+    discard: usize,
+    // Whether the packet being dropped is continued by another.
+    discarding: bool,
+    max_allowed_packet: usize,
+    // Whether the handshake response, the client's first packet, has been seen.
+    handshake_seen: bool,
+    eof: bool,
+}
+
+impl<R> Intercepted<R> {
+    /// `inner`, letting commands of up to `max_allowed_packet` bytes through.
+    pub fn new(inner: R, commands: Arc<Commands>, max_allowed_packet: usize) -> Intercepted<R> {
+        Intercepted {
+            inner,
+            commands,
+            pending: Vec::new(),
+            ready: Vec::new(),
+            offset: 0,
+            passthrough: 0,
+            discard: 0,
+            discarding: false,
+            max_allowed_packet,
+            handshake_seen: false,
+            eof: false,
+        }
+    }
+
+    // Moves what can be decided from `pending` to `ready`.
+    fn process(&mut self) {
+        loop {
+            if self.passthrough > 0 {
+                let n = self.passthrough.min(self.pending.len());
+                if n == 0 {
+                    return;
+                }
+                self.ready.extend(self.pending.drain(..n));
+                self.passthrough -= n;
+                continue;
+            }
+            if self.discard > 0 {
+                let n = self.discard.min(self.pending.len());
+                if n == 0 {
+                    return;
+                }
+                self.pending.drain(..n);
+                self.discard -= n;
+                continue;
+            }
+            if self.pending.len() < 4 {
+                return;
+            }
+            let length = payload_length(&self.pending);
+            let sequence = self.pending[3];
+            if self.discarding {
+                self.discard = 4 + length;
+                self.discarding = length == MAX_PAYLOAD;
+                continue;
+            }
+            if !self.handshake_seen {
+                let Some(flags) = self.pending.get(4..8) else {
+                    return;
+                };
+                let flags = u32::from_le_bytes([flags[0], flags[1], flags[2], flags[3]]);
+                // The handshake response comes after it.
+                if is_ssl_request(&self.pending) {
+                    self.passthrough = 4 + length;
+                    continue;
+                }
+                // opensrv only asks a client to switch methods when its response is empty.
+                if let Some(plugin) = self.commands.auth_plugin.get() {
+                    if flags & CLIENT_PROTOCOL_41 != 0 && flags & CLIENT_PLUGIN_AUTH != 0 {
+                        let Some(response) = self.pending.get(4..4 + length) else {
+                            return;
+                        };
+                        if let Some(switched) = without_auth_response(response, plugin) {
+                            let mut packet = Vec::with_capacity(4 + switched.len());
+                            write_packet(&mut packet, sequence, &switched);
+                            self.pending.splice(..4 + length, packet);
+                            continue;
+                        }
+                    }
+                }
+                // The collation follows the capabilities and the largest packet.
+                if flags & CLIENT_PROTOCOL_41 != 0 {
+                    let Some(&collation) = self.pending.get(12) else {
+                        return;
+                    };
+                    let charsets = Charsets::handshake(collation);
+                    if self.commands.handshake_charsets.set(charsets).is_ok() {
+                        self.commands.set_charsets(charsets);
+                    }
+                }
+                if flags & CLIENT_PROTOCOL_41 != 0 && flags & CLIENT_CONNECT_ATTRS != 0 {
+                    let Some(response) = self.pending.get(4..4 + length) else {
+                        return;
+                    };
+                    let attrs = connect_attrs(response).unwrap_or_default();
+                    let _ = self.commands.connect_attrs.set(attrs);
+                }
+                // 4.0 clients send two bytes of capabilities.
+                let capabilities = if flags & CLIENT_PROTOCOL_41 != 0 {
+                    flags
+                } else {
+                    flags & 0xffff
+                };
+                self.commands
+                    .capabilities
+                    .store(capabilities, Ordering::Relaxed);
+                self.handshake_seen = true;
+                if flags & CLIENT_PROTOCOL_41 == 0 || flags & CLIENT_SECURE_CONNECTION == 0 {
+                    self.commands.outdated.store(true, Ordering::Relaxed);
+                }
+            }
+            if self.commands.outdated() && sequence != 0 {
+                if self.pending.len() < 4 + length {
+                    return;
+                }
+                self.pending.drain(..4 + length);
+                stand_in_handshake(&mut self.ready, sequence);
+                continue;
+            }
+            // Commands start a new exchange; packets further into one, such as the handshake
+            // response or authentication data, are never commands.
+            if sequence != 0 {
+                self.passthrough = 4 + length;
+                continue;
+            }
+            let size = match self.command_size() {
+                Some(CommandSize::Fits(size)) => size,
+                Some(CommandSize::TooLarge) => {
+                    self.commands.push(Command::TooLarge);
+                    self.commands.read(COM_QUERY);
+                    command_query(&mut self.ready);
+                    self.discard = 4 + length;
+                    self.discarding = length == MAX_PAYLOAD;
+                    continue;
+                }
+                None => return,
+            };
+            // opensrv answers an empty packet as it does commands it doesn't know, with OK.
+            if length == 0 {
+                self.commands.read(COM_SLEEP);
+                self.passthrough = 4;
+                continue;
+            }
+            let Some(&command) = self.pending.get(4) else {
+                return;
+            };
+            if command == COM_STMT_EXECUTE {
+                // The statement id, then the flags.
+                let Some(&flags) = self.pending.get(9) else {
+                    return;
+                };
+                let cursor = flags & CURSOR_TYPE_READ_ONLY != 0;
+                self.commands.cursors.lock().unwrap().push_back(cursor);
+            }
+            let client = self.commands.charsets().client;
+            if matches!(command, COM_QUERY | COM_STMT_PREPARE) && client.converts() {
+                // The statement in the client's character set, as UTF-8.
+                if self.pending.len() < size {
+                    return;
+                }
+                let packets: Vec<u8> = self.pending.drain(..size).collect();
+                let mut payload = Vec::with_capacity(size);
+                let mut at = 0;
+                while at < packets.len() {
+                    let length = payload_length(&packets[at..]);
+                    payload.extend_from_slice(&packets[at + 4..at + 4 + length]);
+                    at += 4 + length;
+                }
+                let mut converted = vec![command];
+                converted.extend_from_slice(&client.decode(&payload[1..]));
+                self.commands.read(command);
+                write_packet(&mut self.ready, 0, &converted);
+                continue;
+            }
+            if !matches!(
+                command,
+                COM_PING | COM_CHANGE_USER | COM_RESET_CONNECTION | COM_FIELD_LIST | COM_STMT_FETCH
+            ) {
+                self.commands.read(command);
+                self.passthrough = size;
+                continue;
+            }
+            if self.pending.len() < 4 + length {
+                return;
+            }
+            let packet: Vec<u8> = self.pending.drain(..4 + length).collect();
+            let command = match command {
+                COM_PING => Command::Ping,
+                COM_RESET_CONNECTION => Command::ResetConnection,
+                COM_FIELD_LIST => field_list(&packet[5..]),
+                COM_STMT_FETCH => fetch(&packet[5..]),
+                _ => change_user(&packet[5..]),
+            };
+            self.commands.push(command);
+            self.commands.read(COM_QUERY);
+            command_query(&mut self.ready);
+        }
+    }
+
+    // The size, headers and all, of the command starting `pending`, once the headers of all its
+    // packets have been read, or as soon as it is known to be too large.
+    fn command_size(&self) -> Option<CommandSize> {
+        let (mut at, mut payload) = (0, 0);
+        loop {
+            let length = payload_length(self.pending.get(at..at + 4)?);
+            payload += length;
+            at += 4 + length;
+            if payload > self.max_allowed_packet {
+                return Some(CommandSize::TooLarge);
+            }
+            if length < MAX_PAYLOAD {
+                return Some(CommandSize::Fits(at));
+            }
+        }
+    }
+}
+
+enum CommandSize {
+    Fits(usize),
+    TooLarge,
+}
+
+// The COM_QUERY of `COMMAND_QUERY` put in place of a command taken out.
+fn command_query(out: &mut Vec<u8>) {
+    let mut payload = vec![COM_QUERY];
+    payload.extend_from_slice(COMMAND_QUERY.as_bytes());
+    write_packet(out, 0, &payload);
+}
+
+/// The error of a command larger than MAX_ALLOWED_PACKET.
+pub fn packet_too_large() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_NET_PACKET_TOO_LARGE,
+        "Got a packet bigger than 'max_allowed_packet' bytes",
+    )
+}
+
+// A 4.1 handshake response opensrv can parse, for an outdated client's: no user, and an
+// authentication response that keeps opensrv from asking for another method.
+fn stand_in_handshake(out: &mut Vec<u8>, sequence: u8) {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&(CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION).to_le_bytes());
+    payload.extend_from_slice(&0x00ff_ffffu32.to_le_bytes());
+    payload.push(UTF8_GENERAL_CI);
+    payload.extend_from_slice(&[0; 23]);
+    payload.push(0);
+    payload.extend_from_slice(&[1, 0]);
+    write_packet(out, sequence, &payload);
+}
+
+// The body of COM_CHANGE_USER: the user, the authentication response after its length, the
+// database, and then the character set, plugin and attributes, which aren't needed. Clients
+// speaking the 4.1 protocol all send the authentication response with its length first.
+fn change_user(body: &[u8]) -> Command {
+    let (user, rest) = null_terminated(body);
+    let (auth_response, rest) = match rest.split_first() {
+        Some((&length, rest)) => (
+            rest.get(..length as usize).unwrap_or_default(),
+            rest.get(length as usize..).unwrap_or_default(),
+        ),
+        None => (&[][..], rest),
+    };
+    let (database, _) = null_terminated(rest);
+    Command::ChangeUser {
+        user,
+        auth_response: auth_response.to_vec(),
+        database: Some(database).filter(|database| !database.is_empty()),
+    }
+}
+
+// The table, then the wildcard to the end of the packet.
+fn field_list(body: &[u8]) -> Command {
+    let (table, rest) = null_terminated(body);
+    Command::FieldList {
+        table,
+        wildcard: String::from_utf8_lossy(rest).into_owned(),
+    }
+}
+
+// The statement id and the number of rows.
+fn fetch(body: &[u8]) -> Command {
+    let number = |at: usize| {
+        body.get(at..at + 4)
+            .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    Command::Fetch {
+        statement: number(0),
+        rows: number(4),
+    }
+}
+
+// The connection attributes at the end of a 4.1 handshake response, after the capabilities, the
+// largest packet, the character set, a filler, the user, the authentication response, and the
+// database and authentication method when the capabilities say they are there.
+fn connect_attrs(response: &[u8]) -> Option<Vec<(String, String)>> {
+    let flags = u32::from_le_bytes(response.get(..4)?.try_into().ok()?);
+    let (_, rest) = null_terminated(response.get(32..)?);
+    let rest = if flags & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+        let (length, rest) = read_length_encoded(rest)?;
+        rest.get(length as usize..)?
+    } else if flags & CLIENT_SECURE_CONNECTION != 0 {
+        let (&length, rest) = rest.split_first()?;
+        rest.get(length as usize..)?
+    } else {
+        null_terminated(rest).1
+    };
+    let rest = match flags & CLIENT_CONNECT_WITH_DB != 0 && !rest.is_empty() {
+        true => null_terminated(rest).1,
+        false => rest,
+    };
+    let rest = match flags & CLIENT_PLUGIN_AUTH != 0 && !rest.is_empty() {
+        true => null_terminated(rest).1,
+        false => rest,
+    };
+    let (length, rest) = read_length_encoded(rest)?;
+    let mut attrs_data = rest.get(..length as usize)?;
+    let mut attrs = Vec::new();
+    let string = |data: &mut &[u8]| {
+        let (length, rest) = read_length_encoded(data)?;
+        let text = rest.get(..length as usize)?;
+        *data = &rest[length as usize..];
+        Some(String::from_utf8_lossy(text).into_owned())
+    };
+    while !attrs_data.is_empty() {
+        let name = string(&mut attrs_data)?;
+        let value = string(&mut attrs_data)?;
+        attrs.push((name, value));
+    }
+    Some(attrs)
+}
+
+// A 4.1 handshake response with an authentication response for another method than `plugin`,
+// with that response left out.
+fn without_auth_response(response: &[u8], plugin: &str) -> Option<Vec<u8>> {
+    let flags = u32::from_le_bytes(response.get(..4)?.try_into().ok()?);
+    let (_, rest) = null_terminated(response.get(32..)?);
+    let start = response.len() - rest.len();
+    let rest = if flags & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+        let (length, rest) = read_length_encoded(rest)?;
+        rest.get(length as usize..)?
+    } else if flags & CLIENT_SECURE_CONNECTION != 0 {
+        let (&length, rest) = rest.split_first()?;
+        rest.get(length as usize..)?
+    } else {
+        null_terminated(rest).1
+    };
+    let end = response.len() - rest.len();
+    let rest = match flags & CLIENT_CONNECT_WITH_DB != 0 {
+        true => null_terminated(rest).1,
+        false => rest,
+    };
+    let (sent_plugin, _) = null_terminated(rest);
+    if sent_plugin == plugin || end - start <= 1 {
+        return None;
+    }
+    // An empty response is a single 0 however its length is given.
+    let mut switched = response[..start].to_vec();
+    switched.push(0);
+    switched.extend_from_slice(&response[end..]);
+    Some(switched)
+}
+
+fn null_terminated(data: &[u8]) -> (String, &[u8]) {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let text = String::from_utf8_lossy(&data[..end]).into_owned();
+    (text, data.get(end + 1..).unwrap_or_default())
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Intercepted<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.offset < this.ready.len() {
+                let n = buf.remaining().min(this.ready.len() - this.offset);
+                buf.put_slice(&this.ready[this.offset..this.offset + n]);
+                this.offset += n;
+                return Poll::Ready(Ok(()));
+            }
+            this.ready.clear();
+            this.offset = 0;
+            this.process();
+            if !this.ready.is_empty() {
+                continue;
+            }
+            if this.eof {
+                // A packet cut short by the end of the stream goes through as it is.
+                if this.pending.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                this.ready = std::mem::take(&mut this.pending);
+                continue;
+            }
+            let mut chunk = [0; 8192];
+            let mut read = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            if read.filled().is_empty() {
+                this.eof = true;
+            } else {
+                this.pending.extend_from_slice(read.filled());
+            }
+        }
+    }
+}
+
+/// A connection's write half. It sends the replies the Backend queued in `Commands`, and puts
+/// the session's `Status` in every OK and EOF packet.
+pub struct Replies<W> {
+    inner: W,
+    commands: Arc<Commands>,
+    status: Arc<Status>,
+    // Bytes on their way to `inner`, from `offset` on.
+    sending: Vec<u8>,
+    offset: usize,
+    // The packet being written.
+    packet: Vec<u8>,
+    // Whether part of a reply has been written since the last flush, and what comes next in it.
+    replying: bool,
+    expect: Expect,
+    // The packet ending a result, held back until it is known whether another result follows.
+    last: Option<Vec<u8>>,
+    // Whether the handshake is over; until then the client has sent no commands.
+    logged_in: bool,
+    // Whether a flush has started, and so has already ended the reply.
+    flushing: bool,
+    // Whether the last packet was full, and so is continued by the next.
+    continued: bool,
+}
+
+// Where a reply is, as far as `Replies` is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    // The server greeting and authentication, ending in OK or ERR.
+    Handshake,
+    // A result: OK, ERR, or the column count of a result set.
+    Result,
+    // The column definitions of a result set still to come.
+    Columns(u64),
+    // The EOF after them, unless the client has deprecated it.
+    ColumnsEof,
+    // Rows until the EOF, or OK with EOF's header, that ends the result set.
+    Rows,
+    // COM_STMT_PREPARE's: the statement's id and counts, then column definitions for its
+    // parameters and its columns, each list followed by EOF. Definitions never start with 0xfe,
+    // so the EOFs can be told apart without counting.
+    Prepared,
+}
+
+impl<W> Replies<W> {
+    pub fn new(inner: W, commands: Arc<Commands>, status: Arc<Status>) -> Replies<W> {
+        Replies {
+            inner,
+            commands,
+            status,
+            sending: Vec::new(),
+            offset: 0,
+            packet: Vec::new(),
+            replying: false,
+            expect: Expect::Handshake,
+            last: None,
+            logged_in: false,
+            flushing: false,
+            continued: false,
+        }
+    }
+
+    // Takes the packets in `buf`.
+    fn take(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            let wanted = if self.packet.len() < 4 {
+                4 - self.packet.len()
+            } else {
+                4 + payload_length(&self.packet) - self.packet.len()
+            };
+            let (now, rest) = buf.split_at(wanted.min(buf.len()));
+            self.packet.extend_from_slice(now);
+            buf = rest;
+            if self.packet.len() >= 4 && self.packet.len() == 4 + payload_length(&self.packet) {
+                let packet = std::mem::take(&mut self.packet);
+                self.next_packet(packet);
+            }
+        }
+    }
+
+    fn next_packet(&mut self, mut packet: Vec<u8>) {
+        // The rest of a large row, whose first byte means nothing.
+        let continues = std::mem::replace(&mut self.continued, packet.len() == 4 + MAX_PAYLOAD);
+        if continues {
+            self.sending.extend_from_slice(&packet);
+            return;
+        }
+        let capabilities = self.commands.capabilities.load(Ordering::Relaxed);
+        // Another result follows the one held back.
+        if let Some(mut last) = self.last.take() {
+            self.status.apply(&mut last[4..], capabilities, true);
+            self.sending.extend_from_slice(&last);
+        }
+        if !self.replying {
+            self.replying = true;
+            self.expect = if !self.logged_in {
+                Expect::Handshake
+            } else if self.commands.replying_to() == Some(COM_STMT_PREPARE) {
+                Expect::Prepared
+            } else {
+                Expect::Result
+            };
+        }
+        let header = packet.get(4).copied();
+        // After the handshake nothing else starts with 0xff.
+        if header == Some(0xff) && self.logged_in {
+            self.status.apply_error_code(&mut packet[4..]);
+        } else if header == Some(0xff) && self.commands.outdated() {
+            let sequence = packet[3];
+            packet.clear();
+            write_packet(
+                &mut packet,
+                sequence,
+                &not_supported_auth_mode(capabilities),
+            );
+        } else if header == Some(0xff) {
+            if let Some(error) = self.commands.refusal.lock().unwrap().take() {
+                let sequence = packet[3];
+                packet.clear();
+                write_packet(&mut packet, sequence, &error_payload(&error, capabilities));
+            }
+        }
+        // opensrv's greeting, the first packet there is.
+        if !self.logged_in && header == Some(10) && packet[3] == 0 {
+            let tls = match self.commands.tls.load(Ordering::Relaxed) {
+                true => CLIENT_SSL,
+                false => 0,
+            };
+            offer_capabilities(
+                &mut packet,
+                CLIENT_LONG_PASSWORD | CLIENT_CONNECT_ATTRS | tls,
+            );
+        }
+        let payload = &mut packet[4..];
+        let mut ends_result = false;
+        match self.expect {
+            // The greeting, an authentication switch, or the OK or ERR that ends it.
+            Expect::Handshake => ends_result = header == Some(0x00),
+            Expect::Result => match header {
+                Some(0x00) => {
+                    length_encode_info(&mut packet, capabilities);
+                    ends_result = true;
+                }
+                // ERR, or a LOCAL INFILE request.
+                Some(0xff | 0xfb) | None => {}
+                Some(_) => {
+                    let columns = length_encoded_int(payload).unwrap_or(0);
+                    self.expect = Expect::Columns(columns);
+                }
+            },
+            Expect::Columns(left) => {
+                let results = self.commands.charsets().results_collation();
+                fix_column_collation(payload, results);
+                self.expect = if left > 1 {
+                    Expect::Columns(left - 1)
+                } else if capabilities & CLIENT_DEPRECATE_EOF != 0 {
+                    Expect::Rows
+                } else {
+                    Expect::ColumnsEof
+                };
+            }
+            Expect::ColumnsEof => {
+                self.status.apply(payload, capabilities, false);
+                self.expect = Expect::Rows;
+            }
+            // A row can only start with 0xfe if it is at least 16MB, in which case it fills the
+            // packet.
+            Expect::Rows => match header {
+                Some(0xfe) if payload.len() < 0xff_ffff => {
+                    ends_result = true;
+                    self.expect = Expect::Result;
+                }
+                Some(0xff) => self.expect = Expect::Result,
+                _ => {}
+            },
+            Expect::Prepared => match header {
+                // The warning count follows the id, the counts and a filler byte.
+                Some(0x00) => self.status.apply_warnings(payload, 10),
+                Some(0xfe) if payload.len() == 5 => self.status.apply(payload, capabilities, false),
+                _ => {
+                    let results = self.commands.charsets().results_collation();
+                    fix_column_collation(payload, results);
+                }
+            },
+        }
+        if ends_result {
+            self.last = Some(packet);
+        } else {
+            self.sending.extend_from_slice(&packet);
+        }
+    }
+
+    // opensrv flushes once at the end of each reply, and for commands without one.
+    fn end_reply(&mut self) {
+        if self.logged_in {
+            self.commands.answered();
+        }
+        if let Some(mut last) = self.last.take() {
+            let capabilities = self.commands.capabilities.load(Ordering::Relaxed);
+            self.status.apply(&mut last[4..], capabilities, false);
+            self.sending.extend_from_slice(&last);
+            // Only the OK at the end of the handshake is held back in it.
+            self.logged_in = true;
+        }
+        self.replying = false;
+    }
+}
+
+/// Adds `capabilities` to those of `greeting`, the server's first packet: after the protocol
+/// version, the server's version, the connection id, the first part of the salt and a filler
+/// come the lower two bytes of the capabilities, and after the character set and status the
+/// upper two.
+pub fn offer_capabilities(greeting: &mut [u8], capabilities: u32) {
+    let Some(end) = greeting.iter().skip(5).position(|&b| b == 0) else {
+        return;
+    };
+    let lower = 5 + end + 1 + 4 + 8 + 1;
+    let upper = lower + 2 + 1 + 2;
+    if greeting.len() < upper + 2 {
+        return;
+    }
+    let offered = u32::from_le_bytes([
+        greeting[lower],
+        greeting[lower + 1],
+        greeting[upper],
+        greeting[upper + 1],
+    ]) | capabilities;
+    let bytes = offered.to_le_bytes();
+    greeting[lower..lower + 2].copy_from_slice(&bytes[..2]);
+    greeting[upper..upper + 2].copy_from_slice(&bytes[2..]);
+}
+
+// The ERR packet refusing an outdated client. Clients before 4.1 read no SQLSTATE.
+fn not_supported_auth_mode(capabilities: u32) -> Vec<u8> {
+    let mut payload = vec![0xff];
+    payload.extend_from_slice(&ER_NOT_SUPPORTED_AUTH_MODE.to_le_bytes());
+    if capabilities & CLIENT_PROTOCOL_41 != 0 {
+        payload.extend_from_slice(b"#08004");
+    }
+    payload.extend_from_slice(NOT_SUPPORTED_AUTH_MODE.as_bytes());
+    payload
+}
+
+fn error_payload(error: &MysqlError, capabilities: u32) -> Vec<u8> {
+    let mut payload = vec![0xff];
+    payload.extend_from_slice(&error.code().to_le_bytes());
+    if capabilities & CLIENT_PROTOCOL_41 != 0 {
+        payload.push(b'#');
+        payload.extend_from_slice(error.kind.sqlstate());
+    }
+    payload.extend_from_slice(error.message.as_bytes());
+    payload
+}
+
+/// Refuses a client that has just connected with `error`, sent in place of the server's
+/// greeting, as MySQL refuses one over max_connections, and closes the connection. Its
+/// capabilities aren't known yet, so the error goes without its SQLSTATE.
+pub async fn refuse<W: AsyncWrite + Unpin>(mut writer: W, error: &MysqlError) -> io::Result<()> {
+    let mut packet = Vec::new();
+    write_packet(&mut packet, 0, &error_payload(error, 0));
+    writer.write_all(&packet).await?;
+    writer.shutdown().await
+}
+
+/// The length of the payload of `packet`, from its header.
+pub fn payload_length(packet: &[u8]) -> usize {
+    u32::from_le_bytes([packet[0], packet[1], packet[2], 0]) as usize
+}
+
+/// Whether `packet`, the client's first, with its header, is an SSLRequest: the capabilities,
+/// CLIENT_SSL among them, the largest packet and the collation of a 4.1 handshake response,
+/// without the rest.
+pub fn is_ssl_request(packet: &[u8]) -> bool {
+    packet.len() >= 8
+        && payload_length(packet) == 32
+        && u32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]) & CLIENT_SSL != 0
+}
+
+// A length-encoded integer, and what follows it.
+fn read_length_encoded(data: &[u8]) -> Option<(u64, &[u8])> {
+    let value = length_encoded_int(data)?;
+    let width = match data[0] {
+        0xfc => 3,
+        0xfd => 4,
+        0xfe => 9,
+        _ => 1,
+    };
+    Some((value, &data[width..]))
+}
+
+fn length_encoded_int(payload: &[u8]) -> Option<u64> {
+    let (&first, rest) = payload.split_first()?;
+    let width = match first {
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => return Some(first.into()),
+    };
+    let mut bytes = [0; 8];
+    bytes[..width].copy_from_slice(rest.get(..width)?);
+    Some(u64::from_le_bytes(bytes))
+}
+
+impl<W: AsyncWrite + Unpin> Replies<W> {
+    // Sends what has been taken, and then the queued replies.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.offset == self.sending.len() {
+                self.sending.clear();
+                self.offset = 0;
+                self.sending
+                    .append(&mut self.commands.replies.lock().unwrap());
+                if self.sending.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            let written =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.sending[self.offset..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.offset += written;
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Replies<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_send(cx))?;
+        self.take(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.flushing {
+            self.end_reply();
+            self.flushing = true;
+        }
+        ready!(self.poll_send(cx))?;
+        let flushed = ready!(Pin::new(&mut self.inner).poll_flush(cx));
+        self.flushing = false;
+        Poll::Ready(flushed)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(last) = self.last.take() {
+            self.sending.extend_from_slice(&last);
+        }
+        ready!(self.poll_send(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,146 @@
+// Reconnecting to PostgreSQL when a connection's session is lost, to a restart or a failover
+// say, so the client's next statements run again rather than failing until it reconnects
+// itself.
+//
+// A connection's session is checked before each command it runs, and one found closed is
+// replaced. Sessions are opened, for new clients as well, with DB_RECONNECT_ATTEMPTS more tries
+// (5 by default; 0 tries once) should the first fail, backing off from 100 ms and doubling the
+// wait each time up to 5 s. A session an Upstream hands out already closed, a pooled one whose
+// server went away say, counts as a failed try.
+//
+// The new session is set up as far as the proxy knows the old one: the current database and the
+// prepared statements are restored. The transaction that was open is rolled back with the old
+// session, and temporary tables, session settings and user-level locks are gone, as with MySQL's
+// auto-reconnect. The statement that finds a transaction's session lost, or else the next one,
+// fails with error 1213, as a deadlock's victim is told its transaction was rolled back, so the
+// client doesn't go on as if the transaction's earlier statements had taken effect. A ROLLBACK
+// is let through instead, and the statements after it run on the new session as usual.
+//
+// A statement whose session is lost while it runs is run again on the new one, once, if that is
+// safe: a SELECT outside a transaction that neither locks rows nor calls a function with side
+// effects. Any other fails with the error the session was lost with, since whether it took
+// effect can't be told, unless it is a write keyed as idempotency.rs describes.
+
+use std::error::Error;
+use std::time::Duration;
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::logging::Logger;
+use crate::server::{Session, Upstream};
+use crate::translator::{self, Token};
+
+pub const DEFAULT_ATTEMPTS: u32 = 5;
+
+const FIRST_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+// Functions a SELECT can call that change something, and so mustn't be run twice.
+const SIDE_EFFECTS: [&str; 4] = [
+    "nextval",
+    "setval",
+    "pg_advisory_lock",
+    "pg_advisory_xact_lock",
+];
+
+/// A session from `upstream`, tried `attempts` more times if it can't be opened.
+pub async fn connect(
+    upstream: &dyn Upstream,
+    attempts: u32,
+    log: Logger,
+) -> Result<Session, Box<dyn Error + Send + Sync>> {
+    let mut backoff = FIRST_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let error = match upstream.connect().await {
+            Ok(session) if !session.is_closed() => return Ok(session),
+            Ok(_) => "the session was closed".into(),
+            Err(e) => e,
+        };
+        if attempt == attempts {
+            return Err(error);
+        }
+        attempt += 1;
+        log.info(format_args!(
+            "Failed to connect to PostgreSQL ({}), trying again in {:?}",
+            error, backoff
+        ));
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Whether `sql`, a translated statement, can be run again after its session was lost, outside
+/// a transaction.
+pub fn retryable(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    let Some(first) = tokens.first() else {
+        return false;
+    };
+    if !first.is_word("SELECT") {
+        return false;
+    }
+    let locks = tokens.windows(2).any(|pair| {
+        pair[0].is_word("FOR")
+            && ["UPDATE", "SHARE", "NO", "KEY"]
+                .iter()
+                .any(|word| pair[1].is_word(word))
+    });
+    let side_effects = tokens.windows(2).any(|pair| {
+        matches!(pair[1], Token::LParen)
+            && SIDE_EFFECTS
+                .iter()
+                .any(|function| pair[0].is_word(function))
+    });
+    // Nor a script, whose other statements could be anything.
+    let script = tokens.iter().any(|token| matches!(token, Token::Semicolon));
+    !locks && !side_effects && !script && !tokens.iter().any(|token| token.is_word("INTO"))
+}
+
+/// What the first statement after the session was lost in a transaction fails with.
+pub fn rolled_back() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_LOCK_DEADLOCK,
+        "Lost the connection to PostgreSQL in a transaction, which was rolled back; try \
+         restarting transaction",
+    )
+}
+
+/// Whether `sql` is a plain ROLLBACK, which the loss of a transaction needn't fail.
+pub fn is_rollback(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    match tokens.as_slice() {
+        [rollback] | [rollback, Token::Semicolon] => rollback.is_word("ROLLBACK"),
+        [rollback, work] | [rollback, work, Token::Semicolon] => {
+            rollback.is_word("ROLLBACK") && work.is_word("WORK")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_only_plain_selects() {
+        assert!(retryable("SELECT * FROM t WHERE a = 1"));
+        assert!(!retryable("SELECT * FROM t FOR UPDATE"));
+        assert!(!retryable("SELECT nextval('s')"));
+        assert!(!retryable("SELECT 1; DELETE FROM t"));
+        assert!(!retryable("INSERT INTO t VALUES (1)"));
+    }
+
+    #[test]
+    fn recognizes_a_plain_rollback() {
+        assert!(is_rollback("ROLLBACK"));
+        assert!(is_rollback("rollback work;"));
+        assert!(!is_rollback("ROLLBACK TO SAVEPOINT s"));
+        assert!(!is_rollback("COMMIT"));
+    }
+}
@@ -0,0 +1,398 @@
+// An in-memory cache of SELECT results, for dashboards and the like that run the same queries
+// over and over:
+//
+//   RESULT_CACHE_RULES  "orders:5, daily_totals:300, reports.*:60"
+//   RESULT_CACHE_SIZE   the results kept at most, 1024 by default
+//
+// A rule is a table, `table` in any database or `db.table`, or `*` for every table (`db.*` for
+// a database's), and the seconds a result read from it is kept. A SELECT is cached when every
+// table it reads has a rule, for the shortest of their times; one that reads no table isn't. A
+// result is kept by the statement as it runs on PostgreSQL, without its comments and spacing,
+// with the current database, the parameters of an executed prepared statement, and what of the
+// session the result depends on: the user and the PostgreSQL role they read as, the time zone,
+// the sql_mode and date modes, the character sets and the settings made through GUC_MAPPINGS.
+// Sessions differing in any of them don't share results.
+//
+// A statement that writes through the proxy drops the cached results of the tables it writes,
+// as it runs and again as its transaction ends, since other connections could have cached what
+// was there until it committed. DDL, and CALL, whose writes can't be told, drop every result,
+// as do RESET QUERY CACHE and FLUSH QUERY CACHE. Writes PostgreSQL gets otherwise, from other
+// clients or triggers or to the tables under a view, show when the results expire.
+//
+// Nothing is cached or served from the cache in a transaction, whose reads see its own writes;
+// nor a SELECT with SQL_NO_CACHE, a locking clause, INTO, variables, or functions whose result
+// changes from call to call, like NOW() and RAND(); nor a result of more than 10000 rows.
+//
+// The SELECTs answered from the cache and those run to be cached, and the results dropped to
+// make room, are counted in proxy_stats metrics, as result_cache_hits_total,
+// result_cache_misses_total and result_cache_evictions_total, and shown by SHOW PROXY CACHES.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mysql_common::value::Value;
+use opensrv_mysql::Column;
+use tokio_postgres::types::ToSql;
+
+use crate::catalog::ObjectName;
+use crate::policy;
+use crate::stats::{table_access, Stats};
+use crate::translator::{self, Token};
+
+pub const DEFAULT_SIZE: usize = 1024;
+
+// Larger results aren't worth the memory.
+pub const MAX_ROWS: usize = 10_000;
+
+// Functions whose result changes from call to call, or with the session.
+const VOLATILE: &[&str] = &[
+    "NOW",
+    "SYSDATE",
+    "CURDATE",
+    "CURTIME",
+    "UTC_DATE",
+    "UTC_TIME",
+    "UTC_TIMESTAMP",
+    "UNIX_TIMESTAMP",
+    "RAND",
+    "UUID",
+    "UUID_SHORT",
+    "CONNECTION_ID",
+    "LAST_INSERT_ID",
+    "ROW_COUNT",
+    "FOUND_ROWS",
+    "USER",
+    "SESSION_USER",
+    "SYSTEM_USER",
+    "DATABASE",
+    "SCHEMA",
+    "GET_LOCK",
+    "RELEASE_LOCK",
+    "IS_FREE_LOCK",
+    "IS_USED_LOCK",
+    "SLEEP",
+    "BENCHMARK",
+    "NEXTVAL",
+];
+
+// And those that needn't be called with parentheses.
+const VOLATILE_WORDS: &[&str] = &[
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+    "CURRENT_USER",
+];
+
+/// How long results read from a table, or from any table, are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheRule {
+    // None for any database, or any table.
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub ttl: Duration,
+}
+
+impl CacheRule {
+    /// The rule for `table`, as RESULT_CACHE_RULES names it: `table`, `db.table`, `*` or `db.*`.
+    pub fn new(table: &str, ttl: Duration) -> CacheRule {
+        let table = table.trim().to_lowercase();
+        let (schema, name) = match table.split_once('.') {
+            Some((schema, name)) => (Some(schema.to_string()), name.to_string()),
+            None => (None, table),
+        };
+        CacheRule {
+            schema,
+            table: (name != "*").then_some(name),
+            ttl,
+        }
+    }
+
+    fn covers(&self, schema: Option<&str>, table: &str) -> bool {
+        self.schema.as_deref().is_none_or(|s| Some(s) == schema)
+            && self.table.as_deref().is_none_or(|t| t == table)
+    }
+}
+
+/// The rules (RESULT_CACHE_RULES) and how many results are kept (RESULT_CACHE_SIZE).
+#[derive(Debug, Clone)]
+pub struct ResultCacheConfig {
+    pub rules: Vec<CacheRule>,
+    pub size: usize,
+}
+
+/// A result as it was sent to the client.
+pub struct CachedResult {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    database: Option<String>,
+    sql: String,
+    params: String,
+    // What of the session the result depends on.
+    session: String,
+}
+
+/// A statement whose result is cached: where, for how long, and the tables it reads.
+pub struct Cacheable {
+    key: Key,
+    ttl: Duration,
+    tables: Vec<ObjectName>,
+}
+
+struct Entry {
+    result: Arc<CachedResult>,
+    tables: Vec<ObjectName>,
+    expires: Instant,
+}
+
+/// The tables a statement writes, or all of them when that can't be told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Written {
+    Tables(Vec<ObjectName>),
+    All,
+}
+
+/// The cached results, shared by all connections.
+pub(crate) struct ResultCache {
+    config: ResultCacheConfig,
+    entries: Mutex<HashMap<Key, Entry>>,
+    stats: Arc<Stats>,
+}
+
+impl ResultCache {
+    pub fn new(config: ResultCacheConfig, stats: Arc<Stats>) -> ResultCache {
+        ResultCache {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            stats,
+        }
+    }
+
+    /// The results kept, and how many may be.
+    pub fn size(&self) -> (usize, usize) {
+        (self.entries.lock().unwrap().len(), self.config.size)
+    }
+
+    /// Whether the result of `sql`, a statement as the client sent it, run as `translated` with
+    /// `params` in `database` by a session in the state `session` describes, is one to cache,
+    /// and how.
+    pub fn cacheable(
+        &self,
+        sql: &str,
+        translated: &str,
+        params: &[&(dyn ToSql + Sync)],
+        database: Option<&str>,
+        session: &str,
+    ) -> Option<Cacheable> {
+        let tokens = translator::significant_tokens(sql)?;
+        if !tokens.first().is_some_and(|t| t.is_word("SELECT")) || !deterministic(&tokens) {
+            return None;
+        }
+        let tables: Vec<ObjectName> = table_access(&tokens)?
+            .into_iter()
+            .map(|(table, _)| resolve(table, database))
+            .collect();
+        let ttl = tables
+            .iter()
+            .map(|table| {
+                self.config
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.covers(table.schema.as_deref(), &table.name))
+                    .map(|rule| rule.ttl)
+                    .min()
+            })
+            .collect::<Option<Vec<Duration>>>()?
+            .into_iter()
+            .min()?;
+        let translated = translator::significant_tokens(translated)?;
+        Some(Cacheable {
+            key: Key {
+                database: database.map(str::to_string),
+                sql: translated
+                    .iter()
+                    .map(Token::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                params: format!("{:?}", params),
+                session: session.to_string(),
+            },
+            ttl,
+            tables,
+        })
+    }
+
+    /// The result kept for `cacheable`, unless it has expired.
+    pub fn get(&self, cacheable: &Cacheable) -> Option<Arc<CachedResult>> {
+        let entries = self.entries.lock().unwrap();
+        let result = entries
+            .get(&cacheable.key)
+            .filter(|entry| entry.expires > Instant::now())
+            .map(|entry| Arc::clone(&entry.result));
+        self.stats.record_result_cache(result.is_some());
+        result
+    }
+
+    /// Keeps `result`, unless it is too big. When the cache is full, expired results go first,
+    /// and then the one closest to expiring.
+    pub fn put(&self, cacheable: Cacheable, result: CachedResult) {
+        if result.rows.len() > MAX_ROWS || self.config.size == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.size && !entries.contains_key(&cacheable.key) {
+            entries.retain(|_, entry| entry.expires > now);
+            if entries.len() >= self.config.size {
+                let soonest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires)
+                    .map(|(key, _)| key.clone());
+                if let Some(key) = soonest {
+                    entries.remove(&key);
+                    self.stats.record_result_cache_eviction();
+                }
+            }
+        }
+        entries.insert(
+            cacheable.key,
+            Entry {
+                result: Arc::new(result),
+                tables: cacheable.tables,
+                expires: now + cacheable.ttl,
+            },
+        );
+    }
+
+    /// Drops the results read from what `written` wrote.
+    pub fn invalidate(&self, written: &Written) {
+        let mut entries = self.entries.lock().unwrap();
+        match written {
+            Written::All => entries.clear(),
+            Written::Tables(tables) => {
+                entries.retain(|_, entry| !entry.tables.iter().any(|t| tables.contains(t)))
+            }
+        }
+    }
+}
+
+/// What `sql`, a statement as the client sent it that succeeded in `database`, wrote; None if
+/// it only read.
+pub fn written(sql: &str, database: Option<&str>) -> Option<Written> {
+    if !policy::writes_data(sql) {
+        return None;
+    }
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return Some(Written::All);
+    };
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    // A script could write anything.
+    if tokens.contains(&Token::Semicolon) {
+        return Some(Written::All);
+    }
+    let dml = ["INSERT", "REPLACE", "UPDATE", "DELETE", "TRUNCATE"]
+        .iter()
+        .any(|word| tokens.first().is_some_and(|t| t.is_word(word)));
+    // Tables that can't be told could be any.
+    let tables: Vec<ObjectName> = table_access(tokens)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, write)| *write)
+        .map(|(table, _)| resolve(table, database))
+        .collect();
+    match dml && !tables.is_empty() {
+        true => Some(Written::Tables(tables)),
+        false => Some(Written::All),
+    }
+}
+
+/// Whether `sql` is RESET QUERY CACHE or FLUSH QUERY CACHE.
+pub fn is_reset(sql: &str) -> bool {
+    let Some(tokens) = translator::significant_tokens(sql) else {
+        return false;
+    };
+    matches!(
+        tokens.as_slice(),
+        [verb, query, cache] | [verb, query, cache, Token::Semicolon]
+            if (verb.is_word("RESET") || verb.is_word("FLUSH"))
+                && query.is_word("QUERY")
+                && cache.is_word("CACHE")
+    )
+}
+
+// `table`, in `database` if it doesn't name its own.
+fn resolve(table: ObjectName, database: Option<&str>) -> ObjectName {
+    ObjectName {
+        schema: table.schema.or_else(|| database.map(str::to_lowercase)),
+        name: table.name,
+    }
+}
+
+// Whether a SELECT gives the same result run again on the same data.
+fn deterministic(tokens: &[Token]) -> bool {
+    !tokens.iter().enumerate().any(|(i, token)| {
+        let next = tokens.get(i + 1);
+        let next_is = |word: &str| next.is_some_and(|t| t.is_word(word));
+        match token {
+            Token::Variable(_) => true,
+            Token::Word(word) => {
+                let word = word.to_ascii_uppercase();
+                match word.as_str() {
+                    "SQL_NO_CACHE" | "INTO" => true,
+                    "FOR" => next_is("UPDATE") || next_is("SHARE"),
+                    "LOCK" => next_is("IN"),
+                    word if VOLATILE_WORDS.contains(&word) => true,
+                    word => VOLATILE.contains(&word) && matches!(next, Some(Token::LParen)),
+                }
+            }
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> ResultCache {
+        let config = ResultCacheConfig {
+            rules: vec![CacheRule::new("*", Duration::from_secs(60))],
+            size: 10,
+        };
+        ResultCache::new(config, Arc::new(Stats::default()))
+    }
+
+    fn cacheable(cache: &ResultCache, session: &str) -> Cacheable {
+        let sql = "SELECT * FROM orders";
+        cache
+            .cacheable(sql, sql, &[], Some("shop"), session)
+            .unwrap()
+    }
+
+    #[test]
+    fn sessions_in_another_state_dont_share_results() {
+        let cache = cache();
+        let result = CachedResult {
+            columns: Vec::new(),
+            rows: vec![vec![Value::Int(1)]],
+        };
+        cache.put(cacheable(&cache, "alice reader UTC"), result);
+        assert!(cache.get(&cacheable(&cache, "alice reader UTC")).is_some());
+        for session in ["bob reader UTC", "alice admin UTC", "alice reader +02:00"] {
+            assert!(
+                cache.get(&cacheable(&cache, session)).is_none(),
+                "{}",
+                session
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Result sets produced by the proxy itself (emulated SHOW commands and the like) rather than
+// read from PostgreSQL. Every column is sent as text, which is what MySQL does for SHOW output.
+
+use std::io;
+
+use opensrv_mysql::{Column, ColumnFlags, ColumnType, QueryResultWriter};
+use tokio::io::AsyncWrite;
+
+pub struct ResultSet {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl ResultSet {
+    pub fn new<S: AsRef<str>>(columns: &[S]) -> Self {
+        ResultSet {
+            columns: columns
+                .iter()
+                .map(|name| Column {
+                    table: String::new(),
+                    column: name.as_ref().to_string(),
+                    coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
+                    colflags: ColumnFlags::empty(),
+                })
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push_row(&mut self, row: Vec<Option<String>>) {
+        self.rows.push(row);
+    }
+
+    pub async fn write<'a, W: AsyncWrite + Send + Unpin>(
+        &'a self,
+        results: QueryResultWriter<'a, W>,
+    ) -> io::Result<()> {
+        let mut writer = results.start(&self.columns).await?;
+        for row in &self.rows {
+            writer.write_row(row).await?;
+        }
+        writer.finish().await
+    }
+}
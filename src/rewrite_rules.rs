@@ -0,0 +1,372 @@
+// Rewrite rules from a file (REWRITE_RULES), for changing statements before they are translated
+// without writing a QueryInterceptor in Rust. The file is TOML:
+//
+//   # The schema of the tables the statement doesn't name a schema for.
+//   schema = "app"
+//
+//   # Regular expressions and their replacements, tried in order on the statement's text.
+//   # $1 and ${name} in a replacement are the pattern's groups.
+//   [[rule]]
+//   pattern = '(?i)\bFROM old_orders\b'
+//   replacement = "FROM orders"
+//
+//   # Tables known by another name or in another schema.
+//   [tables]
+//   customers = "clients"
+//   legacy.items = "catalog.items"
+//
+// The rules run first, then the tables are renamed, then the schema is filled in. A pattern
+// matches anywhere in the text, string literals and comments included. The tables, unlike the
+// rules, are only renamed where the statement refers to a table: after FROM, JOIN, INTO, UPDATE,
+// TABLE and the like, in the lists of tables after FROM and TABLE, and as the qualifier of a
+// column, as in old_name.id. Names of common table expressions aren't given the schema.
+//
+// The proxy rereads the file on SIGHUP. Should the new file be invalid the rules already loaded
+// stay in use.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::{Arc, RwLock};
+
+use regex::Regex;
+use toml_edit::{Document, Item, Table};
+
+use crate::config::ConfigError;
+use crate::error::MysqlError;
+use crate::intercept::{Context, QueryInterceptor};
+use crate::logging::Logger;
+use crate::translator::{self, literals, Node, Token};
+
+// The words after which a name is a table.
+const BEFORE_TABLE: &[&str] = &[
+    "FROM",
+    "JOIN",
+    "STRAIGHT_JOIN",
+    "INTO",
+    "UPDATE",
+    "TABLE",
+    "TABLES",
+    "REFERENCES",
+    "TRUNCATE",
+    "EXISTS",
+];
+
+// The words that start the lists of tables separated by commas.
+const TABLE_LISTS: &[&str] = &["FROM", "TABLE", "TABLES"];
+
+// The words that end a list of tables.
+const AFTER_TABLES: &[&str] = &[
+    "WHERE",
+    "GROUP",
+    "HAVING",
+    "ORDER",
+    "LIMIT",
+    "WINDOW",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "SET",
+    "VALUES",
+    "SELECT",
+    "FOR",
+    "INTO",
+];
+
+// Words after those of BEFORE_TABLE that aren't tables, as in CREATE TABLE IF NOT EXISTS and
+// SELECT ... FOR UPDATE NOWAIT.
+const NOT_TABLES: &[&str] = &[
+    "IF", "TABLE", "SELECT", "WITH", "LATERAL", "ONLY", "OUTFILE", "DUMPFILE", "FROM", "LIKE",
+    "WHERE", "NOWAIT", "SKIP", "OF",
+];
+
+/// The rules of a rules file.
+#[derive(Debug, Default)]
+pub struct Rules {
+    patterns: Vec<(Regex, String)>,
+    // By the table's name, or `schema.table`, in lower case.
+    tables: HashMap<String, String>,
+    schema: Option<String>,
+}
+
+impl Rules {
+    /// Reads the rules file at `path`.
+    pub fn load(path: &str) -> Result<Rules, ConfigError> {
+        let file_error = |error: String| ConfigError::File {
+            path: path.to_string(),
+            error,
+        };
+        let text = fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+        let document: Document = text.parse().map_err(|e| file_error(format!("{}", e)))?;
+
+        let mut rules = Rules::default();
+        for (key, item) in document.iter() {
+            match (key, item) {
+                ("rule", Item::ArrayOfTables(array)) => {
+                    for table in array.iter() {
+                        rules.patterns.push(pattern(table).map_err(file_error)?);
+                    }
+                }
+                ("tables", Item::Table(table)) => {
+                    names(table, "", &mut rules.tables).map_err(file_error)?
+                }
+                ("schema", item) => match item.as_str() {
+                    Some(schema) => rules.schema = Some(schema.to_string()),
+                    None => return Err(file_error("schema isn't a string".to_string())),
+                },
+                ("rule" | "tables", _) => return Err(file_error(format!("{} isn't a table", key))),
+                _ => {
+                    return Err(ConfigError::Unknown {
+                        path: path.to_string(),
+                        key: key.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// How many rules there are: patterns, renamed tables and the default schema.
+    pub fn count(&self) -> usize {
+        self.patterns.len() + self.tables.len() + usize::from(self.schema.is_some())
+    }
+
+    /// `sql` with the rules applied, or `None` if none of them changed it.
+    pub fn apply(&self, sql: &str) -> Option<String> {
+        let mut text = sql.to_string();
+        for (regex, replacement) in &self.patterns {
+            text = regex.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        if !self.tables.is_empty() || self.schema.is_some() {
+            // SHOW names databases and tables its own way, and is answered by the proxy.
+            let show = translator::significant_tokens(&text)
+                .is_some_and(|tokens| tokens.first().is_some_and(|t| t.is_word("SHOW")));
+            if let (Ok(nodes), false) = (translator::parse(&text), show) {
+                let mut ctes = HashSet::new();
+                cte_names(&nodes, &mut ctes);
+                text = translator::render(&self.rename(nodes, true, &ctes));
+            }
+        }
+        (text != sql).then_some(text)
+    }
+
+    // Renames and qualifies the tables `nodes` refer to, subqueries included. `query` is false
+    // for the arguments of a function and other groups where FROM doesn't name a table, as in
+    // EXTRACT(YEAR FROM d).
+    fn rename(&self, nodes: Vec<Node>, query: bool, ctes: &HashSet<String>) -> Vec<Node> {
+        let mut nodes: Vec<Node> = nodes
+            .into_iter()
+            .map(|node| match node {
+                Node::Group(inner) => {
+                    let subquery = inner.iter().find(|n| !n.is_trivia()).is_some_and(
+                        |n| matches!(n, Node::Token(t) if t.is_word("SELECT") || t.is_word("WITH")),
+                    );
+                    Node::Group(self.rename(inner, subquery, ctes))
+                }
+                token => token,
+            })
+            .collect();
+        if !query {
+            return nodes;
+        }
+
+        let significant: Vec<usize> = (0..nodes.len())
+            .filter(|&i| !nodes[i].is_trivia())
+            .collect();
+        let token = |k: usize| match significant.get(k).map(|&i| &nodes[i]) {
+            Some(Node::Token(t)) => Some(t.clone()),
+            _ => None,
+        };
+        // The nodes from one index to another, replaced by a name.
+        let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+        let mut in_list = false;
+        let mut k = 0;
+        while k < significant.len() {
+            let Some(current) = token(k) else {
+                k += 1;
+                continue;
+            };
+            let previous = k.checked_sub(1).and_then(token);
+            // Not the UPDATE of ON DUPLICATE KEY UPDATE and FOR UPDATE.
+            let update_clause = k
+                .checked_sub(2)
+                .and_then(token)
+                .is_some_and(|t| t.is_word("KEY") || t.is_word("FOR"));
+            let at_table = match &previous {
+                Some(Token::Comma) => in_list,
+                Some(word) if word.is_word("UPDATE") => !update_clause,
+                Some(word) => BEFORE_TABLE.iter().any(|w| word.is_word(w)),
+                None => false,
+            };
+            if TABLE_LISTS.iter().any(|w| current.is_word(w)) {
+                in_list = true;
+            } else if AFTER_TABLES.iter().any(|w| current.is_word(w)) {
+                in_list = false;
+            }
+            let Some(name) = literals::identifier_name(&current) else {
+                k += 1;
+                continue;
+            };
+            let dotted = token(k + 1).is_some_and(|t| t.is_operator("."));
+
+            if at_table && !NOT_TABLES.iter().any(|w| current.is_word(w)) {
+                // `schema.table`, or a table of the current schema.
+                let (reference, last) = match token(k + 2).filter(|_| dotted) {
+                    Some(table) => match literals::identifier_name(&table) {
+                        Some(table) => (format!("{}.{}", name, table), k + 2),
+                        None => (name, k),
+                    },
+                    None => (name, k),
+                };
+                let qualified = last > k;
+                let replacement = match self.tables.get(&reference) {
+                    Some(target) if target.contains('.') || qualified => Some(target.clone()),
+                    Some(target) => Some(self.qualify(target, ctes)),
+                    None if !qualified && self.schema.is_some() && !ctes.contains(&reference) => {
+                        Some(self.qualify(&current.to_string(), ctes))
+                    }
+                    None => None,
+                };
+                if let Some(replacement) = replacement {
+                    replacements.push((significant[k], significant[last], replacement));
+                }
+                k = last + 1;
+                continue;
+            }
+
+            // The qualifier of a column, as in old_name.id, takes the table's new name without
+            // its schema.
+            let after_dot = previous.is_some_and(|t| t.is_operator("."));
+            if dotted && !after_dot {
+                if let Some(target) = self.tables.get(&name) {
+                    let table = target.rsplit('.').next().unwrap_or(target);
+                    replacements.push((significant[k], significant[k], table.to_string()));
+                }
+            }
+            k += 1;
+        }
+
+        for (from, to, name) in replacements.into_iter().rev() {
+            nodes.splice(from..=to, [Node::Token(Token::Word(name))]);
+        }
+        nodes
+    }
+
+    // `table` in the configured schema, if there is one.
+    fn qualify(&self, table: &str, ctes: &HashSet<String>) -> String {
+        match &self.schema {
+            Some(schema) if !ctes.contains(&table.to_lowercase()) => {
+                format!("{}.{}", schema, table)
+            }
+            _ => table.to_string(),
+        }
+    }
+}
+
+// A [[rule]] of the file.
+fn pattern(table: &Table) -> Result<(Regex, String), String> {
+    if let Some((key, _)) = table
+        .iter()
+        .find(|(key, _)| !["pattern", "replacement"].contains(key))
+    {
+        return Err(format!("unknown key in a rule: {}", key));
+    }
+    let text = |key: &str| {
+        table
+            .get(key)
+            .and_then(Item::as_str)
+            .ok_or_else(|| format!("a rule needs a {} string", key))
+    };
+    let regex = Regex::new(text("pattern")?).map_err(|e| e.to_string())?;
+    Ok((regex, text("replacement")?.to_string()))
+}
+
+// The renames of [tables], `schema.table` keys being dotted keys or subtables.
+fn names(table: &Table, prefix: &str, tables: &mut HashMap<String, String>) -> Result<(), String> {
+    for (key, item) in table.iter() {
+        let name = format!("{}{}", prefix, key.to_lowercase());
+        match item {
+            Item::Table(inner) => names(inner, &format!("{}.", name), tables)?,
+            _ => {
+                let target = item
+                    .as_str()
+                    .ok_or_else(|| format!("the new name of {} isn't a string", name))?;
+                tables.insert(name, target.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+// The names `WITH name AS (...)` gives common table expressions.
+fn cte_names(nodes: &[Node], names: &mut HashSet<String>) {
+    let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+    for window in significant.windows(3) {
+        if let [Node::Token(name), Node::Token(as_), Node::Group(_)] = window {
+            if as_.is_word("AS") {
+                if let Some(name) = literals::identifier_name(name) {
+                    names.insert(name);
+                }
+            }
+        }
+    }
+    for node in nodes {
+        if let Node::Group(inner) = node {
+            cte_names(inner, names);
+        }
+    }
+}
+
+/// The rules file as a QueryInterceptor. Clones share the rules, so one can reload them for
+/// all.
+#[derive(Clone)]
+pub struct RuleFile {
+    path: String,
+    rules: Arc<RwLock<Arc<Rules>>>,
+}
+
+impl RuleFile {
+    pub fn open(path: &str) -> Result<RuleFile, ConfigError> {
+        Ok(RuleFile {
+            path: path.to_string(),
+            rules: Arc::new(RwLock::new(Arc::new(Rules::load(path)?))),
+        })
+    }
+
+    /// Rereads the file, keeping the rules it had if the file is invalid now.
+    pub fn reload(&self) -> Result<(), ConfigError> {
+        let rules = Rules::load(&self.path)?;
+        *self.rules.write().unwrap() = Arc::new(rules);
+        Ok(())
+    }
+
+    /// Rereads the file whenever the process gets SIGHUP, saying so in `log`.
+    #[cfg(unix)]
+    pub(crate) fn reload_on_hangup(&self, log: Logger) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = signal(SignalKind::hangup())?;
+        let file = self.clone();
+        tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                match file.reload() {
+                    Ok(()) => log.info(format_args!(
+                        "Reloaded the rewrite rules from {}",
+                        file.path
+                    )),
+                    Err(e) => log.error(format_args!(
+                        "Keeping the rewrite rules loaded before: {}",
+                        e
+                    )),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl QueryInterceptor for RuleFile {
+    fn before_translate(&self, _: &Context, sql: &str) -> Result<Option<String>, MysqlError> {
+        let rules = Arc::clone(&self.rules.read().unwrap());
+        Ok(rules.apply(sql))
+    }
+}
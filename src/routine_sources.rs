@@ -0,0 +1,274 @@
+// The MySQL source of the stored routines created through the proxy, kept next to the PL/pgSQL
+// they were translated to so that what was written can be compared with what runs.
+//
+// A CREATE PROCEDURE or CREATE FUNCTION that succeeds has its text, as the client sent it, kept
+// in `proxy_metadata.routines` (created on first use, from a session of its own so a failure
+// there can't touch the client's transaction); DROP PROCEDURE and DROP FUNCTION remove it. Then
+//
+//   SHOW CREATE PROCEDURE add_points                  the MySQL source
+//   SHOW PROCEDURE CODE add_points                    the PL/pgSQL PostgreSQL runs, by line
+//   SELECT routine_definition, translated_definition
+//     FROM information_schema.routines                both
+//
+// information_schema.routines is replaced by a subquery with MySQL's columns over PostgreSQL's
+// view, without the routines of pg_catalog and information_schema, and with the kept source as
+// routine_definition where there is one. Routines created otherwise show their PL/pgSQL.
+
+use std::error::Error;
+
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Client;
+
+use crate::catalog::ObjectName;
+use crate::emulation::{self, is_table_alias};
+use crate::server::Upstream;
+use crate::translator::{self, literals, Node, Token};
+
+const TABLE: &str = "proxy_metadata.routines";
+
+const CREATE_TABLE: &str = "CREATE SCHEMA IF NOT EXISTS proxy_metadata; \
+     CREATE TABLE IF NOT EXISTS proxy_metadata.routines ( \
+       routine_schema text NOT NULL, \
+       routine_name text NOT NULL, \
+       routine_type text NOT NULL, \
+       source text NOT NULL, \
+       created timestamptz NOT NULL DEFAULT now(), \
+       PRIMARY KEY (routine_schema, routine_name, routine_type))";
+
+/// A routine a statement created or dropped; `kind` is `PROCEDURE` or `FUNCTION`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created {
+        kind: &'static str,
+        name: ObjectName,
+        source: String,
+    },
+    Dropped {
+        kind: &'static str,
+        name: ObjectName,
+    },
+}
+
+/// The routine `sql`, a statement as the client sent it, creates or drops, if any.
+pub fn change(sql: &str) -> Option<Change> {
+    let tokens = translator::significant_tokens(sql)?;
+    let (first, rest) = tokens.split_first()?;
+    if first.is_word("CREATE") {
+        // CREATE [DEFINER = user] {PROCEDURE | FUNCTION} name
+        let at = rest.iter().position(|t| kind(t).is_some())?;
+        if at > 0 && !rest[0].is_word("DEFINER") {
+            return None;
+        }
+        let (name, _) = emulation::object_name(&rest[at + 1..])?;
+        return Some(Change::Created {
+            kind: kind(&rest[at])?,
+            name,
+            source: sql.trim().trim_end_matches(';').trim_end().to_string(),
+        });
+    }
+    if first.is_word("DROP") {
+        let (kind, rest) = rest
+            .split_first()
+            .and_then(|(t, rest)| Some((kind(t)?, rest)))?;
+        let rest = match rest {
+            [if_, exists, rest @ ..] if if_.is_word("IF") && exists.is_word("EXISTS") => rest,
+            _ => rest,
+        };
+        let (name, _) = emulation::object_name(rest)?;
+        return Some(Change::Dropped { kind, name });
+    }
+    None
+}
+
+fn kind(token: &Token) -> Option<&'static str> {
+    ["PROCEDURE", "FUNCTION"]
+        .into_iter()
+        .find(|kind| token.is_word(kind))
+}
+
+/// Keeps or forgets the source of the routine `change` is about. `session` is the one that ran
+/// the statement, for its current schema; the table is written from a session of `upstream`'s.
+pub async fn record(
+    session: &Client,
+    upstream: &dyn Upstream,
+    change: Change,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (Change::Created { name, .. } | Change::Dropped { name, .. }) = &change;
+    let schema: String = match &name.schema {
+        Some(schema) => schema.clone(),
+        None => session
+            .query_one("SELECT current_schema()::text", &[])
+            .await?
+            .get(0),
+    };
+    let metadata = upstream.connect().await?;
+    match change {
+        Change::Created {
+            kind, name, source, ..
+        } => {
+            metadata.batch_execute(CREATE_TABLE).await?;
+            metadata
+                .execute(
+                    &format!(
+                        "INSERT INTO {} (routine_schema, routine_name, routine_type, source) \
+                         VALUES ($1, $2, $3, $4) \
+                         ON CONFLICT (routine_schema, routine_name, routine_type) \
+                         DO UPDATE SET source = EXCLUDED.source, created = now()",
+                        TABLE
+                    ),
+                    &[&schema, &name.name, &kind, &source],
+                )
+                .await?;
+        }
+        Change::Dropped { kind, name } => {
+            let deleted = metadata
+                .execute(
+                    &format!(
+                        "DELETE FROM {} \
+                         WHERE routine_schema = $1 AND routine_name = $2 AND routine_type = $3",
+                        TABLE
+                    ),
+                    &[&schema, &name.name, &kind],
+                )
+                .await;
+            match deleted {
+                // Nothing was ever kept.
+                Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => {}
+                deleted => {
+                    deleted?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The kept source of routine `name` of `schema`, of `kind` (`PROCEDURE` or `FUNCTION`).
+pub async fn source(
+    client: &Client,
+    schema: &str,
+    name: &str,
+    kind: &str,
+) -> Result<Option<String>, tokio_postgres::Error> {
+    if !kept(client).await? {
+        return Ok(None);
+    }
+    let row = client
+        .query_opt(
+            &format!(
+                "SELECT source FROM {} \
+                 WHERE routine_schema = $1 AND routine_name = $2 AND routine_type = $3",
+                TABLE
+            ),
+            &[&schema, &name, &kind],
+        )
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+// Whether any source has been kept, that is whether the table is there.
+async fn kept(client: &Client) -> Result<bool, tokio_postgres::Error> {
+    Ok(client
+        .query_one("SELECT to_regclass($1::text) IS NOT NULL", &[&TABLE])
+        .await?
+        .get(0))
+}
+
+/// The translated statement `sql` with its references to information_schema.routines replaced
+/// by MySQL's view of them. `None` if it has none.
+pub async fn rewrite(client: &Client, sql: &str) -> Result<Option<String>, tokio_postgres::Error> {
+    let lowered = sql.to_ascii_lowercase();
+    if !lowered.contains("information_schema") || !lowered.contains("routines") {
+        return Ok(None);
+    }
+    let Ok(nodes) = translator::parse(sql) else {
+        return Ok(None);
+    };
+    let subquery =
+        translator::parse(&routines(kept(client).await?)).expect("generated SQL is balanced");
+    let mut expanded = false;
+    let nodes = expand_nodes(nodes, &subquery, &mut expanded);
+    Ok(expanded.then(|| translator::render(&nodes)))
+}
+
+fn expand_nodes(nodes: Vec<Node>, subquery: &[Node], expanded: &mut bool) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut iter = nodes.into_iter().peekable();
+
+    while let Some(node) = iter.next() {
+        let node = match node {
+            Node::Group(inner) => Node::Group(expand_nodes(inner, subquery, expanded)),
+            other => other,
+        };
+        out.push(node);
+
+        // Looking for `information_schema . routines`, ending at the node just pushed.
+        let [.., Node::Token(schema), Node::Token(dot), Node::Token(name)] = out.as_slice() else {
+            continue;
+        };
+        if !dot.is_operator(".")
+            || literals::identifier_name(schema).as_deref() != Some("information_schema")
+            || literals::identifier_name(name).as_deref() != Some("routines")
+        {
+            continue;
+        }
+
+        out.truncate(out.len() - 3);
+        out.push(Node::Group(subquery.to_vec()));
+        // A derived table needs an alias; keep the client's if it gave one.
+        let aliased = iter
+            .clone()
+            .find(|n| !n.is_trivia())
+            .is_some_and(|n| is_table_alias(&n));
+        if !aliased {
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::Word("AS".to_string())));
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::Word("routines".to_string())));
+        }
+        *expanded = true;
+    }
+
+    out
+}
+
+// MySQL's information_schema.ROUTINES over PostgreSQL's, with the kept sources if `kept`.
+fn routines(kept: bool) -> String {
+    let (definition, join) = match kept {
+        true => (
+            "COALESCE(s.source, r.routine_definition)",
+            format!(
+                " LEFT JOIN {} s ON s.routine_schema = r.routine_schema::text \
+                 AND s.routine_name = r.routine_name::text \
+                 AND s.routine_type = r.routine_type::text",
+                TABLE
+            ),
+        ),
+        false => ("r.routine_definition", String::new()),
+    };
+    format!(
+        "SELECT r.routine_name::text AS specific_name, \
+           'def'::text AS routine_catalog, \
+           r.routine_schema::text AS routine_schema, \
+           r.routine_name::text AS routine_name, \
+           r.routine_type::text AS routine_type, \
+           COALESCE(r.data_type::text, '') AS data_type, \
+           'SQL'::text AS routine_body, \
+           {definition}::text AS routine_definition, \
+           r.routine_definition::text AS translated_definition, \
+           NULL::text AS external_name, \
+           'SQL'::text AS external_language, \
+           'SQL'::text AS parameter_style, \
+           r.is_deterministic::text AS is_deterministic, \
+           COALESCE(r.sql_data_access::text, 'CONTAINS SQL') AS sql_data_access, \
+           NULL::text AS sql_path, \
+           COALESCE(r.security_type::text, 'INVOKER') AS security_type, \
+           ''::text AS sql_mode, \
+           ''::text AS routine_comment, \
+           'utf8mb4'::text AS character_set_client, \
+           'utf8mb4_0900_ai_ci'::text AS collation_connection, \
+           'utf8mb4_0900_ai_ci'::text AS database_collation \
+         FROM information_schema.routines r{join} \
+         WHERE r.routine_schema::text NOT IN ('pg_catalog', 'information_schema')"
+    )
+}
@@ -0,0 +1,37 @@
+// The tokio runtime the proxy runs on, which main builds from the configuration rather than
+// taking tokio's defaults: RUNTIME_WORKER_THREADS (one per CPU by default),
+// RUNTIME_MAX_BLOCKING_THREADS (512 by default) and RUNTIME_THREAD_NAME, which is what the
+// threads are called in top, gdb and the like.
+//
+// The blocking pool runs the translation of statements of BLOCKING_TRANSLATION_SIZE bytes or
+// more, a big INSERT from a dump say, so that a worker isn't kept from its other connections
+// while it is translated.
+
+use std::io;
+
+use tokio::runtime::{Builder, Runtime};
+
+pub const DEFAULT_THREAD_NAME: &str = "postmyrustache";
+
+/// How the runtime's threads are set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    pub worker_threads: Option<usize>,
+    pub max_blocking_threads: Option<usize>,
+    pub thread_name: String,
+}
+
+impl RuntimeConfig {
+    /// A multi-threaded runtime set up this way.
+    pub fn build(&self) -> io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all().thread_name(&self.thread_name);
+        if let Some(threads) = self.worker_threads {
+            builder.worker_threads(threads);
+        }
+        if let Some(threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(threads);
+        }
+        builder.build()
+    }
+}
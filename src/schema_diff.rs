@@ -0,0 +1,306 @@
+// `postmyrustache diff-schema`: compares the tables of a MySQL schema dump with PostgreSQL's.
+//
+//   postmyrustache diff-schema --mysql-dump schema.sql [--schema name]
+//
+// Every CREATE TABLE in the dump is looked up in PostgreSQL, in the schema the dump USEs (or
+// --schema, or else the search_path), and the differences are listed one per line: missing
+// tables, missing and extra columns, NOT NULL mismatches, and missing and extra indexes. This
+// checks a migration done through the proxy or outside it (pgloader, hand-written DDL).
+//
+// Column types aren't compared, since most MySQL types have several reasonable PostgreSQL
+// counterparts. Indexes are matched by their columns and uniqueness rather than their names, which
+// the proxy prefixes with the table name. FULLTEXT and SPATIAL indexes aren't compared, nor are
+// tables that only PostgreSQL has. The exit status is 1 when there are differences.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+use tokio_postgres::Client;
+
+use crate::catalog::{self, ObjectName};
+use crate::translator::{self, literals, split_args, Node, Token};
+
+const USAGE: &str = "usage: postmyrustache diff-schema --mysql-dump <file> [--schema <name>]";
+
+struct Table {
+    schema: Option<String>,
+    name: String,
+    columns: Vec<Column>,
+    indexes: Vec<Index>,
+}
+
+struct Column {
+    name: String,
+    not_null: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Index {
+    primary: bool,
+    unique: bool,
+    columns: Vec<String>,
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.primary {
+            "PRIMARY KEY"
+        } else if self.unique {
+            "UNIQUE KEY"
+        } else {
+            "KEY"
+        };
+        let columns: Vec<String> = self.columns.iter().map(|c| format!("`{}`", c)).collect();
+        write!(f, "{} ({})", kind, columns.join(","))
+    }
+}
+
+/// Runs the subcommand with the arguments after `diff-schema`. Returns whether the schemas match.
+pub async fn run(args: &[String], client: &Client) -> Result<bool, Box<dyn Error>> {
+    let mut dump = None;
+    let mut schema = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mysql-dump" => dump = args.next(),
+            "--schema" => schema = args.next(),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let dump = dump.ok_or(USAGE)?;
+    let sql = fs::read_to_string(dump).map_err(|e| format!("can't read {}: {}", dump, e))?;
+    let mut tables = dump_tables(&sql).map_err(|e| format!("can't parse {}: {}", dump, e))?;
+    if let Some(schema) = schema {
+        for table in &mut tables {
+            table.schema = Some(schema.to_lowercase());
+        }
+    }
+
+    let mut differences = 0;
+    for table in &tables {
+        for difference in compare(table, client).await? {
+            let name = match &table.schema {
+                Some(schema) => format!("`{}`.`{}`", schema, table.name),
+                None => format!("`{}`", table.name),
+            };
+            println!("{}: {}", name, difference);
+            differences += 1;
+        }
+    }
+    println!(
+        "{} tables compared, {} differences",
+        tables.len(),
+        differences
+    );
+    Ok(differences == 0)
+}
+
+// The differences between a table of the dump and the one in PostgreSQL.
+async fn compare(table: &Table, client: &Client) -> Result<Vec<String>, tokio_postgres::Error> {
+    let object = ObjectName {
+        schema: table.schema.clone(),
+        name: table.name.clone(),
+    };
+    let columns = catalog::table_columns(client, &object).await?;
+    if columns.is_empty() {
+        return Ok(vec!["table is missing".to_string()]);
+    }
+    // Expression indexes, like the GIN index of a FULLTEXT one, have no plain columns.
+    let indexes: Vec<Index> = catalog::table_indexes(client, &object)
+        .await?
+        .into_iter()
+        .filter(|index| !index.columns.is_empty())
+        .map(|index| Index {
+            primary: index.primary,
+            unique: index.unique,
+            columns: index.columns.iter().map(|c| c.to_lowercase()).collect(),
+        })
+        .collect();
+
+    let mut differences = Vec::new();
+    for column in &table.columns {
+        match columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(&column.name))
+        {
+            None => differences.push(format!("column `{}` is missing", column.name)),
+            Some(c) if c.nullable && column.not_null => differences.push(format!(
+                "column `{}` is nullable, not NOT NULL",
+                column.name
+            )),
+            Some(c) if !c.nullable && !column.not_null => differences.push(format!(
+                "column `{}` is NOT NULL, not nullable",
+                column.name
+            )),
+            Some(_) => {}
+        }
+    }
+    for c in &columns {
+        if !table
+            .columns
+            .iter()
+            .any(|column| column.name.eq_ignore_ascii_case(&c.name))
+        {
+            differences.push(format!("column `{}` is not in the dump", c.name));
+        }
+    }
+    for index in &table.indexes {
+        if !indexes.contains(index) {
+            differences.push(format!("index {} is missing", index));
+        }
+    }
+    for index in &indexes {
+        if !table.indexes.contains(index) {
+            differences.push(format!("index {} is not in the dump", index));
+        }
+    }
+    Ok(differences)
+}
+
+fn is_word(node: &Node, word: &str) -> bool {
+    matches!(node, Node::Token(t) if t.is_word(word))
+}
+
+// The tables the dump creates.
+fn dump_tables(sql: &str) -> Result<Vec<Table>, translator::TranslateError> {
+    let nodes = translator::parse(sql)?;
+    let mut database = None;
+    let mut tables = Vec::new();
+    for statement in nodes.split(|n| matches!(n, Node::Token(Token::Semicolon))) {
+        let significant: Vec<&Node> = statement.iter().filter(|n| !n.is_trivia()).collect();
+        match significant.as_slice() {
+            [use_, Node::Token(name)] if is_word(use_, "USE") => {
+                database = literals::identifier_name(name);
+            }
+            [create, table, rest @ ..] if is_word(create, "CREATE") && is_word(table, "TABLE") => {
+                tables.extend(create_table(rest, &database));
+            }
+            _ => {}
+        }
+    }
+    Ok(tables)
+}
+
+// `[IF NOT EXISTS] name (items) [options]`. CREATE TABLE ... LIKE and ... SELECT have no items to
+// compare and are skipped.
+fn create_table(rest: &[&Node], database: &Option<String>) -> Option<Table> {
+    let rest = match rest {
+        [if_, not, exists, rest @ ..]
+            if is_word(if_, "IF") && is_word(not, "NOT") && is_word(exists, "EXISTS") =>
+        {
+            rest
+        }
+        rest => rest,
+    };
+    let (schema, name, body) = match rest {
+        [Node::Token(schema), Node::Token(dot), Node::Token(name), Node::Group(body), ..]
+            if dot.is_operator(".") =>
+        {
+            (literals::identifier_name(schema), name, body)
+        }
+        [Node::Token(name), Node::Group(body), ..] => (database.clone(), name, body),
+        _ => return None,
+    };
+    let mut table = Table {
+        schema,
+        name: literals::identifier_name(name)?,
+        columns: Vec::new(),
+        indexes: Vec::new(),
+    };
+    for item in split_args(body) {
+        let significant: Vec<&Node> = item.iter().filter(|n| !n.is_trivia()).collect();
+        table_item(&mut table, &significant);
+    }
+    // Primary key columns are NOT NULL whether or not they say so.
+    if let Some(primary) = table.indexes.iter().find(|index| index.primary) {
+        for column in &mut table.columns {
+            if primary.columns.contains(&column.name) {
+                column.not_null = true;
+            }
+        }
+    }
+    Some(table)
+}
+
+// A column definition or a key.
+fn table_item(table: &mut Table, item: &[&Node]) {
+    match item {
+        [constraint, rest @ ..] if is_word(constraint, "CONSTRAINT") => {
+            // `CONSTRAINT [symbol] PRIMARY KEY | UNIQUE | FOREIGN KEY | CHECK ...`
+            let rest = match rest {
+                [symbol, rest @ ..]
+                    if !["PRIMARY", "UNIQUE", "FOREIGN", "CHECK"]
+                        .iter()
+                        .any(|w| is_word(symbol, w)) =>
+                {
+                    rest
+                }
+                rest => rest,
+            };
+            table_item(table, rest);
+        }
+        [primary, key, rest @ ..] if is_word(primary, "PRIMARY") && is_word(key, "KEY") => {
+            table.indexes.extend(index(true, true, rest));
+        }
+        [unique, rest @ ..] if is_word(unique, "UNIQUE") => {
+            table.indexes.extend(index(false, true, rest));
+        }
+        [key, rest @ ..] if is_word(key, "KEY") || is_word(key, "INDEX") => {
+            table.indexes.extend(index(false, false, rest));
+        }
+        [first, ..]
+            if ["FULLTEXT", "SPATIAL", "FOREIGN", "CHECK"]
+                .iter()
+                .any(|w| is_word(first, w)) => {}
+        [Node::Token(name), definition @ ..] => {
+            let Some(name) = literals::identifier_name(name) else {
+                return;
+            };
+            let has = |words: &[&str]| {
+                definition
+                    .windows(words.len())
+                    .any(|w| w.iter().zip(words).all(|(node, word)| is_word(node, word)))
+            };
+            if has(&["PRIMARY", "KEY"]) {
+                table.indexes.push(Index {
+                    primary: true,
+                    unique: true,
+                    columns: vec![name.clone()],
+                });
+            } else if has(&["UNIQUE"]) {
+                table.indexes.push(Index {
+                    primary: false,
+                    unique: true,
+                    columns: vec![name.clone()],
+                });
+            }
+            table.columns.push(Column {
+                not_null: has(&["NOT", "NULL"]),
+                name,
+            });
+        }
+        _ => {}
+    }
+}
+
+// The index of a key definition, from its key parts: the first group after the key word. Keys on
+// expressions aren't compared.
+fn index(primary: bool, unique: bool, rest: &[&Node]) -> Option<Index> {
+    let key_parts = rest.iter().find_map(|n| match n {
+        Node::Group(key_parts) => Some(key_parts),
+        Node::Token(_) => None,
+    })?;
+    let columns = split_args(key_parts)
+        .iter()
+        .map(|part| match part.iter().find(|n| !n.is_trivia()) {
+            Some(Node::Token(column)) => literals::identifier_name(column),
+            _ => None,
+        })
+        .collect::<Option<Vec<String>>>()?;
+    Some(Index {
+        primary,
+        unique,
+        columns,
+    })
+}
@@ -0,0 +1,3240 @@
+// The proxy as a library: a MySQL server whose clients' statements run on PostgreSQL.
+//
+//   let server = ServerBuilder::new(Config::from_env()?).build().await?;
+//   server.run().await?;
+//
+// ServerBuilder starts from the settings the binary reads from the environment or a config file
+// and can swap two of the parts the binary uses: the Translator, say one with functions of its
+// own registered, and where the PostgreSQL sessions come from (see Upstream). A Server serves the
+// clients of its TCP listener with run(), or a single connection over any other transport, such
+// as an in-memory tokio::io::duplex in a test, with serve_connection(). Each connection is handled
+// by a Backend, the AsyncMysqlShim that answers its commands or translates and forwards them.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc; // For shared ownership of the PostgreSQL client.
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// AsyncRead and AsyncWrite from tokio, the transport a connection is served over.
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+// Importing necessary components from the opensrv_mysql crate.
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use opensrv_mysql::*;
+
+// Additional imports for PostgreSQL support.
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::{Client, Row, RowStream, Statement};
+use tokio_rustls::TlsAcceptor;
+use tracing::Instrument;
+
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::{self, AuthProvider, Credentials, Identity, Method};
+use crate::catalog::ObjectName;
+use crate::charset;
+use crate::client_tls::{self, Requirement, TlsSession};
+use crate::compression::{Algorithms, Compressing, Decompressing, Negotiation};
+use crate::config::{self, Config, ParseFailure};
+use crate::connection_ids::{self, ConnectionIds};
+use crate::cursors::{self, Cursor};
+use crate::ddl_history::{self, Before};
+use crate::diagnostics::{Diagnostics, Level};
+use crate::digest::Digest;
+use crate::emulation::caches::CacheUsage;
+use crate::emulation::{self, locks::Locks};
+use crate::error::MysqlError;
+use crate::failover::{self, HostSession, Hosts};
+use crate::failures::{self, Category, Failure};
+use crate::guc_mappings::{self, GucMapping, MappedSettings};
+use crate::idempotency::{self, IdempotencyKeys};
+use crate::intercept::{self, Context, Outcome, QueryInterceptor};
+use crate::limits::StatementLimits;
+use crate::logging::{Logger, StatementRecord};
+use crate::mysql_specific::{self, Specific};
+use crate::policy::{self, Policy, PolicyConfig};
+use crate::profiling::{Phase, Profiler};
+use crate::protocol::{self, Command, Commands, Intercepted, Replies, Status};
+use crate::reconnect;
+use crate::result_cache::{self, Cacheable, CachedResult, ResultCache, Written};
+use crate::rewrite_rules::RuleFile;
+use crate::session_init::{SessionInit, SessionInits};
+use crate::sessions::Sessions;
+use crate::shadow::{self, Expected, Shadow, ShadowSession};
+use crate::sql_mode;
+use crate::statement_cache::StatementCache;
+use crate::stats::{self, Counted, Stats};
+use crate::system_variables;
+use crate::telemetry;
+use crate::throttle::{Throttle, UserGuard};
+use crate::time_zone::{self, TimeZone};
+use crate::timeouts::{self, Deadline, TimeoutConfig};
+use crate::tls::MakeTls;
+use crate::trace::{ConnectionTrace, Traced, Tracer};
+use crate::transaction_modes::{
+    self, Characteristics, Scope, Statement as TransactionStatement, TransactionModes,
+};
+use crate::translator::dates::{self, Coerced};
+use crate::translator::{self, literals, Token, TranslateError, Translator};
+use crate::transport::{self, Connection, Listener, Transport};
+use crate::{
+    call, date_columns, implicit_defaults, load_data, routine_sources, snapshot, upstream,
+};
+
+/// A PostgreSQL session for one MySQL connection: a Client of its own, or one on loan from a
+/// pool that gets it back when the connection ends.
+pub type Session = Arc<dyn Deref<Target = Client> + Send + Sync>;
+
+/// Where each MySQL connection gets its PostgreSQL session.
+///
+/// A connection needs a session to itself, so transactions, session settings and locks stay with
+/// the client that made them. A pool handing out sessions should reset the ones it gets back, as
+/// DISCARD ALL does.
+#[async_trait]
+pub trait Upstream: Send + Sync {
+    async fn connect(&self) -> Result<Session, Box<dyn Error + Send + Sync>>;
+
+    /// The servers sessions are opened with, each with the sessions open with it, for SHOW PROXY
+    /// CACHES. None unless implemented.
+    fn sessions(&self) -> Vec<(String, usize)> {
+        Vec::new()
+    }
+}
+
+/// Opens sessions with the configured DB_HOST, DB_USER, DB_PASSWORD, TLS settings and timeouts,
+/// through DB_TUNNEL if it is set, failing over to DB_STANDBY_HOSTS (see failover.rs).
+#[derive(Clone)]
+pub struct Connector {
+    // All but the host, which is one of `hosts`.
+    connection_string: String,
+    hosts: Arc<Hosts>,
+    tls: MakeTls,
+    #[cfg(feature = "compression")]
+    tunnel: Option<String>,
+    // tokio-postgres times only the connections it opens itself, not the tunnel's.
+    #[cfg(feature = "compression")]
+    connect_timeout: Option<Duration>,
+    log: Logger,
+}
+
+impl Connector {
+    pub fn new(config: &Config) -> io::Result<Connector> {
+        if config.db_tunnel.is_some() && !cfg!(feature = "compression") {
+            return Err(io::Error::other(
+                "DB_TUNNEL is set, but this build has no compression; build with --features \
+                 compression",
+            ));
+        }
+        // Sessions through the tunnel go without TLS (see tunnel.rs).
+        let sslmode = match config.db_tunnel {
+            Some(_) => "disable",
+            None => config.tls.mode.connection_mode(),
+        };
+        let hosts = Hosts::new(&config.db_host, &config.db_standby_hosts);
+        let mut connection_string = format!(
+            "user={} password={} sslmode={}",
+            config.db_user, config.db_password, sslmode
+        );
+        // A standby that hasn't been promoted yet isn't one to fail over to.
+        if hosts.standbys() {
+            connection_string.push_str(" target_session_attrs=read-write");
+        }
+        if let Some(timeout) = config.db_connect_timeout {
+            connection_string.push_str(&format!(" connect_timeout={}", timeout.as_secs()));
+        }
+        // Set for the session as it starts, so a session's SET statement_timeout still wins.
+        if let Some(timeout) = config.db_statement_timeout {
+            connection_string.push_str(&format!(
+                " options='-c statement_timeout={}'",
+                timeout.as_millis()
+            ));
+        }
+        Ok(Connector {
+            connection_string,
+            hosts: Arc::new(hosts),
+            tls: MakeTls::new(&config.tls)?,
+            #[cfg(feature = "compression")]
+            tunnel: config.db_tunnel.clone(),
+            #[cfg(feature = "compression")]
+            connect_timeout: config.db_connect_timeout,
+            log: Logger::new(config.log_format).sql_format(config.log_sql_format),
+        })
+    }
+
+    /// Opens a session. The connection object performs the communication with the database, so
+    /// it is spawned off to run on its own.
+    pub async fn client(&self) -> Result<Client, Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "compression")]
+        if let Some(addr) = &self.tunnel {
+            let config: tokio_postgres::Config = self.connection_string.parse()?;
+            let connect = async {
+                let stream = crate::tunnel::connect(addr).await?;
+                let connected = config.connect_raw(stream, tokio_postgres::NoTls).await?;
+                Ok::<_, Box<dyn Error + Send + Sync>>(connected)
+            };
+            let (client, connection) = match self.connect_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, connect)
+                    .await
+                    .map_err(|_| format!("no session through the tunnel within {:?}", timeout))??,
+                None => connect.await?,
+            };
+            // The tunnel's far end is DB_HOST's.
+            self.spawn(connection, self.hosts.session(0));
+            return Ok(client);
+        }
+        let mut failed = None;
+        for (index, host) in self.hosts.in_order() {
+            let connection_string = format!("host={} {}", host, self.connection_string);
+            match tokio_postgres::connect(&connection_string, self.tls.clone()).await {
+                Ok((client, connection)) => {
+                    self.spawn(connection, self.hosts.session(index));
+                    self.hosts.connected(index, self.log);
+                    return Ok(client);
+                }
+                // The active host's error is the one to report.
+                Err(e) => {
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+        Err(failed.expect("there is always DB_HOST").into())
+    }
+
+    // Runs `connection`, the session counted by `session` for as long as it is open.
+    fn spawn<S, T>(&self, connection: tokio_postgres::Connection<S, T>, session: HostSession)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let log = self.log;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log.error(format_args!("connection error: {}", e));
+            }
+            drop(session);
+        });
+    }
+}
+
+#[async_trait]
+impl Upstream for Connector {
+    async fn connect(&self) -> Result<Session, Box<dyn Error + Send + Sync>> {
+        Ok(Arc::new(Box::new(self.client().await?)))
+    }
+
+    fn sessions(&self) -> Vec<(String, usize)> {
+        #[cfg(feature = "compression")]
+        if let Some(addr) = &self.tunnel {
+            let open = self.hosts.sessions().first().map_or(0, |(_, open)| *open);
+            return vec![(format!("{} (tunnel)", addr), open)];
+        }
+        self.hosts.sessions()
+    }
+}
+
+/// Sets up a Server from the proxy's settings.
+pub struct ServerBuilder {
+    config: Config,
+    translator: Option<Translator>,
+    upstream: Option<Arc<dyn Upstream>>,
+    interceptors: Vec<Box<dyn QueryInterceptor>>,
+    transport: Option<Arc<dyn Transport>>,
+    auth: Option<Arc<dyn AuthProvider>>,
+}
+
+impl ServerBuilder {
+    pub fn new(config: Config) -> ServerBuilder {
+        ServerBuilder {
+            config,
+            translator: None,
+            upstream: None,
+            interceptors: Vec::new(),
+            transport: None,
+            auth: None,
+        }
+    }
+
+    /// Translates statements with `translator` rather than one made from the configured
+    /// translation options.
+    pub fn translator(mut self, translator: Translator) -> ServerBuilder {
+        self.translator = Some(translator);
+        self
+    }
+
+    /// Takes the PostgreSQL sessions from `upstream` rather than connecting with the configured
+    /// settings.
+    pub fn upstream(mut self, upstream: impl Upstream + 'static) -> ServerBuilder {
+        self.upstream = Some(Arc::new(upstream));
+        self
+    }
+
+    /// Accepts the clients with `transport` rather than the configured LISTEN_TRANSPORT.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> ServerBuilder {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Checks the clients' users and passwords with `auth` rather than the configured
+    /// AUTH_PROVIDER (see auth/mod.rs).
+    pub fn auth(mut self, auth: impl AuthProvider + 'static) -> ServerBuilder {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// Adds `interceptor` to the end of the chain every statement goes through (see
+    /// intercept.rs).
+    pub fn interceptor(mut self, interceptor: impl QueryInterceptor + 'static) -> ServerBuilder {
+        self.interceptors.push(Box::new(interceptor));
+        self
+    }
+
+    /// Opens the trace file, if there is one, and connects to PostgreSQL once, so a wrong
+    /// address or password shows now rather than with the first client.
+    pub async fn build(self) -> Result<Server, Box<dyn Error + Send + Sync>> {
+        let config = self.config;
+        let log = Logger::new(config.log_format).sql_format(config.log_sql_format);
+        // Its clients log in as anyone, so only this host may reach it, whoever built the Config.
+        if let Some(addr) = &config.admin_listen_addr {
+            if !config::is_loopback(addr) {
+                return Err(format!("ADMIN_LISTEN_ADDR {} isn't a loopback address", addr).into());
+            }
+        }
+        let upstream: Arc<dyn Upstream> = match self.upstream {
+            Some(upstream) => upstream,
+            None => Arc::new(Connector::new(&config)?),
+        };
+        upstream.connect().await?;
+        let transport: Arc<dyn Transport> = match self.transport {
+            Some(transport) => transport,
+            None => transport::of_kind(config.listen_transport, log)?.into(),
+        };
+        let auth = match self.auth {
+            Some(auth) => Some(auth),
+            None => auth::provider(&config.auth)?,
+        };
+        let client_tls = config
+            .client_tls
+            .as_ref()
+            .map(client_tls::acceptor)
+            .transpose()?;
+
+        // The rules file goes first, so the interceptors given here see the statements it made.
+        let mut interceptors = self.interceptors;
+        if let Some(path) = &config.rewrite_rules {
+            let rules = RuleFile::open(path)?;
+            #[cfg(unix)]
+            rules.reload_on_hangup(log)?;
+            interceptors.insert(0, Box::new(rules));
+        }
+        // And the policy last, so it judges the statements as they will run.
+        if config.policy.restricts() {
+            interceptors.push(Box::new(Policy::new(config.policy.clone())));
+        }
+
+        let translator = self
+            .translator
+            .unwrap_or_else(|| Translator::with_options(config.translation.clone()));
+        let tracer = match &config.trace {
+            Some(trace) => {
+                Some(Arc::new(Tracer::open(trace.clone(), log).map_err(|e| {
+                    format!("can't open the trace file {}: {}", trace.file, e)
+                })?))
+            }
+            None => None,
+        };
+        let audit = match &config.audit {
+            Some(audit) => Some(Arc::new(
+                AuditLog::open(audit, log)
+                    .map_err(|e| format!("can't open the audit log: {}", e))?,
+            )),
+            None => None,
+        };
+        let shadow = match &config.shadow {
+            Some(shadow) => Some(Arc::new(Shadow::new(shadow)?)),
+            None => None,
+        };
+        let stats = Arc::new(Stats::default());
+        let result_cache = config
+            .result_cache
+            .map(|cache| Arc::new(ResultCache::new(cache, Arc::clone(&stats))));
+        if let Some(path) = &config.stats_file {
+            stats
+                .restore(path)
+                .map_err(|e| format!("can't read the stats file {}: {}", path, e))?;
+            let (stats, path) = (Arc::clone(&stats), path.clone());
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(stats::SAVE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = stats.save(&path) {
+                        log.error(format_args!("Failed to save the stats to {}: {}", path, e));
+                    }
+                }
+            });
+        }
+        Ok(Server {
+            upstream,
+            auth,
+            translator: Arc::new(translator),
+            interceptors: interceptors.into(),
+            parameterize: config.parameterize,
+            parse_failure: config.parse_failure,
+            implicit_defaults: config.implicit_defaults,
+            ddl_history: config.ddl_history,
+            policy: Arc::new(config.policy.clone()),
+            requirements: config
+                .client_tls
+                .as_ref()
+                .map_or_else(Vec::new, |tls| tls.requirements.clone())
+                .into(),
+            client_tls,
+            auto_create_databases: config.auto_create_databases,
+            secure_file_priv: config.secure_file_priv.clone(),
+            blocking_translation_size: config.blocking_translation_size,
+            limits: config.limits,
+            max_allowed_packet: config.max_allowed_packet,
+            compression: config.protocol_compression,
+            throttle: Arc::new(Throttle::new(config.throttle)),
+            timeouts: config.timeouts,
+            reconnect_attempts: config.db_reconnect_attempts,
+            idempotency: config
+                .idempotent_writes
+                .then(|| Arc::new(IdempotencyKeys::new())),
+            error_history: config.error_history,
+            statement_cache_size: config.statement_cache_size,
+            stats,
+            locks: Arc::new(Locks::default()),
+            sessions: Arc::new(Sessions::default()),
+            estimated_counts: config.estimated_counts.into(),
+            guc_mappings: config.guc_mappings.into(),
+            session_inits: Arc::new(config.session_inits),
+            tracer,
+            shadow,
+            audit,
+            result_cache,
+            connection_ids: Arc::new(ConnectionIds::new(connection_ids::REUSE_DELAY)),
+            handshakes: Arc::new(Semaphore::new(config.max_handshakes)),
+            handshake_timeout: config.handshake_timeout,
+            listen_addr: config.listen_addr,
+            admin_listen_addr: config.admin_listen_addr,
+            transport,
+            log,
+        })
+    }
+}
+
+/// The MySQL server. Clones share the connections, statistics and locks of the original.
+#[derive(Clone)]
+pub struct Server {
+    upstream: Arc<dyn Upstream>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    translator: Arc<Translator>,
+    interceptors: Arc<[Box<dyn QueryInterceptor>]>,
+    parameterize: bool,
+    parse_failure: ParseFailure,
+    implicit_defaults: bool,
+    ddl_history: bool,
+    policy: Arc<PolicyConfig>,
+    requirements: Arc<[(String, Requirement)]>,
+    // TLS for the clients that ask for it (LISTEN_TLS_CERT).
+    client_tls: Option<TlsAcceptor>,
+    auto_create_databases: bool,
+    secure_file_priv: Option<String>,
+    blocking_translation_size: usize,
+    limits: StatementLimits,
+    max_allowed_packet: usize,
+    compression: Algorithms,
+    throttle: Arc<Throttle>,
+    timeouts: TimeoutConfig,
+    reconnect_attempts: u32,
+    idempotency: Option<Arc<IdempotencyKeys>>,
+    error_history: usize,
+    statement_cache_size: usize,
+    stats: Arc<Stats>,
+    locks: Arc<Locks>,
+    sessions: Arc<Sessions>,
+    estimated_counts: Arc<[ObjectName]>,
+    guc_mappings: Arc<[GucMapping]>,
+    session_inits: Arc<SessionInits>,
+    tracer: Option<Arc<Tracer>>,
+    shadow: Option<Arc<Shadow>>,
+    audit: Option<Arc<AuditLog>>,
+    result_cache: Option<Arc<ResultCache>>,
+    connection_ids: Arc<ConnectionIds>,
+    // Clients that haven't logged in yet, port scanners among them, are limited, so they can't
+    // hold every PostgreSQL session the proxy can open.
+    handshakes: Arc<Semaphore>,
+    handshake_timeout: Duration,
+    listen_addr: String,
+    // The listener for sidecar tooling (ADMIN_LISTEN_ADDR), whose clients may run admin
+    // commands on every connection.
+    admin_listen_addr: Option<String>,
+    transport: Arc<dyn Transport>,
+    log: Logger,
+}
+
+impl Server {
+    /// Listens on the configured addresses and serves every client that connects.
+    pub async fn run(&self) -> io::Result<()> {
+        let mut listener = self.transport.bind(&self.listen_addr).await?;
+        self.log.info(format_args!(
+            "MySQL server is running on {}",
+            self.listen_addr
+        ));
+        let mut admin_listener = match &self.admin_listen_addr {
+            Some(addr) => {
+                let listener = self.transport.bind(addr).await?;
+                self.log
+                    .info(format_args!("Admin listener is running on {}", addr));
+                Some(listener)
+            }
+            None => None,
+        };
+
+        loop {
+            let (accepted, admin) = tokio::select! {
+                accepted = listener.accept() => (accepted, false),
+                accepted = accept(admin_listener.as_mut()) => (accepted, true),
+            };
+            let connection = match accepted {
+                Ok(accepted) => accepted,
+                // Most likely out of file descriptors; the server carries on once some are freed.
+                Err(e) => {
+                    self.log
+                        .error(format_args!("Failed to accept a connection: {}", e));
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                let Connection {
+                    reader,
+                    writer,
+                    peer,
+                } = connection;
+                if let Err(e) = server.serve_client(reader, writer, peer, admin).await {
+                    server.log.error(format_args!("Error: {}", e));
+                }
+            });
+        }
+    }
+
+    /// Serves one client over `reader` and `writer`, which needn't be a TCP socket; `peer` is the
+    /// address SHOW PROCESSLIST gives for it. Returns once the client disconnects, is killed or
+    /// doesn't log in in time.
+    pub async fn serve_connection<R, W>(
+        &self,
+        reader: R,
+        writer: W,
+        peer: SocketAddr,
+    ) -> io::Result<()>
+    where
+        R: AsyncRead + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        self.serve_client(reader, writer, peer, false).await
+    }
+
+    // Serves one client, who came in on the admin listener if `admin`.
+    async fn serve_client<R, W>(
+        &self,
+        reader: R,
+        writer: W,
+        peer: SocketAddr,
+        admin: bool,
+    ) -> io::Result<()>
+    where
+        R: AsyncRead + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        let tls = Arc::new(OnceLock::new());
+        match &self.client_tls {
+            Some(acceptor) => {
+                let (reader, writer) =
+                    client_tls::upgradable(reader, writer, acceptor.clone(), Arc::clone(&tls));
+                self.serve(reader, writer, peer, admin, tls).await
+            }
+            None => self.serve(reader, writer, peer, admin, tls).await,
+        }
+    }
+
+    // serve_client's, over the connection TLS may have been started on; `tls` is set if it is.
+    async fn serve<R, W>(
+        &self,
+        reader: R,
+        writer: W,
+        peer: SocketAddr,
+        admin: bool,
+        tls: Arc<OnceLock<TlsSession>>,
+    ) -> io::Result<()>
+    where
+        R: AsyncRead + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        let _connected = match self.throttle.connect() {
+            Ok(connected) => connected,
+            Err(error) => {
+                self.log
+                    .info(format_args!("Too many connections, refusing {}", peer));
+                return protocol::refuse(writer, &error).await;
+            }
+        };
+        let Ok(slot) = Arc::clone(&self.handshakes).try_acquire_owned() else {
+            self.log.info(format_args!(
+                "Too many clients logging in, disconnecting {}",
+                peer
+            ));
+            return Ok(());
+        };
+        self.stats.record_connection();
+        let connected = Instant::now();
+        let deadline = tokio::time::Instant::now() + self.handshake_timeout;
+        // Kept until the connection ends, for its id to be held back then.
+        let allocated = match self.connection_ids.allocate() {
+            Ok(id) => id,
+            Err(error) => {
+                self.log
+                    .info(format_args!("No connection id free, refusing {}", peer));
+                return protocol::refuse(writer, &error).await;
+            }
+        };
+        let connection_id = allocated.get();
+        let trace = self
+            .tracer
+            .as_ref()
+            .and_then(|tracer| tracer.connection(connection_id, peer));
+
+        // The PostgreSQL session is opened once the client has logged in (see authenticate).
+        let kill = self.sessions.register(connection_id, peer);
+        let (r, w) = (
+            Counted::new(reader, Arc::clone(&self.stats)),
+            Counted::new(writer, Arc::clone(&self.stats)),
+        );
+        let negotiation = Negotiation::new(self.compression);
+        let (r, w) = (
+            Decompressing::new(r, Arc::clone(&negotiation)),
+            Compressing::new(w, negotiation),
+        );
+        let (r, w) = (Traced::new(r, trace.clone()), Traced::new(w, trace.clone()));
+        let commands = Arc::new(Commands::default());
+        if let Some(auth) = &self.auth {
+            commands.require_auth_plugin(auth.method().plugin());
+        }
+        if self.client_tls.is_some() {
+            commands.offer_tls();
+        }
+        let status = Arc::new(Status::default());
+        let (r, w) = (
+            Intercepted::new(r, Arc::clone(&commands), self.max_allowed_packet),
+            Replies::new(w, Arc::clone(&commands), Arc::clone(&status)),
+        );
+        let logged_in = Arc::new(Notify::new());
+        let log = self.log.connection(connection_id);
+        let connection = AsyncMysqlIntermediary::run_on(
+            Backend {
+                upstream_session: OnceLock::new(),
+                upstream: Arc::clone(&self.upstream),
+                auth: self.auth.clone(),
+                salt: auth::salt(),
+                max_execution_time: self.timeouts.max_execution_time,
+                reconnect_attempts: self.reconnect_attempts,
+                idempotency: self.idempotency.clone(),
+                last_key: None,
+                translator: Arc::clone(&self.translator),
+                server_translator: Arc::clone(&self.translator),
+                interceptors: Arc::clone(&self.interceptors),
+                parameterize: self.parameterize,
+                stats: Arc::clone(&self.stats),
+                user: OnceLock::new(),
+                role: OnceLock::new(),
+                parse_failure: self.parse_failure,
+                implicit_defaults: self.implicit_defaults,
+                ddl_history: self.ddl_history,
+                policy: Arc::clone(&self.policy),
+                requirements: Arc::clone(&self.requirements),
+                tls,
+                auto_create_databases: self.auto_create_databases,
+                secure_file_priv: self.secure_file_priv.clone(),
+                blocking_translation_size: self.blocking_translation_size,
+                limits: self.limits,
+                throttle: Arc::clone(&self.throttle),
+                user_slot: Mutex::new(None),
+                diagnostics: Diagnostics::new(self.error_history, Arc::clone(&status)),
+                locks: Arc::clone(&self.locks),
+                connection_id,
+                admin,
+                database: None,
+                handshake: Mutex::new(Some((slot, Arc::clone(&logged_in)))),
+                commands: Arc::clone(&commands),
+                status,
+                sessions: Arc::clone(&self.sessions),
+                estimated_counts: Arc::clone(&self.estimated_counts),
+                guc_mappings: Arc::clone(&self.guc_mappings),
+                mapped_settings: MappedSettings::default(),
+                time_zone: TimeZone::default(),
+                session_inits: Arc::clone(&self.session_inits),
+                session_started: false,
+                transaction_lost: false,
+                profiler: Profiler::default(),
+                trace,
+                statements: HashMap::new(),
+                next_statement_id: 0,
+                statement_cache: StatementCache::new(
+                    self.statement_cache_size,
+                    Arc::clone(&self.stats),
+                ),
+                cursors: HashMap::new(),
+                transaction_modes: TransactionModes::default(),
+                log,
+                rows: None,
+                shadow: self
+                    .shadow
+                    .as_ref()
+                    .map(|shadow| shadow.session(Arc::clone(&self.stats), log)),
+                expected: None,
+                audit: self.audit.clone(),
+                peer,
+                result_cache: self.result_cache.clone(),
+                cache_writes: Vec::new(),
+            },
+            r,
+            w,
+        );
+        tokio::select! {
+            result = connection => result,
+            _ = kill.notified() => {
+                self.log.info(format_args!("Connection {} killed", connection_id));
+                Ok(())
+            }
+            _ = timeouts::idle(&commands, connected, self.timeouts.wait_timeout) => {
+                self.log.info(format_args!(
+                    "Connection {} idle longer than WAIT_TIMEOUT, disconnecting",
+                    connection_id
+                ));
+                Ok(())
+            }
+            _ = async {
+                if tokio::time::timeout_at(deadline, logged_in.notified()).await.is_ok() {
+                    std::future::pending::<()>().await;
+                }
+            } => {
+                self.log.info(format_args!(
+                    "Connection {} from {} didn't log in within {:?}, disconnecting",
+                    connection_id, peer, self.handshake_timeout
+                ));
+                Ok(())
+            }
+        }
+    }
+}
+
+// The next client of a listener that may not be configured; without one, none ever comes.
+async fn accept(listener: Option<&mut Box<dyn Listener>>) -> io::Result<Connection> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+// A statement prepared with COM_STMT_PREPARE: its SQL as the client sent it, and the
+// translation PostgreSQL prepared, to prepare again should the session be replaced.
+#[derive(Clone)]
+struct PreparedStatement {
+    statement: Statement,
+    sql: String,
+    translated: String,
+}
+
+/// One client's connection: the AsyncMysqlShim that answers its commands itself, or translates
+/// them and runs them on its PostgreSQL session. A Server makes one for each client.
+pub struct Backend {
+    // The PostgreSQL session and its process id, opened as the client logs in. Where it came
+    // from, to cancel a statement that runs longer than MAX_EXECUTION_TIME from another session,
+    // and to replace the session if it is lost (DB_RECONNECT_ATTEMPTS).
+    upstream_session: OnceLock<(Session, i32)>,
+    upstream: Arc<dyn Upstream>,
+    // Who may log in (AUTH_PROVIDER), anyone if unset, and the salt the client's password is
+    // scrambled with.
+    auth: Option<Arc<dyn AuthProvider>>,
+    salt: [u8; auth::SALT_LENGTH],
+    max_execution_time: Option<Duration>,
+    reconnect_attempts: u32,
+    // The idempotency keys of writes (IDEMPOTENT_WRITES), and the key of the connection's last
+    // keyed write, deleted with the next.
+    idempotency: Option<Arc<IdempotencyKeys>>,
+    last_key: Option<String>,
+    // The session's translator, with the modes of SET sql_mode, and the server's it was made from.
+    translator: Arc<Translator>,
+    server_translator: Arc<Translator>,
+    // The QueryInterceptors registered with the ServerBuilder, in order.
+    interceptors: Arc<[Box<dyn QueryInterceptor>]>,
+    // Send string literals as bind parameters (PARAMETERIZE_QUERIES).
+    parameterize: bool,
+    stats: Arc<Stats>,
+    // The MySQL user the client logged in as, set during the handshake.
+    user: OnceLock<String>,
+    // The PostgreSQL role the auth provider gave the user, which their sessions take.
+    role: OnceLock<String>,
+    // Statements the translator can't parse are forwarded or rejected (PARSE_FAILURE).
+    parse_failure: ParseFailure,
+    // Fill in NOT NULL columns an INSERT leaves out (IMPLICIT_DEFAULTS).
+    implicit_defaults: bool,
+    // Keep the schema changes made (DDL_HISTORY).
+    ddl_history: bool,
+    // The statement policy, for SHOW GRANTS and whose KILL may end other users' connections.
+    policy: Arc<PolicyConfig>,
+    // What some users' connections need (USER_REQUIRE), and the connection's TLS, set once the
+    // client has started it.
+    requirements: Arc<[(String, Requirement)]>,
+    tls: Arc<OnceLock<TlsSession>>,
+    // Create the schemas of unknown databases as they are used (AUTO_CREATE_DATABASES).
+    auto_create_databases: bool,
+    // Where LOAD DATA INFILE reads files from (SECURE_FILE_PRIV).
+    secure_file_priv: Option<String>,
+    // Statements this long or longer are translated on the blocking pool
+    // (BLOCKING_TRANSLATION_SIZE).
+    blocking_translation_size: usize,
+    // The longest, most complex statement accepted (MAX_STATEMENT_LENGTH and friends).
+    limits: StatementLimits,
+    // The connections of every user and the statements they run a second (MAX_CONNECTIONS and
+    // friends).
+    throttle: Arc<Throttle>,
+    // Once the client has logged in, its place among its user's connections.
+    user_slot: Mutex<Option<UserGuard>>,
+    // Warnings and errors of the last statement and the session's recent errors, for SHOW
+    // WARNINGS and SHOW ERRORS.
+    diagnostics: Diagnostics,
+    // GET_LOCK and friends; the locks are shared by all connections.
+    locks: Arc<Locks>,
+    connection_id: u32,
+    // Whether the client came in on the admin listener (ADMIN_LISTEN_ADDR), whose clients may
+    // run admin commands on every connection.
+    admin: bool,
+    // The database chosen with USE, whose schema is the search_path.
+    database: Option<String>,
+    // Until the client has logged in: its MAX_HANDSHAKES slot, and the notification that stops
+    // the HANDSHAKE_TIMEOUT.
+    handshake: Mutex<Option<(OwnedSemaphorePermit, Arc<Notify>)>>,
+    // COM_PING, COM_RESET_CONNECTION, COM_CHANGE_USER and COM_FIELD_LIST, taken from the
+    // client's stream.
+    commands: Arc<Commands>,
+    // Whether a transaction is open and the last statement's warning count, for the packet
+    // ending each reply.
+    status: Arc<Status>,
+    // Every connection, for KILL and SHOW PROCESSLIST.
+    sessions: Arc<Sessions>,
+    // Tables whose COUNT(*) is answered from the planner's estimate (ESTIMATED_COUNT_TABLES).
+    estimated_counts: Arc<[ObjectName]>,
+    // The MySQL variables set as PostgreSQL settings (GUC_MAPPINGS), and the settings made.
+    guc_mappings: Arc<[GucMapping]>,
+    mapped_settings: MappedSettings,
+    // What SET time_zone made the session's time zone.
+    time_zone: TimeZone,
+    // The database and init statements of each user's sessions (USER_DEFAULT_DATABASE,
+    // USER_INIT_SQL), and whether the user's have been, as the first command after login runs.
+    session_inits: Arc<SessionInits>,
+    session_started: bool,
+    // Whether the session was lost, and replaced, in a transaction the client hasn't been told
+    // was rolled back yet.
+    transaction_lost: bool,
+    // Phase timings of the statements run since SET profiling = 1, for SHOW PROFILE(S).
+    profiler: Profiler,
+    // The connection's protocol trace, if TRACE_FILE covers it.
+    trace: Option<Arc<ConnectionTrace>>,
+    // Statements prepared with COM_STMT_PREPARE, by the id the client was given.
+    statements: HashMap<u32, PreparedStatement>,
+    next_statement_id: u32,
+    // The statements prepared on the session, to be executed again (STATEMENT_CACHE_SIZE).
+    statement_cache: StatementCache,
+    // The cursors COM_STMT_EXECUTE opened, by the id of their statement (see cursors.rs).
+    cursors: HashMap<u32, Cursor>,
+    // The access mode and isolation level SET TRANSACTION gave.
+    transaction_modes: TransactionModes,
+    log: Logger,
+    // The rows the statement being run returned or affected, for its log record.
+    rows: Option<u64>,
+    // The connection's session on the shadow MySQL server (SHADOW_MYSQL_URL), and what
+    // PostgreSQL made of the statement being run, for the shadow to compare.
+    shadow: Option<ShadowSession>,
+    expected: Option<Expected>,
+    // Where the statements are recorded (AUDIT_LOG), and the client's address for it.
+    audit: Option<Arc<AuditLog>>,
+    peer: SocketAddr,
+    // The SELECT results kept (RESULT_CACHE_RULES), and what the transaction in progress wrote,
+    // to drop their results again as it ends.
+    result_cache: Option<Arc<ResultCache>>,
+    cache_writes: Vec<Written>,
+}
+
+impl Drop for Backend {
+    // MySQL releases a connection's user-level locks when it disconnects.
+    fn drop(&mut self) {
+        self.sessions.remove(self.connection_id);
+        // A client that never logged in has no session.
+        let Some((client, _)) = self.upstream_session.get() else {
+            return;
+        };
+        let client = Arc::clone(client);
+        let locks = Arc::clone(&self.locks);
+        let connection = self.connection_id;
+        let log = self.log;
+        tokio::spawn(async move {
+            if let Err(e) = locks.release_all(&client, connection).await {
+                log.error(format_args!(
+                    "Failed to release locks of connection {}: {:?}",
+                    connection, e
+                ));
+            }
+        });
+    }
+}
+
+impl Backend {
+    // What the QueryInterceptors are told of the connection.
+    fn context(&self) -> Context<'_> {
+        Context {
+            connection_id: self.connection_id,
+            user: self.user.get().map_or("", String::as_str),
+            database: self.database.as_deref(),
+            read_only: self
+                .transaction_modes
+                .read_only(self.status.in_transaction()),
+            options: self.translator.options(),
+        }
+    }
+
+    // The statement the client sent, as the QueryInterceptors rewrite it.
+    #[tracing::instrument(name = "intercept", skip_all)]
+    fn intercept<'s>(&self, sql: &'s str) -> Result<Cow<'s, str>, MysqlError> {
+        let intercepted =
+            match intercept::before_translate(&self.interceptors, &self.context(), sql)? {
+                Some(rewritten) => {
+                    self.log.debug(format_args!(
+                        "Intercepted SQL query: {}",
+                        self.log.sql(&rewritten)
+                    ));
+                    Cow::Owned(rewritten)
+                }
+                None => Cow::Borrowed(sql),
+            };
+        // The role the auth provider gave the user is the session's until it ends.
+        if self.role.get().is_some()
+            && policy::changes_role(&intercepted, self.translator.options())
+        {
+            return Err(policy::role_denied());
+        }
+        Ok(intercepted)
+    }
+
+    // Tells the QueryInterceptors how `sql`, the statement as the client sent it, turned out.
+    fn report(&mut self, sql: &str, outcome: Outcome) {
+        self.rows = match outcome {
+            Outcome::Affected(count) => Some(count),
+            Outcome::Rows(count) => Some(count as u64),
+            Outcome::Failed(_) => None,
+        };
+        match outcome {
+            Outcome::Affected(count) => self.expect(|| Expected::Affected(count)),
+            Outcome::Failed(error) => self.expect(|| Expected::Failed(error.code())),
+            // Compared row by row, as they are sent.
+            Outcome::Rows(_) => {}
+        }
+        let context = self.context();
+        for interceptor in self.interceptors.iter() {
+            interceptor.on_result(&context, sql, outcome);
+        }
+    }
+
+    // Keeps what PostgreSQL made of the statement being run, if there is a shadow to compare it
+    // with.
+    fn expect(&mut self, expected: impl FnOnce() -> Expected) {
+        if self.shadow.is_some() {
+            self.expected = Some(expected());
+        }
+    }
+
+    // Rewrites MySQL-only syntax before handing the statement to PostgreSQL. If the statement
+    // can't be tokenized it is either forwarded untouched, so PostgreSQL reports any error, or
+    // rejected here, depending on PARSE_FAILURE.
+    #[tracing::instrument(name = "translate", skip_all)]
+    async fn translate(&mut self, sql: &str) -> Result<String, MysqlError> {
+        let rewritten = if sql.len() < self.blocking_translation_size {
+            let parsed = self.translator.parse(sql);
+            self.profiler.mark(Phase::Parse);
+            if let Ok(nodes) = &parsed {
+                self.limits.check(nodes)?;
+            }
+            parsed.and_then(|nodes| self.translator.rewrite(nodes))
+        } else {
+            // A statement this big can take a while to translate, which would hold up every
+            // other connection on this worker.
+            let owned = sql.to_string();
+            let translator = Arc::clone(&self.translator);
+            let parsed = blocking(move || translator.parse(&owned)).await;
+            self.profiler.mark(Phase::Parse);
+            if let Ok(nodes) = &parsed {
+                self.limits.check(nodes)?;
+            }
+            let translator = Arc::clone(&self.translator);
+            blocking(move || parsed.and_then(|nodes| translator.rewrite(nodes))).await
+        };
+        let translated = match rewritten {
+            Ok(translated) => translated,
+            // Forwarding these would only get a less clear error from PostgreSQL.
+            Err(TranslateError::Unsupported(what)) => {
+                self.log.info(format_args!(
+                    "Query uses something that can't be translated: {}",
+                    what
+                ));
+                self.stats
+                    .record_failure(Failure::new(Category::Unsupported, what.clone()), sql);
+                return Err(MysqlError::new(
+                    ErrorKind::ER_NOT_SUPPORTED_YET,
+                    format!("This version of MySQL doesn't yet support '{}'", what),
+                ));
+            }
+            Err(e) => {
+                let near = e.near(sql);
+                match self.parse_failure {
+                    ParseFailure::Passthrough => {
+                        self.log.info(format_args!(
+                            "Failed to translate query, forwarding as-is: {}",
+                            e
+                        ));
+                        self.diagnostics.push(
+                            Level::Warning,
+                            ErrorKind::ER_PARSE_ERROR,
+                            format!("Statement forwarded untranslated: {} near '{}'", e, near),
+                        );
+                        sql.to_string()
+                    }
+                    ParseFailure::Reject => {
+                        self.log.debug(format_args!(
+                            "Failed to translate query, rejecting it: {}",
+                            e
+                        ));
+                        self.stats
+                            .record_failure(failures::translate_error(&e), sql);
+                        return Err(MysqlError::new(
+                            ErrorKind::ER_PARSE_ERROR,
+                            format!(
+                                "You have an error in your SQL syntax: {} near '{}'",
+                                e, near
+                            ),
+                        ));
+                    }
+                }
+            }
+        };
+        let translated =
+            emulation::virtual_tables::expand(&translated, &self.stats).unwrap_or(translated);
+        let user = self.user.get().map_or("", String::as_str);
+        let translated = emulation::connect_attrs::expand(&translated, &self.sessions, user)
+            .unwrap_or(translated);
+        let translated = emulation::information_schema::expand(&translated).unwrap_or(translated);
+        let read_only = self
+            .transaction_modes
+            .read_only(self.status.in_transaction());
+        let translated = transaction_modes::variables(&translated, read_only).unwrap_or(translated);
+        let charsets = self.commands.charsets();
+        let time_zone = &self.time_zone;
+        let sql_mode = self.sql_mode();
+        let server_sql_mode = self.server_sql_mode();
+        let translated = system_variables::substitute(&translated, &|name| {
+            let value = charsets
+                .variable(name)
+                .or_else(|| time_zone.variable(name).map(Some))
+                .or_else(|| match name {
+                    "sql_mode" => Some(Some(sql_mode.clone())),
+                    "global.sql_mode" => Some(Some(server_sql_mode.clone())),
+                    _ => None,
+                })?;
+            Some(match value {
+                Some(value) => Token::String(literals::pg_string(&value)),
+                None => Token::Word("NULL".to_string()),
+            })
+        })
+        .unwrap_or(translated);
+        // Point-in-time reads: /*+ AS_OF '...' */
+        let translated = match snapshot::hint(sql) {
+            Some(timestamp) => {
+                match snapshot::rewrite(&self.pg_client(), &translated, &timestamp).await {
+                    Ok(rewritten) => rewritten,
+                    Err(error) => {
+                        self.log.debug(format_args!("AS_OF read failed: {}", error));
+                        if error.kind == ErrorKind::ER_NOT_SUPPORTED_YET {
+                            self.stats
+                                .record_failure(Failure::new(Category::Emulation, "AS_OF"), sql);
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+            None => translated,
+        };
+        // information_schema.routines with the MySQL source of the routines.
+        let translated = match routine_sources::rewrite(&self.pg_client(), &translated).await {
+            Ok(rewritten) => rewritten.unwrap_or(translated),
+            Err(e) => return Err(MysqlError::from(e)),
+        };
+        // proxy_stats.ddl_history, from the schema changes kept.
+        let translated = match ddl_history::rewrite(&self.pg_client(), &translated).await {
+            Ok(rewritten) => rewritten.unwrap_or(translated),
+            Err(e) => return Err(MysqlError::from(e)),
+        };
+        // CALL of a set-returning function standing in for a procedure.
+        let translated = match call::rewrite(&self.pg_client(), sql, &translated).await {
+            Ok(rewritten) => rewritten.unwrap_or(translated),
+            Err(e) => return Err(MysqlError::from(e)),
+        };
+        // Zero and impossible dates given to date columns.
+        let modes = self.translator.options().dates;
+        let translated = match date_columns::rewrite(&self.pg_client(), &translated, modes).await {
+            Ok(rewritten) => rewritten.unwrap_or(translated),
+            Err(e) => return Err(MysqlError::from(e)),
+        };
+        // NOT NULL columns an INSERT leaves out, given MySQL's implicit defaults.
+        let translated = if self.implicit_defaults {
+            match implicit_defaults::rewrite(&self.pg_client(), &translated).await {
+                Ok(Some((rewritten, columns))) => {
+                    for column in columns {
+                        self.diagnostics.push(
+                            Level::Warning,
+                            ErrorKind::ER_NO_DEFAULT_FOR_FIELD,
+                            format!("Field '{}' doesn't have a default value", column),
+                        );
+                    }
+                    rewritten
+                }
+                Ok(None) => translated,
+                Err(e) => return Err(MysqlError::from(e)),
+            }
+        } else {
+            translated
+        };
+        let translated =
+            match intercept::after_translate(&self.interceptors, &self.context(), &translated)? {
+                Some(rewritten) => rewritten,
+                None => translated,
+            };
+        self.profiler.mark(Phase::Translate);
+        if translated != sql {
+            self.log.debug(format_args!(
+                "Translated SQL query: {}",
+                self.log.sql(&translated)
+            ));
+        }
+        Ok(translated)
+    }
+
+    // Makes `db` the current database: MySQL databases are PostgreSQL schemas, so this sets the
+    // search_path.
+    async fn use_database(&mut self, db: &str) -> Result<(), MysqlError> {
+        let schema = db.to_lowercase();
+        let exists = self.schema_exists(&schema).await?;
+        if !exists && !self.auto_create_databases {
+            return Err(MysqlError::new(
+                ErrorKind::ER_BAD_DB_ERROR,
+                format!("Unknown database '{}'", db),
+            ));
+        }
+        if !exists {
+            self.create_schema(&schema).await?;
+        }
+        self.pg_client()
+            .batch_execute(&format!(
+                "SET search_path TO {}",
+                translator::literals::pg_identifier(&schema)
+            ))
+            .await?;
+        self.sessions.set_db(self.connection_id, Some(&schema));
+        // Names the statements resolved could now be other tables, of other columns.
+        self.statement_cache.clear();
+        self.database = Some(schema);
+        Ok(())
+    }
+
+    async fn schema_exists(&self, schema: &str) -> Result<bool, MysqlError> {
+        Ok(self
+            .pg_client()
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)",
+                &[&schema],
+            )
+            .await?
+            .get(0))
+    }
+
+    // Creates the schema of a database that doesn't exist yet (AUTO_CREATE_DATABASES), as the
+    // tools written for a fresh MySQL server expect to use any database they name.
+    async fn create_schema(&mut self, schema: &str) -> Result<(), MysqlError> {
+        self.pg_client()
+            .batch_execute(&format!(
+                "CREATE SCHEMA IF NOT EXISTS {}",
+                translator::literals::pg_identifier(schema)
+            ))
+            .await?;
+        self.log.info(format_args!(
+            "Created schema {} for an unknown database",
+            schema
+        ));
+        Ok(())
+    }
+
+    // Creates the schema a CREATE TABLE or the like names, unless it is there already.
+    async fn ensure_schema(&mut self, schema: &str) -> Result<(), MysqlError> {
+        match self.schema_exists(schema).await? {
+            true => Ok(()),
+            false => self.create_schema(schema).await,
+        }
+    }
+
+    // CREATE DATABASE: the schema USE makes current.
+    async fn create_database(&mut self, name: &str, if_not_exists: bool) -> Result<(), MysqlError> {
+        if self.schema_exists(name).await? {
+            if if_not_exists {
+                self.log.debug(format_args!(
+                    "Database {} already exists, skipping creation.",
+                    name
+                ));
+                return Ok(());
+            }
+            return Err(MysqlError::new(
+                ErrorKind::ER_DB_CREATE_EXISTS,
+                format!("Can't create database '{}'; database exists", name),
+            ));
+        }
+        self.pg_client()
+            .batch_execute(&format!(
+                "CREATE SCHEMA {}",
+                translator::literals::pg_identifier(name)
+            ))
+            .await?;
+        self.log
+            .info(format_args!("Database {} created successfully.", name));
+        Ok(())
+    }
+
+    // DROP DATABASE: the schema and everything in it, as MySQL drops the database's tables.
+    async fn drop_database(&mut self, name: &str, if_exists: bool) -> Result<(), MysqlError> {
+        if !self.schema_exists(name).await? {
+            if if_exists {
+                return Ok(());
+            }
+            return Err(MysqlError::new(
+                ErrorKind::ER_DB_DROP_EXISTS,
+                format!("Can't drop database '{}'; database doesn't exist", name),
+            ));
+        }
+        self.pg_client()
+            .batch_execute(&format!(
+                "DROP SCHEMA {} CASCADE",
+                translator::literals::pg_identifier(name)
+            ))
+            .await?;
+        self.statement_cache.clear();
+        // Dropping the current database leaves the session without one, as in MySQL.
+        if self.database.as_deref() == Some(name) {
+            self.database = None;
+            self.sessions.set_db(self.connection_id, None);
+        }
+        self.log.info(format_args!("Database {} dropped.", name));
+        Ok(())
+    }
+
+    // How the client sends its password: as AUTH_PROVIDER wants it, or scrambled when anyone may
+    // log in and it isn't looked at.
+    fn auth_method(&self) -> Method {
+        self.auth
+            .as_ref()
+            .map_or(Method::NativePassword, |auth| auth.method())
+    }
+
+    // Whether `user` may log in with `auth_data`, the password as the client sent it, at login or
+    // COM_CHANGE_USER; ER_ACCESS_DENIED_ERROR if not. Clients of the admin listener, which only
+    // binds loopback addresses, are trusted and log in as any user.
+    async fn check_password(&self, user: &str, auth_data: &[u8]) -> Result<Identity, MysqlError> {
+        if self.admin {
+            return Ok(Identity::default());
+        }
+        // A certificate whose subject USER_REQUIRE names stands for the password.
+        if let Some((_, requirement)) = self.requirements.iter().find(|(name, _)| name == user) {
+            if !requirement.met_by(self.tls.get()) {
+                self.log.info(format_args!(
+                    "Connection of {:?} from {} doesn't meet REQUIRE {}",
+                    user, self.peer, requirement
+                ));
+                return Err(self.access_denied(user, auth_data));
+            }
+            if requirement.replaces_password() {
+                return Ok(Identity::default());
+            }
+        }
+        let Some(auth) = &self.auth else {
+            return Ok(Identity::default());
+        };
+        let credentials = match auth.method() {
+            Method::NativePassword => Credentials::Scrambled {
+                salt: &self.salt,
+                scramble: auth_data,
+            },
+            // Sent with a NUL at the end.
+            Method::ClearPassword => {
+                let password = auth_data.strip_suffix(b"\0").unwrap_or(auth_data);
+                Credentials::Clear(std::str::from_utf8(password).unwrap_or_default())
+            }
+        };
+        match auth.authenticate(user, credentials, self.peer).await {
+            Ok(Some(identity)) => return Ok(identity),
+            Ok(None) => self.log.info(format_args!(
+                "Wrong password for {:?} from {}",
+                user, self.peer
+            )),
+            Err(e) => self.log.error(format_args!(
+                "Failed to authenticate {:?} from {}: {}",
+                user, self.peer, e
+            )),
+        }
+        Err(self.access_denied(user, auth_data))
+    }
+
+    fn access_denied(&self, user: &str, auth_data: &[u8]) -> MysqlError {
+        MysqlError::new(
+            ErrorKind::ER_ACCESS_DENIED_ERROR,
+            format!(
+                "Access denied for user '{}'@'{}' (using password: {})",
+                user,
+                self.peer.ip(),
+                if auth_data.is_empty() { "NO" } else { "YES" }
+            ),
+        )
+    }
+
+    // Replaces the session if it has been lost since the last command.
+    async fn check_session(&mut self) -> Result<(), MysqlError> {
+        self.open_session(None).await
+    }
+
+    // Fails the first statement after the session was lost in a transaction, but for a ROLLBACK,
+    // as the transaction went with it (see reconnect.rs).
+    fn check_transaction(&mut self, sql: &str) -> Result<(), MysqlError> {
+        match std::mem::take(&mut self.transaction_lost) {
+            true if !reconnect::is_rollback(sql) => Err(reconnect::rolled_back()),
+            _ => Ok(()),
+        }
+    }
+
+    // What a statement that found its session lost in a transaction fails with, in place of
+    // `error`: the transaction was rolled back with it.
+    fn lost_transaction(&mut self, error: MysqlError) -> MysqlError {
+        match std::mem::take(&mut self.transaction_lost) {
+            true => reconnect::rolled_back(),
+            false => error,
+        }
+    }
+
+    // Reconnects if the session was lost, and as the first command after login runs, uses
+    // `database`, or else the user's default database, takes the role the auth provider gave the
+    // user, and runs the user's init statements (see session_init.rs). After that, `database` is
+    // used as any other.
+    async fn open_session(&mut self, database: Option<&str>) -> Result<(), MysqlError> {
+        if self.pg_client().is_closed() {
+            self.reconnect().await?;
+        }
+        if self.session_started {
+            return match database {
+                Some(db) => self.use_database(db).await,
+                None => Ok(()),
+            };
+        }
+        let inits = Arc::clone(&self.session_inits);
+        let init = self.user.get().and_then(|user| inits.get(user));
+        if let Some(db) = database.or(init.and_then(|init| init.database.as_deref())) {
+            self.use_database(db).await?;
+        }
+        if let Some(sql) = self.session_sql() {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        self.session_started = true;
+        Ok(())
+    }
+
+    // The SETs of the session's read-only mode and role, and the user's init statements, once
+    // they have run as the session started.
+    fn init_sql(&self) -> Option<String> {
+        self.session_sql().filter(|_| self.session_started)
+    }
+
+    // What the user's sessions start with: read-only transactions in READ_ONLY mode, so
+    // PostgreSQL refuses the writes the statement policy doesn't, their role, then their init
+    // statements.
+    fn session_sql(&self) -> Option<String> {
+        let read_only = self
+            .policy
+            .read_only
+            .then(|| "SET default_transaction_read_only = on".to_string());
+        let role = self
+            .role
+            .get()
+            .map(|role| format!("SET ROLE {}", literals::pg_identifier(role)));
+        let init = self
+            .user
+            .get()
+            .and_then(|user| self.session_inits.get(user))
+            .and_then(SessionInit::sql);
+        let sql: Vec<String> = [read_only, role, init].into_iter().flatten().collect();
+        (!sql.is_empty()).then(|| sql.join("; "))
+    }
+
+    // The client's PostgreSQL session, opened as it logged in.
+    fn pg_client(&self) -> Session {
+        let (session, _) = self
+            .upstream_session
+            .get()
+            .expect("commands only run once the client has logged in");
+        Arc::clone(session)
+    }
+
+    // The process id of the client's PostgreSQL session, for KILL to cancel its statements.
+    fn backend_pid(&self) -> i32 {
+        self.upstream_session.get().map_or(0, |(_, pid)| *pid)
+    }
+
+    // A session from the upstream, tried DB_RECONNECT_ATTEMPTS more times, and its process id.
+    async fn connect_upstream(&self) -> Result<(Session, i32), MysqlError> {
+        let session = reconnect::connect(&*self.upstream, self.reconnect_attempts, self.log)
+            .await
+            .map_err(|e| failover::unavailable(&e))?;
+        let backend_pid: i32 = session
+            .query_one("SELECT pg_backend_pid()", &[])
+            .await
+            .map_err(|e| failover::unavailable(&e))?
+            .get(0);
+        self.sessions
+            .set_backend_pid(self.connection_id, backend_pid);
+        Ok((session, backend_pid))
+    }
+
+    // Replaces a lost session with a new one, with the current database and the prepared
+    // statements of the old one (see reconnect.rs).
+    async fn reconnect(&mut self) -> Result<(), MysqlError> {
+        self.log.info(format_args!(
+            "Lost the PostgreSQL session of connection {}, reconnecting",
+            self.connection_id
+        ));
+        self.upstream_session = OnceLock::from(self.connect_upstream().await?);
+        self.statement_cache.clear();
+        self.cursors.clear();
+        self.transaction_lost |= self.status.in_transaction();
+        self.status.set_in_transaction(false);
+        // The locks went with the old session; this only forgets them.
+        if let Err(e) = self
+            .locks
+            .release_all(&self.pg_client(), self.connection_id)
+            .await
+        {
+            self.log.debug(format_args!(
+                "Failed to forget the locks of a lost session: {}",
+                e
+            ));
+        }
+        let ids: Vec<u32> = self.statements.keys().copied().collect();
+        for id in ids {
+            let translated = self.statements[&id].translated.clone();
+            match self
+                .statement_cache
+                .prepare(&self.pg_client(), &translated)
+                .await
+            {
+                Ok(statement) => self.statements.get_mut(&id).unwrap().statement = statement,
+                Err(e) => {
+                    self.log.info(format_args!(
+                        "Failed to prepare statement {} again after reconnecting: {}",
+                        id, e
+                    ));
+                    self.statements.remove(&id);
+                }
+            }
+        }
+        if let Some(sql) = self.init_sql() {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        if let Some(sql) = self.transaction_modes.session_sql() {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        if let Some(sql) = self.mapped_settings.session_sql() {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        if let Some(sql) = self.time_zone.session_sql() {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        match self.database.take() {
+            Some(db) => self.use_database(&db).await,
+            None => Ok(()),
+        }
+    }
+
+    // After a statement failed: reconnects if the session was lost, and if `prepared`, the
+    // statement's translation, can be run again on the new session, prepares it there.
+    async fn retry(&mut self, prepared: &str) -> Option<Statement> {
+        // A ROLLBACK of the transaction lost with the session has nothing left to undo.
+        let rollback = reconnect::is_rollback(prepared);
+        let retryable =
+            rollback || (!self.status.in_transaction() && reconnect::retryable(prepared));
+        // A session terminated under a statement fails it before the client notices it's closed,
+        // so one that could be retried is asked whether it's still there.
+        let lost = self.pg_client().is_closed()
+            || (retryable && self.pg_client().simple_query("").await.is_err());
+        if !lost {
+            return None;
+        }
+        if let Err(error) = self.reconnect().await {
+            self.log.info(format_args!("{}", error));
+            return None;
+        }
+        if !retryable {
+            return None;
+        }
+        if rollback {
+            self.transaction_lost = false;
+        }
+        self.log.info(format_args!(
+            "Running the statement of connection {} again on the new session",
+            self.connection_id
+        ));
+        self.statement_cache
+            .prepare(&self.pg_client(), prepared)
+            .await
+            .ok()
+    }
+
+    // SET TRANSACTION: kept for the next transaction, or set on the session.
+    async fn set_transaction(
+        &mut self,
+        scope: Scope,
+        characteristics: Characteristics,
+    ) -> Result<(), MysqlError> {
+        let in_transaction = self.status.in_transaction();
+        if let Some(sql) = self
+            .transaction_modes
+            .set(scope, characteristics, in_transaction)?
+        {
+            self.pg_client().batch_execute(&sql).await?;
+        }
+        Ok(())
+    }
+
+    // A SET of mapped variables: their GUCs set on the session, and kept to be set again.
+    async fn set_mapped(
+        &mut self,
+        assignments: Vec<(&GucMapping, guc_mappings::Setting)>,
+    ) -> Result<(), MysqlError> {
+        let sql: Vec<String> = assignments
+            .iter()
+            .map(|(mapping, setting)| guc_mappings::sql(mapping, setting))
+            .collect();
+        self.pg_client().batch_execute(&sql.join("; ")).await?;
+        for (mapping, setting) in assignments {
+            self.mapped_settings.set(mapping, setting);
+        }
+        Ok(())
+    }
+
+    // SET time_zone: the zone set on the session, and kept to be set again. One PostgreSQL
+    // doesn't know is error 1298.
+    async fn set_time_zone(&mut self, zone: TimeZone) -> Result<(), MysqlError> {
+        match self.pg_client().batch_execute(&zone.sql()).await {
+            Ok(()) => {
+                self.time_zone = zone;
+                Ok(())
+            }
+            Err(e) if e.code() == Some(&SqlState::INVALID_PARAMETER_VALUE) => {
+                Err(time_zone::unknown(&zone.name()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // The session's sql_mode, as @@sql_mode reads it.
+    fn sql_mode(&self) -> String {
+        let sql_mode = &self.translator.options().sql_mode;
+        sql_mode::normalize(sql_mode).unwrap_or_else(|| sql_mode.clone())
+    }
+
+    // SQL_MODE, as @@global.sql_mode reads it.
+    fn server_sql_mode(&self) -> String {
+        let sql_mode = &self.server_translator.options().sql_mode;
+        sql_mode::normalize(sql_mode).unwrap_or_else(|| sql_mode.clone())
+    }
+
+    // How each cache is used, for SHOW PROXY CACHES.
+    fn caches(&self) -> Vec<CacheUsage> {
+        let [entries, hits, misses, evictions] = self.stats.statement_cache();
+        let connections = self.sessions.processes().len() as u64;
+        let mut caches = vec![CacheUsage {
+            name: "statement cache",
+            entries,
+            capacity: self.statement_cache.size() as u64 * connections,
+            hits,
+            misses,
+            evictions,
+        }];
+        let [hits, misses, evictions] = self.stats.result_cache();
+        let (entries, capacity) = self
+            .result_cache
+            .as_ref()
+            .map_or((0, 0), |cache| cache.size());
+        caches.push(CacheUsage {
+            name: "result cache",
+            entries: entries as u64,
+            capacity: capacity as u64,
+            hits,
+            misses,
+            evictions,
+        });
+        caches
+    }
+
+    // Puts the session back the way it was after login, as COM_RESET_CONNECTION does: the
+    // transaction is rolled back, and temporary tables, prepared statements, user-level locks,
+    // session settings and diagnostics are dropped. The current database is kept.
+    async fn reset(&mut self) -> Result<(), MysqlError> {
+        self.statements.clear();
+        self.statement_cache.clear();
+        self.cursors.clear();
+        self.diagnostics.reset();
+        self.profiler = Profiler::default();
+        self.locks
+            .release_all(&self.pg_client(), self.connection_id)
+            .await?;
+        self.pg_client()
+            .batch_execute(
+                "ROLLBACK; CLOSE ALL; RESET ALL; RESET ROLE; DISCARD TEMP; DISCARD SEQUENCES; \
+                 UNLISTEN *",
+            )
+            .await?;
+        self.status.set_in_transaction(false);
+        self.transaction_lost = false;
+        self.transaction_modes = TransactionModes::default();
+        self.mapped_settings = MappedSettings::default();
+        self.time_zone = TimeZone::default();
+        self.translator = Arc::clone(&self.server_translator);
+        self.commands.reset_charsets();
+        if let Some(db) = self.database.take() {
+            self.use_database(&db).await?;
+        }
+        // RESET ALL undid them, and RESET ROLE the role, which after COM_CHANGE_USER is the
+        // previous user's.
+        match self.init_sql() {
+            Some(sql) => Ok(self.pg_client().batch_execute(&sql).await?),
+            None => Ok(()),
+        }
+    }
+
+    // Runs a command taken out of the client's stream (see protocol.rs).
+    async fn run_command<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        command: Command,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        if let Err(error) = self.check_session().await {
+            self.log.info(format_args!("Command failed: {}", error));
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        }
+        let done = match command {
+            // Connection pools ping to check a connection is still good, which it isn't
+            // without its PostgreSQL session.
+            Command::Ping => self
+                .pg_client()
+                .simple_query("")
+                .await
+                .map(drop)
+                .map_err(MysqlError::from),
+            Command::ResetConnection => {
+                self.log
+                    .info(format_args!("Resetting connection {}", self.connection_id));
+                self.reset().await
+            }
+            // The new user is checked as at login: clients of the admin listener may become any
+            // user without a password, others must give the new user's password, where an auth
+            // provider checks passwords, and meet its USER_REQUIRE.
+            Command::ChangeUser {
+                user,
+                auth_response,
+                database,
+            } => {
+                self.log.info(format_args!(
+                    "Changing user of connection {} to {:?}",
+                    self.connection_id, user
+                ));
+                let identity = match self.check_password(&user, &auth_response).await {
+                    Ok(identity) => identity,
+                    Err(error) => {
+                        self.diagnostics.push_error(&error);
+                        return error.write(results).await;
+                    }
+                };
+                // The connection leaves its user's count before joining the new user's.
+                let slot = self.user_slot.get_mut().unwrap();
+                let previous = slot.take();
+                match self.throttle.login(&user) {
+                    Ok(joined) => {
+                        *slot = Some(joined);
+                        self.sessions.set_user(self.connection_id, &user);
+                        self.user = OnceLock::from(user);
+                        self.role = identity.role.map_or_else(OnceLock::new, OnceLock::from);
+                        self.database = None;
+                        self.sessions.set_db(self.connection_id, None);
+                        // The session starts again as the new user's.
+                        self.session_started = false;
+                        match self.reset().await {
+                            Ok(()) => self.open_session(database.as_deref()).await,
+                            Err(error) => Err(error),
+                        }
+                    }
+                    Err(error) => {
+                        *slot = previous;
+                        Err(error)
+                    }
+                }
+            }
+            // Answered with column definitions rather than OK.
+            Command::FieldList { table, wildcard } => {
+                return self.field_list(&table, &wildcard, results).await;
+            }
+            Command::Fetch { statement, rows } => {
+                return self.fetch(statement, rows, results).await;
+            }
+            Command::TooLarge => Err(protocol::packet_too_large()),
+        };
+        match done {
+            Ok(()) => results.completed(OkResponse::default()).await,
+            Err(error) => {
+                self.log.info(format_args!("Command failed: {}", error));
+                self.diagnostics.push_error(&error);
+                error.write(results).await
+            }
+        }
+    }
+
+    // Sends the columns of a table for COM_FIELD_LIST. The column definitions go out without
+    // opensrv, which has no way to send them without a result set (see protocol.rs).
+    async fn field_list<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        table: &str,
+        wildcard: &str,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        let columns = match self.policy.check_field_list(&self.context(), table) {
+            Ok(()) => emulation::field_list::columns(&self.pg_client(), table, wildcard).await,
+            Err(error) => Err(error),
+        };
+        match columns {
+            Ok(columns) => {
+                let database = self.database.as_deref().unwrap_or_default();
+                self.commands
+                    .reply_field_list(database, table, &columns, &self.status);
+                Ok(())
+            }
+            Err(error) => {
+                self.log
+                    .info(format_args!("COM_FIELD_LIST failed: {}", error));
+                self.diagnostics.push_error(&error);
+                error.write(results).await
+            }
+        }
+    }
+
+    // Opens a cursor for prepared statement `id`, `sql` as the client sent it, in place of
+    // sending its rows. The reply, its columns, goes out without opensrv (see protocol.rs).
+    async fn open_cursor<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        id: u32,
+        statement: &Statement,
+        translated: &str,
+        params: &[&(dyn ToSql + Sync)],
+        sql: &str,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        self.close_cursor(id).await;
+        let columns: Vec<Column> = statement
+            .columns()
+            .iter()
+            .map(|col| upstream::column(col.name(), col.type_()))
+            .collect();
+        let cursor = Cursor::new(id, columns);
+        let declare = cursor.declare(translated);
+        let client = self.pg_client();
+        let execution = client
+            .execute(declare.as_str(), params)
+            .instrument(tracing::info_span!("execute"));
+        let declared = self.execute(sql, execution).await;
+        self.profiler.mark(Phase::Execute);
+        match declared {
+            Ok(_) => {
+                self.log
+                    .debug(format_args!("Opened cursor {}", cursor.name));
+                self.commands.reply_cursor(&cursor.columns, &self.status);
+                self.cursors.insert(id, cursor);
+                Ok(())
+            }
+            Err(error) => {
+                self.report(sql, Outcome::Failed(&error));
+                self.diagnostics.push_error(&error);
+                error.write(results).await
+            }
+        }
+    }
+
+    // COM_STMT_FETCH: sends the next `rows` rows of the cursor of prepared statement `id`,
+    // closing it after the last. The rows go out without opensrv (see protocol.rs).
+    async fn fetch<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        id: u32,
+        rows: u32,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        let Some(cursor) = self.cursors.get(&id) else {
+            let error = cursors::no_open_cursor(id);
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        };
+        let fetch = cursor.fetch(rows);
+        let columns = cursor.columns.clone();
+        let client = self.pg_client();
+        let execution = client.query(fetch.as_str(), &[]);
+        let fetched = match self.execute(&fetch, execution).await {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                self.log
+                    .info(format_args!("COM_STMT_FETCH failed: {}", error));
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        let charsets = self.commands.charsets();
+        let zero_dates = self.translator.options().dates.zero_date_results();
+        let mut payloads = Vec::with_capacity(fetched.len());
+        for row in &fetched {
+            let values = (0..row.len())
+                .map(|i| upstream::value(row, i, zero_dates))
+                .collect::<io::Result<Vec<_>>>()?;
+            let values = charsets.encode_row(&columns, values);
+            payloads.push(cursors::binary_row(&values, &columns)?);
+        }
+        let done = fetched.len() < rows as usize;
+        if done {
+            self.close_cursor(id).await;
+        }
+        self.commands.reply_fetch(&payloads, done, &self.status);
+        Ok(())
+    }
+
+    // Closes the cursor of prepared statement `id`, if it has one.
+    async fn close_cursor(&mut self, id: u32) {
+        let Some(cursor) = self.cursors.remove(&id) else {
+            return;
+        };
+        // Gone already if its transaction was rolled back, which is as good.
+        let _ = self.pg_client().batch_execute(&cursor.close()).await;
+    }
+
+    // Counts a statement PostgreSQL rejected towards the construct it didn't accept, if that
+    // is what the error is about.
+    fn record_upstream_failure(&self, sql: &str, e: &tokio_postgres::Error) {
+        if let Some(failure) = failures::upstream_error(sql, e) {
+            self.stats.record_failure(failure, sql);
+        }
+    }
+
+    // Notes whether a transaction is open once `sql` has run. COMMIT and ROLLBACK end it even
+    // when they fail.
+    fn track_transaction(&self, sql: &str, succeeded: bool) {
+        match upstream::transaction_change(sql) {
+            Some(in_transaction) if succeeded || !in_transaction => {
+                self.status.set_in_transaction(in_transaction)
+            }
+            _ => {}
+        }
+    }
+
+    // Whether the result of `sql`, run as `translated` with `params`, is one for the result
+    // cache. Statements in a transaction could read its own writes, so they never are.
+    fn cacheable(
+        &self,
+        sql: &str,
+        translated: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Option<Cacheable> {
+        if self.status.in_transaction() {
+            return None;
+        }
+        let cache = self.result_cache.as_ref()?;
+        cache.cacheable(
+            sql,
+            translated,
+            params,
+            self.database.as_deref(),
+            &self.cache_session(),
+        )
+    }
+
+    // What of the session a result depends on, beyond its statement: the user, whose grants
+    // and init statements apply, the role PostgreSQL reads as, the time zone, the translation
+    // options with the sql_mode and date modes, the character sets and the mapped settings.
+    fn cache_session(&self) -> String {
+        format!(
+            "{:?} {:?} {:?} {:?} {:?} {:?}",
+            self.user.get(),
+            self.role.get(),
+            self.time_zone.session_sql(),
+            self.translator.options(),
+            self.commands.charsets(),
+            self.mapped_settings.session_sql(),
+        )
+    }
+
+    // Sends the client a result from the result cache, as run() sends one from PostgreSQL.
+    async fn send_cached<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        sql: &str,
+        cached: &CachedResult,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        self.log
+            .debug(format_args!("Answered from the result cache: {:?}", sql));
+        self.report(sql, Outcome::Rows(cached.rows.len()));
+        let charsets = self.commands.charsets();
+        let mut w = results.start(&cached.columns).await?;
+        for row in &cached.rows {
+            w.write_row(charsets.encode_row(&cached.columns, row.clone()))
+                .await?;
+        }
+        w.finish().await?;
+        self.expected = self.shadow.as_ref().map(|_| {
+            Expected::Rows(
+                cached
+                    .rows
+                    .iter()
+                    .map(|row| row.iter().map(shadow::text).collect())
+                    .collect(),
+            )
+        });
+        Ok(())
+    }
+
+    // Drops the cached results of the tables `sql` wrote, if it `succeeded`, and once the
+    // transaction it wrote them in has ended, again: other connections could have cached what
+    // they read until it committed.
+    fn note_writes(&mut self, sql: &str, succeeded: bool) {
+        let Some(cache) = &self.result_cache else {
+            return;
+        };
+        let written = succeeded
+            .then(|| result_cache::written(sql, self.database.as_deref()))
+            .flatten();
+        if let Some(written) = written {
+            cache.invalidate(&written);
+            if self.status.in_transaction() {
+                self.cache_writes.push(written);
+            }
+        }
+        if !self.status.in_transaction() {
+            for written in self.cache_writes.drain(..) {
+                cache.invalidate(&written);
+            }
+        }
+    }
+
+    // Runs a statement sent with COM_QUERY.
+    async fn query<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        sql: &str,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        self.log
+            .debug(format_args!("Received SQL query: {}", self.log.sql(sql)));
+        let checked = self
+            .limits
+            .check_length(sql)
+            .and_then(|()| self.limits.check_depth(sql))
+            .and_then(|()| self.throttle.statement(self.context().user));
+        if let Err(error) = checked {
+            self.diagnostics.clear();
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        }
+        let checked = match self.check_session().await {
+            Ok(()) => self.check_transaction(sql),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = checked {
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        }
+        let _running = self.sessions.start(self.connection_id, "Query", sql);
+        let intercepted = match self.intercept(sql) {
+            Ok(intercepted) => intercepted,
+            Err(error) => {
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        let sql = intercepted.as_ref();
+
+        // Statements the proxy answers itself (SHOW CREATE ... and friends).
+        if let Some(reply) = emulation::handle(
+            &self.pg_client(),
+            sql,
+            &mut self.diagnostics,
+            &self.locks,
+            self.connection_id,
+            &self.sessions,
+            &self.stats,
+        )
+        .await
+        {
+            self.profiler.mark(Phase::Execute);
+            return match reply {
+                Ok(result) => result.write(results).await,
+                Err(e) => {
+                    self.log.debug(format_args!("Emulated query failed: {}", e));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SHOW PROXY CACHES.
+        if emulation::caches::parse(sql) {
+            let result = emulation::caches::execute(&self.caches(), &self.upstream.sessions());
+            self.profiler.mark(Phase::Execute);
+            return result.write(results).await;
+        }
+
+        // SET profiling and SHOW PROFILE(S).
+        if let Some(statement) = emulation::profiling::parse(sql) {
+            let reply = emulation::profiling::execute(&mut self.profiler, statement);
+            self.profiler.mark(Phase::Execute);
+            return match reply {
+                Ok(Some(result)) => result.write(results).await,
+                Ok(None) => results.completed(OkResponse::default()).await,
+                Err(e) => {
+                    self.log
+                        .debug(format_args!("Profiling statement failed: {}", e));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        let user = self.user.get().map_or("", String::as_str);
+        self.stats.record_statement(user, sql);
+
+        if let Some(count) = emulation::estimated_count::parse(sql, &self.estimated_counts) {
+            let estimated = emulation::estimated_count::execute(
+                &self.pg_client(),
+                &count,
+                &self.estimated_counts,
+            )
+            .await;
+            self.profiler.mark(Phase::Execute);
+            match estimated {
+                Some(Ok(result)) => return result.write(results).await,
+                Some(Err(e)) => {
+                    self.log
+                        .debug(format_args!("Estimated count failed: {}", e));
+                    self.diagnostics.push_error(&e);
+                    return e.write(results).await;
+                }
+                // Counted exactly by PostgreSQL.
+                None => {}
+            }
+        }
+
+        if let Some(user) = emulation::grants::parse(sql) {
+            let user = user.as_deref().unwrap_or(self.context().user);
+            let result = emulation::grants::execute(&self.policy.grants, &self.requirements, user);
+            self.profiler.mark(Phase::Execute);
+            return result.write(results).await;
+        }
+
+        if let Some(kill) = emulation::kill::parse(sql) {
+            let killed = emulation::kill::execute(
+                &self.pg_client(),
+                &self.sessions,
+                self.connection_id,
+                self.admin || self.policy.admin(self.context().user),
+                kill,
+            )
+            .await;
+            self.profiler.mark(Phase::Execute);
+            return match killed {
+                Ok(()) => results.completed(OkResponse::default()).await,
+                Err(e) => {
+                    self.log.debug(format_args!("KILL failed: {}", e));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // LOAD DATA INFILE.
+        if let Some(load) = load_data::parse(sql) {
+            let directory = self.secure_file_priv.as_deref();
+            let dates = self.translator.options().dates;
+            let loaded = match load {
+                Ok(load) => load_data::execute(&self.pg_client(), &load, directory, dates).await,
+                Err(e) => Err(e),
+            };
+            self.profiler.mark(Phase::Execute);
+            return match loaded {
+                Ok(records) => {
+                    self.note_writes(sql, true);
+                    self.report(sql, Outcome::Affected(records));
+                    let response = OkResponse {
+                        affected_rows: records,
+                        info: format!("Records: {}  Deleted: 0  Skipped: 0  Warnings: 0", records),
+                        ..Default::default()
+                    };
+                    results.completed(response).await
+                }
+                Err(e) => {
+                    self.log.debug(format_args!("LOAD DATA failed: {}", e));
+                    self.report(sql, Outcome::Failed(&e));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SET TRANSACTION, for the next transaction or the session's.
+        if let Some(TransactionStatement::Set(scope, characteristics)) =
+            transaction_modes::parse(sql)
+        {
+            let set = self.set_transaction(scope, characteristics).await;
+            self.profiler.mark(Phase::Execute);
+            return match set {
+                Ok(()) => {
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(e) => {
+                    self.log
+                        .debug(format_args!("SET TRANSACTION failed: {}", e));
+                    self.expect(|| Expected::Failed(e.code()));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SET of MySQL variables mapped to PostgreSQL settings.
+        let guc_mappings = Arc::clone(&self.guc_mappings);
+        if let Some(assignments) = guc_mappings::parse(sql, &guc_mappings) {
+            let set = self.set_mapped(assignments).await;
+            self.profiler.mark(Phase::Execute);
+            return match set {
+                Ok(()) => {
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(e) => {
+                    self.log.debug(format_args!("SET failed: {}", e));
+                    self.expect(|| Expected::Failed(e.code()));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SET NAMES, SET CHARACTER SET and SETs of the character set variables.
+        if let Some(set) = charset::set(sql, self.commands.charsets()) {
+            self.profiler.mark(Phase::Execute);
+            return match set {
+                Ok(charsets) => {
+                    self.commands.set_charsets(charsets);
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(e) => {
+                    self.log.debug(format_args!("SET failed: {}", e));
+                    self.expect(|| Expected::Failed(e.code()));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SET time_zone.
+        if let Some(set) = time_zone::set(sql) {
+            let set = match set {
+                Ok(zone) => self.set_time_zone(zone).await,
+                Err(e) => Err(e),
+            };
+            self.profiler.mark(Phase::Execute);
+            return match set {
+                Ok(()) => {
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(e) => {
+                    self.log.debug(format_args!("SET failed: {}", e));
+                    self.expect(|| Expected::Failed(e.code()));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        // SET sql_mode: the session's statements are translated with its modes from then on.
+        if let Some(set) = sql_mode::set(sql, &self.sql_mode(), &self.server_sql_mode()) {
+            self.profiler.mark(Phase::Execute);
+            return match set {
+                Ok(modes) => {
+                    self.translator = Arc::new(self.server_translator.with_sql_mode(&modes));
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(e) => {
+                    self.log.debug(format_args!("SET failed: {}", e));
+                    self.expect(|| Expected::Failed(e.code()));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        if let Some(pattern) =
+            translator::significant_tokens(sql).and_then(|t| emulation::variables::parse(&t))
+        {
+            let mut variables = self.commands.charsets().variables();
+            variables.push(("time_zone", self.time_zone.name()));
+            variables.push(("sql_mode", self.sql_mode()));
+            let result = emulation::variables::execute(&variables, &pattern);
+            self.profiler.mark(Phase::Execute);
+            return result.write(results).await;
+        }
+
+        // RESET QUERY CACHE and FLUSH QUERY CACHE empty the result cache.
+        if result_cache::is_reset(sql) {
+            if let Some(cache) = &self.result_cache {
+                cache.invalidate(&Written::All);
+            }
+            self.profiler.mark(Phase::Execute);
+            self.expect(|| Expected::Replay);
+            return results.completed(OkResponse::default()).await;
+        }
+
+        // EXPLAIN of a statement, which needs the statement translated first.
+        if let Some(explain) = emulation::explain::parse(sql) {
+            let reply = match self.translate(explain.statement).await {
+                Ok(translated) => {
+                    emulation::explain::execute(&self.pg_client(), &explain, &translated).await
+                }
+                Err(error) => Err(error),
+            };
+            self.profiler.mark(Phase::Execute);
+            return match reply {
+                Ok(result) => result.write(results).await,
+                Err(e) => {
+                    self.log.debug(format_args!("EXPLAIN failed: {}", e));
+                    self.diagnostics.push_error(&e);
+                    e.write(results).await
+                }
+            };
+        }
+
+        let translated = match self.translate(sql).await {
+            Ok(translated) => translated,
+            Err(error) => {
+                self.expect(|| Expected::Failed(error.code()));
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        // Transactions start with what SET TRANSACTION left for them, and a statement outside
+        // one is a transaction of its own.
+        let translated = match transaction_modes::parse(sql) {
+            Some(TransactionStatement::Start(characteristics)) => {
+                self.transaction_modes.start(characteristics)
+            }
+            _ if self.status.in_transaction() => translated,
+            _ => match self.transaction_modes.autocommit(sql) {
+                Ok(()) => translated,
+                Err(error) => {
+                    self.expect(|| Expected::Failed(error.code()));
+                    self.diagnostics.push_error(&error);
+                    return error.write(results).await;
+                }
+            },
+        };
+        let original = sql;
+        let sql = translated.as_str();
+
+        // Statements answered here, or forwarded with their functions respelled.
+        let mut rewritten = None;
+        let answered = match mysql_specific::classify(sql, self.connection_id) {
+            None => None,
+            Some(Specific::Rewritten(statement)) => {
+                self.log.debug(format_args!(
+                    "Intercepted MySQL-specific query, modified to {:?}",
+                    statement
+                ));
+                rewritten = Some(statement);
+                None
+            }
+            Some(Specific::Ignored) => {
+                self.log.debug(format_args!(
+                    "Intercepted MySQL-specific query, returning dummy response."
+                ));
+                Some(Ok(()))
+            }
+            Some(Specific::CreateDatabase {
+                name,
+                if_not_exists,
+            }) => Some(self.create_database(&name, if_not_exists).await),
+            Some(Specific::DropDatabase { name, if_exists }) => {
+                Some(self.drop_database(&name, if_exists).await)
+            }
+            Some(Specific::Use(db)) => Some(self.use_database(&db).await),
+        };
+        if let Some(done) = answered {
+            self.profiler.mark(Phase::Execute);
+            return match done {
+                Ok(()) => {
+                    self.expect(|| Expected::Replay);
+                    results.completed(OkResponse::default()).await
+                }
+                Err(error) => {
+                    self.log
+                        .debug(format_args!("MySQL-specific query failed: {}", error));
+                    self.expect(|| Expected::Failed(error.code()));
+                    self.diagnostics.push_error(&error);
+                    error.write(results).await
+                }
+            };
+        }
+        let sql = rewritten.as_deref().unwrap_or(sql);
+
+        if let Some(schema) = mysql_specific::created_in(sql).filter(|_| self.auto_create_databases)
+        {
+            if let Err(error) = self.ensure_schema(&schema).await {
+                self.expect(|| Expected::Failed(error.code()));
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        }
+
+        // A SELECT whose result is kept is answered from the result cache.
+        let cacheable = self.cacheable(original, sql, &[]);
+        if let Some(cached) = cacheable
+            .as_ref()
+            .and_then(|c| self.result_cache.as_ref()?.get(c))
+        {
+            self.profiler.mark(Phase::Execute);
+            return self.send_cached(original, &cached, results).await;
+        }
+
+        // Forward other queries to PostgreSQL.
+        let mut prepared = upstream::prepare(
+            &self.pg_client(),
+            &mut self.statement_cache,
+            sql,
+            self.parameterize,
+            self.log,
+        )
+        .instrument(telemetry::prepare_span(sql))
+        .await;
+        // A session lost since the last command may only show as the statement is prepared.
+        if prepared.is_err() && self.retry(sql).await.is_some() {
+            prepared = upstream::prepare(
+                &self.pg_client(),
+                &mut self.statement_cache,
+                sql,
+                self.parameterize,
+                self.log,
+            )
+            .instrument(telemetry::prepare_span(sql))
+            .await;
+        }
+        self.profiler.mark(Phase::Execute);
+        let (statement, prepared, params) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.log
+                    .debug(format_args!("Error executing query: {:?}", e));
+                self.record_upstream_failure(original, &e);
+                let error = self.lost_transaction(MysqlError::from(e));
+                self.expect(|| Expected::Failed(error.code()));
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as _).collect();
+
+        self.run(&statement, &prepared, &params, original, cacheable, results)
+            .await
+    }
+
+    // Forgets the last statement's rows and error, as a statement starts, and tells when it
+    // arrived.
+    fn start_statement(&mut self) -> Instant {
+        self.rows = None;
+        self.expected = None;
+        self.diagnostics.take_error();
+        self.commands.received().unwrap_or_else(Instant::now)
+    }
+
+    // Writes the log record of the statement just run, `sql` as the client sent it with
+    // `command`, and unless it was only prepared, counts it towards its digest and audits it
+    // with the `params` it was executed with, if any.
+    fn log_statement(
+        &mut self,
+        command: &'static str,
+        sql: &str,
+        params: Option<&[Option<String>]>,
+        received: Instant,
+    ) {
+        let duration = received.elapsed();
+        let error_code = self.diagnostics.take_error();
+        let digest = Digest::of(sql);
+        if command != "COM_STMT_PREPARE" {
+            self.stats
+                .record_digest(&digest, duration, error_code.is_some());
+            self.audit(command, sql, params, error_code);
+        }
+        let record = StatementRecord {
+            command,
+            user: self.user.get().map_or("", String::as_str),
+            database: self.database.as_deref(),
+            digest: &digest,
+            duration,
+            rows: self.rows.take(),
+            error_code,
+        };
+        self.log.statement(&record);
+    }
+
+    // Records `sql` in the audit log, if there is one.
+    fn audit(
+        &self,
+        command: &'static str,
+        sql: &str,
+        params: Option<&[Option<String>]>,
+        error_code: Option<u16>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.record(&AuditRecord {
+                connection_id: self.connection_id,
+                client: self.peer,
+                user: self.user.get().map_or("", String::as_str),
+                database: self.database.as_deref(),
+                command,
+                statement: sql,
+                params,
+                error_code,
+                connect_attrs: self.commands.connect_attrs(),
+            });
+        }
+    }
+
+    // Has the shadow MySQL server run the statement just run, `sql` as the client sent it, if
+    // PostgreSQL ran it or would have. `params` are those of an executed prepared statement.
+    fn shadow_statement(&mut self, sql: &str, params: Option<Vec<Option<String>>>) {
+        if let (Some(shadow), Some(expected)) = (&mut self.shadow, self.expected.take()) {
+            shadow.send(sql, params, expected);
+        }
+    }
+
+    // Logs the phase timings of the statement just run, if it was profiled.
+    fn finish_profile(&mut self) {
+        if let Some(profile) = self.profiler.finish() {
+            self.log.info(format_args!("Profile of {}", profile));
+            if let Some(trace) = &self.trace {
+                trace.note(&format!("profile of {}", profile));
+            }
+        }
+    }
+
+    // Keeps the MySQL source of the routine `sql`, a statement as the client sent it that just
+    // succeeded, created, or forgets that of the one it dropped (see routine_sources.rs).
+    async fn note_routine(&self, sql: &str) {
+        let Some(change) = routine_sources::change(sql) else {
+            return;
+        };
+        if let Err(e) = routine_sources::record(&self.pg_client(), &*self.upstream, change).await {
+            self.log.error(format_args!(
+                "Failed to keep the source of a routine of connection {}: {}",
+                self.connection_id, e
+            ));
+        }
+    }
+
+    // The definitions of the tables `sql`, a statement as the client sent it, is about to change,
+    // for the DDL history (see ddl_history.rs); none unless DDL_HISTORY is set.
+    async fn ddl_before(&self, sql: &str) -> Vec<Before> {
+        let targets = match self.ddl_history {
+            true => ddl_history::targets(sql),
+            false => Vec::new(),
+        };
+        if targets.is_empty() {
+            return Vec::new();
+        }
+        ddl_history::before(&self.pg_client(), targets)
+            .await
+            .unwrap_or_else(|e| {
+                self.log.error(format_args!(
+                    "Failed to read the tables a statement of connection {} changes: {}",
+                    self.connection_id, e
+                ));
+                Vec::new()
+            })
+    }
+
+    // Keeps `sql`, a schema change that just succeeded, with the definitions `befores` of the
+    // tables it changed (see ddl_history.rs).
+    async fn note_ddl(&self, sql: &str, befores: Vec<Before>) {
+        if befores.is_empty() {
+            return;
+        }
+        let user = self.context().user;
+        if let Err(e) =
+            ddl_history::record(&self.pg_client(), &*self.upstream, user, sql, befores).await
+        {
+            self.log.error(format_args!(
+                "Failed to keep a schema change of connection {}: {}",
+                self.connection_id, e
+            ));
+        }
+    }
+
+    // Runs `execution`, the statement `sql` as the client sent it, on the session, cancelling it
+    // once it has run for MAX_EXECUTION_TIME.
+    async fn execute<T>(
+        &self,
+        sql: &str,
+        execution: impl Future<Output = Result<T, tokio_postgres::Error>>,
+    ) -> Result<T, MysqlError> {
+        let mut deadline = Deadline::after(self.max_execution_time);
+        self.execute_until(sql, &mut deadline, execution).await
+    }
+
+    // Runs `statement`, a write outside a transaction prepared from `prepared`, with an
+    // idempotency key (see idempotency.rs): should its session be lost, the key tells whether it
+    // took effect, and if it didn't it is run again on the new session.
+    async fn execute_keyed(
+        &mut self,
+        keys: &IdempotencyKeys,
+        statement: &Statement,
+        prepared: &str,
+        params: &[&(dyn ToSql + Sync)],
+        sql: &str,
+    ) -> Result<u64, MysqlError> {
+        let key = keys.next_key();
+        let mut statement = statement.clone();
+        let mut retried = false;
+        loop {
+            let error = match self
+                .execute_with_key(keys, &statement, params, sql, &key)
+                .await
+            {
+                Ok(row_count) => {
+                    self.last_key = Some(key);
+                    return Ok(row_count);
+                }
+                Err(error) => error,
+            };
+            if !self.pg_client().is_closed() && self.pg_client().simple_query("").await.is_ok() {
+                // It failed as it ran, or was cancelled; nothing of it committed.
+                if let Err(e) = self.pg_client().batch_execute("ROLLBACK").await {
+                    self.log
+                        .debug(format_args!("Failed to roll back a keyed write: {}", e));
+                }
+                return Err(error);
+            }
+            if retried {
+                return Err(error);
+            }
+            if let Err(e) = self.reconnect().await {
+                self.log.info(format_args!("{}", e));
+                return Err(error);
+            }
+            match idempotency::recorded(&self.pg_client(), &key).await {
+                Ok(Some(row_count)) => {
+                    self.log.info(format_args!(
+                        "The write of connection {} took effect before its session was lost",
+                        self.connection_id
+                    ));
+                    self.last_key = Some(key);
+                    return Ok(row_count);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.log.info(format_args!(
+                        "Failed to look up the idempotency key of a write: {}",
+                        e
+                    ));
+                    return Err(error);
+                }
+            }
+            statement = match self
+                .statement_cache
+                .prepare(&self.pg_client(), prepared)
+                .await
+            {
+                Ok(statement) => statement,
+                Err(_) => return Err(error),
+            };
+            self.log.info(format_args!(
+                "Running the write of connection {} again on the new session",
+                self.connection_id
+            ));
+            retried = true;
+        }
+    }
+
+    // One try of a keyed write: its transaction, with its key recorded.
+    async fn execute_with_key(
+        &self,
+        keys: &IdempotencyKeys,
+        statement: &Statement,
+        params: &[&(dyn ToSql + Sync)],
+        sql: &str,
+        key: &str,
+    ) -> Result<u64, MysqlError> {
+        keys.create_table(&self.pg_client()).await?;
+        self.pg_client()
+            .batch_execute(&idempotency::begin(self.last_key.as_deref()))
+            .await?;
+        let client = self.pg_client();
+        let execution = client
+            .execute(statement, params)
+            .instrument(tracing::info_span!("execute"));
+        let row_count = self.execute(sql, execution).await?;
+        self.pg_client()
+            .batch_execute(&idempotency::commit(key, row_count))
+            .await?;
+        Ok(row_count)
+    }
+
+    // Runs `execution`, a step of the statement `sql`, cancelling the statement at `deadline`
+    // unless it has been.
+    async fn execute_until<T>(
+        &self,
+        sql: &str,
+        deadline: &mut Option<Deadline>,
+        execution: impl Future<Output = Result<T, tokio_postgres::Error>>,
+    ) -> Result<T, MysqlError> {
+        tokio::pin!(execution);
+        let result = match deadline {
+            None => execution.await,
+            Some(deadline) => {
+                let timed_out = match deadline.cancelled {
+                    true => None,
+                    false => tokio::time::timeout_at(deadline.at.into(), &mut execution)
+                        .await
+                        .ok(),
+                };
+                match timed_out {
+                    Some(result) => result,
+                    None => {
+                        if !deadline.cancelled {
+                            deadline.cancelled = true;
+                            if let Err(e) =
+                                timeouts::cancel(&*self.upstream, self.backend_pid()).await
+                            {
+                                self.log.error(format_args!(
+                                    "Failed to cancel a statement of connection {}: {}",
+                                    self.connection_id, e
+                                ));
+                            }
+                        }
+                        // It may have finished before the cancel got there.
+                        match execution.await {
+                            Err(e) if e.code() == Some(&SqlState::QUERY_CANCELED) => {
+                                return Err(timeouts::exceeded())
+                            }
+                            result => result,
+                        }
+                    }
+                }
+            }
+        };
+        result.map_err(|e| {
+            self.log
+                .debug(format_args!("Error executing query: {:?}", e));
+            self.record_upstream_failure(sql, &e);
+            MysqlError::from(e)
+        })
+    }
+
+    // Runs the prepared statement `sql` and waits for the first of the rows PostgreSQL then
+    // streams, which is when one that fails as it runs fails. The rest are read from the stream
+    // by `deadline`.
+    async fn query_rows(
+        &self,
+        sql: &str,
+        statement: &Statement,
+        params: &[&(dyn ToSql + Sync)],
+        deadline: &mut Option<Deadline>,
+    ) -> Result<(Pin<Box<RowStream>>, Option<Row>), MysqlError> {
+        let client = self.pg_client();
+        let execution = client
+            .query_raw(statement, params.iter().copied())
+            .instrument(tracing::info_span!("execute"));
+        let mut rows = Box::pin(self.execute_until(sql, deadline, execution).await?);
+        let first = self
+            .execute_until(sql, deadline, rows.as_mut().try_next())
+            .await?;
+        Ok((rows, first))
+    }
+
+    // Runs a prepared statement and sends the client its rows or an OK packet. `prepared` is the
+    // text it was prepared from, and `sql` the statement as the client sent it; its rows are kept
+    // in the result cache if it is `cacheable`.
+    //
+    // Rows are sent as PostgreSQL returns them, a row at a time, so that a result of millions of
+    // rows takes no more memory than one; writing to a slow client holds up reading the next. A
+    // statement that fails once rows have been sent ends the result set with the error.
+    async fn run<W: AsyncWrite + Send + Unpin>(
+        &mut self,
+        statement: &Statement,
+        prepared: &str,
+        params: &[&(dyn ToSql + Sync)],
+        sql: &str,
+        cacheable: Option<Cacheable>,
+        results: QueryResultWriter<'_, W>,
+    ) -> io::Result<()> {
+        // Anything that returns rows gets a result set, empty or not: SELECT, but also
+        // INSERT/UPDATE/DELETE ... RETURNING. Everything else gets an OK packet.
+        if statement.columns().is_empty() {
+            let befores = self.ddl_before(sql).await;
+            let executed = match self.idempotency.clone() {
+                Some(keys) if !self.status.in_transaction() && idempotency::keyed(prepared) => {
+                    self.execute_keyed(&keys, statement, prepared, params, sql)
+                        .await
+                }
+                _ => {
+                    let client = self.pg_client();
+                    let execution = client
+                        .execute(statement, params)
+                        .instrument(tracing::info_span!("execute"));
+                    self.execute(sql, execution).await
+                }
+            };
+            self.profiler.mark(Phase::Execute);
+            return match executed {
+                Ok(row_count) => {
+                    self.log.debug(format_args!(
+                        "Query executed successfully, {} rows affected.",
+                        row_count
+                    ));
+                    self.track_transaction(sql, true);
+                    self.note_writes(sql, true);
+                    if policy::changes_schema(sql) {
+                        self.statement_cache.clear();
+                    }
+                    self.note_routine(sql).await;
+                    self.note_ddl(sql, befores).await;
+                    self.report(sql, Outcome::Affected(row_count));
+                    let warnings = self.diagnostics.warning_count();
+                    let response = OkResponse {
+                        affected_rows: row_count,
+                        warnings,
+                        info: upstream::ok_info(sql, row_count, warnings),
+                        ..Default::default()
+                    };
+                    results.completed(response).await
+                }
+                Err(error) => {
+                    self.statement_cache.remove(prepared);
+                    self.track_transaction(sql, false);
+                    self.note_writes(sql, false);
+                    self.report(sql, Outcome::Failed(&error));
+                    self.diagnostics.push_error(&error);
+                    error.write(results).await
+                }
+            };
+        }
+
+        let mut deadline = Deadline::after(self.max_execution_time);
+        let mut queried = self.query_rows(sql, statement, params, &mut deadline).await;
+        if queried.is_err() {
+            if let Some(statement) = self.retry(prepared).await {
+                queried = self
+                    .query_rows(sql, &statement, params, &mut deadline)
+                    .await;
+            }
+        }
+        self.profiler.mark(Phase::Execute);
+        let (mut pg_rows, mut next) = match queried {
+            Ok(rows) => rows,
+            Err(error) => {
+                let error = self.lost_transaction(error);
+                self.statement_cache.remove(prepared);
+                self.report(sql, Outcome::Failed(&error));
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        // INSERT ... RETURNING and the like.
+        self.note_writes(sql, true);
+
+        let cols: Vec<Column> = statement
+            .columns()
+            .iter()
+            .map(|col| upstream::column(col.name(), col.type_()))
+            .collect();
+
+        // Entered while each row is encoded: a guard held across an await would make the future
+        // not Send.
+        let encode = tracing::info_span!("encode");
+        let charsets = self.commands.charsets();
+        let zero_dates = self.translator.options().dates.zero_date_results();
+        // Iterate over rows and send each row to the MySQL client
+        let mut w = results.start(&cols).await?;
+        let mut shadow_rows = self.shadow.as_ref().map(|_| Vec::new());
+        let mut cached_rows = cacheable.as_ref().map(|_| Vec::new());
+        let mut sent = 0;
+        while let Some(row) = next {
+            let mut row_values = Vec::new();
+            for i in 0..cols.len() {
+                row_values.push(upstream::value(&row, i, zero_dates)?);
+            }
+            // Kept only up to the rows the shadow compares and the cache keeps.
+            shadow_rows = shadow_rows.filter(|rows| rows.len() < shadow::MAX_ROWS);
+            if let Some(rows) = &mut shadow_rows {
+                rows.push(row_values.iter().map(shadow::text).collect());
+            }
+            cached_rows = cached_rows.filter(|rows| rows.len() < result_cache::MAX_ROWS);
+            if let Some(rows) = &mut cached_rows {
+                rows.push(row_values.clone());
+            }
+            let encoded = {
+                let _encode = encode.clone().entered();
+                charsets.encode_row(&cols, row_values)
+            };
+            // Write each row separately
+            w.write_row(encoded).await?;
+            sent += 1;
+            next = match self
+                .execute_until(sql, &mut deadline, pg_rows.as_mut().try_next())
+                .await
+            {
+                Ok(row) => row,
+                Err(error) => {
+                    self.statement_cache.remove(prepared);
+                    self.report(sql, Outcome::Failed(&error));
+                    self.diagnostics.push_error(&error);
+                    return error.finish(w).await;
+                }
+            };
+        }
+        w.finish().await?;
+        self.log.debug(format_args!("Sent {} rows", sent));
+        self.report(sql, Outcome::Rows(sent));
+        self.expected = match shadow_rows {
+            Some(rows) => Some(Expected::Rows(rows)),
+            // Too many to compare.
+            None => self.shadow.as_ref().map(|_| Expected::Replay),
+        };
+        if let (Some(cache), Some(cacheable), Some(rows)) =
+            (&self.result_cache, cacheable, cached_rows)
+        {
+            cache.put(
+                cacheable,
+                CachedResult {
+                    columns: cols,
+                    rows,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// MariaDB clients send their extended capability flags in the reserved bytes of the handshake
+// response. opensrv skips those bytes and advertises no extended capabilities of its own, so
+// MariaDB clients fall back to the plain MySQL protocol.
+#[async_trait]
+impl<W: AsyncWrite + Send + Unpin> AsyncMysqlShim<W> for Backend {
+    type Error = io::Error;
+
+    fn connect_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    fn default_auth_plugin(&self) -> &str {
+        self.auth_method().plugin()
+    }
+
+    async fn auth_plugin_for_username<'a>(&'a self, _user: &[u8]) -> &'a str {
+        self.auth_method().plugin()
+    }
+
+    fn salt(&self) -> [u8; auth::SALT_LENGTH] {
+        self.salt
+    }
+
+    async fn authenticate(
+        &self,
+        _auth_plugin: &str,
+        username: &[u8],
+        _salt: &[u8],
+        auth_data: &[u8],
+    ) -> bool {
+        // Let in only to be told to upgrade; see protocol.rs.
+        if self.commands.outdated() {
+            self.log.info(format_args!(
+                "Refusing a client that only has the pre-4.1 protocol or old passwords"
+            ));
+            return false;
+        }
+        let user = String::from_utf8_lossy(username).into_owned();
+        let identity = match self.check_password(&user, auth_data).await {
+            Ok(identity) => identity,
+            Err(error) => {
+                self.commands.refuse(error);
+                return false;
+            }
+        };
+        match self.throttle.login(&user) {
+            Ok(slot) => *self.user_slot.lock().unwrap() = Some(slot),
+            Err(error) => {
+                self.log
+                    .info(format_args!("Refusing {:?}: {}", user, error));
+                self.commands.refuse(error);
+                return false;
+            }
+        }
+        // Only a client that logged in gets a PostgreSQL session, within the handshake's time.
+        match self.connect_upstream().await {
+            Ok(session) => {
+                let _ = self.upstream_session.set(session);
+            }
+            Err(error) => {
+                self.log.error(format_args!(
+                    "Failed to connect to PostgreSQL, refusing {:?}: {}",
+                    user, error
+                ));
+                self.commands.refuse(error);
+                return false;
+            }
+        }
+        self.sessions.set_user(self.connection_id, &user);
+        self.sessions
+            .set_connect_attrs(self.connection_id, self.commands.connect_attrs());
+        let _ = self.user.set(user);
+        if let Some(role) = identity.role {
+            let _ = self.role.set(role);
+        }
+        if let Some((_slot, logged_in)) = self.handshake.lock().unwrap().take() {
+            logged_in.notify_one();
+        }
+        true
+    }
+
+    async fn on_prepare<'a>(
+        &'a mut self,
+        sql: &'a str,
+        info: StatementMetaWriter<'a, W>,
+    ) -> io::Result<()> {
+        let received = self.start_statement();
+        let span = telemetry::query_span(
+            "COM_STMT_PREPARE",
+            self.connection_id,
+            sql,
+            self.commands.received(),
+        );
+        // Prepared on PostgreSQL, translated, with the client told its parameters and columns.
+        let done = async {
+            self.log
+                .debug(format_args!("Received statement to prepare: {:?}", sql));
+            self.diagnostics.clear();
+            let checked = self
+                .limits
+                .check_length(sql)
+                .and_then(|()| self.limits.check_depth(sql))
+                .and_then(|()| self.throttle.statement(self.context().user));
+            if let Err(error) = checked {
+                self.diagnostics.push_error(&error);
+                return info.error(error.kind, error.message.as_bytes()).await;
+            }
+            if let Err(error) = self.check_session().await {
+                self.diagnostics.push_error(&error);
+                return info.error(error.kind, error.message.as_bytes()).await;
+            }
+            let intercepted = match self.intercept(sql) {
+                Ok(intercepted) => intercepted,
+                Err(error) => {
+                    self.diagnostics.push_error(&error);
+                    return info.error(error.kind, error.message.as_bytes()).await;
+                }
+            };
+            let sql = intercepted.as_ref();
+            let user = self.user.get().map_or("", String::as_str);
+            self.stats.record_statement(user, sql);
+
+            let translated = match self.translate(sql).await {
+                Ok(translated) => translated,
+                Err(error) => {
+                    self.diagnostics.push_error(&error);
+                    return info.error(error.kind, error.message.as_bytes()).await;
+                }
+            };
+            if let Some(schema) =
+                mysql_specific::created_in(&translated).filter(|_| self.auto_create_databases)
+            {
+                if let Err(error) = self.ensure_schema(&schema).await {
+                    self.diagnostics.push_error(&error);
+                    return info.error(error.kind, error.message.as_bytes()).await;
+                }
+            }
+            // The client's `?` placeholders become PostgreSQL's numbered ones.
+            let numbered =
+                translator::parameters::number_placeholders(&translated).unwrap_or(translated);
+            let prepared = self
+                .statement_cache
+                .prepare(&self.pg_client(), &numbered)
+                .instrument(telemetry::prepare_span(&numbered))
+                .await;
+            let statement = match prepared {
+                Ok(statement) => statement,
+                Err(e) => {
+                    self.log
+                        .debug(format_args!("Error preparing statement: {:?}", e));
+                    self.record_upstream_failure(sql, &e);
+                    let error = MysqlError::from(e);
+                    self.diagnostics.push_error(&error);
+                    return info.error(error.kind, error.message.as_bytes()).await;
+                }
+            };
+
+            let params: Vec<Column> = statement
+                .params()
+                .iter()
+                .map(|ty| upstream::column("?", ty))
+                .collect();
+            let columns: Vec<Column> = statement
+                .columns()
+                .iter()
+                .map(|col| upstream::column(col.name(), col.type_()))
+                .collect();
+            self.next_statement_id += 1;
+            let id = self.next_statement_id;
+            self.statements.insert(
+                id,
+                PreparedStatement {
+                    statement,
+                    sql: sql.to_string(),
+                    translated: numbered,
+                },
+            );
+            info.reply(id, &params, &columns).await
+        }
+        .instrument(span)
+        .await;
+        self.log_statement("COM_STMT_PREPARE", sql, None, received);
+        done
+    }
+
+    async fn on_execute<'a>(
+        &'a mut self,
+        id: u32,
+        params: opensrv_mysql::ParamParser<'a>,
+        results: QueryResultWriter<'a, W>,
+    ) -> io::Result<()> {
+        let cursor = self.commands.take_cursor();
+        let received = self.start_statement();
+        self.diagnostics.clear();
+        if let Err(error) = self.check_session().await {
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        }
+        let Some(PreparedStatement {
+            statement,
+            sql,
+            translated,
+        }) = self.statements.get(&id).cloned()
+        else {
+            let error = MysqlError::new(
+                ErrorKind::ER_UNKNOWN_STMT_HANDLER,
+                format!(
+                    "Unknown prepared statement handler ({}) given to mysqld_stmt_execute",
+                    id
+                ),
+            );
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        };
+        let checked = self
+            .throttle
+            .statement(self.context().user)
+            .and_then(|()| self.check_transaction(&sql));
+        if let Err(error) = checked {
+            self.diagnostics.push_error(&error);
+            return error.write(results).await;
+        }
+        let _running = self.sessions.start(self.connection_id, "Execute", &sql);
+        // Parameters are bound as text and parsed by PostgreSQL as whatever type it inferred.
+        let client = self.commands.charsets().client;
+        let values = params
+            .into_iter()
+            .map(|param| upstream::param_text(param.value.into_inner(), client))
+            .collect::<io::Result<Vec<_>>>();
+        // Zero and impossible dates, for the parameters PostgreSQL inferred a date type for.
+        let modes = self.translator.options().dates;
+        let dated = |i: usize| {
+            statement
+                .params()
+                .get(i)
+                .is_some_and(|t| matches!(*t, Type::DATE | Type::TIMESTAMP | Type::TIMESTAMPTZ))
+        };
+        let values: Vec<Option<upstream::TextParam>> = match values {
+            Ok(values) => values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let coerced = value
+                        .as_deref()
+                        .filter(|_| dated(i))
+                        .and_then(|v| dates::coerce(v, modes));
+                    match coerced {
+                        Some(Coerced::Null) => None,
+                        Some(Coerced::Clamped(date)) => Some(upstream::TextParam(date)),
+                        None => value.map(upstream::TextParam),
+                    }
+                })
+                .collect(),
+            Err(e) => {
+                let error = MysqlError::new(ErrorKind::ER_WRONG_ARGUMENTS, e.to_string());
+                self.diagnostics.push_error(&error);
+                return error.write(results).await;
+            }
+        };
+        let params: Vec<&(dyn ToSql + Sync)> = values.iter().map(|v| v as _).collect();
+        // As text, for the shadow and the audit log.
+        let bound = (self.shadow.is_some() || self.audit.is_some()).then(|| {
+            values
+                .iter()
+                .map(|value| value.as_ref().map(|param| param.0.clone()))
+                .collect::<Vec<_>>()
+        });
+
+        self.profiler.start(&sql, self.commands.received());
+        let span = telemetry::query_span(
+            "COM_STMT_EXECUTE",
+            self.connection_id,
+            &sql,
+            self.commands.received(),
+        );
+        let cacheable = self.cacheable(&sql, &translated, &params);
+        let cached = cacheable
+            .as_ref()
+            .and_then(|c| self.result_cache.as_ref()?.get(c));
+        let done = match cached {
+            _ if cursor && !statement.columns().is_empty() => {
+                self.open_cursor(id, &statement, &translated, &params, &sql, results)
+                    .instrument(span)
+                    .await
+            }
+            Some(cached) => {
+                self.profiler.mark(Phase::Execute);
+                self.send_cached(&sql, &cached, results)
+                    .instrument(span)
+                    .await
+            }
+            None => {
+                self.run(&statement, &translated, &params, &sql, cacheable, results)
+                    .instrument(span)
+                    .await
+            }
+        };
+        self.finish_profile();
+        self.log_statement("COM_STMT_EXECUTE", &sql, bound.as_deref(), received);
+        self.shadow_statement(&sql, bound);
+        done
+    }
+
+    async fn on_close(&mut self, id: u32) {
+        self.close_cursor(id).await;
+        self.statements.remove(&id);
+    }
+
+    // USE, COM_INIT_DB and the database named in the handshake.
+    async fn on_init<'a>(&'a mut self, db: &'a str, writer: InitWriter<'a, W>) -> io::Result<()> {
+        self.log
+            .debug(format_args!("Switching to database {:?}", db));
+        self.diagnostics.clear();
+        let used = self.open_session(Some(db)).await;
+        let statement = format!("USE {}", emulation::backtick(db));
+        self.audit(
+            "COM_INIT_DB",
+            &statement,
+            None,
+            used.as_ref().err().map(MysqlError::code),
+        );
+        if let Some(shadow) = &mut self.shadow {
+            let expected = match &used {
+                Ok(()) => Expected::Replay,
+                Err(error) => Expected::Failed(error.code()),
+            };
+            shadow.send(&statement, None, expected);
+        }
+        match used {
+            Ok(()) => writer.ok().await,
+            Err(error) => {
+                self.log
+                    .info(format_args!("Failed to switch database: {}", error));
+                self.diagnostics.push_error(&error);
+                writer.error(error.kind, error.message.as_bytes()).await
+            }
+        }
+    }
+
+    async fn on_query<'a>(
+        &'a mut self,
+        sql: &'a str,
+        results: QueryResultWriter<'a, W>,
+    ) -> io::Result<()> {
+        if sql == protocol::COMMAND_QUERY {
+            if let Some(command) = self.commands.take() {
+                return self.run_command(command, results).await;
+            }
+        }
+        let received = self.start_statement();
+        self.profiler.start(sql, self.commands.received());
+        let span = telemetry::query_span(
+            "COM_QUERY",
+            self.connection_id,
+            sql,
+            self.commands.received(),
+        );
+        let done = self.query(sql, results).instrument(span).await;
+        self.finish_profile();
+        self.log_statement("COM_QUERY", sql, None, received);
+        self.shadow_statement(sql, None);
+        done
+    }
+}
+
+// Runs `f` on the blocking pool. A panic in it is a panic here, as if it had been run in place.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
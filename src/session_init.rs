@@ -0,0 +1,78 @@
+// How each user's sessions start, as MySQL's init_connect does for everyone:
+//
+//   USER_DEFAULT_DATABASE  "alice:shop; bob:reports"
+//   USER_INIT_SQL          "alice:SET ROLE shop_reader; alice:SET search_path TO shop, public;
+//                           bob:SET work_mem TO '64MB'"
+//
+// A user's default database is used when the client logs in without naming one, and before its
+// first command names one with COM_INIT_DB or USE. A user's init statements are PostgreSQL's,
+// run as they are, one per entry and in order, as the session starts, after its database is
+// used, so that they can change the search_path it set. They run again on a new session should
+// the connection's be lost, and after COM_RESET_CONNECTION, whose RESET ALL undoes them; with
+// COM_CHANGE_USER, the new user's run, and their default database is used if the client names
+// none.
+//
+// Until they have run, every command fails with the error that kept them from it, so a
+// connection never runs its statements without its user's settings.
+
+/// What a user's sessions start with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionInit {
+    // The database used when the client names none.
+    pub database: Option<String>,
+    // PostgreSQL statements, run in order.
+    pub statements: Vec<String>,
+}
+
+impl SessionInit {
+    /// The statements to run, if there are any.
+    pub fn sql(&self) -> Option<String> {
+        (!self.statements.is_empty()).then(|| self.statements.join("; "))
+    }
+}
+
+/// The users whose sessions start with something (USER_DEFAULT_DATABASE, USER_INIT_SQL).
+#[derive(Debug, Clone, Default)]
+pub struct SessionInits {
+    users: Vec<(String, SessionInit)>,
+}
+
+impl SessionInits {
+    /// Makes `database` the default of `user`.
+    pub fn set_database(&mut self, user: &str, database: &str) {
+        self.user(user).database = Some(database.to_string());
+    }
+
+    /// Adds `statement` to those run for `user`.
+    pub fn add_statement(&mut self, user: &str, statement: &str) {
+        self.user(user).statements.push(statement.to_string());
+    }
+
+    /// What the sessions of `user` start with, if anything.
+    pub fn get(&self, user: &str) -> Option<&SessionInit> {
+        self.users
+            .iter()
+            .find(|(name, _)| name == user)
+            .map(|(_, init)| init)
+    }
+
+    /// The users with something, in the order first named.
+    pub fn users(&self) -> impl Iterator<Item = &str> {
+        self.users.iter().map(|(user, _)| user.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    fn user(&mut self, user: &str) -> &mut SessionInit {
+        let index = match self.users.iter().position(|(name, _)| name == user) {
+            Some(index) => index,
+            None => {
+                self.users.push((user.to_string(), SessionInit::default()));
+                self.users.len() - 1
+            }
+        };
+        &mut self.users[index].1
+    }
+}
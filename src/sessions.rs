@@ -0,0 +1,178 @@
+// The proxy's client connections, by connection id, shared by all of them for KILL and SHOW
+// PROCESSLIST.
+//
+// A connection is registered once its PostgreSQL session is open, with the process id of that
+// session, and removed when it ends. KILL QUERY cancels whatever the session is running;
+// KILL CONNECTION does that as well and ends the client's connection. While a statement runs,
+// its SQL is kept here for the process list, and the client's connection attributes for
+// session_connect_attrs.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::Notify;
+
+struct Session {
+    user: Option<String>,
+    host: String,
+    db: Option<String>,
+    // What the connection is doing, as SHOW PROCESSLIST's Command: Sleep between statements.
+    command: &'static str,
+    // The statement being run.
+    info: Option<String>,
+    // When the command started.
+    since: Instant,
+    // The process id of the connection's PostgreSQL session, for pg_cancel_backend; 0 until the
+    // client logs in and the session opens.
+    backend_pid: i32,
+    // Notified to end the connection.
+    kill: Arc<Notify>,
+    connect_attrs: Vec<(String, String)>,
+}
+
+/// A connection as SHOW PROCESSLIST lists it.
+pub struct Process {
+    pub id: u32,
+    pub user: Option<String>,
+    pub host: String,
+    pub db: Option<String>,
+    pub command: &'static str,
+    // Seconds in the current command.
+    pub time: u64,
+    pub info: Option<String>,
+    // The attributes the client sent as it connected, in the order sent.
+    pub connect_attrs: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<u32, Session>>,
+}
+
+impl Sessions {
+    /// Adds a connection. The connection should end once the returned notification fires.
+    pub fn register(&self, connection: u32, peer: SocketAddr) -> Arc<Notify> {
+        let kill = Arc::new(Notify::new());
+        self.sessions.lock().unwrap().insert(
+            connection,
+            Session {
+                user: None,
+                host: peer.to_string(),
+                db: None,
+                command: "Sleep",
+                info: None,
+                since: Instant::now(),
+                backend_pid: 0,
+                kill: Arc::clone(&kill),
+                connect_attrs: Vec::new(),
+            },
+        );
+        kill
+    }
+
+    pub fn remove(&self, connection: u32) {
+        self.sessions.lock().unwrap().remove(&connection);
+    }
+
+    pub fn set_user(&self, connection: u32, user: &str) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&connection) {
+            session.user = Some(user.to_string());
+        }
+    }
+
+    pub fn set_db(&self, connection: u32, db: Option<&str>) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&connection) {
+            session.db = db.map(str::to_string);
+        }
+    }
+
+    pub fn set_connect_attrs(&self, connection: u32, attrs: &[(String, String)]) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&connection) {
+            session.connect_attrs = attrs.to_vec();
+        }
+    }
+
+    /// Marks a connection as running `sql` until the returned guard is dropped.
+    pub fn start(self: &Arc<Self>, connection: u32, command: &'static str, sql: &str) -> Running {
+        self.set_command(connection, command, Some(sql.to_string()));
+        Running {
+            sessions: Arc::clone(self),
+            connection,
+        }
+    }
+
+    fn set_command(&self, connection: u32, command: &'static str, info: Option<String>) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&connection) {
+            session.command = command;
+            session.info = info;
+            session.since = Instant::now();
+        }
+    }
+
+    /// The user a connection logged in as, `None` if there is no such connection or it hasn't
+    /// logged in.
+    pub fn user(&self, connection: u32) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&connection)?.user.clone()
+    }
+
+    /// Notes a connection's new PostgreSQL session, after it lost the one it had.
+    pub fn set_backend_pid(&self, connection: u32, backend_pid: i32) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&connection) {
+            session.backend_pid = backend_pid;
+        }
+    }
+
+    /// The PostgreSQL process id of a connection, `None` if there is no such connection.
+    pub fn backend_pid(&self, connection: u32) -> Option<i32> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&connection).map(|session| session.backend_pid)
+    }
+
+    /// Ends a connection. Returns whether there was one with the id.
+    pub fn kill(&self, connection: u32) -> bool {
+        match self.sessions.lock().unwrap().get(&connection) {
+            Some(session) => {
+                // notify_one keeps the notification if the connection isn't waiting for it
+                // right now.
+                session.kill.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every connection, by id.
+    pub fn processes(&self) -> Vec<Process> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut processes: Vec<Process> = sessions
+            .iter()
+            .map(|(id, session)| Process {
+                id: *id,
+                user: session.user.clone(),
+                host: session.host.clone(),
+                db: session.db.clone(),
+                command: session.command,
+                time: session.since.elapsed().as_secs(),
+                info: session.info.clone(),
+                connect_attrs: session.connect_attrs.clone(),
+            })
+            .collect();
+        processes.sort_by_key(|process| process.id);
+        processes
+    }
+}
+
+/// A statement being run; the connection goes back to Sleep when this is dropped.
+pub struct Running {
+    sessions: Arc<Sessions>,
+    connection: u32,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.sessions.set_command(self.connection, "Sleep", None);
+    }
+}
@@ -0,0 +1,241 @@
+// Point-in-time reads with the proxy's AS_OF hint.
+//
+//   SELECT /*+ AS_OF '2024-01-01 00:00' */ * FROM orders WHERE customer_id = 7
+//
+// reads `orders` as it was at that moment, like MariaDB's `FOR SYSTEM_TIME AS OF`. PostgreSQL
+// has no such thing built in; tables versioned with the temporal_tables `versioning` trigger
+// keep their old row versions in a history table, so each table in the query is replaced by the
+// row versions of both tables whose validity period contains the timestamp. A table without
+// such a trigger can't be read in the past and the statement is rejected.
+
+use opensrv_mysql::ErrorKind;
+use tokio_postgres::Client;
+
+use crate::catalog::{self, ObjectName};
+use crate::emulation::is_table_alias;
+use crate::error::MysqlError;
+use crate::translator::{self, literals, statement_starts_with, Node, Token};
+
+/// The timestamp of an AS_OF hint in `sql`, if it has one.
+pub fn hint(sql: &str) -> Option<String> {
+    let nodes = translator::parse(sql).ok()?;
+    nodes.iter().find_map(|node| match node {
+        Node::Token(Token::Comment(comment)) if comment.starts_with("/*+") => {
+            hint_timestamp(comment)
+        }
+        _ => None,
+    })
+}
+
+// `AS_OF '...'` or `AS_OF('...')` among the hints of an optimizer-hint comment.
+fn hint_timestamp(comment: &str) -> Option<String> {
+    let at = comment.to_ascii_uppercase().find("AS_OF")?;
+    let rest = comment[at + "AS_OF".len()..].trim_start();
+    let rest = rest.strip_prefix('(').unwrap_or(rest).trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let end = rest[1..].find(quote)?;
+    Some(rest[1..1 + end].to_string())
+}
+
+/// Rewrites the translated statement `sql` to read every table as of `timestamp`.
+pub async fn rewrite(client: &Client, sql: &str, timestamp: &str) -> Result<String, MysqlError> {
+    let nodes = translator::parse(sql)
+        .map_err(|e| MysqlError::new(ErrorKind::ER_PARSE_ERROR, e.to_string()))?;
+    if !statement_starts_with(&nodes, &["SELECT"]) && !statement_starts_with(&nodes, &["WITH"]) {
+        return Err(MysqlError::new(
+            ErrorKind::ER_NOT_SUPPORTED_YET,
+            "AS_OF is only supported in SELECT statements",
+        ));
+    }
+
+    let mut tables: Vec<ObjectName> = Vec::new();
+    replace_tables(nodes.clone(), &mut |table| {
+        if !tables.contains(table) {
+            tables.push(table.clone());
+        }
+        None
+    });
+
+    let at = format!("{}::timestamptz", literals::pg_string(timestamp));
+    let mut replacements: Vec<(ObjectName, String)> = Vec::new();
+    for table in tables {
+        let columns = catalog::table_columns(client, &table).await?;
+        // Not a table: a CTE, a function or something PostgreSQL will complain about itself.
+        if columns.is_empty() {
+            continue;
+        }
+        let Some(history) = catalog::table_history(client, &table).await? else {
+            return Err(MysqlError::new(
+                ErrorKind::ER_NOT_SUPPORTED_YET,
+                format!(
+                    "Table '{}' is not system-versioned; AS_OF needs a temporal_tables versioning trigger",
+                    table.name
+                ),
+            ));
+        };
+
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|c| literals::pg_identifier(&c.name))
+            .collect();
+        let columns = columns.join(", ");
+        let current = match &table.schema {
+            Some(schema) => format!(
+                "{}.{}",
+                literals::pg_identifier(schema),
+                literals::pg_identifier(&table.name)
+            ),
+            None => literals::pg_identifier(&table.name),
+        };
+        let period = literals::pg_identifier(&history.period_column);
+        let subquery = format!(
+            "SELECT {columns} FROM {current} WHERE {period} @> {at} \
+             UNION ALL SELECT {columns} FROM {history} WHERE {period} @> {at}",
+            columns = columns,
+            current = current,
+            history = history.table,
+            period = period,
+            at = at,
+        );
+        replacements.push((table, subquery));
+    }
+
+    let nodes = replace_tables(nodes, &mut |table| {
+        replacements
+            .iter()
+            .find(|(t, _)| t == table)
+            .map(|(_, subquery)| subquery.clone())
+    });
+    Ok(translator::render(&nodes))
+}
+
+// Replaces each table named in a FROM or JOIN list (here and in subqueries) by the subquery
+// `replace` returns for it, if any, keeping the alias or adding one.
+fn replace_tables(
+    nodes: Vec<Node>,
+    replace: &mut dyn FnMut(&ObjectName) -> Option<String>,
+) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    // Inside a table list, and whether a table (rather than an alias or comma) comes next.
+    let mut in_list = false;
+    let mut expect_table = false;
+    let mut i = 0;
+
+    while i < nodes.len() {
+        let node = &nodes[i];
+        if node.is_trivia() {
+            out.push(node.clone());
+            i += 1;
+            continue;
+        }
+
+        match node {
+            Node::Group(inner) => {
+                let subquery = matches!(
+                    inner.iter().find(|n| !n.is_trivia()),
+                    Some(Node::Token(t)) if t.is_word("SELECT") || t.is_word("WITH")
+                );
+                if subquery || expect_table {
+                    out.push(Node::Group(replace_tables(inner.clone(), replace)));
+                } else {
+                    out.push(node.clone());
+                }
+                if expect_table {
+                    expect_table = false;
+                    in_list = true;
+                }
+                i += 1;
+            }
+            Node::Token(t) if t.is_word("FROM") || t.is_word("JOIN") => {
+                out.push(node.clone());
+                in_list = true;
+                expect_table = true;
+                i += 1;
+            }
+            Node::Token(Token::Comma) if in_list && !expect_table => {
+                out.push(node.clone());
+                expect_table = true;
+                i += 1;
+            }
+            Node::Token(token) if expect_table => {
+                expect_table = false;
+                let Some((table, end)) = table_name(&nodes, i, token) else {
+                    out.push(node.clone());
+                    i += 1;
+                    continue;
+                };
+                let Some(subquery) = replace(&table) else {
+                    out.extend_from_slice(&nodes[i..end]);
+                    i = end;
+                    continue;
+                };
+                out.push(Node::Group(
+                    translator::parse(&subquery).expect("generated SQL is balanced"),
+                ));
+                let aliased = nodes[end..]
+                    .iter()
+                    .find(|n| !n.is_trivia())
+                    .is_some_and(is_table_alias);
+                if !aliased {
+                    out.push(Node::Token(Token::Whitespace(" ".to_string())));
+                    out.push(Node::Token(Token::Word("AS".to_string())));
+                    out.push(Node::Token(Token::Whitespace(" ".to_string())));
+                    out.push(Node::Token(Token::QuotedIdent(format!(
+                        "\"{}\"",
+                        table.name.replace('"', "\"\"")
+                    ))));
+                }
+                i = end;
+            }
+            Node::Token(_) => {
+                // An alias (or AS) keeps the list going; any other keyword ends it.
+                if in_list && !is_table_alias(node) {
+                    in_list = false;
+                }
+                out.push(node.clone());
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+// A (possibly schema-qualified) table name starting at `nodes[i]`, and the index after it.
+// Function calls in the FROM list (`generate_series(1, 3)`) aren't tables.
+fn table_name(nodes: &[Node], i: usize, first: &Token) -> Option<(ObjectName, usize)> {
+    let token_at = |j: usize| match nodes.get(j) {
+        Some(Node::Token(t)) => Some(t),
+        _ => None,
+    };
+    let first = identifier(first)?;
+    let (table, end) = match (token_at(i + 1), token_at(i + 2)) {
+        (Some(dot), Some(second)) if dot.is_operator(".") => (
+            ObjectName {
+                schema: Some(first),
+                name: identifier(second)?,
+            },
+            i + 3,
+        ),
+        _ => (
+            ObjectName {
+                schema: None,
+                name: first,
+            },
+            i + 1,
+        ),
+    };
+    if matches!(nodes.get(end), Some(Node::Group(_))) {
+        return None;
+    }
+    Some((table, end))
+}
+
+// Like `literals::identifier_name`, but also taking the "double quoted" identifiers of the
+// translated statement.
+fn identifier(token: &Token) -> Option<String> {
+    match token {
+        Token::DoubleQuoted(raw) => Some(raw[1..raw.len() - 1].replace("\"\"", "\"")),
+        _ => literals::identifier_name(token),
+    }
+}
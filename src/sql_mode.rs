@@ -0,0 +1,289 @@
+// The connection's sql_mode: SQL_MODE's to begin with, and then whatever SET sql_mode makes it.
+// The session's statements are translated with its modes (see TranslationOptions::sql_mode):
+//
+//   ANSI_QUOTES           "name" is an identifier rather than a string
+//   PIPES_AS_CONCAT       a || b concatenates, rather than being a OR b
+//   NO_BACKSLASH_ESCAPES  a backslash in a string is a backslash
+//   ONLY_FULL_GROUP_BY    without it, columns a query doesn't group by are aggregated
+//
+// and the date modes. The value is taken as MySQL takes it, a string, DEFAULT (SQL_MODE's), or
+// built from @@sql_mode with CONCAT() and REPLACE(), as frameworks set it:
+//
+//   SET sql_mode = 'ANSI_QUOTES,STRICT_TRANS_TABLES'
+//   SET SESSION sql_mode = CONCAT(@@sql_mode, ',PIPES_AS_CONCAT')
+//   SET @@sql_mode = REPLACE(@@sql_mode, 'ONLY_FULL_GROUP_BY', '')
+//
+// The combination modes ANSI and TRADITIONAL are expanded, and a mode MySQL doesn't have is
+// refused with error 1231, as MySQL refuses it. SELECT @@sql_mode and SHOW VARIABLES report the
+// modes; they go with COM_RESET_CONNECTION.
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::translator::{self, literals, Token};
+
+// MySQL's modes, in the order @@sql_mode lists them.
+const MODES: &[&str] = &[
+    "REAL_AS_FLOAT",
+    "PIPES_AS_CONCAT",
+    "ANSI_QUOTES",
+    "IGNORE_SPACE",
+    "ONLY_FULL_GROUP_BY",
+    "NO_UNSIGNED_SUBTRACTION",
+    "NO_DIR_IN_CREATE",
+    "POSTGRESQL",
+    "ORACLE",
+    "MSSQL",
+    "DB2",
+    "MAXDB",
+    "NO_KEY_OPTIONS",
+    "NO_TABLE_OPTIONS",
+    "NO_FIELD_OPTIONS",
+    "MYSQL323",
+    "MYSQL40",
+    "ANSI",
+    "NO_AUTO_VALUE_ON_ZERO",
+    "NO_BACKSLASH_ESCAPES",
+    "STRICT_TRANS_TABLES",
+    "STRICT_ALL_TABLES",
+    "NO_ZERO_IN_DATE",
+    "NO_ZERO_DATE",
+    "ALLOW_INVALID_DATES",
+    "ERROR_FOR_DIVISION_BY_ZERO",
+    "TRADITIONAL",
+    "NO_AUTO_CREATE_USER",
+    "HIGH_NOT_PRECEDENCE",
+    "NO_ENGINE_SUBSTITUTION",
+    "PAD_CHAR_TO_FULL_LENGTH",
+    "TIME_TRUNCATE_FRACTIONAL",
+];
+
+// The modes each combination mode sets.
+const COMBINATIONS: &[(&str, &[&str])] = &[
+    (
+        "ANSI",
+        &[
+            "REAL_AS_FLOAT",
+            "PIPES_AS_CONCAT",
+            "ANSI_QUOTES",
+            "IGNORE_SPACE",
+            "ONLY_FULL_GROUP_BY",
+        ],
+    ),
+    (
+        "TRADITIONAL",
+        &[
+            "STRICT_TRANS_TABLES",
+            "STRICT_ALL_TABLES",
+            "NO_ZERO_IN_DATE",
+            "NO_ZERO_DATE",
+            "ERROR_FOR_DIVISION_BY_ZERO",
+            "NO_ENGINE_SUBSTITUTION",
+        ],
+    ),
+];
+
+/// `modes`, a comma-separated list, as @@sql_mode has them: in upper case and MySQL's order,
+/// with the modes of ANSI and TRADITIONAL. `None` if one isn't a mode.
+pub fn normalize(modes: &str) -> Option<String> {
+    let mut set = vec![false; MODES.len()];
+    for mode in modes.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        let mode = mode.to_ascii_uppercase();
+        set[MODES.iter().position(|m| *m == mode)?] = true;
+        for (combination, modes) in COMBINATIONS {
+            if *combination == mode {
+                for mode in *modes {
+                    set[MODES.iter().position(|m| m == mode)?] = true;
+                }
+            }
+        }
+    }
+    Some(
+        MODES
+            .iter()
+            .zip(set)
+            .filter_map(|(mode, set)| set.then_some(*mode))
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/// The sql_mode after `sql`, a statement as the client sent it, if it is a SET of the session's
+/// sql_mode alone. `current` is the mode before it, and `default` SQL_MODE's.
+pub fn set(sql: &str, current: &str, default: &str) -> Option<Result<String, MysqlError>> {
+    let tokens = translator::significant_tokens(sql)?;
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    let (set, rest) = tokens.split_first()?;
+    if !set.is_word("SET") {
+        return None;
+    }
+    let rest = match rest {
+        [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => rest,
+        rest => rest,
+    };
+    let (name, equals, value) = match rest {
+        [name, equals, value @ ..] => (name, equals, value),
+        _ => return None,
+    };
+    let name = match name {
+        Token::Word(word) => word.to_ascii_lowercase(),
+        Token::Variable(variable) => {
+            let variable = variable.to_ascii_lowercase();
+            ["@@session.", "@@local.", "@@"]
+                .iter()
+                .find_map(|prefix| variable.strip_prefix(prefix))?
+                .to_string()
+        }
+        _ => return None,
+    };
+    if name != "sql_mode" || !(equals.is_operator("=") || equals.is_operator(":=")) {
+        return None;
+    }
+    // Other variables set with it are left to the SET that sets them.
+    if split(value).len() > 1 {
+        return None;
+    }
+    let value = match value {
+        [value] if value.is_word("DEFAULT") => default.to_string(),
+        value => match evaluate(value, current) {
+            Some(value) => value,
+            None => return Some(Err(wrong_value(&render(value)))),
+        },
+    };
+    Some(normalize(&value).ok_or_else(|| wrong_value(&value)))
+}
+
+// The string the expression `tokens` gives: a string, `@@sql_mode`, a number that is a set of no
+// modes, or CONCAT() and REPLACE() of them.
+fn evaluate(tokens: &[Token], current: &str) -> Option<String> {
+    match tokens {
+        [Token::String(raw)] => Some(literals::mysql_string_value(raw)),
+        [Token::Number(zero)] if zero == "0" => Some(String::new()),
+        [Token::Variable(variable)]
+            if ["@@sql_mode", "@@session.sql_mode", "@@global.sql_mode"]
+                .iter()
+                .any(|v| variable.eq_ignore_ascii_case(v)) =>
+        {
+            Some(current.to_string())
+        }
+        [function, Token::LParen, args @ .., Token::RParen] => {
+            let args = split(args)
+                .into_iter()
+                .map(|arg| evaluate(arg, current))
+                .collect::<Option<Vec<_>>>()?;
+            match args.as_slice() {
+                args if function.is_word("CONCAT") => Some(args.concat()),
+                [text, from, to] if function.is_word("REPLACE") => Some(text.replace(from, to)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// The arguments of a call, split on the commas outside their own parentheses.
+fn split(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::Comma if depth == 0 => {
+                args.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&tokens[start..]);
+    args
+}
+
+fn render(tokens: &[Token]) -> String {
+    tokens.iter().map(Token::to_string).collect()
+}
+
+fn wrong_value(value: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_WRONG_VALUE_FOR_VAR,
+        format!(
+            "Variable 'sql_mode' can't be set to the value of '{}'",
+            value
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_to(sql: &str, current: &str) -> Option<String> {
+        set(sql, current, "STRICT_TRANS_TABLES").map(|result| result.unwrap())
+    }
+
+    #[test]
+    fn normalizes_as_mysql_lists_modes() {
+        assert_eq!(
+            normalize("only_full_group_by, ansi_quotes,,").as_deref(),
+            Some("ANSI_QUOTES,ONLY_FULL_GROUP_BY")
+        );
+        assert_eq!(
+            normalize("ANSI").as_deref(),
+            Some("REAL_AS_FLOAT,PIPES_AS_CONCAT,ANSI_QUOTES,IGNORE_SPACE,ONLY_FULL_GROUP_BY,ANSI")
+        );
+        assert_eq!(normalize("NO_SUCH_MODE"), None);
+    }
+
+    #[test]
+    fn follows_set_sql_mode() {
+        let current = "ONLY_FULL_GROUP_BY,STRICT_TRANS_TABLES";
+        assert_eq!(
+            set_to("SET sql_mode = 'ansi_quotes'", current).as_deref(),
+            Some("ANSI_QUOTES")
+        );
+        assert_eq!(
+            set_to(
+                "SET SESSION sql_mode = CONCAT(@@sql_mode, ',PIPES_AS_CONCAT');",
+                current
+            )
+            .as_deref(),
+            Some("PIPES_AS_CONCAT,ONLY_FULL_GROUP_BY,STRICT_TRANS_TABLES")
+        );
+        assert_eq!(
+            set_to(
+                "SET @@session.sql_mode = REPLACE(@@sql_mode, 'ONLY_FULL_GROUP_BY', '')",
+                current
+            )
+            .as_deref(),
+            Some("STRICT_TRANS_TABLES")
+        );
+        assert_eq!(
+            set_to("SET sql_mode = DEFAULT", current).as_deref(),
+            Some("STRICT_TRANS_TABLES")
+        );
+        assert_eq!(set_to("SET sql_mode = 0", current).as_deref(), Some(""));
+        // Not the session's sql_mode alone.
+        assert_eq!(set_to("SET sql_mode = '', autocommit = 1", current), None);
+        assert_eq!(set_to("SET GLOBAL sql_mode = ''", current), None);
+        assert_eq!(set_to("SET names utf8mb4", current), None);
+    }
+
+    #[test]
+    fn refuses_what_isnt_a_mode() {
+        let error = set("SET sql_mode = 'ANSI_QUOTE'", "", "")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::ER_WRONG_VALUE_FOR_VAR);
+        assert_eq!(
+            error.message,
+            "Variable 'sql_mode' can't be set to the value of 'ANSI_QUOTE'"
+        );
+        assert!(set("SET sql_mode = LOWER('ansi')", "", "")
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,106 @@
+// The statements prepared on a connection's PostgreSQL session, kept so that a statement run
+// again is executed without PostgreSQL parsing and planning it anew. Statements are kept by
+// their translated text, `?` placeholders numbered and, with PARAMETERIZE_QUERIES, literals
+// made parameters, so the same query with other values is the same statement.
+//
+//   STATEMENT_CACHE_SIZE  the statements kept per connection, 256 by default; 0 keeps none
+//
+// When a connection has as many as it may keep, the one used least recently is closed to make
+// room for the next. Those of the statements that hit and missed, and those closed, are counted
+// in proxy_stats metrics, as statement_cache_hits_total, statement_cache_misses_total and
+// statement_cache_evictions_total, and shown by SHOW PROXY CACHES.
+//
+// A statement stays valid as tables change, PostgreSQL planning it again as it needs, unless
+// the columns it returns change; so the statements are dropped after DDL the connection runs, a
+// USE, a reset or a new session, and a statement that fails is dropped in case that was why.
+// DDL from other connections can still fail a statement once, with "cached plan must not change
+// result type".
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio_postgres::{Client, Statement};
+
+use crate::stats::Stats;
+
+pub const DEFAULT_SIZE: usize = 256;
+
+/// A connection's prepared statements, by the text they were prepared from.
+pub struct StatementCache {
+    size: usize,
+    // Each statement with when it was last used, on `clock`.
+    statements: HashMap<String, (Statement, u64)>,
+    clock: u64,
+    stats: Arc<Stats>,
+}
+
+impl StatementCache {
+    pub fn new(size: usize, stats: Arc<Stats>) -> StatementCache {
+        StatementCache {
+            size,
+            statements: HashMap::new(),
+            clock: 0,
+            stats,
+        }
+    }
+
+    /// How many statements are kept at most.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// `sql` prepared on `client`, the session the statements are kept for: the one kept, or
+    /// else a new one, kept in place of the least recently used if there's no more room.
+    pub async fn prepare(
+        &mut self,
+        client: &Client,
+        sql: &str,
+    ) -> Result<Statement, tokio_postgres::Error> {
+        if self.size == 0 {
+            return client.prepare(sql).await;
+        }
+        self.clock += 1;
+        if let Some((statement, used)) = self.statements.get_mut(sql) {
+            *used = self.clock;
+            self.stats.record_statement_cache(true);
+            return Ok(statement.clone());
+        }
+        self.stats.record_statement_cache(false);
+        let statement = client.prepare(sql).await?;
+        if self.statements.len() >= self.size {
+            let least_recent = self
+                .statements
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(sql, _)| sql.clone());
+            if let Some(sql) = least_recent {
+                self.statements.remove(&sql);
+                self.stats.record_statement_cache_entries(0, 1, 1);
+            }
+        }
+        self.statements
+            .insert(sql.to_string(), (statement.clone(), self.clock));
+        self.stats.record_statement_cache_entries(1, 0, 0);
+        Ok(statement)
+    }
+
+    /// Drops the statement prepared from `sql`, if one is kept.
+    pub fn remove(&mut self, sql: &str) {
+        if self.statements.remove(sql).is_some() {
+            self.stats.record_statement_cache_entries(0, 1, 0);
+        }
+    }
+
+    /// Drops every statement, as when the session they were prepared on goes.
+    pub fn clear(&mut self) {
+        let removed = self.statements.len() as u64;
+        self.statements.clear();
+        self.stats.record_statement_cache_entries(0, removed, 0);
+    }
+}
+
+impl Drop for StatementCache {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
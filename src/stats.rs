@@ -0,0 +1,913 @@
+// Proxy statistics: which tables each user reads and writes, the statements run by digest, and
+// running totals.
+//
+// Tables are picked out of the statements as the client sent them, before translation. This is
+// a best-effort scan of FROM/JOIN lists and INSERT/UPDATE/DELETE targets, not a full parse; it
+// is meant to show which tables an application touches, e.g. to scope a migration.
+//
+// Statements are totalled by digest (see digest.rs), up to MAX_DIGESTS of them, as MySQL's
+// performance_schema_digests_size; the statements of any further digests are totalled together,
+// under an empty fingerprint. The same goes for the statements the shadow MySQL server (see
+// shadow.rs) disagreed with PostgreSQL on, by digest and kind of mismatch.
+//
+// The running totals start from zero with each process, unless STATS_FILE names a file to keep
+// them in: they are restored from it on start-up and saved to it every few seconds, so they count
+// from the first start, uptime included. The file has a `name = value` line for each total.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use toml_edit::Document;
+
+use crate::catalog::ObjectName;
+use crate::digest::Digest;
+use crate::emulation::{object_name, virtual_tables};
+use crate::failures::Failure;
+use crate::shadow::Mismatch;
+use crate::translator::{self, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AccessKey {
+    pub user: String,
+    // The schema the statement named, empty when it didn't qualify the table.
+    pub schema: String,
+    pub table: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessCounts {
+    pub reads: u64,
+    pub writes: u64,
+    pub last_access: DateTime<Local>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailureCounts {
+    pub failures: u64,
+    pub last_failure: DateTime<Local>,
+    // The last statement that failed this way, as the client sent it.
+    pub example: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DigestCounts {
+    pub text: String,
+    pub count: u64,
+    // How many of them got an error.
+    pub errors: u64,
+    // From when each statement arrived until its reply was sent.
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MismatchCounts {
+    pub text: String,
+    pub count: u64,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    // The last statement that differed this way, as the client sent it, and what PostgreSQL and
+    // the shadow MySQL server made of it.
+    pub example: String,
+    pub postgres: String,
+    pub mysql: String,
+}
+
+// How many digests are told apart.
+const MAX_DIGESTS: usize = 10_000;
+
+// How much of a failed statement is kept as its example.
+const EXAMPLE_LENGTH: usize = 200;
+
+// How often the running totals are saved to STATS_FILE.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Stats {
+    started: Instant,
+    // Seconds the proxy ran before this process started, from STATS_FILE.
+    earlier_uptime: AtomicU64,
+    connections: AtomicU64,
+    statements: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    table_reads: AtomicU64,
+    table_writes: AtomicU64,
+    table_access: Mutex<HashMap<AccessKey, AccessCounts>>,
+    translation_failures: AtomicU64,
+    failures: Mutex<HashMap<Failure, FailureCounts>>,
+    // By fingerprint.
+    digests: Mutex<HashMap<String, DigestCounts>>,
+    shadow_mismatches_total: AtomicU64,
+    // Statements found prepared on the session already, those that had to be, and those closed
+    // to make room; and how many are prepared now, on all sessions.
+    statement_cache_hits: AtomicU64,
+    statement_cache_misses: AtomicU64,
+    statement_cache_evictions: AtomicU64,
+    statement_cache_entries: AtomicU64,
+    // SELECTs answered from the result cache, those that could have been, and results dropped
+    // to make room.
+    result_cache_hits: AtomicU64,
+    result_cache_misses: AtomicU64,
+    result_cache_evictions: AtomicU64,
+    // By fingerprint and kind.
+    shadow_mismatches: Mutex<HashMap<(String, Mismatch), MismatchCounts>>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            started: Instant::now(),
+            earlier_uptime: AtomicU64::default(),
+            connections: AtomicU64::default(),
+            statements: AtomicU64::default(),
+            bytes_received: AtomicU64::default(),
+            bytes_sent: AtomicU64::default(),
+            table_reads: AtomicU64::default(),
+            table_writes: AtomicU64::default(),
+            table_access: Mutex::default(),
+            translation_failures: AtomicU64::default(),
+            failures: Mutex::default(),
+            digests: Mutex::default(),
+            shadow_mismatches_total: AtomicU64::default(),
+            statement_cache_hits: AtomicU64::default(),
+            statement_cache_misses: AtomicU64::default(),
+            statement_cache_evictions: AtomicU64::default(),
+            statement_cache_entries: AtomicU64::default(),
+            result_cache_hits: AtomicU64::default(),
+            result_cache_misses: AtomicU64::default(),
+            result_cache_evictions: AtomicU64::default(),
+            shadow_mismatches: Mutex::default(),
+        }
+    }
+}
+
+impl Stats {
+    /// Records a client connecting, whether or not it goes on to log in.
+    pub fn record_connection(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn statements(&self) -> u64 {
+        self.statements.load(Ordering::Relaxed)
+    }
+
+    /// The bytes read from clients and written to them.
+    pub fn bytes(&self) -> (u64, u64) {
+        (
+            self.bytes_received.load(Ordering::Relaxed),
+            self.bytes_sent.load(Ordering::Relaxed),
+        )
+    }
+
+    /// How long this process has been running.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Records a statement received from `user`.
+    pub fn record_statement(&self, user: &str, sql: &str) {
+        self.statements.fetch_add(1, Ordering::Relaxed);
+
+        let Some(mut access) = translator::significant_tokens(sql).and_then(|t| table_access(&t))
+        else {
+            return;
+        };
+        // The proxy's own virtual tables aren't interesting.
+        access.retain(|(table, _)| table.schema.as_deref() != Some(virtual_tables::SCHEMA));
+        if access.is_empty() {
+            return;
+        }
+
+        let now = Local::now();
+        let mut table_access = self.table_access.lock().unwrap();
+        for (table, write) in access {
+            let key = AccessKey {
+                user: user.to_string(),
+                schema: table.schema.unwrap_or_default(),
+                table: table.name,
+            };
+            let counts = table_access.entry(key).or_insert(AccessCounts {
+                reads: 0,
+                writes: 0,
+                last_access: now,
+            });
+            if write {
+                counts.writes += 1;
+                self.table_writes.fetch_add(1, Ordering::Relaxed);
+            } else {
+                counts.reads += 1;
+                self.table_reads.fetch_add(1, Ordering::Relaxed);
+            }
+            counts.last_access = now;
+        }
+    }
+
+    /// Per-user, per-table access counts, sorted by user and table.
+    pub fn table_access(&self) -> Vec<(AccessKey, AccessCounts)> {
+        let mut rows: Vec<_> = self
+            .table_access
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    }
+
+    /// Records a statement that failed because of `failure`.
+    pub fn record_failure(&self, failure: Failure, sql: &str) {
+        self.translation_failures.fetch_add(1, Ordering::Relaxed);
+        let example: String = sql.trim().chars().take(EXAMPLE_LENGTH).collect();
+        let now = Local::now();
+        let mut failures = self.failures.lock().unwrap();
+        let counts = failures.entry(failure).or_insert(FailureCounts {
+            failures: 0,
+            last_failure: now,
+            example: String::new(),
+        });
+        counts.failures += 1;
+        counts.last_failure = now;
+        counts.example = example;
+    }
+
+    /// Failures by construct, the most frequent first.
+    pub fn failures(&self) -> Vec<(Failure, FailureCounts)> {
+        let mut rows: Vec<_> = self
+            .failures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        rows.sort_by(|a, b| b.1.failures.cmp(&a.1.failures).then_with(|| a.0.cmp(&b.0)));
+        rows
+    }
+
+    /// Records a statement with `digest` that took `duration`, and whether it failed.
+    pub fn record_digest(&self, digest: &Digest, duration: Duration, failed: bool) {
+        let now = Local::now();
+        let mut digests = self.digests.lock().unwrap();
+        let (fingerprint, text) =
+            if digests.len() < MAX_DIGESTS || digests.contains_key(&digest.fingerprint) {
+                (digest.fingerprint.as_str(), digest.text.as_str())
+            } else {
+                ("", "(other statements)")
+            };
+        let counts = digests
+            .entry(fingerprint.to_string())
+            .or_insert_with(|| DigestCounts {
+                text: text.to_string(),
+                count: 0,
+                errors: 0,
+                total: Duration::ZERO,
+                min: duration,
+                max: duration,
+                first_seen: now,
+                last_seen: now,
+            });
+        counts.count += 1;
+        counts.errors += u64::from(failed);
+        counts.total += duration;
+        counts.min = counts.min.min(duration);
+        counts.max = counts.max.max(duration);
+        counts.last_seen = now;
+    }
+
+    /// The statements run by digest fingerprint, those that took the longest in all first.
+    pub fn digests(&self) -> Vec<(String, DigestCounts)> {
+        let mut rows: Vec<_> = self
+            .digests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        rows
+    }
+
+    /// Records a statement with `digest` whose outcome on the shadow MySQL server differed from
+    /// PostgreSQL's, `sql` as the client sent it.
+    #[cfg(feature = "shadow")]
+    pub fn record_shadow_mismatch(
+        &self,
+        digest: &Digest,
+        mismatch: Mismatch,
+        sql: &str,
+        postgres: &str,
+        mysql: &str,
+    ) {
+        self.shadow_mismatches_total.fetch_add(1, Ordering::Relaxed);
+        let now = Local::now();
+        let mut mismatches = self.shadow_mismatches.lock().unwrap();
+        let key = (digest.fingerprint.clone(), mismatch);
+        let (key, text) = if mismatches.len() < MAX_DIGESTS || mismatches.contains_key(&key) {
+            (key, digest.text.as_str())
+        } else {
+            ((String::new(), mismatch), "(other statements)")
+        };
+        let counts = mismatches.entry(key).or_insert_with(|| MismatchCounts {
+            text: text.to_string(),
+            count: 0,
+            first_seen: now,
+            last_seen: now,
+            example: String::new(),
+            postgres: String::new(),
+            mysql: String::new(),
+        });
+        counts.count += 1;
+        counts.last_seen = now;
+        counts.example = sql.trim().chars().take(EXAMPLE_LENGTH).collect();
+        counts.postgres = postgres.to_string();
+        counts.mysql = mysql.to_string();
+    }
+
+    /// The shadow mismatches by digest fingerprint and kind, the most frequent first.
+    pub fn shadow_mismatches(&self) -> Vec<(String, Mismatch, MismatchCounts)> {
+        let mut rows: Vec<_> = self
+            .shadow_mismatches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((fingerprint, mismatch), counts)| {
+                (fingerprint.clone(), *mismatch, counts.clone())
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.2.count
+                .cmp(&a.2.count)
+                .then_with(|| (&a.0, a.1).cmp(&(&b.0, b.1)))
+        });
+        rows
+    }
+
+    /// Records a statement found prepared on a connection's session, if `hit`, or prepared.
+    pub fn record_statement_cache(&self, hit: bool) {
+        match hit {
+            true => &self.statement_cache_hits,
+            false => &self.statement_cache_misses,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records `added` statements prepared on a session and kept, and `removed` no longer kept,
+    /// `evicted` of them to make room.
+    pub fn record_statement_cache_entries(&self, added: u64, removed: u64, evicted: u64) {
+        self.statement_cache_entries
+            .fetch_add(added, Ordering::Relaxed);
+        self.statement_cache_entries
+            .fetch_sub(removed, Ordering::Relaxed);
+        self.statement_cache_evictions
+            .fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    /// Records a SELECT answered from the result cache, if `hit`, or run to be cached.
+    pub fn record_result_cache(&self, hit: bool) {
+        match hit {
+            true => &self.result_cache_hits,
+            false => &self.result_cache_misses,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a result dropped from the result cache to make room.
+    pub fn record_result_cache_eviction(&self) {
+        self.result_cache_evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The statement cache's use: the statements kept on all sessions, then its hits, misses
+    /// and evictions.
+    pub fn statement_cache(&self) -> [u64; 4] {
+        [
+            &self.statement_cache_entries,
+            &self.statement_cache_hits,
+            &self.statement_cache_misses,
+            &self.statement_cache_evictions,
+        ]
+        .map(|counter| counter.load(Ordering::Relaxed))
+    }
+
+    /// The result cache's hits, misses and evictions.
+    pub fn result_cache(&self) -> [u64; 3] {
+        [
+            &self.result_cache_hits,
+            &self.result_cache_misses,
+            &self.result_cache_evictions,
+        ]
+        .map(|counter| counter.load(Ordering::Relaxed))
+    }
+
+    /// Running totals as (name, value) pairs.
+    pub fn metrics(&self) -> Vec<(&'static str, u64)> {
+        let mut metrics: Vec<(&'static str, u64)> = self
+            .counters()
+            .into_iter()
+            .map(|(name, counter)| (name, counter.load(Ordering::Relaxed)))
+            .collect();
+        metrics.push((
+            "uptime_seconds_total",
+            self.earlier_uptime.load(Ordering::Relaxed) + self.uptime().as_secs(),
+        ));
+        metrics
+    }
+
+    // The counters of the running totals, by name.
+    fn counters(&self) -> [(&'static str, &AtomicU64); 14] {
+        [
+            ("connections_total", &self.connections),
+            ("statements_total", &self.statements),
+            ("bytes_received_total", &self.bytes_received),
+            ("bytes_sent_total", &self.bytes_sent),
+            ("table_reads_total", &self.table_reads),
+            ("table_writes_total", &self.table_writes),
+            ("translation_failures_total", &self.translation_failures),
+            ("shadow_mismatches_total", &self.shadow_mismatches_total),
+            ("statement_cache_hits_total", &self.statement_cache_hits),
+            ("statement_cache_misses_total", &self.statement_cache_misses),
+            (
+                "statement_cache_evictions_total",
+                &self.statement_cache_evictions,
+            ),
+            ("result_cache_hits_total", &self.result_cache_hits),
+            ("result_cache_misses_total", &self.result_cache_misses),
+            ("result_cache_evictions_total", &self.result_cache_evictions),
+        ]
+    }
+
+    /// Adds the totals saved in the file at `path` to this process's, if there is a file yet.
+    pub fn restore(&self, path: &str) -> Result<(), String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let document: Document = text.parse().map_err(|e| format!("{}", e))?;
+        for (name, item) in document.iter() {
+            let value = item
+                .as_integer()
+                .and_then(|value| u64::try_from(value).ok())
+                .ok_or_else(|| format!("{} isn't a count", name))?;
+            if name == "uptime_seconds_total" {
+                self.earlier_uptime.fetch_add(value, Ordering::Relaxed);
+            } else if let Some((_, counter)) = self.counters().into_iter().find(|(n, _)| *n == name)
+            {
+                counter.fetch_add(value, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the running totals to the file at `path`, replacing it whole so a crash can't
+    /// leave half of it.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text: String = self
+            .metrics()
+            .into_iter()
+            .map(|(name, value)| format!("{} = {}\n", name, value))
+            .collect();
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// A client connection's read or write half, counting the bytes that pass through it.
+pub struct Counted<S> {
+    inner: S,
+    stats: Arc<Stats>,
+}
+
+impl<S> Counted<S> {
+    pub fn new(inner: S, stats: Arc<Stats>) -> Counted<S> {
+        Counted { inner, stats }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counted<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            let read = (buf.filled().len() - before) as u64;
+            self.stats.bytes_received.fetch_add(read, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counted<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.stats
+                .bytes_sent
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// Words that end a table list or can't be a table alias.
+const NOT_ALIAS: &[&str] = &[
+    "WHERE",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "CROSS",
+    "NATURAL",
+    "FULL",
+    "OUTER",
+    "STRAIGHT_JOIN",
+    "ON",
+    "USING",
+    "GROUP",
+    "ORDER",
+    "LIMIT",
+    "HAVING",
+    "UNION",
+    "SET",
+    "FOR",
+    "LOCK",
+    "WINDOW",
+    "INTO",
+    "VALUES",
+    "VALUE",
+    "SELECT",
+    "PARTITION",
+    "USE",
+    "IGNORE",
+    "FORCE",
+    "AS",
+];
+
+// DML modifiers that can sit between the verb and the table.
+const MODIFIERS: &[&str] = &[
+    "LOW_PRIORITY",
+    "DELAYED",
+    "HIGH_PRIORITY",
+    "QUICK",
+    "IGNORE",
+];
+
+// The words a clause after a FROM list starts with, ending it.
+const AFTER_FROM: &[&str] = &[
+    "WHERE",
+    "GROUP",
+    "ORDER",
+    "LIMIT",
+    "HAVING",
+    "WINDOW",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "INTO",
+    "SET",
+    "LOCK",
+    "RETURNING",
+    "DUPLICATE",
+];
+
+// Options between EXPLAIN and the statement it explains.
+const EXPLAIN_OPTIONS: &[&str] = &["ANALYZE", "VERBOSE", "EXTENDED", "PARTITIONS"];
+
+// What each level of parentheses of a statement is, as table_access scans it.
+#[derive(Clone, Copy)]
+struct Level {
+    // Whether its FROM and JOIN name tables: the statement itself, a subquery or a join in
+    // parentheses, but not a function call like EXTRACT(YEAR FROM d) or TRIM(x FROM s).
+    reads: bool,
+    // Whether it is in a list of tables, where a comma or parentheses start another.
+    from: bool,
+}
+
+/// Tables a statement touches, each with whether it is written (`true`) or read. None if they
+/// can't be told, say after a FROM naming no table or in parentheses that don't balance.
+pub(crate) fn table_access(tokens: &[Token]) -> Option<Vec<(ObjectName, bool)>> {
+    let mut access = Vec::new();
+    let skip_modifiers = |mut i: usize| {
+        while tokens
+            .get(i)
+            .is_some_and(|t| MODIFIERS.iter().any(|m| t.is_word(m)))
+        {
+            i += 1;
+        }
+        i
+    };
+
+    // The statement's write target(s), and where the scan for reads starts.
+    let first = tokens.first();
+    let mut from = false;
+    // The tables an UPDATE joins are written as well, up to its SET.
+    let mut updated = false;
+    // Where DELETE ... USING (t1 JOIN t2) has its join, unlike JOIN t USING (c).
+    let mut using = None;
+    let mut i = if first.is_some_and(|t| t.is_word("INSERT") || t.is_word("REPLACE")) {
+        let mut i = skip_modifiers(1);
+        if tokens.get(i).is_some_and(|t| t.is_word("INTO")) {
+            i += 1;
+        }
+        table_list(tokens, i, true, &mut access)?
+    } else if first.is_some_and(|t| t.is_word("UPDATE")) {
+        (from, updated) = (true, true);
+        table_list(tokens, skip_modifiers(1), true, &mut access)?
+    } else if first.is_some_and(|t| t.is_word("DELETE")) {
+        let i = skip_modifiers(1);
+        let i = if tokens.get(i).is_some_and(|t| t.is_word("FROM")) {
+            table_list(tokens, i + 1, true, &mut access)?
+        } else {
+            // Multi-table DELETE: `DELETE t1, t2 FROM ...` writes the listed tables.
+            table_list(tokens, i, true, &mut access)?
+        };
+        // DELETE FROM t1 USING t1 JOIN t2 reads the tables after USING.
+        if tokens.get(i).is_some_and(|t| t.is_word("USING")) {
+            from = true;
+            using = Some(i + 1);
+            table_list(tokens, i + 1, false, &mut access)?
+        } else {
+            i
+        }
+    } else if first.is_some_and(|t| t.is_word("TRUNCATE")) {
+        let i = if tokens.get(1).is_some_and(|t| t.is_word("TABLE")) {
+            2
+        } else {
+            1
+        };
+        table_list(tokens, i, true, &mut access)?
+    } else if first.is_some_and(|t| t.is_word("LOCK")) {
+        return locked(tokens);
+    } else if first
+        .is_some_and(|t| t.is_word("EXPLAIN") || t.is_word("DESCRIBE") || t.is_word("DESC"))
+    {
+        return explained(&tokens[1..]);
+    } else if first.is_some_and(|t| {
+        t.is_word("SELECT") || t.is_word("WITH") || t.is_word("TABLE") || *t == Token::LParen
+    }) {
+        0
+    } else {
+        return Some(access);
+    };
+
+    // Reads: FROM and JOIN lists at the top level, in subqueries and in joins in parentheses,
+    // the tables of TABLE t, which is SELECT * FROM t, and the statement of a WITH d AS (DELETE
+    // ... RETURNING *), which writes its tables.
+    let mut levels = vec![Level { reads: true, from }];
+    while i < tokens.len() {
+        let level = *levels.last()?;
+        let previous = i.checked_sub(1).map(|before| &tokens[before]);
+        let after_for = previous.is_some_and(|t| t.is_word("FOR"));
+        match &tokens[i] {
+            Token::LParen => {
+                let next = tokens.get(i + 1);
+                if next.is_some_and(|t| {
+                    ["INSERT", "UPDATE", "DELETE", "REPLACE"]
+                        .iter()
+                        .any(|word| t.is_word(word))
+                }) {
+                    let end = closing(tokens, i)?;
+                    access.extend(table_access(&tokens[i + 1..end])?);
+                    i = end + 1;
+                    continue;
+                }
+                let subquery = next.is_some_and(|t| {
+                    ["SELECT", "WITH", "TABLE", "VALUES"]
+                        .iter()
+                        .any(|word| t.is_word(word))
+                });
+                // (t1 JOIN t2 ON ...) where a table is to be.
+                let joined = !subquery
+                    && level.reads
+                    && previous.is_some_and(|t| {
+                        ["FROM", "JOIN", "STRAIGHT_JOIN", "LATERAL"]
+                            .iter()
+                            .any(|word| t.is_word(word))
+                            || (level.from && matches!(t, Token::Comma | Token::LParen))
+                    })
+                    || using == Some(i);
+                levels.push(Level {
+                    reads: subquery || joined,
+                    from: joined,
+                });
+                i += 1;
+                if joined {
+                    i = table_list(tokens, i, updated && levels.len() == 2, &mut access)?;
+                }
+            }
+            Token::RParen => {
+                levels.pop();
+                i += 1;
+            }
+            Token::Comma if level.reads && level.from => {
+                let write = updated && levels.len() == 1;
+                i = table_list(tokens, i + 1, write, &mut access)?;
+            }
+            t if level.reads
+                && (t.is_word("FROM")
+                    || (t.is_word("JOIN") && !after_for)
+                    || t.is_word("TABLE")
+                    || joins(tokens, i)) =>
+            {
+                levels.last_mut()?.from = true;
+                let write = updated && levels.len() == 1 && !t.is_word("FROM");
+                i = table_list(tokens, i + 1, write, &mut access)?;
+            }
+            // FOR UPDATE ends the list, FOR JOIN of an index hint doesn't.
+            t if level.reads
+                && ((AFTER_FROM.iter().any(|word| t.is_word(word)) && !after_for)
+                    || (t.is_word("FOR")
+                        && !tokens.get(i + 1).is_some_and(|next| {
+                            next.is_word("JOIN") || next.is_word("ORDER") || next.is_word("GROUP")
+                        }))) =>
+            {
+                if levels.len() == 1 && t.is_word("SET") {
+                    updated = false;
+                }
+                levels.last_mut()?.from = false;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    if levels.len() != 1 {
+        return None;
+    }
+
+    // A multi-table DELETE names its targets again after FROM; count them once, as writes.
+    let mut seen = Vec::new();
+    access.retain(|(table, write)| {
+        if seen.contains(table) {
+            return false;
+        }
+        if *write {
+            seen.push(table.clone());
+        }
+        true
+    });
+    Some(access)
+}
+
+/// Where the parenthesis closing the one at `open` is.
+pub(crate) fn closing(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    (open..tokens.len()).find(|&i| {
+        match tokens[i] {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            _ => {}
+        }
+        depth == 0
+    })
+}
+
+// The tables of LOCK TABLES t1 READ, t2 AS a WRITE: written when locked for WRITE.
+fn locked(tokens: &[Token]) -> Option<Vec<(ObjectName, bool)>> {
+    let mut access = Vec::new();
+    let mut rest = match tokens {
+        [_, tables, rest @ ..] if tables.is_word("TABLES") || tables.is_word("TABLE") => rest,
+        _ => return Some(access),
+    };
+    loop {
+        let (table, after) = object_name(rest)?;
+        let end = after
+            .iter()
+            .position(|t| *t == Token::Comma)
+            .unwrap_or(after.len());
+        let write = after[..end].iter().any(|t| t.is_word("WRITE"));
+        access.push((table, write));
+        match after.get(end) {
+            Some(_) => rest = &after[end + 1..],
+            None => return Some(access),
+        }
+    }
+}
+
+// The tables of EXPLAIN, DESCRIBE or DESC, `rest` the tokens after it: those of the statement
+// it explains, which EXPLAIN ANALYZE runs, or the table it describes.
+fn explained(mut rest: &[Token]) -> Option<Vec<(ObjectName, bool)>> {
+    loop {
+        match rest {
+            [option, rest_ @ ..] if EXPLAIN_OPTIONS.iter().any(|o| option.is_word(o)) => {
+                rest = rest_
+            }
+            // FORMAT=JSON, or PostgreSQL's EXPLAIN (ANALYZE, FORMAT JSON).
+            [format, equals, _, rest_ @ ..]
+                if format.is_word("FORMAT") && equals.is_operator("=") =>
+            {
+                rest = rest_
+            }
+            [Token::LParen, option, ..]
+                if !["SELECT", "WITH", "TABLE", "VALUES"]
+                    .iter()
+                    .any(|word| option.is_word(word)) =>
+            {
+                rest = &rest[closing(rest, 0)? + 1..]
+            }
+            _ => break,
+        }
+    }
+    match rest.first() {
+        // EXPLAIN FOR CONNECTION n explains another connection's statement.
+        Some(t) if t.is_word("FOR") => Some(Vec::new()),
+        Some(t)
+            if *t == Token::LParen
+                || [
+                    "SELECT", "WITH", "TABLE", "VALUES", "INSERT", "REPLACE", "UPDATE", "DELETE",
+                ]
+                .iter()
+                .any(|word| t.is_word(word)) =>
+        {
+            table_access(rest)
+        }
+        _ => Some(vec![(object_name(rest)?.0, false)]),
+    }
+}
+
+// Reads a comma-separated list of tables (with optional aliases) starting at `i`. Returns the
+// position after the list, or of the parentheses of a derived table or join in it; None if
+// something other than a table starts it or follows a comma.
+fn table_list(
+    tokens: &[Token],
+    mut i: usize,
+    write: bool,
+    access: &mut Vec<(ObjectName, bool)>,
+) -> Option<usize> {
+    loop {
+        if tokens.get(i).is_some_and(|t| t.is_word("LATERAL")) {
+            i += 1;
+        }
+        match tokens.get(i) {
+            Some(Token::LParen) => return Some(i),
+            Some(t) if t.is_word("DUAL") => return Some(i + 1),
+            Some(Token::Word(w)) if NOT_ALIAS.iter().any(|k| w.eq_ignore_ascii_case(k)) => {
+                return None
+            }
+            _ => {}
+        }
+        let (table, rest) = object_name(&tokens[i..])?;
+        access.push((table, write));
+        i = tokens.len() - rest.len();
+
+        // Optional alias.
+        if tokens.get(i).is_some_and(|t| t.is_word("AS")) {
+            i += 2;
+        } else if tokens.get(i).is_some_and(is_alias) {
+            i += 1;
+        }
+
+        if matches!(tokens.get(i), Some(Token::Comma)) {
+            i += 1;
+        } else {
+            return Some(i);
+        }
+    }
+}
+
+// Whether the STRAIGHT_JOIN at `i` joins tables, rather than being the SELECT modifier.
+fn joins(tokens: &[Token], i: usize) -> bool {
+    const SELECT_MODIFIERS: [&str; 5] =
+        ["SELECT", "ALL", "DISTINCT", "DISTINCTROW", "HIGH_PRIORITY"];
+    tokens[i].is_word("STRAIGHT_JOIN")
+        && !i
+            .checked_sub(1)
+            .is_some_and(|before| SELECT_MODIFIERS.iter().any(|m| tokens[before].is_word(m)))
+}
+
+fn is_alias(token: &Token) -> bool {
+    match token {
+        Token::Word(w) => !NOT_ALIAS.iter().any(|k| w.eq_ignore_ascii_case(k)),
+        Token::QuotedIdent(_) => true,
+        _ => false,
+    }
+}
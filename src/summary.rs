@@ -0,0 +1,483 @@
+// What the binary prints as it starts serving: where it listens, the PostgreSQL it runs the
+// statements on and how, what is switched on, the MySQL behaviour it imitates and the limits it
+// enforces, one setting per line, so that a setting that isn't what was meant stands out:
+//
+//   PostMyRustache 0.1.0
+//     listening      0.0.0.0:3306 over tcp
+//     upstream       postgres@localhost, sslmode prefer
+//     users          any user, with any password
+//     statements     read-only, report only select
+//     compatibility  sql_mode STRICT_TRANS_TABLES, CHECK constraints enforced, ...
+//     rewrite rules  12 from rules.toml, reread on SIGHUP
+//     subsystems     stats file stats.toml, OTLP tracing off, protocol trace off, shadow MySQL off
+//     limits         statements up to 64 MiB, nesting 256 deep, 1024 UNION branches, ...
+//     clients        up to 200 connections, no per-user connection limit, ...
+//     runtime        a worker thread per CPU, 512 blocking threads
+//
+// On a terminal the names are in colour and settings that weaken the proxy, an unencrypted or
+// unverified upstream say, in yellow, unless NO_COLOR is set. With LOG_FORMAT = json each line
+// is logged as a message instead.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::audit::{AuditConfig, AuditSink};
+use crate::auth::{AuthConfig, JwtKey};
+use crate::config::{Config, ConfigError, ParseFailure};
+use crate::logging::{LogFormat, Logger};
+use crate::policy::{PolicyConfig, StatementClass};
+use crate::result_cache::ResultCacheConfig;
+use crate::rewrite_rules::Rules;
+use crate::tls::SslMode;
+use crate::translator::{CheckConstraints, IdentifierCase, ZeroDates};
+use crate::transport::TransportKind;
+
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+// A line of the summary, and whether its setting deserves a second look.
+struct Line {
+    name: &'static str,
+    value: String,
+    caution: bool,
+}
+
+/// Prints the summary of `config`. Fails if its rules file can't be read.
+pub fn print(config: &Config) -> Result<(), ConfigError> {
+    let heading = format!("PostMyRustache {}", env!("CARGO_PKG_VERSION"));
+    let lines = lines(config)?;
+    if config.log_format == LogFormat::Json {
+        let log = Logger::new(config.log_format);
+        log.info(format_args!("{}", heading));
+        for line in &lines {
+            log.info(format_args!("{}: {}", line.name, line.value));
+        }
+        return Ok(());
+    }
+
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let paint = |code: &'static str| if color { code } else { "" };
+    let width = lines.iter().map(|line| line.name.len()).max().unwrap_or(0);
+    println!("{}{}{}", paint(BOLD), heading, paint(RESET));
+    for line in &lines {
+        let (caution, end) = match line.caution {
+            true => (paint(YELLOW), paint(RESET)),
+            false => ("", ""),
+        };
+        println!(
+            "  {}{:width$}{}  {}{}{}",
+            paint(CYAN),
+            line.name,
+            paint(RESET),
+            caution,
+            line.value,
+            end,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+fn lines(config: &Config) -> Result<Vec<Line>, ConfigError> {
+    let line = |name, value: String| Line {
+        name,
+        value,
+        caution: false,
+    };
+    let mut lines = Vec::new();
+
+    let transport = match config.listen_transport {
+        TransportKind::Tcp => "tcp",
+        TransportKind::IoUring => "io_uring",
+    };
+    let mut listening = format!("{} over {}", config.listen_addr, transport);
+    if let Some(tls) = &config.client_tls {
+        listening.push_str(match tls.ca_file {
+            Some(_) => ", TLS with client certificates",
+            None => ", TLS",
+        });
+    }
+    lines.push(line("listening", listening));
+    if let Some(addr) = &config.admin_listen_addr {
+        // Its clients log in without a password and may KILL every user's connections.
+        lines.push(Line {
+            name: "admin listener",
+            value: addr.clone(),
+            caution: true,
+        });
+    }
+
+    let mut upstream = format!(
+        "{}@{}, sslmode {}",
+        config.db_user, config.db_host, config.tls.mode
+    );
+    if config.tls.client_cert.is_some() {
+        upstream.push_str(" with a client certificate");
+    }
+    if !config.db_standby_hosts.is_empty() {
+        upstream.push_str(&format!(
+            ", failing over to {}",
+            config.db_standby_hosts.join(", ")
+        ));
+    }
+    if config.idempotent_writes {
+        upstream.push_str(", writes retried with idempotency keys");
+    }
+    if let Some(timeout) = config.db_statement_timeout {
+        upstream.push_str(&format!(", statement_timeout {} ms", timeout.as_millis()));
+    }
+    // The tunnel's sessions go without TLS, whatever the sslmode.
+    if let Some(tunnel) = &config.db_tunnel {
+        upstream.push_str(&format!(", compressed through {} unencrypted", tunnel));
+    }
+    lines.push(Line {
+        name: "upstream",
+        value: upstream,
+        caution: config.db_tunnel.is_some()
+            || matches!(config.tls.mode, SslMode::Disable | SslMode::Prefer),
+    });
+
+    // Without a provider everyone reaches PostgreSQL as DB_USER. With an unencrypted webhook or
+    // directory the passwords go on over the network as they are.
+    let (mut users, caution) = match &config.auth {
+        AuthConfig::None => ("any user, with any password".to_string(), true),
+        AuthConfig::Static(users) => (format!("the {} of AUTH_USERS", users.len()), false),
+        AuthConfig::Webhook { url, .. } => (
+            format!("checked with the webhook at {}", url),
+            !url.to_ascii_lowercase().starts_with("https://"),
+        ),
+        AuthConfig::Ldap { url, .. } => (
+            format!("checked with the LDAP directory at {}", url),
+            !url.to_ascii_lowercase().starts_with("ldaps://"),
+        ),
+        AuthConfig::Jwt(jwt) => {
+            let mut users = match &jwt.key {
+                JwtKey::Secret(_) => "with tokens signed with AUTH_JWT_SECRET".to_string(),
+                JwtKey::PublicKeyFile(path) => {
+                    format!("with tokens signed with the key of {}", path.display())
+                }
+            };
+            if let Some(claim) = &jwt.role_claim {
+                users.push_str(&format!(", as the role of their {} claim", claim));
+            }
+            (users, false)
+        }
+    };
+    let required = config
+        .client_tls
+        .as_ref()
+        .map_or(0, |tls| tls.requirements.len());
+    if required > 0 {
+        users.push_str(&format!(", {} with USER_REQUIRE", required));
+    }
+    lines.push(Line {
+        name: "users",
+        value: users,
+        caution,
+    });
+
+    lines.push(line("statements", policy(&config.policy)));
+    lines.push(compatibility(config));
+
+    lines.push(line(
+        "rewrite rules",
+        match &config.rewrite_rules {
+            Some(path) => format!(
+                "{} from {}, reread on SIGHUP",
+                Rules::load(path)?.count(),
+                path
+            ),
+            None => "none".to_string(),
+        },
+    ));
+
+    let subsystems = [
+        ("stats file", config.stats_file.clone()),
+        (
+            "OTLP tracing",
+            config.telemetry.as_ref().map(|t| t.endpoint.clone()),
+        ),
+        (
+            "protocol trace",
+            config.trace.as_ref().map(|t| t.file.clone()),
+        ),
+        (
+            "shadow MySQL",
+            config.shadow.as_ref().map(|s| s.target().to_string()),
+        ),
+        ("audit log", config.audit.as_ref().map(audit)),
+        (
+            "DDL history",
+            config
+                .ddl_history
+                .then(|| "in proxy_metadata.ddl_history".to_string()),
+        ),
+        (
+            "statement cache",
+            (config.statement_cache_size > 0)
+                .then(|| format!("{} per connection", config.statement_cache_size)),
+        ),
+        (
+            "result cache",
+            config.result_cache.as_ref().map(result_cache),
+        ),
+        (
+            "GUC mappings",
+            (!config.guc_mappings.is_empty()).then(|| {
+                config
+                    .guc_mappings
+                    .iter()
+                    .map(|m| format!("{}->{}", m.variable, m.guc))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }),
+        ),
+        (
+            "session init",
+            (!config.session_inits.is_empty())
+                .then(|| config.session_inits.users().collect::<Vec<_>>().join(" ")),
+        ),
+        (
+            "protocol compression",
+            Some(config.protocol_compression)
+                .map(|offered| {
+                    [("zlib", offered.zlib), ("zstd", offered.zstd)]
+                        .iter()
+                        .filter(|(_, on)| *on)
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .filter(|names| !names.is_empty()),
+        ),
+    ];
+    let subsystems: Vec<String> = subsystems
+        .into_iter()
+        .map(|(name, setting)| format!("{} {}", name, setting.as_deref().unwrap_or("off")))
+        .collect();
+    lines.push(line("subsystems", subsystems.join(", ")));
+
+    let limits = &config.limits;
+    let limits = [
+        limit(limits.max_length, "length", |max| {
+            format!("statements up to {}", bytes(max as u64))
+        }),
+        limit(
+            Some(config.max_allowed_packet).filter(|max| *max != usize::MAX),
+            "packet",
+            |max| format!("commands up to {}", bytes(max as u64)),
+        ),
+        limit(limits.max_depth, "nesting", |max| {
+            format!("nesting {} deep", max)
+        }),
+        limit(limits.max_union_branches, "UNION", |max| {
+            format!("{} UNION branches", max)
+        }),
+        limit(limits.max_in_list_items, "IN-list", |max| {
+            format!("{} IN-list items", max)
+        }),
+        limit(
+            config
+                .timeouts
+                .max_execution_time
+                .map(|max| max.as_millis() as usize),
+            "execution time",
+            |max| format!("statements cancelled after {} ms", max),
+        ),
+    ];
+    lines.push(line("limits", limits.join(", ")));
+
+    let throttle = &config.throttle;
+    let mut clients = vec![
+        limit(throttle.max_connections, "connection", |max| {
+            format!("up to {} connections", max)
+        }),
+        limit(
+            throttle.max_user_connections,
+            "per-user connection",
+            |max| format!("{} per user", max),
+        ),
+    ];
+    for (user, max) in &throttle.users {
+        clients.push(format!("{} for {}", max, user));
+    }
+    clients.push(limit(
+        throttle.max_queries_per_second.map(|max| max as usize),
+        "statement rate",
+        |max| format!("{} statements a second per user", max),
+    ));
+    clients.push(limit(
+        config
+            .timeouts
+            .wait_timeout
+            .map(|wait| wait.as_secs() as usize),
+        "idle time",
+        |wait| format!("closed after {} s idle", wait),
+    ));
+    lines.push(line("clients", clients.join(", ")));
+
+    let workers = match config.runtime.worker_threads {
+        Some(threads) => format!("{} worker threads", threads),
+        None => "a worker thread per CPU".to_string(),
+    };
+    // tokio's default.
+    let blocking = config.runtime.max_blocking_threads.unwrap_or(512);
+    lines.push(line(
+        "runtime",
+        format!("{}, {} blocking threads", workers, blocking),
+    ));
+    Ok(lines)
+}
+
+// SQL_MODE as the translator has it, and the settings that make the proxy behave unlike MySQL
+// or like an older one.
+fn compatibility(config: &Config) -> Line {
+    let options = &config.translation;
+    let modes: Vec<&str> = [
+        (options.pipes_as_concat, "PIPES_AS_CONCAT"),
+        (options.ansi_quotes, "ANSI_QUOTES"),
+        (options.only_full_group_by, "ONLY_FULL_GROUP_BY"),
+        (options.no_backslash_escapes, "NO_BACKSLASH_ESCAPES"),
+        (options.dates.strict, "STRICT_TRANS_TABLES"),
+        (options.dates.no_zero_date, "NO_ZERO_DATE"),
+        (options.dates.no_zero_in_date, "NO_ZERO_IN_DATE"),
+        (options.dates.allow_invalid_dates, "ALLOW_INVALID_DATES"),
+    ]
+    .into_iter()
+    .filter_map(|(set, mode)| set.then_some(mode))
+    .collect();
+    let mut parts = vec![
+        if modes.is_empty() {
+            "sql_mode empty".to_string()
+        } else {
+            format!("sql_mode {}", modes.join(","))
+        },
+        match options.check_constraints {
+            CheckConstraints::Enforce => "CHECK constraints enforced".to_string(),
+            CheckConstraints::Strip => "CHECK constraints dropped".to_string(),
+        },
+        match config.parse_failure {
+            ParseFailure::Passthrough => "unparsable statements forwarded".to_string(),
+            ParseFailure::Reject => "unparsable statements rejected".to_string(),
+        },
+    ];
+    if config.implicit_defaults {
+        parts.push("implicit defaults".to_string());
+    }
+    if config.auto_create_databases {
+        parts.push("unknown databases created".to_string());
+    }
+    if let Some(directory) = &config.secure_file_priv {
+        parts.push(format!("LOAD DATA INFILE from {}", directory));
+    }
+    if config.parameterize {
+        parts.push("literals as parameters".to_string());
+    }
+    if options.fulltext_indexes {
+        parts.push("FULLTEXT as GIN indexes".to_string());
+    }
+    if options.identifiers == IdentifierCase::Preserve {
+        parts.push("quoted identifiers keep their case".to_string());
+    }
+    if options.strict {
+        parts.push("MySQL-only syntax rejected".to_string());
+    }
+    match options.dates.zero_dates {
+        ZeroDates::Null => {}
+        ZeroDates::NullAsZero => parts.push("NULL dates read as zero dates".to_string()),
+        ZeroDates::Sentinel(sentinel) => parts.push(format!("zero dates stored as {}", sentinel)),
+    }
+    if let Some(now) = options.pinned_now {
+        parts.push(format!("NOW() pinned to {}", now));
+    }
+    Line {
+        name: "compatibility",
+        value: parts.join(", "),
+        caution: options.pinned_now.is_some(),
+    }
+}
+
+// The statements refused, by whom.
+fn policy(policy: &PolicyConfig) -> String {
+    let classes = |classes: &[StatementClass]| {
+        let names: Vec<&str> = classes
+            .iter()
+            .map(|class| match class {
+                StatementClass::Select => "select",
+                StatementClass::Dml => "dml",
+                StatementClass::Ddl => "ddl",
+                StatementClass::Admin => "admin",
+            })
+            .collect();
+        names.join(",")
+    };
+    let mut parts = Vec::new();
+    if policy.read_only {
+        parts.push("read-only".to_string());
+    }
+    if let Some(allowed) = &policy.allowed {
+        parts.push(format!("only {}", classes(allowed)));
+    }
+    for (user, allowed) in &policy.users {
+        parts.push(format!("{} only {}", user, classes(allowed)));
+    }
+    for (user, grants) in &policy.grants {
+        let tables: Vec<String> = grants
+            .iter()
+            .map(|grant| {
+                let name = |name: &Option<String>| name.as_deref().unwrap_or("*").to_string();
+                let access = if grant.write { "write" } else { "read" };
+                format!("{}.{}={}", name(&grant.schema), name(&grant.table), access)
+            })
+            .collect();
+        parts.push(format!("{} only tables {}", user, tables.join(",")));
+    }
+    match parts.is_empty() {
+        true => "all allowed".to_string(),
+        false => parts.join(", "),
+    }
+}
+
+// Where the audit log goes, and whose statements it has.
+fn audit(audit: &AuditConfig) -> String {
+    let mut setting = match &audit.sink {
+        AuditSink::File(path) => path.clone(),
+        AuditSink::Syslog => "to syslog".to_string(),
+    };
+    if let Some(users) = &audit.users {
+        setting.push_str(&format!(" for {}", users.join(", ")));
+    }
+    if audit.redact {
+        setting.push_str(" with values redacted");
+    }
+    setting
+}
+
+// The rule count and the most results kept, e.g. `3 rules, up to 1024 results`.
+fn result_cache(cache: &ResultCacheConfig) -> String {
+    format!(
+        "{} rule{}, up to {} results",
+        cache.rules.len(),
+        if cache.rules.len() == 1 { "" } else { "s" },
+        cache.size
+    )
+}
+
+// A limit as `describe` puts it, if there is one.
+fn limit(limit: Option<usize>, name: &str, describe: impl Fn(usize) -> String) -> String {
+    limit.map_or_else(|| format!("no {} limit", name), describe)
+}
+
+// `count` bytes in the largest unit they are a whole number of.
+fn bytes(count: u64) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KiB", "MiB", "GiB"];
+    let mut value = count;
+    let mut unit = 0;
+    while unit + 1 < UNITS.len() && value >= 1024 && value.is_multiple_of(1024) {
+        value /= 1024;
+        unit += 1;
+    }
+    format!("{} {}", value, UNITS[unit])
+}
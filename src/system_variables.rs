@@ -0,0 +1,87 @@
+// System variables the proxy knows the value of, put in the statements that read them, as
+// PostgreSQL has no @@ variables:
+//
+//   SELECT @@character_set_client          ->  SELECT 'utf8mb4' AS "@@character_set_client"
+//   SELECT @@session.tx_read_only, a FROM t ->  SELECT 0 AS "@@session.tx_read_only", a FROM t
+//
+// A variable selected as it is keeps the column name MySQL gives it. @@global. variables go by
+// `global.name`, for the few whose global value the proxy knows apart from the session's.
+
+use crate::translator::{self, render, Node, Token};
+
+/// The name of `variable`, `@@name`, `@@session.name` or `@@local.name`, in lower case, and
+/// `global.name` for `@@global.name`; `None` for others.
+pub fn name(variable: &str) -> Option<String> {
+    let variable = variable.to_ascii_lowercase();
+    ["@@session.", "@@local.", "@@"]
+        .iter()
+        .find_map(|prefix| variable.strip_prefix(prefix))
+        .map(str::to_string)
+}
+
+/// The translated statement `sql` with the variables `value` knows, by name, replaced by the
+/// token it gives. `None` if it reads none of them.
+pub fn substitute(sql: &str, value: &dyn Fn(&str) -> Option<Token>) -> Option<String> {
+    if !sql.contains("@@") {
+        return None;
+    }
+    let nodes = translator::parse(sql).ok()?;
+    let mut replaced = false;
+    let nodes = replace(nodes, value, &mut replaced);
+    replaced.then(|| render(&nodes))
+}
+
+fn replace(
+    nodes: Vec<Node>,
+    value: &dyn Fn(&str) -> Option<Token>,
+    replaced: &mut bool,
+) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut nodes = nodes.into_iter().peekable();
+    while let Some(node) = nodes.next() {
+        let (variable, token) = match node {
+            Node::Group(inner) => {
+                out.push(Node::Group(replace(inner, value, replaced)));
+                continue;
+            }
+            Node::Token(Token::Variable(variable)) => {
+                match name(&variable).and_then(|name| value(&name)) {
+                    Some(token) => (variable, token),
+                    None => {
+                        out.push(Node::Token(Token::Variable(variable)));
+                        continue;
+                    }
+                }
+            }
+            other => {
+                out.push(other);
+                continue;
+            }
+        };
+        *replaced = true;
+        out.push(Node::Token(token));
+        // A select item of its own, without an alias.
+        let previous = out.iter().rev().skip(1).find(|n| !n.is_trivia());
+        let item =
+            matches!(previous, Some(Node::Token(t)) if t.is_word("SELECT") || *t == Token::Comma);
+        let next = nodes.clone().find(|n| !n.is_trivia());
+        let unaliased = match next {
+            None => true,
+            Some(Node::Token(Token::Comma | Token::Semicolon)) => true,
+            Some(Node::Token(t)) => ["FROM", "LIMIT", "WHERE", "UNION", "ORDER", "GROUP", "INTO"]
+                .iter()
+                .any(|word| t.is_word(word)),
+            Some(Node::Group(_)) => false,
+        };
+        if item && unaliased {
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::Word("AS".to_string())));
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::DoubleQuoted(format!(
+                "\"{}\"",
+                variable.replace('"', "\"\"")
+            ))));
+        }
+    }
+    out
+}
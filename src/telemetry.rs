@@ -0,0 +1,114 @@
+// Tracing spans for each statement, to tell which part of a slow MySQL request was slow and
+// which PostgreSQL statement it ran.
+//
+// A statement gets a `query` span with these under it, as far as it gets:
+//
+//   query       command, connection_id, fingerprint, statement, queue_time_us
+//     intercept   the QueryInterceptors' before_translate
+//     translate   MySQL to PostgreSQL
+//     prepare     preparing on PostgreSQL, db.statement the PostgreSQL statement
+//     execute     running it
+//     encode      sending the rows to the client
+//
+// `command` is COM_QUERY, COM_STMT_PREPARE or COM_STMT_EXECUTE. `statement` is the digest text
+// of the MySQL statement, with its literals as `?` and lists of them shortened, and
+// `fingerprint` its fingerprint (see digest.rs). queue_time_us is how long
+// the command waited after it arrived, before the proxy started on it. Values never end up in a
+// span.
+//
+// The spans are `tracing` spans, and cost next to nothing without a subscriber. The binary,
+// built with the otlp feature, exports them over OTLP to OTEL_EXPORTER_OTLP_ENDPOINT, as
+// OTEL_SERVICE_NAME (postmyrustache by default). A program embedding the proxy can install
+// a subscriber of its own instead.
+
+use std::error::Error;
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::digest::{self, Digest};
+
+pub const DEFAULT_SERVICE_NAME: &str = "postmyrustache";
+
+/// Where the spans are exported (OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_SERVICE_NAME).
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+/// Exports the spans of this process over OTLP (gRPC), as the tracing subscriber.
+#[cfg(feature = "otlp")]
+pub fn export(config: &TelemetryConfig) -> Result<(), Box<dyn Error>> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::trace::TracerProvider;
+    use opentelemetry_sdk::{runtime, Resource};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build();
+    let tracer = provider.tracer("postmyrustache");
+    opentelemetry::global::set_tracer_provider(provider);
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "otlp"))]
+pub fn export(_config: &TelemetryConfig) -> Result<(), Box<dyn Error>> {
+    Err(
+        "OTEL_EXPORTER_OTLP_ENDPOINT is set, but this build has no OTLP exporter; build with \
+         --features otlp"
+            .into(),
+    )
+}
+
+/// The span of a statement `sql`, which came with `command` and was received from the client
+/// at `received`.
+pub(crate) fn query_span(
+    command: &'static str,
+    connection_id: u32,
+    sql: &str,
+    received: Option<Instant>,
+) -> Span {
+    let span = tracing::info_span!(
+        "query",
+        command,
+        connection_id,
+        fingerprint = Empty,
+        statement = Empty,
+        queue_time_us = Empty
+    );
+    if !span.is_disabled() {
+        let digest = Digest::of(sql);
+        span.record("fingerprint", digest.fingerprint.as_str());
+        span.record("statement", digest.text.as_str());
+        if let Some(received) = received {
+            span.record("queue_time_us", received.elapsed().as_micros() as u64);
+        }
+    }
+    span
+}
+
+/// The span of preparing `sql`, a translated statement, on PostgreSQL.
+pub(crate) fn prepare_span(sql: &str) -> Span {
+    let span = tracing::info_span!("prepare", db.statement = Empty);
+    if !span.is_disabled() {
+        span.record("db.statement", digest::normalize(sql).as_str());
+    }
+    span
+}
@@ -0,0 +1,163 @@
+// Connection limits and per-user throttling, so a runaway application can't take every
+// PostgreSQL session or keep PostgreSQL busy for everyone else:
+//
+//   MAX_CONNECTIONS         clients connected at once, all users together
+//   MAX_USER_CONNECTIONS    clients connected at once as any one user
+//   USER_MAX_CONNECTIONS    "etl: 20; report: 2", in place of MAX_USER_CONNECTIONS for those users
+//   MAX_QUERIES_PER_SECOND  statements any one user may run in a second, over all their
+//                           connections
+//
+// None of them is set by default. A client over MAX_CONNECTIONS is refused as it connects,
+// before it logs in or a PostgreSQL session is opened for it, with MySQL's error 1040. One over
+// its user's limit is refused at login with error 1226, as MySQL refuses an account over its
+// MAX_USER_CONNECTIONS, and so is a statement over MAX_QUERIES_PER_SECOND, which counts the
+// statements and executions of prepared statements of each second.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+
+/// The limits (MAX_CONNECTIONS, MAX_USER_CONNECTIONS, USER_MAX_CONNECTIONS,
+/// MAX_QUERIES_PER_SECOND), None where there is none.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleConfig {
+    pub max_connections: Option<usize>,
+    pub max_user_connections: Option<usize>,
+    // The connections some users may have, in place of `max_user_connections`.
+    pub users: Vec<(String, usize)>,
+    pub max_queries_per_second: Option<u32>,
+}
+
+impl ThrottleConfig {
+    // How many connections `user` may have, None for any number.
+    fn user_connections(&self, user: &str) -> Option<usize> {
+        match self.users.iter().find(|(name, _)| name == user) {
+            Some((_, max)) => Some(*max),
+            None => self.max_user_connections,
+        }
+    }
+}
+
+// A user's connections, and statements in the current second.
+#[derive(Default)]
+struct User {
+    connections: usize,
+    second: Option<Instant>,
+    statements: u32,
+}
+
+/// The connections and statement rates of every client, shared by all connections.
+pub(crate) struct Throttle {
+    config: ThrottleConfig,
+    connections: AtomicUsize,
+    users: Mutex<HashMap<String, User>>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Throttle {
+        Throttle {
+            config,
+            connections: AtomicUsize::new(0),
+            users: Mutex::default(),
+        }
+    }
+
+    /// Counts a client that has connected, for as long as the guard is kept.
+    pub fn connect(self: &Arc<Self>) -> Result<ConnectionGuard, MysqlError> {
+        let connections = self.connections.fetch_add(1, Ordering::Relaxed);
+        let guard = ConnectionGuard {
+            throttle: Arc::clone(self),
+        };
+        match self.config.max_connections {
+            Some(max) if connections >= max => Err(MysqlError::new(
+                ErrorKind::ER_CON_COUNT_ERROR,
+                "Too many connections",
+            )),
+            _ => Ok(guard),
+        }
+    }
+
+    /// Counts a connection logged in as `user`, for as long as the guard is kept.
+    pub fn login(self: &Arc<Self>, user: &str) -> Result<UserGuard, MysqlError> {
+        let mut users = self.users.lock().unwrap();
+        let connections = users.get(user).map_or(0, |state| state.connections);
+        if let Some(max) = self.config.user_connections(user) {
+            if connections >= max {
+                return Err(exceeded(user, "max_user_connections", max as u64));
+            }
+        }
+        users.entry(user.to_string()).or_default().connections += 1;
+        Ok(UserGuard {
+            throttle: Arc::clone(self),
+            user: user.to_string(),
+        })
+    }
+
+    /// Counts a statement `user` runs, refusing it if it is one too many this second.
+    pub fn statement(&self, user: &str) -> Result<(), MysqlError> {
+        let Some(max) = self.config.max_queries_per_second else {
+            return Ok(());
+        };
+        let mut users = self.users.lock().unwrap();
+        let state = users.entry(user.to_string()).or_default();
+        let now = Instant::now();
+        match state.second {
+            Some(start) if now.duration_since(start).as_secs() == 0 => {}
+            _ => {
+                state.second = Some(now);
+                state.statements = 0;
+            }
+        }
+        if state.statements >= max {
+            return Err(exceeded(user, "max_queries_per_second", max.into()));
+        }
+        state.statements += 1;
+        Ok(())
+    }
+}
+
+/// A connected client, counted against MAX_CONNECTIONS until dropped.
+pub(crate) struct ConnectionGuard {
+    throttle: Arc<Throttle>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.throttle.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A logged-in connection, counted against its user's limit until dropped.
+pub(crate) struct UserGuard {
+    throttle: Arc<Throttle>,
+    user: String,
+}
+
+impl Drop for UserGuard {
+    fn drop(&mut self) {
+        let mut users = self.throttle.users.lock().unwrap();
+        if let Some(state) = users.get_mut(&self.user) {
+            state.connections -= 1;
+            // Forgotten once gone, as anyone can log in by any name.
+            if state.connections == 0 {
+                users.remove(&self.user);
+            }
+        }
+    }
+}
+
+// MySQL's refusal of an account over one of its resource limits.
+fn exceeded(user: &str, resource: &str, value: u64) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_USER_LIMIT_REACHED,
+        format!(
+            "User '{}' has exceeded the '{}' resource (current value: {})",
+            user, resource, value
+        ),
+    )
+}
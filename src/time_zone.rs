@@ -0,0 +1,157 @@
+// The connection's time zone, as MySQL's time_zone: SYSTEM to begin with, the server's, and then
+// whatever SET time_zone makes it. It is set on the PostgreSQL session as its TimeZone, which
+// timestamptz values are shown in and NOW() and the like read the time of:
+//
+//   SET time_zone = '+02:00'         ->  SET TIME ZONE INTERVAL '+02:00' HOUR TO MINUTE
+//   SET time_zone = 'Europe/Berlin'  ->  SET TIME ZONE 'Europe/Berlin'
+//   SET time_zone = SYSTEM           ->  SET TIME ZONE DEFAULT
+//
+// An offset goes as an interval: PostgreSQL reads '+02:00' as a POSIX zone, two hours west of
+// UTC. MySQL takes offsets from -13:59 to +14:00; a named zone is whatever PostgreSQL knows. Any
+// other is refused with error 1298, as MySQL refuses a zone it doesn't know.
+//
+// The zone is kept, and set again on a new session should the connection's be lost; it goes with
+// COM_RESET_CONNECTION. SELECT @@time_zone and SHOW VARIABLES report it, and @@global.time_zone
+// is SYSTEM.
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::translator::{self, literals, Token};
+
+/// A connection's time zone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimeZone {
+    /// The PostgreSQL server's.
+    #[default]
+    System,
+    /// Minutes east of UTC.
+    Offset(i32),
+    /// A zone of PostgreSQL's time zone database, as the client named it.
+    Named(String),
+}
+
+impl TimeZone {
+    /// The zone `value`, a time_zone setting, names; `None` if it can't be one.
+    fn parse(value: &str) -> Option<TimeZone> {
+        if value.eq_ignore_ascii_case("SYSTEM") {
+            return Some(TimeZone::System);
+        }
+        let Some((sign, offset)) = value
+            .strip_prefix('+')
+            .map(|offset| (1, offset))
+            .or_else(|| value.strip_prefix('-').map(|offset| (-1, offset)))
+        else {
+            let plain = !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c));
+            return plain.then(|| TimeZone::Named(value.to_string()));
+        };
+        let (hours, minutes) = offset.split_once(':')?;
+        let digits = |part: &str, max: usize| {
+            (!part.is_empty() && part.len() <= max && part.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| part.parse::<i32>().ok())
+                .flatten()
+        };
+        let (hours, minutes) = (digits(hours, 2)?, digits(minutes, 2)?);
+        if minutes > 59 {
+            return None;
+        }
+        let offset = sign * (hours * 60 + minutes);
+        (-(13 * 60 + 59)..=14 * 60)
+            .contains(&offset)
+            .then_some(TimeZone::Offset(offset))
+    }
+
+    /// The zone as @@time_zone reads it: `SYSTEM`, `+02:00` or the name.
+    pub fn name(&self) -> String {
+        match self {
+            TimeZone::System => "SYSTEM".to_string(),
+            TimeZone::Offset(offset) => format!(
+                "{}{:02}:{:02}",
+                if *offset < 0 { '-' } else { '+' },
+                offset.abs() / 60,
+                offset.abs() % 60
+            ),
+            TimeZone::Named(name) => name.clone(),
+        }
+    }
+
+    /// The statement setting the zone on a PostgreSQL session.
+    pub fn sql(&self) -> String {
+        match self {
+            TimeZone::System => "SET TIME ZONE DEFAULT".to_string(),
+            TimeZone::Offset(_) => {
+                format!("SET TIME ZONE INTERVAL '{}' HOUR TO MINUTE", self.name())
+            }
+            TimeZone::Named(name) => format!("SET TIME ZONE {}", literals::pg_string(name)),
+        }
+    }
+
+    /// The statement setting the zone on a new session, if one was set.
+    pub fn session_sql(&self) -> Option<String> {
+        (*self != TimeZone::System).then(|| self.sql())
+    }
+
+    /// The value of `name`, lower case and `global.` before a global variable's, as SELECT
+    /// @@name reads it; `None` if it isn't time_zone.
+    pub fn variable(&self, name: &str) -> Option<String> {
+        match name {
+            "time_zone" => Some(self.name()),
+            "global.time_zone" => Some(TimeZone::System.name()),
+            _ => None,
+        }
+    }
+}
+
+/// The zone `sql`, a statement as the client sent it, sets, if it is a SET of the session's
+/// time_zone alone: `SET [SESSION] time_zone = '+02:00'`, `SET @@time_zone = DEFAULT` and the
+/// like. A zone that can't be one is error 1298.
+pub fn set(sql: &str) -> Option<Result<TimeZone, MysqlError>> {
+    let tokens = translator::significant_tokens(sql)?;
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    let (set, rest) = tokens.split_first()?;
+    if !set.is_word("SET") {
+        return None;
+    }
+    let rest = match rest {
+        [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => rest,
+        rest => rest,
+    };
+    let [name, equals, value] = rest else {
+        return None;
+    };
+    let name = match name {
+        Token::Word(word) => word.to_ascii_lowercase(),
+        Token::Variable(variable) => {
+            let variable = variable.to_ascii_lowercase();
+            ["@@session.", "@@local.", "@@"]
+                .iter()
+                .find_map(|prefix| variable.strip_prefix(prefix))?
+                .to_string()
+        }
+        _ => return None,
+    };
+    if name != "time_zone" || !(equals.is_operator("=") || equals.is_operator(":=")) {
+        return None;
+    }
+    let value = match value {
+        value if value.is_word("DEFAULT") => return Some(Ok(TimeZone::System)),
+        Token::String(raw) => literals::mysql_string_value(raw),
+        Token::Word(word) => word.clone(),
+        token => token.to_string(),
+    };
+    Some(TimeZone::parse(&value).ok_or_else(|| unknown(&value)))
+}
+
+/// Error 1298, for a zone PostgreSQL doesn't know.
+pub fn unknown(name: &str) -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_UNKNOWN_TIME_ZONE,
+        format!("Unknown or incorrect time zone: '{}'", name),
+    )
+}
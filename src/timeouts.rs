@@ -0,0 +1,101 @@
+// Timeouts for idle connections and long statements, without which a client that never
+// disconnects holds its PostgreSQL session for good, and a statement that hangs, on a lock say,
+// holds its connection.
+//
+// WAIT_TIMEOUT closes a connection that has had no command running for that many seconds, as
+// MySQL's wait_timeout does, and with its default of 28800 (eight hours); 0 leaves idle
+// connections open. MAX_EXECUTION_TIME, in milliseconds as MySQL's max_execution_time is,
+// cancels a statement still running on PostgreSQL after that long, with pg_cancel_backend from
+// a session of its own, since the statement's session is busy running it. Its client gets
+//
+//   ERROR 3024 (HY000): Query execution was interrupted, maximum statement execution time
+//   exceeded
+//
+// MySQL times only SELECT; the proxy times every statement it sends to PostgreSQL, as a write
+// waiting on a lock hangs just as well. Statements the proxy answers itself aren't timed.
+// MAX_EXECUTION_TIME is off (0) by default.
+//
+// PostgreSQL can time statements itself as well: DB_STATEMENT_TIMEOUT, in milliseconds, is the
+// statement_timeout of every session the proxy opens, off by default, and a statement it cancels
+// fails with the same error. DB_CONNECT_TIMEOUT, 10 seconds by default, bounds how long opening
+// a session may take.
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::protocol::Commands;
+use crate::server::Upstream;
+
+// MySQL's wait_timeout, in seconds.
+pub const DEFAULT_WAIT_TIMEOUT: u64 = 28800;
+
+// MySQL's error for a statement that ran out of max_execution_time, which opensrv doesn't know.
+// Its SQLSTATE is HY000, as ER_UNKNOWN_ERROR's is.
+const ER_QUERY_TIMEOUT: u16 = 3024;
+
+/// How long connections may be idle and statements run (WAIT_TIMEOUT, MAX_EXECUTION_TIME); `None`
+/// for no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutConfig {
+    pub wait_timeout: Option<Duration>,
+    pub max_execution_time: Option<Duration>,
+}
+
+/// When a statement is cancelled for running longer than MAX_EXECUTION_TIME, kept across the
+/// steps it is run in, as its rows are read, and whether it has been.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    pub at: Instant,
+    pub cancelled: bool,
+}
+
+impl Deadline {
+    /// The deadline of a statement starting now, if there is a `max_execution_time`.
+    pub fn after(max_execution_time: Option<Duration>) -> Option<Deadline> {
+        max_execution_time.map(|limit| Deadline {
+            at: Instant::now() + limit,
+            cancelled: false,
+        })
+    }
+}
+
+/// Returns once the client of `commands`, which `connected` at the time given, has been idle for
+/// `wait_timeout`, or never without one.
+pub async fn idle(commands: &Commands, connected: Instant, wait_timeout: Option<Duration>) {
+    let Some(wait_timeout) = wait_timeout else {
+        return std::future::pending().await;
+    };
+    loop {
+        match commands.idle_since(connected) {
+            Some(since) if since.elapsed() >= wait_timeout => return,
+            Some(since) => tokio::time::sleep_until((since + wait_timeout).into()).await,
+            // Running a command; it can't have been idle long enough before this much later.
+            None => tokio::time::sleep(wait_timeout).await,
+        }
+    }
+}
+
+/// Cancels the statement the PostgreSQL session with process id `backend_pid` is running, from
+/// a session of `upstream`'s.
+pub async fn cancel(
+    upstream: &dyn Upstream,
+    backend_pid: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let session = upstream.connect().await?;
+    session
+        .execute("SELECT pg_cancel_backend($1)", &[&backend_pid])
+        .await?;
+    Ok(())
+}
+
+/// The error of a statement cancelled for running longer than MAX_EXECUTION_TIME.
+pub fn exceeded() -> MysqlError {
+    MysqlError::with_code(
+        ErrorKind::ER_UNKNOWN_ERROR,
+        ER_QUERY_TIMEOUT,
+        "Query execution was interrupted, maximum statement execution time exceeded",
+    )
+}
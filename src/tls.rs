@@ -0,0 +1,338 @@
+// TLS for the connections to PostgreSQL, with rustls, which managed services (RDS, Cloud SQL,
+// Azure) require.
+//
+// DB_SSLMODE works as libpq's sslmode: `disable` never uses TLS, `prefer` (the default) uses it
+// if the server supports it, `require` insists on it, and neither checks the server's
+// certificate. `verify-full` checks the certificate against DB_SSLROOTCERT, or the public web
+// CAs if that is unset, and that it is for DB_HOST. DB_SSLCERT and DB_SSLKEY give a client
+// certificate for servers that ask for one.
+//
+// Over TLS, SCRAM authentication is bound to the server's certificate (SCRAM-SHA-256-PLUS) when
+// the server offers it, so a password exchange can't be relayed to another server.
+
+use std::fmt;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufReader};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect};
+
+/// How the connection to PostgreSQL is encrypted (DB_SSLMODE).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    #[default]
+    Prefer,
+    Require,
+    VerifyFull,
+}
+
+impl SslMode {
+    /// The sslmode for tokio-postgres's connection string. It has no verify-full of its own;
+    /// the certificate is checked by the connector instead.
+    pub fn connection_mode(self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require | SslMode::VerifyFull => "require",
+        }
+    }
+}
+
+impl std::fmt::Display for SslMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyFull => "verify-full",
+        })
+    }
+}
+
+impl std::str::FromStr for SslMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SslMode, ()> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    pub mode: SslMode,
+    // PEM file of the CAs to check the server's certificate with (DB_SSLROOTCERT).
+    pub root_cert: Option<String>,
+    // PEM files of a client certificate and its private key (DB_SSLCERT, DB_SSLKEY).
+    pub client_cert: Option<(String, String)>,
+}
+
+/// Makes the TLS connections for tokio-postgres.
+#[derive(Clone)]
+pub struct MakeTls {
+    config: Arc<ClientConfig>,
+}
+
+impl MakeTls {
+    /// Reads the certificates `config` names, failing if they can't be read.
+    pub fn new(config: &TlsConfig) -> io::Result<MakeTls> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+        let builder = if config.mode == SslMode::VerifyFull {
+            let mut roots = RootCertStore::empty();
+            match &config.root_cert {
+                Some(file) => {
+                    for certificate in read_certificates(file)? {
+                        roots.add(certificate).map_err(|e| invalid(file, e))?;
+                    }
+                }
+                None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+            }
+            builder.with_root_certificates(roots)
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+        };
+        let config = match &config.client_cert {
+            Some((cert_file, key_file)) => builder
+                .with_client_auth_cert(read_certificates(cert_file)?, read_private_key(key_file)?)
+                .map_err(|e| invalid(key_file, e))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(MakeTls {
+            config: Arc::new(config),
+        })
+    }
+}
+
+fn open(file: &str) -> io::Result<BufReader<File>> {
+    File::open(file)
+        .map(BufReader::new)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))
+}
+
+/// The certificates of the PEM file `file`, failing if it has none.
+pub fn read_certificates(file: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certificates = rustls_pemfile::certs(&mut open(file)?).collect::<io::Result<Vec<_>>>()?;
+    if certificates.is_empty() {
+        return Err(invalid(file, "no certificates"));
+    }
+    Ok(certificates)
+}
+
+/// The private key of the PEM file `file`.
+pub fn read_private_key(file: &str) -> io::Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut open(file)?)?.ok_or_else(|| invalid(file, "no private key"))
+}
+
+/// An error for `file`, which doesn't hold what it should.
+pub fn invalid(file: &str, e: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file, e))
+}
+
+// For `prefer` and `require`, which encrypt without caring who the server is. The signatures
+// of the handshake are still checked, with the certificate's key.
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+impl<S> MakeTlsConnect<S> for MakeTls
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<S>;
+    type TlsConnect = TlsConnector;
+    type Error = io::Error;
+
+    fn make_tls_connect(&mut self, domain: &str) -> io::Result<TlsConnector> {
+        let name = ServerName::try_from(domain.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(TlsConnector {
+            connector: tokio_rustls::TlsConnector::from(Arc::clone(&self.config)),
+            name,
+        })
+    }
+}
+
+pub struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+    name: ServerName<'static>,
+}
+
+impl<S> TlsConnect<S> for TlsConnector
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<S>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<TlsStream<S>>> + Send>>;
+
+    fn connect(self, stream: S) -> Self::Future {
+        Box::pin(async move {
+            let stream = self.connector.connect(self.name, stream).await?;
+            Ok(TlsStream(stream))
+        })
+    }
+}
+
+pub struct TlsStream<S>(tokio_rustls::client::TlsStream<S>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    // PostgreSQL closes the connection as soon as it reads Terminate, which can be before the
+    // TLS close is through.
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match ready!(Pin::new(&mut self.0).poll_shutdown(cx)) {
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => Poll::Ready(Ok(())),
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> tokio_postgres::tls::TlsStream for TlsStream<S> {
+    fn channel_binding(&self) -> ChannelBinding {
+        let (_, connection) = self.0.get_ref();
+        match connection
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .and_then(|certificate| server_end_point(certificate))
+        {
+            Some(hash) => ChannelBinding::tls_server_end_point(hash),
+            None => ChannelBinding::none(),
+        }
+    }
+}
+
+// The tls-server-end-point channel binding (RFC 5929): the hash of the server's certificate,
+// with the hash function its signature uses, or SHA-256 for MD5 and SHA-1. `None` for other
+// signatures, which then go without channel binding, as they do with PostgreSQL's own clients.
+fn server_end_point(certificate: &[u8]) -> Option<Vec<u8>> {
+    // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signatureValue }
+    let (certificate_fields, _) = der(certificate, SEQUENCE)?;
+    let (_, rest) = der(certificate_fields, SEQUENCE)?;
+    let (algorithm, _) = der(rest, SEQUENCE)?;
+    let (oid, _) = der(algorithm, OBJECT_IDENTIFIER)?;
+    let hash = match oid {
+        // md5WithRSAEncryption, sha1WithRSAEncryption and ecdsa-with-SHA1
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x04 | 0x05]
+        | [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01]
+        // sha256WithRSAEncryption and ecdsa-with-SHA256
+        | [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]
+        | [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02] => Sha256::digest(certificate).to_vec(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c]
+        | [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03] => Sha384::digest(certificate).to_vec(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d]
+        | [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04] => Sha512::digest(certificate).to_vec(),
+        _ => return None,
+    };
+    Some(hash)
+}
+
+const SEQUENCE: u8 = 0x30;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+
+// The contents of the DER element with `tag` at the start of `data`, and what follows it.
+fn der(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&found, rest) = data.split_first()?;
+    if found != tag {
+        return None;
+    }
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let width = usize::from(first & 0x7f);
+        if width == 0 || width > 4 {
+            return None;
+        }
+        let (bytes, rest) = rest.split_at_checked(width)?;
+        let length = bytes
+            .iter()
+            .fold(0, |length, &byte| length << 8 | usize::from(byte));
+        (length, rest)
+    };
+    rest.split_at_checked(length)
+}
@@ -0,0 +1,567 @@
+// Packet-level protocol trace, for debugging drivers that misbehave against the proxy.
+//
+// With TRACE_FILE set, every MySQL packet of the selected connections is appended to that file
+// as a hex dump under a one-line summary of what it is:
+//
+//   2024-03-01 12:00:00.123 #7 client seq 0, 9 bytes: COM_QUERY "SELECT 1"
+//       0000  03 53 45 4c 45 43 54 20 31                        .SELECT 1
+//
+// TRACE_CONNECTION and TRACE_USER pick the connections, by the id the server handshake gives the
+// client or by the user it logs in as; a connection matching either is traced. Without them,
+// every connection is. Packets of a connection that may still match TRACE_USER are held back
+// until its handshake response names the user.
+//
+// The summaries follow the client's commands closely enough to tell result set columns from
+// rows, but don't decode values. A connection with SET profiling = 1 also gets a line with the
+// phase timings of each statement it runs.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use chrono::Local;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::logging::Logger;
+
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    pub file: String,
+    pub connection: Option<u32>,
+    pub user: Option<String>,
+}
+
+/// The trace file, shared by all connections.
+pub struct Tracer {
+    file: Mutex<File>,
+    connection: Option<u32>,
+    user: Option<String>,
+    // Where a failure to write the trace is reported.
+    log: Logger,
+}
+
+impl Tracer {
+    pub(crate) fn open(config: TraceConfig, log: Logger) -> io::Result<Tracer> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.file)?;
+        Ok(Tracer {
+            file: Mutex::new(file),
+            connection: config.connection,
+            user: config.user,
+            log,
+        })
+    }
+
+    /// The trace of a new connection, or `None` if it can't match the filters.
+    pub fn connection(self: &Arc<Self>, id: u32, peer: SocketAddr) -> Option<Arc<ConnectionTrace>> {
+        let decision = match (self.connection, &self.user) {
+            (None, None) => Decision::Trace,
+            (Some(connection), _) if connection == id => Decision::Trace,
+            (_, Some(_)) => Decision::Pending(Vec::new()),
+            _ => return None,
+        };
+        let trace = ConnectionTrace {
+            tracer: Arc::clone(self),
+            id,
+            state: Mutex::new(State {
+                decision,
+                client: Vec::new(),
+                server: Vec::new(),
+                client_packets: 0,
+                server_packets: 0,
+                user: None,
+                command: None,
+                response: Response::Start,
+            }),
+        };
+        trace.log(format!("{} #{} connected from {}\n", timestamp(), id, peer));
+        Some(Arc::new(trace))
+    }
+
+    fn write(&self, text: &str) {
+        if let Err(e) = self.file.lock().unwrap().write_all(text.as_bytes()) {
+            self.log
+                .error(format_args!("Failed to write the protocol trace: {}", e));
+        }
+    }
+}
+
+pub struct ConnectionTrace {
+    tracer: Arc<Tracer>,
+    id: u32,
+    state: Mutex<State>,
+}
+
+enum Decision {
+    Trace,
+    // Waiting for the user name; the records so far.
+    Pending(Vec<String>),
+    Skip,
+}
+
+struct State {
+    decision: Decision,
+    // Bytes of a packet not yet complete, per direction.
+    client: Vec<u8>,
+    server: Vec<u8>,
+    client_packets: u64,
+    server_packets: u64,
+    // The user the client logged in as, once known.
+    user: Option<String>,
+    // The command byte of the client's last command.
+    command: Option<u8>,
+    response: Response,
+}
+
+// Where the server is in its reply to the last command.
+enum Response {
+    Start,
+    // Column definitions still to come.
+    Columns(u64),
+    // After the column definitions: an EOF packet, unless the client asked for none.
+    ColumnsEnd,
+    Rows,
+    // The parameter and column definitions after a COM_STMT_PREPARE reply.
+    Definitions,
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Client,
+    Server,
+}
+
+impl ConnectionTrace {
+    fn record(&self, direction: Direction, data: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(state.decision, Decision::Skip) || data.is_empty() {
+            return;
+        }
+        match direction {
+            Direction::Client => state.client.extend_from_slice(data),
+            Direction::Server => state.server.extend_from_slice(data),
+        }
+        while let Some(packet) = state.take_packet(direction) {
+            let summary = state.summarize(direction, &packet);
+            let record = format!(
+                "{} #{} {} seq {}, {} bytes: {}\n{}",
+                timestamp(),
+                self.id,
+                match direction {
+                    Direction::Client => "client",
+                    Direction::Server => "server",
+                },
+                packet.sequence,
+                packet.payload.len(),
+                summary,
+                hex_dump(&packet.payload)
+            );
+            match &mut state.decision {
+                Decision::Trace => self.tracer.write(&record),
+                Decision::Pending(records) => {
+                    records.push(record);
+                    if let Some(held) = state.settle(self.tracer.user.as_deref()) {
+                        self.tracer.write(&held);
+                    }
+                }
+                Decision::Skip => return,
+            }
+        }
+    }
+
+    /// Adds a line about the connection to the trace, such as a statement's profile.
+    pub fn note(&self, text: &str) {
+        self.log(format!("{} #{} {}\n", timestamp(), self.id, text));
+    }
+
+    fn log(&self, record: String) {
+        match &mut self.state.lock().unwrap().decision {
+            Decision::Trace => self.tracer.write(&record),
+            Decision::Pending(records) => records.push(record),
+            Decision::Skip => {}
+        }
+    }
+}
+
+impl Drop for ConnectionTrace {
+    fn drop(&mut self) {
+        self.log(format!("{} #{} disconnected\n", timestamp(), self.id));
+    }
+}
+
+struct Packet {
+    sequence: u8,
+    payload: Vec<u8>,
+}
+
+impl State {
+    // Decides whether a connection waiting for its user name is traced, once the handshake
+    // response, the client's first packet, has named it. Returns the records held back if so.
+    fn settle(&mut self, user: Option<&str>) -> Option<String> {
+        if self.client_packets == 0 {
+            return None;
+        }
+        let Decision::Pending(records) = std::mem::replace(&mut self.decision, Decision::Skip)
+        else {
+            return None;
+        };
+        if self.user.is_none() || self.user.as_deref() != user {
+            return None;
+        }
+        self.decision = Decision::Trace;
+        Some(records.concat())
+    }
+
+    fn take_packet(&mut self, direction: Direction) -> Option<Packet> {
+        let buffer = match direction {
+            Direction::Client => &mut self.client,
+            Direction::Server => &mut self.server,
+        };
+        if buffer.len() < 4 {
+            return None;
+        }
+        let length = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], 0]) as usize;
+        if buffer.len() < 4 + length {
+            return None;
+        }
+        let sequence = buffer[3];
+        let payload = buffer[4..4 + length].to_vec();
+        buffer.drain(..4 + length);
+        Some(Packet { sequence, payload })
+    }
+
+    fn summarize(&mut self, direction: Direction, packet: &Packet) -> String {
+        match direction {
+            Direction::Client => {
+                self.client_packets += 1;
+                if self.client_packets == 1 {
+                    let response = handshake_response(&packet.payload);
+                    self.user = response.user;
+                    return response.summary;
+                }
+                self.summarize_command(packet)
+            }
+            Direction::Server => {
+                self.server_packets += 1;
+                if self.server_packets == 1 {
+                    return server_handshake(&packet.payload);
+                }
+                self.summarize_response(&packet.payload)
+            }
+        }
+    }
+
+    fn summarize_command(&mut self, packet: &Packet) -> String {
+        // Packets later in an exchange carry authentication data or long data continuation.
+        if packet.sequence != 0 {
+            return "authentication data".to_string();
+        }
+        let Some((&command, body)) = packet.payload.split_first() else {
+            return "empty packet".to_string();
+        };
+        self.command = Some(command);
+        self.response = Response::Start;
+        let name = command_name(command);
+        match command {
+            // COM_INIT_DB, COM_QUERY, COM_STMT_PREPARE
+            0x02 | 0x03 | 0x16 => format!("{} {:?}", name, String::from_utf8_lossy(body)),
+            // COM_STMT_EXECUTE, COM_STMT_CLOSE, COM_STMT_RESET, COM_STMT_FETCH,
+            // COM_STMT_SEND_LONG_DATA
+            0x17 | 0x19 | 0x1a | 0x1c | 0x18 if body.len() >= 4 => format!(
+                "{} statement {}",
+                name,
+                u32::from_le_bytes([body[0], body[1], body[2], body[3]])
+            ),
+            _ => name,
+        }
+    }
+
+    fn summarize_response(&mut self, payload: &[u8]) -> String {
+        let first = payload.first().copied();
+        if first == Some(0xff) {
+            self.response = Response::Start;
+            return error_packet(payload);
+        }
+        let eof = first == Some(0xfe) && payload.len() < 9;
+        match self.response {
+            Response::Start => match first {
+                Some(0x00) if self.command == Some(0x16) => {
+                    self.response = Response::Definitions;
+                    prepare_ok(payload)
+                }
+                Some(0x00) => ok_packet(payload),
+                Some(0xfb) => "LOCAL INFILE request".to_string(),
+                // During the handshake this is an authentication method switch.
+                Some(0xfe) if self.command.is_none() => "auth switch request".to_string(),
+                _ if eof => "EOF".to_string(),
+                _ => match length_encoded(payload) {
+                    Some((0, _)) | None => "packet".to_string(),
+                    Some((columns, _)) => {
+                        self.response = Response::Columns(columns);
+                        format!("result set, {} columns", columns)
+                    }
+                },
+            },
+            Response::Columns(left) => {
+                self.response = if left > 1 {
+                    Response::Columns(left - 1)
+                } else {
+                    Response::ColumnsEnd
+                };
+                column_definition(payload)
+            }
+            Response::ColumnsEnd if eof => {
+                self.response = Response::Rows;
+                "EOF".to_string()
+            }
+            Response::ColumnsEnd | Response::Rows if !eof => {
+                self.response = Response::Rows;
+                "row".to_string()
+            }
+            Response::ColumnsEnd | Response::Rows => {
+                self.response = Response::Start;
+                "end of result set".to_string()
+            }
+            Response::Definitions if eof => "EOF".to_string(),
+            Response::Definitions => column_definition(payload),
+        }
+    }
+}
+
+struct HandshakeResponse {
+    user: Option<String>,
+    summary: String,
+}
+
+fn handshake_response(payload: &[u8]) -> HandshakeResponse {
+    const CLIENT_PROTOCOL_41: u32 = 0x200;
+    const CLIENT_SSL: u32 = 0x800;
+
+    let capabilities = match payload {
+        [a, b, c, d, ..] if u16::from_le_bytes([*a, *b]) as u32 & CLIENT_PROTOCOL_41 != 0 => {
+            u32::from_le_bytes([*a, *b, *c, *d])
+        }
+        [a, b, ..] => u16::from_le_bytes([*a, *b]) as u32,
+        _ => 0,
+    };
+    if capabilities & CLIENT_SSL != 0 && payload.len() == 32 {
+        return HandshakeResponse {
+            user: None,
+            summary: "SSL request".to_string(),
+        };
+    }
+    let start = if capabilities & CLIENT_PROTOCOL_41 != 0 {
+        32
+    } else {
+        5
+    };
+    let user = payload.get(start..).and_then(|rest| {
+        let end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+    });
+    let summary = match &user {
+        Some(user) => format!("handshake response, user {:?}", user),
+        None => "handshake response".to_string(),
+    };
+    HandshakeResponse { user, summary }
+}
+
+fn server_handshake(payload: &[u8]) -> String {
+    match payload.split_first() {
+        Some((0x0a, rest)) => {
+            let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+            let version = String::from_utf8_lossy(&rest[..end]);
+            match rest.get(end + 1..end + 5) {
+                Some(id) => format!(
+                    "handshake, server version {:?}, connection id {}",
+                    version,
+                    u32::from_le_bytes([id[0], id[1], id[2], id[3]])
+                ),
+                None => format!("handshake, server version {:?}", version),
+            }
+        }
+        Some((0xff, _)) => error_packet(payload),
+        _ => "handshake".to_string(),
+    }
+}
+
+fn command_name(command: u8) -> String {
+    let name = match command {
+        0x01 => "COM_QUIT",
+        0x02 => "COM_INIT_DB",
+        0x03 => "COM_QUERY",
+        0x04 => "COM_FIELD_LIST",
+        0x09 => "COM_STATISTICS",
+        0x0e => "COM_PING",
+        0x11 => "COM_CHANGE_USER",
+        0x16 => "COM_STMT_PREPARE",
+        0x17 => "COM_STMT_EXECUTE",
+        0x18 => "COM_STMT_SEND_LONG_DATA",
+        0x19 => "COM_STMT_CLOSE",
+        0x1a => "COM_STMT_RESET",
+        0x1b => "COM_SET_OPTION",
+        0x1c => "COM_STMT_FETCH",
+        0x1f => "COM_RESET_CONNECTION",
+        other => return format!("command 0x{:02x}", other),
+    };
+    name.to_string()
+}
+
+fn ok_packet(payload: &[u8]) -> String {
+    let Some((affected, used)) = payload.get(1..).and_then(length_encoded) else {
+        return "OK".to_string();
+    };
+    match payload.get(1 + used..).and_then(length_encoded) {
+        Some((last_id, _)) => format!("OK, {} rows affected, last insert id {}", affected, last_id),
+        None => format!("OK, {} rows affected", affected),
+    }
+}
+
+fn prepare_ok(payload: &[u8]) -> String {
+    match payload {
+        [_, a, b, c, d, e, f, g, h, ..] => format!(
+            "prepared statement {}, {} columns, {} parameters",
+            u32::from_le_bytes([*a, *b, *c, *d]),
+            u16::from_le_bytes([*e, *f]),
+            u16::from_le_bytes([*g, *h])
+        ),
+        _ => "prepare OK".to_string(),
+    }
+}
+
+fn error_packet(payload: &[u8]) -> String {
+    let Some(code) = payload.get(1..3) else {
+        return "ERR".to_string();
+    };
+    let code = u16::from_le_bytes([code[0], code[1]]);
+    let message = match payload.get(3..) {
+        Some([b'#', state @ ..]) if state.len() >= 5 => format!(
+            "({}) {}",
+            String::from_utf8_lossy(&state[..5]),
+            String::from_utf8_lossy(&state[5..])
+        ),
+        Some(message) => String::from_utf8_lossy(message).into_owned(),
+        None => String::new(),
+    };
+    format!("ERR {} {}", code, message)
+}
+
+// catalog, schema, table, org_table, name, ...
+fn column_definition(payload: &[u8]) -> String {
+    let mut rest = payload;
+    for _ in 0..4 {
+        match length_encoded_bytes(rest) {
+            Some((_, after)) => rest = after,
+            None => return "column definition".to_string(),
+        }
+    }
+    match length_encoded_bytes(rest) {
+        Some((name, _)) => format!("column definition {:?}", String::from_utf8_lossy(name)),
+        None => "column definition".to_string(),
+    }
+}
+
+// A length-encoded integer and how many bytes it took.
+fn length_encoded(data: &[u8]) -> Option<(u64, usize)> {
+    let (&first, rest) = data.split_first()?;
+    let width = match first {
+        0..=0xfa => return Some((first as u64, 1)),
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => return None,
+    };
+    let bytes = rest.get(..width)?;
+    let mut value = [0; 8];
+    value[..width].copy_from_slice(bytes);
+    Some((u64::from_le_bytes(value), 1 + width))
+}
+
+fn length_encoded_bytes(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (length, used) = length_encoded(data)?;
+    let rest = &data[used..];
+    let length = usize::try_from(length).ok().filter(|&l| l <= rest.len())?;
+    Some(rest.split_at(length))
+}
+
+fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let text: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "    {:04x}  {:<48} {}\n",
+            line * 16,
+            hex.join(" "),
+            text
+        ));
+    }
+    out
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+/// A connection's read or write half, recording what passes through it when traced.
+pub struct Traced<S> {
+    inner: S,
+    trace: Option<Arc<ConnectionTrace>>,
+}
+
+impl<S> Traced<S> {
+    pub fn new(inner: S, trace: Option<Arc<ConnectionTrace>>) -> Traced<S> {
+        Traced { inner, trace }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Traced<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(trace)) = (&poll, &self.trace) {
+            trace.record(Direction::Client, &buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Traced<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(written)), Some(trace)) = (&poll, &self.trace) {
+            trace.record(Direction::Server, &buf[..*written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,270 @@
+// Transaction characteristics, the access mode (READ ONLY, READ WRITE) and isolation level that
+// SET TRANSACTION and START TRANSACTION give. PostgreSQL has them too, but sets them otherwise:
+//
+//   SET SESSION TRANSACTION READ ONLY  ->  SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY
+//   SET TRANSACTION READ ONLY          ->  (kept for the next transaction)
+//   START TRANSACTION                  ->  START TRANSACTION READ ONLY
+//
+// MySQL's SET TRANSACTION, without SESSION, is for the next transaction only, where
+// PostgreSQL's is for the one in progress; so the proxy keeps the characteristics and starts
+// the next transaction with them. A statement run outside a transaction is a transaction of its
+// own, and takes them instead: after SET TRANSACTION READ ONLY, the next statement is refused
+// with error 1792 if it writes, as it is in a read-only transaction, PostgreSQL's refusal
+// getting the same error. SET TRANSACTION in a transaction fails with error 1568, as in MySQL,
+// and SET GLOBAL TRANSACTION isn't supported. START TRANSACTION WITH CONSISTENT SNAPSHOT starts
+// a REPEATABLE READ transaction, whose snapshot PostgreSQL takes at its first statement.
+//
+// SELECT @@transaction_read_only (or @@tx_read_only, @@session.transaction_read_only) reports
+// the access mode of the transaction in progress, or else of the next one: 1 if read-only, 0
+// if not. QueryInterceptors see it as well, in Context::read_only, to route reads.
+
+use opensrv_mysql::ErrorKind;
+
+use crate::error::MysqlError;
+use crate::system_variables;
+use crate::translator::{self, Token};
+
+/// The characteristics a transaction is started with; `None` leaves one as it would be.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Characteristics {
+    // REPEATABLE READ, READ COMMITTED, ...
+    pub isolation: Option<String>,
+    pub read_only: Option<bool>,
+}
+
+impl Characteristics {
+    // The characteristics of a comma-separated list of them.
+    fn parse(tokens: &[Token]) -> Option<Characteristics> {
+        let mut characteristics = Characteristics::default();
+        for item in tokens.split(|token| *token == Token::Comma) {
+            let words: Vec<String> = item
+                .iter()
+                .map(|token| match token {
+                    Token::Word(word) => Some(word.to_ascii_uppercase()),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            let words: Vec<&str> = words.iter().map(String::as_str).collect();
+            match words.as_slice() {
+                ["READ", "ONLY"] => characteristics.read_only = Some(true),
+                ["READ", "WRITE"] => characteristics.read_only = Some(false),
+                ["ISOLATION", "LEVEL", level @ ..]
+                    if matches!(
+                        level,
+                        ["REPEATABLE", "READ"]
+                            | ["READ", "COMMITTED"]
+                            | ["READ", "UNCOMMITTED"]
+                            | ["SERIALIZABLE"]
+                    ) =>
+                {
+                    characteristics.isolation = Some(level.join(" "))
+                }
+                ["WITH", "CONSISTENT", "SNAPSHOT"] => {
+                    characteristics.isolation = Some("REPEATABLE READ".to_string())
+                }
+                _ => return None,
+            }
+        }
+        Some(characteristics)
+    }
+
+    // These, with those of `other` these leave as they would be.
+    fn or(self, other: &Characteristics) -> Characteristics {
+        Characteristics {
+            isolation: self.isolation.or_else(|| other.isolation.clone()),
+            read_only: self.read_only.or(other.read_only),
+        }
+    }
+
+    // As PostgreSQL lists them after START TRANSACTION: ` ISOLATION LEVEL ..., READ ONLY`.
+    fn sql(&self) -> String {
+        let mut modes = Vec::new();
+        if let Some(isolation) = &self.isolation {
+            modes.push(format!("ISOLATION LEVEL {}", isolation));
+        }
+        match self.read_only {
+            Some(true) => modes.push("READ ONLY".to_string()),
+            Some(false) => modes.push("READ WRITE".to_string()),
+            None => {}
+        }
+        match modes.is_empty() {
+            true => String::new(),
+            false => format!(" {}", modes.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    // SET TRANSACTION
+    Next,
+    // SET SESSION TRANSACTION
+    Session,
+    // SET GLOBAL TRANSACTION
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Set(Scope, Characteristics),
+    // START TRANSACTION or BEGIN [WORK].
+    Start(Characteristics),
+}
+
+/// The SET TRANSACTION or START TRANSACTION `sql` is, if it is one.
+pub fn parse(sql: &str) -> Option<Statement> {
+    let tokens = translator::significant_tokens(sql)?;
+    let tokens = match tokens.as_slice() {
+        [rest @ .., Token::Semicolon] => rest,
+        tokens => tokens,
+    };
+    match tokens {
+        [begin] if begin.is_word("BEGIN") => Some(Statement::Start(Characteristics::default())),
+        [begin, work] if begin.is_word("BEGIN") && work.is_word("WORK") => {
+            Some(Statement::Start(Characteristics::default()))
+        }
+        [start, transaction, rest @ ..]
+            if start.is_word("START") && transaction.is_word("TRANSACTION") =>
+        {
+            match rest {
+                [] => Some(Statement::Start(Characteristics::default())),
+                rest => Characteristics::parse(rest).map(Statement::Start),
+            }
+        }
+        [set, rest @ ..] if set.is_word("SET") => {
+            let (scope, rest) = match rest {
+                [scope, rest @ ..] if scope.is_word("GLOBAL") => (Scope::Global, rest),
+                [scope, rest @ ..] if scope.is_word("SESSION") || scope.is_word("LOCAL") => {
+                    (Scope::Session, rest)
+                }
+                rest => (Scope::Next, rest),
+            };
+            match rest {
+                [transaction, rest @ ..] if transaction.is_word("TRANSACTION") => {
+                    let characteristics = Characteristics::parse(rest)?;
+                    Some(Statement::Set(scope, characteristics))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A connection's transaction characteristics.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionModes {
+    // SET SESSION TRANSACTION's, for every transaction.
+    session: Characteristics,
+    // SET TRANSACTION's, for the next one only.
+    next: Option<Characteristics>,
+    // Whether the transaction in progress, if there is one, is read-only.
+    current: bool,
+}
+
+impl TransactionModes {
+    /// Applies SET TRANSACTION, given whether a transaction is in progress. Returns the
+    /// statement setting the session's characteristics on PostgreSQL, if there is one to run.
+    pub fn set(
+        &mut self,
+        scope: Scope,
+        characteristics: Characteristics,
+        in_transaction: bool,
+    ) -> Result<Option<String>, MysqlError> {
+        match scope {
+            Scope::Global => Err(MysqlError::new(
+                ErrorKind::ER_NOT_SUPPORTED_YET,
+                "This version of MySQL doesn't yet support 'SET GLOBAL TRANSACTION'",
+            )),
+            Scope::Next if in_transaction => Err(MysqlError::new(
+                ErrorKind::ER_CANT_CHANGE_TX_ISOLATION,
+                "Transaction characteristics can't be changed while a transaction is in progress",
+            )),
+            Scope::Next => {
+                self.next = Some(characteristics);
+                Ok(None)
+            }
+            Scope::Session => {
+                let sql = format!(
+                    "SET SESSION CHARACTERISTICS AS TRANSACTION{}",
+                    characteristics.sql()
+                );
+                self.session = characteristics.or(&self.session);
+                Ok(Some(sql))
+            }
+        }
+    }
+
+    /// The statement starting a transaction with `characteristics`, and those SET TRANSACTION
+    /// left for it.
+    pub fn start(&mut self, characteristics: Characteristics) -> String {
+        let characteristics = match self.next.take() {
+            Some(next) => characteristics.or(&next),
+            None => characteristics,
+        };
+        self.current = characteristics
+            .read_only
+            .or(self.session.read_only)
+            .unwrap_or(false);
+        format!("START TRANSACTION{}", characteristics.sql())
+    }
+
+    /// Checks `sql`, a statement run outside a transaction and so one of its own, against the
+    /// characteristics SET TRANSACTION left for it, which it uses up.
+    pub fn autocommit(&mut self, sql: &str) -> Result<(), MysqlError> {
+        match self.next.take() {
+            Some(next) if next.read_only == Some(true) && crate::policy::writes_data(sql) => {
+                Err(read_only_transaction())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether statements run read-only: those of the transaction in progress, if
+    /// `in_transaction`, or else the next.
+    pub fn read_only(&self, in_transaction: bool) -> bool {
+        if in_transaction {
+            return self.current;
+        }
+        self.next
+            .as_ref()
+            .and_then(|next| next.read_only)
+            .or(self.session.read_only)
+            .unwrap_or(false)
+    }
+
+    /// The statement setting the session's characteristics on a new PostgreSQL session, if any
+    /// were set.
+    pub fn session_sql(&self) -> Option<String> {
+        (self.session != Characteristics::default()).then(|| {
+            format!(
+                "SET SESSION CHARACTERISTICS AS TRANSACTION{}",
+                self.session.sql()
+            )
+        })
+    }
+}
+
+/// MySQL's refusal of a write in a read-only transaction.
+pub fn read_only_transaction() -> MysqlError {
+    MysqlError::new(
+        ErrorKind::ER_CANT_EXECUTE_IN_READ_ONLY_TRANSACTION,
+        "Cannot execute statement in a READ ONLY transaction.",
+    )
+}
+
+// The names @@transaction_read_only goes by.
+const READ_ONLY_VARIABLES: &[&str] = &["transaction_read_only", "tx_read_only"];
+
+/// The translated statement `sql` with @@transaction_read_only as `read_only` has it, 1 or 0.
+/// `None` if it doesn't use it.
+pub fn variables(sql: &str, read_only: bool) -> Option<String> {
+    if !sql.to_ascii_lowercase().contains("read_only") {
+        return None;
+    }
+    system_variables::substitute(sql, &|name| {
+        READ_ONLY_VARIABLES
+            .contains(&name)
+            .then(|| Token::Number(if read_only { "1" } else { "0" }.to_string()))
+    })
+}
@@ -0,0 +1,119 @@
+// How the MySQL clients' connections are accepted and their bytes moved: plain tokio TCP by
+// default, or with the io-uring feature on Linux, io_uring (LISTEN_TRANSPORT = io-uring), which
+// takes fewer system calls per packet under many small queries.
+//
+// A Server binds its listener through a Transport, and serves whatever reader and writer the
+// Listener gives it for each client, so a program embedding the proxy can hand it connections
+// of its own with ServerBuilder::transport.
+
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+use crate::logging::{LogFormat, Logger};
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+
+/// Which Transport serves the clients (LISTEN_TRANSPORT).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    #[default]
+    Tcp,
+    IoUring,
+}
+
+impl std::str::FromStr for TransportKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<TransportKind, ()> {
+        match s.to_ascii_lowercase().as_str() {
+            "tcp" => Ok(TransportKind::Tcp),
+            "io-uring" | "io_uring" => Ok(TransportKind::IoUring),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A client's connection, as a Listener accepted it.
+pub struct Connection {
+    pub reader: Box<dyn AsyncRead + Send + Unpin>,
+    pub writer: Box<dyn AsyncWrite + Send + Unpin>,
+    // The address SHOW PROCESSLIST gives for the client.
+    pub peer: SocketAddr,
+}
+
+/// Listens for MySQL clients.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn bind(&self, addr: &str) -> std::io::Result<Box<dyn Listener>>;
+}
+
+/// A bound listener, from Transport::bind.
+#[async_trait]
+pub trait Listener: Send {
+    async fn accept(&mut self) -> std::io::Result<Connection>;
+}
+
+/// tokio's TcpListener.
+pub struct Tcp {
+    // Where a socket option that can't be set is reported.
+    log: Logger,
+}
+
+impl Default for Tcp {
+    fn default() -> Self {
+        Tcp {
+            log: Logger::new(LogFormat::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for Tcp {
+    async fn bind(&self, addr: &str) -> std::io::Result<Box<dyn Listener>> {
+        Ok(Box::new(TcpAccept {
+            listener: TcpListener::bind(addr).await?,
+            log: self.log,
+        }))
+    }
+}
+
+struct TcpAccept {
+    listener: TcpListener,
+    log: Logger,
+}
+
+#[async_trait]
+impl Listener for TcpAccept {
+    async fn accept(&mut self) -> std::io::Result<Connection> {
+        let (stream, peer) = self.listener.accept().await?;
+        // Replies are written in several small packets; without this every request that waits
+        // on one stalls for the client's delayed ACK.
+        if let Err(e) = stream.set_nodelay(true) {
+            self.log
+                .error(format_args!("Failed to set TCP_NODELAY: {}", e));
+        }
+        let (reader, writer) = stream.into_split();
+        Ok(Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            peer,
+        })
+    }
+}
+
+/// The Transport LISTEN_TRANSPORT names, if this build has it, reporting its errors in `log`.
+pub(crate) fn of_kind(kind: TransportKind, log: Logger) -> Result<Box<dyn Transport>, String> {
+    match kind {
+        TransportKind::Tcp => Ok(Box::new(Tcp { log })),
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        TransportKind::IoUring => Ok(Box::new(uring::Uring { log })),
+        #[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+        TransportKind::IoUring => Err("LISTEN_TRANSPORT is io-uring, but this build has no \
+                                       io_uring support; build with --features io-uring on Linux"
+            .to_string()),
+    }
+}
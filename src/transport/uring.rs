@@ -0,0 +1,146 @@
+// LISTEN_TRANSPORT = io-uring: the clients' sockets are accepted, read and written with
+// io_uring by tokio-uring.
+//
+// tokio-uring needs a runtime of its own, on one thread, and its sockets can't be shared with
+// other threads. So that thread does all the socket I/O, and each connection's bytes are passed
+// to the Backend, on the main runtime, through an in-memory pipe:
+//
+//   client socket  <-- io_uring -->  uring thread  <-- duplex pipe -->  serve_connection
+
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, oneshot};
+use tokio_uring::buf::BoundedBuf;
+use tokio_uring::net::{TcpListener, TcpStream};
+
+use super::{Connection, Listener, Transport};
+use crate::logging::Logger;
+
+// The size of each pipe's buffer, and of the reads on either side of it.
+const BUFFER: usize = 64 * 1024;
+// Connections accepted but not yet taken by the server.
+const BACKLOG: usize = 128;
+
+pub struct Uring {
+    // Where a connection that can't be accepted or set up is reported.
+    pub(super) log: Logger,
+}
+
+#[async_trait]
+impl Transport for Uring {
+    async fn bind(&self, addr: &str) -> io::Result<Box<dyn Listener>> {
+        let addr = tokio::net::lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("{} has no address", addr)))?;
+        let (accepted, connections) = mpsc::channel(BACKLOG);
+        let (bound, bind_result) = oneshot::channel();
+        let log = self.log;
+        std::thread::Builder::new()
+            .name("postmyrustache-uring".to_string())
+            .spawn(move || tokio_uring::start(accept(addr, bound, accepted, log)))?;
+        bind_result
+            .await
+            .map_err(|_| io::Error::other("the io_uring thread exited"))??;
+        Ok(Box::new(UringListener(connections)))
+    }
+}
+
+struct UringListener(mpsc::Receiver<(DuplexStream, SocketAddr)>);
+
+#[async_trait]
+impl Listener for UringListener {
+    async fn accept(&mut self) -> io::Result<Connection> {
+        let (pipe, peer) = self
+            .0
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::other("the io_uring thread exited"))?;
+        let (reader, writer) = tokio::io::split(pipe);
+        Ok(Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            peer,
+        })
+    }
+}
+
+// Runs on the uring thread: binds `addr`, says how that went on `bound`, then hands each client
+// to the server as the other end of its pipe, until the server stops taking them.
+async fn accept(
+    addr: SocketAddr,
+    bound: oneshot::Sender<io::Result<()>>,
+    accepted: mpsc::Sender<(DuplexStream, SocketAddr)>,
+    log: Logger,
+) {
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = bound.send(Err(e));
+            return;
+        }
+    };
+    let _ = bound.send(Ok(()));
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            // Most likely out of file descriptors; carries on once some are freed.
+            Err(e) => {
+                log.error(format_args!("Failed to accept a connection: {}", e));
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            log.error(format_args!("Failed to set TCP_NODELAY: {}", e));
+        }
+        let (ours, theirs) = tokio::io::duplex(BUFFER);
+        if accepted.send((theirs, peer)).await.is_err() {
+            return;
+        }
+        let stream = Rc::new(stream);
+        let (from_server, to_server) = tokio::io::split(ours);
+        tokio_uring::spawn(receive(Rc::clone(&stream), to_server));
+        tokio_uring::spawn(send(stream, from_server));
+    }
+}
+
+// Client to server: what the socket reads goes into the pipe, until either end closes.
+async fn receive(stream: Rc<TcpStream>, mut pipe: WriteHalf<DuplexStream>) {
+    let mut buffer = vec![0; BUFFER];
+    loop {
+        let (read, returned) = stream.read(buffer).await;
+        buffer = returned;
+        match read {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if pipe.write_all(&buffer[..n]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = pipe.shutdown().await;
+}
+
+// Server to client: what the pipe gives is written to the socket, which is shut down once the
+// server is done with the connection.
+async fn send(stream: Rc<TcpStream>, mut pipe: ReadHalf<DuplexStream>) {
+    let mut buffer = vec![0; BUFFER];
+    loop {
+        let n = match pipe.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let (written, returned) = stream.write_all(buffer.slice(..n)).await;
+        buffer = returned.into_inner();
+        if written.is_err() {
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
@@ -0,0 +1,116 @@
+// A compressing tunnel for the PostgreSQL sessions, for when the proxy and PostgreSQL are in
+// different regions and large result sets cost more to move than to compress. PostgreSQL has
+// no compression of its own any more (TLS compression went in PostgreSQL 14), so this program
+// is at both ends of the link: `postmyrustache tunnel` runs next to PostgreSQL, listening on
+// TUNNEL_LISTEN_ADDR and relaying each connection to TUNNEL_TARGET (localhost:5432 by default),
+// and the proxy, with DB_TUNNEL set to the tunnel's address, opens its sessions through it
+// rather than with DB_HOST:
+//
+//   next to PostgreSQL:  TUNNEL_LISTEN_ADDR=0.0.0.0:6543 postmyrustache tunnel
+//   the proxy:           DB_TUNNEL=pg-eu.internal:6543 postmyrustache
+//
+// Each tunnel serves the one PostgreSQL server it targets; a proxy in front of another runs its
+// own. Both directions are zlib streams, flushed whenever the sender has nothing more to send
+// for now, so a reply is never held back for more to compress.
+//
+// The tunnel doesn't encrypt, and the sessions through it can't be encrypted either, as TLS
+// inside the stream would leave nothing to compress: DB_SSLMODE = prefer goes without TLS
+// through the tunnel, and require and verify-full can't be used with it. The tunnel should be
+// reachable only over a private network or a VPN, as PostgreSQL's port would be. Both ends need
+// a build with the `compression` feature.
+
+use std::io;
+
+use crate::logging::LogFormat;
+#[cfg(feature = "compression")]
+use crate::logging::Logger;
+use crate::runtime::RuntimeConfig;
+
+pub const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:6543";
+pub const DEFAULT_TARGET: &str = "localhost:5432";
+
+/// Where `postmyrustache tunnel` listens, and the PostgreSQL it relays to (TUNNEL_LISTEN_ADDR,
+/// TUNNEL_TARGET). The runtime and LOG_FORMAT are set up as the proxy's are.
+#[derive(Debug, Clone)]
+pub struct TunnelConfig {
+    pub listen_addr: String,
+    pub target: String,
+    pub runtime: RuntimeConfig,
+    pub log_format: LogFormat,
+}
+
+/// Relays the connections of proxies with DB_TUNNEL set to the target, until the process is
+/// stopped.
+#[cfg(feature = "compression")]
+pub async fn serve(config: &TunnelConfig) -> io::Result<()> {
+    use tokio::net::TcpListener;
+
+    let log = Logger::new(config.log_format);
+    let listener = TcpListener::bind(&config.listen_addr).await?;
+    log.info(format_args!(
+        "Tunnel is running on {}, relaying to {}",
+        config.listen_addr, config.target
+    ));
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log.error(format_args!("Failed to accept a connection: {}", e));
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let target = config.target.clone();
+        tokio::spawn(async move {
+            if let Err(e) = relay(socket, &target).await {
+                log.info(format_args!("Tunnel from {} closed: {}", peer, e));
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+pub async fn serve(_config: &TunnelConfig) -> io::Result<()> {
+    Err(io::Error::other(
+        "this build has no compression; build with --features compression",
+    ))
+}
+
+#[cfg(feature = "compression")]
+async fn relay(socket: tokio::net::TcpStream, target: &str) -> io::Result<()> {
+    let mut upstream = tokio::net::TcpStream::connect(target).await?;
+    upstream.set_nodelay(true)?;
+    socket.set_nodelay(true)?;
+    let mut compressed = stream::compressed(socket);
+    tokio::io::copy_bidirectional(&mut compressed, &mut upstream).await?;
+    Ok(())
+}
+
+/// Connects to the tunnel at `addr`, for a session to be opened through.
+#[cfg(feature = "compression")]
+pub(crate) async fn connect(addr: &str) -> io::Result<stream::Compressed<tokio::net::TcpStream>> {
+    let socket = tokio::net::TcpStream::connect(addr).await?;
+    socket.set_nodelay(true)?;
+    Ok(stream::compressed(socket))
+}
+
+#[cfg(feature = "compression")]
+pub(crate) mod stream {
+    use async_compression::tokio::bufread::ZlibDecoder;
+    use async_compression::tokio::write::ZlibEncoder;
+    use tokio::io::{AsyncRead, AsyncWrite, BufReader, Join, ReadHalf, WriteHalf};
+
+    /// A stream compressed both ways: what is written is compressed, what is read decompressed.
+    pub type Compressed<S> = Join<ZlibDecoder<BufReader<ReadHalf<S>>>, ZlibEncoder<WriteHalf<S>>>;
+
+    // tokio's copy, which the relay uses, and tokio-postgres both flush once they have nothing
+    // more to write, and the encoder flushes with a zlib sync flush, so whatever was written
+    // reaches the other end whole.
+    pub fn compressed<S: AsyncRead + AsyncWrite>(stream: S) -> Compressed<S> {
+        let (reader, writer) = tokio::io::split(stream);
+        tokio::io::join(
+            ZlibDecoder::new(BufReader::new(reader)),
+            ZlibEncoder::new(writer),
+        )
+    }
+}
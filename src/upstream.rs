@@ -0,0 +1,468 @@
+// Preparing translated statements for PostgreSQL and reading back the types it returns.
+
+use std::error::Error;
+use std::io;
+
+use bytes::{BufMut, BytesMut};
+use chrono::DateTime;
+use mysql_common::value::Value;
+use opensrv_mysql::{Column, ColumnFlags, ColumnType, ValueInner};
+use tokio_postgres::types::{to_sql_checked, Format, FromSql, IsNull, ToSql, Type};
+use tokio_postgres::{Client, Row, Statement};
+
+use crate::charset::Charset;
+use crate::logging::Logger;
+use crate::statement_cache::StatementCache;
+use crate::translator::{self, parameters, statement_starts_with, Node, Token, ZeroDates};
+
+// PostgreSQL's dates and timestamps count from 2000-01-01, 946684800 seconds after 1970's.
+const PG_EPOCH_SECONDS: i64 = 946_684_800;
+
+/// A bind parameter sent in PostgreSQL's text format, so the server parses it with the input
+/// function of whatever type it inferred for the placeholder, exactly as it would the literal.
+#[derive(Debug)]
+pub struct TextParam(pub String);
+
+impl ToSql for TextParam {
+    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        out.put_slice(self.0.as_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    fn encode_format(&self, _: &Type) -> Format {
+        Format::Text
+    }
+
+    to_sql_checked!();
+}
+
+/// A json or jsonb value as its text, which is how MySQL sends JSON columns.
+#[derive(Debug)]
+pub struct JsonText(pub String);
+
+impl<'a> FromSql<'a> for JsonText {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        // jsonb's binary format is a version byte followed by the text.
+        let text = match (ty, raw.split_first()) {
+            (&Type::JSONB, Some((1, text))) => text,
+            (&Type::JSONB, _) => return Err("unsupported jsonb version".into()),
+            _ => raw,
+        };
+        Ok(JsonText(std::str::from_utf8(text)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::JSON | Type::JSONB)
+    }
+}
+
+// Whether a value is NULL, whatever its type.
+struct Null(bool);
+
+impl<'a> FromSql<'a> for Null {
+    fn from_sql(_: &Type, _: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(Null(false))
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(Null(true))
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
+/// A date or timestamp as MySQL writes it, `2024-01-02` or `2024-01-02 03:04:05.678900`.
+#[derive(Debug)]
+pub struct DateText(pub String);
+
+impl<'a> FromSql<'a> for DateText {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        // Days and microseconds since PostgreSQL's epoch, the largest and smallest being infinity.
+        let text = match *ty {
+            Type::DATE => match i32::from_be_bytes(raw.try_into()?) {
+                i32::MAX => "infinity".to_string(),
+                i32::MIN => "-infinity".to_string(),
+                days => DateTime::from_timestamp(PG_EPOCH_SECONDS + i64::from(days) * 86_400, 0)
+                    .ok_or("date out of range")?
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            },
+            _ => match i64::from_be_bytes(raw.try_into()?) {
+                i64::MAX => "infinity".to_string(),
+                i64::MIN => "-infinity".to_string(),
+                micros => {
+                    let timestamp = micros
+                        .checked_add(PG_EPOCH_SECONDS * 1_000_000)
+                        .and_then(DateTime::from_timestamp_micros)
+                        .ok_or("timestamp out of range")?
+                        .naive_utc();
+                    match micros % 1_000_000 {
+                        0 => timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        _ => timestamp.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+                    }
+                }
+            },
+        };
+        Ok(DateText(text))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::DATE | Type::TIMESTAMP)
+    }
+}
+
+/// The MySQL column definition for a PostgreSQL column or parameter of type `ty`. Clients of the
+/// binary protocol decode values by these types, so they have to match what is sent.
+pub fn column(name: &str, ty: &Type) -> Column {
+    let (coltype, colflags) = match *ty {
+        Type::INT2 => (ColumnType::MYSQL_TYPE_SHORT, ColumnFlags::empty()),
+        Type::INT4 => (ColumnType::MYSQL_TYPE_LONG, ColumnFlags::empty()),
+        Type::INT8 => (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::empty()),
+        Type::FLOAT4 => (ColumnType::MYSQL_TYPE_FLOAT, ColumnFlags::empty()),
+        Type::FLOAT8 => (ColumnType::MYSQL_TYPE_DOUBLE, ColumnFlags::empty()),
+        Type::JSON | Type::JSONB => (ColumnType::MYSQL_TYPE_JSON, ColumnFlags::empty()),
+        Type::BYTEA => (ColumnType::MYSQL_TYPE_BLOB, ColumnFlags::BINARY_FLAG),
+        _ => (ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty()),
+    };
+    Column {
+        table: String::new(),
+        column: name.to_string(),
+        coltype,
+        colflags,
+    }
+}
+
+/// The value of column `i` of `row`, as it is sent to the client, with the zero dates of
+/// `zero_dates` (see the translator's dates.rs) in place of the sentinel or NULL.
+pub fn value(row: &Row, i: usize, zero_dates: ZeroDates) -> io::Result<Value> {
+    let ty = row.columns()[i].type_();
+    // The dates go their own way, ZERO_DATES sending some NULLs as zero dates.
+    if !matches!(*ty, Type::DATE | Type::TIMESTAMP) && row.get::<_, Null>(i).0 {
+        return Ok(Value::NULL);
+    }
+    let value = match *ty {
+        Type::INT4 => {
+            let value: i32 = row.get(i);
+            Value::Int(value.into())
+        }
+        Type::INT2 => {
+            let value: i16 = row.get(i);
+            Value::Int(value.into())
+        }
+        Type::INT8 => {
+            let value: i64 = row.get(i);
+            Value::Int(value)
+        }
+        Type::VARCHAR | Type::TEXT | Type::BPCHAR | Type::NAME => {
+            let value: String = row.get(i);
+            Value::Bytes(value.into_bytes())
+        }
+        Type::JSON | Type::JSONB => {
+            let value: JsonText = row.get(i);
+            Value::Bytes(value.0.into_bytes())
+        }
+        Type::BYTEA => {
+            let value: Vec<u8> = row.get(i);
+            Value::Bytes(value)
+        }
+        Type::BOOL => {
+            let value: bool = row.get(i);
+            Value::Bytes(value.to_string().into_bytes())
+        }
+        Type::FLOAT4 => {
+            let value: f32 = row.get(i);
+            Value::Float(value)
+        }
+        Type::FLOAT8 => {
+            let value: f64 = row.get(i);
+            Value::Double(value)
+        }
+        Type::DATE | Type::TIMESTAMP => {
+            let value: Option<DateText> = row.get(i);
+            date_value(value.map(|DateText(text)| text), ty, zero_dates)
+        }
+        // Add more match arms for other types as needed
+        _ => return Err(io::Error::other("Unsupported type")),
+    };
+    Ok(value)
+}
+
+// A DATE or TIMESTAMP, with the zero date in place of NULL or the sentinel per ZERO_DATES.
+fn date_value(text: Option<String>, ty: &Type, zero_dates: ZeroDates) -> Value {
+    let zero = match *ty {
+        Type::DATE => "0000-00-00",
+        _ => "0000-00-00 00:00:00",
+    };
+    match (text, zero_dates) {
+        (None, ZeroDates::NullAsZero) => Value::Bytes(zero.into()),
+        (None, _) => Value::NULL,
+        // With the time of day it was stored with.
+        (Some(text), ZeroDates::Sentinel(sentinel))
+            if text.starts_with(&sentinel.format("%Y-%m-%d").to_string()) =>
+        {
+            Value::Bytes(format!("0000-00-00{}", &text[10..]).into_bytes())
+        }
+        (Some(text), _) => Value::Bytes(text.into_bytes()),
+    }
+}
+
+/// The text of a parameter a client sent with COM_STMT_EXECUTE, in character set `client`, to
+/// bind as a `TextParam`. `None` for NULL.
+pub fn param_text(value: ValueInner<'_>, client: Charset) -> io::Result<Option<String>> {
+    let text = match value {
+        ValueInner::NULL => return Ok(None),
+        ValueInner::Bytes(bytes) => String::from_utf8(client.decode(bytes).into_owned())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        ValueInner::Int(n) => n.to_string(),
+        ValueInner::UInt(n) => n.to_string(),
+        ValueInner::Double(n) => n.to_string(),
+        ValueInner::Date(bytes) | ValueInner::Datetime(bytes) => datetime_text(bytes)?,
+        ValueInner::Time(bytes) => time_text(bytes)?,
+    };
+    Ok(Some(text))
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed {} parameter", what),
+    )
+}
+
+// Binary DATE/DATETIME: length 0, 4 (date), 7 (and time) or 11 (and microseconds).
+fn datetime_text(b: &[u8]) -> io::Result<String> {
+    if b.is_empty() {
+        return Ok("0000-00-00".to_string());
+    }
+    if !matches!(b.len(), 4 | 7 | 11) {
+        return Err(invalid("datetime"));
+    }
+    let mut text = format!(
+        "{:04}-{:02}-{:02}",
+        u16::from_le_bytes([b[0], b[1]]),
+        b[2],
+        b[3]
+    );
+    if b.len() >= 7 {
+        text.push_str(&format!(" {:02}:{:02}:{:02}", b[4], b[5], b[6]));
+    }
+    if b.len() == 11 {
+        text.push_str(&format!(
+            ".{:06}",
+            u32::from_le_bytes([b[7], b[8], b[9], b[10]])
+        ));
+    }
+    Ok(text)
+}
+
+// Binary TIME: length 0, 8 (sign, days, time) or 12 (and microseconds).
+fn time_text(b: &[u8]) -> io::Result<String> {
+    if b.is_empty() {
+        return Ok("00:00:00".to_string());
+    }
+    if !matches!(b.len(), 8 | 12) {
+        return Err(invalid("time"));
+    }
+    let days = u32::from_le_bytes([b[1], b[2], b[3], b[4]]);
+    let mut text = format!(
+        "{}{:02}:{:02}:{:02}",
+        if b[0] == 1 { "-" } else { "" },
+        days * 24 + u32::from(b[5]),
+        b[6],
+        b[7]
+    );
+    if b.len() == 12 {
+        text.push_str(&format!(
+            ".{:06}",
+            u32::from_le_bytes([b[8], b[9], b[10], b[11]])
+        ));
+    }
+    Ok(text)
+}
+
+/// Whether running `sql` leaves the session in a transaction block: `Some(true)` for BEGIN and
+/// START TRANSACTION, `Some(false)` for COMMIT and ROLLBACK, and `None` for statements that
+/// don't start or end one. COMMIT AND CHAIN starts the next transaction straight away, and
+/// ROLLBACK TO SAVEPOINT stays in the one it is in.
+pub fn transaction_change(sql: &str) -> Option<bool> {
+    let tokens = translator::significant_tokens(sql)?;
+    let (first, rest) = tokens.split_first()?;
+    if (first.is_word("BEGIN") && !translator::is_compound_statement(&tokens))
+        || (first.is_word("START") && rest.first().is_some_and(|t| t.is_word("TRANSACTION")))
+    {
+        return Some(true);
+    }
+    if !(first.is_word("COMMIT") || first.is_word("ROLLBACK"))
+        || rest.iter().any(|t| t.is_word("TO"))
+    {
+        return None;
+    }
+    let chain = rest
+        .windows(2)
+        .any(|pair| pair[0].is_word("AND") && pair[1].is_word("CHAIN"));
+    Some(chain)
+}
+
+/// The info string MySQL puts in the OK packet of `sql`, which changed `row_count` rows with
+/// `warnings` warnings; the mysql client prints it under the statement:
+///
+///   UPDATE                              Rows matched: 3  Changed: 3  Warnings: 0
+///   INSERT of several rows, or          Records: 3  Duplicates: 1  Warnings: 0
+///   INSERT ... SELECT
+///
+/// PostgreSQL writes every row an UPDATE matches, even one set to the values it had, so every
+/// matched row counts as changed. The duplicates of an INSERT are the rows it was given but didn't
+/// add. Other statements have no info string.
+pub fn ok_info(sql: &str, row_count: u64, warnings: u16) -> String {
+    let Ok(nodes) = translator::parse(sql) else {
+        return String::new();
+    };
+    if statement_starts_with(&nodes, &["UPDATE"]) {
+        return format!(
+            "Rows matched: {}  Changed: {}  Warnings: {}",
+            row_count, row_count, warnings
+        );
+    }
+    if !statement_starts_with(&nodes, &["INSERT"]) {
+        return String::new();
+    }
+    let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+    let is_word = |node: &Node, word: &str| matches!(node, Node::Token(t) if t.is_word(word));
+    let records = match significant
+        .iter()
+        .position(|n| is_word(n, "VALUES") || is_word(n, "VALUE"))
+    {
+        // The row constructors of the VALUES list, with or without ROW before each.
+        Some(at) => {
+            let rows = significant[at + 1..]
+                .iter()
+                .take_while(|n| {
+                    matches!(n, Node::Group(_) | Node::Token(Token::Comma)) || is_word(n, "ROW")
+                })
+                .filter(|n| matches!(n, Node::Group(_)))
+                .count() as u64;
+            if rows < 2 {
+                return String::new();
+            }
+            rows
+        }
+        None if significant.iter().any(|n| is_word(n, "SELECT")) => row_count,
+        None => return String::new(),
+    };
+    format!(
+        "Records: {}  Duplicates: {}  Warnings: {}",
+        records,
+        records.saturating_sub(row_count),
+        warnings
+    )
+}
+
+/// Prepares `sql`, with its string literals as bind parameters when `parameterize` is set, and
+/// returns the statement with the text it was prepared from and its parameters.
+///
+/// Should PostgreSQL refuse the parameterized form (a literal in a position where it can't infer
+/// a type, say) the statement is prepared as-is instead. Either is taken from `cache` if it was
+/// prepared before.
+pub async fn prepare(
+    client: &Client,
+    cache: &mut StatementCache,
+    sql: &str,
+    parameterize: bool,
+    log: Logger,
+) -> Result<(Statement, String, Vec<TextParam>), tokio_postgres::Error> {
+    if parameterize {
+        if let Some(parameterized) = parameters::extract(sql) {
+            match cache.prepare(client, &parameterized.sql).await {
+                Ok(statement) => {
+                    let params = parameterized.params.into_iter().map(TextParam).collect();
+                    return Ok((statement, parameterized.sql, params));
+                }
+                Err(e) => log.debug(format_args!(
+                    "Failed to prepare parameterized query, sending it inline: {}",
+                    e
+                )),
+            }
+        }
+    }
+    Ok((
+        cache.prepare(client, sql).await?,
+        sql.to_string(),
+        Vec::new(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn text(value: Value) -> Option<String> {
+        match value {
+            Value::NULL => None,
+            Value::Bytes(bytes) => Some(String::from_utf8(bytes).unwrap()),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_dates_back_as_zero_dates() {
+        let sentinel = ZeroDates::Sentinel(NaiveDate::from_ymd_opt(1, 1, 1).unwrap());
+        for (value, ty, zero_dates, sent) in [
+            (None, Type::DATE, ZeroDates::Null, None),
+            (None, Type::DATE, ZeroDates::NullAsZero, Some("0000-00-00")),
+            (
+                None,
+                Type::TIMESTAMP,
+                ZeroDates::NullAsZero,
+                Some("0000-00-00 00:00:00"),
+            ),
+            (None, Type::DATE, sentinel, None),
+            (Some("0001-01-01"), Type::DATE, sentinel, Some("0000-00-00")),
+            (
+                Some("0001-01-01 10:30:00"),
+                Type::TIMESTAMP,
+                sentinel,
+                Some("0000-00-00 10:30:00"),
+            ),
+            (Some("0001-01-02"), Type::DATE, sentinel, Some("0001-01-02")),
+            (
+                Some("0001-01-01"),
+                Type::DATE,
+                ZeroDates::Null,
+                Some("0001-01-01"),
+            ),
+        ] {
+            assert_eq!(
+                text(date_value(value.map(str::to_string), &ty, zero_dates)).as_deref(),
+                sent
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_binary_dates() {
+        let date = |days: i32| {
+            DateText::from_sql(&Type::DATE, &days.to_be_bytes())
+                .unwrap()
+                .0
+        };
+        assert_eq!(date(0), "2000-01-01");
+        assert_eq!(date(-730119), "0001-01-01");
+        assert_eq!(date(i32::MAX), "infinity");
+        let timestamp = |micros: i64| {
+            DateText::from_sql(&Type::TIMESTAMP, &micros.to_be_bytes())
+                .unwrap()
+                .0
+        };
+        assert_eq!(timestamp(90_000_000_000), "2000-01-02 01:00:00");
+        assert_eq!(timestamp(1_500_000), "2000-01-01 00:00:01.500000");
+        assert_eq!(timestamp(i64::MIN), "-infinity");
+    }
+}
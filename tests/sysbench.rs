@@ -0,0 +1,272 @@
+// A scaled-down sysbench oltp_read_write run through the proxy.
+//
+// sysbench's default workload prepares every statement once and executes them over the binary
+// protocol inside BEGIN/COMMIT, so this covers the path real benchmark runs take. Each
+// transaction issues the same statements, in the same proportions, as oltp_read_write with its
+// default options.
+//
+// Needs a PostgreSQL the proxy can reach, configured as for the proxy itself:
+//
+//   DB_HOST=localhost DB_USER=postgres DB_PASSWORD=secret cargo test --features sysbench
+//
+// The proxy is started on SYSBENCH_LISTEN_ADDR (127.0.0.1:33306 by default), and the test
+// creates and drops a table named sbtest1.
+#![cfg(feature = "sysbench")]
+
+use std::env;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use mysql_async::prelude::*;
+use mysql_async::{Conn, Opts, OptsBuilder, TxOpts};
+
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:33306";
+const TABLE_SIZE: i32 = 1000;
+const TRANSACTIONS: i32 = 100;
+// oltp_read_write's defaults.
+const POINT_SELECTS: i32 = 10;
+const SIMPLE_RANGES: i32 = 1;
+const SUM_RANGES: i32 = 1;
+const ORDER_RANGES: i32 = 1;
+const DISTINCT_RANGES: i32 = 1;
+const RANGE_SIZE: i32 = 100;
+const INDEX_UPDATES: i32 = 1;
+const NON_INDEX_UPDATES: i32 = 1;
+const DELETE_INSERTS: i32 = 1;
+
+struct Proxy(Child);
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[tokio::test]
+async fn oltp_read_write() {
+    let listen_addr =
+        env::var("SYSBENCH_LISTEN_ADDR").unwrap_or_else(|_| DEFAULT_LISTEN_ADDR.to_string());
+    let _proxy = Proxy(
+        Command::new(env!("CARGO_BIN_EXE_postmyrustache"))
+            .env("LISTEN_ADDR", &listen_addr)
+            .spawn()
+            .expect("failed to start the proxy"),
+    );
+    let (host, port) = listen_addr.rsplit_once(':').expect("host:port");
+    let opts: Opts = OptsBuilder::default()
+        .ip_or_hostname(host)
+        .tcp_port(port.parse().expect("port"))
+        .user(Some("sbtest"))
+        .pass(Some("sbtest"))
+        .prefer_socket(false)
+        // Like libmysqlclient, don't ask the server for these on connect.
+        .max_allowed_packet(Some(16 * 1024 * 1024))
+        .wait_timeout(Some(28800))
+        .into();
+    let mut conn = connect(opts).await;
+
+    prepare(&mut conn).await;
+    let started = Instant::now();
+    for transaction in 0..TRANSACTIONS {
+        run_transaction(&mut conn, transaction).await;
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{} transactions in {:.2?} ({:.0} tps)",
+        TRANSACTIONS,
+        elapsed,
+        TRANSACTIONS as f64 / elapsed.as_secs_f64()
+    );
+    check(&mut conn).await;
+
+    conn.query_drop("DROP TABLE sbtest1").await.unwrap();
+    conn.disconnect().await.unwrap();
+}
+
+// The proxy takes a moment to start listening.
+async fn connect(opts: Opts) -> Conn {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match Conn::new(opts.clone()).await {
+            Ok(conn) => return conn,
+            Err(e) if Instant::now() > deadline => panic!("couldn't connect to the proxy: {}", e),
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+}
+
+// What `sysbench oltp_read_write prepare` does, with the table definition it sends to MySQL.
+async fn prepare(conn: &mut Conn) {
+    conn.query_drop("DROP TABLE IF EXISTS sbtest1")
+        .await
+        .unwrap();
+    conn.query_drop(
+        "CREATE TABLE sbtest1(
+  id INTEGER NOT NULL AUTO_INCREMENT,
+  k INTEGER DEFAULT '0' NOT NULL,
+  c CHAR(120) DEFAULT '' NOT NULL,
+  pad CHAR(60) DEFAULT '' NOT NULL,
+  PRIMARY KEY (id)
+) /*! ENGINE = innodb */ ",
+    )
+    .await
+    .unwrap();
+
+    let rows: Vec<String> = (1..=TABLE_SIZE)
+        .map(|id| format!("({}, '{}', '{}')", key(id), c_value(id), pad_value(id)))
+        .collect();
+    for batch in rows.chunks(100) {
+        conn.query_drop(format!(
+            "INSERT INTO sbtest1(k, c, pad) VALUES {}",
+            batch.join(",")
+        ))
+        .await
+        .unwrap();
+    }
+    conn.query_drop("CREATE INDEX k_1 ON sbtest1(k)")
+        .await
+        .unwrap();
+}
+
+async fn run_transaction(conn: &mut Conn, transaction: i32) {
+    // Prepared once per connection, as sysbench does; later calls hit mysql_async's cache.
+    let point_select = conn.prep("SELECT c FROM sbtest1 WHERE id=?").await.unwrap();
+    let simple_range = conn
+        .prep("SELECT c FROM sbtest1 WHERE id BETWEEN ? AND ?")
+        .await
+        .unwrap();
+    let sum_range = conn
+        .prep("SELECT SUM(k) FROM sbtest1 WHERE id BETWEEN ? AND ?")
+        .await
+        .unwrap();
+    let order_range = conn
+        .prep("SELECT c FROM sbtest1 WHERE id BETWEEN ? AND ? ORDER BY c")
+        .await
+        .unwrap();
+    let distinct_range = conn
+        .prep("SELECT DISTINCT c FROM sbtest1 WHERE id BETWEEN ? AND ? ORDER BY c")
+        .await
+        .unwrap();
+    let index_update = conn
+        .prep("UPDATE sbtest1 SET k=k+1 WHERE id=?")
+        .await
+        .unwrap();
+    let non_index_update = conn
+        .prep("UPDATE sbtest1 SET c=? WHERE id=?")
+        .await
+        .unwrap();
+    let delete = conn.prep("DELETE FROM sbtest1 WHERE id=?").await.unwrap();
+    let insert = conn
+        .prep("INSERT INTO sbtest1 (id, k, c, pad) VALUES (?, ?, ?, ?)")
+        .await
+        .unwrap();
+
+    // The ids a transaction touches, spread over the table but repeatable.
+    let mut next = (transaction as u32).wrapping_mul(2_654_435_761);
+    let mut id = move |span: i32| {
+        next = next.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (next >> 8) as i32 % (TABLE_SIZE - span) + 1
+    };
+
+    let mut tx = conn.start_transaction(TxOpts::default()).await.unwrap();
+
+    for _ in 0..POINT_SELECTS {
+        let id = id(0);
+        let c: Option<String> = tx.exec_first(&point_select, (id,)).await.unwrap();
+        assert!(c.is_some(), "row {} is missing", id);
+    }
+    for _ in 0..SIMPLE_RANGES {
+        let from = id(RANGE_SIZE);
+        let rows: Vec<String> = tx
+            .exec(&simple_range, (from, from + RANGE_SIZE - 1))
+            .await
+            .unwrap();
+        assert!(rows.len() as i32 <= RANGE_SIZE);
+    }
+    for _ in 0..SUM_RANGES {
+        let from = id(RANGE_SIZE);
+        let sum: Option<Option<i64>> = tx
+            .exec_first(&sum_range, (from, from + RANGE_SIZE - 1))
+            .await
+            .unwrap();
+        assert!(sum.flatten().is_some_and(|sum| sum > 0));
+    }
+    for _ in 0..ORDER_RANGES {
+        let from = id(RANGE_SIZE);
+        let rows: Vec<String> = tx
+            .exec(&order_range, (from, from + RANGE_SIZE - 1))
+            .await
+            .unwrap();
+        assert!(rows.windows(2).all(|w| w[0] <= w[1]), "not in order");
+    }
+    for _ in 0..DISTINCT_RANGES {
+        let from = id(RANGE_SIZE);
+        let rows: Vec<String> = tx
+            .exec(&distinct_range, (from, from + RANGE_SIZE - 1))
+            .await
+            .unwrap();
+        assert!(rows.windows(2).all(|w| w[0] < w[1]), "not distinct");
+    }
+    for _ in 0..INDEX_UPDATES {
+        tx.exec_drop(&index_update, (id(0),)).await.unwrap();
+        assert_eq!(tx.affected_rows(), 1);
+    }
+    for _ in 0..NON_INDEX_UPDATES {
+        let id = id(0);
+        tx.exec_drop(&non_index_update, (c_value(id + transaction), id))
+            .await
+            .unwrap();
+        assert_eq!(tx.affected_rows(), 1);
+    }
+    for _ in 0..DELETE_INSERTS {
+        let id = id(0);
+        tx.exec_drop(&delete, (id,)).await.unwrap();
+        assert_eq!(tx.affected_rows(), 1);
+        tx.exec_drop(&insert, (id, key(id), c_value(id), pad_value(id)))
+            .await
+            .unwrap();
+        assert_eq!(tx.affected_rows(), 1);
+    }
+
+    tx.commit().await.unwrap();
+}
+
+// Every row is still there, and every index update was committed.
+async fn check(conn: &mut Conn) {
+    let count: Option<i64> = conn
+        .query_first("SELECT COUNT(*) FROM sbtest1")
+        .await
+        .unwrap();
+    assert_eq!(count, Some(TABLE_SIZE as i64));
+    let updated: Option<i64> = conn
+        .query_first("SELECT COUNT(*) FROM sbtest1 WHERE k <> ((id * 7919) % 1000) + 1")
+        .await
+        .unwrap();
+    assert!(updated.is_some_and(|n| n > 0 && n <= (TRANSACTIONS * INDEX_UPDATES) as i64));
+}
+
+fn key(id: i32) -> i32 {
+    (id * 7919) % 1000 + 1
+}
+
+// sysbench fills c and pad with groups of digits separated by dashes.
+fn c_value(seed: i32) -> String {
+    digit_groups(seed, 10)
+}
+
+fn pad_value(seed: i32) -> String {
+    digit_groups(seed, 5)
+}
+
+fn digit_groups(seed: i32, groups: usize) -> String {
+    (0..groups)
+        .map(|i| {
+            format!(
+                "{:011}",
+                (seed as u64 * 2_654_435_761 + i as u64) % 100_000_000_000
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
@@ -0,0 +1,204 @@
+// AUTO_INCREMENT columns as identity columns.
+//
+//   CREATE TABLE t (id INTEGER NOT NULL AUTO_INCREMENT, ...) AUTO_INCREMENT = 100
+//   -> CREATE TABLE t (id INTEGER NOT NULL GENERATED BY DEFAULT AS IDENTITY (START WITH 100), ...)
+//
+// BY DEFAULT, because MySQL lets an INSERT supply its own value for the column. The table option
+// setting the next value becomes the identity's START WITH in CREATE TABLE. ALTER TABLE sets the
+// next value of the table's identity sequence with it instead:
+//
+//   ALTER TABLE t AUTO_INCREMENT = 100
+//   -> DO $auto_increment$ BEGIN PERFORM pg_catalog.setval(pg_get_serial_sequence('t', attname),
+//      100, false) FROM pg_attribute WHERE attrelid = 't'::regclass AND attidentity <> ''; END
+//      $auto_increment$
+//
+// The rest of the ALTER TABLE, if it does more, goes first in the same block.
+
+use super::literals::{self, IdentifierCase};
+use super::{parse_fragment, render, statement_starts_with, Node, Token};
+
+const IDENTITY: &str = "GENERATED BY DEFAULT AS IDENTITY";
+
+pub fn rewrite(nodes: Vec<Node>, identifiers: IdentifierCase) -> Vec<Node> {
+    let create = statement_starts_with(&nodes, &["CREATE", "TABLE"])
+        || statement_starts_with(&nodes, &["CREATE", "TEMPORARY", "TABLE"]);
+    if !create && !statement_starts_with(&nodes, &["ALTER", "TABLE"]) {
+        return nodes;
+    }
+
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut iter = nodes.into_iter().peekable();
+    let mut in_body = !create;
+    // ALTER TABLE's `AUTO_INCREMENT = n`.
+    let mut next_value = None;
+
+    while let Some(node) = iter.next() {
+        match node {
+            // The column list of CREATE TABLE.
+            Node::Group(inner) if !in_body => {
+                in_body = true;
+                out.push(Node::Group(inner.into_iter().flat_map(column).collect()));
+            }
+            Node::Token(ref t) if t.is_word("AUTO_INCREMENT") => {
+                let mut ahead = iter.clone().filter(|n| !n.is_trivia());
+                let option = match ahead.next() {
+                    Some(Node::Token(Token::Number(_))) => true,
+                    Some(Node::Token(t)) if t.is_operator("=") => {
+                        matches!(ahead.next(), Some(Node::Token(Token::Number(_))))
+                    }
+                    _ => false,
+                };
+                if option {
+                    // The table option, `AUTO_INCREMENT [=] n`, and the comma separating it from
+                    // the options or ALTER TABLE actions around it.
+                    while out.last().is_some_and(Node::is_trivia) {
+                        out.pop();
+                    }
+                    let separated = matches!(out.last(), Some(Node::Token(Token::Comma)));
+                    if separated {
+                        out.pop();
+                    }
+                    while iter
+                        .next_if(|n| {
+                            n.is_trivia() || matches!(n, Node::Token(t) if t.is_operator("="))
+                        })
+                        .is_some()
+                    {}
+                    if let Some(Node::Token(Token::Number(n))) = iter.next() {
+                        next_value = Some(n);
+                    }
+                    if !separated {
+                        while iter.next_if(Node::is_trivia).is_some() {}
+                        iter.next_if(|n| matches!(n, Node::Token(Token::Comma)));
+                    }
+                } else {
+                    // A column added by ALTER TABLE.
+                    out.extend(parse_fragment(IDENTITY));
+                }
+            }
+            other => out.push(other),
+        }
+    }
+
+    match next_value {
+        Some(value) if create => start_with(out, &value),
+        Some(value) => set_next_value(out, &value, identifiers),
+        None => out,
+    }
+}
+
+// PostgreSQL's sequences start at 1, MySQL takes 0 for it.
+fn first_value(value: &str) -> String {
+    match value.parse::<i64>() {
+        Ok(n) => n.max(1).to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+// CREATE TABLE `nodes` with its identity column starting at `value`.
+fn start_with(mut nodes: Vec<Node>, value: &str) -> Vec<Node> {
+    let Some(Node::Group(body)) = nodes.iter_mut().find(|n| matches!(n, Node::Group(_))) else {
+        return nodes;
+    };
+    if let Some(at) = body
+        .iter()
+        .position(|n| matches!(n, Node::Token(t) if t.is_word("IDENTITY")))
+    {
+        let start = parse_fragment(&format!(" (START WITH {})", first_value(value)));
+        body.splice(at + 1..at + 1, start);
+    }
+    nodes
+}
+
+// ALTER TABLE `nodes`, without its AUTO_INCREMENT option, followed by setting the next value of
+// the table's identity column to `value`.
+fn set_next_value(nodes: Vec<Node>, value: &str, identifiers: IdentifierCase) -> Vec<Node> {
+    // The name follows ALTER TABLE, and ends at the first token that isn't part of it.
+    let start = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !n.is_trivia())
+        .nth(2)
+        .map_or(nodes.len(), |(i, _)| i);
+    let mut rest = nodes[start..].iter().peekable();
+    let mut table = String::new();
+    while let Some(Node::Token(t)) = rest.peek() {
+        match t {
+            Token::Word(w) => table.push_str(w),
+            Token::QuotedIdent(raw) => {
+                table.push_str(&literals::quoted_identifier(raw, identifiers))
+            }
+            t if t.is_operator(".") => table.push('.'),
+            _ => break,
+        }
+        rest.next();
+    }
+    let table = literals::pg_string(&table);
+    let value = first_value(value);
+
+    let mut sql = String::from("DO $auto_increment$ BEGIN ");
+    if rest.any(|n| !n.is_trivia()) {
+        sql.push_str(render(&nodes).trim());
+        sql.push_str("; ");
+    }
+    sql.push_str(&format!(
+        "PERFORM pg_catalog.setval(pg_get_serial_sequence({table}, attname), {value}, false) \
+         FROM pg_attribute WHERE attrelid = {table}::regclass AND attidentity <> ''; \
+         END $auto_increment$"
+    ));
+    parse_fragment(&sql)
+}
+
+fn column(node: Node) -> Vec<Node> {
+    match node {
+        Node::Token(ref t) if t.is_word("AUTO_INCREMENT") => parse_fragment(IDENTITY),
+        other => vec![other],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn auto_increment_columns_become_identity_columns() {
+        assert_eq!(
+            translate("CREATE TABLE t (id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY, name TEXT) AUTO_INCREMENT = 100"),
+            "CREATE TABLE t (id INTEGER NOT NULL GENERATED BY DEFAULT AS IDENTITY (START WITH 100) PRIMARY KEY, name TEXT)"
+        );
+        assert_eq!(
+            translate("CREATE TABLE t (id INT AUTO_INCREMENT, PRIMARY KEY (id)) ENGINE=InnoDB, AUTO_INCREMENT=0"),
+            "CREATE TABLE t (id INT GENERATED BY DEFAULT AS IDENTITY (START WITH 1), PRIMARY KEY (id))"
+        );
+        assert_eq!(
+            translate("CREATE TABLE t (id INT AUTO_INCREMENT, n INT DEFAULT 5)"),
+            "CREATE TABLE t (id INT GENERATED BY DEFAULT AS IDENTITY, n INT DEFAULT 5)"
+        );
+    }
+
+    #[test]
+    fn alter_table_adds_identity_columns() {
+        assert_eq!(
+            translate("ALTER TABLE t ADD COLUMN id INT AUTO_INCREMENT"),
+            "ALTER TABLE t ADD COLUMN id INT GENERATED BY DEFAULT AS IDENTITY"
+        );
+        assert_eq!(
+            translate("ALTER TABLE s.t ADD COLUMN b INT, AUTO_INCREMENT 0"),
+            "DO $auto_increment$ BEGIN ALTER TABLE s.t ADD COLUMN b INT; PERFORM pg_catalog.setval(pg_get_serial_sequence('s.t', attname), 1, false) \
+             FROM pg_attribute WHERE attrelid = 's.t'::regclass AND attidentity <> ''; END $auto_increment$"
+        );
+    }
+
+    #[test]
+    fn alter_table_auto_increment_sets_the_next_value() {
+        assert_eq!(
+            translate("ALTER TABLE t AUTO_INCREMENT = 100"),
+            "DO $auto_increment$ BEGIN PERFORM pg_catalog.setval(pg_get_serial_sequence('t', attname), 100, false) \
+             FROM pg_attribute WHERE attrelid = 't'::regclass AND attidentity <> ''; END $auto_increment$"
+        );
+    }
+}
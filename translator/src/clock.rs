@@ -0,0 +1,163 @@
+// Pinning of the current date and time.
+//
+// When a fixed "now" is configured, calls that read the clock (NOW(), CURRENT_TIMESTAMP,
+// CURDATE(), ...) are replaced by constants so test suites that snapshot query results get the
+// same answers on every run. Table definitions are left alone: a pinned `DEFAULT
+// CURRENT_TIMESTAMP` would be stored in the schema for good.
+
+use chrono::NaiveDateTime;
+
+use super::{parse_fragment, statement_starts_with, Node, Token};
+
+// Functions that return the current timestamp, date and time. Those marked `true` can also be
+// written without parentheses.
+const TIMESTAMP_FUNCTIONS: &[(&str, bool)] = &[
+    ("NOW", false),
+    ("SYSDATE", false),
+    ("UTC_TIMESTAMP", true),
+    ("CURRENT_TIMESTAMP", true),
+    ("LOCALTIMESTAMP", true),
+    ("LOCALTIME", true),
+];
+const DATE_FUNCTIONS: &[(&str, bool)] = &[
+    ("CURDATE", false),
+    ("CURRENT_DATE", true),
+    ("UTC_DATE", true),
+];
+const TIME_FUNCTIONS: &[(&str, bool)] = &[
+    ("CURTIME", false),
+    ("CURRENT_TIME", true),
+    ("UTC_TIME", true),
+];
+
+pub fn rewrite(nodes: Vec<Node>, now: NaiveDateTime) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["CREATE"]) || statement_starts_with(&nodes, &["ALTER"]) {
+        return nodes;
+    }
+    pin(nodes, now)
+}
+
+fn pin(nodes: Vec<Node>, now: NaiveDateTime) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut iter = nodes.into_iter().peekable();
+
+    while let Some(node) = iter.next() {
+        let name = match node {
+            Node::Group(inner) => {
+                out.push(Node::Group(pin(inner, now)));
+                continue;
+            }
+            Node::Token(Token::Word(ref name)) => name.clone(),
+            other => {
+                out.push(other);
+                continue;
+            }
+        };
+        let qualified = matches!(
+            out.iter().rev().find(|n| !n.is_trivia()),
+            Some(Node::Token(t)) if t.is_operator(".")
+        );
+        // `None` when not called, otherwise whether it was called without arguments.
+        let call = match iter.peek() {
+            // The only argument most of these take is a fractional seconds precision.
+            Some(Node::Group(args)) => {
+                if args.iter().all(Node::is_trivia) {
+                    Some(true)
+                } else if args
+                    .iter()
+                    .all(|n| n.is_trivia() || matches!(n, Node::Token(Token::Number(_))))
+                {
+                    Some(false)
+                } else {
+                    out.push(node);
+                    continue;
+                }
+            }
+            _ => None,
+        };
+        let Some(replacement) = (!qualified).then(|| constant(&name, call, now)).flatten() else {
+            out.push(node);
+            continue;
+        };
+        if call.is_some() {
+            iter.next();
+        }
+        out.extend(parse_fragment(&replacement));
+    }
+
+    out
+}
+
+// The constant replacing a clock function, if `name` is one.
+fn constant(name: &str, call: Option<bool>, now: NaiveDateTime) -> Option<String> {
+    let matches = |functions: &[(&str, bool)]| {
+        functions
+            .iter()
+            .any(|(f, bare)| name.eq_ignore_ascii_case(f) && (call.is_some() || *bare))
+    };
+    if matches(TIMESTAMP_FUNCTIONS) {
+        Some(format!(
+            "TIMESTAMP '{}'",
+            now.format("%Y-%m-%d %H:%M:%S%.f")
+        ))
+    } else if matches(DATE_FUNCTIONS) {
+        Some(format!("DATE '{}'", now.format("%Y-%m-%d")))
+    } else if matches(TIME_FUNCTIONS) {
+        Some(format!("TIME '{}'", now.format("%H:%M:%S%.f")))
+    } else if name.eq_ignore_ascii_case("UNIX_TIMESTAMP") && call == Some(true) {
+        Some(now.and_utc().timestamp().to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{TranslationOptions, Translator};
+
+    fn translate(sql: &str) -> String {
+        let options = TranslationOptions {
+            pinned_now: NaiveDate::from_ymd_opt(2024, 5, 6).and_then(|d| d.and_hms_opt(7, 8, 9)),
+            ..Default::default()
+        };
+        Translator::with_options(options).translate(sql).unwrap()
+    }
+
+    #[test]
+    fn pins_the_clock_functions() {
+        assert_eq!(
+            translate("SELECT NOW(), CURDATE(), CURRENT_TIME"),
+            "SELECT TIMESTAMP '2024-05-06 07:08:09', DATE '2024-05-06', TIME '07:08:09'"
+        );
+    }
+
+    #[test]
+    fn pins_calls_with_a_precision_and_without_parentheses() {
+        assert_eq!(
+            translate("SELECT NOW(3), SYSDATE(), UTC_TIMESTAMP, CURRENT_TIMESTAMP(), CURRENT_DATE"),
+            "SELECT TIMESTAMP '2024-05-06 07:08:09', TIMESTAMP '2024-05-06 07:08:09', \
+             TIMESTAMP '2024-05-06 07:08:09', TIMESTAMP '2024-05-06 07:08:09', DATE '2024-05-06'"
+        );
+        assert_eq!(
+            translate("SELECT UNIX_TIMESTAMP(), UNIX_TIMESTAMP(d) FROM t"),
+            "SELECT 1714979289, UNIX_TIMESTAMP(d) FROM t"
+        );
+        assert_eq!(
+            translate("INSERT INTO t (at) VALUES (NOW())"),
+            "INSERT INTO t (at) VALUES (TIMESTAMP '2024-05-06 07:08:09')"
+        );
+    }
+
+    #[test]
+    fn leaves_column_defaults_alone() {
+        for sql in [
+            "CREATE TABLE t (at TIMESTAMP DEFAULT CURRENT_TIMESTAMP)",
+            "ALTER TABLE t ALTER COLUMN at SET DEFAULT CURRENT_TIMESTAMP",
+            "SELECT 'NOW()', now FROM t",
+        ] {
+            assert_eq!(translate(sql), sql);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// MySQL's character sets and collations, in the statements that name them.
+//
+//   WHERE name = 'x' COLLATE utf8mb4_bin           ->  WHERE name = 'x' COLLATE "C"
+//   name VARCHAR(20) COLLATE utf8mb4_0900_ai_ci    ->  name VARCHAR(20)
+//   name VARCHAR(20) CHARACTER SET latin1          ->  name VARCHAR(20)
+//   CAST(x AS CHAR CHARSET utf8mb4)                ->  CAST(x AS CHAR)
+//   _utf8mb4'text'                                 ->  'text'
+//   CONVERT(name USING utf8mb4)                    ->  CAST(name AS TEXT)
+//
+// A PostgreSQL database keeps all its text in the one encoding, so character sets are dropped, and
+// CONVERT ... USING only makes text of its argument, or with USING binary its UTF-8 bytes. A
+// binary collation (`_bin`, `binary`) compares code points as "C" does; others are dropped too,
+// which leaves the collation of the column or the database. That is case-sensitive where MySQL's
+// `_ci` collations aren't. Collations that aren't MySQL's, "C" or "de_DE" say, are left alone.
+
+use super::{is_word, literals, parse_fragment, render, Node, Token};
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut i = 0;
+    while i < nodes.len() {
+        // An introducer, the character set of the literal after it, whitespace between them or
+        // not.
+        if let Node::Token(Token::Word(word)) = &nodes[i] {
+            let literal = next_significant(&nodes, i + 1);
+            if word.strip_prefix('_').is_some_and(is_charset)
+                && literal.is_some_and(|at| matches!(&nodes[at], Node::Token(Token::String(_))))
+            {
+                i = literal.expect("checked above");
+                continue;
+            }
+        }
+        if let Some((end, converted)) = convert_using(&nodes, i) {
+            out.extend(parse_fragment(&converted));
+            i = end;
+            continue;
+        }
+        let Some((end, binary)) = clause(&nodes, i) else {
+            out.push(nodes[i].clone());
+            i += 1;
+            continue;
+        };
+        i = end;
+        if binary {
+            out.push(Node::Token(Token::Word("COLLATE".to_string())));
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            out.push(Node::Token(Token::QuotedIdent("\"C\"".to_string())));
+        } else {
+            while matches!(out.last(), Some(Node::Token(Token::Whitespace(_)))) {
+                out.pop();
+            }
+        }
+    }
+    out
+}
+
+// The end of the `COLLATE name`, `CHARSET name` or `CHARACTER SET name` clause at `at`, and
+// whether it is a binary collation.
+fn clause(nodes: &[Node], at: usize) -> Option<(usize, bool)> {
+    let Node::Token(token) = &nodes[at] else {
+        return None;
+    };
+    let mut name = at;
+    let collate = token.is_word("COLLATE");
+    if !collate {
+        if token.is_word("CHARACTER") {
+            name = next_significant(nodes, name + 1)?;
+            if !matches!(&nodes[name], Node::Token(t) if t.is_word("SET")) {
+                return None;
+            }
+        } else if !token.is_word("CHARSET") {
+            return None;
+        }
+        // `DEFAULT CHARSET utf8mb4` and `CHARSET = utf8mb4` are table options, which table_ddl
+        // drops from CREATE TABLE. Elsewhere they are left as they are.
+        let previous = nodes[..at].iter().rposition(|n| !n.is_trivia());
+        if previous.is_some_and(|p| matches!(&nodes[p], Node::Token(t) if t.is_word("DEFAULT"))) {
+            return None;
+        }
+    }
+    name = next_significant(nodes, name + 1)?;
+    let value = match &nodes[name] {
+        Node::Token(Token::String(raw)) => literals::mysql_string_value(raw).to_lowercase(),
+        Node::Token(token) => literals::identifier_name(token)?,
+        Node::Group(_) => return None,
+    };
+    let known = match collate {
+        true => value == "binary" || value.split('_').next().is_some_and(is_charset),
+        false => is_charset(&value),
+    };
+    if !known {
+        return None;
+    }
+    let binary = collate && (value == "binary" || value.ends_with("_bin"));
+    Some((name + 1, binary))
+}
+
+// `CONVERT(expr USING charset)` at `at`: where it ends, and the text or, for `binary`, the bytes
+// it converts to.
+fn convert_using(nodes: &[Node], at: usize) -> Option<(usize, String)> {
+    if !matches!(&nodes[at], Node::Token(t) if t.is_word("CONVERT")) {
+        return None;
+    }
+    let Some(Node::Group(args)) = nodes.get(at + 1) else {
+        return None;
+    };
+    let significant: Vec<usize> = (0..args.len()).filter(|&i| !args[i].is_trivia()).collect();
+    let [.., using, charset] = significant[..] else {
+        return None;
+    };
+    let charset = match &args[charset] {
+        Node::Token(token) => literals::identifier_name(token)?,
+        Node::Group(_) => return None,
+    };
+    if !is_word(args.get(using), "USING") || !is_charset(&charset) {
+        return None;
+    }
+    let expr = render(&args[..using]);
+    let text = format!("CAST({} AS TEXT)", expr.trim());
+    let converted = match charset.as_str() {
+        "binary" => format!("convert_to({}, 'UTF8')", text),
+        _ => text,
+    };
+    Some((at + 2, converted))
+}
+
+fn next_significant(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| !nodes[i].is_trivia())
+}
+
+fn is_charset(name: &str) -> bool {
+    CHARSETS
+        .iter()
+        .any(|charset| charset.eq_ignore_ascii_case(name))
+}
+
+// MySQL's character sets, as SHOW CHARACTER SET lists them.
+const CHARSETS: &[&str] = &[
+    "armscii8", "ascii", "big5", "binary", "cp1250", "cp1251", "cp1256", "cp1257", "cp850",
+    "cp852", "cp866", "cp932", "dec8", "eucjpms", "euckr", "gb18030", "gb2312", "gbk", "geostd8",
+    "greek", "hebrew", "hp8", "keybcs2", "koi8r", "koi8u", "latin1", "latin2", "latin5", "latin7",
+    "macce", "macroman", "sjis", "swe7", "tis620", "ucs2", "ujis", "utf16", "utf16le", "utf32",
+    "utf8", "utf8mb3", "utf8mb4",
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn binary_collations_become_c() {
+        assert_eq!(
+            translate("SELECT name COLLATE utf8mb4_bin FROM t"),
+            "SELECT name COLLATE \"C\" FROM t"
+        );
+    }
+
+    #[test]
+    fn drops_character_sets_and_other_collations() {
+        assert_eq!(
+            translate("CREATE TABLE t (name VARCHAR(10) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci)"),
+            "CREATE TABLE t (name VARCHAR(10))"
+        );
+    }
+
+    #[test]
+    fn drops_introducers_and_converts_with_using() {
+        for (mysql, postgres) in [
+            (
+                "SELECT _latin1 'x', _utf8mb4'y' COLLATE utf8mb4_bin",
+                "SELECT 'x', 'y' COLLATE \"C\"",
+            ),
+            // Not a character set, so a column named _foo.
+            ("SELECT _foo 'x'", "SELECT _foo 'x'"),
+            (
+                "SELECT CONVERT(a USING utf8mb4), convert(b + 1 using binary) FROM t",
+                "SELECT CAST(a AS TEXT), convert_to(CAST(b + 1 AS TEXT), 'UTF8') FROM t",
+            ),
+            (
+                "SELECT * FROM t WHERE a = 'x' COLLATE 'utf8mb4_bin' ORDER BY a COLLATE `binary`",
+                "SELECT * FROM t WHERE a = 'x' COLLATE \"C\" ORDER BY a COLLATE \"C\"",
+            ),
+            (
+                "SELECT CAST(x AS CHAR(10) CHARACTER SET latin1)",
+                "SELECT CAST(x AS CHAR(10))",
+            ),
+        ] {
+            assert_eq!(translate(mysql), postgres);
+        }
+    }
+
+    #[test]
+    fn table_character_sets_are_no_ops() {
+        for (mysql, postgres) in [
+            (
+                "ALTER TABLE t CONVERT TO CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci",
+                "DO $$ BEGIN END $$",
+            ),
+            ("ALTER TABLE t COMMENT = 'a, b'", "DO $$ BEGIN END $$"),
+            (
+                "ALTER TABLE s.t ADD COLUMN b INT, ENGINE=InnoDB, DEFAULT CHARSET=utf8mb4",
+                "ALTER TABLE s.t ADD COLUMN b INT",
+            ),
+            (
+                "ALTER TABLE t ENGINE=InnoDB, ADD COLUMN engine INT",
+                "ALTER TABLE t ADD COLUMN engine INT",
+            ),
+        ] {
+            assert_eq!(translate(mysql), postgres);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+// CHECK constraint translation for CREATE TABLE and ALTER TABLE.
+//
+// MySQL 8 accepts `[CONSTRAINT name] CHECK (expr) [NOT] ENFORCED`; PostgreSQL has no ENFORCED
+// clause and can't keep an unenforced check around, so NOT ENFORCED checks are dropped as a table
+// is created. `ALTER TABLE t ALTER CHECK c NOT ENFORCED` is refused as unsupported instead, as
+// dropping the check couldn't be undone by `ALTER CHECK c ENFORCED`, which does nothing. Before
+// 8.0.16 MySQL parsed CHECK clauses and ignored them; `CheckConstraints::Strip` reproduces that
+// for applications that (unknowingly) rely on it.
+
+use super::{statement_starts_with, Node, Token, TranslateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckConstraints {
+    /// Create CHECK constraints on PostgreSQL, so they are enforced.
+    #[default]
+    Enforce,
+    /// Drop every CHECK clause, like MySQL before 8.0.16.
+    Strip,
+}
+
+/// PostgreSQL's no-op statement, used when every action of an ALTER TABLE was dropped.
+pub(super) const NOOP: &str = "DO $$ BEGIN END $$";
+
+pub fn rewrite(mut nodes: Vec<Node>, mode: CheckConstraints) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["CREATE", "TABLE"])
+        || statement_starts_with(&nodes, &["CREATE", "TEMPORARY", "TABLE"])
+    {
+        // The column list is the first group in the statement.
+        if let Some(Node::Group(body)) = nodes.iter_mut().find(|n| matches!(n, Node::Group(_))) {
+            let items = std::mem::take(body);
+            *body = rewrite_list(items, mode, false).0;
+        }
+        nodes
+    } else if statement_starts_with(&nodes, &["ALTER", "TABLE"]) {
+        let (nodes, all_removed) = rewrite_list(nodes, mode, true);
+        if all_removed {
+            return super::parse_fragment(NOOP);
+        }
+        rename_drop_check(nodes)
+    } else {
+        nodes
+    }
+}
+
+/// Refuses `ALTER CHECK name NOT ENFORCED` in an ALTER TABLE while checks are enforced.
+pub fn check_alter(nodes: &[Node], mode: CheckConstraints) -> Result<(), TranslateError> {
+    if mode == CheckConstraints::Strip || !statement_starts_with(nodes, &["ALTER", "TABLE"]) {
+        return Ok(());
+    }
+    let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+    let word = |node: &Node, word: &str| matches!(node, Node::Token(t) if t.is_word(word));
+    let unenforced = significant.windows(5).any(|w| {
+        word(w[0], "ALTER") && word(w[1], "CHECK") && word(w[3], "NOT") && word(w[4], "ENFORCED")
+    });
+    match unenforced {
+        true => Err(TranslateError::Unsupported(
+            "ALTER CHECK ... NOT ENFORCED".to_string(),
+        )),
+        false => Ok(()),
+    }
+}
+
+fn has_content(nodes: &[Node]) -> bool {
+    nodes.iter().any(|n| !n.is_trivia())
+}
+
+// Rewrites each comma-separated item of a list, dropping items that end up empty.
+//
+// For ALTER TABLE the first item also carries `ALTER TABLE name`; the returned flag is set when
+// no action is left after it.
+fn rewrite_list(nodes: Vec<Node>, mode: CheckConstraints, alter: bool) -> (Vec<Node>, bool) {
+    let items: Vec<Vec<Node>> = nodes
+        .split(|n| matches!(n, Node::Token(Token::Comma)))
+        .map(<[Node]>::to_vec)
+        .collect();
+
+    let mut kept: Vec<Vec<Node>> = Vec::with_capacity(items.len());
+    let mut head_only = false;
+    for (i, item) in items.into_iter().enumerate() {
+        let was_empty = !has_content(&item);
+        let mut item = rewrite_checks(item, mode);
+        // `ADD` on its own means the constraint it added was removed.
+        let dangling_add = alter
+            && matches!(
+                item.iter().rev().find(|n| !n.is_trivia()),
+                Some(Node::Token(t)) if t.is_word("ADD")
+            );
+        if dangling_add {
+            let idx = item
+                .iter()
+                .rposition(|n| matches!(n, Node::Token(t) if t.is_word("ADD")))
+                .expect("found ADD");
+            item.truncate(idx);
+        }
+        if dangling_add || (alter && alter_check(&mut item, mode)) {
+            if i == 0 {
+                head_only = true;
+                kept.push(item);
+            }
+            continue;
+        }
+        if !was_empty && !has_content(&item) {
+            continue;
+        }
+        kept.push(item);
+    }
+
+    let all_removed = head_only && kept.len() == 1;
+    let mut out = Vec::new();
+    for (i, item) in kept.into_iter().enumerate() {
+        if i > 0 && !(i == 1 && head_only) {
+            out.push(Node::Token(Token::Comma));
+        }
+        out.extend(item);
+    }
+    (out, all_removed)
+}
+
+// Removes or normalizes every `[CONSTRAINT [name]] CHECK (...) [[NOT] ENFORCED]` in `nodes`.
+fn rewrite_checks(mut nodes: Vec<Node>, mode: CheckConstraints) -> Vec<Node> {
+    let mut search_from = 0;
+    while let Some(check) = find_check(&nodes, search_from) {
+        let group = next_significant(&nodes, check + 1).expect("CHECK is followed by a group");
+
+        let mut start = check;
+        if let Some(prev) = prev_significant(&nodes, start) {
+            if matches!(&nodes[prev], Node::Token(t) if t.is_word("CONSTRAINT")) {
+                start = prev;
+            } else if let Some(before) = prev_significant(&nodes, prev) {
+                if matches!(&nodes[before], Node::Token(t) if t.is_word("CONSTRAINT")) {
+                    start = before;
+                }
+            }
+        }
+
+        let mut end = group + 1;
+        let mut not_enforced = false;
+        let mut enforced_at = None;
+        if let Some(next) = next_significant(&nodes, end) {
+            if matches!(&nodes[next], Node::Token(t) if t.is_word("NOT")) {
+                if let Some(after) = next_significant(&nodes, next + 1) {
+                    if matches!(&nodes[after], Node::Token(t) if t.is_word("ENFORCED")) {
+                        not_enforced = true;
+                        end = after + 1;
+                    }
+                }
+            } else if matches!(&nodes[next], Node::Token(t) if t.is_word("ENFORCED")) {
+                enforced_at = Some(next);
+                end = next + 1;
+            }
+        }
+
+        if mode == CheckConstraints::Strip || not_enforced {
+            // Take the whitespace in front of the constraint with it.
+            while start > 0 && nodes[start - 1].is_trivia() {
+                start -= 1;
+            }
+            nodes.drain(start..end);
+            search_from = start;
+        } else {
+            if let Some(at) = enforced_at {
+                nodes.drain(group + 1..at + 1);
+            }
+            search_from = group + 1;
+        }
+    }
+    nodes
+}
+
+fn find_check(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| {
+        matches!(&nodes[i], Node::Token(t) if t.is_word("CHECK"))
+            && matches!(
+                next_significant(nodes, i + 1).map(|g| &nodes[g]),
+                Some(Node::Group(_))
+            )
+    })
+}
+
+fn next_significant(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| !nodes[i].is_trivia())
+}
+
+fn prev_significant(nodes: &[Node], before: usize) -> Option<usize> {
+    nodes[..before].iter().rposition(|n| !n.is_trivia())
+}
+
+// `ALTER CHECK name [NOT] ENFORCED` in an item of ALTER TABLE: cut from the item, which is what
+// the returned flag says, but for NOT ENFORCED while checks are enforced, which check_alter
+// refuses, and which is left as it is rather than dropping the check.
+fn alter_check(item: &mut Vec<Node>, mode: CheckConstraints) -> bool {
+    let significant: Vec<usize> = (0..item.len()).filter(|&i| !item[i].is_trivia()).collect();
+    let word = |k: usize, word: &str| matches!(significant.get(k).map(|&i| &item[i]), Some(Node::Token(t)) if t.is_word(word));
+    let Some(k) = (0..significant.len()).find(|&k| word(k, "ALTER") && word(k + 1, "CHECK")) else {
+        return false;
+    };
+    if significant.len() <= k + 2 {
+        return false;
+    }
+    let not_enforced = word(k + 3, "NOT") && word(k + 4, "ENFORCED");
+    if !not_enforced && !word(k + 3, "ENFORCED") {
+        return false;
+    }
+    if not_enforced && mode == CheckConstraints::Enforce {
+        return false;
+    }
+    item.truncate(significant[k]);
+    true
+}
+
+// MySQL's `DROP CHECK name` is `DROP CONSTRAINT name` on PostgreSQL.
+fn rename_drop_check(mut nodes: Vec<Node>) -> Vec<Node> {
+    for i in 0..nodes.len() {
+        if !matches!(&nodes[i], Node::Token(t) if t.is_word("CHECK")) {
+            continue;
+        }
+        let after_drop = prev_significant(&nodes, i)
+            .is_some_and(|p| matches!(&nodes[p], Node::Token(t) if t.is_word("DROP")));
+        if after_drop {
+            nodes[i] = Node::Token(Token::Word("CONSTRAINT".to_string()));
+        }
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NOOP;
+    use crate::{CheckConstraints, TranslateError, TranslationOptions, Translator};
+
+    fn translate(sql: &str, mode: CheckConstraints) -> String {
+        let options = TranslationOptions {
+            check_constraints: mode,
+            ..Default::default()
+        };
+        Translator::with_options(options).translate(sql).unwrap()
+    }
+
+    #[test]
+    fn keeps_enforced_checks() {
+        let sql = "ALTER TABLE t ADD CONSTRAINT c CHECK (a > 0)";
+        assert_eq!(translate(sql, CheckConstraints::Enforce), sql);
+        assert_eq!(
+            translate("ALTER TABLE t DROP CHECK c", CheckConstraints::Enforce),
+            "ALTER TABLE t DROP CONSTRAINT c"
+        );
+    }
+
+    #[test]
+    fn drops_unenforced_checks() {
+        assert_eq!(
+            translate(
+                "CREATE TABLE t (a INT, CHECK (a > 0) NOT ENFORCED)",
+                CheckConstraints::Enforce
+            ),
+            "CREATE TABLE t (a INT)"
+        );
+    }
+
+    #[test]
+    fn drops_the_enforced_clause_of_column_and_named_checks() {
+        assert_eq!(
+            translate(
+                "CREATE TABLE t (a INT CHECK (a > 0) ENFORCED, CONSTRAINT c CHECK (a < 9) ENFORCED)",
+                CheckConstraints::Enforce
+            ),
+            "CREATE TABLE t (a INT CHECK (a > 0), CONSTRAINT c CHECK (a < 9))"
+        );
+        assert_eq!(
+            translate(
+                "CREATE TABLE t (a INT CHECK (a > 0) NOT ENFORCED, b INT)",
+                CheckConstraints::Enforce
+            ),
+            "CREATE TABLE t (a INT, b INT)"
+        );
+    }
+
+    #[test]
+    fn alters_whether_a_check_is_enforced() {
+        // Dropping the check couldn't be undone.
+        for sql in [
+            "ALTER TABLE t ALTER CHECK c NOT ENFORCED",
+            "ALTER TABLE t ALTER CHECK c NOT ENFORCED, DROP CHECK d",
+            "ALTER TABLE t ADD COLUMN b INT, ALTER CHECK c NOT ENFORCED",
+        ] {
+            assert_eq!(
+                Translator::new().translate(sql),
+                Err(TranslateError::Unsupported(
+                    "ALTER CHECK ... NOT ENFORCED".to_string()
+                )),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            translate(
+                "ALTER TABLE t ALTER CHECK c ENFORCED",
+                CheckConstraints::Enforce
+            ),
+            NOOP
+        );
+        assert_eq!(
+            translate(
+                "ALTER TABLE t ADD COLUMN b INT, ALTER CHECK c ENFORCED",
+                CheckConstraints::Enforce
+            ),
+            "ALTER TABLE t ADD COLUMN b INT"
+        );
+        assert_eq!(
+            translate(
+                "ALTER TABLE t ALTER CHECK c NOT ENFORCED",
+                CheckConstraints::Strip
+            ),
+            NOOP
+        );
+    }
+
+    #[test]
+    fn strips_every_check_before_8_0_16() {
+        assert_eq!(
+            translate(
+                "CREATE TABLE t (a INT, CONSTRAINT c CHECK (a > 0))",
+                CheckConstraints::Strip
+            ),
+            "CREATE TABLE t (a INT)"
+        );
+        assert_eq!(
+            translate(
+                "ALTER TABLE t ADD CONSTRAINT c CHECK (a > 0)",
+                CheckConstraints::Strip
+            ),
+            NOOP
+        );
+    }
+}
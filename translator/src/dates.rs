@@ -0,0 +1,319 @@
+// Zero and impossible dates in string literals (sql_mode's NO_ZERO_DATE, NO_ZERO_IN_DATE and
+// ALLOW_INVALID_DATES).
+//
+// MySQL stores '0000-00-00', dates with a zero month or day and, with ALLOW_INVALID_DATES, days
+// past the end of the month such as '2024-02-31'. Outside strict mode it takes any other
+// impossible date too, storing the zero date with a warning. PostgreSQL rejects all of them with
+// "date/time field value out of range". String literals shaped like such a date are rewritten to
+// what the configured sql_mode would have MySQL store, as far as PostgreSQL can hold it:
+//
+//   '0000-00-00', '2024-00-15'  ->  NULL, PostgreSQL having no zero date
+//   '2024-02-31 10:00:00'       ->  '2024-02-29 10:00:00' with ALLOW_INVALID_DATES, else NULL
+//   '2024-13-01'                ->  NULL
+//   '0000-01-15'                ->  NULL, PostgreSQL having no year 0 either
+//
+// ZERO_DATES can have the zero date stored as a sentinel date instead, for NOT NULL columns and
+// to tell it from NULL, and have the proxy send sessions that take zero dates (NO_ZERO_DATE unset)
+// the zero date back in its place, or in place of NULL:
+//
+//   ZERO_DATES = null          '0000-00-00' is NULL, and NULL reads as NULL
+//   ZERO_DATES = null-as-zero  '0000-00-00' is NULL, and NULL dates read as '0000-00-00'
+//   ZERO_DATES = 0001-01-01    '0000-00-00' is 0001-01-01, which reads as '0000-00-00'
+//
+// In strict mode (STRICT_TRANS_TABLES, STRICT_ALL_TABLES or TRADITIONAL) the dates MySQL would
+// refuse are left for PostgreSQL to refuse. The translator only rewrites the strings it can tell
+// are dates, typed literals such as DATE '0000-00-00' and CAST('2024-02-31' AS DATE): the same
+// string may be plain text given to a varchar column. The proxy coerces the strings given to date
+// and datetime columns once it has looked up the columns' types.
+
+use chrono::NaiveDate;
+
+use super::{literals, Node, Token};
+
+// Words that make the string after them a typed literal, as in DATE '2024-01-01'.
+const TYPED_LITERALS: &[&str] = &["DATE", "DATETIME", "TIMESTAMP"];
+
+/// The sql_mode flags that decide what becomes of zero and impossible dates, and ZERO_DATES.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateModes {
+    pub strict: bool,
+    pub no_zero_date: bool,
+    pub no_zero_in_date: bool,
+    pub allow_invalid_dates: bool,
+    pub zero_dates: ZeroDates,
+}
+
+impl DateModes {
+    /// What the session is sent in place of zero dates: NULL, as they're stored, unless
+    /// ZERO_DATES says otherwise and the session takes zero dates.
+    pub fn zero_date_results(&self) -> ZeroDates {
+        match self.no_zero_date {
+            true => ZeroDates::Null,
+            false => self.zero_dates,
+        }
+    }
+}
+
+/// What the zero date is stored as, and read back as (ZERO_DATES).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroDates {
+    #[default]
+    Null,
+    // NULL, with NULL dates read back as the zero date.
+    NullAsZero,
+    // This date, read back as the zero date.
+    Sentinel(NaiveDate),
+}
+
+/// What a zero or impossible date is stored as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Coerced {
+    Null,
+    // The date moved back to the last day of its month, or the ZERO_DATES sentinel, with its time
+    // of day.
+    Clamped(String),
+}
+
+pub fn rewrite(nodes: Vec<Node>, modes: DateModes, ansi_quotes: bool) -> Vec<Node> {
+    let cast = is_cast_argument(&nodes);
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let typed = |n: &Node| match n {
+            Node::Token(t) => TYPED_LITERALS.iter().any(|word| t.is_word(word)),
+            Node::Group(_) => false,
+        };
+        let previous = out.iter().rposition(|n| !n.is_trivia());
+        let dated = cast || previous.is_some_and(|at| typed(&out[at]));
+        let coerced = match &node {
+            Node::Token(Token::String(raw)) if dated && raw.starts_with('\'') => {
+                coerce(&literals::mysql_string_value(raw), modes)
+            }
+            Node::Token(Token::DoubleQuoted(raw)) if dated && !ansi_quotes => {
+                coerce(&literals::mysql_string_value(raw), modes)
+            }
+            _ => None,
+        };
+        match coerced {
+            None => out.push(node),
+            Some(Coerced::Clamped(date)) => {
+                out.push(Node::Token(Token::String(literals::pg_string(&date))))
+            }
+            Some(Coerced::Null) => {
+                // NULL takes the place of the whole typed literal.
+                if let Some(at) = previous.filter(|&at| typed(&out[at])) {
+                    out.truncate(at);
+                }
+                out.push(Node::Token(Token::Word("NULL".to_string())));
+            }
+        }
+    }
+    out
+}
+
+// Whether the nodes of a group are `'...' AS DATE`, the argument of a CAST to a date type.
+fn is_cast_argument(nodes: &[Node]) -> bool {
+    let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+    match significant[..] {
+        [Node::Token(Token::String(_) | Token::DoubleQuoted(_)), Node::Token(as_), Node::Token(target), ..] => {
+            as_.is_word("AS") && TYPED_LITERALS.iter().any(|word| target.is_word(word))
+        }
+        _ => false,
+    }
+}
+
+/// What `value` is stored as under `modes`, when it is a zero or impossible date PostgreSQL
+/// can't take. `None` for anything else, and for the dates strict mode refuses.
+pub fn coerce(value: &str, modes: DateModes) -> Option<Coerced> {
+    let (year, month, day, time) = split_date(value)?;
+    // PostgreSQL counts years from 1 AD, with no year 0.
+    if year > 0 && NaiveDate::from_ymd_opt(year, month, day).is_some() {
+        return None;
+    }
+    let zero = year == 0 && month == 0 && day == 0;
+    let refused = if zero {
+        modes.no_zero_date
+    } else if month == 0 || day == 0 {
+        modes.no_zero_in_date
+    } else if month <= 12 && day <= 31 {
+        if modes.allow_invalid_dates {
+            let last = (28..day)
+                .rev()
+                .find(|&d| NaiveDate::from_ymd_opt(year, month, d).is_some())?;
+            return Some(Coerced::Clamped(format!(
+                "{:04}-{:02}-{:02}{}",
+                year, month, last, time
+            )));
+        }
+        true
+    } else {
+        true
+    };
+    match (refused && modes.strict, modes.zero_dates) {
+        (true, _) => None,
+        (false, ZeroDates::Sentinel(sentinel)) if zero => Some(Coerced::Clamped(format!(
+            "{}{}",
+            sentinel.format("%Y-%m-%d"),
+            time
+        ))),
+        (false, _) => Some(Coerced::Null),
+    }
+}
+
+// `YYYY-MM-DD`, optionally followed by a time of day after a space or `T`: the year, month and
+// day, and the time with its separator.
+fn split_date(value: &str) -> Option<(i32, u32, u32, &str)> {
+    let (date, time) = value.split_at(value.find([' ', 'T']).unwrap_or(value.len()));
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return None;
+    };
+    let digits = |part: &str, lengths: std::ops::RangeInclusive<usize>| {
+        lengths.contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit())
+    };
+    if !digits(year, 4..=4) || !digits(month, 1..=2) || !digits(day, 1..=2) {
+        return None;
+    }
+    if !time.is_empty() {
+        let clock = &time[1..];
+        if !clock.contains(':')
+            || !clock
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b == b':' || b == b'.')
+        {
+            return None;
+        }
+    }
+    Some((
+        year.parse().ok()?,
+        month.parse().ok()?,
+        day.parse().ok()?,
+        time,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TranslationOptions, Translator};
+
+    fn translate(sql: &str, sql_mode: &str) -> String {
+        let options = TranslationOptions::default().sql_mode(sql_mode);
+        Translator::with_options(options).translate(sql).unwrap()
+    }
+
+    #[test]
+    fn coerces_typed_literals_and_casts_only() {
+        assert_eq!(
+            translate(
+                "SELECT '0000-00-00', CAST('2024-02-31' AS DATE), DATE '2024-02-31'",
+                ""
+            ),
+            "SELECT '0000-00-00', CAST(NULL AS DATE), NULL"
+        );
+    }
+
+    #[test]
+    fn strict_mode_leaves_refused_dates_for_postgresql() {
+        assert_eq!(
+            translate(
+                "SELECT DATE '0000-00-00'",
+                "STRICT_TRANS_TABLES,NO_ZERO_DATE"
+            ),
+            "SELECT DATE '0000-00-00'"
+        );
+    }
+
+    #[test]
+    fn coerces_zero_and_impossible_dates() {
+        let modes = DateModes::default();
+        assert_eq!(coerce("2024-01-31", modes), None);
+        assert_eq!(coerce("0000-00-00", modes), Some(Coerced::Null));
+        assert_eq!(coerce("2024-13-01", modes), Some(Coerced::Null));
+        let invalid = DateModes {
+            allow_invalid_dates: true,
+            ..modes
+        };
+        assert_eq!(
+            coerce("2024-02-31 10:00:00", invalid),
+            Some(Coerced::Clamped("2024-02-29 10:00:00".to_string()))
+        );
+        let sentinel = DateModes {
+            zero_dates: ZeroDates::Sentinel(NaiveDate::from_ymd_opt(1, 1, 1).unwrap()),
+            ..modes
+        };
+        assert_eq!(
+            coerce("0000-00-00", sentinel),
+            Some(Coerced::Clamped("0001-01-01".to_string()))
+        );
+    }
+
+    #[test]
+    fn coerces_by_the_modes_that_refuse_each_date() {
+        for (sql, sql_mode, postgres) in [
+            ("SELECT DATE '2024-00-15'", "", "SELECT NULL"),
+            (
+                "SELECT DATE '2024-00-15'",
+                "STRICT_ALL_TABLES,NO_ZERO_IN_DATE",
+                "SELECT DATE '2024-00-15'",
+            ),
+            // Strict mode without NO_ZERO_DATE still takes the zero date.
+            (
+                "SELECT DATE '0000-00-00'",
+                "STRICT_TRANS_TABLES",
+                "SELECT NULL",
+            ),
+            (
+                "SELECT TIMESTAMP '2023-02-29 23:59:59.5'",
+                "ALLOW_INVALID_DATES",
+                "SELECT TIMESTAMP '2023-02-28 23:59:59.5'",
+            ),
+            (
+                "SELECT DATETIME \"2024-04-31T08:00\"",
+                "ALLOW_INVALID_DATES",
+                "SELECT DATETIME '2024-04-30T08:00'",
+            ),
+            // Neither a date nor a typed literal.
+            (
+                "SELECT DATE '2024-02-31 tomorrow'",
+                "",
+                "SELECT DATE '2024-02-31 tomorrow'",
+            ),
+            (
+                "SELECT CAST('0000-00-00' AS CHAR)",
+                "",
+                "SELECT CAST('0000-00-00' AS CHAR)",
+            ),
+        ] {
+            assert_eq!(translate(sql, sql_mode), postgres, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn coerces_years_postgresql_has_not() {
+        let modes = DateModes::default();
+        assert_eq!(coerce("0000-01-15", modes), Some(Coerced::Null));
+        assert_eq!(coerce("0001-01-15", modes), None);
+        let sentinel = DateModes {
+            zero_dates: ZeroDates::Sentinel(NaiveDate::from_ymd_opt(1, 1, 1).unwrap()),
+            ..modes
+        };
+        assert_eq!(
+            coerce("0000-00-00 00:00:00", sentinel),
+            Some(Coerced::Clamped("0001-01-01 00:00:00".to_string()))
+        );
+    }
+
+    #[test]
+    fn only_sessions_taking_zero_dates_read_them() {
+        let modes = DateModes {
+            zero_dates: ZeroDates::NullAsZero,
+            ..DateModes::default()
+        };
+        assert_eq!(modes.zero_date_results(), ZeroDates::NullAsZero);
+        let no_zero_date = DateModes {
+            no_zero_date: true,
+            ..modes
+        };
+        assert_eq!(no_zero_date.zero_date_results(), ZeroDates::Null);
+    }
+}
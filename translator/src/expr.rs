@@ -0,0 +1,130 @@
+// Helpers for finding the operands of infix operators in a node list.
+//
+// These don't build a full expression tree; they recognise the operand shapes that show up in
+// practice: literals, (qualified) identifiers, function calls and parenthesized groups.
+
+use super::{Node, Token};
+
+/// Index of the last node that isn't whitespace or a comment.
+pub fn last_significant(nodes: &[Node]) -> Option<usize> {
+    nodes.iter().rposition(|n| !n.is_trivia())
+}
+
+fn significant_before(nodes: &[Node], end: usize) -> Option<usize> {
+    nodes[..end].iter().rposition(|n| !n.is_trivia())
+}
+
+fn is_multiplicative(node: &Node) -> bool {
+    match node {
+        Node::Token(t) => {
+            t.is_operator("*") || t.is_operator("/") || t.is_operator("%") || t.is_word("MOD")
+        }
+        Node::Group(_) => false,
+    }
+}
+
+// Keywords that can never be an operand, so `SELECT div FROM t` isn't mistaken for a division.
+const RESERVED: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "AND", "OR", "NOT", "XOR", "ON", "AS", "BY", "GROUP", "ORDER",
+    "HAVING", "LIMIT", "OFFSET", "SET", "VALUES", "INTO", "UPDATE", "DELETE", "INSERT", "JOIN",
+    "UNION", "CASE", "WHEN", "THEN", "ELSE", "END", "IS", "IN", "LIKE", "BETWEEN", "DISTINCT",
+    "WITH", "USING",
+];
+
+fn is_name(node: &Node) -> bool {
+    match node {
+        Node::Token(Token::Word(w)) => !RESERVED.iter().any(|r| w.eq_ignore_ascii_case(r)),
+        Node::Token(Token::QuotedIdent(_) | Token::DoubleQuoted(_)) => true,
+        _ => false,
+    }
+}
+
+/// Start of the single operand that ends right before `end` (ignoring trailing trivia).
+pub fn atom_start_before(nodes: &[Node], end: usize) -> Option<usize> {
+    let last = significant_before(nodes, end)?;
+    let mut start = match &nodes[last] {
+        Node::Group(_) => {
+            // A function call: the name has to be directly in front of the group.
+            match last.checked_sub(1).map(|p| &nodes[p]) {
+                Some(name) if is_name(name) => last - 1,
+                _ => return Some(last),
+            }
+        }
+        Node::Token(
+            Token::Number(_)
+            | Token::Hex(_)
+            | Token::Bit(_)
+            | Token::String(_)
+            | Token::Variable(_)
+            | Token::Placeholder,
+        ) => return Some(last),
+        node if is_name(node) => last,
+        _ => return None,
+    };
+    // Qualified names: schema.table.column
+    while start >= 2 {
+        let dot = &nodes[start - 1];
+        let part = &nodes[start - 2];
+        if matches!(dot, Node::Token(t) if t.is_operator(".")) && is_name(part) {
+            start -= 2;
+        } else {
+            break;
+        }
+    }
+    Some(start)
+}
+
+/// Start of the left-hand operand of a multiplicative operator placed at the end of `nodes`.
+///
+/// Multiplicative operators are left-associative, so for `a * b DIV c` the operand is `a * b`.
+pub fn left_operand_start(nodes: &[Node]) -> Option<usize> {
+    let mut start = atom_start_before(nodes, nodes.len())?;
+    while let Some(op) = significant_before(nodes, start) {
+        if !is_multiplicative(&nodes[op]) {
+            break;
+        }
+        match atom_start_before(nodes, op) {
+            Some(prev) => start = prev,
+            None => break,
+        }
+    }
+    Some(start)
+}
+
+/// End (exclusive) of the single operand starting at `from`, including a leading sign.
+pub fn right_operand_end(nodes: &[Node], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < nodes.len() && nodes[i].is_trivia() {
+        i += 1;
+    }
+    if matches!(nodes.get(i), Some(Node::Token(t)) if t.is_operator("-") || t.is_operator("+")) {
+        i += 1;
+    }
+
+    match nodes.get(i)? {
+        Node::Group(_) => Some(i + 1),
+        Node::Token(
+            Token::Number(_)
+            | Token::Hex(_)
+            | Token::Bit(_)
+            | Token::String(_)
+            | Token::Variable(_)
+            | Token::Placeholder,
+        ) => Some(i + 1),
+        node if is_name(node) => {
+            let mut end = i + 1;
+            loop {
+                match (nodes.get(end), nodes.get(end + 1)) {
+                    (Some(Node::Group(_)), _) => return Some(end + 1),
+                    (Some(Node::Token(dot)), Some(part))
+                        if dot.is_operator(".") && is_name(part) =>
+                    {
+                        end += 2;
+                    }
+                    _ => return Some(end),
+                }
+            }
+        }
+        _ => None,
+    }
+}
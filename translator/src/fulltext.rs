@@ -0,0 +1,432 @@
+// Full-text search: MATCH ... AGAINST and FULLTEXT indexes on PostgreSQL's tsvector.
+//
+//   MATCH(title, body) AGAINST('+rust -java' IN BOOLEAN MODE)
+//   -> to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(body, ''))
+//        @@ to_tsquery('simple', 'rust & !java')
+//
+// In a WHERE, HAVING or ON condition MATCH is a match test; anywhere else (the select list,
+// ORDER BY, or compared with a number) it is the relevance score, ts_rank(). MySQL's full-text
+// parser doesn't stem, so neither does the 'simple' configuration used here.
+//
+// FULLTEXT index definitions have no PostgreSQL counterpart inside CREATE TABLE and are removed
+// (see indexes.rs). With `fulltext_indexes` set each one becomes a GIN index on the same tsvector
+// expression that MATCH produces, so PostgreSQL can use it; `CREATE FULLTEXT INDEX` always does.
+
+use super::{
+    is_word, literals, parse_fragment, render, split_args, statement_starts_with, Node, Token,
+};
+
+const CONFIG: &str = "'simple'";
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["CREATE", "FULLTEXT", "INDEX"]) {
+        rewrite_create_index(nodes)
+    } else {
+        rewrite_matches(nodes, false)
+    }
+}
+
+// The tsvector of the given (comma-separated, MySQL syntax) columns. The index and the queries
+// have to use exactly this expression for the index to be usable.
+fn document(columns: &[Node]) -> Option<String> {
+    let columns: Vec<String> = split_args(columns)
+        .iter()
+        .map(|c| render(c).trim().to_string())
+        .collect();
+    if columns.is_empty() || columns.iter().any(String::is_empty) {
+        return None;
+    }
+    let parts: Vec<String> = columns
+        .iter()
+        .map(|c| format!("coalesce({}, '')", c))
+        .collect();
+    Some(format!(
+        "to_tsvector({}, {})",
+        CONFIG,
+        parts.join(" || ' ' || ")
+    ))
+}
+
+fn significant(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| !nodes[i].is_trivia())
+}
+
+// Words after which a MATCH is a condition rather than a score.
+const CONDITION_CLAUSES: &[&str] = &["WHERE", "HAVING", "ON", "WHEN"];
+const VALUE_CLAUSES: &[&str] = &[
+    "SELECT", "FROM", "GROUP", "ORDER", "LIMIT", "SET", "THEN", "ELSE", "VALUES",
+];
+const COMPARISONS: &[&str] = &["=", "<>", "!=", "<", ">", "<=", ">="];
+
+fn is_comparison(node: Option<&Node>) -> bool {
+    matches!(node, Some(Node::Token(t)) if COMPARISONS.iter().any(|op| t.is_operator(op)))
+}
+
+fn rewrite_matches(nodes: Vec<Node>, condition: bool) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut condition = condition;
+    let mut i = 0;
+
+    while i < nodes.len() {
+        match &nodes[i] {
+            Node::Group(inner) => {
+                // A subquery starts over in its select list; other groups are part of the
+                // surrounding expression.
+                let subquery = is_word(inner.iter().find(|n| !n.is_trivia()), "SELECT");
+                let inner = inner.clone();
+                out.push(Node::Group(rewrite_matches(inner, condition && !subquery)));
+                i += 1;
+                continue;
+            }
+            Node::Token(t) if CONDITION_CLAUSES.iter().any(|w| t.is_word(w)) => condition = true,
+            Node::Token(t) if VALUE_CLAUSES.iter().any(|w| t.is_word(w)) => condition = false,
+            _ => {}
+        }
+
+        if let Some((replacement, end)) = match_against(&nodes, i, condition, &out) {
+            out.extend(parse_fragment(&replacement));
+            i = end;
+        } else {
+            out.push(nodes[i].clone());
+            i += 1;
+        }
+    }
+
+    out
+}
+
+// `MATCH (cols) AGAINST (query [modifier])` starting at `nodes[i]`: its replacement and the
+// index after it.
+fn match_against(
+    nodes: &[Node],
+    i: usize,
+    condition: bool,
+    out: &[Node],
+) -> Option<(String, usize)> {
+    if !is_word(nodes.get(i), "MATCH") {
+        return None;
+    }
+    let columns_at = significant(nodes, i + 1)?;
+    let Node::Group(columns) = &nodes[columns_at] else {
+        return None;
+    };
+    let against_at = significant(nodes, columns_at + 1)?;
+    if !is_word(nodes.get(against_at), "AGAINST") {
+        return None;
+    }
+    let query_at = significant(nodes, against_at + 1)?;
+    let Node::Group(query) = &nodes[query_at] else {
+        return None;
+    };
+    let end = query_at + 1;
+
+    let document = document(columns)?;
+    let query = tsquery(query)?;
+    let compared = is_comparison(out.iter().rev().find(|n| !n.is_trivia()))
+        || is_comparison(significant(nodes, end).map(|j| &nodes[j]));
+    let replacement = if condition && !compared {
+        format!("({} @@ {})", document, query)
+    } else {
+        format!("ts_rank({}, {})", document, query)
+    };
+    Some((replacement, end))
+}
+
+// The tsquery for the contents of AGAINST(...).
+fn tsquery(against: &[Node]) -> Option<String> {
+    let positions: Vec<usize> = (0..against.len())
+        .filter(|&i| !against[i].is_trivia())
+        .collect();
+    let words: Vec<String> = positions
+        .iter()
+        .rev()
+        .map_while(|&i| match &against[i] {
+            Node::Token(Token::Word(w)) => Some(w.to_ascii_uppercase()),
+            _ => None,
+        })
+        .collect();
+    let ends_with = |modifier: &[&str]| {
+        words.len() >= modifier.len() && modifier.iter().rev().zip(&words).all(|(m, w)| m == w)
+    };
+    // The search modifier; query expansion is treated as plain natural language mode.
+    let (boolean, modifier_len) = if ends_with(&["IN", "BOOLEAN", "MODE"]) {
+        (true, 3)
+    } else if ends_with(&[
+        "IN",
+        "NATURAL",
+        "LANGUAGE",
+        "MODE",
+        "WITH",
+        "QUERY",
+        "EXPANSION",
+    ]) {
+        (false, 7)
+    } else if ends_with(&["IN", "NATURAL", "LANGUAGE", "MODE"]) {
+        (false, 4)
+    } else if ends_with(&["WITH", "QUERY", "EXPANSION"]) {
+        (false, 3)
+    } else {
+        (false, 0)
+    };
+    let expr = &positions[..positions.len().checked_sub(modifier_len)?];
+    let (&first, &last) = (expr.first()?, expr.last()?);
+
+    match &against[first..=last] {
+        [Node::Token(Token::String(raw) | Token::DoubleQuoted(raw))] => {
+            let text = literals::mysql_string_value(raw);
+            let query = if boolean {
+                boolean_query(&text)
+            } else {
+                natural_query(&text)
+            };
+            Some(format!("to_tsquery({}, '{}')", CONFIG, query))
+        }
+        expr => {
+            // Not a literal: leave the parsing to PostgreSQL. plainto_tsquery() requires every
+            // word, which is stricter than MySQL's natural language mode.
+            let function = if boolean {
+                "websearch_to_tsquery"
+            } else {
+                "plainto_tsquery"
+            };
+            Some(format!("{}({}, {})", function, CONFIG, render(expr)))
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The words of a search string, lower-cased, as tsquery lexemes.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !is_word_char(c))
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Natural language mode: rows matching any of the words.
+fn natural_query(text: &str) -> String {
+    words(text).join(" | ")
+}
+
+// Boolean mode: `+word` is required, `-word` excluded, `word*` a prefix, `"a phrase"` a phrase
+// and `(...)` a group of terms read the same way; other words are optional, and match on their
+// own when nothing is required. The ranking operators (`<`, `>`, `~`) aren't modelled.
+fn boolean_query(text: &str) -> String {
+    let mut required = Vec::new();
+    let mut optional = Vec::new();
+    let mut excluded = Vec::new();
+
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let list = match c {
+            '+' => {
+                chars.next();
+                &mut required
+            }
+            '-' => {
+                chars.next();
+                &mut excluded
+            }
+            c if c == '"' || c == '(' || is_word_char(c) => &mut optional,
+            _ => {
+                chars.next();
+                continue;
+            }
+        };
+        if let Some(term) = boolean_term(&mut chars) {
+            list.push(term);
+        }
+    }
+
+    let mut parts: Vec<String> = match (required.is_empty(), optional.is_empty()) {
+        (false, _) => required,
+        (true, false) => vec![format!("({})", optional.join(" | "))],
+        (true, true) => Vec::new(),
+    };
+    parts.extend(excluded.iter().map(|t| format!("!{}", t)));
+    parts.join(" & ")
+}
+
+// A word (with `*` for a prefix), a quoted phrase or a group.
+fn boolean_term(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    if chars.peek() == Some(&'(') {
+        chars.next();
+        let mut depth = 1;
+        let group: String = chars
+            .by_ref()
+            .take_while(|&c| {
+                depth += match c {
+                    '(' => 1,
+                    ')' => -1,
+                    _ => 0,
+                };
+                depth > 0
+            })
+            .collect();
+        let query = boolean_query(&group);
+        return (!query.is_empty()).then(|| format!("({})", query));
+    }
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+        let words = words(&phrase);
+        return match words.len() {
+            0 => None,
+            1 => Some(words[0].clone()),
+            _ => Some(format!("({})", words.join(" <-> "))),
+        };
+    }
+
+    let mut word = String::new();
+    while let Some(&c) = chars.peek().filter(|&&c| is_word_char(c)) {
+        word.push(c);
+        chars.next();
+    }
+    if word.is_empty() {
+        return None;
+    }
+    let word = word.to_lowercase();
+    if chars.peek() == Some(&'*') {
+        chars.next();
+        return Some(format!("{}:*", word));
+    }
+    Some(word)
+}
+
+// `CREATE INDEX` for a `FULLTEXT [INDEX | KEY] [name] (columns)` table item. PostgreSQL index
+// names are per schema rather than per table, so the table name is prefixed.
+pub fn fulltext_index(item: &[Node], table: &str, table_name: &str) -> Option<String> {
+    let significant: Vec<&Node> = item.iter().filter(|n| !n.is_trivia()).collect();
+    let mut rest = &significant[1..];
+    if is_word(rest.first().copied(), "INDEX") || is_word(rest.first().copied(), "KEY") {
+        rest = &rest[1..];
+    }
+    let (name, columns) = match rest {
+        [Node::Group(columns), ..] => ("fulltext".to_string(), columns),
+        [Node::Token(name), Node::Group(columns), ..] => {
+            (literals::identifier_name(name)?, columns)
+        }
+        _ => return None,
+    };
+    let name = format!("{}_{}", table_name, name);
+    Some(format!(
+        "CREATE INDEX IF NOT EXISTS `{}` ON {} USING gin ({})",
+        name.replace('`', "``"),
+        table,
+        document(columns)?
+    ))
+}
+
+// CREATE FULLTEXT INDEX name ON table (columns) -> CREATE INDEX name ON table USING gin (...)
+fn rewrite_create_index(nodes: Vec<Node>) -> Vec<Node> {
+    let Some(columns_at) = nodes.iter().position(|n| matches!(n, Node::Group(_))) else {
+        return nodes;
+    };
+    let Node::Group(columns) = &nodes[columns_at] else {
+        unreachable!("found a group");
+    };
+    let Some(document) = document(columns) else {
+        return nodes;
+    };
+    let Some(fulltext) = nodes.iter().position(|n| is_word(Some(n), "FULLTEXT")) else {
+        return nodes;
+    };
+    let mut head = nodes[..columns_at].to_vec();
+    head.remove(fulltext);
+    if head.get(fulltext).is_some_and(Node::is_trivia) {
+        head.remove(fulltext);
+    }
+    let sql = format!(
+        "{} USING gin ({}){}",
+        render(&head).trim_end(),
+        document,
+        render(&nodes[columns_at + 1..])
+    );
+    parse_fragment(&sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TranslationOptions, Translator};
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn match_is_a_test_in_conditions_and_a_score_elsewhere() {
+        assert_eq!(
+            translate(
+                "SELECT MATCH(title, body) AGAINST('+rust -java' IN BOOLEAN MODE) AS score FROM t \
+                 WHERE MATCH(title, body) AGAINST('+rust -java' IN BOOLEAN MODE)"
+            ),
+            "SELECT ts_rank(to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(body, '')), \
+             to_tsquery('simple', 'rust & !java')) AS score FROM t \
+             WHERE (to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(body, '')) \
+             @@ to_tsquery('simple', 'rust & !java'))"
+        );
+        assert_eq!(
+            translate("SELECT * FROM t WHERE MATCH (title, body) AGAINST ('rust proxy')"),
+            "SELECT * FROM t WHERE (to_tsvector('simple', coalesce(title, '') || ' ' || \
+             coalesce(body, '')) @@ to_tsquery('simple', 'rust | proxy'))"
+        );
+    }
+
+    #[test]
+    fn boolean_mode_phrases_prefixes_and_groups() {
+        let query = |against: &str| {
+            let sql = format!(
+                "SELECT * FROM t WHERE MATCH(b) AGAINST('{}' IN BOOLEAN MODE)",
+                against
+            );
+            let translated = translate(&sql);
+            let start = translated.find("to_tsquery('simple', '").unwrap() + 22;
+            translated[start..translated.len() - 3].to_string()
+        };
+        assert_eq!(
+            query("\"exact phrase\" rust*"),
+            "((exact <-> phrase) | rust:*)"
+        );
+        assert_eq!(query("+(a b) ~c <d >e"), "((a | b))");
+        assert_eq!(query("+rust -(java c)"), "rust & !((java | c))");
+        assert_eq!(query("it''s"), "(it | s)");
+    }
+
+    #[test]
+    fn modes_and_queries_that_arent_literals() {
+        assert_eq!(
+            translate("SELECT * FROM t WHERE MATCH(b) AGAINST('rust' WITH QUERY EXPANSION)"),
+            "SELECT * FROM t WHERE (to_tsvector('simple', coalesce(b, '')) @@ to_tsquery('simple', 'rust'))"
+        );
+        assert_eq!(
+            translate("SELECT * FROM t WHERE MATCH(b) AGAINST(? IN BOOLEAN MODE)"),
+            "SELECT * FROM t WHERE (to_tsvector('simple', coalesce(b, '')) @@ websearch_to_tsquery('simple', ?))"
+        );
+        assert_eq!(
+            translate("SELECT * FROM t WHERE MATCH(b) AGAINST('rust') > 0.5"),
+            "SELECT * FROM t WHERE ts_rank(to_tsvector('simple', coalesce(b, '')), to_tsquery('simple', 'rust')) > 0.5"
+        );
+    }
+
+    #[test]
+    fn fulltext_indexes_become_gin_indexes() {
+        assert_eq!(
+            translate("CREATE FULLTEXT INDEX ft ON t (title, body)"),
+            "CREATE INDEX ft ON t USING gin (to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(body, '')))"
+        );
+        let sql = "CREATE TABLE t (title TEXT, body TEXT, FULLTEXT KEY ft (title, body))";
+        assert_eq!(translate(sql), "CREATE TABLE t (title TEXT, body TEXT)");
+        let options = TranslationOptions {
+            fulltext_indexes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Translator::with_options(options).translate(sql).unwrap(),
+            "DO $indexes$ BEGIN CREATE TABLE t (title TEXT, body TEXT); CREATE INDEX IF NOT EXISTS \"t_ft\" \
+             ON t USING gin (to_tsvector('simple', coalesce(title, '') || ' ' || coalesce(body, ''))); END $indexes$"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+// Registry of MySQL functions that need rewriting for PostgreSQL.
+//
+// A mapping receives the already-translated arguments of a call and returns the replacement
+// expression, or `None` to leave the call untouched (e.g. when the arity doesn't match).
+
+use std::collections::HashMap;
+
+pub type FunctionMapping = Box<dyn Fn(&[String]) -> Option<String> + Send + Sync>;
+
+pub struct FunctionRegistry {
+    mappings: HashMap<String, FunctionMapping>,
+}
+
+impl FunctionRegistry {
+    /// An empty registry. Use `FunctionRegistry::default()` for one with the builtin mappings.
+    pub fn new() -> Self {
+        FunctionRegistry {
+            mappings: HashMap::new(),
+        }
+    }
+
+    /// Registers (or replaces) the mapping for `name`. Names are case-insensitive.
+    pub fn register<F>(&mut self, name: &str, mapping: F)
+    where
+        F: Fn(&[String]) -> Option<String> + Send + Sync + 'static,
+    {
+        self.mappings
+            .insert(name.to_ascii_uppercase(), Box::new(mapping));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FunctionMapping> {
+        self.mappings.get(&name.to_ascii_uppercase())
+    }
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        let mut registry = FunctionRegistry::new();
+        registry.register("CONCAT", concat);
+        registry.register("SUBSTRING_INDEX", substring_index);
+        registry.register("LOCATE", locate);
+        registry.register("INSTR", instr);
+        registry.register("LPAD", |args| pad("lpad", args));
+        registry.register("RPAD", |args| pad("rpad", args));
+        super::json::register(&mut registry);
+        super::sequences::register(&mut registry);
+        // CONCAT_WS needs no mapping: both databases skip NULL arguments and return NULL for a
+        // NULL separator.
+        registry
+    }
+}
+
+// MySQL's CONCAT() returns NULL as soon as one argument is NULL, whereas PostgreSQL's concat()
+// skips NULLs. The `||` operator has MySQL's semantics; the casts keep it working for
+// non-text arguments.
+fn concat(args: &[String]) -> Option<String> {
+    if args.is_empty() {
+        return None;
+    }
+    let parts: Vec<String> = args.iter().map(|a| format!("({})::text", a)).collect();
+    Some(format!("({})", parts.join(" || ")))
+}
+
+// SUBSTRING_INDEX(str, delim, count): everything before the count-th delimiter, counting from
+// the right when count is negative.
+fn substring_index(args: &[String]) -> Option<String> {
+    let [s, delim, count] = args else {
+        return None;
+    };
+
+    let parts = format!("string_to_array({}, {})", s, delim);
+    let from_left = |n: &str| format!("array_to_string(({})[1:{}], {})", parts, n, delim);
+    let from_right = |n: &str| {
+        format!(
+            "array_to_string(({parts})[greatest(array_length({parts}, 1) + ({n}) + 1, 1):], {delim})",
+            parts = parts,
+            n = n,
+            delim = delim,
+        )
+    };
+
+    match count.parse::<i64>() {
+        Ok(1) => Some(format!("split_part({}, {}, 1)", s, delim)),
+        Ok(0) => Some("''".to_string()),
+        Ok(n) if n > 0 => Some(from_left(count)),
+        Ok(_) => Some(from_right(count)),
+        Err(_) => Some(format!(
+            "(CASE WHEN ({count}) >= 0 THEN {left} ELSE {right} END)",
+            count = count,
+            left = from_left(count),
+            right = from_right(count),
+        )),
+    }
+}
+
+// LOCATE(needle, haystack [, pos]) takes its arguments in the opposite order of strpos() and
+// supports a start position.
+fn locate(args: &[String]) -> Option<String> {
+    match args {
+        [needle, haystack] => Some(format!("strpos({}, {})", haystack, needle)),
+        [needle, haystack, pos] => {
+            let found = format!("strpos(substr({}, {}), {})", haystack, pos, needle);
+            Some(format!(
+                "(CASE WHEN ({pos}) < 1 OR {found} = 0 THEN 0 ELSE {found} + ({pos}) - 1 END)",
+                pos = pos,
+                found = found,
+            ))
+        }
+        _ => None,
+    }
+}
+
+// INSTR(haystack, needle) is strpos() with a different name.
+fn instr(args: &[String]) -> Option<String> {
+    match args {
+        [haystack, needle] => Some(format!("strpos({}, {})", haystack, needle)),
+        _ => None,
+    }
+}
+
+// MySQL returns NULL for a negative length, and for an empty pad string when padding would be
+// needed; PostgreSQL returns '' and the unpadded string respectively.
+fn pad(func: &str, args: &[String]) -> Option<String> {
+    let [s, len, padstr] = args else {
+        return None;
+    };
+    Some(format!(
+        "(CASE WHEN ({len}) < 0 OR (({padstr}) = '' AND char_length({s}) < ({len})) THEN NULL \
+         ELSE {func}({s}, {len}, {padstr}) END)",
+        func = func,
+        s = s,
+        len = len,
+        padstr = padstr,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FunctionRegistry;
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn rewrites_registered_functions() {
+        assert_eq!(
+            translate("SELECT CONCAT(a, 'x', b), SUBSTRING_INDEX(s, ',', 1), LOCATE('a', s), INSTR(s, 'a')"),
+            "SELECT ((a)::text || ('x')::text || (b)::text), split_part(s, ',', 1), strpos(s, 'a'), strpos(s, 'a')"
+        );
+        assert_eq!(
+            translate("SELECT LPAD(s, 5, '0')"),
+            "SELECT (CASE WHEN (5) < 0 OR (('0') = '' AND char_length(s) < (5)) THEN NULL ELSE lpad(s, 5, '0') END)"
+        );
+    }
+
+    #[test]
+    fn substring_index_counts_from_the_end_and_locate_from_a_position() {
+        assert_eq!(
+            translate("SELECT SUBSTRING_INDEX(s, '.', -2)"),
+            "SELECT array_to_string((string_to_array(s, '.'))[greatest(array_length(string_to_array(s, '.'), 1) + (-2) + 1, 1):], '.')"
+        );
+        assert_eq!(
+            translate("SELECT SUBSTRING_INDEX(s, '.', n)"),
+            "SELECT (CASE WHEN (n) >= 0 THEN array_to_string((string_to_array(s, '.'))[1:n], '.') ELSE array_to_string((string_to_array(s, '.'))[greatest(array_length(string_to_array(s, '.'), 1) + (n) + 1, 1):], '.') END)"
+        );
+        assert_eq!(
+            translate("SELECT LOCATE('a', s, 3)"),
+            "SELECT (CASE WHEN (3) < 1 OR strpos(substr(s, 3), 'a') = 0 THEN 0 ELSE strpos(substr(s, 3), 'a') + (3) - 1 END)"
+        );
+    }
+
+    #[test]
+    fn concat_is_null_with_a_null_argument_and_concat_ws_is_not() {
+        assert_eq!(
+            translate("SELECT CONCAT(a, NULL), CONCAT_WS(',', a, NULL, b)"),
+            "SELECT ((a)::text || (NULL)::text), CONCAT_WS(',', a, NULL, b)"
+        );
+        assert_eq!(
+            translate("SELECT concat(locate('a', s), 'x')"),
+            "SELECT ((strpos(s, 'a'))::text || ('x')::text)"
+        );
+    }
+
+    #[test]
+    fn registered_mappings_are_found_by_any_case() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("Ifnull", |args| match args {
+            [a, b] => Some(format!("coalesce({}, {})", a, b)),
+            _ => None,
+        });
+        let mapping = registry.get("IFNULL").unwrap();
+        assert_eq!(
+            mapping(&["a".to_string(), "0".to_string()]),
+            Some("coalesce(a, 0)".to_string())
+        );
+        assert_eq!(mapping(&["a".to_string()]), None);
+        assert!(registry.get("NULLIF").is_none());
+    }
+
+    #[test]
+    fn table_names_are_not_calls() {
+        for sql in [
+            "INSERT INTO concat (a) VALUES (1)",
+            "SELECT * FROM locate (a)",
+            "SELECT s.concat(a) FROM t",
+        ] {
+            assert_eq!(translate(sql), sql);
+        }
+    }
+
+    #[test]
+    fn calls_after_from_in_arguments_are_calls() {
+        for (sql, expected) in [
+            (
+                "SELECT TRIM(BOTH ' ' FROM CONCAT(a, b)) FROM t",
+                "SELECT TRIM(BOTH ' ' FROM ((a)::text || (b)::text)) FROM t",
+            ),
+            (
+                "SELECT EXTRACT(YEAR FROM SUBSTRING_INDEX(s, ' ', 1)) FROM t",
+                "SELECT EXTRACT(YEAR FROM split_part(s, ' ', 1)) FROM t",
+            ),
+            (
+                "DELETE FROM concat WHERE a = 1",
+                "DELETE FROM concat WHERE a = 1",
+            ),
+        ] {
+            assert_eq!(translate(sql), expected, "{}", sql);
+        }
+    }
+}
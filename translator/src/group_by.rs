@@ -0,0 +1,305 @@
+// Grouped queries as MySQL runs them without ONLY_FULL_GROUP_BY, which lets one select a column
+// it doesn't group by, and gives any of the column's values in each group. PostgreSQL refuses
+// such a query, unless the column depends on a primary key it groups by, so the column is
+// aggregated to one of its values:
+//
+//   SELECT id, name, COUNT(*) FROM t GROUP BY id
+//       ->  SELECT id, (array_agg(name))[1] AS name, COUNT(*) FROM t GROUP BY id
+//   SELECT name, MAX(age) FROM t
+//       ->  SELECT (array_agg(name))[1] AS name, MAX(age) FROM t
+//
+// Only columns selected as they are, with or without their table, are aggregated. One in an
+// expression, or in HAVING or ORDER BY, is left for PostgreSQL to refuse.
+
+use super::{is_word, parse_fragment, render, Node, Token};
+
+// Aggregate functions, by MySQL's names and PostgreSQL's.
+const AGGREGATES: &[&str] = &[
+    "ARRAY_AGG",
+    "AVG",
+    "BIT_AND",
+    "BIT_OR",
+    "BIT_XOR",
+    "BOOL_AND",
+    "BOOL_OR",
+    "COUNT",
+    "EVERY",
+    "GROUP_CONCAT",
+    "JSON_AGG",
+    "JSON_ARRAYAGG",
+    "JSON_OBJECTAGG",
+    "JSONB_AGG",
+    "MAX",
+    "MIN",
+    "STD",
+    "STDDEV",
+    "STDDEV_POP",
+    "STDDEV_SAMP",
+    "STRING_AGG",
+    "SUM",
+    "VAR_POP",
+    "VAR_SAMP",
+    "VARIANCE",
+];
+
+// The modifiers that can come between SELECT and the first select item.
+const MODIFIERS: &[&str] = &[
+    "ALL",
+    "DISTINCT",
+    "DISTINCTROW",
+    "HIGH_PRIORITY",
+    "STRAIGHT_JOIN",
+    "SQL_SMALL_RESULT",
+    "SQL_BIG_RESULT",
+    "SQL_BUFFER_RESULT",
+    "SQL_NO_CACHE",
+    "SQL_CACHE",
+    "SQL_CALC_FOUND_ROWS",
+];
+
+// The clauses after GROUP BY.
+const AFTER_GROUP_BY: &[&str] = &[
+    "HAVING", "WINDOW", "ORDER", "LIMIT", "OFFSET", "FETCH", "FOR", "WITH", "ON",
+];
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    let selects: Vec<usize> = (0..nodes.len())
+        .filter(|&i| is_word(nodes.get(i), "SELECT"))
+        .collect();
+    // Each (start, end) of the nodes replaced, with what replaces them, in order.
+    let mut replacements: Vec<(usize, usize, Vec<Node>)> = Vec::new();
+    for (n, &select) in selects.iter().enumerate() {
+        let end = selects.get(n + 1).copied().unwrap_or(nodes.len());
+        let end = (select + 1..end)
+            .find(|&i| {
+                ["UNION", "INTERSECT", "EXCEPT"]
+                    .iter()
+                    .any(|word| is_word(nodes.get(i), word))
+                    || matches!(nodes[i], Node::Token(Token::Semicolon))
+            })
+            .unwrap_or(end);
+        replacements.extend(query(&nodes, select, end));
+    }
+    if replacements.is_empty() {
+        return nodes;
+    }
+    let mut out = Vec::with_capacity(nodes.len() + replacements.len() * 8);
+    let mut at = 0;
+    for (start, end, replacement) in replacements {
+        out.extend_from_slice(&nodes[at..start]);
+        out.extend(replacement);
+        at = end;
+    }
+    out.extend_from_slice(&nodes[at..]);
+    out
+}
+
+// The replacements of the ungrouped columns of the query between `select` and `end`.
+fn query(nodes: &[Node], select: usize, end: usize) -> Vec<(usize, usize, Vec<Node>)> {
+    let Some(from) = (select + 1..end).find(|&i| is_word(nodes.get(i), "FROM")) else {
+        return Vec::new();
+    };
+    let group_by = (from + 1..end).find(|&i| {
+        is_word(nodes.get(i), "GROUP")
+            && next_significant(nodes, i + 1).is_some_and(|by| is_word(nodes.get(by), "BY"))
+    });
+    let keys: Vec<String> = match group_by {
+        Some(group) => {
+            let start = next_significant(nodes, group + 1).unwrap_or(end) + 1;
+            let stop = (start..end)
+                .find(|&i| {
+                    AFTER_GROUP_BY
+                        .iter()
+                        .any(|word| is_word(nodes.get(i), word))
+                })
+                .unwrap_or(end);
+            nodes[start..stop]
+                .split(|n| matches!(n, Node::Token(Token::Comma)))
+                .map(normalized)
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let mut first = select + 1;
+    while let Some(i) = next_significant(nodes, first).filter(|&i| i < from) {
+        if !MODIFIERS.iter().any(|word| is_word(nodes.get(i), word)) {
+            break;
+        }
+        first = i + 1;
+    }
+    let mut items = Vec::new();
+    let mut start = first;
+    for i in (first..from).filter(|&i| matches!(nodes[i], Node::Token(Token::Comma))) {
+        items.push((start, i));
+        start = i + 1;
+    }
+    items.push((start, from));
+    // A query without GROUP BY is grouped by an aggregate it selects.
+    if group_by.is_none() && !items.iter().any(|&(s, e)| has_aggregate(&nodes[s..e])) {
+        return Vec::new();
+    }
+
+    let mut replacements = Vec::new();
+    for (position, &(start, end)) in items.iter().enumerate() {
+        let Some((column_start, column_end, alias)) = column(nodes, start, end) else {
+            continue;
+        };
+        let column = &nodes[column_start..column_end];
+        let name = normalized(column);
+        let last = name.rsplit('.').next().unwrap_or(&name).to_string();
+        let grouped = keys.iter().any(|key| {
+            *key == name
+                || *key == last
+                || key.rsplit('.').next() == Some(&name)
+                || alias.as_ref().is_some_and(|alias| key == alias)
+                || *key == (position + 1).to_string()
+        });
+        if grouped {
+            continue;
+        }
+        let mut sql = format!("(array_agg({}))[1]", render(column).trim());
+        if alias.is_none() {
+            // The column's name, as PostgreSQL would have called it.
+            if let Some(Node::Token(name)) = column.last() {
+                sql.push_str(&format!(" AS {}", name));
+            }
+        }
+        replacements.push((column_start, column_end, parse_fragment(&sql)));
+    }
+    replacements
+}
+
+// Where the column the select item between `start` and `end` is starts and ends, if it is one,
+// `name`, `table.name` or `schema.table.name`, and its alias, normalized.
+fn column(nodes: &[Node], start: usize, end: usize) -> Option<(usize, usize, Option<String>)> {
+    let significant: Vec<usize> = (start..end).filter(|&i| !nodes[i].is_trivia()).collect();
+    let is_name = |i: usize| match &nodes[i] {
+        Node::Token(Token::Word(word)) => !["NULL", "TRUE", "FALSE", "DISTINCT"]
+            .iter()
+            .any(|keyword| word.eq_ignore_ascii_case(keyword)),
+        Node::Token(Token::QuotedIdent(_) | Token::DoubleQuoted(_)) => true,
+        _ => false,
+    };
+    let mut length = 0;
+    loop {
+        if !significant.get(length).is_some_and(|&i| is_name(i)) || length > 4 {
+            return None;
+        }
+        length += 1;
+        match significant.get(length) {
+            Some(&dot) if matches!(&nodes[dot], Node::Token(t) if t.is_operator(".")) => {
+                length += 1
+            }
+            _ => break,
+        }
+    }
+    let alias = match &significant[length..] {
+        [] => None,
+        [alias] if is_name(*alias) => Some(normalized(&nodes[*alias..*alias + 1])),
+        [r#as, alias] if is_word(nodes.get(*r#as), "AS") => {
+            Some(normalized(&nodes[*alias..*alias + 1]))
+        }
+        _ => return None,
+    };
+    Some((significant[0], significant[length - 1] + 1, alias))
+}
+
+// Whether `nodes` call an aggregate function, rather than the window function of the same name.
+fn has_aggregate(nodes: &[Node]) -> bool {
+    (0..nodes.len()).any(|i| {
+        let Node::Token(Token::Word(word)) = &nodes[i] else {
+            return false;
+        };
+        AGGREGATES.iter().any(|a| a.eq_ignore_ascii_case(word))
+            && matches!(nodes.get(i + 1), Some(Node::Group(_)))
+            && !next_significant(nodes, i + 2).is_some_and(|over| is_word(nodes.get(over), "OVER"))
+    })
+}
+
+// An expression as written, in lower case and without whitespace or quotes, to compare.
+fn normalized(nodes: &[Node]) -> String {
+    render(nodes)
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '"' && *c != '`')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn next_significant(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| !nodes[i].is_trivia())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TranslationOptions, Translator};
+
+    #[test]
+    fn picks_a_value_for_columns_not_grouped_by() {
+        let translator = Translator::new();
+        assert_eq!(
+            translator
+                .translate("SELECT a, b FROM t GROUP BY a")
+                .unwrap(),
+            "SELECT a, (array_agg(b))[1] AS b FROM t GROUP BY a"
+        );
+        let sql = "SELECT a, COUNT(*) FROM t GROUP BY a";
+        assert_eq!(translator.translate(sql).unwrap(), sql);
+    }
+
+    #[test]
+    fn only_full_group_by_leaves_the_query_alone() {
+        let options = TranslationOptions::default().sql_mode("ONLY_FULL_GROUP_BY");
+        let sql = "SELECT a, b FROM t GROUP BY a";
+        assert_eq!(
+            Translator::with_options(options).translate(sql).unwrap(),
+            sql
+        );
+    }
+
+    #[test]
+    fn aggregates_selected_columns_only() {
+        let translator = Translator::new();
+        for (mysql, postgres) in [
+            (
+                "SELECT t.a, t.b, u.c FROM t JOIN u ON u.id = t.id GROUP BY t.a",
+                "SELECT t.a, (array_agg(t.b))[1] AS b, (array_agg(u.c))[1] AS c \
+                 FROM t JOIN u ON u.id = t.id GROUP BY t.a",
+            ),
+            (
+                "SELECT a, b AS bee FROM t GROUP BY A",
+                "SELECT a, (array_agg(b))[1] AS bee FROM t GROUP BY A",
+            ),
+            // By position.
+            (
+                "SELECT a, b FROM t GROUP BY 1",
+                "SELECT a, (array_agg(b))[1] AS b FROM t GROUP BY 1",
+            ),
+            (
+                "SELECT `a`, `b` FROM t GROUP BY `a`",
+                "SELECT \"a\", (array_agg(\"b\"))[1] AS \"b\" FROM t GROUP BY \"a\"",
+            ),
+            (
+                "SELECT x FROM (SELECT a, b FROM t GROUP BY a) s",
+                "SELECT x FROM (SELECT a, (array_agg(b))[1] AS b FROM t GROUP BY a) s",
+            ),
+            (
+                "SELECT a, b FROM t GROUP BY a UNION SELECT c, d FROM u GROUP BY c",
+                "SELECT a, (array_agg(b))[1] AS b FROM t GROUP BY a \
+                 UNION SELECT c, (array_agg(d))[1] AS d FROM u GROUP BY c",
+            ),
+            // Grouped by all, expressions and SELECT * are left to PostgreSQL.
+            (
+                "SELECT a, b FROM t GROUP BY a, b",
+                "SELECT a, b FROM t GROUP BY a, b",
+            ),
+            (
+                "SELECT a, b + 1 FROM t GROUP BY a",
+                "SELECT a, b + 1 FROM t GROUP BY a",
+            ),
+            ("SELECT * FROM t GROUP BY a", "SELECT * FROM t GROUP BY a"),
+        ] {
+            assert_eq!(translator.translate(mysql).unwrap(), postgres);
+        }
+    }
+}
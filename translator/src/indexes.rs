@@ -0,0 +1,234 @@
+// Index definitions inside CREATE TABLE.
+//
+// PostgreSQL's table body takes constraints but not indexes, so KEY and INDEX items are removed
+// and created with CREATE INDEX along with the table, in one statement:
+//
+//   CREATE TABLE t (id INT, v VARCHAR(200), KEY v_prefix (v(10)))
+//   -> DO $indexes$ BEGIN CREATE TABLE t (id INT, v VARCHAR(200));
+//        CREATE INDEX IF NOT EXISTS t_v_prefix ON t (v); END $indexes$
+//
+// PostgreSQL has no prefix indexes, so a column prefix length indexes the whole column. UNIQUE
+// KEY and UNIQUE INDEX are UNIQUE constraints. Index names are per schema rather than per table
+// in PostgreSQL, so the table name is prefixed; an unnamed index is named after its first column,
+// as MySQL does. FULLTEXT items are turned into GIN indexes by fulltext.rs.
+
+use super::table_ddl::{self, TableName};
+use super::{fulltext, is_word, literals, parse_fragment, render, split_args, Node, Token};
+
+pub fn rewrite(mut nodes: Vec<Node>, fulltext_indexes: bool) -> Vec<Node> {
+    let Some((name_start, name_end)) = table_ddl::created_table(&nodes) else {
+        return nodes;
+    };
+    let Some(body_at) = table_ddl::next_significant(&nodes, name_end) else {
+        return nodes;
+    };
+    let Node::Group(body) = &nodes[body_at] else {
+        return nodes;
+    };
+    let table = render(&nodes[name_start..name_end]).trim().to_string();
+    let Some(table_name) = TableName::parse(&nodes[name_start..name_end])
+        .and_then(|table| literals::identifier_name(&table.name))
+    else {
+        return nodes;
+    };
+
+    let mut kept: Vec<Vec<Node>> = Vec::new();
+    let mut indexes: Vec<String> = Vec::new();
+    let mut changed = false;
+    for item in body.split(|n| matches!(n, Node::Token(Token::Comma))) {
+        if is_word(item.iter().find(|n| !n.is_trivia()), "FULLTEXT") {
+            if fulltext_indexes {
+                indexes.extend(fulltext::fulltext_index(item, &table, &table_name));
+            }
+            changed = true;
+            continue;
+        }
+        match index_item(item) {
+            Some(index) if index.unique => {
+                let constraint = match &index.name {
+                    Some(name) => format!("CONSTRAINT {} ", quote(&table_name, name)),
+                    None => String::new(),
+                };
+                let mut unique: Vec<Node> =
+                    item.iter().take_while(|n| n.is_trivia()).cloned().collect();
+                unique.extend(parse_fragment(&format!(
+                    "{}UNIQUE ({})",
+                    constraint, index.columns
+                )));
+                kept.push(unique);
+                changed = true;
+            }
+            Some(index) => {
+                let name = index.name.as_deref().unwrap_or(&index.first_column);
+                indexes.push(format!(
+                    "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+                    quote(&table_name, name),
+                    table,
+                    index.columns
+                ));
+                changed = true;
+            }
+            None => kept.push(item.to_vec()),
+        }
+    }
+    if !changed {
+        return nodes;
+    }
+
+    let mut body = Vec::new();
+    for (i, item) in kept.into_iter().enumerate() {
+        if i > 0 {
+            body.push(Node::Token(Token::Comma));
+        }
+        body.extend(item);
+    }
+    nodes[body_at] = Node::Group(body);
+    if indexes.is_empty() {
+        return nodes;
+    }
+
+    // One statement, so the table and its indexes are created together.
+    let mut sql = format!("DO $indexes$ BEGIN {}", render(&nodes).trim());
+    for index in indexes {
+        sql.push_str("; ");
+        sql.push_str(&index);
+    }
+    sql.push_str("; END $indexes$");
+    parse_fragment(&sql)
+}
+
+// The name of an index on `table`, quoted MySQL style.
+fn quote(table: &str, name: &str) -> String {
+    format!("`{}`", format!("{}_{}", table, name).replace('`', "``"))
+}
+
+struct Index {
+    unique: bool,
+    name: Option<String>,
+    first_column: String,
+    // The key parts, as PostgreSQL index columns.
+    columns: String,
+}
+
+// A `{KEY | INDEX | UNIQUE [KEY | INDEX]} [name] [USING type] (key_parts) [options]` table item.
+// Index options (USING after the key parts, COMMENT, VISIBLE and so on) are dropped.
+fn index_item(item: &[Node]) -> Option<Index> {
+    let significant: Vec<&Node> = item.iter().filter(|n| !n.is_trivia()).collect();
+    let (unique, mut rest) = match significant.as_slice() {
+        [first, rest @ ..] if is_word(Some(first), "KEY") || is_word(Some(first), "INDEX") => {
+            (false, rest)
+        }
+        [first, rest @ ..] if is_word(Some(first), "UNIQUE") => (true, rest),
+        _ => return None,
+    };
+    if unique {
+        if let [first, tail @ ..] = rest {
+            if is_word(Some(first), "KEY") || is_word(Some(first), "INDEX") {
+                rest = tail;
+            }
+        }
+    }
+    let mut name = None;
+    if let [Node::Token(t), tail @ ..] = rest {
+        if !t.is_word("USING") {
+            name = Some(literals::identifier_name(t)?);
+            rest = tail;
+        }
+    }
+    if let [using, _, tail @ ..] = rest {
+        if is_word(Some(using), "USING") {
+            rest = tail;
+        }
+    }
+    let [Node::Group(key_parts), ..] = rest else {
+        return None;
+    };
+    let (first_column, columns) = key_parts_columns(key_parts)?;
+    Some(Index {
+        unique,
+        name,
+        first_column,
+        columns,
+    })
+}
+
+// `col [(length)] [ASC | DESC]` key parts, without the prefix lengths. Anything else, like the
+// `INT(11)` of a column named `key`, isn't an index.
+fn key_parts_columns(key_parts: &[Node]) -> Option<(String, String)> {
+    let mut first_column = None;
+    let mut columns = Vec::new();
+    for part in split_args(key_parts) {
+        let significant: Vec<&Node> = part.iter().filter(|n| !n.is_trivia()).collect();
+        let (column, order) = match significant.as_slice() {
+            [Node::Token(column), rest @ ..] => (column, rest),
+            _ => return None,
+        };
+        let name = literals::identifier_name(column)?;
+        let order = match order {
+            [Node::Group(length), order @ ..]
+                if matches!(length.as_slice(), [Node::Token(Token::Number(_))]) =>
+            {
+                order
+            }
+            order => order,
+        };
+        let column = render(&[Node::Token((*column).clone())]);
+        match order {
+            [] => columns.push(column),
+            [Node::Token(t)] if t.is_word("ASC") || t.is_word("DESC") => {
+                columns.push(format!("{} {}", column, render(&[Node::Token(t.clone())])))
+            }
+            _ => return None,
+        }
+        first_column.get_or_insert(name);
+    }
+    Some((first_column?, columns.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    #[test]
+    fn creates_the_indexes_declared_in_create_table() {
+        assert_eq!(
+            Translator::new()
+                .translate("CREATE TABLE t (a INT, b TEXT, INDEX idx_a (a), UNIQUE KEY (b))")
+                .unwrap(),
+            "DO $indexes$ BEGIN CREATE TABLE t (a INT, b TEXT, UNIQUE (b)); \
+             CREATE INDEX IF NOT EXISTS \"t_idx_a\" ON t (a); END $indexes$"
+        );
+    }
+
+    #[test]
+    fn indexes_temporary_and_qualified_tables() {
+        let translator = Translator::new();
+        assert_eq!(
+            translator
+                .translate("CREATE TEMPORARY TABLE t (a INT, KEY (a)) ENGINE=MEMORY")
+                .unwrap(),
+            "DO $indexes$ BEGIN CREATE TEMPORARY TABLE t (a INT); \
+             CREATE INDEX IF NOT EXISTS \"t_a\" ON t (a); END $indexes$"
+        );
+        assert_eq!(
+            translator
+                .translate(
+                    "CREATE TABLE s.t (a INT, b TEXT, KEY (a, b(5)) USING BTREE, INDEX `i x` (b DESC))"
+                )
+                .unwrap(),
+            "DO $indexes$ BEGIN CREATE TABLE s.t (a INT, b TEXT); \
+             CREATE INDEX IF NOT EXISTS \"t_a\" ON s.t (a, b); \
+             CREATE INDEX IF NOT EXISTS \"t_i x\" ON s.t (b DESC); END $indexes$"
+        );
+        // Constraints stay in the table body, and an option's comma doesn't split the item.
+        assert_eq!(
+            translator
+                .translate(
+                    "CREATE TABLE t (a INT, PRIMARY KEY (a), UNIQUE INDEX u (a), KEY k (a) COMMENT 'c, d')"
+                )
+                .unwrap(),
+            "DO $indexes$ BEGIN CREATE TABLE t (a INT, PRIMARY KEY (a), CONSTRAINT \"t_u\" UNIQUE (a)); \
+             CREATE INDEX IF NOT EXISTS \"t_k\" ON t (a); END $indexes$"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// MySQL's `INSERT ... SET` form.
+//
+// `INSERT INTO t SET a = 1, b = 'x'` is MySQL's alternative to a column list and VALUES, and
+// some PHP frameworks generate nothing else. It is rewritten to the standard form:
+//
+//   INSERT INTO t (a, b) VALUES (1, 'x')
+//
+// Anything after the assignments (ON DUPLICATE KEY UPDATE, a row alias) is kept as it was.
+
+use super::{parse_fragment, split_args, statement_starts_with, Node, Token};
+
+// Top-level words that end the assignment list.
+const TERMINATORS: &[&str] = &["ON", "AS", "RETURNING"];
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    if !statement_starts_with(&nodes, &["INSERT"]) && !statement_starts_with(&nodes, &["REPLACE"]) {
+        return nodes;
+    }
+    // SET has to come before anything that starts the other forms.
+    let Some(set) = nodes.iter().position(|n| {
+        matches!(n, Node::Token(t) if t.is_word("SET") || t.is_word("VALUES")
+            || t.is_word("VALUE") || t.is_word("SELECT"))
+            || matches!(n, Node::Group(_))
+    }) else {
+        return nodes;
+    };
+    if !matches!(&nodes[set], Node::Token(t) if t.is_word("SET")) {
+        return nodes;
+    }
+    let end = nodes[set + 1..]
+        .iter()
+        .position(|n| matches!(n, Node::Token(t) if TERMINATORS.iter().any(|w| t.is_word(w))))
+        .map_or(nodes.len(), |i| set + 1 + i);
+
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for assignment in split_args(&nodes[set + 1..end]) {
+        let Some(eq) = assignment
+            .iter()
+            .position(|n| matches!(n, Node::Token(t) if t.is_operator("=")))
+        else {
+            return nodes;
+        };
+        let column = trim(&assignment[..eq]);
+        let value = trim(&assignment[eq + 1..]);
+        if column.is_empty() || value.is_empty() {
+            return nodes;
+        }
+        columns.push(column.to_vec());
+        values.push(value.to_vec());
+    }
+    if columns.is_empty() {
+        return nodes;
+    }
+
+    let mut out: Vec<Node> = nodes[..set].to_vec();
+    out.push(Node::Group(join(columns)));
+    out.extend(parse_fragment(" VALUES "));
+    out.push(Node::Group(join(values)));
+    if end < nodes.len() {
+        out.push(Node::Token(Token::Whitespace(" ".to_string())));
+        out.extend_from_slice(&nodes[end..]);
+    }
+    out
+}
+
+// `nodes` without leading and trailing whitespace and comments.
+fn trim(nodes: &[Node]) -> &[Node] {
+    let start = nodes
+        .iter()
+        .position(|n| !n.is_trivia())
+        .unwrap_or(nodes.len());
+    let end = nodes
+        .iter()
+        .rposition(|n| !n.is_trivia())
+        .map_or(start, |i| i + 1);
+    &nodes[start..end]
+}
+
+// Comma-separated list of the given items.
+fn join(items: Vec<Vec<Node>>) -> Vec<Node> {
+    let mut out = Vec::new();
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            out.push(Node::Token(Token::Comma));
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+        }
+        out.extend(item);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    #[test]
+    fn insert_set_becomes_a_values_list() {
+        assert_eq!(
+            Translator::new()
+                .translate("INSERT INTO t SET a = 1, b = 'x'")
+                .unwrap(),
+            "INSERT INTO t (a, b) VALUES (1, 'x')"
+        );
+    }
+
+    #[test]
+    fn values_keep_their_commas_and_what_follows_is_kept() {
+        let translator = Translator::new();
+        for (sql, expected) in [
+            (
+                "INSERT INTO s.t SET `a` = CONCAT('x', 'y'), b = (SELECT 1), c = b = 1",
+                "INSERT INTO s.t (\"a\", b, c) VALUES ((('x')::text || ('y')::text), (SELECT 1), b = 1)",
+            ),
+            (
+                "INSERT IGNORE INTO t SET a = 1, b = 2 ON DUPLICATE KEY UPDATE b = 3",
+                "INSERT IGNORE INTO t (a, b) VALUES (1, 2) ON DUPLICATE KEY UPDATE b = 3",
+            ),
+            (
+                "REPLACE INTO t SET a = 'SET b = 1' RETURNING id",
+                "REPLACE INTO t (a) VALUES ('SET b = 1') RETURNING id",
+            ),
+        ] {
+            assert_eq!(translator.translate(sql).unwrap(), expected);
+        }
+        let sql = "INSERT INTO t (a) SELECT a FROM u";
+        assert_eq!(translator.translate(sql).unwrap(), sql);
+    }
+}
@@ -0,0 +1,279 @@
+// MySQL JSON functions and path expressions on PostgreSQL's jsonb.
+//
+// MySQL addresses parts of a document with path strings (`'$.address.city'`, `'$.tags[0]'`);
+// the jsonb equivalents are the `#>`/`#>>` operators with a text array of keys and indexes:
+//
+//   JSON_EXTRACT(doc, '$.a.b[0]')  ->  ((doc)::jsonb #> '{a,b,0}')
+//   doc->>'$.a'                    ->  ((doc)::jsonb #>> '{a}')
+//   JSON_EXTRACT(doc, '$.a', '$.b')  ->  the values found at either, as a jsonb array
+//
+// Arguments are cast to jsonb so the same SQL works on json, jsonb and text columns. Paths with
+// wildcards (`$.*`, `$**.a`) and ranges have no such equivalent and are left untranslated.
+
+use super::functions::FunctionRegistry;
+use super::literals;
+
+pub fn register(registry: &mut FunctionRegistry) {
+    registry.register("JSON_EXTRACT", json_extract);
+    registry.register("JSON_UNQUOTE", |args| match args {
+        [json] => Some(format!("(({})::jsonb #>> '{{}}')", json)),
+        _ => None,
+    });
+    registry.register("JSON_OBJECT", |args| {
+        Some(format!("jsonb_build_object({})", args.join(", ")))
+    });
+    registry.register("JSON_ARRAY", |args| {
+        Some(format!("jsonb_build_array({})", args.join(", ")))
+    });
+    registry.register("JSON_CONTAINS", json_contains);
+    registry.register("JSON_CONTAINS_PATH", json_contains_path);
+    registry.register("JSON_LENGTH", json_length);
+    registry.register("JSON_KEYS", json_keys);
+}
+
+/// `(json)::jsonb #> path`, or `#>>` when `unquote` is set, for a MySQL path literal as
+/// translated (`'$.a.b'`). `None` if the path can't be expressed that way.
+pub fn extract(json: &str, path_literal: &str, unquote: bool) -> Option<String> {
+    let keys = path(&literals::pg_string_value(path_literal)?)?;
+    let op = if unquote { "#>>" } else { "#>" };
+    Some(format!("(({})::jsonb {} {})", json, op, keys))
+}
+
+// JSON_EXTRACT with several paths returns an array of the matches; only the single-path form
+// is translated.
+// With several paths, MySQL gives an array of the values found, or NULL when there are none.
+fn json_extract(args: &[String]) -> Option<String> {
+    match args {
+        [json, path] => extract(json, path, false),
+        [json, paths @ ..] if !paths.is_empty() => {
+            let values: Option<Vec<String>> = paths
+                .iter()
+                .map(|path| extract(json, path, false))
+                .collect();
+            Some(format!(
+                "(SELECT jsonb_agg(v) FROM unnest(ARRAY[{}]) AS v WHERE v IS NOT NULL)",
+                values?.join(", ")
+            ))
+        }
+        _ => None,
+    }
+}
+
+// The value at `path` (or the document itself) as jsonb.
+fn target(json: &str, path: Option<&String>) -> Option<String> {
+    match path {
+        Some(path) => extract(json, path, false),
+        None => Some(format!("({})::jsonb", json)),
+    }
+}
+
+fn json_contains(args: &[String]) -> Option<String> {
+    let (json, candidate, path) = match args {
+        [json, candidate] => (json, candidate, None),
+        [json, candidate, path] => (json, candidate, Some(path)),
+        _ => return None,
+    };
+    Some(format!(
+        "({} @> ({})::jsonb)",
+        target(json, path)?,
+        candidate
+    ))
+}
+
+// JSON_CONTAINS_PATH(doc, 'one' | 'all', path, ...)
+fn json_contains_path(args: &[String]) -> Option<String> {
+    let [json, mode, paths @ ..] = args else {
+        return None;
+    };
+    if paths.is_empty() {
+        return None;
+    }
+    let joiner = match literals::pg_string_value(mode)?
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "one" => " OR ",
+        "all" => " AND ",
+        _ => return None,
+    };
+    let checks = paths
+        .iter()
+        .map(|path| Some(format!("{} IS NOT NULL", extract(json, path, false)?)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("({})", checks.join(joiner)))
+}
+
+// Number of elements of an array, keys of an object, or 1 for a scalar; NULL when the path
+// doesn't exist.
+fn json_length(args: &[String]) -> Option<String> {
+    let j = match args {
+        [json] => target(json, None)?,
+        [json, path] => target(json, Some(path))?,
+        _ => return None,
+    };
+    Some(format!(
+        "(CASE WHEN {j} IS NULL THEN NULL \
+         WHEN jsonb_typeof({j}) = 'array' THEN jsonb_array_length({j}) \
+         WHEN jsonb_typeof({j}) = 'object' THEN (SELECT count(*)::int FROM jsonb_object_keys({j})) \
+         ELSE 1 END)",
+        j = j
+    ))
+}
+
+// The keys of an object as a JSON array; NULL for anything else, as in MySQL.
+fn json_keys(args: &[String]) -> Option<String> {
+    let j = match args {
+        [json] => target(json, None)?,
+        [json, path] => target(json, Some(path))?,
+        _ => return None,
+    };
+    Some(format!(
+        "(CASE WHEN jsonb_typeof({j}) = 'object' \
+         THEN (SELECT coalesce(jsonb_agg(k), '[]'::jsonb) FROM jsonb_object_keys({j}) AS k) END)",
+        j = j
+    ))
+}
+
+// Converts a MySQL path (`$.a."b c"[2]`) into a text-array literal of keys and indexes
+// (`'{a,"b c",2}'`). `[last]` becomes -1, which jsonb counts from the end like MySQL.
+fn path(mysql_path: &str) -> Option<String> {
+    let mut rest = mysql_path.trim().strip_prefix('$')?;
+    let mut keys: Vec<String> = Vec::new();
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('.') {
+            let after = after.trim_start();
+            if let Some(quoted) = after.strip_prefix('"') {
+                let mut key = String::new();
+                let mut chars = quoted.char_indices();
+                let end = loop {
+                    match chars.next()? {
+                        (i, '"') => break i,
+                        (_, '\\') => key.push(chars.next()?.1),
+                        (_, c) => key.push(c),
+                    }
+                };
+                keys.push(key);
+                rest = &quoted[end + 1..];
+            } else {
+                let end = after
+                    .find(|c: char| c == '.' || c == '[' || c.is_whitespace())
+                    .unwrap_or(after.len());
+                let key = &after[..end];
+                if key.is_empty() || key.contains('*') {
+                    return None;
+                }
+                keys.push(key.to_string());
+                rest = &after[end..];
+            }
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let index = after[..end].trim();
+            let index = if let Ok(n) = index.parse::<u32>() {
+                n.to_string()
+            } else if index == "last" {
+                "-1".to_string()
+            } else {
+                // `[last - n]`; wildcards and ranges (`[*]`, `[1 to 3]`) aren't supported.
+                let n: i64 = index
+                    .strip_prefix("last")?
+                    .trim_start()
+                    .strip_prefix('-')?
+                    .trim()
+                    .parse()
+                    .ok()?;
+                (-1 - n).to_string()
+            };
+            keys.push(index);
+            rest = &after[end + 1..];
+        } else if rest.is_empty() {
+            break;
+        } else {
+            return None;
+        }
+    }
+
+    let elements: Vec<String> = keys.iter().map(|k| array_element(k)).collect();
+    Some(literals::pg_string(&format!("{{{}}}", elements.join(","))))
+}
+
+// Quotes an element of a PostgreSQL array literal when it needs it.
+fn array_element(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value.eq_ignore_ascii_case("null")
+        && !value
+            .chars()
+            .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || c.is_whitespace());
+    if plain {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn paths_become_jsonb_path_operators() {
+        assert_eq!(
+            translate("SELECT JSON_EXTRACT(doc, '$.a.b'), doc->>'$.c'"),
+            "SELECT ((doc)::jsonb #> '{a,b}'), ((doc)::jsonb #>> '{c}')"
+        );
+        assert_eq!(
+            translate("SELECT JSON_CONTAINS(doc, '1', '$.a')"),
+            "SELECT (((doc)::jsonb #> '{a}') @> ('1')::jsonb)"
+        );
+    }
+
+    #[test]
+    fn paths_with_indexes_quoted_keys_or_several_paths() {
+        assert_eq!(
+            translate("SELECT JSON_EXTRACT(doc, '$.tags[0]'), JSON_EXTRACT(doc, '$.\"a b\".c'), JSON_EXTRACT(doc, '$')"),
+            "SELECT ((doc)::jsonb #> '{tags,0}'), ((doc)::jsonb #> '{\"a b\",c}'), ((doc)::jsonb #> '{}')"
+        );
+        assert_eq!(
+            translate("SELECT JSON_EXTRACT(doc, '$.a', '$.b')"),
+            "SELECT (SELECT jsonb_agg(v) FROM unnest(ARRAY[((doc)::jsonb #> '{a}'), ((doc)::jsonb #> '{b}')]) AS v WHERE v IS NOT NULL)"
+        );
+        assert_eq!(
+            translate("SELECT JSON_UNQUOTE(doc->'$.a')"),
+            "SELECT ((((doc)::jsonb #> '{a}'))::jsonb #>> '{}')"
+        );
+    }
+
+    #[test]
+    fn leaves_paths_jsonb_has_no_operator_for() {
+        for sql in [
+            "SELECT JSON_EXTRACT(doc, '$.*'), JSON_EXTRACT(doc, '$**.a'), JSON_EXTRACT(doc, '$[1 to 2]')",
+            "SELECT JSON_EXTRACT(doc, path), JSON_EXTRACT(doc, '$.a', path)",
+            "SELECT doc->'a'",
+        ] {
+            assert_eq!(translate(sql), sql);
+        }
+    }
+
+    #[test]
+    fn contains_path_with_one_or_all() {
+        assert_eq!(
+            translate("SELECT JSON_CONTAINS_PATH(doc, 'one', '$.a', '$.b'), JSON_CONTAINS_PATH(doc, 'all', '$.a', '$.b')"),
+            "SELECT (((doc)::jsonb #> '{a}') IS NOT NULL OR ((doc)::jsonb #> '{b}') IS NOT NULL), \
+             (((doc)::jsonb #> '{a}') IS NOT NULL AND ((doc)::jsonb #> '{b}') IS NOT NULL)"
+        );
+    }
+
+    #[test]
+    fn json_keys_of_an_object() {
+        assert_eq!(
+            translate("SELECT JSON_KEYS(doc)"),
+            "SELECT (CASE WHEN jsonb_typeof((doc)::jsonb) = 'object' THEN (SELECT coalesce(jsonb_agg(k), '[]'::jsonb) \
+             FROM jsonb_object_keys((doc)::jsonb) AS k) END)"
+        );
+    }
+}
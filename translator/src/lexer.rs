@@ -0,0 +1,311 @@
+// Tokenizer for MySQL statements.
+//
+// The lexer keeps the exact source text of every token (including whitespace and comments) so
+// that a statement which needs no rewriting renders back byte-for-byte identical.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Whitespace(String),
+    Comment(String),
+    // Unquoted identifiers and keywords.
+    Word(String),
+    // `backtick quoted` identifier, stored with its backticks.
+    QuotedIdent(String),
+    // 'single quoted' string literal, stored with its quotes. Once translated this may be a
+    // PostgreSQL E'' or B'' literal.
+    String(String),
+    // "double quoted" text, stored with its quotes. Depending on the sql_mode this is either a
+    // string literal or an identifier.
+    DoubleQuoted(String),
+    Number(String),
+    // X'4D79' or 0x4D79 hexadecimal literal, as written.
+    Hex(String),
+    // b'1010' or 0b1010 bit-value literal, as written.
+    Bit(String),
+    // @user_variable or @@system_variable.
+    Variable(String),
+    Placeholder,
+    LParen,
+    RParen,
+    Comma,
+    Semicolon,
+    Operator(String),
+}
+
+impl Token {
+    /// True for tokens which carry no meaning for the statement (whitespace and comments).
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace(_) | Token::Comment(_))
+    }
+
+    /// Case-insensitive check for an unquoted keyword/identifier.
+    pub fn is_word(&self, word: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(word))
+    }
+
+    pub fn is_operator(&self, op: &str) -> bool {
+        matches!(self, Token::Operator(o) if o == op)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Whitespace(s)
+            | Token::Comment(s)
+            | Token::Word(s)
+            | Token::QuotedIdent(s)
+            | Token::String(s)
+            | Token::DoubleQuoted(s)
+            | Token::Number(s)
+            | Token::Hex(s)
+            | Token::Bit(s)
+            | Token::Variable(s)
+            | Token::Operator(s) => f.write_str(s),
+            Token::Placeholder => f.write_str("?"),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+            Token::Comma => f.write_str(","),
+            Token::Semicolon => f.write_str(";"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub message: String,
+    // Byte offset into the statement where the problem starts.
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+// Operators that are longer than one character, longest first so that `<=>` wins over `<=`.
+const MULTI_CHAR_OPERATORS: &[&str] = &[
+    "<=>", "->>", "<=", ">=", "<>", "!=", "||", "&&", "->", ":=", "<<", ">>", "::",
+];
+
+pub fn tokenize(sql: &str) -> Result<Vec<Token>, LexError> {
+    tokenize_with(sql, true)
+}
+
+/// Tokenizes `sql` with backslashes escaping the next character of a string or not, as they
+/// don't under NO_BACKSLASH_ESCAPES.
+pub fn tokenize_with(sql: &str, backslash_escapes: bool) -> Result<Vec<Token>, LexError> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let c = bytes[pos];
+
+        let token = if c.is_ascii_whitespace() {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            Token::Whitespace(sql[start..pos].to_string())
+        } else if c == b'#' || starts_line_comment(bytes, pos) {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            Token::Comment(sql[start..pos].to_string())
+        } else if c == b'/' && bytes.get(pos + 1) == Some(&b'*') {
+            pos = match sql[pos + 2..].find("*/") {
+                Some(end) => pos + 2 + end + 2,
+                None => return Err(error("unterminated comment", start)),
+            };
+            Token::Comment(sql[start..pos].to_string())
+        } else if c == b'\'' {
+            pos = quoted_end(bytes, pos, b'\'', backslash_escapes)
+                .ok_or_else(|| error("unterminated string literal", start))?;
+            Token::String(sql[start..pos].to_string())
+        } else if c == b'"' {
+            pos = quoted_end(bytes, pos, b'"', backslash_escapes)
+                .ok_or_else(|| error("unterminated double-quoted string", start))?;
+            Token::DoubleQuoted(sql[start..pos].to_string())
+        } else if c == b'`' {
+            pos = quoted_end(bytes, pos, b'`', false)
+                .ok_or_else(|| error("unterminated quoted identifier", start))?;
+            Token::QuotedIdent(sql[start..pos].to_string())
+        } else if let Some(end) = prefixed_literal_end(bytes, pos) {
+            pos = end;
+            let raw = sql[start..pos].to_string();
+            if matches!(bytes[start], b'x' | b'X') || bytes[start + 1].eq_ignore_ascii_case(&b'x') {
+                Token::Hex(raw)
+            } else {
+                Token::Bit(raw)
+            }
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            pos = number_end(bytes, pos);
+            Token::Number(sql[start..pos].to_string())
+        } else if c == b'@' {
+            pos += 1;
+            if bytes.get(pos) == Some(&b'@') {
+                pos += 1;
+            }
+            match bytes.get(pos) {
+                Some(q @ (b'\'' | b'"' | b'`')) => {
+                    pos = quoted_end(bytes, pos, *q, *q != b'`')
+                        .ok_or_else(|| error("unterminated variable name", start))?;
+                }
+                _ => {
+                    while pos < bytes.len() && (is_word_byte(bytes[pos]) || bytes[pos] == b'.') {
+                        pos += 1;
+                    }
+                }
+            }
+            Token::Variable(sql[start..pos].to_string())
+        } else if is_word_byte(c) {
+            while pos < bytes.len() && is_word_byte(bytes[pos]) {
+                pos += 1;
+            }
+            Token::Word(sql[start..pos].to_string())
+        } else {
+            match c {
+                b'(' => {
+                    pos += 1;
+                    Token::LParen
+                }
+                b')' => {
+                    pos += 1;
+                    Token::RParen
+                }
+                b',' => {
+                    pos += 1;
+                    Token::Comma
+                }
+                b';' => {
+                    pos += 1;
+                    Token::Semicolon
+                }
+                b'?' => {
+                    pos += 1;
+                    Token::Placeholder
+                }
+                _ => {
+                    let op = MULTI_CHAR_OPERATORS
+                        .iter()
+                        .find(|op| sql[pos..].starts_with(**op))
+                        .map(|op| op.len())
+                        .unwrap_or_else(|| char_len(sql, pos));
+                    pos += op;
+                    Token::Operator(sql[start..pos].to_string())
+                }
+            }
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn error(message: &str, offset: usize) -> LexError {
+    LexError {
+        message: message.to_string(),
+        offset,
+    }
+}
+
+// MySQL only treats `--` as a comment when it is followed by whitespace (or the end of input).
+fn starts_line_comment(bytes: &[u8], pos: usize) -> bool {
+    bytes[pos] == b'-'
+        && bytes.get(pos + 1) == Some(&b'-')
+        && bytes.get(pos + 2).is_none_or(|b| b.is_ascii_whitespace())
+}
+
+// Returns the position just past the closing quote. Doubled quotes are always an escaped quote;
+// backslash escapes are honoured for string literals.
+fn quoted_end(bytes: &[u8], start: usize, quote: u8, backslash_escapes: bool) -> Option<usize> {
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if backslash_escapes && c == b'\\' {
+            pos += 2;
+        } else if c == quote {
+            if bytes.get(pos + 1) == Some(&quote) {
+                pos += 2;
+            } else {
+                return Some(pos + 1);
+            }
+        } else {
+            pos += 1;
+        }
+    }
+    None
+}
+
+// End of a hexadecimal (X'1F', 0x1F) or bit-value (b'101', 0b101) literal starting at `start`.
+//
+// The quoted forms take either case of prefix; the 0x/0b forms are case-sensitive, as in MySQL,
+// and only count when no other word characters follow (`0x1G` is an identifier).
+fn prefixed_literal_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let (digits_start, is_digit, quoted): (usize, fn(&u8) -> bool, bool) =
+        match (bytes[start], bytes.get(start + 1)) {
+            (b'x' | b'X', Some(b'\'')) => (start + 2, u8::is_ascii_hexdigit, true),
+            (b'b' | b'B', Some(b'\'')) => (start + 2, |b| matches!(b, b'0' | b'1'), true),
+            (b'0', Some(b'x')) => (start + 2, u8::is_ascii_hexdigit, false),
+            (b'0', Some(b'b')) => (start + 2, |b| matches!(b, b'0' | b'1'), false),
+            _ => return None,
+        };
+
+    let mut end = digits_start;
+    while end < bytes.len() && is_digit(&bytes[end]) {
+        end += 1;
+    }
+    if quoted {
+        (bytes.get(end) == Some(&b'\'')).then_some(end + 1)
+    } else {
+        (end > digits_start && !bytes.get(end).is_some_and(|b| is_word_byte(*b))).then_some(end)
+    }
+}
+
+fn number_end(bytes: &[u8], start: usize) -> usize {
+    let mut pos = start;
+
+    while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+        pos += 1;
+    }
+    if bytes.get(pos) == Some(&b'.') {
+        pos += 1;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+    }
+    if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+        let mut exp = pos + 1;
+        if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            pos = exp;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+        }
+    }
+    // Identifiers may start with digits in MySQL (e.g. `1st_place`); keep them in one token.
+    while pos < bytes.len() && is_word_byte(bytes[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80
+}
+
+fn char_len(sql: &str, pos: usize) -> usize {
+    sql[pos..].chars().next().map_or(1, char::len_utf8)
+}
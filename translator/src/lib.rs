@@ -0,0 +1,561 @@
+// MySQL to PostgreSQL statement translation.
+//
+// Statements are tokenized, folded into a tree of parenthesized groups and then rewritten by a
+// series of passes. Anything the passes don't recognise is rendered back unchanged, so the
+// translator is safe to run on every statement.
+//
+// The crate does no I/O and needs nothing of the proxy's, so the same code builds for wasm32
+// (see wasm.rs) and runs in a browser or an editor.
+
+mod auto_increment;
+mod clock;
+mod collations;
+pub mod constraints;
+pub mod dates;
+mod expr;
+mod fulltext;
+pub mod functions;
+mod group_by;
+mod indexes;
+mod insert_set;
+mod json;
+pub mod lexer;
+pub mod literals;
+pub mod mysql_only;
+mod operators;
+pub mod parameters;
+pub mod pretty;
+mod routines;
+mod row_limit;
+mod sequences;
+mod table_ddl;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+
+pub use constraints::CheckConstraints;
+pub use dates::{DateModes, ZeroDates};
+pub use functions::FunctionRegistry;
+pub use lexer::{LexError, Token};
+pub use literals::IdentifierCase;
+
+/// A token, or a parenthesized group of nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Token(Token),
+    Group(Vec<Node>),
+}
+
+impl Node {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Node::Token(t) if t.is_trivia())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslateError {
+    Lex(LexError),
+    UnbalancedParens { offset: usize },
+    // A construct the translator recognises but can't express in PostgreSQL.
+    Unsupported(String),
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslateError::Lex(e) => write!(f, "{}", e),
+            TranslateError::UnbalancedParens { offset } => {
+                write!(f, "unbalanced parentheses at offset {}", offset)
+            }
+            TranslateError::Unsupported(what) => write!(f, "{} is not supported", what),
+        }
+    }
+}
+
+impl TranslateError {
+    /// Byte offset into the statement where translation gave up.
+    pub fn offset(&self) -> usize {
+        match self {
+            TranslateError::Lex(e) => e.offset,
+            TranslateError::UnbalancedParens { offset } => *offset,
+            TranslateError::Unsupported(_) => 0,
+        }
+    }
+
+    /// The text of `sql` from the error onwards, shortened the way MySQL quotes it in syntax
+    /// errors (`... near '...'`).
+    pub fn near<'a>(&self, sql: &'a str) -> &'a str {
+        let rest = sql.get(self.offset()..).unwrap_or("");
+        match rest.char_indices().nth(80) {
+            Some((end, _)) => &rest[..end],
+            None => rest,
+        }
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+impl From<LexError> for TranslateError {
+    fn from(e: LexError) -> Self {
+        TranslateError::Lex(e)
+    }
+}
+
+/// Knobs controlling how statements are translated.
+///
+/// Programs of their own start from the default, the proxy's with an empty sql_mode, and set
+/// what they need; more options may come, so they aren't built field by field:
+///
+/// ```text
+/// let mut options = TranslationOptions::default()
+///     .sql_mode("STRICT_TRANS_TABLES,ANSI_QUOTES")
+///     .mysql_version(5, 7, 44);
+/// options.strict = true;
+/// let translated = translate_mysql_to_postgres(sql, &options)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct TranslationOptions {
+    pub check_constraints: CheckConstraints,
+    // The sql_mode the modes below were set from, as @@sql_mode reads it.
+    pub sql_mode: String,
+    // With ANSI_QUOTES, "double quoted" text is an identifier instead of a string literal.
+    pub ansi_quotes: bool,
+    // With PIPES_AS_CONCAT, || concatenates strings, as PostgreSQL's does; without it, it is OR.
+    pub pipes_as_concat: bool,
+    // With NO_BACKSLASH_ESCAPES, a backslash in a string literal is just a backslash.
+    pub no_backslash_escapes: bool,
+    // Without ONLY_FULL_GROUP_BY, a grouped query may select columns it doesn't group by, and
+    // gets any of their values in each group.
+    pub only_full_group_by: bool,
+    // Fixed value for NOW(), CURDATE() and the other clock functions, for deterministic tests.
+    pub pinned_now: Option<NaiveDateTime>,
+    // Create GIN indexes for the FULLTEXT indexes declared in CREATE TABLE.
+    pub fulltext_indexes: bool,
+    // What zero and impossible date literals become, from SQL_MODE.
+    pub dates: DateModes,
+    // How `quoted` identifiers are written for PostgreSQL.
+    pub identifiers: IdentifierCase,
+    // Fail a statement whose translation still has MySQL-only syntax or types (see
+    // mysql_only.rs), rather than leave PostgreSQL to reject it.
+    pub strict: bool,
+}
+
+impl TranslationOptions {
+    /// These options with the modes `sql_mode`, a comma-separated list of modes as SET sql_mode
+    /// takes them, has: ANSI_QUOTES, PIPES_AS_CONCAT, NO_BACKSLASH_ESCAPES, ONLY_FULL_GROUP_BY
+    /// and the date modes.
+    pub fn sql_mode(mut self, sql_mode: &str) -> TranslationOptions {
+        let has = |mode: &str| {
+            sql_mode
+                .split(',')
+                .any(|m| m.trim().eq_ignore_ascii_case(mode))
+        };
+        self.sql_mode = sql_mode.to_string();
+        // ANSI is these among others.
+        self.ansi_quotes = has("ANSI_QUOTES") || has("ANSI");
+        self.pipes_as_concat = has("PIPES_AS_CONCAT") || has("ANSI");
+        self.no_backslash_escapes = has("NO_BACKSLASH_ESCAPES");
+        self.only_full_group_by = has("ONLY_FULL_GROUP_BY") || has("ANSI");
+        // TRADITIONAL is the strict modes with NO_ZERO_DATE and NO_ZERO_IN_DATE, among others.
+        self.dates = DateModes {
+            strict: ["STRICT_TRANS_TABLES", "STRICT_ALL_TABLES", "TRADITIONAL"]
+                .iter()
+                .any(|mode| has(mode)),
+            no_zero_date: has("NO_ZERO_DATE") || has("TRADITIONAL"),
+            no_zero_in_date: has("NO_ZERO_IN_DATE") || has("TRADITIONAL"),
+            allow_invalid_dates: has("ALLOW_INVALID_DATES"),
+            zero_dates: self.dates.zero_dates,
+        };
+        self
+    }
+
+    /// These options with what differs between MySQL versions as it is in `major.minor.patch`:
+    /// CHECK clauses are only enforced from 8.0.16.
+    pub fn mysql_version(mut self, major: u32, minor: u32, patch: u32) -> TranslationOptions {
+        self.check_constraints = match (major, minor, patch) < (8, 0, 16) {
+            true => CheckConstraints::Strip,
+            false => CheckConstraints::Enforce,
+        };
+        self
+    }
+}
+
+/// Translates `sql`, a MySQL statement or a script of them, to PostgreSQL as the proxy would
+/// with `options`, for programs that want the translation without the server: migration
+/// scripts, linters, editors.
+pub fn translate_mysql_to_postgres(
+    sql: &str,
+    options: &TranslationOptions,
+) -> Result<String, TranslateError> {
+    Translator::with_options(options.clone()).translate_script(sql)
+}
+
+pub struct Translator {
+    functions: Arc<FunctionRegistry>,
+    options: TranslationOptions,
+}
+
+impl Default for Translator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Translator {
+    pub fn new() -> Self {
+        Self::with_options(TranslationOptions::default())
+    }
+
+    pub fn with_options(options: TranslationOptions) -> Self {
+        Translator {
+            functions: Arc::new(FunctionRegistry::default()),
+            options,
+        }
+    }
+
+    /// This translator with the modes of `sql_mode` in place of its own, for a session that
+    /// SET sql_mode.
+    pub fn with_sql_mode(&self, sql_mode: &str) -> Translator {
+        Translator {
+            functions: Arc::clone(&self.functions),
+            options: self.options.clone().sql_mode(sql_mode),
+        }
+    }
+
+    pub fn options(&self) -> &TranslationOptions {
+        &self.options
+    }
+
+    /// Translates a single MySQL statement into PostgreSQL syntax.
+    pub fn translate(&self, sql: &str) -> Result<String, TranslateError> {
+        self.rewrite(self.parse(sql)?)
+    }
+
+    /// Translates a script of MySQL statements, which may use the mysql client's `DELIMITER`
+    /// command, into PostgreSQL syntax.
+    pub fn translate_script(&self, sql: &str) -> Result<String, TranslateError> {
+        self.rewrite(self.parse_script(sql)?)
+    }
+
+    /// Parses a statement as `parse` does, reading string literals as NO_BACKSLASH_ESCAPES has
+    /// them if the sql_mode has it.
+    pub fn parse(&self, sql: &str) -> Result<Vec<Node>, TranslateError> {
+        fold(lexer::tokenize_with(
+            sql,
+            !self.options.no_backslash_escapes,
+        )?)
+    }
+
+    /// Parses a script as `parse_script` does, with the sql_mode's string literals.
+    pub fn parse_script(&self, sql: &str) -> Result<Vec<Node>, TranslateError> {
+        let script = routines::strip_delimiters(sql);
+        self.parse(script.as_deref().unwrap_or(sql))
+    }
+
+    /// Translates statements parsed with `parse` or `parse_script`.
+    pub fn rewrite(&self, nodes: Vec<Node>) -> Result<String, TranslateError> {
+        let nodes = match self.options.pipes_as_concat {
+            true => nodes,
+            false => operators::pipes_as_or(nodes),
+        };
+        literals::refuse_nul(
+            &nodes,
+            self.options.ansi_quotes,
+            !self.options.no_backslash_escapes,
+        )?;
+        let mut out = Vec::with_capacity(nodes.len());
+        for (i, statement) in split_statements(nodes).into_iter().enumerate() {
+            if i > 0 {
+                out.push(Node::Token(Token::Semicolon));
+            }
+            if routines::is_routine(&statement) {
+                out.extend(routines::create(&statement, self)?);
+                continue;
+            }
+            if routines::is_block(&statement) {
+                out.extend(routines::block(&statement, self)?);
+                continue;
+            }
+            constraints::check_alter(&statement, self.options.check_constraints)?;
+            let statement = self.rewrite_statement(statement);
+            out.extend(self.rewrite_expressions(statement));
+        }
+        let translated = render(&out);
+        if self.options.strict {
+            if let Some(leftover) = mysql_only::find(&translated).into_iter().next() {
+                return Err(TranslateError::Unsupported(leftover.construct));
+            }
+        }
+        Ok(translated)
+    }
+
+    // Passes that need to see the shape of the whole statement.
+    fn rewrite_statement(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let nodes = constraints::rewrite(nodes, self.options.check_constraints);
+        let nodes = row_limit::rewrite(nodes);
+        let nodes = auto_increment::rewrite(nodes, self.options.identifiers);
+        let nodes = insert_set::rewrite(nodes);
+        let nodes = table_ddl::rewrite(nodes);
+        let nodes = sequences::rewrite(nodes);
+        let nodes = routines::rewrite(nodes);
+        let nodes = match self.options.pinned_now {
+            Some(now) => clock::rewrite(nodes, now),
+            None => nodes,
+        };
+        let nodes = indexes::rewrite(nodes, self.options.fulltext_indexes);
+        fulltext::rewrite(nodes)
+    }
+
+    // Passes that apply to expressions anywhere in the statement, innermost groups first.
+    fn rewrite_expressions(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let nodes = nodes
+            .into_iter()
+            .map(|node| match node {
+                Node::Group(inner) => Node::Group(self.rewrite_expressions(inner)),
+                other => other,
+            })
+            .collect();
+        let nodes = dates::rewrite(nodes, self.options.dates, self.options.ansi_quotes);
+        let nodes = literals::rewrite(
+            nodes,
+            self.options.ansi_quotes,
+            !self.options.no_backslash_escapes,
+            self.options.identifiers,
+        );
+        let nodes = collations::rewrite(nodes);
+        let nodes = self.rewrite_functions(nodes);
+        let nodes = operators::rewrite(nodes);
+        match self.options.only_full_group_by {
+            true => nodes,
+            false => group_by::rewrite(nodes),
+        }
+    }
+
+    // Replaces calls to registered functions. Arguments have already been rewritten by the time
+    // the mapping sees them, so nested calls translate inside-out.
+    fn rewrite_functions(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+        let mut iter = nodes.into_iter().peekable();
+
+        while let Some(node) = iter.next() {
+            let name = match &node {
+                Node::Token(Token::Word(name)) => name.clone(),
+                _ => {
+                    out.push(node);
+                    continue;
+                }
+            };
+            // `schema.func(...)` is a user-defined function, not a builtin, and a name after
+            // INTO, TABLE, FROM or JOIN is a table's, as in `INSERT INTO concat (a) VALUES (1)`.
+            // A FROM is a clause's only after the SELECT, DELETE or SHOW it belongs to, and not
+            // in the arguments of TRIM(BOTH ' ' FROM ...) or EXTRACT(YEAR FROM ...).
+            let clause = || {
+                out.iter().any(|n| {
+                    matches!(n, Node::Token(t) if ["SELECT", "DELETE", "SHOW"]
+                        .iter()
+                        .any(|word| t.is_word(word)))
+                })
+            };
+            let not_a_call = match out.iter().rev().find(|n| !n.is_trivia()) {
+                Some(Node::Token(t)) if t.is_word("FROM") => clause(),
+                Some(Node::Token(t)) => {
+                    t.is_operator(".") || TABLE_NAME_BEFORE.iter().any(|word| t.is_word(word))
+                }
+                _ => false,
+            };
+            let mapping = match (not_a_call, iter.peek()) {
+                (false, Some(Node::Group(_))) => self.functions.get(&name),
+                _ => None,
+            };
+            let Some(mapping) = mapping else {
+                out.push(node);
+                continue;
+            };
+            let Some(Node::Group(args)) = iter.next() else {
+                unreachable!("peeked a group");
+            };
+            let rendered: Vec<String> = split_args(&args)
+                .iter()
+                .map(|arg| render(arg).trim().to_string())
+                .collect();
+            match mapping(&rendered) {
+                Some(replacement) => out.extend(parse_fragment(&replacement)),
+                None => {
+                    out.push(node);
+                    out.push(Node::Group(args));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Words after which a name is a table's rather than a function's, besides a clause's FROM.
+const TABLE_NAME_BEFORE: &[&str] = &["INTO", "TABLE", "JOIN"];
+
+/// Parses a statement for `Translator::rewrite`, or several in a script of `DELIMITER`
+/// commands.
+pub fn parse_script(sql: &str) -> Result<Vec<Node>, TranslateError> {
+    let script = routines::strip_delimiters(sql);
+    parse(script.as_deref().unwrap_or(sql))
+}
+
+/// Tokenizes `sql` and folds parenthesized sections into groups.
+pub fn parse(sql: &str) -> Result<Vec<Node>, TranslateError> {
+    fold(lexer::tokenize(sql)?)
+}
+
+fn fold(tokens: Vec<Token>) -> Result<Vec<Node>, TranslateError> {
+    let mut stack: Vec<(usize, Vec<Node>)> = vec![(0, Vec::new())];
+    let mut offset = 0;
+    for token in tokens {
+        let len = token.to_string().len();
+        match token {
+            Token::LParen => stack.push((offset, Vec::new())),
+            Token::RParen => {
+                if stack.len() == 1 {
+                    return Err(TranslateError::UnbalancedParens { offset });
+                }
+                let (_, group) = stack.pop().expect("stack has an open group");
+                stack
+                    .last_mut()
+                    .expect("stack has a root")
+                    .1
+                    .push(Node::Group(group));
+            }
+            other => stack
+                .last_mut()
+                .expect("stack has a root")
+                .1
+                .push(Node::Token(other)),
+        }
+        offset += len;
+    }
+
+    if stack.len() > 1 {
+        let (open, _) = stack.pop().expect("stack has an open group");
+        return Err(TranslateError::UnbalancedParens { offset: open });
+    }
+    Ok(stack.pop().expect("stack has a root").1)
+}
+
+/// Parses SQL generated by a rewrite back into nodes. Generated fragments are always balanced;
+/// should one not be, it is kept as a single opaque token rather than dropped.
+fn parse_fragment(sql: &str) -> Vec<Node> {
+    parse(sql).unwrap_or_else(|_| vec![Node::Token(Token::Word(sql.to_string()))])
+}
+
+pub fn render(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    render_into(nodes, &mut out);
+    out
+}
+
+fn render_into(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Token(t) => out.push_str(&t.to_string()),
+            Node::Group(inner) => {
+                out.push('(');
+                render_into(inner, out);
+                out.push(')');
+            }
+        }
+    }
+}
+
+/// The meaningful tokens of a single statement, without whitespace, comments or a trailing
+/// semicolon. `None` if the statement can't be tokenized.
+pub fn significant_tokens(sql: &str) -> Option<Vec<Token>> {
+    let mut tokens: Vec<Token> = lexer::tokenize(sql)
+        .ok()?
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    while matches!(tokens.last(), Some(Token::Semicolon)) {
+        tokens.pop();
+    }
+    Some(tokens)
+}
+
+/// The significant tokens of a statement as a session with `options` reads it: with
+/// NO_BACKSLASH_ESCAPES a backslash ends no string, and with ANSI_QUOTES "double quoted" text
+/// is a quoted identifier.
+pub fn significant_tokens_with(sql: &str, options: &TranslationOptions) -> Option<Vec<Token>> {
+    let mut tokens: Vec<Token> = lexer::tokenize_with(sql, !options.no_backslash_escapes)
+        .ok()?
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .map(|t| match t {
+            Token::DoubleQuoted(raw) if options.ansi_quotes => Token::QuotedIdent(raw),
+            t => t,
+        })
+        .collect();
+    while matches!(tokens.last(), Some(Token::Semicolon)) {
+        tokens.pop();
+    }
+    Some(tokens)
+}
+
+/// Whether the significant tokens of a statement are a compound statement, `[label:] BEGIN
+/// ... END`, rather than BEGIN [WORK] starting a transaction.
+pub fn is_compound_statement(tokens: &[Token]) -> bool {
+    let rest = match tokens {
+        [_, Token::Operator(colon), rest @ ..] if colon == ":" => rest,
+        _ => tokens,
+    };
+    matches!(rest, [begin, next, ..]
+        if begin.is_word("BEGIN") && !next.is_word("WORK") && *next != Token::Semicolon)
+}
+
+// The semicolons inside the body of a stored routine, or of a compound statement, don't end it.
+fn split_statements(nodes: Vec<Node>) -> Vec<Vec<Node>> {
+    let mut statements = vec![Vec::new()];
+    let mut open_blocks = 0;
+    for node in nodes {
+        let statement = statements.last_mut().expect("at least one statement");
+        // The semicolon after a transaction's BEGIN ends it: that BEGIN opened no block after all.
+        let in_body = routines::is_routine(statement) || routines::is_block(statement);
+        if matches!(node, Node::Token(Token::Semicolon)) && (open_blocks == 0 || !in_body) {
+            statements.push(Vec::new());
+            open_blocks = 0;
+            continue;
+        }
+        let before = statement.len();
+        statement.push(node);
+        if routines::is_routine(statement) || routines::begins_block(statement) {
+            open_blocks =
+                routines::open_blocks(open_blocks, &statement[..before], &statement[before]);
+        }
+    }
+    statements
+}
+
+/// True when the first words of the statement are `words` (case-insensitive).
+pub fn statement_starts_with(nodes: &[Node], words: &[&str]) -> bool {
+    let mut significant = nodes.iter().filter(|n| !n.is_trivia());
+    words
+        .iter()
+        .all(|w| matches!(significant.next(), Some(Node::Token(t)) if t.is_word(w)))
+}
+
+/// Whether `node` is the unquoted word `word` (case-insensitive).
+pub fn is_word(node: Option<&Node>, word: &str) -> bool {
+    matches!(node, Some(Node::Token(t)) if t.is_word(word))
+}
+
+/// Splits the contents of a group on its top-level commas.
+pub fn split_args(nodes: &[Node]) -> Vec<&[Node]> {
+    if nodes.iter().all(Node::is_trivia) {
+        return Vec::new();
+    }
+    nodes
+        .split(|n| matches!(n, Node::Token(Token::Comma)))
+        .collect()
+}
@@ -0,0 +1,450 @@
+// Literal and quoted identifier normalization.
+//
+// MySQL treats backslash as an escape character inside string literals, unless
+// NO_BACKSLASH_ESCAPES is set, and, unless ANSI_QUOTES is set, accepts "double quoted" strings. PostgreSQL (standard_conforming_strings = on) takes
+// backslashes literally and reads "double quoted" text as an identifier. Literals are decoded
+// with MySQL's rules and written back in a form PostgreSQL reads the same way. Strings written
+// one after the other, `'a' "b"`, which MySQL joins and PostgreSQL only joins across a newline,
+// are joined into one. PostgreSQL text can't hold a NUL character, so a string with a `\0`
+// escape is refused rather than changed (see `refuse_nul`).
+//
+// Hexadecimal and bit-value literals are binary strings in MySQL unless they are used as a
+// number, e.g. `0x10 + 1`, or compared with other than a string, e.g. `id = 0x10`. They become
+// bytea (or bit strings) and integer constants respectively.
+
+use super::{parse_fragment, Node, Token, TranslateError};
+
+/// How `quoted` identifiers are written for PostgreSQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdentifierCase {
+    /// In lower case, as PostgreSQL folds unquoted names, so `` `Users` `` and `Users` are the
+    /// same table (see `pg_identifier`).
+    #[default]
+    Lower,
+    /// As they are, for a PostgreSQL schema whose names were created quoted in mixed case.
+    Preserve,
+}
+
+pub fn rewrite(
+    nodes: Vec<Node>,
+    ansi_quotes: bool,
+    backslash_escapes: bool,
+    identifiers: IdentifierCase,
+) -> Vec<Node> {
+    let numeric: Vec<bool> = (0..nodes.len())
+        .map(|i| in_numeric_context(&nodes, i))
+        .collect();
+
+    let mut out = Vec::with_capacity(nodes.len());
+    for (node, numeric) in nodes.into_iter().zip(numeric) {
+        match node {
+            Node::Token(Token::String(raw)) if raw.starts_with('\'') && raw.contains('\\') => out
+                .push(Node::Token(Token::String(pg_string(&string_value(
+                    &raw,
+                    backslash_escapes,
+                ))))),
+            Node::Token(Token::DoubleQuoted(raw)) if !ansi_quotes => out.push(Node::Token(
+                Token::String(pg_string(&string_value(&raw, backslash_escapes))),
+            )),
+            Node::Token(Token::QuotedIdent(raw)) => out.push(Node::Token(Token::QuotedIdent(
+                quoted_identifier(&raw, identifiers),
+            ))),
+            Node::Token(Token::Hex(raw)) => out.extend(parse_fragment(&hex_literal(&raw, numeric))),
+            Node::Token(Token::Bit(raw)) => out.push(Node::Token(bit_literal(&raw, numeric))),
+            other => out.push(other),
+        }
+    }
+    join_adjacent_strings(out)
+}
+
+/// Refuses a statement with a string literal that has a `\0` escape, whose NUL PostgreSQL text
+/// can't hold, in groups too.
+pub fn refuse_nul(
+    nodes: &[Node],
+    ansi_quotes: bool,
+    backslash_escapes: bool,
+) -> Result<(), TranslateError> {
+    for node in nodes {
+        let nul = match node {
+            Node::Group(inner) => {
+                refuse_nul(inner, ansi_quotes, backslash_escapes)?;
+                false
+            }
+            Node::Token(Token::String(raw)) if raw.starts_with('\'') => has_nul(raw),
+            Node::Token(Token::DoubleQuoted(raw)) if !ansi_quotes => has_nul(raw),
+            _ => false,
+        };
+        if nul && backslash_escapes {
+            return Err(TranslateError::Unsupported(
+                "a NUL character (\\0) in a string".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Whether a quoted string literal has a `\0` escape.
+fn has_nul(raw: &str) -> bool {
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.next() == Some('0') {
+            return true;
+        }
+    }
+    false
+}
+
+fn join_adjacent_strings(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        if let Node::Token(Token::String(raw)) = &node {
+            let previous = out
+                .iter()
+                .rposition(|n| !matches!(n, Node::Token(Token::Whitespace(_))));
+            let joined = previous.and_then(|at| match &out[at] {
+                Node::Token(Token::String(first)) => {
+                    Some((at, pg_string_value(first)? + &pg_string_value(raw)?))
+                }
+                _ => None,
+            });
+            if let Some((at, value)) = joined {
+                out.truncate(at);
+                out.push(Node::Token(Token::String(pg_string(&value))));
+                continue;
+            }
+        }
+        out.push(node);
+    }
+    out
+}
+
+// True when the node at `i` is an operand of an arithmetic or bitwise operator, or of a
+// comparison with other than a string, as in `id = 0x10`, which PostgreSQL couldn't make of an
+// integer column and bytea.
+fn in_numeric_context(nodes: &[Node], i: usize) -> bool {
+    let is_arithmetic = |node: &Node| match node {
+        Node::Token(Token::Operator(op)) => {
+            matches!(
+                op.as_str(),
+                "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "~" | "<<" | ">>"
+            )
+        }
+        Node::Token(t) => t.is_word("DIV") || t.is_word("MOD"),
+        Node::Group(_) => false,
+    };
+    let is_comparison = |node: &Node| {
+        matches!(node, Node::Token(Token::Operator(op))
+            if matches!(op.as_str(), "=" | "<>" | "!=" | "<" | ">" | "<=" | ">=" | "<=>"))
+    };
+    let is_string = |node: Option<&Node>| {
+        matches!(
+            node,
+            Some(Node::Token(Token::String(_) | Token::DoubleQuoted(_)))
+        )
+    };
+    // The two nodes before the one at `i`, nearest first, and the two after it.
+    let before: Vec<&Node> = nodes[..i]
+        .iter()
+        .rev()
+        .filter(|n| !n.is_trivia())
+        .take(2)
+        .collect();
+    let after: Vec<&Node> = nodes[i + 1..]
+        .iter()
+        .filter(|n| !n.is_trivia())
+        .take(2)
+        .collect();
+    let (prev, next) = (before.first().copied(), after.first().copied());
+    let compared = (prev.is_some_and(is_comparison) && !is_string(before.get(1).copied()))
+        || (next.is_some_and(is_comparison) && !is_string(after.get(1).copied()));
+    prev.is_some_and(is_arithmetic) || next.is_some_and(is_arithmetic) || compared
+}
+
+// The digits of a X'..', 0x.., b'..' or 0b.. literal.
+fn literal_digits(raw: &str) -> &str {
+    raw[2..].trim_end_matches('\'')
+}
+
+// MySQL hex literals: X'4D79' / 0x4D79.
+fn hex_literal(raw: &str, numeric: bool) -> String {
+    let digits = literal_digits(raw).to_ascii_lowercase();
+    if numeric {
+        if let Ok(value) = u64::from_str_radix(&digits, 16) {
+            return value.to_string();
+        }
+    }
+    // 0x with an odd number of digits has an implied leading zero.
+    let padding = if digits.len() % 2 == 1 { "0" } else { "" };
+    format!("decode('{}{}', 'hex')", padding, digits)
+}
+
+// MySQL bit-value literals: b'1010' / 0b1010.
+fn bit_literal(raw: &str, numeric: bool) -> Token {
+    let digits = literal_digits(raw);
+    if numeric {
+        if let Ok(value) = u64::from_str_radix(digits, 2) {
+            return Token::Number(value.to_string());
+        }
+    }
+    Token::String(format!("B'{}'", digits))
+}
+
+/// Decodes a quoted MySQL string literal (including its quotes) into its value.
+pub fn mysql_string_value(raw: &str) -> String {
+    string_value(raw, true)
+}
+
+// The value of a quoted string literal, with backslash escapes or, under NO_BACKSLASH_ESCAPES,
+// without.
+fn string_value(raw: &str, backslash_escapes: bool) -> String {
+    let quote = raw.chars().next().unwrap_or('\'');
+    let inner = &raw[quote.len_utf8()..raw.len() - quote.len_utf8()];
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == quote && chars.peek() == Some(&quote) {
+            chars.next();
+            value.push(quote);
+        } else if c == '\\' && backslash_escapes {
+            match chars.next() {
+                // PostgreSQL text can't hold NUL bytes, so \0 is dropped; refuse_nul refuses
+                // the statements that have one before they are translated.
+                Some('0') => {}
+                Some('b') => value.push('\x08'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('Z') => value.push('\x1a'),
+                // \% and \_ keep their backslash so they still escape LIKE wildcards.
+                Some(c @ ('%' | '_')) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                Some(c) => value.push(c),
+                None => value.push('\\'),
+            }
+        } else {
+            value.push(c);
+        }
+    }
+    value
+}
+
+/// Quotes `value` as a PostgreSQL string literal, using an E'' literal only when the value has
+/// characters that need a backslash escape.
+pub fn pg_string(value: &str) -> String {
+    let needs_escapes = value.chars().any(|c| c == '\\' || c.is_control());
+    if !needs_escapes {
+        return format!("'{}'", value.replace('\'', "''"));
+    }
+
+    let mut out = String::with_capacity(value.len() + 3);
+    out.push_str("E'");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("''"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\x08' => out.push_str("\\b"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Decodes a PostgreSQL string literal as produced by `pg_string` ('...' or E'...'). Other forms
+/// (B'...' bit strings) give `None`.
+pub fn pg_string_value(raw: &str) -> Option<String> {
+    if let Some(inner) = raw.strip_prefix('\'') {
+        return Some(inner.strip_suffix('\'')?.replace("''", "'"));
+    }
+    let inner = raw
+        .strip_prefix("E'")
+        .or_else(|| raw.strip_prefix("e'"))?
+        .strip_suffix('\'')?;
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // Doubled quote.
+                chars.next();
+                value.push('\'');
+            }
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'b' => value.push('\x08'),
+                'f' => value.push('\x0c'),
+                'u' => {
+                    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    Some(value)
+}
+
+/// The PostgreSQL name an identifier token refers to, or `None` if it isn't an identifier.
+pub fn identifier_name(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(w) => Some(w.to_lowercase()),
+        Token::QuotedIdent(raw) => Some(unquote_identifier(raw).to_lowercase()),
+        _ => None,
+    }
+}
+
+/// A `quoted` MySQL identifier as PostgreSQL is to read it with `identifiers`.
+pub fn quoted_identifier(raw: &str, identifiers: IdentifierCase) -> String {
+    let name = unquote_identifier(raw);
+    match identifiers {
+        IdentifierCase::Lower => pg_identifier(&name),
+        IdentifierCase::Preserve => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// The name in a `backquoted` identifier, or a "double quoted" one under ANSI_QUOTES.
+fn unquote_identifier(raw: &str) -> String {
+    let quote = &raw[..1];
+    raw[1..raw.len() - 1].replace(&quote.repeat(2), quote)
+}
+
+/// Quotes an identifier for PostgreSQL.
+///
+/// MySQL identifiers are case-insensitive (column names always, table names on most setups)
+/// while quoted PostgreSQL identifiers are not. Unquoted names fold to lower case on
+/// PostgreSQL, so quoted ones are lower-cased too to keep `Users` and `` `Users` `` pointing at
+/// the same table.
+pub fn pg_identifier(name: &str) -> String {
+    format!("\"{}\"", name.to_lowercase().replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TranslateError, TranslationOptions, Translator};
+
+    fn translate(sql: &str, sql_mode: &str) -> String {
+        let options = TranslationOptions::default().sql_mode(sql_mode);
+        Translator::with_options(options).translate(sql).unwrap()
+    }
+
+    #[test]
+    fn decodes_strings_with_mysql_rules() {
+        assert_eq!(translate("SELECT 'it\\'s'", ""), "SELECT 'it''s'");
+        assert_eq!(
+            translate("SELECT 1 FROM t WHERE a = \"x\\ny\"", ""),
+            "SELECT 1 FROM t WHERE a = E'x\\ny'"
+        );
+        assert_eq!(
+            translate("SELECT 'a\\b'", "NO_BACKSLASH_ESCAPES"),
+            "SELECT E'a\\\\b'"
+        );
+    }
+
+    #[test]
+    fn refuses_nul_characters() {
+        for sql in [
+            "SELECT 'a\\0b'",
+            "INSERT INTO t VALUES (\"\\0\")",
+            "SELECT CONCAT('a', 'b\\0')",
+        ] {
+            assert_eq!(
+                Translator::new().translate(sql),
+                Err(TranslateError::Unsupported(
+                    "a NUL character (\\0) in a string".to_string()
+                )),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            translate("SELECT 'a\\0b', 'c\\\\0'", "NO_BACKSLASH_ESCAPES"),
+            "SELECT E'a\\\\0b', E'c\\\\\\\\0'"
+        );
+        assert_eq!(translate("SELECT 'c\\\\0'", ""), "SELECT E'c\\\\0'");
+    }
+
+    #[test]
+    fn double_quotes_and_backticks() {
+        assert_eq!(
+            translate("SELECT \"a\", `order` FROM t", ""),
+            "SELECT 'a', \"order\" FROM t"
+        );
+        assert_eq!(
+            translate("SELECT \"a\" FROM t", "ANSI_QUOTES"),
+            "SELECT \"a\" FROM t"
+        );
+    }
+
+    #[test]
+    fn keeps_escapes_mysql_keeps_and_joins_adjacent_strings() {
+        assert_eq!(
+            translate("SELECT 'tab\\there', 'x\\%y', 'z\\Z'", ""),
+            "SELECT E'tab\\there', E'x\\\\%y', E'z\\u001a'"
+        );
+        assert_eq!(
+            translate("SELECT 'it''s', \"say \"\"hi\"\"\", \"it's\"", ""),
+            "SELECT 'it''s', 'say \"hi\"', 'it''s'"
+        );
+        assert_eq!(
+            translate("SELECT 'a' \"b\" 'c\\n', 'd'", ""),
+            "SELECT E'abc\\n', 'd'"
+        );
+        assert_eq!(
+            translate("SELECT 'a' \"b\"", "ANSI_QUOTES"),
+            "SELECT 'a' \"b\""
+        );
+    }
+
+    #[test]
+    fn hex_and_bit_literals_are_binary_unless_used_as_numbers() {
+        assert_eq!(
+            translate("SELECT * FROM t WHERE int_col = 0x10 OR 0x20 < int_col", ""),
+            "SELECT * FROM t WHERE int_col = 16 OR 32 < int_col"
+        );
+        assert_eq!(
+            translate("SELECT * FROM t WHERE 'A' = 0x41", ""),
+            "SELECT * FROM t WHERE 'A' = decode('41', 'hex')"
+        );
+        assert_eq!(
+            translate("SELECT x'41', 0x41 + 1, b'101', b'101' + 0", ""),
+            "SELECT decode('41', 'hex'), 65 + 1, B'101', 5 + 0"
+        );
+        assert_eq!(
+            translate("SELECT X'', 0xABC, 0b11 | 1, B''", ""),
+            "SELECT decode('', 'hex'), decode('0abc', 'hex'), 3 | 1, B''"
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_follow_the_sql_mode() {
+        // A backslash can end a string when it escapes nothing.
+        assert_eq!(
+            translate("SELECT 'C:\\temp\\'", "NO_BACKSLASH_ESCAPES"),
+            "SELECT E'C:\\\\temp\\\\'"
+        );
+        assert_eq!(
+            translate("SELECT \"it\"\"s\" FROM t", "ANSI_QUOTES"),
+            "SELECT \"it\"\"s\" FROM t"
+        );
+        // ANSI is ANSI_QUOTES among others.
+        assert_eq!(
+            translate("SELECT \"a\", 'b' FROM \"t\"", "ANSI"),
+            "SELECT \"a\", 'b' FROM \"t\""
+        );
+        assert_eq!(
+            translate("SELECT \"x\\\" FROM t", "ANSI_QUOTES,NO_BACKSLASH_ESCAPES"),
+            "SELECT \"x\\\" FROM t"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// MySQL-only syntax and types a translation can still have, because the translator leaves
+// them as they are: `postmyrustache check` reports them, and strict translation refuses them.
+
+use super::{significant_tokens, Token};
+
+// Statement modifiers and options only MySQL has.
+const MYSQL_ONLY_WORDS: &[&str] = &[
+    "SQL_CALC_FOUND_ROWS",
+    "SQL_NO_CACHE",
+    "SQL_CACHE",
+    "STRAIGHT_JOIN",
+    "HIGH_PRIORITY",
+    "LOW_PRIORITY",
+    "DELAYED",
+];
+
+// Column types only MySQL has, in CREATE TABLE and ALTER TABLE.
+const MYSQL_ONLY_TYPES: &[&str] = &[
+    "TINYINT",
+    "MEDIUMINT",
+    "UNSIGNED",
+    "ZEROFILL",
+    "DATETIME",
+    "TINYTEXT",
+    "MEDIUMTEXT",
+    "LONGTEXT",
+    "TINYBLOB",
+    "BLOB",
+    "MEDIUMBLOB",
+    "LONGBLOB",
+];
+
+/// What kind of construct a leftover is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeftoverKind {
+    Syntax,
+    Type,
+}
+
+/// A MySQL-only construct, as `LIMIT offset, count` or `MEDIUMTEXT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leftover {
+    pub kind: LeftoverKind,
+    pub construct: String,
+}
+
+/// The MySQL-only constructs left in `translated`, a translation, which PostgreSQL will reject.
+pub fn find(translated: &str) -> Vec<Leftover> {
+    let Some(tokens) = significant_tokens(translated) else {
+        return Vec::new();
+    };
+    let table_ddl = tokens.windows(2).any(|pair| {
+        (pair[0].is_word("CREATE") || pair[0].is_word("ALTER") || pair[0].is_word("TEMPORARY"))
+            && pair[1].is_word("TABLE")
+    });
+
+    let mut leftovers: Vec<Leftover> = Vec::new();
+    let mut found = |kind: LeftoverKind, construct: String| {
+        let leftover = Leftover { kind, construct };
+        if !leftovers.contains(&leftover) {
+            leftovers.push(leftover);
+        }
+    };
+    for (i, token) in tokens.iter().enumerate() {
+        let next = |n: usize| tokens.get(i + n);
+        let next_is = |n: usize, word: &str| next(n).is_some_and(|t| t.is_word(word));
+        let Token::Word(word) = token else {
+            continue;
+        };
+        let word = word.to_ascii_uppercase();
+        match word.as_str() {
+            "INSERT" if next_is(1, "IGNORE") => {
+                found(LeftoverKind::Syntax, "INSERT IGNORE".to_string())
+            }
+            "REPLACE" if next_is(1, "INTO") => found(LeftoverKind::Syntax, "REPLACE".to_string()),
+            "ON" if next_is(1, "DUPLICATE") && next_is(2, "KEY") => {
+                found(LeftoverKind::Syntax, "ON DUPLICATE KEY UPDATE".to_string())
+            }
+            "LIMIT"
+                if matches!(
+                    (next(1), next(2), next(3)),
+                    (
+                        Some(Token::Number(_)),
+                        Some(Token::Comma),
+                        Some(Token::Number(_))
+                    )
+                ) =>
+            {
+                found(LeftoverKind::Syntax, "LIMIT offset, count".to_string())
+            }
+            "USE" | "FORCE" | "IGNORE"
+                if (next_is(1, "INDEX") || next_is(1, "KEY"))
+                    && matches!(next(2), Some(Token::LParen)) =>
+            {
+                found(LeftoverKind::Syntax, format!("{} INDEX", word))
+            }
+            "DELETE"
+                if matches!(next(1), Some(Token::Word(_)))
+                    && !["FROM", "LOW_PRIORITY", "QUICK", "IGNORE"]
+                        .iter()
+                        .any(|modifier| next_is(1, modifier)) =>
+            {
+                found(LeftoverKind::Syntax, "multiple-table DELETE".to_string())
+            }
+            "AS" if next_is(1, "SIGNED") => found(LeftoverKind::Type, "CAST AS SIGNED".to_string()),
+            "AS" if next_is(1, "UNSIGNED") => {
+                found(LeftoverKind::Type, "CAST AS UNSIGNED".to_string())
+            }
+            _ if MYSQL_ONLY_WORDS.contains(&word.as_str()) => found(LeftoverKind::Syntax, word),
+            _ if table_ddl && MYSQL_ONLY_TYPES.contains(&word.as_str()) => {
+                found(LeftoverKind::Type, word)
+            }
+            "DOUBLE" if table_ddl && !next_is(1, "PRECISION") => found(LeftoverKind::Type, word),
+            "ENUM" | "SET" if table_ddl && matches!(next(1), Some(Token::LParen)) => {
+                found(LeftoverKind::Type, word)
+            }
+            _ => {}
+        }
+    }
+    leftovers
+}
@@ -0,0 +1,201 @@
+// Rewrites for MySQL-only operators.
+//
+//   a <=> b          ->  a IS NOT DISTINCT FROM b
+//   a DIV b          ->  (div((a)::numeric, (b)::numeric)::bigint)
+//   a [NOT] REGEXP b ->  a ~* b / a !~* b   (RLIKE is a synonym; ~ and !~ after BINARY)
+//   col -> '$.a'     ->  ((col)::jsonb #> '{a}')   (and ->> with #>>)
+//   a || b           ->  a OR b   (without PIPES_AS_CONCAT, see pipes_as_or)
+
+use super::{expr, json, parse_fragment, render, Node, Token};
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut i = 0;
+
+    while i < nodes.len() {
+        let node = &nodes[i];
+        i += 1;
+        let Node::Token(token) = node else {
+            out.push(node.clone());
+            continue;
+        };
+
+        if token.is_operator("<=>") {
+            out.extend(parse_fragment("IS NOT DISTINCT FROM"));
+        } else if token.is_word("REGEXP") || token.is_word("RLIKE") {
+            // MySQL's default collations are case-insensitive, and so is REGEXP on them, unless
+            // the pattern is BINARY.
+            let negated = match expr::last_significant(&out) {
+                Some(idx) if matches!(&out[idx], Node::Token(t) if t.is_word("NOT")) => {
+                    out.truncate(idx);
+                    true
+                }
+                _ => false,
+            };
+            let binary = (i..nodes.len())
+                .find(|&j| !nodes[j].is_trivia())
+                .filter(|&j| matches!(&nodes[j], Node::Token(t) if t.is_word("BINARY")));
+            if let Some(j) = binary {
+                i = j + 1;
+            }
+            let op = match (negated, binary.is_some()) {
+                (false, false) => "~*",
+                (true, false) => "!~*",
+                (false, true) => "~",
+                (true, true) => "!~",
+            };
+            out.push(Node::Token(Token::Operator(op.to_string())));
+        } else if let Some((replacement, start, end)) = json_arrow(token, &out, &nodes, i) {
+            // MySQL's column->path shorthands. PostgreSQL has -> and ->> too, but they take a
+            // single key, so only arrows followed by a `$` path are rewritten.
+            out.truncate(start);
+            i = end;
+            out.extend(parse_fragment(&replacement));
+        } else if token.is_word("DIV") && !matches!(nodes.get(i), Some(Node::Group(_))) {
+            let (Some(start), Some(end)) = (
+                expr::left_operand_start(&out),
+                expr::right_operand_end(&nodes, i),
+            ) else {
+                // Not something we understand (e.g. a column called `div`), keep it.
+                out.push(node.clone());
+                continue;
+            };
+            let left = out.split_off(start);
+            let right = &nodes[i..end];
+            i = end;
+            out.extend(parse_fragment(&format!(
+                "(div(({})::numeric, ({})::numeric)::bigint)",
+                render(&left).trim(),
+                render(right).trim()
+            )));
+        } else {
+            out.push(node.clone());
+        }
+    }
+
+    out
+}
+
+/// `nodes`, a whole statement, with MySQL's `||` as OR, as it is without PIPES_AS_CONCAT.
+/// PostgreSQL's concatenates, as the rewrites put it in CONCAT()'s place, so this runs before
+/// them.
+pub fn pipes_as_or(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            Node::Group(inner) => out.push(Node::Group(pipes_as_or(inner))),
+            Node::Token(token) if token.is_operator("||") => {
+                out.push(Node::Token(Token::Whitespace(" ".to_string())));
+                out.push(Node::Token(Token::Word("OR".to_string())));
+                out.push(Node::Token(Token::Whitespace(" ".to_string())));
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+// The jsonb expression for `->`/`->>` at `nodes[i - 1]`, with where its left operand starts in
+// `out` and where its right operand ends in `nodes`.
+fn json_arrow(
+    token: &Token,
+    out: &[Node],
+    nodes: &[Node],
+    i: usize,
+) -> Option<(String, usize, usize)> {
+    let unquote = match token {
+        t if t.is_operator("->") => false,
+        t if t.is_operator("->>") => true,
+        _ => return None,
+    };
+    let path = nodes[i..].iter().position(|n| !n.is_trivia())? + i;
+    let Node::Token(Token::String(path_literal)) = &nodes[path] else {
+        return None;
+    };
+    let start = expr::atom_start_before(out, out.len())?;
+    let column = render(&out[start..]);
+    let replacement = json::extract(column.trim(), path_literal, unquote)?;
+    Some((replacement, start, path + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TranslationOptions, Translator};
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn rewrites_mysql_operators() {
+        assert_eq!(
+            translate("SELECT a <=> b, a DIV b"),
+            "SELECT a IS NOT DISTINCT FROM b, (div((a)::numeric, (b)::numeric)::bigint)"
+        );
+        assert_eq!(
+            translate("SELECT a REGEXP 'x', a NOT RLIKE 'y'"),
+            "SELECT a ~* 'x', a !~* 'y'"
+        );
+    }
+
+    #[test]
+    fn keeps_operators_in_strings_and_columns_named_like_them() {
+        for sql in [
+            "SELECT 'a <=> b', 'x DIV y', 'a REGEXP b'",
+            "SELECT div FROM t",
+        ] {
+            assert_eq!(translate(sql), sql);
+        }
+    }
+
+    #[test]
+    fn null_safe_equality_matches_nulls() {
+        assert_eq!(
+            translate("SELECT * FROM t WHERE a <=> NULL OR NOT a <=> b"),
+            "SELECT * FROM t WHERE a IS NOT DISTINCT FROM NULL OR NOT a IS NOT DISTINCT FROM b"
+        );
+    }
+
+    #[test]
+    fn div_takes_the_operands_next_to_it() {
+        assert_eq!(
+            translate("SELECT a + b DIV c, -7 DIV 2"),
+            "SELECT a + (div((b)::numeric, (c)::numeric)::bigint), -(div((7)::numeric, (2)::numeric)::bigint)"
+        );
+    }
+
+    #[test]
+    fn binary_patterns_match_case_sensitively() {
+        assert_eq!(
+            translate("SELECT a REGEXP BINARY 'x', a NOT RLIKE BINARY 'y'"),
+            "SELECT a ~ 'x', a !~ 'y'"
+        );
+    }
+
+    #[test]
+    fn pipes_are_or_unless_pipes_as_concat() {
+        assert_eq!(translate("SELECT a || b"), "SELECT a  OR  b");
+        let options = TranslationOptions::default().sql_mode("PIPES_AS_CONCAT");
+        assert_eq!(
+            Translator::with_options(options)
+                .translate("SELECT a || b")
+                .unwrap(),
+            "SELECT a || b"
+        );
+    }
+
+    #[test]
+    fn ansi_mode_concatenates_with_pipes() {
+        assert_eq!(
+            translate("SELECT a FROM t WHERE x || y OR z"),
+            "SELECT a FROM t WHERE x  OR  y OR z"
+        );
+        let options = TranslationOptions::default().sql_mode("ANSI");
+        assert_eq!(
+            Translator::with_options(options)
+                .translate("SELECT \"a\" || 'x' FROM t")
+                .unwrap(),
+            "SELECT \"a\" || 'x' FROM t"
+        );
+    }
+}
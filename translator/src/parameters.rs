@@ -0,0 +1,181 @@
+// Extraction of string literals into bind parameters.
+//
+// A translated statement can be sent upstream with its string literals replaced by `$n`
+// placeholders and the values bound separately. PostgreSQL then never parses text that went
+// through the proxy's own rewriting as SQL, and statements that differ only in their values
+// share a plan cache entry.
+//
+// Only literals in plain value positions are extracted. Typed literals (`DATE '2024-01-01'`,
+// `INTERVAL '1 day'`), aliases and anything else that PostgreSQL doesn't accept a parameter for
+// stay inline.
+
+use super::{literals, parse, render, statement_starts_with, Node, Token};
+
+/// A statement with its string literals replaced by `$1`, `$2`, ... and their values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameterized {
+    pub sql: String,
+    pub params: Vec<String>,
+}
+
+// PostgreSQL only takes parameters in these statements.
+const STATEMENTS: &[&str] = &["SELECT", "INSERT", "UPDATE", "DELETE", "WITH", "VALUES"];
+
+// Keywords after which a string literal is a plain value.
+const VALUE_KEYWORDS: &[&str] = &[
+    "SELECT", "WHERE", "AND", "OR", "NOT", "XOR", "LIKE", "ILIKE", "SIMILAR", "ESCAPE", "BETWEEN",
+    "CASE", "WHEN", "THEN", "ELSE", "HAVING", "FROM", "ANY", "ALL",
+];
+
+/// Extracts the string literals of a single translated statement. Returns `None` when there is
+/// nothing to extract or the statement can't take parameters.
+pub fn extract(sql: &str) -> Option<Parameterized> {
+    let nodes = parse(sql).ok()?;
+    if !STATEMENTS
+        .iter()
+        .any(|s| statement_starts_with(&nodes, &[s]))
+    {
+        return None;
+    }
+    // Several statements can't be prepared together, and existing placeholders would clash
+    // with the ones added here.
+    if contains(&nodes, &|t| {
+        matches!(t, Token::Semicolon | Token::Placeholder)
+            || matches!(t, Token::Word(w) if w.starts_with('$'))
+    }) {
+        return None;
+    }
+
+    let mut params = Vec::new();
+    let nodes = replace_literals(nodes, &mut params);
+    if params.is_empty() {
+        return None;
+    }
+    Some(Parameterized {
+        sql: render(&nodes),
+        params,
+    })
+}
+
+/// Replaces the `?` placeholders of a prepared statement by PostgreSQL's `$1`, `$2`, ... in the
+/// order they appear. `None` if the statement can't be tokenized.
+pub fn number_placeholders(sql: &str) -> Option<String> {
+    let nodes = parse(sql).ok()?;
+    let mut count = 0;
+    Some(render(&number(nodes, &mut count)))
+}
+
+fn number(nodes: Vec<Node>, count: &mut usize) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Group(inner) => Node::Group(number(inner, count)),
+            Node::Token(Token::Placeholder) => {
+                *count += 1;
+                Node::Token(Token::Word(format!("${}", count)))
+            }
+            other => other,
+        })
+        .collect()
+}
+
+fn contains(nodes: &[Node], pred: &dyn Fn(&Token) -> bool) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Token(t) => pred(t),
+        Node::Group(inner) => contains(inner, pred),
+    })
+}
+
+fn replace_literals(nodes: Vec<Node>, params: &mut Vec<String>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            Node::Group(inner) => out.push(Node::Group(replace_literals(inner, params))),
+            Node::Token(Token::String(raw)) => {
+                // An E'' string reads as the word E and the string right after it.
+                let escaped = matches!(out.last(), Some(Node::Token(Token::Word(w))) if w.eq_ignore_ascii_case("E"));
+                let before = out.len() - usize::from(escaped);
+                let value = match escaped {
+                    true => literals::pg_string_value(&format!("E{}", raw)),
+                    false => literals::pg_string_value(&raw),
+                };
+                match value
+                    .filter(|_| takes_value(out[..before].iter().rev().find(|n| !n.is_trivia())))
+                {
+                    Some(value) => {
+                        out.truncate(before);
+                        params.push(value);
+                        out.push(Node::Token(Token::Word(format!("${}", params.len()))));
+                    }
+                    None => out.push(Node::Token(Token::String(raw))),
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+// Whether a literal following `prev` (the previous significant node in the same group) is a
+// plain value.
+fn takes_value(prev: Option<&Node>) -> bool {
+    match prev {
+        // First thing in a group: a function argument or parenthesized expression.
+        None => true,
+        Some(Node::Token(Token::Comma)) => true,
+        // `table.'x'` isn't valid anyway; everything else is an operator.
+        Some(Node::Token(Token::Operator(op))) => op != ".",
+        Some(Node::Token(Token::Word(w))) => {
+            VALUE_KEYWORDS.iter().any(|k| w.eq_ignore_ascii_case(k))
+        }
+        Some(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_string_values() {
+        assert_eq!(
+            extract("SELECT a FROM t WHERE b = 'x' AND c LIKE 'y%'"),
+            Some(Parameterized {
+                sql: "SELECT a FROM t WHERE b = $1 AND c LIKE $2".to_string(),
+                params: vec!["x".to_string(), "y%".to_string()],
+            })
+        );
+        assert_eq!(
+            extract("INSERT INTO t (a) VALUES ('x'), ('y')").map(|p| p.sql),
+            Some("INSERT INTO t (a) VALUES ($1), ($2)".to_string())
+        );
+    }
+
+    #[test]
+    fn binds_the_decoded_values_of_escaped_strings() {
+        assert_eq!(
+            extract("UPDATE t SET a = E'x\\ny', b = 'it''s' WHERE c IN ('p', 'q')"),
+            Some(Parameterized {
+                sql: "UPDATE t SET a = $1, b = $2 WHERE c IN ($3, $4)".to_string(),
+                params: ["x\ny", "it's", "p", "q"].map(String::from).to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn leaves_statements_that_cant_be_parameterized_alone() {
+        assert_eq!(extract("SELECT 'a'; SELECT 'b'"), None);
+        assert_eq!(extract("SELECT a FROM t WHERE b = $1 AND c = 'x'"), None);
+        assert_eq!(extract("SELECT a FROM t WHERE b = 1"), None);
+        assert_eq!(
+            extract("SELECT a AS 'x', B'101', INTERVAL '1 day' FROM t ORDER BY 'y'"),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_typed_literals_and_other_statements_alone() {
+        assert_eq!(extract("SELECT a FROM t WHERE d > DATE '2024-01-01'"), None);
+        assert_eq!(extract("CREATE TABLE t (a TEXT DEFAULT 'x')"), None);
+    }
+}
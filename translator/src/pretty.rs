@@ -0,0 +1,331 @@
+// Laying a statement out for people to read, as the text log shows statements with
+// LOG_SQL_FORMAT = pretty: a clause to a line, the conditions of WHERE and HAVING a line each,
+// subqueries indented in their parentheses, and keywords in one case.
+//
+//   SELECT o.id, o.total FROM orders o LEFT JOIN users u ON u.id = o.user_id
+//   WHERE o.total > 10 AND u.id IN (SELECT id FROM vip)
+//
+//   SELECT o.id, o.total
+//   FROM orders o
+//   LEFT JOIN users u ON u.id = o.user_id
+//   WHERE o.total > 10
+//     AND u.id IN (
+//       SELECT id
+//       FROM vip
+//     )
+//
+// Nothing but whitespace and the case of keywords changes: tokens stay as they were written, and
+// those that weren't apart aren't put apart. A statement that can't be parsed is left as it is.
+
+use super::{parse, Node, Token};
+
+// Words that start a clause on a line of its own.
+const CLAUSES: &[&str] = &[
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "GROUP",
+    "HAVING",
+    "WINDOW",
+    "ORDER",
+    "LIMIT",
+    "OFFSET",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "VALUES",
+    "SET",
+    "RETURNING",
+    "JOIN",
+];
+
+// Words that start a join, before JOIN.
+const JOINS: &[&str] = &[
+    "LEFT", "RIGHT", "INNER", "FULL", "CROSS", "NATURAL", "OUTER",
+];
+
+// Words whose case KeywordCase sets; other words are names, and are left alone.
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "ALTER",
+    "AND",
+    "AS",
+    "ASC",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CHECK",
+    "COLLATE",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DO",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "FALSE",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "ILIKE",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NATURAL",
+    "NOT",
+    "NOTHING",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PRIMARY",
+    "RECURSIVE",
+    "REFERENCES",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "SELECT",
+    "SET",
+    "SHARE",
+    "START",
+    "TABLE",
+    "THEN",
+    "TRANSACTION",
+    "TRUE",
+    "TRUNCATE",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+const INDENT: &str = "  ";
+
+const KEYWORD_COLOR: &str = "\x1b[1;34m";
+const STRING_COLOR: &str = "\x1b[32m";
+const COMMENT_COLOR: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// The case keywords are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    // As they were written.
+    Preserve,
+}
+
+/// How `pretty` lays a statement out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Layout {
+    pub keywords: KeywordCase,
+    // Keywords, strings and comments in colour, with ANSI escapes, for a terminal.
+    pub ansi: bool,
+}
+
+/// `sql` laid out over lines as `layout` has it.
+pub fn pretty(sql: &str, layout: &Layout) -> String {
+    let Ok(nodes) = parse(sql) else {
+        return sql.to_string();
+    };
+    let mut writer = Writer {
+        out: String::with_capacity(sql.len() * 2),
+        layout: *layout,
+        indent: 0,
+    };
+    writer.nodes(&nodes, 0, true);
+    writer.out.trim().to_string()
+}
+
+struct Writer {
+    out: String,
+    layout: Layout,
+    // How far the line being written is indented.
+    indent: usize,
+}
+
+impl Writer {
+    // Writes `nodes` at `depth`, starting clauses on lines of their own if `lines`; a group
+    // that isn't a subquery is written inline.
+    fn nodes(&mut self, nodes: &[Node], depth: usize, lines: bool) {
+        let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+        // Whether the next token was apart from the last, whether it is in a WHERE or HAVING
+        // clause, and whether an AND would be BETWEEN's.
+        let mut apart = false;
+        let mut conditions = false;
+        let mut between = false;
+        let mut at: usize = 0;
+        for node in nodes {
+            let token = match node {
+                Node::Token(token) if token.is_trivia() => {
+                    if let Token::Comment(comment) = token {
+                        self.separate(apart);
+                        self.colored(COMMENT_COLOR, comment.trim_end());
+                        // A line comment runs to the end of its line.
+                        if comment.starts_with("--") || comment.starts_with('#') {
+                            self.newline(depth);
+                            apart = false;
+                            continue;
+                        }
+                    }
+                    apart = true;
+                    continue;
+                }
+                Node::Token(token) => token,
+                Node::Group(inner) => {
+                    self.separate(apart);
+                    self.group(inner);
+                    apart = false;
+                    at += 1;
+                    continue;
+                }
+            };
+            let previous = at.checked_sub(1).and_then(|i| significant.get(i));
+            let next = significant.get(at + 1);
+            let starts_line = lines
+                && at > 0
+                && match token {
+                    Token::Word(w) if JOINS.iter().any(|j| w.eq_ignore_ascii_case(j)) => {
+                        !is_any(previous, JOINS) && is_any(next, &["JOIN", "OUTER"])
+                    }
+                    Token::Word(w) if w.eq_ignore_ascii_case("JOIN") => !is_any(previous, JOINS),
+                    Token::Word(w) if w.eq_ignore_ascii_case("ON") => is_any(next, &["CONFLICT"]),
+                    Token::Word(w) if w.eq_ignore_ascii_case("FROM") => {
+                        !is_any(previous, &["DELETE", "DISTINCT"])
+                    }
+                    Token::Word(w) if CLAUSES.iter().any(|c| w.eq_ignore_ascii_case(c)) => {
+                        // CHARACTER SET isn't a SET clause.
+                        !(w.eq_ignore_ascii_case("SET") && is_any(previous, &["CHARACTER"]))
+                    }
+                    Token::Word(w)
+                        if (w.eq_ignore_ascii_case("AND") || w.eq_ignore_ascii_case("OR"))
+                            && conditions =>
+                    {
+                        !std::mem::take(&mut between) || !w.eq_ignore_ascii_case("AND")
+                    }
+                    _ => false,
+                };
+            if let Token::Word(w) = token {
+                let upper = w.to_ascii_uppercase();
+                if CLAUSES.contains(&upper.as_str()) {
+                    conditions = upper == "WHERE" || upper == "HAVING";
+                }
+                between |= upper == "BETWEEN";
+            }
+            if starts_line {
+                let and_or = token.is_word("AND") || token.is_word("OR");
+                self.newline(depth + usize::from(and_or));
+            } else {
+                self.separate(apart);
+            }
+            self.token(token);
+            if *token == Token::Semicolon && lines {
+                self.newline(depth);
+            }
+            apart = false;
+            at += 1;
+        }
+    }
+
+    // A group: a subquery indented on lines of its own, under the line it opens on, anything
+    // else inline.
+    fn group(&mut self, inner: &[Node]) {
+        let depth = self.indent;
+        let subquery = inner.iter().find(|n| !n.is_trivia()).is_some_and(
+            |n| matches!(n, Node::Token(t) if t.is_word("SELECT") || t.is_word("WITH")),
+        );
+        self.out.push('(');
+        if subquery {
+            self.newline(depth + 1);
+            self.nodes(inner, depth + 1, true);
+            self.newline(depth);
+        } else {
+            self.nodes(inner, depth, false);
+        }
+        self.out.push(')');
+    }
+
+    fn token(&mut self, token: &Token) {
+        match token {
+            Token::Word(word) if KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) => {
+                let word = match self.layout.keywords {
+                    KeywordCase::Upper => word.to_ascii_uppercase(),
+                    KeywordCase::Lower => word.to_ascii_lowercase(),
+                    KeywordCase::Preserve => word.clone(),
+                };
+                self.colored(KEYWORD_COLOR, &word);
+            }
+            Token::String(text) => self.colored(STRING_COLOR, text),
+            token => self.out.push_str(&token.to_string()),
+        }
+    }
+
+    fn colored(&mut self, color: &str, text: &str) {
+        match self.layout.ansi {
+            true => {
+                self.out.push_str(color);
+                self.out.push_str(text);
+                self.out.push_str(RESET);
+            }
+            false => self.out.push_str(text),
+        }
+    }
+
+    // A space, if the tokens were apart and the line has begun.
+    fn separate(&mut self, apart: bool) {
+        if apart && !self.out.is_empty() && !self.out.ends_with(['\n', ' ', '(']) {
+            self.out.push(' ');
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        for _ in 0..depth {
+            self.out.push_str(INDENT);
+        }
+        self.indent = depth;
+    }
+}
+
+// Whether `node` is one of `words`.
+fn is_any(node: Option<&&Node>, words: &[&str]) -> bool {
+    matches!(node, Some(Node::Token(t)) if words.iter().any(|w| t.is_word(w)))
+}
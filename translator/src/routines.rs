@@ -0,0 +1,887 @@
+// Stored routines: CREATE PROCEDURE and CREATE FUNCTION as PL/pgSQL.
+//
+//   CREATE PROCEDURE add_points(IN player INT, IN amount INT)
+//   BEGIN
+//     DECLARE total INT DEFAULT 0;
+//     SELECT points INTO total FROM players WHERE id = player;
+//     IF total + amount > 100 THEN SET total = 100; ELSE SET total = total + amount; END IF;
+//     UPDATE players SET points = total WHERE id = player;
+//   END
+//   -> CREATE PROCEDURE add_points(IN player INT, IN amount INT) LANGUAGE plpgsql AS $routine$
+//        DECLARE total INT := 0; BEGIN SELECT points INTO total FROM players WHERE id = player;
+//        IF total + amount > 100 THEN total := 100; ELSE total := total + amount; END IF;
+//        UPDATE players SET points = total WHERE id = player; END $routine$
+//
+// The statements and expressions of the body go through the rest of the translator. Local
+// variables, IF, CASE, the loops with their labels, LEAVE and ITERATE, cursors and SIGNAL have
+// PL/pgSQL counterparts. Condition handlers, SET of user or system variables and SELECTs that
+// return a result set don't, and are rejected as unsupported rather than left for PostgreSQL to
+// fail on. Routine characteristics (DETERMINISTIC, SQL SECURITY, COMMENT, ...) are dropped.
+//
+// A routine's body holds semicolons, so a definition is one statement up to the END of its
+// body. The DELIMITER command of scripts written for the mysql client is understood as well.
+//
+// A compound statement sent on its own, as MariaDB runs `BEGIN NOT ATOMIC ... END` and some
+// tools send a bare `BEGIN ... END`, is translated the same way into an anonymous DO block:
+//
+//   BEGIN NOT ATOMIC DECLARE n INT; SELECT COUNT(*) INTO n FROM t; INSERT INTO log VALUES (n); END
+//   -> DO $block$ DECLARE n INT; BEGIN SELECT COUNT(*) INTO n FROM t; INSERT INTO log VALUES (n);
+//        END $block$
+//
+// so that what can't be translated is refused as a whole, naming what, rather than sent as
+// statements that fail from the middle of the block. BEGIN alone, or BEGIN WORK, still starts a
+// transaction.
+
+use super::{is_word, parse_fragment, render, split_args, statement_starts_with, Node, Token};
+use super::{literals, TranslateError, Translator};
+
+// The characteristics of a routine, by their first word, with how many words they have.
+const CHARACTERISTICS: &[(&str, usize)] = &[
+    ("COMMENT", 2),
+    ("LANGUAGE", 2),
+    ("NOT", 2),
+    ("DETERMINISTIC", 1),
+    ("CONTAINS", 2),
+    ("NO", 2),
+    ("READS", 3),
+    ("MODIFIES", 3),
+    ("SQL", 3),
+];
+
+/// Whether a statement defines a stored procedure or function:
+/// `CREATE [DEFINER = user] {PROCEDURE | FUNCTION}`.
+pub fn is_routine(nodes: &[Node]) -> bool {
+    let mut p = Parser::new(nodes);
+    p.eat("CREATE") && {
+        p.definer();
+        p.peek_word("PROCEDURE") || p.peek_word("FUNCTION")
+    }
+}
+
+/// Whether a statement is a compound statement of its own, `[label:] BEGIN [NOT ATOMIC] ... END`,
+/// rather than BEGIN [WORK] starting a transaction.
+pub fn is_block(nodes: &[Node]) -> bool {
+    let mut p = Parser::new(nodes);
+    p.label();
+    p.eat("BEGIN")
+        && !matches!(p.peek(), None | Some(Node::Token(Token::Semicolon)))
+        && !p.peek_word("WORK")
+}
+
+/// Whether a statement starts `[label:] BEGIN`, as a compound statement does and so does a
+/// transaction's BEGIN.
+pub fn begins_block(nodes: &[Node]) -> bool {
+    let mut p = Parser::new(nodes);
+    p.label();
+    p.eat("BEGIN")
+}
+
+/// The BEGIN ... END blocks of a routine definition still open after `node`, given how many
+/// were open before it and the statement so far. CASE ... END counts too, since its END would
+/// otherwise close a block, and so do the loops, which can be a routine's body without a BEGIN;
+/// END IF doesn't.
+pub fn open_blocks(open: usize, before: &[Node], node: &Node) -> usize {
+    let mut significant = before.iter().rev().filter(|n| !n.is_trivia());
+    let last = significant.next();
+    let Node::Token(t) = node else {
+        // REPEAT(str, n) is the string function rather than a loop.
+        let repeat = is_word(last, "REPEAT") && !is_word(significant.next(), "END");
+        return if repeat { open.saturating_sub(1) } else { open };
+    };
+    let after_end = is_word(last, "END");
+    let opens = ["BEGIN", "CASE", "LOOP", "WHILE", "REPEAT"]
+        .iter()
+        .any(|w| t.is_word(w));
+    if opens && !after_end {
+        open + 1
+    } else if t.is_word("END") {
+        open.saturating_sub(1)
+    } else if after_end && t.is_word("IF") {
+        open + 1
+    } else {
+        open
+    }
+}
+
+/// A script using the mysql client's `DELIMITER` command with its custom delimiters replaced by
+/// semicolons and the DELIMITER lines blanked, so offsets into it are offsets into `sql`. `None`
+/// if the script doesn't change the delimiter.
+pub fn strip_delimiters(sql: &str) -> Option<String> {
+    let mut delimiter: Option<&str> = None;
+    let mut out = String::with_capacity(sql.len());
+    for line in sql.split_inclusive('\n') {
+        let content = line.trim_end();
+        let mut words = content.split_whitespace();
+        if let (Some(command), Some(new), None) = (words.next(), words.next(), words.next()) {
+            if command.eq_ignore_ascii_case("DELIMITER") {
+                delimiter = Some(new).filter(|&d| d != ";");
+                out.push_str(&" ".repeat(content.len()));
+                out.push_str(&line[content.len()..]);
+                continue;
+            }
+        }
+        match delimiter {
+            Some(d) if content.ends_with(d) => {
+                out.push_str(&content[..content.len() - d.len()]);
+                out.push(';');
+                out.push_str(&" ".repeat(d.len() - 1));
+                out.push_str(&line[content.len()..]);
+            }
+            _ => out.push_str(line),
+        }
+    }
+    (out != sql).then_some(out)
+}
+
+/// `CALL name` without an argument list, which PostgreSQL requires.
+pub fn rewrite(mut nodes: Vec<Node>) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["CALL"])
+        && !nodes.iter().any(|n| matches!(n, Node::Group(_)))
+    {
+        let end = nodes
+            .iter()
+            .rposition(|n| !n.is_trivia())
+            .map_or(nodes.len(), |i| i + 1);
+        nodes.insert(end, Node::Group(Vec::new()));
+    }
+    nodes
+}
+
+/// Translates a CREATE PROCEDURE or CREATE FUNCTION statement.
+pub fn create(nodes: &[Node], translator: &Translator) -> Result<Vec<Node>, TranslateError> {
+    let mut p = Parser::new(nodes);
+    let mut routine = Routine {
+        translator,
+        variables: Vec::new(),
+    };
+    p.expect("CREATE")?;
+    p.definer();
+    let function = p.eat("FUNCTION");
+    if !function {
+        p.expect("PROCEDURE")?;
+    }
+    if p.peek_word("IF") {
+        return Err(unsupported(
+            "IF NOT EXISTS in CREATE PROCEDURE and CREATE FUNCTION",
+        ));
+    }
+    let name_start = p.pos;
+    while matches!(p.peek(), Some(Node::Token(_))) {
+        p.pos += 1;
+    }
+    let name = routine.expression(&nodes[name_start..p.pos]);
+    let Some(Node::Group(params)) = p.next() else {
+        return Err(p.unexpected());
+    };
+    let params = params_list(&mut routine, params, function)?;
+
+    let mut sql = if function {
+        p.expect("RETURNS")?;
+        let start = p.pos;
+        while !p.at_body_start() {
+            p.pos += 1;
+        }
+        format!(
+            "CREATE FUNCTION {}({}) RETURNS {}",
+            name,
+            params,
+            routine.data_type(&nodes[start..p.pos])
+        )
+    } else {
+        format!("CREATE PROCEDURE {}({})", name, params)
+    };
+    p.characteristics();
+
+    // The body is a block; a single statement gets one.
+    let mut lookahead = p.clone();
+    lookahead.label();
+    let body = routine.statement(&mut p)?;
+    let body = if lookahead.peek_word("BEGIN") {
+        body
+    } else {
+        format!("BEGIN {}; END", body)
+    };
+    while p.eat_semicolon() {}
+    if p.peek().is_some() {
+        return Err(p.unexpected());
+    }
+
+    sql.push_str(&format!(
+        " LANGUAGE plpgsql AS $routine$ {} $routine$",
+        body
+    ));
+    Ok(parse_fragment(&sql))
+}
+
+/// Translates a compound statement sent on its own into a DO block.
+pub fn block(nodes: &[Node], translator: &Translator) -> Result<Vec<Node>, TranslateError> {
+    let mut p = Parser::new(nodes);
+    let mut routine = Routine {
+        translator,
+        variables: Vec::new(),
+    };
+    let body = routine.statement(&mut p)?;
+    while p.eat_semicolon() {}
+    if p.peek().is_some() {
+        return Err(p.unexpected());
+    }
+    Ok(parse_fragment(&format!("DO $block$ {} $block$", body)))
+}
+
+fn unsupported(what: &str) -> TranslateError {
+    TranslateError::Unsupported(what.to_string())
+}
+
+// `[IN | OUT | INOUT] name type` parameters; functions only have IN ones and don't say so.
+fn params_list(
+    routine: &mut Routine,
+    params: &[Node],
+    function: bool,
+) -> Result<String, TranslateError> {
+    let mut out = Vec::new();
+    for param in split_args(params) {
+        let mut p = Parser::new(param);
+        let mode = if function {
+            None
+        } else {
+            ["IN", "OUT", "INOUT"].into_iter().find(|mode| p.eat(mode))
+        };
+        let Some(Node::Token(name)) = p.next() else {
+            return Err(p.unexpected());
+        };
+        routine.declare(name);
+        let param = format!(
+            "{}{} {}",
+            mode.map(|m| format!("{} ", m)).unwrap_or_default(),
+            routine.expression(&[Node::Token(name.clone())]),
+            routine.data_type(&param[p.pos..])
+        );
+        out.push(param);
+    }
+    Ok(out.join(", "))
+}
+
+struct Routine<'a> {
+    translator: &'a Translator,
+    // Parameters and local variables, which SET assigns with `:=`.
+    variables: Vec<String>,
+}
+
+impl Routine<'_> {
+    fn declare(&mut self, name: &Token) {
+        self.variables.extend(literals::identifier_name(name));
+    }
+
+    fn is_variable(&self, name: &Token) -> bool {
+        literals::identifier_name(name).is_some_and(|name| self.variables.contains(&name))
+    }
+
+    // A statement of the body, translated like any other.
+    fn sql(&self, nodes: &[Node]) -> String {
+        let nodes = self.translator.rewrite_statement(nodes.to_vec());
+        render(&self.translator.rewrite_expressions(nodes))
+            .trim()
+            .to_string()
+    }
+
+    fn expression(&self, nodes: &[Node]) -> String {
+        render(&self.translator.rewrite_expressions(nodes.to_vec()))
+            .trim()
+            .to_string()
+    }
+
+    // A type without the CHARSET and COLLATE of MySQL's string types.
+    fn data_type(&self, nodes: &[Node]) -> String {
+        let mut kept = Vec::new();
+        let mut p = Parser::new(nodes);
+        while let Some(node) = p.next() {
+            let charset = is_word(Some(node), "CHARSET")
+                || is_word(Some(node), "COLLATE")
+                || (is_word(Some(node), "CHARACTER") && p.eat("SET"));
+            if charset {
+                p.next();
+            } else {
+                kept.push(node.clone());
+                kept.push(Node::Token(Token::Whitespace(" ".to_string())));
+            }
+        }
+        self.expression(&kept)
+    }
+
+    // Statements up to one of `terminators`, each followed by a semicolon.
+    fn statements(
+        &mut self,
+        p: &mut Parser,
+        terminators: &[&str],
+    ) -> Result<String, TranslateError> {
+        let mut out = String::new();
+        loop {
+            match p.peek() {
+                None => break,
+                Some(Node::Token(Token::Semicolon)) => {
+                    p.pos += 1;
+                }
+                Some(Node::Token(t)) if terminators.iter().any(|w| t.is_word(w)) => break,
+                Some(_) => {
+                    out.push_str(&self.statement(p)?);
+                    out.push_str("; ");
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn statement(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        let label = p.label();
+        let keyword = match p.peek() {
+            Some(Node::Token(Token::Word(w))) => w.to_ascii_uppercase(),
+            _ => String::new(),
+        };
+        let statement = match keyword.as_str() {
+            "BEGIN" => self.block(p)?,
+            "IF" => self.if_statement(p)?,
+            "CASE" => self.case_statement(p)?,
+            "WHILE" => {
+                p.pos += 1;
+                let condition = self.expression(p.until(&["DO"]));
+                p.expect("DO")?;
+                let body = self.statements(p, &["END"])?;
+                p.expect("END")?;
+                p.expect("WHILE")?;
+                format!("WHILE {} LOOP {}END LOOP", condition, body)
+            }
+            "REPEAT" => {
+                p.pos += 1;
+                let body = self.statements(p, &["UNTIL"])?;
+                p.expect("UNTIL")?;
+                let condition = self.expression(p.until(&["END"]));
+                p.expect("END")?;
+                p.expect("REPEAT")?;
+                format!("LOOP {}EXIT WHEN {}; END LOOP", body, condition)
+            }
+            "LOOP" => {
+                p.pos += 1;
+                let body = self.statements(p, &["END"])?;
+                p.expect("END")?;
+                p.expect("LOOP")?;
+                format!("LOOP {}END LOOP", body)
+            }
+            _ if label.is_some() => return Err(p.unexpected()),
+            "DECLARE" => return Err(unsupported("DECLARE after other statements")),
+            "LEAVE" | "ITERATE" => {
+                p.pos += 1;
+                let Some(Node::Token(target)) = p.next() else {
+                    return Err(p.unexpected());
+                };
+                let keyword = if keyword == "LEAVE" {
+                    "EXIT"
+                } else {
+                    "CONTINUE"
+                };
+                return Ok(format!("{} {}", keyword, target));
+            }
+            "RETURN" => {
+                p.pos += 1;
+                return Ok(format!("RETURN {}", self.expression(p.until(&[]))));
+            }
+            "SET" => return self.set(p),
+            "SIGNAL" => return self.signal(p),
+            "SELECT" | "WITH" => {
+                let nodes = p.until(&[]);
+                if !nodes.iter().any(|n| is_word(Some(n), "INTO")) {
+                    return Err(unsupported("result sets from stored procedures"));
+                }
+                return Ok(self.sql(nodes));
+            }
+            _ => {
+                let nodes = p.until(&[]);
+                if nodes.is_empty() {
+                    return Err(p.unexpected());
+                }
+                return Ok(self.sql(nodes));
+            }
+        };
+        // `label: LOOP ... END LOOP label`
+        match label {
+            Some(label) => {
+                let mut end = p.clone();
+                if let Some(Node::Token(t)) = end.next() {
+                    if literals::identifier_name(t).as_ref() == Some(&label) {
+                        *p = end;
+                    }
+                }
+                Ok(format!("<<{}>> {} {}", label, statement, label))
+            }
+            None => Ok(statement),
+        }
+    }
+
+    // BEGIN [NOT ATOMIC] [DECLARE ...;] statements END
+    fn block(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        p.expect("BEGIN")?;
+        if p.eat("NOT") {
+            p.expect("ATOMIC")?;
+        }
+        let mut declarations = String::new();
+        while p.eat("DECLARE") {
+            declarations.push_str(&self.declaration(p.until(&[]))?);
+            declarations.push(' ');
+            p.eat_semicolon();
+        }
+        let body = self.statements(p, &["END"])?;
+        p.expect("END")?;
+        let declare = if declarations.is_empty() {
+            String::new()
+        } else {
+            format!("DECLARE {}", declarations)
+        };
+        Ok(format!("{}BEGIN {}END", declare, body))
+    }
+
+    // `name[, name] type [DEFAULT value]` or `name CURSOR FOR query`
+    fn declaration(&mut self, nodes: &[Node]) -> Result<String, TranslateError> {
+        if nodes.iter().any(|n| is_word(Some(n), "HANDLER")) {
+            return Err(unsupported("DECLARE ... HANDLER"));
+        }
+        let mut p = Parser::new(nodes);
+        let Some(Node::Token(first)) = p.next() else {
+            return Err(p.unexpected());
+        };
+        if p.eat("CONDITION") {
+            return Err(unsupported("DECLARE ... CONDITION"));
+        }
+        if p.eat("CURSOR") {
+            p.expect("FOR")?;
+            return Ok(format!(
+                "{} CURSOR FOR {};",
+                self.expression(&[Node::Token(first.clone())]),
+                self.sql(&nodes[p.pos..])
+            ));
+        }
+
+        let items: Vec<&[Node]> = nodes
+            .split(|n| matches!(n, Node::Token(Token::Comma)))
+            .collect();
+        let (last, names) = items.split_last().expect("split yields an item");
+        let mut p = Parser::new(last);
+        let Some(Node::Token(last_name)) = p.next() else {
+            return Err(p.unexpected());
+        };
+        let rest = &last[p.pos..];
+        let (data_type, default) = match rest.iter().position(|n| is_word(Some(n), "DEFAULT")) {
+            Some(i) => (
+                self.data_type(&rest[..i]),
+                format!(" := {}", self.expression(&rest[i + 1..])),
+            ),
+            None => (self.data_type(rest), String::new()),
+        };
+
+        let mut declared = Vec::new();
+        for name in names {
+            match Parser::new(name).next() {
+                Some(Node::Token(name)) => declared.push(name),
+                _ => return Err(unsupported("this DECLARE")),
+            }
+        }
+        declared.push(last_name);
+
+        let mut out = Vec::new();
+        for name in declared {
+            self.declare(name);
+            out.push(format!(
+                "{} {}{};",
+                self.expression(&[Node::Token(name.clone())]),
+                data_type,
+                default
+            ));
+        }
+        Ok(out.join(" "))
+    }
+
+    // IF condition THEN statements [ELSEIF condition THEN statements]... [ELSE statements] END IF
+    fn if_statement(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        p.expect("IF")?;
+        let mut out = String::from("IF");
+        loop {
+            let condition = self.expression(p.until(&["THEN"]));
+            p.expect("THEN")?;
+            let body = self.statements(p, &["ELSEIF", "ELSE", "END"])?;
+            out.push_str(&format!(" {} THEN {}", condition, body));
+            if p.eat("ELSEIF") {
+                out.push_str("ELSIF");
+            } else {
+                break;
+            }
+        }
+        if p.eat("ELSE") {
+            out.push_str("ELSE ");
+            out.push_str(&self.statements(p, &["END"])?);
+        }
+        p.expect("END")?;
+        p.expect("IF")?;
+        out.push_str("END IF");
+        Ok(out)
+    }
+
+    // CASE [value] WHEN ... THEN statements ... [ELSE statements] END CASE
+    fn case_statement(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        p.expect("CASE")?;
+        let mut out = format!("CASE {}", self.expression(p.until(&["WHEN"])));
+        while p.eat("WHEN") {
+            let condition = self.expression(p.until(&["THEN"]));
+            p.expect("THEN")?;
+            let body = self.statements(p, &["WHEN", "ELSE", "END"])?;
+            out.push_str(&format!(" WHEN {} THEN {}", condition, body));
+        }
+        if p.eat("ELSE") {
+            out.push_str(" ELSE ");
+            out.push_str(&self.statements(p, &["END"])?);
+        }
+        p.expect("END")?;
+        p.expect("CASE")?;
+        out.push_str(" END CASE");
+        Ok(out)
+    }
+
+    // SET var = value[, var = value]: assignments to parameters and local variables.
+    fn set(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        p.expect("SET")?;
+        let mut out = Vec::new();
+        for assignment in split_args(p.until(&[])) {
+            let mut target = Parser::new(assignment);
+            let name = match target.next() {
+                Some(Node::Token(name)) if self.is_variable(name) => name,
+                _ => {
+                    return Err(unsupported(
+                        "SET of user and system variables in stored routines",
+                    ))
+                }
+            };
+            if !target.eat_operator("=") && !target.eat_operator(":=") {
+                return Err(target.unexpected());
+            }
+            out.push(format!(
+                "{} := {}",
+                self.expression(&[Node::Token(name.clone())]),
+                self.expression(&assignment[target.pos..])
+            ));
+        }
+        Ok(out.join("; "))
+    }
+
+    // SIGNAL SQLSTATE [VALUE] 'state' [SET MESSAGE_TEXT = message]
+    fn signal(&mut self, p: &mut Parser) -> Result<String, TranslateError> {
+        p.expect("SIGNAL")?;
+        if !p.eat("SQLSTATE") {
+            return Err(unsupported("SIGNAL of a named condition"));
+        }
+        p.eat("VALUE");
+        let Some(Node::Token(state @ Token::String(_))) = p.next() else {
+            return Err(p.unexpected());
+        };
+        let mut out = format!(
+            "RAISE EXCEPTION USING ERRCODE = {}",
+            self.expression(&[Node::Token(state.clone())])
+        );
+        if p.eat("SET") {
+            for item in split_args(p.until(&[])) {
+                let mut item_parser = Parser::new(item);
+                if item_parser.eat("MESSAGE_TEXT") && item_parser.eat_operator("=") {
+                    out.push_str(&format!(
+                        ", MESSAGE = {}",
+                        self.expression(&item[item_parser.pos..])
+                    ));
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+// A position in a statement's nodes that skips whitespace and comments.
+#[derive(Clone)]
+struct Parser<'a> {
+    nodes: &'a [Node],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(nodes: &'a [Node]) -> Self {
+        Parser { nodes, pos: 0 }
+    }
+
+    fn peek(&mut self) -> Option<&'a Node> {
+        while self.nodes.get(self.pos).is_some_and(Node::is_trivia) {
+            self.pos += 1;
+        }
+        self.nodes.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let node = self.peek();
+        if node.is_some() {
+            self.pos += 1;
+        }
+        node
+    }
+
+    fn peek_word(&mut self, word: &str) -> bool {
+        is_word(self.peek(), word)
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        let found = self.peek_word(word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_operator(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Node::Token(t)) if t.is_operator(op));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_semicolon(&mut self) -> bool {
+        let found = matches!(self.peek(), Some(Node::Token(Token::Semicolon)));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), TranslateError> {
+        if self.eat(word) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // What the routine translation couldn't make sense of.
+    fn unexpected(&mut self) -> TranslateError {
+        match self.peek() {
+            Some(node) => TranslateError::Unsupported(format!(
+                "'{}' here in a stored routine",
+                render(std::slice::from_ref(node))
+            )),
+            None => unsupported("this stored routine"),
+        }
+    }
+
+    // `DEFINER = user`, where the user is `name`, `name@host` or CURRENT_USER[()].
+    fn definer(&mut self) {
+        if !self.eat("DEFINER") {
+            return;
+        }
+        self.eat_operator("=");
+        self.next();
+        if matches!(
+            self.peek(),
+            Some(Node::Token(Token::Variable(_)) | Node::Group(_))
+        ) {
+            self.pos += 1;
+        }
+    }
+
+    // `label:` in front of a block or loop.
+    fn label(&mut self) -> Option<String> {
+        let start = self.pos;
+        if let Some(Node::Token(name)) = self.next() {
+            if self.eat_operator(":") {
+                return literals::identifier_name(name);
+            }
+        }
+        self.pos = start;
+        None
+    }
+
+    fn characteristics(&mut self) {
+        while let Some(&(_, words)) = CHARACTERISTICS
+            .iter()
+            .find(|(word, _)| self.peek_word(word))
+        {
+            for _ in 0..words {
+                self.next();
+            }
+        }
+    }
+
+    // Whether a function's RETURNS type ends here.
+    fn at_body_start(&mut self) -> bool {
+        let mut lookahead = self.clone();
+        lookahead.peek().is_none()
+            || CHARACTERISTICS
+                .iter()
+                .any(|(word, _)| lookahead.peek_word(word))
+            || lookahead.peek_word("BEGIN")
+            || lookahead.peek_word("RETURN")
+            || lookahead.label().is_some()
+    }
+
+    // The nodes up to one of `words` or a semicolon. The END of a CASE expression doesn't end
+    // anything.
+    fn until(&mut self, words: &[&str]) -> &'a [Node] {
+        self.peek();
+        let start = self.pos;
+        let mut cases = 0;
+        while let Some(node) = self.nodes.get(self.pos) {
+            if let Node::Token(t) = node {
+                if matches!(t, Token::Semicolon) && cases == 0 {
+                    break;
+                } else if t.is_word("CASE") {
+                    cases += 1;
+                } else if t.is_word("END") && cases > 0 {
+                    cases -= 1;
+                } else if cases == 0 && words.iter().any(|w| t.is_word(w)) {
+                    break;
+                }
+            }
+            self.pos += 1;
+        }
+        // Without the whitespace before whatever ended it.
+        let mut end = self.pos;
+        while end > start && self.nodes[end - 1].is_trivia() {
+            end -= 1;
+        }
+        &self.nodes[start..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TranslateError, Translator};
+
+    fn translate(sql: &str) -> Result<String, TranslateError> {
+        Translator::new().translate(sql)
+    }
+
+    #[test]
+    fn procedures_become_plpgsql() {
+        assert_eq!(
+            translate(
+                "CREATE PROCEDURE p(IN n INT) BEGIN DECLARE total INT DEFAULT 0; \
+                 SET total = n + 1; UPDATE t SET a = total; END"
+            )
+            .unwrap(),
+            "CREATE PROCEDURE p(IN n INT) LANGUAGE plpgsql AS $routine$ DECLARE total INT := 0; \
+             BEGIN total := n + 1; UPDATE t SET a = total; END $routine$"
+        );
+    }
+
+    #[test]
+    fn compound_statements_become_do_blocks() {
+        assert_eq!(
+            translate("BEGIN NOT ATOMIC DECLARE n INT; SELECT COUNT(*) INTO n FROM t; INSERT INTO log VALUES (n); END")
+                .unwrap(),
+            "DO $block$ DECLARE n INT; BEGIN SELECT COUNT(*) INTO n FROM t; INSERT INTO log VALUES (n); END $block$"
+        );
+    }
+
+    #[test]
+    fn refuses_result_sets() {
+        assert_eq!(
+            translate("CREATE PROCEDURE p() BEGIN SELECT * FROM t; END")
+                .unwrap_err()
+                .to_string(),
+            "result sets from stored procedures is not supported"
+        );
+    }
+
+    #[test]
+    fn scripts_understand_delimiter() {
+        assert_eq!(
+            Translator::new()
+                .translate_script("DELIMITER //\nCREATE PROCEDURE p() BEGIN UPDATE t SET a = 1; END//\nDELIMITER ;\n")
+                .unwrap()
+                .trim(),
+            "CREATE PROCEDURE p() LANGUAGE plpgsql AS $routine$ BEGIN UPDATE t SET a = 1; END $routine$;"
+        );
+    }
+
+    #[test]
+    fn calls_take_an_argument_list() {
+        for (mysql, postgres) in [
+            ("CALL p", "CALL p()"),
+            ("CALL s.p(1, 'a')", "CALL s.p(1, 'a')"),
+        ] {
+            assert_eq!(translate(mysql).unwrap(), postgres);
+        }
+    }
+
+    #[test]
+    fn functions_drop_definer_and_characteristics() {
+        assert_eq!(
+            translate("CREATE FUNCTION f(x INT) RETURNS INT DETERMINISTIC RETURN x + 1").unwrap(),
+            "CREATE FUNCTION f(x INT) RETURNS INT LANGUAGE plpgsql AS $routine$ \
+             BEGIN RETURN x + 1; END $routine$"
+        );
+        assert_eq!(
+            translate(
+                "CREATE DEFINER=`root`@`%` FUNCTION f(x INT) RETURNS INT DETERMINISTIC NO SQL \
+                 COMMENT 'c' BEGIN DECLARE y INT; SET y = x * 2; RETURN y; END"
+            )
+            .unwrap(),
+            "CREATE FUNCTION f(x INT) RETURNS INT LANGUAGE plpgsql AS $routine$ \
+             DECLARE y INT; BEGIN y := x * 2; RETURN y; END $routine$"
+        );
+    }
+
+    #[test]
+    fn loops_cursors_and_signals() {
+        // A loop can be the whole body, semicolons and all.
+        assert_eq!(
+            translate(
+                "CREATE PROCEDURE p(INOUT n INT) l1: LOOP SET n = n + 1; \
+                 IF n > 10 THEN LEAVE l1; ELSEIF n = 5 THEN ITERATE l1; END IF; END LOOP l1; SELECT 1"
+            )
+            .unwrap(),
+            "CREATE PROCEDURE p(INOUT n INT) LANGUAGE plpgsql AS $routine$ BEGIN <<l1>> LOOP \
+             n := n + 1; IF n > 10 THEN EXIT l1; ELSIF n = 5 THEN CONTINUE l1; END IF; \
+             END LOOP l1; END $routine$; SELECT 1"
+        );
+        // REPEAT(str, n) doesn't open a loop.
+        assert_eq!(
+            translate(
+                "CREATE PROCEDURE p(n INT, OUT s TEXT) BEGIN SELECT REPEAT('x', n) INTO s; \
+                 w: WHILE n > 0 DO SET n = n - 1; END WHILE w; \
+                 REPEAT SET n = n + 1; UNTIL n > 3 END REPEAT; END; SELECT 1"
+            )
+            .unwrap(),
+            "CREATE PROCEDURE p(n INT, OUT s TEXT) LANGUAGE plpgsql AS $routine$ BEGIN \
+             SELECT REPEAT('x', n) INTO s; <<w>> WHILE n > 0 LOOP n := n - 1; END LOOP w; \
+             LOOP n := n + 1; EXIT WHEN n > 3; END LOOP; END $routine$; SELECT 1"
+        );
+        assert_eq!(
+            translate(
+                "CREATE PROCEDURE p() BEGIN DECLARE v INT; DECLARE c CURSOR FOR SELECT a FROM t; \
+                 OPEN c; FETCH c INTO v; CLOSE c; \
+                 IF v IS NULL THEN SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'bad'; END IF; END"
+            )
+            .unwrap(),
+            "CREATE PROCEDURE p() LANGUAGE plpgsql AS $routine$ DECLARE v INT; \
+             c CURSOR FOR SELECT a FROM t; BEGIN OPEN c; FETCH c INTO v; CLOSE c; \
+             IF v IS NULL THEN RAISE EXCEPTION USING ERRCODE = '45000', MESSAGE = 'bad'; END IF; \
+             END $routine$"
+        );
+    }
+
+    #[test]
+    fn refuses_what_plpgsql_lacks() {
+        for (mysql, error) in [
+            (
+                "CREATE PROCEDURE p() BEGIN DECLARE CONTINUE HANDLER FOR NOT FOUND SET @done = 1; END",
+                "DECLARE ... HANDLER is not supported",
+            ),
+            (
+                "CREATE PROCEDURE p() BEGIN SET @x = 1; END",
+                "SET of user and system variables in stored routines is not supported",
+            ),
+        ] {
+            assert_eq!(translate(mysql).unwrap_err().to_string(), error);
+        }
+    }
+}
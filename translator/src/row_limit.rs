@@ -0,0 +1,236 @@
+// ORDER BY and LIMIT on single-table UPDATE and DELETE.
+//
+// MySQL lets `UPDATE`/`DELETE` pick the rows to change with `ORDER BY ... LIMIT n`, which batch
+// jobs use to work through a table in chunks. PostgreSQL has no such clauses, so the rows are
+// chosen by a subquery on their physical location instead:
+//
+//   DELETE FROM t WHERE ctid IN (SELECT ctid FROM t WHERE ... ORDER BY ... LIMIT n)
+
+use super::{is_word, parse_fragment, render, statement_starts_with, Node, Token};
+
+// DELETE/UPDATE modifiers that PostgreSQL doesn't know and that don't change the result.
+const MODIFIERS: &[&str] = &["LOW_PRIORITY", "QUICK", "IGNORE"];
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    let rewritten = if statement_starts_with(&nodes, &["DELETE"]) {
+        rewrite_delete(&nodes)
+    } else if statement_starts_with(&nodes, &["UPDATE"]) {
+        rewrite_update(&nodes)
+    } else {
+        None
+    };
+    match rewritten {
+        Some(sql) => parse_fragment(&sql),
+        None => nodes,
+    }
+}
+
+// The clauses of a single-table UPDATE or DELETE, as rendered SQL.
+struct Clauses {
+    // Table name and optional alias.
+    table: String,
+    // UPDATE only: the SET list.
+    set: Option<String>,
+    condition: Option<String>,
+    order_by: Option<String>,
+    limit: Option<String>,
+}
+
+impl Clauses {
+    fn needs_rewrite(&self) -> bool {
+        self.order_by.is_some() || self.limit.is_some()
+    }
+
+    // `WHERE ...` for the outer statement.
+    fn where_clause(&self) -> String {
+        match (&self.condition, &self.limit) {
+            // Without a LIMIT the ORDER BY has no effect and is simply dropped.
+            (Some(condition), None) => format!(" WHERE {}", condition),
+            (None, None) => String::new(),
+            (condition, Some(limit)) => {
+                let mut subquery = format!("SELECT ctid FROM {}", self.table);
+                if let Some(condition) = condition {
+                    subquery.push_str(&format!(" WHERE {}", condition));
+                }
+                if let Some(order_by) = &self.order_by {
+                    subquery.push_str(&format!(" ORDER BY {}", order_by));
+                }
+                subquery.push_str(&format!(" LIMIT {}", limit));
+                format!(" WHERE ctid IN ({})", subquery)
+            }
+        }
+    }
+}
+
+fn rewrite_delete(nodes: &[Node]) -> Option<String> {
+    let words = significant(nodes);
+    // DELETE [modifiers] FROM table ...; multi-table deletes can't have ORDER BY or LIMIT.
+    let from = 1 + words[1..]
+        .iter()
+        .take_while(|(_, n)| MODIFIERS.iter().any(|m| is_word(Some(n), m)))
+        .count();
+    if !words
+        .get(from)
+        .is_some_and(|(_, n)| is_word(Some(n), "FROM"))
+    {
+        return None;
+    }
+    let clauses = clauses(nodes, &words[from + 1..], false)?;
+    if !clauses.needs_rewrite() {
+        return None;
+    }
+    Some(format!(
+        "DELETE FROM {}{}",
+        clauses.table,
+        clauses.where_clause()
+    ))
+}
+
+fn rewrite_update(nodes: &[Node]) -> Option<String> {
+    let words = significant(nodes);
+    let table = 1 + words[1..]
+        .iter()
+        .take_while(|(_, n)| MODIFIERS.iter().any(|m| is_word(Some(n), m)))
+        .count();
+    let clauses = clauses(nodes, &words[table..], true)?;
+    if !clauses.needs_rewrite() {
+        return None;
+    }
+    Some(format!(
+        "UPDATE {} SET {}{}",
+        clauses.table,
+        clauses.set.as_deref()?,
+        clauses.where_clause()
+    ))
+}
+
+// Significant top-level nodes with their index in `nodes`.
+fn significant(nodes: &[Node]) -> Vec<(usize, &Node)> {
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !n.is_trivia())
+        .collect()
+}
+
+// Splits the statement from the table reference (`words[0]`) onwards into its clauses.
+fn clauses(nodes: &[Node], words: &[(usize, &Node)], update: bool) -> Option<Clauses> {
+    let position = |word: &str| words.iter().position(|(_, n)| is_word(Some(n), word));
+    let set = if update { Some(position("SET")?) } else { None };
+    let where_ = position("WHERE");
+    let order = position("ORDER").filter(|&i| {
+        words
+            .get(i + 1)
+            .is_some_and(|(_, n)| is_word(Some(n), "BY"))
+    });
+    let limit = position("LIMIT");
+
+    // Clause boundaries, in the order MySQL requires them.
+    let mut bounds: Vec<usize> = [set, where_, order, limit].into_iter().flatten().collect();
+    if bounds.windows(2).any(|w| w[0] >= w[1]) {
+        return None;
+    }
+    bounds.push(words.len());
+
+    // Source text between two significant positions.
+    let text = |from: usize, to: usize| -> String {
+        if from >= to {
+            return String::new();
+        }
+        let start = words[from].0;
+        let end = words.get(to).map_or(nodes.len(), |(i, _)| *i);
+        render(&nodes[start..end]).trim().to_string()
+    };
+    let clause = |at: Option<usize>, skip: usize| {
+        at.map(|i| {
+            let end = *bounds.iter().find(|&&b| b > i).expect("end bound");
+            text(i + skip, end)
+        })
+    };
+
+    let table_end = bounds[0];
+    if table_end == 0 {
+        return None;
+    }
+    // Joins and comma-separated table lists make it a multi-table statement.
+    let multi_table = words[..table_end]
+        .iter()
+        .any(|(_, n)| matches!(n, Node::Token(Token::Comma)) || is_word(Some(n), "JOIN"));
+    if multi_table {
+        return None;
+    }
+
+    let limit = clause(limit, 1);
+    // MySQL only takes a row count here; `LIMIT offset, count` is a syntax error.
+    if limit
+        .as_deref()
+        .is_some_and(|l| l.contains(',') || l.to_ascii_uppercase().contains("OFFSET"))
+    {
+        return None;
+    }
+    Some(Clauses {
+        table: text(0, table_end),
+        set: clause(set, 1),
+        condition: clause(where_, 1),
+        order_by: clause(order, 2),
+        limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    #[test]
+    fn order_by_and_limit_select_the_rows_by_ctid() {
+        let translator = Translator::new();
+        assert_eq!(
+            translator
+                .translate("DELETE FROM t ORDER BY id LIMIT 10")
+                .unwrap(),
+            "DELETE FROM t WHERE ctid IN (SELECT ctid FROM t ORDER BY id LIMIT 10)"
+        );
+        assert_eq!(
+            translator
+                .translate("UPDATE t SET a = 1 ORDER BY id LIMIT 5")
+                .unwrap(),
+            "UPDATE t SET a = 1 WHERE ctid IN (SELECT ctid FROM t ORDER BY id LIMIT 5)"
+        );
+    }
+
+    #[test]
+    fn keeps_the_where_clause_and_the_table_as_written() {
+        let translator = Translator::new();
+        for (sql, expected) in [
+            (
+                "DELETE FROM t WHERE a = 1 OR b = 2 ORDER BY id LIMIT 10",
+                "DELETE FROM t WHERE ctid IN (SELECT ctid FROM t WHERE a = 1 OR b = 2 ORDER BY id LIMIT 10)",
+            ),
+            (
+                "DELETE LOW_PRIORITY QUICK IGNORE FROM s.t WHERE a = 1 LIMIT 10",
+                "DELETE FROM s.t WHERE ctid IN (SELECT ctid FROM s.t WHERE a = 1 LIMIT 10)",
+            ),
+            (
+                "DELETE FROM t AS x WHERE x.a = 1 LIMIT 3",
+                "DELETE FROM t AS x WHERE ctid IN (SELECT ctid FROM t AS x WHERE x.a = 1 LIMIT 3)",
+            ),
+            (
+                "UPDATE t SET a = 'LIMIT 1' WHERE b = 2 LIMIT ?",
+                "UPDATE t SET a = 'LIMIT 1' WHERE ctid IN (SELECT ctid FROM t WHERE b = 2 LIMIT ?)",
+            ),
+        ] {
+            assert_eq!(translator.translate(sql).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn leaves_statements_without_their_own_limit_alone() {
+        let translator = Translator::new();
+        for sql in [
+            "DELETE FROM t WHERE id IN (SELECT id FROM u ORDER BY id LIMIT 2)",
+            "DELETE t FROM t JOIN u ON t.id = u.id WHERE u.x = 1",
+        ] {
+            assert_eq!(translator.translate(sql).unwrap(), sql);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// MariaDB sequences on PostgreSQL's.
+//
+// Both databases have `CREATE SEQUENCE` with nearly the same options; MariaDB additionally
+// accepts `=` between an option and its value and spells some options as one word:
+//
+//   CREATE SEQUENCE s START WITH 100 INCREMENT = 10 NOCACHE NOCYCLE
+//   -> CREATE SEQUENCE s START WITH 100 INCREMENT 10 CACHE 1 NO CYCLE
+//
+// The value functions take the sequence as an identifier rather than a regclass string, and
+// there is also a standard syntax for them:
+//
+//   NEXT VALUE FOR s, NEXTVAL(s)      -> nextval('s')
+//   PREVIOUS VALUE FOR s, LASTVAL(s)  -> currval('s')
+//   SETVAL(s, 1000)                   -> setval('s', 1000, true)
+//
+// Unlike MariaDB, currval() fails instead of returning NULL before the session has called
+// nextval() on the sequence.
+
+use super::functions::FunctionRegistry;
+use super::{literals, parse_fragment, render, statement_starts_with, Node, Token};
+
+// MariaDB's one-word options and their PostgreSQL spelling.
+const OPTION_WORDS: &[(&str, &str)] = &[
+    ("NOCYCLE", "NO CYCLE"),
+    ("NOMINVALUE", "NO MINVALUE"),
+    ("NOMAXVALUE", "NO MAXVALUE"),
+    // PostgreSQL's smallest cache is one value, which is no cache.
+    ("NOCACHE", "CACHE 1"),
+];
+
+// Options that may be followed by `=` in MariaDB.
+const VALUE_OPTIONS: &[&str] = &[
+    "START",
+    "INCREMENT",
+    "MINVALUE",
+    "MAXVALUE",
+    "CACHE",
+    "RESTART",
+];
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["CREATE", "OR", "REPLACE", "SEQUENCE"])
+        || statement_starts_with(
+            &nodes,
+            &["CREATE", "OR", "REPLACE", "TEMPORARY", "SEQUENCE"],
+        )
+    {
+        replace_sequence(nodes)
+    } else if statement_starts_with(&nodes, &["CREATE", "SEQUENCE"])
+        || statement_starts_with(&nodes, &["CREATE", "TEMPORARY", "SEQUENCE"])
+        || statement_starts_with(&nodes, &["ALTER", "SEQUENCE"])
+    {
+        rewrite_options(nodes)
+    } else {
+        rewrite_value_for(nodes)
+    }
+}
+
+pub fn register(registry: &mut FunctionRegistry) {
+    registry.register("NEXTVAL", |args| match args {
+        [sequence] => Some(format!("nextval({})", regclass(sequence)?)),
+        _ => None,
+    });
+    // PostgreSQL's own lastval() takes no argument and is left alone.
+    registry.register("LASTVAL", |args| match args {
+        [sequence] => Some(format!("currval({})", regclass(sequence)?)),
+        _ => None,
+    });
+    registry.register("SETVAL", setval);
+}
+
+// SETVAL(sequence, value [, is_used [, round]]). PostgreSQL has no rounds, so only round 0 (the
+// current one) is translated.
+fn setval(args: &[String]) -> Option<String> {
+    let (sequence, value, used) = match args {
+        [sequence, value] => (sequence, value, "true"),
+        [sequence, value, used] => (sequence, value, used.as_str()),
+        [sequence, value, used, round] if round == "0" => (sequence, value, used.as_str()),
+        _ => return None,
+    };
+    // MariaDB's is_used is an integer, PostgreSQL's is_called a boolean.
+    let used = match used {
+        "1" | "true" | "TRUE" => "true",
+        "0" | "false" | "FALSE" => "false",
+        other => {
+            return Some(format!(
+                "setval({}, {}, ({}) <> 0)",
+                regclass(sequence)?,
+                value,
+                other
+            ))
+        }
+    };
+    Some(format!(
+        "setval({}, {}, {})",
+        regclass(sequence)?,
+        value,
+        used
+    ))
+}
+
+// The sequence argument as a regclass string. A string argument is already one (PostgreSQL's
+// own `nextval('s')`), so those calls are left untranslated.
+fn regclass(sequence: &str) -> Option<String> {
+    if sequence.is_empty() || sequence.starts_with('\'') || sequence.starts_with("E'") {
+        return None;
+    }
+    Some(literals::pg_string(sequence))
+}
+
+fn rewrite_options(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut iter = nodes.into_iter().peekable();
+
+    while let Some(node) = iter.next() {
+        let Node::Token(token) = &node else {
+            out.push(node);
+            continue;
+        };
+        if let Some((_, replacement)) = OPTION_WORDS.iter().find(|(w, _)| token.is_word(w)) {
+            out.extend(parse_fragment(replacement));
+        } else if token.is_word("ENGINE") {
+            // A table option MariaDB accepts because sequences are tables there.
+            skip_value(&mut iter);
+            drop_trailing_whitespace(&mut out);
+        } else if token.is_operator("=") && follows_value_option(&out) {
+            while iter.next_if(Node::is_trivia).is_some() {}
+            drop_trailing_whitespace(&mut out);
+            out.push(Node::Token(Token::Whitespace(" ".to_string())));
+        } else {
+            out.push(node);
+        }
+    }
+
+    out
+}
+
+// Whether the last word written is an option that takes a value.
+fn follows_value_option(out: &[Node]) -> bool {
+    matches!(
+        out.iter().rev().find(|n| !n.is_trivia()),
+        Some(Node::Token(t)) if VALUE_OPTIONS.iter().any(|w| t.is_word(w))
+    )
+}
+
+// Consumes `[=] value` after an option.
+fn skip_value(iter: &mut std::iter::Peekable<std::vec::IntoIter<Node>>) {
+    while iter.next_if(Node::is_trivia).is_some() {}
+    if iter
+        .next_if(|n| matches!(n, Node::Token(t) if t.is_operator("=")))
+        .is_some()
+    {
+        while iter.next_if(Node::is_trivia).is_some() {}
+    }
+    iter.next();
+}
+
+fn drop_trailing_whitespace(out: &mut Vec<Node>) {
+    while out.last().is_some_and(Node::is_trivia) {
+        out.pop();
+    }
+}
+
+// PostgreSQL has no CREATE OR REPLACE SEQUENCE; the old sequence is dropped and the new one
+// created in a single statement.
+fn replace_sequence(nodes: Vec<Node>) -> Vec<Node> {
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    // CREATE OR REPLACE [TEMPORARY] SEQUENCE name
+    let sequence = significant
+        .iter()
+        .position(|&i| matches!(&nodes[i], Node::Token(t) if t.is_word("SEQUENCE")))
+        .expect("checked by the caller");
+    let Some((name, _)) = sequence_name(&nodes, &significant[sequence + 1..]) else {
+        return nodes;
+    };
+    let create: Vec<Node> = nodes[..significant[1]]
+        .iter()
+        .cloned()
+        .chain(nodes[significant[3]..].iter().cloned())
+        .collect();
+    let create = render(&rewrite_options(create));
+    parse_fragment(&format!(
+        "DO $sequence$ BEGIN DROP SEQUENCE IF EXISTS {}; {}; END $sequence$",
+        name,
+        create.trim()
+    ))
+}
+
+// A (possibly schema-qualified) sequence name at the given significant positions, and the index
+// after it.
+fn sequence_name(nodes: &[Node], significant: &[usize]) -> Option<(String, usize)> {
+    let is_name =
+        |i: &usize| matches!(&nodes[*i], Node::Token(t) if literals::identifier_name(t).is_some());
+    match significant {
+        [first, dot, second, ..]
+            if is_name(first)
+                && matches!(&nodes[*dot], Node::Token(t) if t.is_operator("."))
+                && is_name(second) =>
+        {
+            Some((render(&nodes[*first..=*second]), second + 1))
+        }
+        [first, ..] if is_name(first) => Some((render(&nodes[*first..=*first]), first + 1)),
+        _ => None,
+    }
+}
+
+// NEXT VALUE FOR s and PREVIOUS VALUE FOR s, anywhere in the statement, become the function
+// calls the registry translates.
+fn rewrite_value_for(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut i = 0;
+
+    while i < nodes.len() {
+        let node = &nodes[i];
+        if let Node::Group(inner) = node {
+            out.push(Node::Group(rewrite_value_for(inner.clone())));
+            i += 1;
+            continue;
+        }
+        let function = match node {
+            Node::Token(t) if t.is_word("NEXT") => "NEXTVAL",
+            Node::Token(t) if t.is_word("PREVIOUS") => "LASTVAL",
+            _ => {
+                out.push(node.clone());
+                i += 1;
+                continue;
+            }
+        };
+        let significant: Vec<usize> = (i + 1..nodes.len())
+            .filter(|&j| !nodes[j].is_trivia())
+            .take(4)
+            .collect();
+        let keywords = matches!(
+            significant.as_slice(),
+            [value, r#for, ..]
+                if matches!(&nodes[*value], Node::Token(t) if t.is_word("VALUE"))
+                    && matches!(&nodes[*r#for], Node::Token(t) if t.is_word("FOR"))
+        );
+        let name = keywords
+            .then(|| {
+                let rest: Vec<usize> = (significant[1] + 1..nodes.len())
+                    .filter(|&j| !nodes[j].is_trivia())
+                    .take(3)
+                    .collect();
+                sequence_name(&nodes, &rest)
+            })
+            .flatten();
+        let Some((name, end)) = name else {
+            out.push(node.clone());
+            i += 1;
+            continue;
+        };
+        out.extend(parse_fragment(&format!("{}({})", function, name)));
+        i = end;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn sequence_functions() {
+        assert_eq!(
+            translate("SELECT NEXT VALUE FOR s, LASTVAL(s), SETVAL(s, 10)"),
+            "SELECT nextval('s'), currval('s'), setval('s', 10, true)"
+        );
+    }
+
+    #[test]
+    fn sequence_options() {
+        assert_eq!(
+            translate("CREATE SEQUENCE s START WITH 10 INCREMENT BY 2 NOCACHE"),
+            "CREATE SEQUENCE s START WITH 10 INCREMENT BY 2 CACHE 1"
+        );
+    }
+
+    #[test]
+    fn qualified_and_quoted_sequences_and_setval_flags() {
+        assert_eq!(
+            translate("SELECT NEXTVAL(db.s), NEXT VALUE FOR `Seq`, PREVIOUS VALUE FOR s, SETVAL(s, 10, 0)"),
+            "SELECT nextval('db.s'), nextval('\"seq\"'), currval('s'), setval('s', 10, false)"
+        );
+    }
+
+    #[test]
+    fn options_with_equals_signs_and_one_word_options() {
+        assert_eq!(
+            translate(
+                "CREATE SEQUENCE IF NOT EXISTS db.s START WITH 100 INCREMENT = 10 MINVALUE = 1 \
+                 NOCACHE NOCYCLE NOMAXVALUE"
+            ),
+            "CREATE SEQUENCE IF NOT EXISTS db.s START WITH 100 INCREMENT 10 MINVALUE 1 CACHE 1 NO CYCLE NO MAXVALUE"
+        );
+        assert_eq!(
+            translate("ALTER SEQUENCE s RESTART = 5 INCREMENT = 2"),
+            "ALTER SEQUENCE s RESTART 5 INCREMENT 2"
+        );
+    }
+
+    #[test]
+    fn returning_passes_through() {
+        for sql in [
+            "INSERT INTO t (a) VALUES (1) RETURNING id, a",
+            "DELETE FROM t WHERE a = 1 RETURNING *",
+        ] {
+            assert_eq!(translate(sql), sql);
+        }
+    }
+}
@@ -0,0 +1,563 @@
+// Table DDL MySQL spells differently.
+//
+//   TRUNCATE TABLE t             -> TRUNCATE TABLE t RESTART IDENTITY
+//   RENAME TABLE a TO b          -> ALTER TABLE a RENAME TO b
+//   CREATE TABLE b LIKE a        -> CREATE TABLE b (LIKE a INCLUDING ALL)
+//   CREATE TABLE t (...) ENGINE=MEMORY DEFAULT CHARSET=utf8mb4
+//                                -> CREATE TABLE t (...)
+//   CREATE TABLE t SELECT ...    -> CREATE TABLE t AS SELECT ...
+//   DROP TEMPORARY TABLE t       -> DROP TABLE pg_temp.t
+//   ALTER TABLE t ENGINE=InnoDB, CONVERT TO CHARACTER SET utf8mb4
+//                                -> DO $$ BEGIN END $$
+//
+// MySQL's TRUNCATE starts AUTO_INCREMENT over, so the identity columns those become are reset
+// too. RENAME TABLE can rename several tables, and move them between schemas; the ALTER TABLE
+// statements that takes are run in one DO block, which keeps the rename atomic as in MySQL.
+// INCLUDING ALL copies defaults, constraints, indexes and identity columns, the closest to
+// MySQL's copy of the table definition.
+//
+// Temporary tables are PostgreSQL's own: every client connection has a PostgreSQL session of its
+// own, so they live and die with the connection and shadow permanent tables of the same name, as
+// in MySQL.
+
+use super::constraints::NOOP;
+use super::{is_word, literals, parse_fragment, split_args, statement_starts_with, Node, Token};
+
+pub fn rewrite(nodes: Vec<Node>) -> Vec<Node> {
+    if statement_starts_with(&nodes, &["TRUNCATE"]) {
+        truncate(nodes)
+    } else if statement_starts_with(&nodes, &["RENAME", "TABLE"])
+        || statement_starts_with(&nodes, &["RENAME", "TABLES"])
+    {
+        rename(nodes)
+    } else if statement_starts_with(&nodes, &["CREATE", "TABLE"])
+        || statement_starts_with(&nodes, &["CREATE", "TEMPORARY", "TABLE"])
+    {
+        create_select(table_options(create_like(nodes)))
+    } else if statement_starts_with(&nodes, &["ALTER", "TABLE"]) {
+        alter_options(nodes)
+    } else if statement_starts_with(&nodes, &["DROP", "TEMPORARY", "TABLE"]) {
+        drop_temporary(nodes)
+    } else {
+        nodes
+    }
+}
+
+fn truncate(mut nodes: Vec<Node>) -> Vec<Node> {
+    // Already PostgreSQL's syntax.
+    let explicit = nodes.iter().any(|n| {
+        matches!(n, Node::Token(t) if t.is_word("RESTART") || t.is_word("CONTINUE")
+            || t.is_word("CASCADE") || t.is_word("RESTRICT"))
+    });
+    if explicit {
+        return nodes;
+    }
+    let trailing = trailing_trivia(&nodes);
+    let rest = nodes.split_off(nodes.len() - trailing);
+    nodes.extend(parse_fragment(" RESTART IDENTITY"));
+    nodes.extend(rest);
+    nodes
+}
+
+fn rename(nodes: Vec<Node>) -> Vec<Node> {
+    let table = nodes
+        .iter()
+        .position(|n| matches!(n, Node::Token(t) if t.is_word("TABLE") || t.is_word("TABLES")))
+        .expect("checked by the caller");
+    let end = nodes.len() - trailing_trivia(&nodes);
+
+    let mut statements = Vec::new();
+    for pair in split_args(&nodes[table + 1..end]) {
+        let Some(to) = pair
+            .iter()
+            .position(|n| matches!(n, Node::Token(t) if t.is_word("TO")))
+        else {
+            return nodes;
+        };
+        let (Some(from), Some(target)) = (
+            TableName::parse(&pair[..to]),
+            TableName::parse(&pair[to + 1..]),
+        ) else {
+            return nodes;
+        };
+        statements.push(format!(
+            "ALTER TABLE {} RENAME TO {}",
+            from.render(),
+            target.name
+        ));
+        // PostgreSQL renames within a schema and moves separately.
+        if let Some(schema) = &target.schema {
+            let same = from.schema.as_ref().is_some_and(|from| {
+                literals::identifier_name(from) == literals::identifier_name(schema)
+            });
+            if !same {
+                let renamed = TableName {
+                    schema: from.schema.clone(),
+                    name: target.name.clone(),
+                };
+                statements.push(format!(
+                    "ALTER TABLE {} SET SCHEMA {}",
+                    renamed.render(),
+                    schema
+                ));
+            }
+        }
+    }
+
+    let sql = match statements.as_slice() {
+        [] => return nodes,
+        [statement] => statement.clone(),
+        _ => format!("DO $rename$ BEGIN {}; END $rename$", statements.join("; ")),
+    };
+    let mut out = parse_fragment(&sql);
+    out.extend(nodes[end..].iter().cloned());
+    out
+}
+
+/// The new table's name in CREATE [TEMPORARY] TABLE [IF NOT EXISTS] name: where it starts and
+/// where it ends. `None` for other statements.
+pub(super) fn created_table(nodes: &[Node]) -> Option<(usize, usize)> {
+    if !statement_starts_with(nodes, &["CREATE", "TABLE"])
+        && !statement_starts_with(nodes, &["CREATE", "TEMPORARY", "TABLE"])
+    {
+        return None;
+    }
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    let mut name = significant
+        .iter()
+        .position(|&i| is_word(nodes.get(i), "TABLE"))?
+        + 1;
+    if significant
+        .get(name)
+        .is_some_and(|&i| is_word(nodes.get(i), "IF"))
+    {
+        name += 3;
+    }
+    let start = *significant.get(name)?;
+    let qualified = significant
+        .get(name + 1)
+        .is_some_and(|&i| matches!(&nodes[i], Node::Token(t) if t.is_operator(".")));
+    let end = if qualified {
+        *significant.get(name + 2)? + 1
+    } else {
+        start + 1
+    };
+    TableName::parse(&nodes[start..end])?;
+    Some((start, end))
+}
+
+/// The first node after `from` that isn't trivia.
+pub(super) fn next_significant(nodes: &[Node], from: usize) -> Option<usize> {
+    (from..nodes.len()).find(|&i| !nodes[i].is_trivia())
+}
+
+// `CREATE TABLE b LIKE a`, and `CREATE TABLE b (LIKE a)`, which MySQL accepts as well.
+fn create_like(nodes: Vec<Node>) -> Vec<Node> {
+    let Some((_, name_end)) = created_table(&nodes) else {
+        return nodes;
+    };
+    let Some(rest) = next_significant(&nodes, name_end) else {
+        return nodes;
+    };
+    let end = nodes.len() - trailing_trivia(&nodes);
+
+    let source = match &nodes[rest] {
+        Node::Group(inner) => {
+            let Some(like) = inner.iter().position(|n| !n.is_trivia()) else {
+                return nodes;
+            };
+            if !matches!(&inner[like], Node::Token(t) if t.is_word("LIKE")) || rest + 1 != end {
+                return nodes;
+            }
+            TableName::parse(&inner[like + 1..])
+        }
+        Node::Token(t) if t.is_word("LIKE") => TableName::parse(&nodes[rest + 1..end]),
+        _ => return nodes,
+    };
+    let Some(source) = source else {
+        return nodes;
+    };
+    let mut out = nodes[..rest].to_vec();
+    out.push(Node::Group(parse_fragment(&format!(
+        "LIKE {} INCLUDING ALL",
+        source.render()
+    ))));
+    out.extend(nodes[end..].iter().cloned());
+    out
+}
+
+// Drops the table options after the column definitions, `ENGINE=MEMORY DEFAULT CHARSET=utf8mb4`
+// and the like. PostgreSQL has no use for them.
+fn table_options(nodes: Vec<Node>) -> Vec<Node> {
+    let Some((_, name_end)) = created_table(&nodes) else {
+        return nodes;
+    };
+    let mut start = name_end;
+    if let Some(group) = next_significant(&nodes, start) {
+        if matches!(&nodes[group], Node::Group(_)) {
+            start = group + 1;
+        }
+    }
+
+    // Each option is `name [=] value`; commas between them are optional.
+    let mut end = start;
+    let mut i = start;
+    while let Some(word) = next_significant(&nodes, i) {
+        if matches!(&nodes[word], Node::Token(Token::Comma)) {
+            i = word + 1;
+            continue;
+        }
+        let Some(value) = option_value(&nodes, word) else {
+            break;
+        };
+        i = value + 1;
+        end = i;
+    }
+    if end == start {
+        return nodes;
+    }
+    let mut out = nodes[..start].to_vec();
+    out.extend(nodes[end..].iter().cloned());
+    out
+}
+
+// Drops the ALTER TABLE actions that only set table options, `ENGINE=InnoDB` and the like, and
+// `CONVERT TO CHARACTER SET utf8mb4`: the database's encoding is the only one PostgreSQL has.
+// With no action left the statement is a no-op.
+fn alter_options(nodes: Vec<Node>) -> Vec<Node> {
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    let Some(&start) = significant.get(2) else {
+        return nodes;
+    };
+    let qualified = significant
+        .get(3)
+        .is_some_and(|&i| matches!(&nodes[i], Node::Token(t) if t.is_operator(".")));
+    let name_end = match qualified {
+        true => significant.get(4).map_or(nodes.len(), |&i| i + 1),
+        false => start + 1,
+    };
+    if TableName::parse(&nodes[start..name_end]).is_none() {
+        return nodes;
+    }
+
+    let actions: Vec<&[Node]> = nodes[name_end..]
+        .split(|n| matches!(n, Node::Token(Token::Comma)))
+        .collect();
+    let kept: Vec<&[Node]> = actions
+        .iter()
+        .copied()
+        .filter(|action| !only_options(action))
+        .collect();
+    if kept.len() == actions.len() {
+        return nodes;
+    }
+    if kept.is_empty() {
+        return parse_fragment(NOOP);
+    }
+    let mut out = nodes[..name_end].to_vec();
+    for (i, action) in kept.into_iter().enumerate() {
+        if i > 0 {
+            out.push(Node::Token(Token::Comma));
+        }
+        out.extend(action.iter().cloned());
+    }
+    out
+}
+
+// Whether an ALTER TABLE action is nothing but table options.
+fn only_options(action: &[Node]) -> bool {
+    let mut i = 0;
+    if let Some(convert) = next_significant(action, 0) {
+        if is_word(action.get(convert), "CONVERT") {
+            match next_significant(action, convert + 1) {
+                Some(to) if is_word(action.get(to), "TO") => i = to + 1,
+                _ => return false,
+            }
+        }
+    }
+    let mut options = 0;
+    while let Some(word) = next_significant(action, i) {
+        let Some(value) = option_value(action, word) else {
+            return false;
+        };
+        i = value + 1;
+        options += 1;
+    }
+    options > 0
+}
+
+// The value of the table option starting at `at`, if there is one there.
+fn option_value(nodes: &[Node], at: usize) -> Option<usize> {
+    let word = |i: usize| match &nodes[i] {
+        Node::Token(t @ Token::Word(_)) => Some(t.to_string().to_ascii_uppercase()),
+        _ => None,
+    };
+    let mut name = at;
+    if word(name).as_deref() == Some("DEFAULT") {
+        name = next_significant(nodes, name + 1)?;
+    }
+    let option = word(name)?;
+    // Options spelled as two words.
+    let second = match option.as_str() {
+        "CHARACTER" => Some("SET"),
+        "DATA" | "INDEX" => Some("DIRECTORY"),
+        _ => None,
+    };
+    if let Some(second) = second {
+        name = next_significant(nodes, name + 1)?;
+        if word(name).as_deref() != Some(second) {
+            return None;
+        }
+    } else if !TABLE_OPTIONS.contains(&option.as_str()) {
+        return None;
+    }
+
+    let mut value = next_significant(nodes, name + 1)?;
+    if matches!(&nodes[value], Node::Token(t) if t.is_operator("=")) {
+        value = next_significant(nodes, value + 1)?;
+    }
+    match &nodes[value] {
+        Node::Token(
+            Token::Word(_) | Token::String(_) | Token::Number(_) | Token::QuotedIdent(_),
+        )
+        | Node::Group(_) => Some(value),
+        _ => None,
+    }
+}
+
+// MySQL table options with a single-word name.
+const TABLE_OPTIONS: &[&str] = &[
+    "ENGINE",
+    "TYPE",
+    "CHARSET",
+    "COLLATE",
+    "ROW_FORMAT",
+    "COMMENT",
+    "AVG_ROW_LENGTH",
+    "CHECKSUM",
+    "COMPRESSION",
+    "CONNECTION",
+    "DELAY_KEY_WRITE",
+    "ENCRYPTION",
+    "INSERT_METHOD",
+    "KEY_BLOCK_SIZE",
+    "MAX_ROWS",
+    "MIN_ROWS",
+    "PACK_KEYS",
+    "STATS_AUTO_RECALC",
+    "STATS_PERSISTENT",
+    "STATS_SAMPLE_PAGES",
+    "TABLESPACE",
+    "UNION",
+];
+
+// `CREATE TABLE t SELECT ...`: MySQL doesn't need the AS.
+fn create_select(nodes: Vec<Node>) -> Vec<Node> {
+    let Some((_, name_end)) = created_table(&nodes) else {
+        return nodes;
+    };
+    let Some(query) = next_significant(&nodes, name_end) else {
+        return nodes;
+    };
+    let select = match &nodes[query] {
+        Node::Token(t) => t.is_word("SELECT") || t.is_word("WITH"),
+        Node::Group(inner) => inner
+            .iter()
+            .find(|n| !n.is_trivia())
+            .is_some_and(|n| matches!(n, Node::Token(t) if t.is_word("SELECT"))),
+    };
+    if !select {
+        return nodes;
+    }
+    let mut out = nodes[..query].to_vec();
+    out.extend(parse_fragment("AS "));
+    out.extend(nodes[query..].iter().cloned());
+    out
+}
+
+// DROP TEMPORARY TABLE only drops temporary tables; those are in the session's pg_temp schema.
+fn drop_temporary(nodes: Vec<Node>) -> Vec<Node> {
+    let significant: Vec<usize> = (0..nodes.len())
+        .filter(|&i| !nodes[i].is_trivia())
+        .collect();
+    // DROP TEMPORARY TABLE [IF EXISTS]
+    let Some(&table) = significant
+        .get(2)
+        .filter(|&&i| is_word(nodes.get(i), "TABLE"))
+    else {
+        return nodes;
+    };
+    let if_exists = significant
+        .get(3)
+        .is_some_and(|&i| is_word(nodes.get(i), "IF"));
+    let list_start = if if_exists {
+        match significant.get(5) {
+            Some(&i) => i,
+            None => return nodes,
+        }
+    } else {
+        table + 1
+    };
+    let list_end = (list_start..nodes.len())
+        .find(|&i| is_word(nodes.get(i), "RESTRICT") || is_word(nodes.get(i), "CASCADE"))
+        .unwrap_or(nodes.len() - trailing_trivia(&nodes));
+
+    let mut names = Vec::new();
+    for name in split_args(&nodes[list_start..list_end]) {
+        let Some(name) = TableName::parse(name) else {
+            return nodes;
+        };
+        names.push(format!("pg_temp.{}", name.name));
+    }
+    let mut sql = "DROP TABLE ".to_string();
+    if if_exists {
+        sql.push_str("IF EXISTS ");
+    }
+    sql.push_str(&names.join(", "));
+    let mut out = parse_fragment(&sql);
+    if list_end < nodes.len() && !nodes[list_end].is_trivia() {
+        out.push(Node::Token(Token::Whitespace(" ".to_string())));
+    }
+    out.extend(nodes[list_end..].iter().cloned());
+    out
+}
+
+/// A table name as written, possibly qualified with its schema.
+pub(super) struct TableName {
+    pub schema: Option<Token>,
+    pub name: Token,
+}
+
+impl TableName {
+    pub fn parse(nodes: &[Node]) -> Option<TableName> {
+        let significant: Vec<&Node> = nodes.iter().filter(|n| !n.is_trivia()).collect();
+        let identifier = |node: &Node| match node {
+            Node::Token(t) if literals::identifier_name(t).is_some() => Some(t.clone()),
+            _ => None,
+        };
+        match significant.as_slice() {
+            [name] => Some(TableName {
+                schema: None,
+                name: identifier(name)?,
+            }),
+            [schema, Node::Token(dot), name] if dot.is_operator(".") => Some(TableName {
+                schema: Some(identifier(schema)?),
+                name: identifier(name)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn render(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{}.{}", schema, self.name),
+            None => self.name.to_string(),
+        }
+    }
+}
+
+fn trailing_trivia(nodes: &[Node]) -> usize {
+    nodes.iter().rev().take_while(|n| n.is_trivia()).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Translator;
+
+    fn translate(sql: &str) -> String {
+        Translator::new().translate(sql).unwrap()
+    }
+
+    #[test]
+    fn rewrites_table_statements() {
+        for (mysql, postgres) in [
+            ("TRUNCATE TABLE t", "TRUNCATE TABLE t RESTART IDENTITY"),
+            (
+                "RENAME TABLE a TO b, c TO d",
+                "DO $rename$ BEGIN ALTER TABLE a RENAME TO b; ALTER TABLE c RENAME TO d; END $rename$",
+            ),
+            ("CREATE TABLE b LIKE a", "CREATE TABLE b (LIKE a INCLUDING ALL)"),
+            ("CREATE TABLE t2 SELECT * FROM t", "CREATE TABLE t2 AS SELECT * FROM t"),
+            ("DROP TEMPORARY TABLE t", "DROP TABLE pg_temp.t"),
+        ] {
+            assert_eq!(translate(mysql), postgres);
+        }
+    }
+
+    #[test]
+    fn drops_table_options() {
+        assert_eq!(
+            translate("CREATE TABLE t (a INT) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"),
+            "CREATE TABLE t (a INT)"
+        );
+    }
+
+    #[test]
+    fn truncates_renames_and_copies_tables() {
+        for (mysql, postgres) in [
+            ("TRUNCATE t", "TRUNCATE t RESTART IDENTITY"),
+            ("TRUNCATE TABLE s.t;", "TRUNCATE TABLE s.t RESTART IDENTITY;"),
+            (
+                "TRUNCATE TABLE t CONTINUE IDENTITY",
+                "TRUNCATE TABLE t CONTINUE IDENTITY",
+            ),
+            (
+                "RENAME TABLE s1.a TO s2.b",
+                "DO $rename$ BEGIN ALTER TABLE s1.a RENAME TO b; ALTER TABLE s1.b SET SCHEMA s2; END $rename$",
+            ),
+            (
+                "RENAME TABLE `a` TO `s2`.`b`",
+                r#"DO $rename$ BEGIN ALTER TABLE "a" RENAME TO "b"; ALTER TABLE "b" SET SCHEMA "s2"; END $rename$"#,
+            ),
+            // A swap through a third name renames in order.
+            (
+                "RENAME TABLE a TO tmp, b TO a, tmp TO b",
+                "DO $rename$ BEGIN ALTER TABLE a RENAME TO tmp; ALTER TABLE b RENAME TO a; ALTER TABLE tmp RENAME TO b; END $rename$",
+            ),
+            (
+                "CREATE TABLE IF NOT EXISTS b LIKE s.a",
+                "CREATE TABLE IF NOT EXISTS b (LIKE s.a INCLUDING ALL)",
+            ),
+            ("CREATE TABLE b (LIKE a)", "CREATE TABLE b (LIKE a INCLUDING ALL)"),
+        ] {
+            assert_eq!(translate(mysql), postgres);
+        }
+    }
+
+    #[test]
+    fn creates_and_drops_temporary_tables() {
+        for (mysql, postgres) in [
+            (
+                "CREATE TEMPORARY TABLE IF NOT EXISTS tt (a INT) ENGINE=MEMORY COMMENT='x, y'",
+                "CREATE TEMPORARY TABLE IF NOT EXISTS tt (a INT)",
+            ),
+            (
+                "CREATE TEMPORARY TABLE tt SELECT a FROM t",
+                "CREATE TEMPORARY TABLE tt AS SELECT a FROM t",
+            ),
+            (
+                "CREATE TEMPORARY TABLE tt (SELECT a FROM t)",
+                "CREATE TEMPORARY TABLE tt AS (SELECT a FROM t)",
+            ),
+            (
+                "CREATE TABLE t2 WITH x AS (SELECT 1) SELECT * FROM x",
+                "CREATE TABLE t2 AS WITH x AS (SELECT 1) SELECT * FROM x",
+            ),
+            (
+                "CREATE TABLE t (a INT) ENGINE = InnoDB, ROW_FORMAT = DYNAMIC, COMMENT = 'a b'",
+                "CREATE TABLE t (a INT)",
+            ),
+            (
+                "DROP TEMPORARY TABLE IF EXISTS a, b",
+                "DROP TABLE IF EXISTS pg_temp.a, pg_temp.b",
+            ),
+            ("DROP TEMPORARY TABLE s.a", "DROP TABLE pg_temp.a"),
+        ] {
+            assert_eq!(translate(mysql), postgres);
+        }
+    }
+}
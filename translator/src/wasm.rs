@@ -0,0 +1,23 @@
+// translate() for JavaScript, with the wasm feature, so a browser playground or an editor
+// extension translates statements with the code the proxy runs:
+//
+//   wasm-pack build translator --target web -- --features wasm
+//
+//   import init, { translate } from "./pkg/postmyrustache_translator.js";
+//   await init();
+//   translate("SELECT * FROM `orders` LIMIT 10, 5", "STRICT_TRANS_TABLES", false);
+//
+// A statement that can't be translated throws an Error with the translator's message.
+
+use wasm_bindgen::prelude::*;
+
+use super::{translate_mysql_to_postgres, TranslationOptions};
+
+/// Translates `sql` as the proxy would with SQL_MODE `sql_mode`, and with STRICT_TRANSLATION
+/// if `strict`.
+#[wasm_bindgen]
+pub fn translate(sql: &str, sql_mode: &str, strict: bool) -> Result<String, JsError> {
+    let mut options = TranslationOptions::default().sql_mode(sql_mode);
+    options.strict = strict;
+    translate_mysql_to_postgres(sql, &options).map_err(|e| JsError::new(&e.to_string()))
+}